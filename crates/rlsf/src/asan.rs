@@ -0,0 +1,43 @@
+//! AddressSanitizer integration (the `asan` feature)
+//!
+//! The program must be built with AddressSanitizer (e.g., `RUSTFLAGS=
+//! -Zsanitizer=address`), which provides these functions.
+use core::ffi::c_void;
+
+extern "C" {
+    fn __asan_poison_memory_region(addr: *const c_void, size: usize);
+    fn __asan_unpoison_memory_region(addr: *const c_void, size: usize);
+}
+
+/// Make `start..end` inaccessible, so that AddressSanitizer reports any
+/// access to it. Does nothing if the range is empty.
+///
+/// # Safety
+///
+/// `start..end` must be owned by the caller.
+#[inline]
+pub(crate) unsafe fn poison(start: *const u8, end: *const u8) {
+    if start < end {
+        __asan_poison_memory_region(start.cast(), end as usize - start as usize);
+    }
+}
+
+/// Make `start..end` accessible again. Does nothing if the range is empty.
+///
+/// # Safety
+///
+/// `start..end` must be owned by the caller.
+#[inline]
+pub(crate) unsafe fn unpoison(start: *const u8, end: *const u8) {
+    if start < end {
+        __asan_unpoison_memory_region(start.cast(), end as usize - start as usize);
+    }
+}
+
+/// Get whether AddressSanitizer reports accesses to `ptr`.
+pub(crate) fn is_poisoned(ptr: *const u8) -> bool {
+    extern "C" {
+        fn __asan_address_is_poisoned(addr: *const c_void) -> i32;
+    }
+    unsafe { __asan_address_is_poisoned(ptr.cast()) != 0 }
+}
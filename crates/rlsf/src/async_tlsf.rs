@@ -0,0 +1,332 @@
+//! `AsyncTlsf`: [`SyncTlsf`] whose allocations can wait for memory
+use core::{
+    alloc,
+    cell::UnsafeCell,
+    fmt,
+    future::Future,
+    pin::Pin,
+    ptr::NonNull,
+    task::{Context, Poll, Waker},
+};
+
+use crate::{int::BinInteger, DefaultLock, StaticGlobalTlsfLock, SyncTlsf, SyncTlsfGuard};
+
+/// [`SyncTlsf`] with an asynchronous allocation method,
+/// [`Self::alloc_async`], that waits until enough memory is freed instead of
+/// failing.
+///
+/// This lets tasks running on an embedded async executor (e.g., Embassy)
+/// apply backpressure when the heap is exhausted. A pending allocation
+/// registers its task's [`Waker`], and every deallocation through `self`
+/// wakes all registered tasks so that they can retry. Up to `MAX_WAITERS`
+/// tasks can be registered at a time; excess tasks are woken immediately
+/// and thus keep polling until a registration slot becomes available.
+///
+/// An allocation that can never succeed (e.g., one larger than all memory
+/// pools) waits forever.
+///
+/// Memory pools are supplied through [`Self::lock`].
+///
+/// # Examples
+///
+/// ```rust
+/// use rlsf::AsyncTlsf;
+/// use std::{alloc::Layout, mem::MaybeUninit};
+///
+/// static TLSF: AsyncTlsf<'static, 4, u16, u16, 12, 16> = AsyncTlsf::new();
+///
+/// async fn task() {
+///     let layout = Layout::new::<[u8; 256]>();
+///     let ptr = TLSF.alloc_async(layout).await;
+///     // ...
+///     unsafe { TLSF.deallocate(ptr, layout.align()) };
+/// }
+///
+/// static mut POOL: [MaybeUninit<u8>; 8192] = [MaybeUninit::uninit(); 8192];
+/// TLSF.lock().insert_free_block(unsafe { &mut POOL });
+/// ```
+pub struct AsyncTlsf<
+    'pool,
+    const MAX_WAITERS: usize,
+    FLBitmap,
+    SLBitmap,
+    const FLLEN: usize,
+    const SLLEN: usize,
+    Lock = DefaultLock,
+> {
+    inner: SyncTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>,
+    /// The wakers of the pending allocations. Protected by `inner`'s lock.
+    waiters: UnsafeCell<[Option<Waker>; MAX_WAITERS]>,
+}
+
+// Safety: `Waker` is `Send + Sync`, and `waiters` is protected by `inner`'s
+//         lock
+unsafe impl<
+        const MAX_WAITERS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock: StaticGlobalTlsfLock,
+    > Sync for AsyncTlsf<'_, MAX_WAITERS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+{
+}
+
+impl<
+        'pool,
+        const MAX_WAITERS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > AsyncTlsf<'pool, MAX_WAITERS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    const NO_WAKER: Option<Waker> = None;
+
+    const_fn! {
+        /// Construct an empty instance of `Self`.
+        #[inline]
+        pub const fn new() -> Self {
+            Self {
+                inner: SyncTlsf::new(),
+                waiters: UnsafeCell::new([Self::NO_WAKER; MAX_WAITERS]),
+            }
+        }
+    }
+
+    /// Acquire the lock of the underlying [`Tlsf`](crate::Tlsf), e.g., to
+    /// supply memory pools. See [`SyncTlsf::lock`].
+    ///
+    /// Deallocations made through the returned guard don't wake the pending
+    /// allocations; use [`Self::deallocate`] instead.
+    #[inline]
+    pub fn lock(&self) -> SyncTlsfGuard<'_, 'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock> {
+        self.inner.lock()
+    }
+
+    /// Get the wakers of the pending allocations.
+    ///
+    /// # Safety
+    ///
+    /// `tlsf` must be a guard of `self.inner`, and the returned reference
+    /// must not outlive it.
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn waiters(
+        &self,
+        tlsf: &SyncTlsfGuard<'_, 'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>,
+    ) -> &mut [Option<Waker>; MAX_WAITERS] {
+        let _ = tlsf;
+        &mut *self.waiters.get()
+    }
+
+    /// Attempt to allocate memory without waiting.
+    #[inline]
+    pub fn allocate(&self, layout: alloc::Layout) -> Option<NonNull<u8>> {
+        self.inner.lock().allocate(layout)
+    }
+
+    /// Allocate memory, waiting until enough memory is freed if the
+    /// underlying [`Tlsf`](crate::Tlsf) is exhausted.
+    ///
+    /// Dropping the returned future cancels the allocation.
+    #[inline]
+    pub fn alloc_async(
+        &self,
+        layout: alloc::Layout,
+    ) -> AllocFuture<'_, 'pool, MAX_WAITERS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock> {
+        AllocFuture {
+            owner: self,
+            layout,
+            slot: None,
+        }
+    }
+
+    /// Deallocate a previously allocated memory block and wake the pending
+    /// allocations.
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `self`.
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `align`.
+    ///
+    /// [`Layout::align`]: core::alloc::Layout::align
+    pub unsafe fn deallocate(&self, ptr: NonNull<u8>, align: usize) {
+        let mut wakers = [Self::NO_WAKER; MAX_WAITERS];
+        {
+            let mut tlsf = self.inner.lock();
+            // Safety: Upheld by the caller
+            tlsf.deallocate(ptr, align);
+            // Safety: `tlsf` is a guard of `self.inner`
+            let waiters = self.waiters(&tlsf);
+            for (waker, waiter) in wakers.iter_mut().zip(waiters.iter()) {
+                waker.clone_from(waiter);
+            }
+        }
+        // Wake the tasks after releasing the lock, in case waking them
+        // re-enters `self`
+        wakers.into_iter().flatten().for_each(Waker::wake);
+    }
+}
+
+impl<
+        'pool,
+        const MAX_WAITERS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > Default for AsyncTlsf<'pool, MAX_WAITERS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<
+        const MAX_WAITERS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > fmt::Debug for AsyncTlsf<'_, MAX_WAITERS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncTlsf").finish_non_exhaustive()
+    }
+}
+
+/// A pending allocation of [`AsyncTlsf`], returned by
+/// [`AsyncTlsf::alloc_async`].
+#[must_use = "futures do nothing unless polled"]
+pub struct AllocFuture<
+    'a,
+    'pool,
+    const MAX_WAITERS: usize,
+    FLBitmap,
+    SLBitmap,
+    const FLLEN: usize,
+    const SLLEN: usize,
+    Lock,
+> where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    owner: &'a AsyncTlsf<'pool, MAX_WAITERS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>,
+    layout: alloc::Layout,
+    /// The index of the registration slot in `owner.waiters`
+    slot: Option<usize>,
+}
+
+impl<
+        const MAX_WAITERS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > Future for AllocFuture<'_, '_, MAX_WAITERS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    type Output = NonNull<u8>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut tlsf = this.owner.inner.lock();
+        let ptr = tlsf.allocate(this.layout);
+
+        // Register the waker while holding the lock so that a deallocation
+        // can't slip in between the failed allocation and the registration
+        // Safety: `tlsf` is a guard of `owner.inner`
+        let waiters = unsafe { this.owner.waiters(&tlsf) };
+        if let Some(ptr) = ptr {
+            let waker = this.slot.take().and_then(|slot| waiters[slot].take());
+            drop(tlsf);
+            drop(waker);
+            return Poll::Ready(ptr);
+        }
+
+        let slot = this
+            .slot
+            .or_else(|| waiters.iter().position(Option::is_none));
+        match slot {
+            Some(slot) => {
+                let waiter = &mut waiters[slot];
+                if !waiter.as_ref().map_or(false, |w| w.will_wake(cx.waker())) {
+                    *waiter = Some(cx.waker().clone());
+                }
+                this.slot = Some(slot);
+            }
+            None => {
+                // No registration slot is available; poll again soon
+                drop(tlsf);
+                cx.waker().wake_by_ref();
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<
+        const MAX_WAITERS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > Drop for AllocFuture<'_, '_, MAX_WAITERS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            let tlsf = self.owner.inner.lock();
+            // Safety: `tlsf` is a guard of `owner.inner`
+            let waker = unsafe { self.owner.waiters(&tlsf) }[slot].take();
+            drop(tlsf);
+            drop(waker);
+        }
+    }
+}
+
+impl<
+        const MAX_WAITERS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > fmt::Debug for AllocFuture<'_, '_, MAX_WAITERS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AllocFuture")
+            .field("layout", &self.layout)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,124 @@
+use core::{
+    mem::MaybeUninit,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use std::{prelude::v1::*, sync::Arc, task::Wake, vec};
+
+use super::*;
+
+type TheAsyncTlsf = AsyncTlsf<'static, 2, u16, u16, 12, 16>;
+
+fn new_pool(size: usize) -> &'static mut [MaybeUninit<u8>] {
+    Box::leak(vec![MaybeUninit::uninit(); size].into_boxed_slice())
+}
+
+/// A waker that counts how many times it was woken
+#[derive(Default)]
+struct CountingWaker(AtomicUsize);
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn poll<F: Future + Unpin>(future: &mut F, waker: &Arc<CountingWaker>) -> Poll<F::Output> {
+    let waker = Waker::from(Arc::clone(waker));
+    Pin::new(future).poll(&mut Context::from_waker(&waker))
+}
+
+/// Allocate memory blocks with `layout` until the pool is exhausted
+fn exhaust(tlsf: &TheAsyncTlsf, layout: alloc::Layout) -> Vec<NonNull<u8>> {
+    std::iter::from_fn(|| tlsf.allocate(layout)).collect()
+}
+
+#[test]
+fn ready() {
+    let tlsf = TheAsyncTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(4096));
+    let waker = Arc::new(CountingWaker::default());
+
+    let layout = alloc::Layout::new::<u64>();
+    let mut future = tlsf.alloc_async(layout);
+    let ptr = match poll(&mut future, &waker) {
+        Poll::Ready(ptr) => ptr,
+        Poll::Pending => panic!("allocation is pending"),
+    };
+    drop(future);
+    unsafe { tlsf.deallocate(ptr, layout.align()) };
+    assert_eq!(waker.0.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn wait_for_deallocation() {
+    let tlsf = TheAsyncTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(4096));
+    let waker = Arc::new(CountingWaker::default());
+
+    let layout = alloc::Layout::from_size_align(256, 8).unwrap();
+    let mut ptrs = exhaust(&tlsf, layout);
+    assert!(!ptrs.is_empty());
+
+    let mut future = tlsf.alloc_async(layout);
+    assert!(poll(&mut future, &waker).is_pending());
+    // Polling again doesn't take another slot
+    assert!(poll(&mut future, &waker).is_pending());
+    assert_eq!(waker.0.load(Ordering::Relaxed), 0);
+
+    unsafe { tlsf.deallocate(ptrs.pop().unwrap(), layout.align()) };
+    assert_eq!(waker.0.load(Ordering::Relaxed), 1);
+
+    let ptr = match poll(&mut future, &waker) {
+        Poll::Ready(ptr) => ptr,
+        Poll::Pending => panic!("allocation is still pending"),
+    };
+    drop(future);
+
+    // The slot was released, so this deallocation wakes nobody
+    unsafe { tlsf.deallocate(ptr, layout.align()) };
+    assert_eq!(waker.0.load(Ordering::Relaxed), 1);
+
+    for ptr in ptrs {
+        unsafe { tlsf.deallocate(ptr, layout.align()) };
+    }
+}
+
+#[test]
+fn excess_waiters() {
+    let tlsf = TheAsyncTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(4096));
+    let wakers: Vec<_> = (0..3).map(|_| Arc::new(CountingWaker::default())).collect();
+
+    let layout = alloc::Layout::from_size_align(256, 8).unwrap();
+    let mut ptrs = exhaust(&tlsf, layout);
+
+    let mut futures: Vec<_> = (0..3).map(|_| tlsf.alloc_async(layout)).collect();
+    for (future, waker) in futures.iter_mut().zip(wakers.iter()) {
+        assert!(poll(future, waker).is_pending());
+    }
+    // The third future couldn't register, so it was woken immediately
+    let counts = || -> Vec<usize> {
+        wakers
+            .iter()
+            .map(|waker| waker.0.load(Ordering::Relaxed))
+            .collect()
+    };
+    assert_eq!(counts(), [0, 0, 1]);
+
+    // Cancelling a pending allocation releases its slot
+    drop(futures.remove(0));
+    assert!(poll(&mut futures[1], &wakers[2]).is_pending());
+    assert_eq!(counts(), [0, 0, 1]);
+
+    unsafe { tlsf.deallocate(ptrs.pop().unwrap(), layout.align()) };
+    assert_eq!(counts(), [0, 1, 2]);
+
+    drop(futures);
+    for ptr in ptrs {
+        unsafe { tlsf.deallocate(ptr, layout.align()) };
+    }
+}
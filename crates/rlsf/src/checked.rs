@@ -0,0 +1,346 @@
+//! `CheckedTlsf`: a [`Tlsf`] validating the pointers passed to it
+use core::{alloc::Layout, fmt, mem::MaybeUninit, num::NonZeroUsize, ptr::NonNull};
+
+use crate::{
+    corruption::{report_corruption, HeapError},
+    int::BinInteger,
+    utils::{nonnull_slice_from_raw_parts, nonnull_slice_start},
+    IntegrityError, InvalidPointer, Tlsf,
+};
+
+/// [`Tlsf`] that remembers its memory pools and validates every pointer
+/// passed to [`Self::deallocate`] and [`Self::reallocate`] before touching
+/// the memory block's header, for tracking down wild frees.
+///
+/// A pointer is accepted only if it lies in one of the memory pools and is
+/// the starting address of a memory block allocated with the given
+/// alignment. Otherwise, these methods panic with the pointer and the memory
+/// block containing it instead of corrupting the heap.
+///
+/// Up to `NUM_POOLS` memory pools can be inserted. Since the pools are known,
+/// [`Self::check_integrity`] is safe to call.
+///
+/// This is a debugging aid; the validation walks the memory pool containing
+/// the pointer, so deallocation takes time proportional to the number of
+/// memory blocks.
+///
+/// In addition, `CheckedTlsf` can run [`Self::check_integrity`] every `N`
+/// operations (see [`Self::set_self_check_interval`]) and panic if it
+/// reports an inconsistency, which catches corruption that isn't caused by
+/// invalid pointers (e.g., buffer overflows into block headers) at a latency
+/// and overhead of the user's choice. By default, this happens on every
+/// operation in debug builds and never in release builds.
+///
+/// # Examples
+///
+/// ```rust,should_panic
+/// use rlsf::CheckedTlsf;
+/// use std::{alloc::Layout, mem::MaybeUninit, ptr::NonNull};
+///
+/// let mut tlsf: CheckedTlsf<'_, u16, u16, 12, 16, 1> = CheckedTlsf::new();
+/// let mut pool = [MaybeUninit::uninit(); 4096];
+/// tlsf.insert_free_block(&mut pool);
+///
+/// let ptr = tlsf.allocate(Layout::new::<[u8; 16]>()).unwrap();
+/// unsafe {
+///     // panics with "invalid pointer passed to deallocate: 0x... is not the
+///     // starting address of the allocation in the memory block at 0x..."
+///     tlsf.deallocate(NonNull::new(ptr.as_ptr().add(4)).unwrap(), 1);
+/// }
+/// ```
+pub struct CheckedTlsf<
+    'pool,
+    FLBitmap,
+    SLBitmap,
+    const FLLEN: usize,
+    const SLLEN: usize,
+    const NUM_POOLS: usize,
+> {
+    tlsf: Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>,
+    /// The memory pools inserted so far (`pools[..num_pools]`), exactly as
+    /// [`Tlsf::check_integrity`] expects them.
+    pools: [NonNull<[u8]>; NUM_POOLS],
+    num_pools: usize,
+    self_check_interval: Option<NonZeroUsize>,
+    /// The number of operations until the next self check
+    ops_until_self_check: usize,
+}
+
+// Safety: All memory pools referenced by a particular instance of
+//         `CheckedTlsf` are logically owned by its `Tlsf`
+unsafe impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, const NUM_POOLS: usize> Send
+    for CheckedTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, NUM_POOLS>
+{
+}
+
+// Safety: `pools` is only read through `&self`
+unsafe impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, const NUM_POOLS: usize> Sync
+    for CheckedTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, NUM_POOLS>
+{
+}
+
+impl<
+        FLBitmap: BinInteger,
+        SLBitmap: BinInteger,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        const NUM_POOLS: usize,
+    > Default for CheckedTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, NUM_POOLS>
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<
+        'pool,
+        FLBitmap: BinInteger,
+        SLBitmap: BinInteger,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        const NUM_POOLS: usize,
+    > CheckedTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, NUM_POOLS>
+{
+    /// The default value of [`Self::self_check_interval`]: every operation
+    /// (`Some(1)`) in debug builds and never (`None`) in release builds.
+    pub const DEFAULT_SELF_CHECK_INTERVAL: Option<NonZeroUsize> = if cfg!(debug_assertions) {
+        NonZeroUsize::new(1)
+    } else {
+        None
+    };
+
+    /// Construct an empty instance of `Self`.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            tlsf: Tlsf::new(),
+            pools: [NonNull::<[u8; 0]>::dangling() as NonNull<[u8]>; NUM_POOLS],
+            num_pools: 0,
+            self_check_interval: Self::DEFAULT_SELF_CHECK_INTERVAL,
+            ops_until_self_check: match Self::DEFAULT_SELF_CHECK_INTERVAL {
+                Some(interval) => interval.get(),
+                None => 0,
+            },
+        }
+    }
+
+    /// Get the underlying [`Tlsf`].
+    ///
+    /// It's not mutable so that the memory pools can't be inserted without
+    /// being registered.
+    #[inline]
+    pub fn tlsf(&self) -> &Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN> {
+        &self.tlsf
+    }
+
+    /// Create a new memory pool at the location specified by a slice and
+    /// register it.
+    ///
+    /// See [`Tlsf::insert_free_block`]. The memory block is ignored if
+    /// `NUM_POOLS` memory pools have already been inserted.
+    #[inline]
+    pub fn insert_free_block(&mut self, block: &'pool mut [MaybeUninit<u8>]) -> impl Send + Sync {
+        // Safety: `block` is a mutable reference, which guarantees the absence
+        // of aliasing references. Being `'pool` means it will outlive `self`.
+        unsafe { self.insert_free_block_ptr(NonNull::new(block as *mut [_] as _).unwrap()) };
+    }
+
+    /// Create a new memory pool at the location specified by a slice pointer
+    /// and register it.
+    ///
+    /// Returns the number of bytes actually incorporated into the memory
+    /// pool or `None` if the memory block is too small or `NUM_POOLS` memory
+    /// pools have already been inserted. See [`Tlsf::insert_free_block_ptr`].
+    ///
+    /// # Safety
+    ///
+    /// See [`Tlsf::insert_free_block_ptr`].
+    pub unsafe fn insert_free_block_ptr(&mut self, block: NonNull<[u8]>) -> Option<NonZeroUsize> {
+        if self.num_pools == NUM_POOLS {
+            return None;
+        }
+        // Safety: Upheld by the caller
+        let len = self.tlsf.insert_free_block_ptr(block)?;
+        self.pools[self.num_pools] =
+            nonnull_slice_from_raw_parts(nonnull_slice_start(block), len.get());
+        self.num_pools += 1;
+        Some(len)
+    }
+
+    /// Get the registered memory pools.
+    #[inline]
+    pub fn pools(&self) -> &[NonNull<[u8]>] {
+        &self.pools[..self.num_pools]
+    }
+
+    /// Get the number of operations between two self checks, or `None` if
+    /// self checks are disabled.
+    #[inline]
+    pub fn self_check_interval(&self) -> Option<NonZeroUsize> {
+        self.self_check_interval
+    }
+
+    /// Run [`Self::check_integrity`] at the start of every `interval`-th call
+    /// to [`Self::allocate`], [`Self::deallocate`], and [`Self::reallocate`]
+    /// and panic if it reports an inconsistency, or disable it by passing
+    /// `None`.
+    ///
+    /// A self check takes time proportional to the number of memory blocks,
+    /// so a smaller interval detects corruption sooner (closer to its cause)
+    /// at a higher overhead. The default is
+    /// [`Self::DEFAULT_SELF_CHECK_INTERVAL`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::CheckedTlsf;
+    /// use std::{alloc::Layout, mem::MaybeUninit, num::NonZeroUsize};
+    ///
+    /// let mut tlsf: CheckedTlsf<'_, u16, u16, 12, 16, 1> = CheckedTlsf::new();
+    /// let mut pool = [MaybeUninit::uninit(); 4096];
+    /// tlsf.insert_free_block(&mut pool);
+    ///
+    /// // Check the heap on every 100th operation, even in release builds
+    /// tlsf.set_self_check_interval(NonZeroUsize::new(100));
+    ///
+    /// let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+    /// unsafe { tlsf.deallocate(ptr, 8) };
+    /// ```
+    pub fn set_self_check_interval(&mut self, interval: Option<NonZeroUsize>) {
+        self.self_check_interval = interval;
+        self.ops_until_self_check = interval.map_or(0, NonZeroUsize::get);
+    }
+
+    /// Count an operation and run a self check if it's due.
+    #[inline]
+    fn count_operation(&mut self) {
+        if let Some(interval) = self.self_check_interval {
+            self.ops_until_self_check -= 1;
+            if self.ops_until_self_check == 0 {
+                self.ops_until_self_check = interval.get();
+                if let Err(error) = self.check_integrity() {
+                    integrity_violation(error);
+                }
+            }
+        }
+    }
+
+    /// Attempt to allocate a block of memory.
+    ///
+    /// See [`Tlsf::allocate`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if a self check is due and fails (see
+    /// [`Self::set_self_check_interval`]).
+    #[inline]
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        self.count_operation();
+        self.tlsf.allocate(layout)
+    }
+
+    /// Validate the memory blocks in the registered memory pools and the
+    /// free lists. Returns the first inconsistency found.
+    ///
+    /// See [`Tlsf::check_integrity`].
+    pub fn check_integrity(&self) -> Result<(), IntegrityError> {
+        // Safety: `pools` is exactly what `check_integrity` expects
+        unsafe { self.tlsf.check_integrity(self.pools()) }
+    }
+
+    /// Panic if `ptr` isn't the starting address of a memory block allocated
+    /// with alignment `align`.
+    fn check_pointer(&self, ptr: NonNull<u8>, align: usize, method: &'static str) {
+        // Safety: `pools` is exactly what `check_pointer` expects
+        if let Err(e) = unsafe { self.tlsf.check_pointer(self.pools(), ptr, align) } {
+            invalid_pointer(method, ptr, e);
+        }
+    }
+
+    /// Deallocate a previously allocated memory block after checking that
+    /// `ptr` denotes one.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(num_blocks)`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ptr` isn't the starting address of an allocated memory
+    /// block in the memory pools of `self`, or if a self check is due and
+    /// fails.
+    ///
+    /// # Safety
+    ///
+    /// See [`Tlsf::deallocate`]. The checks catch many but not all
+    /// violations; e.g., a memory block deallocated and then allocated again
+    /// looks valid.
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, align: usize) {
+        self.count_operation();
+        self.check_pointer(ptr, align, "deallocate");
+        // Safety: Upheld by the caller
+        self.tlsf.deallocate(ptr, align);
+    }
+
+    /// Shrink or grow a previously allocated memory block after checking
+    /// that `ptr` denotes one.
+    ///
+    /// See [`Tlsf::reallocate`].
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(num_blocks + old_size)`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ptr` isn't the starting address of an allocated memory
+    /// block in the memory pools of `self`, or if a self check is due and
+    /// fails.
+    ///
+    /// # Safety
+    ///
+    /// See [`Tlsf::reallocate`].
+    pub unsafe fn reallocate(
+        &mut self,
+        ptr: NonNull<u8>,
+        new_layout: Layout,
+    ) -> Option<NonNull<u8>> {
+        self.count_operation();
+        self.check_pointer(ptr, new_layout.align(), "reallocate");
+        // Safety: Upheld by the caller
+        self.tlsf.reallocate(ptr, new_layout)
+    }
+}
+
+impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, const NUM_POOLS: usize> fmt::Debug
+    for CheckedTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, NUM_POOLS>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CheckedTlsf")
+            .field("pools", &&self.pools[..self.num_pools])
+            .field("self_check_interval", &self.self_check_interval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Report a pointer rejected by [`Tlsf::check_pointer`].
+#[cold]
+#[inline(never)]
+fn invalid_pointer(method: &'static str, ptr: NonNull<u8>, reason: InvalidPointer) -> ! {
+    report_corruption(HeapError::InvalidPointer {
+        method,
+        ptr,
+        reason,
+    })
+}
+
+/// Report an inconsistency found by a self check.
+#[cold]
+#[inline(never)]
+fn integrity_violation(error: IntegrityError) -> ! {
+    report_corruption(HeapError::IntegrityViolation { error })
+}
+
+#[cfg(test)]
+mod tests;
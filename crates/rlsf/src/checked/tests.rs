@@ -0,0 +1,147 @@
+use core::mem::MaybeUninit;
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    prelude::v1::*,
+    vec,
+};
+
+use super::*;
+use crate::GRANULARITY;
+
+type TheCheckedTlsf = CheckedTlsf<'static, u16, u16, 12, 16, 2>;
+
+fn new_pool(size: usize) -> &'static mut [MaybeUninit<u8>] {
+    Box::leak(vec![MaybeUninit::uninit(); size].into_boxed_slice())
+}
+
+fn new_tlsf() -> TheCheckedTlsf {
+    let mut tlsf = TheCheckedTlsf::new();
+    tlsf.insert_free_block(new_pool(65536));
+    tlsf.insert_free_block(new_pool(65536));
+    tlsf
+}
+
+/// Get the panic message of `f`, or `None` if it doesn't panic.
+fn panic_message(f: impl FnOnce()) -> Option<String> {
+    let payload = catch_unwind(AssertUnwindSafe(f)).err()?;
+    Some(
+        payload
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_default(),
+    )
+}
+
+#[test]
+fn valid() {
+    let mut tlsf = new_tlsf();
+    let mut ptrs = Vec::new();
+    for &(size, align) in &[(0, 1), (1, 1), (10, 8), (100, 16), (100, 64), (4000, 256)] {
+        let layout = Layout::from_size_align(size, align).unwrap();
+        ptrs.push((tlsf.allocate(layout).unwrap(), layout));
+    }
+    assert_eq!(tlsf.check_integrity(), Ok(()));
+
+    unsafe {
+        for (ptr, layout) in ptrs.iter_mut().step_by(2) {
+            *layout = Layout::from_size_align(layout.size() * 3 + 1, layout.align()).unwrap();
+            *ptr = tlsf.reallocate(*ptr, *layout).unwrap();
+        }
+        assert_eq!(tlsf.check_integrity(), Ok(()));
+        for (ptr, layout) in ptrs {
+            tlsf.deallocate(ptr, layout.align());
+        }
+    }
+    assert_eq!(tlsf.check_integrity(), Ok(()));
+}
+
+#[test]
+fn too_many_pools() {
+    let mut tlsf = new_tlsf();
+    let pool = new_pool(65536);
+    assert_eq!(
+        unsafe { tlsf.insert_free_block_ptr(NonNull::new(pool as *mut [_] as _).unwrap()) },
+        None
+    );
+    assert_eq!(tlsf.pools().len(), 2);
+    assert_eq!(tlsf.check_integrity(), Ok(()));
+}
+
+#[test]
+fn outside_pools() {
+    let mut tlsf = new_tlsf();
+    let mut local = 0u64;
+    let ptr = NonNull::from(&mut local).cast::<u8>();
+    let message = panic_message(|| unsafe { tlsf.deallocate(ptr, 8) }).unwrap();
+    assert!(
+        message.contains("is not in any memory pool"),
+        "{:?}",
+        message
+    );
+    assert_eq!(tlsf.check_integrity(), Ok(()));
+}
+
+#[test]
+fn inside_block() {
+    let mut tlsf = new_tlsf();
+    for &align in &[1, 8, GRANULARITY, 64] {
+        let layout = Layout::from_size_align(64, align).unwrap();
+        let ptr = tlsf.allocate(layout).unwrap();
+        let interior = NonNull::new(unsafe { ptr.as_ptr().add(8) }).unwrap();
+        let message = panic_message(|| unsafe { tlsf.deallocate(interior, align) }).unwrap();
+        assert!(
+            message.contains("is not the starting address of the allocation"),
+            "{:?}",
+            message
+        );
+        let message = panic_message(|| unsafe {
+            tlsf.reallocate(interior, layout);
+        })
+        .unwrap();
+        assert!(message.starts_with("invalid pointer passed to reallocate"));
+        assert_eq!(tlsf.check_integrity(), Ok(()));
+
+        unsafe { tlsf.deallocate(ptr, align) };
+    }
+}
+
+#[test]
+fn free_block() {
+    let mut tlsf = new_tlsf();
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    let ptr = tlsf.allocate(layout).unwrap();
+    unsafe { tlsf.deallocate(ptr, 8) };
+    let message = panic_message(|| unsafe { tlsf.deallocate(ptr, 8) }).unwrap();
+    assert!(message.contains("(double free?)"), "{:?}", message);
+    assert_eq!(tlsf.check_integrity(), Ok(()));
+}
+
+#[test]
+fn self_check() {
+    let mut tlsf = new_tlsf();
+    assert_eq!(
+        tlsf.self_check_interval(),
+        TheCheckedTlsf::DEFAULT_SELF_CHECK_INTERVAL
+    );
+    tlsf.set_self_check_interval(NonZeroUsize::new(3));
+
+    let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+    unsafe {
+        // Overflow into the size field of the following block's header
+        let size = Tlsf::<'_, u16, u16, 12, 16>::size_of_allocation(ptr, 8);
+        (ptr.as_ptr().add(size) as *mut usize).write_unaligned(3);
+    }
+
+    // The corruption goes unnoticed until the third operation
+    let too_large = Layout::from_size_align(1 << 20, 1).unwrap();
+    assert_eq!(tlsf.allocate(too_large), None);
+    let message = panic_message(|| {
+        tlsf.allocate(too_large);
+    })
+    .unwrap();
+    assert!(
+        message.starts_with("heap corruption detected"),
+        "{:?}",
+        message
+    );
+}
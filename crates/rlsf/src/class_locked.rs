@@ -0,0 +1,329 @@
+//! `ClassLockedTlsf`: [`SyncTlsf`] with a lock per size class
+use core::{
+    alloc, fmt,
+    ptr::{self, NonNull},
+};
+
+use crate::{
+    int::BinInteger, utils::nonnull_slice_from_raw_parts, DefaultLock, StaticGlobalTlsfLock,
+    SyncTlsf, SyncTlsfGuard, GRANULARITY,
+};
+
+/// The number of the largest memory blocks of a size class that a chunk is
+/// sized to hold
+const CHUNK_BLOCKS: usize = 8;
+
+/// Get the first-level index of an allocation of `size` bytes, i.e.,
+/// `floor(log2(size / GRANULARITY))`.
+#[inline]
+fn first_level_index(size: usize) -> usize {
+    let size = size.max(GRANULARITY);
+    (usize::BITS - 1 - size.leading_zeros() - GRANULARITY.trailing_zeros()) as usize
+}
+
+/// [`SyncTlsf`] with a separate lock for each of the first `NUM_CLASSES`
+/// first-level indices (*size classes*), so that allocations of very
+/// different sizes proceed in parallel.
+///
+/// Each size class has its own [`Tlsf`] behind its own lock, which serves
+/// the allocations whose sizes have the size class's first-level index
+/// (i.e., `GRANULARITY << class <= size < GRANULARITY << (class + 1)`,
+/// counting sizes smaller than [`GRANULARITY`] as class 0). Larger
+/// allocations are served by the *main* `Tlsf`, to which memory pools are
+/// supplied through [`Self::lock`]. When a size class runs out of memory, it
+/// carves a *chunk* large enough for 8 of its largest memory blocks out of the
+/// main `Tlsf` (or a smaller one if that fails) and adds it to its own
+/// `Tlsf`.
+///
+/// # Lock Ordering and Coalescing
+///
+/// Coalescing a memory block with its neighbors requires exclusive access to
+/// their free lists, whose size classes are arbitrary, so a single `Tlsf`
+/// can't be protected by per-class locks without taking all of them.
+/// Instead, memory blocks coalesce only with memory blocks in the same chunk,
+/// which are protected by the same lock. The locks are taken in the following
+/// order, which rules out deadlocks:
+///
+///  - At most one size class's lock is held at a time.
+///  - The main `Tlsf`'s lock may be taken while holding a size class's lock
+///    (to carve a chunk), but not the other way around.
+///
+/// Chunks are never returned to the main `Tlsf`, so free space in one size
+/// class can't be reused by another. This suits workloads whose size
+/// distribution is stable.
+///
+/// [`Tlsf`]: crate::Tlsf
+///
+/// # Examples
+///
+/// ```rust
+/// use rlsf::ClassLockedTlsf;
+/// use std::{alloc::{GlobalAlloc, Layout}, mem::MaybeUninit};
+///
+/// static TLSF: ClassLockedTlsf<'static, 6, u16, u16, 12, 16> = ClassLockedTlsf::new();
+///
+/// static mut POOL: [MaybeUninit<u8>; 65536] = [MaybeUninit::uninit(); 65536];
+/// TLSF.lock().insert_free_block(unsafe { &mut POOL });
+///
+/// // These are served by different locks
+/// let small = Layout::new::<u64>();
+/// let large = Layout::new::<[u8; 1024]>();
+/// unsafe {
+///     let ptr1 = TLSF.alloc(small);
+///     let ptr2 = TLSF.alloc(large);
+///     TLSF.dealloc(ptr1, small);
+///     TLSF.dealloc(ptr2, large);
+/// }
+/// ```
+pub struct ClassLockedTlsf<
+    'pool,
+    const NUM_CLASSES: usize,
+    FLBitmap,
+    SLBitmap,
+    const FLLEN: usize,
+    const SLLEN: usize,
+    Lock = DefaultLock,
+> {
+    main: SyncTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>,
+    classes: [SyncTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>; NUM_CLASSES],
+}
+
+impl<
+        'pool,
+        const NUM_CLASSES: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > ClassLockedTlsf<'pool, NUM_CLASSES, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[cfg(not(loom))]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const CLASS: SyncTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock> = SyncTlsf::new();
+
+    const_fn! {
+        /// Construct an empty instance of `Self`.
+        #[inline]
+        pub const fn new() -> Self {
+            Self {
+                main: SyncTlsf::new(),
+                classes: array_repeat!(Self::CLASS, SyncTlsf::new(); NUM_CLASSES),
+            }
+        }
+    }
+
+    /// Acquire the lock of the main [`Tlsf`](crate::Tlsf), e.g., to supply
+    /// memory pools. See [`SyncTlsf::lock`].
+    ///
+    /// Memory blocks allocated through `self` must not be deallocated through
+    /// the returned guard.
+    #[inline]
+    pub fn lock(&self) -> SyncTlsfGuard<'_, 'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock> {
+        self.main.lock()
+    }
+
+    /// Get the size class serving `layout`, or `None` if it's served by the
+    /// main [`Tlsf`](crate::Tlsf).
+    #[inline]
+    fn size_class(layout: alloc::Layout) -> Option<usize> {
+        let class = first_level_index(layout.size());
+        (class < NUM_CLASSES).then(|| class)
+    }
+
+    /// Allocate memory.
+    pub fn allocate(&self, layout: alloc::Layout) -> Option<NonNull<u8>> {
+        let class = match Self::size_class(layout) {
+            Some(class) => class,
+            None => return self.main.lock().allocate(layout),
+        };
+
+        let mut tlsf = self.classes[class].lock();
+        if let Some(ptr) = tlsf.allocate(layout) {
+            return Some(ptr);
+        }
+
+        // Lock ordering: The main `Tlsf`'s lock is taken while holding the
+        // size class's lock
+        let chunk = self.carve_chunk(class, layout)?;
+        // Safety: `chunk` is owned by the size class from now on, and it
+        //         outlives the size class's `Tlsf` because it's never
+        //         deallocated from the main `Tlsf`
+        unsafe { tlsf.insert_free_block_ptr(chunk) };
+        tlsf.allocate(layout)
+    }
+
+    /// Allocate a chunk for size class `class` from the main
+    /// [`Tlsf`](crate::Tlsf), which is large enough to allocate `layout`
+    /// from.
+    fn carve_chunk(&self, class: usize, layout: alloc::Layout) -> Option<NonNull<[u8]>> {
+        let min_size = layout
+            .size()
+            .checked_add(layout.align())?
+            .checked_add(GRANULARITY * 4)?;
+        let preferred_size = 1usize
+            .checked_shl(class as u32 + 1 + GRANULARITY.trailing_zeros())
+            .and_then(|max_block_size| max_block_size.checked_mul(CHUNK_BLOCKS))
+            .map_or(min_size, |size| size.max(min_size));
+
+        let mut main = self.main.lock();
+        [preferred_size, min_size].into_iter().find_map(|size| {
+            let chunk_layout = alloc::Layout::from_size_align(size, GRANULARITY).ok()?;
+            let ptr = main.allocate(chunk_layout)?;
+            Some(nonnull_slice_from_raw_parts(ptr, size))
+        })
+    }
+
+    /// Deallocate a previously allocated memory block.
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `self`.
+    ///  - The memory block must have been allocated with `layout`.
+    pub unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: alloc::Layout) {
+        // Safety: `ptr` was allocated from the `Tlsf` serving `layout` with
+        //         alignment `layout.align()`
+        match Self::size_class(layout) {
+            Some(class) => self.classes[class].lock().deallocate(ptr, layout.align()),
+            None => self.main.lock().deallocate(ptr, layout.align()),
+        }
+    }
+}
+
+impl<
+        'pool,
+        const NUM_CLASSES: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > Default for ClassLockedTlsf<'pool, NUM_CLASSES, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<
+        const NUM_CLASSES: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > fmt::Debug for ClassLockedTlsf<'_, NUM_CLASSES, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClassLockedTlsf").finish_non_exhaustive()
+    }
+}
+
+unsafe impl<
+        const NUM_CLASSES: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > alloc::GlobalAlloc
+    for ClassLockedTlsf<'_, NUM_CLASSES, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    unsafe fn alloc(&self, layout: alloc::Layout) -> *mut u8 {
+        self.allocate(layout)
+            .map(NonNull::as_ptr)
+            .unwrap_or(ptr::null_mut())
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: alloc::Layout) {
+        // Safety: All allocations are non-null
+        let ptr = NonNull::new_unchecked(ptr);
+        // Safety: `ptr` denotes a previous allocation with `layout`
+        self.deallocate(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: alloc::Layout, new_size: usize) -> *mut u8 {
+        // Safety: All allocations are non-null
+        let ptr = NonNull::new_unchecked(ptr);
+        // Safety: `layout.align()` is a power of two, and the size parameter's
+        //         validity is upheld by the caller
+        let new_layout = alloc::Layout::from_size_align_unchecked(new_size, layout.align());
+
+        // Reallocate in place if the memory block stays in the same `Tlsf`
+        let class = Self::size_class(layout);
+        if class == Self::size_class(new_layout) {
+            let tlsf = match class {
+                Some(class) => &self.classes[class],
+                None => &self.main,
+            };
+            // Safety: `ptr` denotes a previous allocation with alignment
+            //         `layout.align()`
+            if let Some(new_ptr) = tlsf.lock().reallocate(ptr, new_layout) {
+                return new_ptr.as_ptr();
+            }
+        }
+
+        // Move the memory block to the `Tlsf` serving `new_layout`
+        let new_ptr = match self.allocate(new_layout) {
+            Some(new_ptr) => new_ptr,
+            None => return ptr::null_mut(),
+        };
+        // Safety: the previously allocated block cannot overlap the newly
+        //         allocated block. The old size is the recorded one, which
+        //         doesn't trust `layout.size()`.
+        let old_size = crate::Tlsf::<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(
+            ptr,
+            layout.align(),
+        );
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), old_size.min(new_size));
+        // Safety: `ptr` denotes a previous allocation with `layout`
+        self.deallocate(ptr, layout);
+        new_ptr.as_ptr()
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "allocator-api")))]
+unsafe impl<
+        const NUM_CLASSES: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > alloc::Allocator for &ClassLockedTlsf<'_, NUM_CLASSES, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    fn allocate(&self, layout: alloc::Layout) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        let ptr = ClassLockedTlsf::allocate(self, layout).ok_or(alloc::AllocError)?;
+        Ok(nonnull_slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: alloc::Layout) {
+        // Safety: `ptr` denotes a previous allocation with `layout`
+        ClassLockedTlsf::deallocate(self, ptr, layout);
+    }
+}
+
+#[cfg(test)]
+mod tests;
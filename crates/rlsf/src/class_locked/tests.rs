@@ -0,0 +1,140 @@
+use core::mem::MaybeUninit;
+use std::{alloc::GlobalAlloc, prelude::v1::*, thread, vec};
+
+use super::*;
+
+type TheClassLockedTlsf = ClassLockedTlsf<'static, 4, u16, u16, 12, 16>;
+
+fn new_pool(size: usize) -> &'static mut [MaybeUninit<u8>] {
+    Box::leak(vec![MaybeUninit::uninit(); size].into_boxed_slice())
+}
+
+#[test]
+fn size_classes() {
+    let layout = |size| alloc::Layout::from_size_align(size, 1).unwrap();
+    assert_eq!(TheClassLockedTlsf::size_class(layout(0)), Some(0));
+    assert_eq!(
+        TheClassLockedTlsf::size_class(layout(GRANULARITY * 2 - 1)),
+        Some(0)
+    );
+    assert_eq!(
+        TheClassLockedTlsf::size_class(layout(GRANULARITY * 2)),
+        Some(1)
+    );
+    assert_eq!(
+        TheClassLockedTlsf::size_class(layout((GRANULARITY << 4) - 1)),
+        Some(3)
+    );
+    assert_eq!(
+        TheClassLockedTlsf::size_class(layout(GRANULARITY << 4)),
+        None
+    );
+}
+
+#[test]
+fn carve_chunks() {
+    let tlsf = TheClassLockedTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(1 << 16));
+
+    unsafe {
+        let small = alloc::Layout::from_size_align(GRANULARITY, 8).unwrap();
+        let ptr = tlsf.alloc(small);
+        assert!(!ptr.is_null());
+        // The size class's chunk was allocated from the main `Tlsf`, and the
+        // allocation was made from the chunk
+        let chunk_size = (GRANULARITY << 1) * CHUNK_BLOCKS;
+        assert!(tlsf.classes[0].lock().allocate(small).is_some());
+        assert!(tlsf.classes[1].lock().allocate(small).is_none());
+
+        // A large alignment may need a chunk larger than the preferred size
+        let aligned = alloc::Layout::from_size_align(8, chunk_size * 2).unwrap();
+        let ptr2 = tlsf.alloc(aligned);
+        assert!(!ptr2.is_null());
+        assert_eq!(ptr2 as usize % aligned.align(), 0);
+
+        tlsf.dealloc(ptr, small);
+        tlsf.dealloc(ptr2, aligned);
+    }
+}
+
+#[test]
+fn exhaustion() {
+    let tlsf = TheClassLockedTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(4096));
+
+    let large = alloc::Layout::from_size_align(3800, 8).unwrap();
+    unsafe {
+        let ptr = tlsf.alloc(large);
+        assert!(!ptr.is_null());
+        // The main `Tlsf` can't provide a chunk
+        let small = alloc::Layout::from_size_align(GRANULARITY * 8, 8).unwrap();
+        assert!(tlsf.alloc(small).is_null());
+        tlsf.dealloc(ptr, large);
+        let ptr = tlsf.alloc(small);
+        assert!(!ptr.is_null());
+        tlsf.dealloc(ptr, small);
+    }
+}
+
+#[test]
+fn realloc_across_classes() {
+    let tlsf = TheClassLockedTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(1 << 16));
+
+    unsafe {
+        let mut layout = alloc::Layout::from_size_align(1, 1).unwrap();
+        let mut ptr = tlsf.alloc(layout);
+        ptr.write(0);
+        for size in [2, GRANULARITY * 3, GRANULARITY * 5, 2000, 1500, 5] {
+            ptr = tlsf.realloc(ptr, layout, size);
+            assert!(!ptr.is_null());
+            for i in 0..layout.size().min(size) {
+                assert_eq!(*ptr.add(i), i as u8);
+            }
+            for i in 0..size {
+                *ptr.add(i) = i as u8;
+            }
+            layout = alloc::Layout::from_size_align(size, 1).unwrap();
+        }
+        tlsf.dealloc(ptr, layout);
+    }
+}
+
+#[test]
+fn concurrent_alloc() {
+    static TLSF: TheClassLockedTlsf = ClassLockedTlsf::new();
+    TLSF.lock().insert_free_block(new_pool(1 << 18));
+
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            thread::spawn(move || unsafe {
+                let layout = alloc::Layout::from_size_align(8 << (i % 6), 8).unwrap();
+                for _ in 0..1000 {
+                    let ptr = TLSF.alloc(layout);
+                    assert!(!ptr.is_null());
+                    ptr.write_bytes(i as u8, layout.size());
+                    thread::yield_now();
+                    for j in 0..layout.size() {
+                        assert_eq!(*ptr.add(j), i as u8);
+                    }
+                    TLSF.dealloc(ptr, layout);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+#[test]
+fn allocator() {
+    let tlsf = TheClassLockedTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(1 << 16));
+
+    let mut v = Vec::new_in(&tlsf);
+    v.extend(0..100u32);
+    v.shrink_to_fit();
+    assert_eq!(v.iter().sum::<u32>(), 4950);
+}
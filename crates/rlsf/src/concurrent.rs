@@ -0,0 +1,723 @@
+//! `ConcurrentTlsf`: [`SyncTlsf`] with wait-free fast paths
+use core::{
+    alloc, fmt,
+    ptr::{self, NonNull},
+};
+
+#[cfg(feature = "allocator-api")]
+use crate::utils::nonnull_slice_from_raw_parts;
+use crate::{
+    int::BinInteger,
+    primitives::{
+        atomic::{fence, AtomicPtr, AtomicUsize, Ordering},
+        hint,
+    },
+    ContentionStats, DefaultLock, StaticGlobalTlsfLock, SyncTlsf, SyncTlsfGuard, Tlsf, GRANULARITY,
+};
+
+/// The number of size classes served by the free slots. Class `i` holds
+/// memory blocks of `(i + 1) * GRANULARITY` bytes.
+const NUM_CLASSES: usize = 32;
+
+/// The number of free slots per size class
+const NUM_SLOTS: usize = 4;
+
+/// The maximum number of hot size classes (see
+/// [`ConcurrentTlsf::with_hot_sizes`])
+const MAX_HOT_CLASSES: usize = 4;
+
+/// The number of free slots per hot size class
+const NUM_HOT_SLOTS: usize = 16;
+
+/// The number of memory blocks allocated or deallocated at once when a hot
+/// size class's free slots are empty or full
+const HOT_BATCH: usize = NUM_HOT_SLOTS / 2;
+
+/// The alignment with which the memory blocks of the size classes are
+/// allocated from [`Tlsf`]. All alignments less than [`GRANULARITY`] are
+/// handled in the same way by `Tlsf`, so they can share memory blocks.
+///
+/// [`Tlsf`]: crate::Tlsf
+const CLASS_ALIGN: usize = GRANULARITY / 2;
+
+/// Get the size class serving `layout`.
+#[inline]
+fn size_class(layout: alloc::Layout) -> Option<usize> {
+    let class = layout.size().saturating_sub(1) / GRANULARITY;
+    (layout.align() <= CLASS_ALIGN && class < NUM_CLASSES).then(|| class)
+}
+
+/// The path serving a layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Route {
+    /// The hot size class with the given index
+    Hot(usize),
+    /// The size class with the given index
+    Small(usize),
+    /// The underlying [`Tlsf`](crate::Tlsf)
+    Large,
+}
+
+/// Get the layout with which the memory blocks of size class `class` are
+/// allocated from [`Tlsf`](crate::Tlsf).
+#[inline]
+fn class_layout(class: usize) -> alloc::Layout {
+    // Safety: `CLASS_ALIGN` is a power of two, and the size is small
+    unsafe { alloc::Layout::from_size_align_unchecked((class + 1) * GRANULARITY, CLASS_ALIGN) }
+}
+
+/// Round up an allocation size to the size of the memory blocks of a hot
+/// size class serving it.
+#[inline]
+const fn round_up_size(size: usize) -> usize {
+    let size = if size == 0 { 1 } else { size };
+    match size.checked_add(GRANULARITY - 1) {
+        Some(x) => x & !(GRANULARITY - 1),
+        None => panic!("hot size class is too large"),
+    }
+}
+
+/// Get the layout with which the memory blocks of a hot size class whose
+/// rounded size is `size` are allocated from [`Tlsf`](crate::Tlsf).
+#[inline]
+fn hot_layout(size: usize) -> Option<alloc::Layout> {
+    alloc::Layout::from_size_align(size, CLASS_ALIGN).ok()
+}
+
+/// Take a memory block from `slots`.
+#[inline]
+fn take_slot(slots: &[AtomicPtr<u8>]) -> Option<NonNull<u8>> {
+    slots
+        .iter()
+        .filter(|slot| !slot.load(Ordering::Relaxed).is_null())
+        .find_map(|slot| NonNull::new(slot.swap(ptr::null_mut(), Ordering::Acquire)))
+}
+
+/// Put a memory block in `slots`. Returns `false` if they are full.
+#[inline]
+fn put_slot(slots: &[AtomicPtr<u8>], ptr: NonNull<u8>) -> bool {
+    slots.iter().any(|slot| {
+        slot.compare_exchange(
+            ptr::null_mut(),
+            ptr.as_ptr(),
+            Ordering::Release,
+            Ordering::Relaxed,
+        )
+        .is_ok()
+    })
+}
+
+/// The changes to [`SeqStats`] made while holding the lock
+#[derive(Debug, Default)]
+struct StatsDelta {
+    bytes_allocated: usize,
+    bytes_deallocated: usize,
+    num_allocated: usize,
+    num_deallocated: usize,
+}
+
+/// The counters behind [`ConcurrentTlsfStats`], updated by the holder of the
+/// lock and read without the lock through a sequence lock
+struct SeqStats {
+    /// Incremented before and after each update, so it's odd during one
+    seq: AtomicUsize,
+    bytes_allocated: AtomicUsize,
+    peak_bytes_allocated: AtomicUsize,
+    num_blocks_allocated: AtomicUsize,
+}
+
+impl SeqStats {
+    const_fn! {
+        const fn new() -> Self {
+            Self {
+                seq: AtomicUsize::new(0),
+                bytes_allocated: AtomicUsize::new(0),
+                peak_bytes_allocated: AtomicUsize::new(0),
+                num_blocks_allocated: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    /// Apply `delta`. Must be called while holding the lock, which makes the
+    /// caller the only writer.
+    fn update(&self, delta: &StatsDelta) {
+        if delta.bytes_allocated == 0 && delta.bytes_deallocated == 0 {
+            return;
+        }
+        let seq = self.seq.load(Ordering::Relaxed);
+        self.seq.store(seq.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        let bytes = self.bytes_allocated.load(Ordering::Relaxed) + delta.bytes_allocated
+            - delta.bytes_deallocated;
+        let peak = self.peak_bytes_allocated.load(Ordering::Relaxed).max(bytes);
+        let num = self.num_blocks_allocated.load(Ordering::Relaxed) + delta.num_allocated
+            - delta.num_deallocated;
+        self.bytes_allocated.store(bytes, Ordering::Relaxed);
+        self.peak_bytes_allocated.store(peak, Ordering::Relaxed);
+        self.num_blocks_allocated.store(num, Ordering::Relaxed);
+
+        self.seq.store(seq.wrapping_add(2), Ordering::Release);
+    }
+
+    /// Read a consistent set of counters, retrying while an update is in
+    /// progress. Returns `(bytes_allocated, peak_bytes_allocated,
+    /// num_blocks_allocated)`.
+    fn read(&self) -> (usize, usize, usize) {
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq % 2 == 0 {
+                let values = (
+                    self.bytes_allocated.load(Ordering::Relaxed),
+                    self.peak_bytes_allocated.load(Ordering::Relaxed),
+                    self.num_blocks_allocated.load(Ordering::Relaxed),
+                );
+                fence(Ordering::Acquire);
+                if self.seq.load(Ordering::Relaxed) == seq {
+                    return values;
+                }
+            }
+            hint::spin_loop();
+        }
+    }
+}
+
+/// [`SyncTlsfGuard`] recording the memory blocks allocated and deallocated
+/// through it in [`SeqStats`] when dropped
+struct CountingGuard<
+    'a,
+    'pool,
+    FLBitmap,
+    SLBitmap,
+    const FLLEN: usize,
+    const SLLEN: usize,
+    Lock: StaticGlobalTlsfLock,
+> {
+    tlsf: SyncTlsfGuard<'a, 'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>,
+    counters: &'a SeqStats,
+    delta: StatsDelta,
+}
+
+impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock>
+    CountingGuard<'_, '_, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    fn allocate(&mut self, layout: alloc::Layout) -> Option<NonNull<u8>> {
+        let ptr = self.tlsf.allocate(layout)?;
+        // Safety: `ptr` was allocated with `layout.align()`
+        let size = unsafe {
+            Tlsf::<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(ptr, layout.align())
+        };
+        self.delta.bytes_allocated += size;
+        self.delta.num_allocated += 1;
+        Some(ptr)
+    }
+
+    /// # Safety
+    ///
+    /// See [`Tlsf::deallocate`].
+    #[inline]
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, align: usize) {
+        // Safety: Upheld by the caller
+        let size = Tlsf::<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(ptr, align);
+        self.delta.bytes_deallocated += size;
+        self.delta.num_deallocated += 1;
+        self.tlsf.deallocate(ptr, align);
+    }
+
+    /// # Safety
+    ///
+    /// See [`Tlsf::reallocate`].
+    #[inline]
+    unsafe fn reallocate(
+        &mut self,
+        ptr: NonNull<u8>,
+        new_layout: alloc::Layout,
+    ) -> Option<NonNull<u8>> {
+        let align = new_layout.align();
+        // Safety: Upheld by the caller
+        let old_size = Tlsf::<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(ptr, align);
+        let new_ptr = self.tlsf.reallocate(ptr, new_layout)?;
+        // Safety: `new_ptr` was allocated with `align`
+        let new_size =
+            Tlsf::<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(new_ptr, align);
+        self.delta.bytes_deallocated += old_size;
+        self.delta.bytes_allocated += new_size;
+        Some(new_ptr)
+    }
+}
+
+impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock: StaticGlobalTlsfLock> Drop
+    for CountingGuard<'_, '_, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+{
+    #[inline]
+    fn drop(&mut self) {
+        // `self.tlsf` is dropped after this, so we still hold the lock
+        self.counters.update(&self.delta);
+    }
+}
+
+/// [`SyncTlsf`] with wait-free fast paths for small allocations.
+///
+/// Small memory blocks (up to `32 * GRANULARITY` bytes with alignments less
+/// than [`GRANULARITY`]) are not returned to the underlying [`Tlsf`] when
+/// deallocated, but stored in a fixed number of atomic *free slots* per size
+/// class. Allocation takes a memory block from a free slot of the matching
+/// size class if there is one. Both operations visit a bounded number of
+/// slots with a single atomic read-modify-write operation each and never
+/// wait for other threads. They fall back to the locked `Tlsf` when the free
+/// slots are empty or full, respectively.
+///
+/// [`Self::with_hot_sizes`] declares up to [`Self::MAX_HOT_CLASSES`]
+/// additional *hot* size classes for allocation sizes that are known to be
+/// frequent, which can be larger than `32 * GRANULARITY` bytes. Each hot size
+/// class has 16 free slots, which are refilled and drained in batches of 8
+/// memory blocks, so that most of its allocations and deallocations are
+/// served by the wait-free fast paths even if they aren't interleaved.
+///
+/// Memory blocks in the free slots aren't coalesced with neighboring free
+/// blocks. Call [`Self::flush`] to return them to the `Tlsf`.
+///
+/// Memory pools are supplied through [`Self::lock`]. Memory blocks allocated
+/// through `ConcurrentTlsf` must not be deallocated through the `Tlsf` and
+/// vice versa.
+///
+/// [`Tlsf`]: crate::Tlsf
+///
+/// # Examples
+///
+/// ```rust
+/// use rlsf::ConcurrentTlsf;
+/// use std::{alloc::{GlobalAlloc, Layout}, mem::MaybeUninit};
+///
+/// static TLSF: ConcurrentTlsf<'static, u16, u16, 12, 16> = ConcurrentTlsf::new();
+///
+/// static mut POOL: [MaybeUninit<u8>; 8192] = [MaybeUninit::uninit(); 8192];
+/// TLSF.lock().insert_free_block(unsafe { &mut POOL });
+///
+/// let layout = Layout::new::<u64>();
+/// unsafe {
+///     let ptr = TLSF.alloc(layout);
+///     TLSF.dealloc(ptr, layout); // kept in a free slot
+///     assert_eq!(TLSF.alloc(layout), ptr); // reused without locking
+/// }
+/// ```
+pub struct ConcurrentTlsf<
+    'pool,
+    FLBitmap,
+    SLBitmap,
+    const FLLEN: usize,
+    const SLLEN: usize,
+    Lock = DefaultLock,
+> {
+    inner: SyncTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>,
+    /// `slots[class]` holds free memory blocks allocated with
+    /// `class_layout(class)`
+    slots: [[AtomicPtr<u8>; NUM_SLOTS]; NUM_CLASSES],
+    /// The allocation sizes of the hot size classes, rounded up to
+    /// `GRANULARITY`. Zero denotes an unused entry.
+    hot_sizes: [usize; MAX_HOT_CLASSES],
+    /// `hot_slots[i]` holds free memory blocks allocated with
+    /// `hot_layout(hot_sizes[i])`
+    hot_slots: [[AtomicPtr<u8>; NUM_HOT_SLOTS]; MAX_HOT_CLASSES],
+    counters: SeqStats,
+}
+
+impl<'pool, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock>
+    ConcurrentTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[cfg(not(loom))]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_SLOT: AtomicPtr<u8> = AtomicPtr::new(ptr::null_mut());
+
+    #[cfg(not(loom))]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_CLASS: [AtomicPtr<u8>; NUM_SLOTS] = [Self::EMPTY_SLOT; NUM_SLOTS];
+
+    #[cfg(not(loom))]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_HOT_CLASS: [AtomicPtr<u8>; NUM_HOT_SLOTS] = [Self::EMPTY_SLOT; NUM_HOT_SLOTS];
+
+    /// The maximum number of hot size classes that can be declared by
+    /// [`Self::with_hot_sizes`]
+    pub const MAX_HOT_CLASSES: usize = MAX_HOT_CLASSES;
+
+    const_fn! {
+        /// Construct an empty instance of `Self`.
+        #[inline]
+        pub const fn new() -> Self {
+            Self::with_hot_sizes(&[])
+        }
+    }
+
+    const_fn! {
+        /// Construct an empty instance of `Self` with a hot size class for
+        /// each allocation size in `hot_sizes`. Allocations whose sizes round
+        /// up to the same multiple of [`GRANULARITY`] as one of `hot_sizes`
+        /// and whose alignments are less than `GRANULARITY` are served by the
+        /// hot size class.
+        ///
+        /// # Panics
+        ///
+        /// This function panics if `hot_sizes` has more than
+        /// [`Self::MAX_HOT_CLASSES`] elements.
+        ///
+        /// # Examples
+        ///
+        /// ```rust
+        /// use rlsf::ConcurrentTlsf;
+        ///
+        /// // Messages of 512 and 1500 bytes are allocated frequently
+        /// static TLSF: ConcurrentTlsf<'static, u16, u16, 12, 16> =
+        ///     ConcurrentTlsf::with_hot_sizes(&[512, 1500]);
+        /// ```
+        pub const fn with_hot_sizes(hot_sizes: &[usize]) -> Self {
+            assert!(
+                hot_sizes.len() <= MAX_HOT_CLASSES,
+                "too many hot size classes"
+            );
+            let mut rounded_sizes = [0; MAX_HOT_CLASSES];
+            let mut i = 0;
+            while i < hot_sizes.len() {
+                rounded_sizes[i] = round_up_size(hot_sizes[i]);
+                i += 1;
+            }
+            Self {
+                inner: SyncTlsf::new(),
+                slots: array_repeat!(
+                    Self::EMPTY_CLASS,
+                    core::array::from_fn(|_| AtomicPtr::new(ptr::null_mut()));
+                    NUM_CLASSES
+                ),
+                hot_sizes: rounded_sizes,
+                hot_slots: array_repeat!(
+                    Self::EMPTY_HOT_CLASS,
+                    core::array::from_fn(|_| AtomicPtr::new(ptr::null_mut()));
+                    MAX_HOT_CLASSES
+                ),
+                counters: SeqStats::new(),
+            }
+        }
+    }
+
+    /// Acquire the lock of the underlying [`Tlsf`](crate::Tlsf), e.g., to
+    /// supply memory pools. See [`SyncTlsf::lock`].
+    #[inline]
+    pub fn lock(&self) -> SyncTlsfGuard<'_, 'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock> {
+        self.inner.lock()
+    }
+
+    /// Return the memory blocks in the free slots to the underlying
+    /// [`Tlsf`](crate::Tlsf), allowing them to be coalesced.
+    pub fn flush(&self) {
+        let mut tlsf = self.lock_counted();
+        let slots = self.slots.iter().flatten();
+        for slot in slots.chain(self.hot_slots.iter().flatten()) {
+            if let Some(ptr) = NonNull::new(slot.swap(ptr::null_mut(), Ordering::Acquire)) {
+                // Safety: `ptr` was allocated with `class_layout(_)` or
+                //         `hot_layout(_)`
+                unsafe { tlsf.deallocate(ptr, CLASS_ALIGN) };
+            }
+        }
+    }
+
+    /// Get the path serving `layout`.
+    #[inline]
+    fn route(&self, layout: alloc::Layout) -> Route {
+        if layout.align() <= CLASS_ALIGN {
+            let size = round_up_size(layout.size());
+            if let Some(i) = self.hot_sizes.iter().position(|&hot_size| hot_size == size) {
+                return Route::Hot(i);
+            }
+        }
+        size_class(layout).map_or(Route::Large, Route::Small)
+    }
+
+    /// Allocate a memory block of hot size class `i`.
+    #[inline]
+    fn allocate_hot(&self, i: usize) -> Option<NonNull<u8>> {
+        if let Some(ptr) = take_slot(&self.hot_slots[i]) {
+            return Some(ptr);
+        }
+
+        // Refill the free slots while we hold the lock
+        self.inner.contention().record_cache_miss();
+        let layout = hot_layout(self.hot_sizes[i])?;
+        let mut tlsf = self.lock_counted();
+        let ptr = tlsf.allocate(layout)?;
+        for _ in 1..HOT_BATCH {
+            let extra = match tlsf.allocate(layout) {
+                Some(extra) => extra,
+                None => break,
+            };
+            if !put_slot(&self.hot_slots[i], extra) {
+                // Safety: `extra` was allocated with `layout`
+                unsafe { tlsf.deallocate(extra, CLASS_ALIGN) };
+                break;
+            }
+        }
+        Some(ptr)
+    }
+
+    /// Deallocate a memory block of hot size class `i`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a previous allocation of hot size class `i`.
+    #[inline]
+    unsafe fn deallocate_hot(&self, i: usize, ptr: NonNull<u8>) {
+        if put_slot(&self.hot_slots[i], ptr) {
+            return;
+        }
+
+        // Drain the free slots while we hold the lock
+        self.inner.contention().record_cache_miss();
+        let mut tlsf = self.lock_counted();
+        // Safety: `ptr` was allocated with `hot_layout(_)`
+        tlsf.deallocate(ptr, CLASS_ALIGN);
+        for _ in 1..HOT_BATCH {
+            let extra = match take_slot(&self.hot_slots[i]) {
+                Some(extra) => extra,
+                None => break,
+            };
+            // Safety: `extra` was allocated with `hot_layout(_)`
+            tlsf.deallocate(extra, CLASS_ALIGN);
+        }
+    }
+
+    /// Get the contention counters of `self`. [`ContentionStats::cache_misses`]
+    /// counts the small allocations and deallocations that the free slots
+    /// couldn't serve.
+    pub fn contention_stats(&self) -> ContentionStats {
+        self.inner.contention_stats()
+    }
+
+    /// Get the statistics of `self` without taking the lock.
+    ///
+    /// The counters are published through a sequence lock by whoever holds
+    /// the lock, so monitoring code can call this method as often as it
+    /// likes without delaying allocations. Only the memory blocks allocated
+    /// through `self` (not through [`Self::lock`]) are counted.
+    ///
+    /// A heap walk (e.g., [`Tlsf::iter_blocks`]) still needs
+    /// [`Self::lock`], but since the wait-free fast paths never take the
+    /// lock, it only delays the allocations and deallocations that the free
+    /// slots can't serve.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::ConcurrentTlsf;
+    /// use std::{alloc::{GlobalAlloc, Layout}, mem::MaybeUninit};
+    ///
+    /// static TLSF: ConcurrentTlsf<'static, u16, u16, 12, 16> = ConcurrentTlsf::new();
+    ///
+    /// static mut POOL: [MaybeUninit<u8>; 8192] = [MaybeUninit::uninit(); 8192];
+    /// TLSF.lock().insert_free_block(unsafe { &mut POOL });
+    ///
+    /// let layout = Layout::new::<u64>();
+    /// unsafe {
+    ///     let ptr = TLSF.alloc(layout);
+    ///     assert_eq!(TLSF.stats().num_blocks_allocated, 1);
+    ///     TLSF.dealloc(ptr, layout); // kept in a free slot
+    /// }
+    /// let stats = TLSF.stats();
+    /// assert_eq!(stats.num_blocks_allocated, 1);
+    /// assert_eq!(stats.num_blocks_cached, 1);
+    /// ```
+    pub fn stats(&self) -> ConcurrentTlsfStats {
+        let (bytes_allocated, peak_bytes_allocated, num_blocks_allocated) = self.counters.read();
+        let slots = self.slots.iter().flatten();
+        let num_blocks_cached = slots
+            .chain(self.hot_slots.iter().flatten())
+            .filter(|slot| !slot.load(Ordering::Relaxed).is_null())
+            .count();
+        ConcurrentTlsfStats {
+            bytes_allocated,
+            peak_bytes_allocated,
+            num_blocks_allocated,
+            num_blocks_cached,
+        }
+    }
+
+    /// Acquire the lock of the underlying [`Tlsf`], recording the memory
+    /// blocks allocated and deallocated through the returned guard in
+    /// [`Self::stats`].
+    #[inline]
+    fn lock_counted(&self) -> CountingGuard<'_, 'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock> {
+        CountingGuard {
+            tlsf: self.inner.lock(),
+            counters: &self.counters,
+            delta: StatsDelta::default(),
+        }
+    }
+
+    #[inline]
+    fn allocate(&self, layout: alloc::Layout) -> Option<NonNull<u8>> {
+        match self.route(layout) {
+            Route::Hot(i) => self.allocate_hot(i),
+            Route::Small(class) => take_slot(&self.slots[class]).or_else(|| {
+                self.inner.contention().record_cache_miss();
+                self.lock_counted().allocate(class_layout(class))
+            }),
+            Route::Large => self.lock_counted().allocate(layout),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `ptr` must denote a previous allocation with `layout`.
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: alloc::Layout) {
+        match self.route(layout) {
+            // Safety: `ptr` was allocated with `hot_layout(hot_sizes[i])`
+            Route::Hot(i) => self.deallocate_hot(i, ptr),
+            Route::Small(class) => {
+                if !put_slot(&self.slots[class], ptr) {
+                    self.inner.contention().record_cache_miss();
+                    // Safety: `ptr` was allocated with `class_layout(class)`
+                    self.lock_counted().deallocate(ptr, CLASS_ALIGN);
+                }
+            }
+            // Safety: `ptr` was allocated with `layout`
+            Route::Large => self.lock_counted().deallocate(ptr, layout.align()),
+        }
+    }
+}
+
+impl<'pool, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock> Default
+    for ConcurrentTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock> fmt::Debug
+    for ConcurrentTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConcurrentTlsf").finish_non_exhaustive()
+    }
+}
+
+unsafe impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock> alloc::GlobalAlloc
+    for ConcurrentTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    unsafe fn alloc(&self, layout: alloc::Layout) -> *mut u8 {
+        self.allocate(layout)
+            .map(NonNull::as_ptr)
+            .unwrap_or(ptr::null_mut())
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: alloc::Layout) {
+        // Safety: All allocations are non-null
+        let ptr = NonNull::new_unchecked(ptr);
+        // Safety: `ptr` denotes a previous allocation with `layout`
+        self.deallocate(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: alloc::Layout, new_size: usize) -> *mut u8 {
+        // Safety: All allocations are non-null
+        let ptr = NonNull::new_unchecked(ptr);
+        // Safety: `layout.align()` is a power of two, and the size parameter's
+        //         validity is upheld by the caller
+        let new_layout = alloc::Layout::from_size_align_unchecked(new_size, layout.align());
+
+        match (self.route(layout), self.route(new_layout)) {
+            // The memory block is large enough
+            (route, new_route) if route == new_route && route != Route::Large => ptr.as_ptr(),
+            // Safety: `ptr` denotes a previous allocation with alignment
+            //         `layout.align()`
+            (Route::Large, Route::Large) => self
+                .lock_counted()
+                .reallocate(ptr, new_layout)
+                .map(NonNull::as_ptr)
+                .unwrap_or(ptr::null_mut()),
+            _ => {
+                let new_ptr = match self.allocate(new_layout) {
+                    Some(new_ptr) => new_ptr,
+                    None => return ptr::null_mut(),
+                };
+                // Safety: the previously allocated block cannot overlap the
+                //         newly allocated block. The old size is the
+                //         recorded one, which doesn't trust `layout.size()`.
+                let old_size = Tlsf::<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(
+                    ptr,
+                    layout.align(),
+                );
+                ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), old_size.min(new_size));
+                // Safety: `ptr` denotes a previous allocation with `layout`
+                self.deallocate(ptr, layout);
+                new_ptr.as_ptr()
+            }
+        }
+    }
+}
+
+/// Statistics of a [`ConcurrentTlsf`], returned by [`ConcurrentTlsf::stats`].
+///
+/// The memory blocks held in the free slots count as allocated from the
+/// underlying [`Tlsf`]. [`Self::num_blocks_cached`] is obtained by
+/// inspecting the free slots one by one, so it's only approximate while
+/// allocations proceed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct ConcurrentTlsfStats {
+    /// The total payload size of the memory blocks allocated from the
+    /// underlying `Tlsf`.
+    pub bytes_allocated: usize,
+    /// The highest value [`Self::bytes_allocated`] has ever reached.
+    pub peak_bytes_allocated: usize,
+    /// The number of memory blocks allocated from the underlying `Tlsf`.
+    pub num_blocks_allocated: usize,
+    /// The number of memory blocks held in the free slots.
+    pub num_blocks_cached: usize,
+}
+
+#[cfg(feature = "allocator-api")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "allocator-api")))]
+unsafe impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock> alloc::Allocator
+    for &ConcurrentTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    fn allocate(&self, layout: alloc::Layout) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        let ptr = ConcurrentTlsf::allocate(self, layout).ok_or(alloc::AllocError)?;
+        // Report only the requested size; the actual size depends on which
+        // path served the allocation
+        Ok(nonnull_slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: alloc::Layout) {
+        // Safety: `ptr` denotes a previous allocation with `layout`
+        ConcurrentTlsf::deallocate(self, ptr, layout);
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,308 @@
+use std::{alloc::GlobalAlloc, mem::MaybeUninit, prelude::v1::*, thread, vec};
+
+use super::*;
+
+type TheConcurrentTlsf = ConcurrentTlsf<'static, u16, u16, 12, 16>;
+
+fn new_pool(size: usize) -> &'static mut [MaybeUninit<u8>] {
+    Box::leak(vec![MaybeUninit::uninit(); size].into_boxed_slice())
+}
+
+#[test]
+fn size_classes() {
+    let layout = |size, align| alloc::Layout::from_size_align(size, align).unwrap();
+    assert_eq!(size_class(layout(0, 1)), Some(0));
+    assert_eq!(size_class(layout(GRANULARITY, 1)), Some(0));
+    assert_eq!(size_class(layout(GRANULARITY + 1, 1)), Some(1));
+    assert_eq!(
+        size_class(layout(NUM_CLASSES * GRANULARITY, CLASS_ALIGN)),
+        Some(NUM_CLASSES - 1)
+    );
+    assert_eq!(size_class(layout(NUM_CLASSES * GRANULARITY + 1, 1)), None);
+    assert_eq!(size_class(layout(8, GRANULARITY)), None);
+}
+
+#[test]
+fn reuse_and_flush() {
+    let tlsf = TheConcurrentTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(4096));
+
+    let layout = alloc::Layout::from_size_align(24, 8).unwrap();
+    unsafe {
+        let ptrs: Vec<_> = (0..NUM_SLOTS + 1).map(|_| tlsf.alloc(layout)).collect();
+        assert!(ptrs.iter().all(|ptr| !ptr.is_null()));
+        for &ptr in &ptrs {
+            tlsf.dealloc(ptr, layout);
+        }
+
+        // The free slots are reused in any order
+        let mut reused: Vec<_> = (0..NUM_SLOTS).map(|_| tlsf.alloc(layout)).collect();
+        reused.sort_unstable();
+        let mut expected = ptrs[..NUM_SLOTS].to_vec();
+        expected.sort_unstable();
+        assert_eq!(reused, expected);
+        for &ptr in &reused {
+            tlsf.dealloc(ptr, layout);
+        }
+
+        // Memory blocks held by the free slots are unavailable to larger
+        // allocations until flushed
+        tlsf.flush();
+        assert!(tlsf
+            .slots
+            .iter()
+            .flatten()
+            .all(|slot| slot.load(Ordering::Relaxed).is_null()));
+        let big = alloc::Layout::from_size_align(3072, 8).unwrap();
+        let ptr = tlsf.alloc(big);
+        assert!(!ptr.is_null());
+        tlsf.dealloc(ptr, big);
+    }
+}
+
+#[test]
+fn cache_misses() {
+    let tlsf = TheConcurrentTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(4096));
+    let base = tlsf.contention_stats();
+
+    let layout = alloc::Layout::from_size_align(24, 8).unwrap();
+    unsafe {
+        // Misses the empty free slots
+        let ptrs: Vec<_> = (0..NUM_SLOTS + 1).map(|_| tlsf.alloc(layout)).collect();
+        // The last one misses the full free slots
+        for &ptr in &ptrs {
+            tlsf.dealloc(ptr, layout);
+        }
+        // Hit
+        let ptr = tlsf.alloc(layout);
+        tlsf.dealloc(ptr, layout);
+    }
+
+    let stats = tlsf.contention_stats();
+    assert_eq!(stats.cache_misses - base.cache_misses, NUM_SLOTS + 2);
+    assert_eq!(
+        stats.lock_acquisitions - base.lock_acquisitions,
+        NUM_SLOTS + 2
+    );
+}
+
+#[test]
+fn hot_routes() {
+    let tlsf = TheConcurrentTlsf::with_hot_sizes(&[GRANULARITY * 2 - 8, 1500]);
+    let layout = |size, align| alloc::Layout::from_size_align(size, align).unwrap();
+    assert_eq!(tlsf.route(layout(GRANULARITY + 1, 8)), Route::Hot(0));
+    assert_eq!(tlsf.route(layout(GRANULARITY * 2, 8)), Route::Hot(0));
+    assert_eq!(tlsf.route(layout(GRANULARITY, 8)), Route::Small(0));
+    assert_eq!(tlsf.route(layout(1500, CLASS_ALIGN)), Route::Hot(1));
+    assert_eq!(tlsf.route(layout(1500, GRANULARITY)), Route::Large);
+    assert_eq!(tlsf.route(layout(4096, 8)), Route::Large);
+
+    // Unused entries don't match anything
+    assert_eq!(
+        TheConcurrentTlsf::new().route(layout(1500, 8)),
+        Route::Large
+    );
+}
+
+#[test]
+#[should_panic]
+fn too_many_hot_classes() {
+    TheConcurrentTlsf::with_hot_sizes(&[64; MAX_HOT_CLASSES + 1]);
+}
+
+#[test]
+fn hot_batches() {
+    let tlsf = TheConcurrentTlsf::with_hot_sizes(&[1500]);
+    tlsf.lock().insert_free_block(new_pool(1 << 16));
+    let layout = alloc::Layout::from_size_align(1500, 8).unwrap();
+    let lock_acquisitions = || tlsf.contention_stats().lock_acquisitions;
+
+    unsafe {
+        // The first allocation refills the free slots
+        let base = lock_acquisitions();
+        let mut ptrs = vec![tlsf.alloc(layout)];
+        assert_eq!(lock_acquisitions() - base, 1);
+        ptrs.extend((1..HOT_BATCH).map(|_| tlsf.alloc(layout)));
+        assert_eq!(lock_acquisitions() - base, 1);
+        ptrs.push(tlsf.alloc(layout));
+        assert_eq!(lock_acquisitions() - base, 2);
+        assert!(ptrs.iter().all(|ptr| !ptr.is_null()));
+
+        // Fill the free slots, starting from empty ones
+        ptrs.extend((ptrs.len()..NUM_HOT_SLOTS + 1).map(|_| tlsf.alloc(layout)));
+        assert!(ptrs.iter().all(|ptr| !ptr.is_null()));
+        tlsf.flush();
+        let base = lock_acquisitions();
+        for &ptr in &ptrs[..NUM_HOT_SLOTS] {
+            tlsf.dealloc(ptr, layout);
+        }
+        assert_eq!(lock_acquisitions(), base);
+
+        // The free slots are full, so this deallocation drains them
+        tlsf.dealloc(ptrs[NUM_HOT_SLOTS], layout);
+        assert_eq!(lock_acquisitions() - base, 1);
+        let num_free = tlsf.hot_slots[0]
+            .iter()
+            .filter(|slot| !slot.load(Ordering::Relaxed).is_null())
+            .count();
+        assert_eq!(num_free, NUM_HOT_SLOTS - (HOT_BATCH - 1));
+
+        // Everything has been deallocated
+        tlsf.flush();
+        let big = alloc::Layout::from_size_align(1 << 15, 8).unwrap();
+        let ptr = tlsf.alloc(big);
+        assert!(!ptr.is_null());
+        tlsf.dealloc(ptr, big);
+    }
+}
+
+#[test]
+fn realloc_across_classes() {
+    let tlsf = TheConcurrentTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(8192));
+
+    unsafe {
+        let mut layout = alloc::Layout::from_size_align(1, 1).unwrap();
+        let mut ptr = tlsf.alloc(layout);
+        ptr.write(0);
+        for size in [2, GRANULARITY, GRANULARITY * 3, 2000, 1500, 5] {
+            ptr = tlsf.realloc(ptr, layout, size);
+            assert!(!ptr.is_null());
+            for i in 0..layout.size().min(size) {
+                assert_eq!(*ptr.add(i), i as u8);
+            }
+            for i in 0..size {
+                *ptr.add(i) = i as u8;
+            }
+            layout = alloc::Layout::from_size_align(size, 1).unwrap();
+        }
+        tlsf.dealloc(ptr, layout);
+    }
+}
+
+#[test]
+fn concurrent_alloc() {
+    static TLSF: TheConcurrentTlsf = ConcurrentTlsf::new();
+    TLSF.lock().insert_free_block(new_pool(1 << 16));
+
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            thread::spawn(move || unsafe {
+                let layouts = [
+                    alloc::Layout::from_size_align(8 + i * 8, 8).unwrap(),
+                    alloc::Layout::from_size_align(2048, 64).unwrap(),
+                ];
+                for k in 0..1000 {
+                    let layout = layouts[(k % 7 == 0) as usize];
+                    let ptr = TLSF.alloc(layout);
+                    assert!(!ptr.is_null());
+                    ptr.write_bytes(i as u8, layout.size());
+                    thread::yield_now();
+                    for j in 0..layout.size() {
+                        assert_eq!(*ptr.add(j), i as u8);
+                    }
+                    TLSF.dealloc(ptr, layout);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // Everything has been deallocated
+    TLSF.flush();
+    let layout = alloc::Layout::from_size_align(1 << 15, 8).unwrap();
+    unsafe {
+        let ptr = TLSF.alloc(layout);
+        assert!(!ptr.is_null());
+        TLSF.dealloc(ptr, layout);
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+#[test]
+fn allocator() {
+    let tlsf = TheConcurrentTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(4096));
+
+    let mut v = Vec::new_in(&tlsf);
+    v.extend(0..100u32);
+    v.shrink_to_fit();
+    assert_eq!(v.iter().sum::<u32>(), 4950);
+}
+
+#[test]
+fn stats() {
+    let tlsf = TheConcurrentTlsf::with_hot_sizes(&[1500]);
+    tlsf.lock().insert_free_block(new_pool(1 << 16));
+    assert_eq!(tlsf.stats(), ConcurrentTlsfStats::default());
+
+    let small = alloc::Layout::from_size_align(24, 8).unwrap();
+    let large = alloc::Layout::from_size_align(4096, 64).unwrap();
+    unsafe {
+        let ptr = tlsf.alloc(small);
+        let stats = tlsf.stats();
+        assert_eq!(stats.num_blocks_allocated, 1);
+        assert!(stats.bytes_allocated >= 24);
+        assert_eq!(stats.num_blocks_cached, 0);
+
+        // Kept in a free slot
+        tlsf.dealloc(ptr, small);
+        let stats = tlsf.stats();
+        assert_eq!(stats.num_blocks_allocated, 1);
+        assert_eq!(stats.num_blocks_cached, 1);
+
+        let ptr = tlsf.alloc(large);
+        let peak = tlsf.stats().bytes_allocated;
+        assert!(peak >= 4096 + 24);
+        let ptr = tlsf.realloc(ptr, large, 8192);
+        assert!(tlsf.stats().bytes_allocated >= 8192 + 24);
+        tlsf.dealloc(ptr, alloc::Layout::from_size_align(8192, 64).unwrap());
+
+        // Hot size classes are refilled in batches
+        let ptr = tlsf.alloc(alloc::Layout::from_size_align(1500, 8).unwrap());
+        assert_eq!(tlsf.stats().num_blocks_allocated, 1 + HOT_BATCH);
+        tlsf.dealloc(ptr, alloc::Layout::from_size_align(1500, 8).unwrap());
+    }
+
+    tlsf.flush();
+    let stats = tlsf.stats();
+    assert_eq!(stats.bytes_allocated, 0);
+    assert_eq!(stats.num_blocks_allocated, 0);
+    assert_eq!(stats.num_blocks_cached, 0);
+    assert!(stats.peak_bytes_allocated >= 8192 + 24);
+}
+
+#[test]
+fn stats_while_allocating() {
+    static TLSF: TheConcurrentTlsf = ConcurrentTlsf::new();
+    TLSF.lock().insert_free_block(new_pool(1 << 16));
+
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            thread::spawn(move || unsafe {
+                let layout = alloc::Layout::from_size_align(512 + i * 64, 8).unwrap();
+                for _ in 0..1000 {
+                    let ptr = TLSF.alloc(layout);
+                    assert!(!ptr.is_null());
+                    TLSF.dealloc(ptr, layout);
+                }
+            })
+        })
+        .collect();
+
+    // Every snapshot is consistent
+    while !threads.iter().all(|thread| thread.is_finished()) {
+        let stats = TLSF.stats();
+        assert!(stats.num_blocks_allocated <= 4);
+        assert!(stats.bytes_allocated <= stats.peak_bytes_allocated);
+        assert_eq!(stats.bytes_allocated == 0, stats.num_blocks_allocated == 0);
+    }
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    TLSF.flush();
+    assert_eq!(TLSF.stats().num_blocks_allocated, 0);
+}
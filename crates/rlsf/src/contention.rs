@@ -0,0 +1,107 @@
+//! Contention counters of the synchronized allocators
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Contention counters of a synchronized allocator, returned by methods such
+/// as [`SyncTlsf::contention_stats`].
+///
+/// The counters are maintained with relaxed atomic operations, so they are
+/// cheap to update but might be slightly out of date with respect to each
+/// other. Counters that don't apply to an allocator are always zero.
+///
+/// A high ratio of [`Self::lock_waits`] to [`Self::lock_acquisitions`]
+/// suggests that the allocator would benefit from sharding (e.g.,
+/// [`ShardedTlsf`]) or a cache in front of it.
+///
+/// [`SyncTlsf::contention_stats`]: crate::SyncTlsf::contention_stats
+/// [`ShardedTlsf`]: crate::ShardedTlsf
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct ContentionStats {
+    /// The number of times the lock was acquired.
+    pub lock_acquisitions: usize,
+    /// The number of lock acquisitions that couldn't be done without
+    /// waiting. Locks that don't implement
+    /// [`StaticGlobalTlsfLock::try_lock`] count every acquisition.
+    ///
+    /// [`StaticGlobalTlsfLock::try_lock`]: crate::StaticGlobalTlsfLock::try_lock
+    pub lock_waits: usize,
+    /// The number of memory blocks deallocated through remote free queues
+    /// ([`ShardedTlsf`](crate::ShardedTlsf)).
+    pub remote_frees: usize,
+    /// The number of allocations and deallocations that a cache in front of
+    /// the lock ([`ConcurrentTlsf`]'s free slots or a thread cache) couldn't
+    /// serve, and the number of times a thread cache was refilled.
+    ///
+    /// [`ConcurrentTlsf`]: crate::ConcurrentTlsf
+    pub cache_misses: usize,
+}
+
+impl ContentionStats {
+    /// Add `other`'s counters to `self`'s.
+    #[inline]
+    pub(crate) fn merge(self, other: Self) -> Self {
+        Self {
+            lock_acquisitions: self.lock_acquisitions + other.lock_acquisitions,
+            lock_waits: self.lock_waits + other.lock_waits,
+            remote_frees: self.remote_frees + other.remote_frees,
+            cache_misses: self.cache_misses + other.cache_misses,
+        }
+    }
+
+    /// Emit the counters through the [`metrics`] facade.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn report_metrics(&self) {
+        metrics::absolute_counter!("rlsf.lock_acquisitions", self.lock_acquisitions as u64);
+        metrics::absolute_counter!("rlsf.lock_waits", self.lock_waits as u64);
+        metrics::absolute_counter!("rlsf.remote_frees", self.remote_frees as u64);
+        metrics::absolute_counter!("rlsf.cache_misses", self.cache_misses as u64);
+    }
+}
+
+/// The atomic counterpart of [`ContentionStats`].
+#[derive(Debug)]
+pub(crate) struct ContentionCounters {
+    lock_acquisitions: AtomicUsize,
+    lock_waits: AtomicUsize,
+    remote_frees: AtomicUsize,
+    cache_misses: AtomicUsize,
+}
+
+impl ContentionCounters {
+    #[allow(clippy::declare_interior_mutable_const)]
+    pub(crate) const NEW: Self = Self {
+        lock_acquisitions: AtomicUsize::new(0),
+        lock_waits: AtomicUsize::new(0),
+        remote_frees: AtomicUsize::new(0),
+        cache_misses: AtomicUsize::new(0),
+    };
+
+    /// Record a lock acquisition. `waited` indicates whether it had to wait.
+    #[inline]
+    pub(crate) fn record_lock(&self, waited: bool) {
+        self.lock_acquisitions.fetch_add(1, Ordering::Relaxed);
+        if waited {
+            self.lock_waits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record_remote_free(&self) {
+        self.remote_frees.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn record_cache_miss(&self) {
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn load(&self) -> ContentionStats {
+        ContentionStats {
+            lock_acquisitions: self.lock_acquisitions.load(Ordering::Relaxed),
+            lock_waits: self.lock_waits.load(Ordering::Relaxed),
+            remote_frees: self.remote_frees.load(Ordering::Relaxed),
+            cache_misses: self.cache_misses.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Reporting heap corruption detected by the checked modes
+use core::{
+    fmt, mem,
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{IntegrityError, InvalidPointer};
+
+/// A function that handles a heap corruption instead of the default panic
+/// (or process abort). See [`set_corruption_handler`].
+pub type CorruptionHandler = fn(HeapError) -> !;
+
+/// The registered [`CorruptionHandler`] as `usize`, or zero if there's none.
+static HANDLER: AtomicUsize = AtomicUsize::new(0);
+
+/// Register a function to be called when a heap corruption is detected by
+/// any of the checks performed by this crate: double free detection,
+/// [`CheckedTlsf`](crate::CheckedTlsf), [`RedzoneTlsf`](crate::RedzoneTlsf),
+/// [`QuarantineTlsf`](crate::QuarantineTlsf), and the `poison`,
+/// `safe-linking`, `header-checksum`, `hardened`, and `mte` features. This replaces the default
+/// behavior, which is panicking (or, for `hardened` `GlobalTlsf`, printing a
+/// message and aborting the process).
+///
+/// The handler is shared by all allocators and replaces the previously
+/// registered one. It may be called while an allocator's lock is held, so it
+/// must not use the allocator that detected the corruption. This makes it
+/// suitable for, e.g., logging the error to flash memory and entering a safe
+/// state on a microcontroller.
+///
+/// # Examples
+///
+/// ```rust,should_panic
+/// use rlsf::{HeapError, Tlsf};
+/// use std::{alloc::Layout, mem::MaybeUninit};
+///
+/// fn on_corruption(error: HeapError) -> ! {
+///     // Firmware would log the error and reset here
+///     panic!("safe-state reset: {}", error);
+/// }
+///
+/// rlsf::set_corruption_handler(on_corruption);
+///
+/// let mut pool = [MaybeUninit::uninit(); 1024];
+/// let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+/// tlsf.insert_free_block(&mut pool);
+///
+/// let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+/// unsafe {
+///     tlsf.deallocate(ptr, 8);
+///     // Calls `on_corruption` with `HeapError::DoubleFree`
+///     tlsf.deallocate(ptr, 8);
+/// }
+/// ```
+pub fn set_corruption_handler(handler: CorruptionHandler) {
+    HANDLER.store(handler as usize, Ordering::Release);
+}
+
+/// Get the handler registered by [`set_corruption_handler`].
+#[inline]
+pub(crate) fn corruption_handler() -> Option<CorruptionHandler> {
+    match HANDLER.load(Ordering::Acquire) {
+        0 => None,
+        // Safety: Non-zero values are stored only by
+        //         `set_corruption_handler`
+        handler => Some(unsafe { mem::transmute::<usize, CorruptionHandler>(handler) }),
+    }
+}
+
+/// Report a heap corruption to the registered handler, or panic with the
+/// error's description if there's none.
+#[cold]
+#[inline(never)]
+pub(crate) fn report_corruption(error: HeapError) -> ! {
+    if let Some(handler) = corruption_handler() {
+        handler(error);
+    }
+    panic!("{}", error)
+}
+
+/// A heap corruption (or misuse that is indistinguishable from one) detected
+/// by one of the checks. Passed to the [`CorruptionHandler`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum HeapError {
+    /// The memory block at `ptr` is being deallocated or reallocated but
+    /// isn't allocated.
+    DoubleFree { ptr: NonNull<u8> },
+    /// The memory block at `ptr` is being deallocated or reallocated with
+    /// the alignment `align`, which differs from the one it was allocated
+    /// with (debug builds only).
+    AlignMismatch { ptr: NonNull<u8>, align: usize },
+    /// The free memory at `at` was modified (`poison` feature).
+    UseAfterFree { at: NonNull<u8> },
+    /// The free list link at `location` is invalid (`safe-linking` feature).
+    CorruptedFreeLink { location: NonNull<u8> },
+    /// The checksum of the header of the memory block at `block` doesn't
+    /// match (`header-checksum` feature).
+    CorruptedBlockHeader { block: NonNull<u8> },
+    /// The sentinel block at `block` guarding an end of a memory pool was
+    /// modified (`pool-guards` feature).
+    PoolGuardViolation { block: NonNull<u8> },
+    /// [`CheckedTlsf`](crate::CheckedTlsf)'s method `method` was given a
+    /// pointer `ptr` that doesn't denote an allocation.
+    InvalidPointer {
+        method: &'static str,
+        ptr: NonNull<u8>,
+        reason: InvalidPointer,
+    },
+    /// A redzone of the `size`-byte allocation at `ptr` was modified at `at`
+    /// ([`RedzoneTlsf`](crate::RedzoneTlsf)). `after` indicates whether it's
+    /// the redzone after the allocation. `size` is `None` if the recorded
+    /// size itself is corrupted.
+    RedzoneViolation {
+        ptr: NonNull<u8>,
+        size: Option<usize>,
+        after: bool,
+        at: NonNull<u8>,
+    },
+    /// The `size`-byte memory block at `ptr` was modified at `at` while in
+    /// quarantine ([`QuarantineTlsf`](crate::QuarantineTlsf)).
+    QuarantineViolation {
+        ptr: NonNull<u8>,
+        size: usize,
+        at: NonNull<u8>,
+    },
+    /// A self check of [`CheckedTlsf`](crate::CheckedTlsf) or the check
+    /// enabled by [`FlexTlsf::set_verify_on_drop`](crate::FlexTlsf::set_verify_on_drop)
+    /// found an inconsistency.
+    IntegrityViolation { error: IntegrityError },
+    /// The tag of `ptr` doesn't match the memory (`MteTlsf`).
+    TagMismatch { ptr: NonNull<u8> },
+    /// The allocation at `ptr` failed the validation of `hardened`
+    /// `GlobalTlsf` for the reason `reason`.
+    BadAllocation {
+        ptr: NonNull<u8>,
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for HeapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::DoubleFree { ptr } => write!(
+                f,
+                "double free detected: the memory block at {:p} is not allocated",
+                ptr
+            ),
+            Self::AlignMismatch { ptr, align } => write!(
+                f,
+                "alignment mismatch detected: the memory block at {:p} was not allocated with \
+                alignment {}",
+                ptr, align
+            ),
+            Self::UseAfterFree { at } => write!(
+                f,
+                "use after free detected: free memory at {:p} was modified",
+                at
+            ),
+            Self::CorruptedFreeLink { location } => write!(
+                f,
+                "heap corruption detected: the free list link at {:p} is invalid",
+                location
+            ),
+            Self::CorruptedBlockHeader { block } => write!(
+                f,
+                "heap corruption detected: the checksum of the header of the memory block at \
+                {:p} doesn't match",
+                block
+            ),
+            Self::PoolGuardViolation { block } => write!(
+                f,
+                "heap corruption detected: the guard block at {:p} at an end of a memory pool \
+                was modified",
+                block
+            ),
+            Self::InvalidPointer {
+                method,
+                ptr,
+                reason,
+            } => {
+                write!(f, "invalid pointer passed to {}: ", method)?;
+                match reason {
+                    InvalidPointer::OutsidePools => {
+                        write!(f, "{:p} is not in any memory pool", ptr)
+                    }
+                    InvalidPointer::BadBlockHeader { block } => write!(
+                        f,
+                        "the header of the memory block at {:p} preceding {:p} is corrupted",
+                        block, ptr
+                    ),
+                    InvalidPointer::FreeBlock { block } => write!(
+                        f,
+                        "{:p} is in the free memory block at {:p} (double free?)",
+                        ptr, block
+                    ),
+                    InvalidPointer::InsideBlock { block } => write!(
+                        f,
+                        "{:p} is not the starting address of the allocation in the memory \
+                        block at {:p}",
+                        ptr, block
+                    ),
+                }
+            }
+            Self::RedzoneViolation {
+                ptr,
+                size: Some(size),
+                after,
+                at,
+            } => write!(
+                f,
+                "heap buffer overflow detected: the redzone {} the {}-byte allocation at {:p} \
+                was modified at {:p}",
+                if after { "after" } else { "before" },
+                size,
+                ptr,
+                at
+            ),
+            Self::RedzoneViolation {
+                ptr,
+                size: None,
+                at,
+                ..
+            } => write!(
+                f,
+                "heap buffer overflow detected: the redzone before the allocation at {:p} was \
+                modified at {:p} (the recorded size is invalid)",
+                ptr, at
+            ),
+            Self::QuarantineViolation { ptr, size, at } => write!(
+                f,
+                "use after free detected: the memory block at {:p} ({} bytes) was modified at \
+                {:p} while in quarantine",
+                ptr, size, at
+            ),
+            Self::IntegrityViolation { error } => {
+                write!(f, "heap corruption detected: {}", error)
+            }
+            Self::TagMismatch { ptr } => write!(
+                f,
+                "invalid pointer {:p} passed to `MteTlsf`: its tag doesn't match the memory \
+                (double free or a pointer not returned by this allocator?)",
+                ptr
+            ),
+            Self::BadAllocation { ptr, reason } => write!(
+                f,
+                "heap corruption detected: {} (allocation at {:p})",
+                reason, ptr
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for HeapError {}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,43 @@
+use std::{
+    alloc::Layout,
+    mem::MaybeUninit,
+    panic::{catch_unwind, AssertUnwindSafe},
+    prelude::v1::*,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::*;
+use crate::Tlsf;
+
+static NUM_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Count the calls and otherwise behave like the default, so that the other
+/// tests running concurrently aren't affected.
+fn counting_handler(error: HeapError) -> ! {
+    NUM_CALLS.fetch_add(1, Ordering::Relaxed);
+    if let HeapError::BadAllocation { .. } = error {
+        std::process::abort();
+    }
+    panic!("{}", error)
+}
+
+#[test]
+fn handler_is_called() {
+    set_corruption_handler(counting_handler);
+
+    let mut pool = [MaybeUninit::uninit(); 1024];
+    let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+    tlsf.insert_free_block(&mut pool);
+
+    let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+    unsafe { tlsf.deallocate(ptr, 8) };
+
+    let num_calls = NUM_CALLS.load(Ordering::Relaxed);
+    let payload =
+        catch_unwind(AssertUnwindSafe(|| unsafe { tlsf.deallocate(ptr, 8) })).unwrap_err();
+    assert_eq!(NUM_CALLS.load(Ordering::Relaxed), num_calls + 1);
+    assert_eq!(
+        payload.downcast_ref::<String>(),
+        Some(&HeapError::DoubleFree { ptr }.to_string())
+    );
+}
@@ -0,0 +1,211 @@
+//! `CrossCoreTlsf`: a [`Tlsf`] owned by one core, accepting deallocations
+//! from other cores
+use core::{alloc, fmt, ptr::NonNull};
+
+use crate::{int::BinInteger, Tlsf};
+
+/// A transport through which other cores send the memory blocks they
+/// deallocate to the core owning a [`CrossCoreTlsf`], such as an inter-core
+/// hardware FIFO (e.g., RP2040's SIO FIFOs) or a ring buffer in shared
+/// memory.
+///
+/// Each message is a single pointer, which fits in a 32-bit FIFO entry on
+/// 32-bit targets.
+///
+/// # Safety
+///
+/// [`Self::receive`] must only return pointers previously passed to a
+/// successful call to [`Self::try_send`], and each of them only once.
+pub unsafe trait FreeMailbox {
+    /// Attempt to send a deallocated memory block to the owning core. Returns
+    /// `Err(ptr)` if the mailbox is full.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a memory block previously allocated via the
+    /// [`CrossCoreTlsf`] receiving from `self`, and it must not be used
+    /// afterwards.
+    unsafe fn try_send(&self, ptr: NonNull<u8>) -> Result<(), NonNull<u8>>;
+
+    /// Receive a memory block sent by [`Self::try_send`]. Only called by the
+    /// owning core.
+    fn receive(&self) -> Option<NonNull<u8>>;
+
+    /// Send a deallocated memory block to the owning core, spinning while
+    /// the mailbox is full.
+    ///
+    /// This doesn't return until the owning core drains the mailbox (e.g.,
+    /// by [`CrossCoreTlsf::drain`]).
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::try_send`].
+    unsafe fn send(&self, mut ptr: NonNull<u8>) {
+        // Safety: Upheld by the caller
+        while let Err(returned) = self.try_send(ptr) {
+            ptr = returned;
+            core::hint::spin_loop();
+        }
+    }
+}
+
+// Safety: Forwarded to `T`
+unsafe impl<T: FreeMailbox + ?Sized> FreeMailbox for &T {
+    #[inline]
+    unsafe fn try_send(&self, ptr: NonNull<u8>) -> Result<(), NonNull<u8>> {
+        (**self).try_send(ptr)
+    }
+
+    #[inline]
+    fn receive(&self) -> Option<NonNull<u8>> {
+        (**self).receive()
+    }
+
+    #[inline]
+    unsafe fn send(&self, ptr: NonNull<u8>) {
+        (**self).send(ptr)
+    }
+}
+
+/// [`Tlsf`] owned by a single core, to which other cores return memory
+/// blocks through a [`FreeMailbox`].
+///
+/// This lets the cores of a heterogeneous or asymmetric multicore
+/// microcontroller (e.g., RP2040 or ESP32) share a single heap without a
+/// lock, which might be unavailable (e.g., on `thumbv6m`, which lacks atomic
+/// compare-and-swap instructions) or too costly for an inter-core
+/// interconnect. Only the owning core allocates memory, and it can pass the
+/// allocated memory blocks to the other cores, which deallocate them by
+/// [`FreeMailbox::send`] or [`FreeMailbox::try_send`].
+///
+/// The memory blocks in the mailbox are reclaimed by [`Self::drain`], which
+/// [`Self::allocate`] calls before allocating. If the owning core may not
+/// allocate for a while, it should call `drain` periodically (e.g., from the
+/// interrupt handler signaling a non-empty FIFO) so that the other cores
+/// don't wait for a full mailbox.
+///
+/// # Examples
+///
+/// ```rust
+/// use rlsf::{CrossCoreTlsf, FreeMailbox};
+/// use std::{alloc::Layout, cell::RefCell, collections::VecDeque, mem::MaybeUninit, ptr::NonNull};
+///
+/// /// Stands in for an inter-core FIFO
+/// #[derive(Default)]
+/// struct Fifo(RefCell<VecDeque<NonNull<u8>>>);
+///
+/// unsafe impl FreeMailbox for Fifo {
+///     unsafe fn try_send(&self, ptr: NonNull<u8>) -> Result<(), NonNull<u8>> {
+///         let mut queue = self.0.borrow_mut();
+///         if queue.len() == 8 {
+///             return Err(ptr);
+///         }
+///         queue.push_back(ptr);
+///         Ok(())
+///     }
+///
+///     fn receive(&self) -> Option<NonNull<u8>> {
+///         self.0.borrow_mut().pop_front()
+///     }
+/// }
+///
+/// let fifo = Fifo::default();
+/// let mut tlsf: CrossCoreTlsf<'_, &Fifo, u16, u16, 12, 16> = CrossCoreTlsf::new(&fifo);
+/// let mut pool = [MaybeUninit::uninit(); 4096];
+/// tlsf.tlsf().insert_free_block(&mut pool);
+///
+/// // On the owning core
+/// let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+///
+/// // On another core
+/// unsafe { fifo.send(ptr) };
+///
+/// // On the owning core
+/// assert_eq!(tlsf.drain(), 1);
+/// ```
+pub struct CrossCoreTlsf<'pool, Mailbox, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize>
+{
+    tlsf: Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>,
+    mailbox: Mailbox,
+}
+
+impl<'pool, Mailbox, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize>
+    CrossCoreTlsf<'pool, Mailbox, FLBitmap, SLBitmap, FLLEN, SLLEN>
+where
+    Mailbox: FreeMailbox,
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+{
+    /// Construct an empty instance of `Self`, receiving deallocated memory
+    /// blocks from `mailbox`.
+    #[inline]
+    pub const fn new(mailbox: Mailbox) -> Self {
+        Self {
+            tlsf: Tlsf::new(),
+            mailbox,
+        }
+    }
+
+    /// Get the underlying [`Tlsf`], e.g., to supply memory pools.
+    #[inline]
+    pub fn tlsf(&mut self) -> &mut Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN> {
+        &mut self.tlsf
+    }
+
+    /// Get the mailbox.
+    #[inline]
+    pub fn mailbox(&self) -> &Mailbox {
+        &self.mailbox
+    }
+
+    /// Deallocate the memory blocks sent by the other cores. Returns the
+    /// number of deallocated memory blocks.
+    pub fn drain(&mut self) -> usize {
+        let mut count = 0;
+        while let Some(ptr) = self.mailbox.receive() {
+            // Safety: `ptr` was sent by `FreeMailbox::try_send`, whose caller
+            //         guarantees it to be a memory block allocated via `self`
+            unsafe { self.tlsf.deallocate_unknown_align(ptr) };
+            count += 1;
+        }
+        count
+    }
+
+    /// Attempt to allocate a block of memory after reclaiming the memory
+    /// blocks sent by the other cores.
+    ///
+    /// # Time Complexity
+    ///
+    /// Unlike [`Tlsf::allocate`], this method's execution time grows
+    /// linearly with the number of memory blocks in the mailbox, which is
+    /// bounded by the mailbox's capacity.
+    #[inline]
+    pub fn allocate(&mut self, layout: alloc::Layout) -> Option<NonNull<u8>> {
+        self.drain();
+        self.tlsf.allocate(layout)
+    }
+
+    /// Deallocate a previously allocated memory block on the owning core.
+    ///
+    /// # Safety
+    ///
+    /// See [`Tlsf::deallocate`].
+    #[inline]
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, align: usize) {
+        // Safety: Upheld by the caller
+        self.tlsf.deallocate(ptr, align);
+    }
+}
+
+impl<Mailbox: fmt::Debug, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize> fmt::Debug
+    for CrossCoreTlsf<'_, Mailbox, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CrossCoreTlsf")
+            .field("mailbox", &self.mailbox)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests;
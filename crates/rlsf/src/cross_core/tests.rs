@@ -0,0 +1,105 @@
+use core::mem::MaybeUninit;
+use std::{collections::VecDeque, prelude::v1::*, sync::Mutex, thread, vec};
+
+use super::*;
+
+type TheCrossCoreTlsf = CrossCoreTlsf<'static, &'static BoundedMailbox, u16, u16, 12, 16>;
+
+fn new_pool(size: usize) -> &'static mut [MaybeUninit<u8>] {
+    Box::leak(vec![MaybeUninit::uninit(); size].into_boxed_slice())
+}
+
+/// A mailbox holding up to `CAPACITY` pointers
+#[derive(Debug, Default)]
+struct BoundedMailbox(Mutex<VecDeque<usize>>);
+
+impl BoundedMailbox {
+    const CAPACITY: usize = 4;
+
+    fn new() -> &'static Self {
+        Box::leak(Box::default())
+    }
+
+    fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+}
+
+unsafe impl FreeMailbox for BoundedMailbox {
+    unsafe fn try_send(&self, ptr: NonNull<u8>) -> Result<(), NonNull<u8>> {
+        let mut queue = self.0.lock().unwrap();
+        if queue.len() == Self::CAPACITY {
+            return Err(ptr);
+        }
+        queue.push_back(ptr.as_ptr() as usize);
+        Ok(())
+    }
+
+    fn receive(&self) -> Option<NonNull<u8>> {
+        let addr = self.0.lock().unwrap().pop_front()?;
+        NonNull::new(addr as *mut u8)
+    }
+}
+
+#[test]
+fn drain() {
+    let mailbox = BoundedMailbox::new();
+    let mut tlsf = TheCrossCoreTlsf::new(mailbox);
+    tlsf.tlsf().insert_free_block(new_pool(4096));
+
+    let layout = alloc::Layout::from_size_align(32, 16).unwrap();
+    let ptrs: Vec<_> = std::iter::from_fn(|| tlsf.tlsf().allocate(layout)).collect();
+    assert!(ptrs.len() > BoundedMailbox::CAPACITY);
+
+    for &ptr in &ptrs[..BoundedMailbox::CAPACITY] {
+        unsafe { mailbox.try_send(ptr) }.unwrap();
+    }
+    let excess = ptrs[BoundedMailbox::CAPACITY];
+    assert_eq!(unsafe { mailbox.try_send(excess) }, Err(excess));
+
+    // `allocate` reclaims the memory blocks in the mailbox
+    assert!(tlsf.allocate(layout).is_some());
+    assert_eq!(mailbox.len(), 0);
+    assert_eq!(tlsf.drain(), 0);
+
+    unsafe { mailbox.try_send(excess) }.unwrap();
+    assert_eq!(tlsf.drain(), 1);
+}
+
+#[test]
+fn remote_core() {
+    let mailbox = BoundedMailbox::new();
+    let mut tlsf = TheCrossCoreTlsf::new(mailbox);
+    tlsf.tlsf().insert_free_block(new_pool(1 << 14));
+
+    // The other core deallocates what the owning core allocates
+    let (sender, receiver) = std::sync::mpsc::sync_channel::<usize>(16);
+    let remote = thread::spawn(move || {
+        for addr in receiver {
+            let ptr = NonNull::new(addr as *mut u8).unwrap();
+            assert_eq!(unsafe { ptr.as_ptr().cast::<usize>().read() }, addr);
+            unsafe { mailbox.send(ptr) };
+        }
+    });
+
+    let layout = alloc::Layout::new::<usize>();
+    for _ in 0..1000 {
+        let ptr = tlsf.allocate(layout).unwrap();
+        unsafe { ptr.as_ptr().cast::<usize>().write(ptr.as_ptr() as usize) };
+        // Don't let the remote core wait for a full mailbox while
+        // `sender.send` waits for the remote core
+        while sender.try_send(ptr.as_ptr() as usize).is_err() {
+            tlsf.drain();
+        }
+    }
+    drop(sender);
+    while !remote.is_finished() {
+        tlsf.drain();
+    }
+    remote.join().unwrap();
+    tlsf.drain();
+
+    // All memory blocks were returned to the pool
+    let ptr = tlsf.allocate(alloc::Layout::from_size_align(8192, 1).unwrap());
+    assert!(ptr.is_some());
+}
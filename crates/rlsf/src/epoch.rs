@@ -0,0 +1,536 @@
+//! `EpochTlsf`: [`SyncTlsf`] with epoch-based deferred deallocation
+use core::{
+    alloc,
+    cell::Cell,
+    fmt, mem,
+    ptr::{self, NonNull},
+};
+
+use crate::{
+    int::BinInteger,
+    primitives::atomic::{self, AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+    DefaultLock, StaticGlobalTlsfLock, SyncTlsf, SyncTlsfGuard, TryAllocError,
+};
+
+/// The number of deferred deallocations between attempts to advance the
+/// epoch
+const ADVANCE_INTERVAL: usize = 64;
+
+/// The number of bags of deferred deallocations. A memory block deferred in
+/// epoch `e` is deallocated when the epoch advances to `e + NUM_BAGS`.
+const NUM_BAGS: usize = 3;
+
+/// The number of memory blocks recorded by a [`Bag`]
+const BAG_CAPACITY: usize = 8;
+
+/// A batch of memory blocks awaiting deallocation. Bags are allocated from
+/// the underlying [`Tlsf`](crate::Tlsf) separately from the memory blocks they
+/// record, which pinned participants may still be reading.
+struct Bag {
+    next: *mut Bag,
+    len: usize,
+    /// The pointers and alignments of the memory blocks
+    entries: [(*mut u8, usize); BAG_CAPACITY],
+}
+
+/// A participant slot of [`EpochTlsf`]
+struct Participant {
+    claimed: AtomicBool,
+    /// `(epoch << 1) | 1` while pinned in `epoch`, `0` otherwise
+    state: AtomicUsize,
+}
+
+impl Participant {
+    const_fn! {
+        const fn new() -> Self {
+            Self {
+                claimed: AtomicBool::new(false),
+                state: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    #[cfg(not(loom))]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const NEW: Self = Self::new();
+}
+
+/// [`SyncTlsf`] whose deallocations can be deferred until no thread can
+/// access the memory blocks anymore, using epoch-based reclamation (EBR).
+///
+/// This makes it possible to free the nodes of a lock-free data structure
+/// directly into an rlsf heap: a thread removing a node can't tell whether
+/// other threads still hold a reference to it, but it can call
+/// [`EpochGuard::defer_deallocate`] to deallocate it once they're done.
+///
+/// Up to `MAX_THREADS` threads participate at a time by calling
+/// [`Self::register`]. A participant *pins* itself (by [`EpochHandle::pin`])
+/// while it accesses the shared data structure. The memory blocks deferred
+/// through an [`EpochGuard`] are recorded in bags of 8, which are allocated
+/// from `self` and published when they're full or when the guard is
+/// dropped. A bag published while the global epoch is `e` is deallocated
+/// along with the memory blocks it records when the global epoch advances to
+/// `e + 3`. The global epoch only advances when every pinned participant
+/// has observed the current one, so a participant that stays pinned
+/// indefinitely prevents reclamation. The epoch is advanced automatically
+/// every 64 deferred deallocations and when an allocation fails, and can be
+/// advanced explicitly by [`Self::try_advance`].
+///
+/// Memory pools are supplied through [`Self::lock`].
+///
+/// # Examples
+///
+/// ```rust
+/// use rlsf::EpochTlsf;
+/// use std::{alloc::Layout, mem::MaybeUninit};
+///
+/// static TLSF: EpochTlsf<'static, 8, u16, u16, 12, 16> = EpochTlsf::new();
+///
+/// static mut POOL: [MaybeUninit<u8>; 8192] = [MaybeUninit::uninit(); 8192];
+/// TLSF.lock().insert_free_block(unsafe { &mut POOL });
+///
+/// let mut handle = TLSF.register().unwrap();
+/// let layout = Layout::new::<u64>();
+/// let node = TLSF.allocate(layout).unwrap();
+/// {
+///     let guard = handle.pin();
+///     // ... unlink `node` from a lock-free data structure ...
+///     unsafe { guard.defer_deallocate(node, layout.align()) }.unwrap();
+/// }
+///
+/// // Three epochs later, `node` is deallocated
+/// for _ in 0..3 {
+///     assert!(TLSF.try_advance());
+/// }
+/// ```
+pub struct EpochTlsf<
+    'pool,
+    const MAX_THREADS: usize,
+    FLBitmap,
+    SLBitmap,
+    const FLLEN: usize,
+    const SLLEN: usize,
+    Lock = DefaultLock,
+> {
+    inner: SyncTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>,
+    /// The global epoch. Only modified while `inner` is locked.
+    epoch: AtomicUsize,
+    /// `bags[e % NUM_BAGS]` holds the bags published in epoch `e`
+    bags: [AtomicPtr<Bag>; NUM_BAGS],
+    participants: [Participant; MAX_THREADS],
+    /// The number of deferred deallocations, used to decide when to advance
+    /// the epoch
+    num_deferred: AtomicUsize,
+}
+
+impl<
+        'pool,
+        const MAX_THREADS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > EpochTlsf<'pool, MAX_THREADS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[cfg(not(loom))]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_BAG: AtomicPtr<Bag> = AtomicPtr::new(ptr::null_mut());
+
+    const_fn! {
+        /// Construct an empty instance of `Self`.
+        #[inline]
+        pub const fn new() -> Self {
+            Self {
+                inner: SyncTlsf::new(),
+                epoch: AtomicUsize::new(0),
+                bags: array_repeat!(Self::EMPTY_BAG, AtomicPtr::new(ptr::null_mut()); NUM_BAGS),
+                participants: array_repeat!(Participant::NEW, Participant::new(); MAX_THREADS),
+                num_deferred: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    /// Acquire the lock of the underlying [`Tlsf`](crate::Tlsf), e.g., to
+    /// supply memory pools. See [`SyncTlsf::lock`].
+    ///
+    /// Memory blocks with pending deferred deallocations must not be
+    /// deallocated through the returned guard.
+    #[inline]
+    pub fn lock(&self) -> SyncTlsfGuard<'_, 'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock> {
+        self.inner.lock()
+    }
+
+    /// Claim a participant slot. Returns `None` if all `MAX_THREADS` slots
+    /// are in use. The slot is released when the returned handle is
+    /// dropped.
+    pub fn register(
+        &self,
+    ) -> Option<EpochHandle<'_, 'pool, MAX_THREADS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>> {
+        let participant = self.participants.iter().find(|participant| {
+            participant
+                .claimed
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        })?;
+        Some(EpochHandle {
+            owner: self,
+            participant,
+        })
+    }
+
+    /// Allocate memory. If the underlying [`Tlsf`](crate::Tlsf) is
+    /// exhausted, this attempts to advance the epoch to reclaim memory and
+    /// retries.
+    pub fn allocate(&self, layout: alloc::Layout) -> Option<NonNull<u8>> {
+        let ptr = self.inner.lock().allocate(layout);
+        ptr.or_else(|| {
+            self.try_advance();
+            self.inner.lock().allocate(layout)
+        })
+    }
+
+    /// Deallocate a memory block immediately. Use this only if no other
+    /// thread can be accessing it; otherwise, use
+    /// [`EpochGuard::defer_deallocate`].
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `self`.
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `align`.
+    ///
+    /// [`Layout::align`]: core::alloc::Layout::align
+    #[inline]
+    pub unsafe fn deallocate(&self, ptr: NonNull<u8>, align: usize) {
+        // Safety: Upheld by the caller
+        self.inner.lock().deallocate(ptr, align);
+    }
+
+    /// Advance the global epoch if every pinned participant has observed the
+    /// current one, and deallocate the memory blocks whose grace period has
+    /// elapsed as a result. Returns `true` if the epoch was advanced.
+    pub fn try_advance(&self) -> bool {
+        let mut tlsf = self.inner.lock();
+
+        let epoch = self.epoch.load(Ordering::Relaxed);
+        atomic::fence(Ordering::SeqCst);
+        let pinned_state = (epoch << 1) | 1;
+        if self.participants.iter().any(|participant| {
+            let state = participant.state.load(Ordering::Relaxed);
+            state != 0 && state != pinned_state
+        }) {
+            return false;
+        }
+        atomic::fence(Ordering::Acquire);
+
+        // Take the bag before publishing the new epoch so that the memory
+        // blocks deferred in the new epoch aren't taken
+        let new_epoch = epoch.wrapping_add(1);
+        let mut node = self.bags[new_epoch % NUM_BAGS].swap(ptr::null_mut(), Ordering::Acquire);
+        self.epoch.store(new_epoch, Ordering::SeqCst);
+
+        while let Some(bag) = NonNull::new(node) {
+            // Safety: `bag` was published by `EpochGuard::publish`
+            let Bag { next, len, entries } = unsafe { bag.as_ptr().read() };
+            for &(ptr, align) in &entries[..len] {
+                // Safety: `ptr` denotes a previous allocation with alignment
+                //         `align`, and no thread can access it anymore
+                unsafe { tlsf.deallocate(NonNull::new_unchecked(ptr), align) };
+            }
+            // Safety: `bag` was allocated by `EpochGuard::defer_deallocate`
+            unsafe { tlsf.deallocate(bag.cast(), mem::align_of::<Bag>()) };
+            node = next;
+        }
+        true
+    }
+}
+
+impl<
+        'pool,
+        const MAX_THREADS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > Default for EpochTlsf<'pool, MAX_THREADS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<
+        const MAX_THREADS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > fmt::Debug for EpochTlsf<'_, MAX_THREADS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EpochTlsf")
+            .field("epoch", &self.epoch)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A participant of [`EpochTlsf`], returned by [`EpochTlsf::register`].
+pub struct EpochHandle<
+    'a,
+    'pool,
+    const MAX_THREADS: usize,
+    FLBitmap,
+    SLBitmap,
+    const FLLEN: usize,
+    const SLLEN: usize,
+    Lock,
+> {
+    owner: &'a EpochTlsf<'pool, MAX_THREADS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>,
+    participant: &'a Participant,
+}
+
+impl<
+        'a,
+        'pool,
+        const MAX_THREADS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > EpochHandle<'a, 'pool, MAX_THREADS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    /// Pin the participant in the current epoch. The memory blocks reachable
+    /// while the returned guard is alive won't be deallocated until it's
+    /// dropped.
+    #[inline]
+    pub fn pin(
+        &mut self,
+    ) -> EpochGuard<'_, 'pool, MAX_THREADS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock> {
+        let epoch = self.owner.epoch.load(Ordering::Relaxed);
+        self.participant
+            .state
+            .store((epoch << 1) | 1, Ordering::Relaxed);
+        atomic::fence(Ordering::SeqCst);
+        EpochGuard {
+            owner: self.owner,
+            participant: self.participant,
+            bag: Cell::new(None),
+        }
+    }
+}
+
+impl<
+        const MAX_THREADS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > Drop for EpochHandle<'_, '_, MAX_THREADS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+{
+    #[inline]
+    fn drop(&mut self) {
+        self.participant.claimed.store(false, Ordering::Release);
+    }
+}
+
+impl<
+        const MAX_THREADS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > fmt::Debug for EpochHandle<'_, '_, MAX_THREADS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EpochHandle").finish_non_exhaustive()
+    }
+}
+
+/// A pinned participant of [`EpochTlsf`], returned by [`EpochHandle::pin`].
+/// The participant is unpinned when this is dropped.
+pub struct EpochGuard<
+    'a,
+    'pool,
+    const MAX_THREADS: usize,
+    FLBitmap,
+    SLBitmap,
+    const FLLEN: usize,
+    const SLLEN: usize,
+    Lock,
+> {
+    owner: &'a EpochTlsf<'pool, MAX_THREADS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>,
+    participant: &'a Participant,
+    /// The bag being filled by [`Self::defer_deallocate`], which is published
+    /// when it's full or when `self` is dropped
+    bag: Cell<Option<NonNull<Bag>>>,
+}
+
+impl<
+        'a,
+        'pool,
+        const MAX_THREADS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > EpochGuard<'a, 'pool, MAX_THREADS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    /// Deallocate a memory block once no participant pinned at this point
+    /// remains pinned.
+    ///
+    /// The memory block must already be unreachable by new accesses (e.g.,
+    /// unlinked from the data structure); only accesses by currently pinned
+    /// participants are guarded against. Its contents are left intact until
+    /// it's deallocated.
+    ///
+    /// The memory block is recorded in a bag allocated from the
+    /// [`EpochTlsf`] every 8 calls, which takes its lock. Returns
+    /// [`TryAllocError::Exhausted`] if the bag can't be allocated even after
+    /// attempting to advance the epoch, in which case the memory block is
+    /// left allocated.
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via the
+    ///    [`EpochTlsf`].
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `align`.
+    ///  - The memory block must not be deallocated again.
+    ///
+    /// [`Layout::align`]: core::alloc::Layout::align
+    pub unsafe fn defer_deallocate(
+        &self,
+        ptr: NonNull<u8>,
+        align: usize,
+    ) -> Result<(), TryAllocError> {
+        let owner = self.owner;
+
+        let bag = match self.bag.get() {
+            Some(bag) if (*bag.as_ptr()).len < BAG_CAPACITY => bag,
+            full_bag => {
+                let bag = owner
+                    .allocate(alloc::Layout::new::<Bag>())
+                    .ok_or(TryAllocError::Exhausted)?
+                    .cast::<Bag>();
+                bag.as_ptr().write(Bag {
+                    next: ptr::null_mut(),
+                    len: 0,
+                    entries: [(ptr::null_mut(), 0); BAG_CAPACITY],
+                });
+                if let Some(full_bag) = full_bag {
+                    self.publish(full_bag);
+                }
+                self.bag.set(Some(bag));
+                bag
+            }
+        };
+
+        // Safety: `bag` is owned by `self` until it's published
+        let bag = &mut *bag.as_ptr();
+        bag.entries[bag.len] = (ptr.as_ptr(), align);
+        bag.len += 1;
+
+        if owner.num_deferred.fetch_add(1, Ordering::Relaxed) % ADVANCE_INTERVAL
+            == ADVANCE_INTERVAL - 1
+        {
+            owner.try_advance();
+        }
+
+        Ok(())
+    }
+}
+
+impl<
+        const MAX_THREADS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > EpochGuard<'_, '_, MAX_THREADS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+{
+    /// Hand `bag` over to [`EpochTlsf::try_advance`]. This must be done while
+    /// `self` is pinned.
+    ///
+    /// # Safety
+    ///
+    /// `bag` must be a bag filled by `self` and not published yet.
+    unsafe fn publish(&self, bag: NonNull<Bag>) {
+        let owner = self.owner;
+        // While `self` is pinned, the global epoch can't reach
+        // `epoch + NUM_BAGS`, which would take this list
+        let epoch = owner.epoch.load(Ordering::SeqCst);
+        let list = &owner.bags[epoch % NUM_BAGS];
+
+        let bag = bag.as_ptr();
+        let mut next = list.load(Ordering::Relaxed);
+        loop {
+            (*bag).next = next;
+            // The consumer takes the whole list at once, so ABA can't happen
+            match list.compare_exchange_weak(next, bag, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(new_next) => next = new_next,
+            }
+        }
+    }
+}
+
+impl<
+        const MAX_THREADS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > Drop for EpochGuard<'_, '_, MAX_THREADS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+{
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(bag) = self.bag.take() {
+            // Safety: `bag` was filled by `self` and hasn't been published
+            unsafe { self.publish(bag) };
+        }
+        self.participant.state.store(0, Ordering::Release);
+    }
+}
+
+impl<
+        const MAX_THREADS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > fmt::Debug for EpochGuard<'_, '_, MAX_THREADS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EpochGuard").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests;
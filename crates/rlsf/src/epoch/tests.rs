@@ -0,0 +1,219 @@
+use core::mem::MaybeUninit;
+use std::{prelude::v1::*, sync::Arc, thread, vec};
+
+use super::*;
+
+type TheEpochTlsf = EpochTlsf<'static, 4, u16, u16, 12, 16>;
+
+fn new_pool(size: usize) -> &'static mut [MaybeUninit<u8>] {
+    Box::leak(vec![MaybeUninit::uninit(); size].into_boxed_slice())
+}
+
+#[test]
+fn register() {
+    let tlsf = TheEpochTlsf::new();
+    let handles: Vec<_> = (0..4).map(|_| tlsf.register().unwrap()).collect();
+    assert!(tlsf.register().is_none());
+    drop(handles);
+    assert!(tlsf.register().is_some());
+}
+
+/// Allocate memory blocks with `layout` until the pool is exhausted,
+/// bypassing the reclamation on failure
+fn exhaust(tlsf: &TheEpochTlsf, layout: alloc::Layout) -> Vec<NonNull<u8>> {
+    std::iter::from_fn(|| tlsf.inner.lock().allocate(layout)).collect()
+}
+
+/// Allocate memory blocks with `layout` until the pool is exhausted except
+/// for the room for a `Bag`
+fn exhaust_but_bag(tlsf: &TheEpochTlsf, layout: alloc::Layout) -> Vec<NonNull<u8>> {
+    let bag = tlsf
+        .inner
+        .lock()
+        .allocate(alloc::Layout::new::<Bag>())
+        .unwrap();
+    let ptrs = exhaust(tlsf, layout);
+    unsafe { tlsf.deallocate(bag, mem::align_of::<Bag>()) };
+    ptrs
+}
+
+#[test]
+fn grace_period() {
+    let tlsf = TheEpochTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(4096));
+    let layout = alloc::Layout::from_size_align(256, 8).unwrap();
+    let ptrs = exhaust_but_bag(&tlsf, layout);
+    assert!(!ptrs.is_empty());
+
+    let mut handle = tlsf.register().unwrap();
+    let guard = handle.pin();
+    unsafe { guard.defer_deallocate(ptrs[0], layout.align()) }.unwrap();
+    drop(guard);
+
+    // Deallocated when the epoch advances by three
+    for _ in 0..2 {
+        assert!(tlsf.try_advance());
+        assert!(tlsf.inner.lock().allocate(layout).is_none());
+    }
+    assert!(tlsf.try_advance());
+    assert!(tlsf.inner.lock().allocate(layout).is_some());
+}
+
+#[test]
+fn bag_exhausted() {
+    let tlsf = TheEpochTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(4096));
+    let layout = alloc::Layout::from_size_align(256, 8).unwrap();
+    let ptrs = exhaust(&tlsf, layout);
+
+    let mut handle = tlsf.register().unwrap();
+    let guard = handle.pin();
+    unsafe {
+        ptrs[0].as_ptr().write(42);
+        assert_eq!(
+            guard.defer_deallocate(ptrs[0], layout.align()),
+            Err(TryAllocError::Exhausted)
+        );
+        // The memory block is left allocated
+        assert_eq!(ptrs[0].as_ptr().read(), 42);
+        tlsf.deallocate(ptrs[0], layout.align());
+    }
+}
+
+#[test]
+fn bags_are_chained() {
+    let tlsf = TheEpochTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(1 << 16));
+    let layout = alloc::Layout::new::<u64>();
+    let num_big_blocks = count_big_blocks(&tlsf);
+
+    let mut handle = tlsf.register().unwrap();
+    let guard = handle.pin();
+    for _ in 0..BAG_CAPACITY * 3 + 1 {
+        let ptr = tlsf.allocate(layout).unwrap();
+        unsafe { guard.defer_deallocate(ptr, layout.align()) }.unwrap();
+    }
+    drop(guard);
+
+    for _ in 0..3 {
+        assert!(tlsf.try_advance());
+    }
+    assert_eq!(count_big_blocks(&tlsf), num_big_blocks);
+}
+
+#[test]
+fn pinned_reader_sees_intact_node() {
+    let tlsf: &'static TheEpochTlsf = Box::leak(Box::new(TheEpochTlsf::new()));
+    tlsf.lock().insert_free_block(new_pool(4096));
+    let layout = alloc::Layout::new::<[u64; 4]>();
+    let node = tlsf.allocate(layout).unwrap().cast::<[u64; 4]>();
+    unsafe { node.as_ptr().write([1, 2, 3, 4]) };
+    let shared = Arc::new(AtomicPtr::new(node.as_ptr()));
+
+    let mut reader = tlsf.register().unwrap();
+    let reader_guard = reader.pin();
+    let node = shared.load(Ordering::Acquire);
+
+    // Another thread unlinks and defers the node
+    let writer = {
+        let shared = Arc::clone(&shared);
+        thread::spawn(move || {
+            let mut handle = tlsf.register().unwrap();
+            let guard = handle.pin();
+            let node = shared.swap(ptr::null_mut(), Ordering::AcqRel);
+            let node = NonNull::new(node).unwrap().cast();
+            unsafe { guard.defer_deallocate(node, layout.align()) }.unwrap();
+            drop(guard);
+            for _ in 0..8 {
+                tlsf.try_advance();
+            }
+        })
+    };
+    writer.join().unwrap();
+
+    // The node's contents are intact while the reader is pinned
+    assert_eq!(unsafe { node.read() }, [1, 2, 3, 4]);
+    drop(reader_guard);
+}
+
+#[test]
+fn pinned_participant_blocks_reclamation() {
+    let tlsf = TheEpochTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(4096));
+    let layout = alloc::Layout::from_size_align(256, 8).unwrap();
+    let ptrs = exhaust_but_bag(&tlsf, layout);
+
+    let mut reader = tlsf.register().unwrap();
+    let mut writer = tlsf.register().unwrap();
+
+    let reader_guard = reader.pin();
+    unsafe { writer.pin().defer_deallocate(ptrs[0], layout.align()) }.unwrap();
+
+    // The reader has observed the current epoch, so it can advance once
+    assert!(tlsf.try_advance());
+    for _ in 0..4 {
+        assert!(!tlsf.try_advance());
+    }
+    assert!(tlsf.allocate(layout).is_none());
+
+    drop(reader_guard);
+    assert!(tlsf.allocate(layout).is_none()); // advances to 2
+    assert!(tlsf.allocate(layout).is_some()); // advances to 3
+}
+
+/// Count the large memory blocks that can be allocated at once
+fn count_big_blocks(tlsf: &TheEpochTlsf) -> usize {
+    let layout = alloc::Layout::from_size_align(1 << 15, 8).unwrap();
+    let ptrs = exhaust(tlsf, layout);
+    for &ptr in ptrs.iter() {
+        unsafe { tlsf.deallocate(ptr, layout.align()) };
+    }
+    ptrs.len()
+}
+
+#[test]
+fn concurrent_defer() {
+    let tlsf: &'static TheEpochTlsf = Box::leak(Box::new(TheEpochTlsf::new()));
+    // A descheduled pinned thread can stall the reclamation indefinitely, so
+    // the pool must hold every node and a bag for each of them
+    tlsf.lock().insert_free_block(new_pool(1 << 22));
+
+    let num_big_blocks = count_big_blocks(tlsf);
+
+    let shared = Arc::new(AtomicPtr::<u64>::new(ptr::null_mut()));
+    let layout = alloc::Layout::new::<u64>();
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || {
+                let mut handle = tlsf.register().unwrap();
+                for k in 0..2000 {
+                    let new = tlsf.allocate(layout).unwrap().cast::<u64>();
+                    unsafe { new.as_ptr().write(i * 10000 + k) };
+                    let guard = handle.pin();
+                    let old = shared.swap(new.as_ptr(), Ordering::AcqRel);
+                    if let Some(old) = NonNull::new(old) {
+                        // Still readable while pinned
+                        assert!(unsafe { old.as_ptr().read() } < 40000);
+                        unsafe { guard.defer_deallocate(old.cast(), layout.align()) }.unwrap();
+                    }
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // Everything except the last one is eventually reclaimed
+    for _ in 0..3 {
+        assert!(tlsf.try_advance());
+    }
+    unsafe {
+        tlsf.deallocate(
+            NonNull::new(shared.load(Ordering::Relaxed)).unwrap().cast(),
+            8,
+        )
+    };
+    assert_eq!(count_big_blocks(tlsf), num_big_blocks);
+}
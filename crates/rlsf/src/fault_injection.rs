@@ -0,0 +1,313 @@
+//! `FaultInjector`: making allocations fail on purpose (the `test-utils`
+//! feature)
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    num::NonZeroUsize,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{int::BinInteger, FlexSource, FlexTlsf, Tlsf};
+
+/// Chooses the allocation requests failed by a [`FaultInjector`].
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "test-utils")))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FaultPolicy {
+    /// Don't fail any requests.
+    Never,
+    /// Fail the `n`-th, `2n`-th, `3n`-th, ... requests.
+    EveryNth(NonZeroUsize),
+    /// Fail each request with a probability of `1 / one_in`. The choices are
+    /// derived from `seed` and the request's position in the sequence, so
+    /// the same requests fail in every run with the same seed.
+    Random {
+        /// The inverse of the failure probability.
+        one_in: NonZeroUsize,
+        /// The seed of the pseudorandom sequence.
+        seed: u64,
+    },
+}
+
+/// A wrapper that makes selected allocation requests to the wrapped
+/// allocator fail, for exercising the out-of-memory handling paths of the
+/// code using it.
+///
+/// Allocations, zeroed allocations, and reallocations are counted as
+/// requests. A failed request doesn't reach the wrapped allocator, so it
+/// leaves the heap intact. Deallocations are always passed through.
+///
+/// `FaultInjector` implements [`GlobalAlloc`] if the wrapped allocator
+/// does, which covers [`GlobalTlsf`], [`SyncTlsf`], and the like. It also
+/// provides `allocate`, `reallocate`, and `deallocate` when it wraps a
+/// [`Tlsf`] or [`FlexTlsf`].
+///
+/// [`GlobalTlsf`]: crate::GlobalTlsf
+/// [`SyncTlsf`]: crate::SyncTlsf
+///
+/// # Examples
+///
+/// ```rust
+/// use rlsf::{FaultInjector, FaultPolicy, SmallGlobalTlsf};
+/// use std::{
+///     alloc::{GlobalAlloc, Layout},
+///     num::NonZeroUsize,
+/// };
+///
+/// static A: FaultInjector<SmallGlobalTlsf> = FaultInjector::new(
+///     SmallGlobalTlsf::new(),
+///     FaultPolicy::EveryNth(match NonZeroUsize::new(3) {
+///         Some(n) => n,
+///         None => unreachable!(),
+///     }),
+/// );
+///
+/// let layout = Layout::new::<u64>();
+/// unsafe {
+///     let ptr1 = A.alloc(layout);
+///     let ptr2 = A.alloc(layout);
+///     assert!(!ptr1.is_null() && !ptr2.is_null());
+///     assert!(A.alloc(layout).is_null());
+///     assert_eq!(A.injected_failures(), 1);
+///     A.dealloc(ptr1, layout);
+///     A.dealloc(ptr2, layout);
+/// }
+/// ```
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "test-utils")))]
+#[derive(Debug)]
+pub struct FaultInjector<A> {
+    inner: A,
+    policy: FaultPolicy,
+    /// The number of requests seen so far.
+    requests: AtomicUsize,
+    /// The number of requests failed so far.
+    injected_failures: AtomicUsize,
+}
+
+impl<A> FaultInjector<A> {
+    /// Wrap `inner`, failing the requests chosen by `policy`.
+    #[inline]
+    pub const fn new(inner: A, policy: FaultPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            requests: AtomicUsize::new(0),
+            injected_failures: AtomicUsize::new(0),
+        }
+    }
+
+    /// Borrow the wrapped allocator.
+    #[inline]
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// Mutably borrow the wrapped allocator, e.g., to supply memory pools.
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut A {
+        &mut self.inner
+    }
+
+    /// Unwrap the wrapped allocator.
+    #[inline]
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+
+    /// Get the current policy.
+    #[inline]
+    pub fn policy(&self) -> FaultPolicy {
+        self.policy
+    }
+
+    /// Replace the policy and restart the sequence of requests.
+    #[inline]
+    pub fn set_policy(&mut self, policy: FaultPolicy) {
+        self.policy = policy;
+        self.reset();
+    }
+
+    /// Restart the sequence of requests, so that the requests that follow
+    /// fail as if `self` had just been created. Also resets
+    /// [`Self::injected_failures`].
+    #[inline]
+    pub fn reset(&self) {
+        self.requests.store(0, Ordering::Relaxed);
+        self.injected_failures.store(0, Ordering::Relaxed);
+    }
+
+    /// Get the number of requests seen so far.
+    #[inline]
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of requests failed so far.
+    #[inline]
+    pub fn injected_failures(&self) -> usize {
+        self.injected_failures.load(Ordering::Relaxed)
+    }
+
+    /// Count a request and decide whether it should fail.
+    ///
+    /// The wrapper methods call this before forwarding a request. It can be
+    /// used to apply the policy to an allocator interface that
+    /// `FaultInjector` doesn't cover.
+    pub fn should_fail(&self) -> bool {
+        let i = self.requests.fetch_add(1, Ordering::Relaxed);
+        let fail = match self.policy {
+            FaultPolicy::Never => false,
+            FaultPolicy::EveryNth(n) => i.wrapping_add(1) % n.get() == 0,
+            FaultPolicy::Random { one_in, seed } => {
+                splitmix64(seed.wrapping_add((i as u64).wrapping_mul(GOLDEN_GAMMA)))
+                    % one_in.get() as u64
+                    == 0
+            }
+        };
+        if fail {
+            self.injected_failures.fetch_add(1, Ordering::Relaxed);
+        }
+        fail
+    }
+}
+
+/// The increment of SplitMix64's state
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// The output function of SplitMix64
+#[inline]
+fn splitmix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for FaultInjector<A> {
+    #[inline]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if self.should_fail() {
+            ptr::null_mut()
+        } else {
+            self.inner.alloc(layout)
+        }
+    }
+
+    #[inline]
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        if self.should_fail() {
+            ptr::null_mut()
+        } else {
+            self.inner.alloc_zeroed(layout)
+        }
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout)
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if self.should_fail() {
+            ptr::null_mut()
+        } else {
+            self.inner.realloc(ptr, layout, new_size)
+        }
+    }
+}
+
+impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, const SLLEN: usize>
+    FaultInjector<Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>>
+{
+    /// Attempt to allocate a block of memory. See [`Tlsf::allocate`].
+    #[inline]
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        if self.should_fail() {
+            None
+        } else {
+            self.inner.allocate(layout)
+        }
+    }
+
+    /// Shrink or grow a previously allocated memory block. See
+    /// [`Tlsf::reallocate`].
+    ///
+    /// # Safety
+    ///
+    /// See [`Tlsf::reallocate`].
+    #[inline]
+    pub unsafe fn reallocate(
+        &mut self,
+        ptr: NonNull<u8>,
+        new_layout: Layout,
+    ) -> Option<NonNull<u8>> {
+        if self.should_fail() {
+            None
+        } else {
+            self.inner.reallocate(ptr, new_layout)
+        }
+    }
+
+    /// Deallocate a previously allocated memory block. See
+    /// [`Tlsf::deallocate`].
+    ///
+    /// # Safety
+    ///
+    /// See [`Tlsf::deallocate`].
+    #[inline]
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, align: usize) {
+        self.inner.deallocate(ptr, align)
+    }
+}
+
+impl<
+        Source: FlexSource,
+        FLBitmap: BinInteger,
+        SLBitmap: BinInteger,
+        const FLLEN: usize,
+        const SLLEN: usize,
+    > FaultInjector<FlexTlsf<Source, FLBitmap, SLBitmap, FLLEN, SLLEN>>
+{
+    /// Attempt to allocate a block of memory. See [`FlexTlsf::allocate`].
+    #[inline]
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        if self.should_fail() {
+            None
+        } else {
+            self.inner.allocate(layout)
+        }
+    }
+
+    /// Shrink or grow a previously allocated memory block. See
+    /// [`FlexTlsf::reallocate`].
+    ///
+    /// # Safety
+    ///
+    /// See [`FlexTlsf::reallocate`].
+    #[inline]
+    pub unsafe fn reallocate(
+        &mut self,
+        ptr: NonNull<u8>,
+        new_layout: Layout,
+    ) -> Option<NonNull<u8>> {
+        if self.should_fail() {
+            None
+        } else {
+            self.inner.reallocate(ptr, new_layout)
+        }
+    }
+
+    /// Deallocate a previously allocated memory block. See
+    /// [`FlexTlsf::deallocate`].
+    ///
+    /// # Safety
+    ///
+    /// See [`FlexTlsf::deallocate`].
+    #[inline]
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, align: usize) {
+        self.inner.deallocate(ptr, align)
+    }
+}
+
+#[cfg(test)]
+mod tests;
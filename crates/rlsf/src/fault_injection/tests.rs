@@ -0,0 +1,132 @@
+use core::{alloc::Layout, mem::MaybeUninit};
+use std::{alloc::System, prelude::v1::*, vec};
+
+use super::*;
+
+fn nz(n: usize) -> NonZeroUsize {
+    NonZeroUsize::new(n).unwrap()
+}
+
+/// Record which of `count` allocation requests fail
+fn pattern(injector: &FaultInjector<System>, count: usize) -> Vec<bool> {
+    let layout = Layout::new::<u64>();
+    (0..count)
+        .map(|_| unsafe {
+            let ptr = injector.alloc(layout);
+            if !ptr.is_null() {
+                injector.dealloc(ptr, layout);
+            }
+            ptr.is_null()
+        })
+        .collect()
+}
+
+#[test]
+fn never() {
+    let injector = FaultInjector::new(System, FaultPolicy::Never);
+    assert!(!pattern(&injector, 100).contains(&true));
+    assert_eq!(injector.requests(), 100);
+    assert_eq!(injector.injected_failures(), 0);
+}
+
+#[test]
+fn every_nth() {
+    let injector = FaultInjector::new(System, FaultPolicy::EveryNth(nz(4)));
+    let failed = pattern(&injector, 12);
+    let expected: Vec<_> = (1..=12).map(|i| i % 4 == 0).collect();
+    assert_eq!(failed, expected);
+    assert_eq!(injector.injected_failures(), 3);
+
+    // The sequence restarts
+    injector.reset();
+    assert_eq!(pattern(&injector, 12), expected);
+
+    let mut injector = injector;
+    injector.set_policy(FaultPolicy::EveryNth(nz(1)));
+    assert!(!pattern(&injector, 10).contains(&false));
+    assert_eq!(injector.injected_failures(), 10);
+}
+
+#[test]
+fn random_is_deterministic() {
+    let policy = FaultPolicy::Random {
+        one_in: nz(8),
+        seed: 42,
+    };
+    let injector = FaultInjector::new(System, policy);
+    let failed = pattern(&injector, 4000);
+    let num_failed = failed.iter().filter(|&&x| x).count();
+    assert_eq!(injector.injected_failures(), num_failed);
+    assert!((300..700).contains(&num_failed), "{}", num_failed);
+
+    let injector = FaultInjector::new(System, policy);
+    assert_eq!(pattern(&injector, 4000), failed);
+
+    let injector = FaultInjector::new(
+        System,
+        FaultPolicy::Random {
+            one_in: nz(8),
+            seed: 43,
+        },
+    );
+    assert_ne!(pattern(&injector, 4000), failed);
+}
+
+#[test]
+fn realloc() {
+    let injector = FaultInjector::new(System, FaultPolicy::EveryNth(nz(2)));
+    let layout = Layout::new::<u64>();
+    unsafe {
+        let ptr = injector.alloc(layout);
+        assert!(!ptr.is_null());
+        ptr.cast::<u64>().write(0x1234);
+
+        // The original memory block is left intact on failure
+        assert!(injector.realloc(ptr, layout, 64).is_null());
+        assert_eq!(ptr.cast::<u64>().read(), 0x1234);
+
+        let ptr = injector.realloc(ptr, layout, 64);
+        assert!(!ptr.is_null());
+        assert_eq!(ptr.cast::<u64>().read(), 0x1234);
+        injector.dealloc(ptr, Layout::from_size_align(64, layout.align()).unwrap());
+    }
+}
+
+#[test]
+fn tlsf() {
+    let pool = Box::leak(vec![MaybeUninit::uninit(); 4096].into_boxed_slice());
+    let mut injector: FaultInjector<Tlsf<'static, u16, u16, 12, 16>> =
+        FaultInjector::new(Tlsf::new(), FaultPolicy::EveryNth(nz(2)));
+    injector.inner_mut().insert_free_block(pool);
+
+    let layout = Layout::new::<u64>();
+    let ptr = injector.allocate(layout).unwrap();
+    assert_eq!(injector.allocate(layout), None);
+    let ptr = unsafe { injector.reallocate(ptr, Layout::new::<[u64; 4]>()) }.unwrap();
+    assert_eq!(
+        unsafe { injector.reallocate(ptr, Layout::new::<[u64; 8]>()) },
+        None
+    );
+    unsafe { injector.deallocate(ptr, layout.align()) };
+    assert_eq!(injector.injected_failures(), 2);
+}
+
+#[test]
+fn flex_tlsf() {
+    use crate::GlobalAllocAsFlexSource;
+
+    let mut injector: FaultInjector<
+        FlexTlsf<GlobalAllocAsFlexSource<System, 1024>, u16, u16, 12, 16>,
+    > = FaultInjector::new(
+        FlexTlsf::new(GlobalAllocAsFlexSource(System)),
+        FaultPolicy::EveryNth(nz(2)),
+    );
+
+    let layout = Layout::new::<u64>();
+    let ptr = injector.allocate(layout).unwrap();
+    assert_eq!(injector.allocate(layout), None);
+    let ptr = unsafe { injector.reallocate(ptr, Layout::new::<[u64; 4]>()) }.unwrap();
+    unsafe { injector.deallocate(ptr, layout.align()) };
+    assert_eq!(injector.requests(), 3);
+    assert_eq!(injector.injected_failures(), 1);
+}
@@ -1,15 +1,44 @@
 //! An allocator with flexible backing stores
 use const_default1::ConstDefault;
-use core::{alloc::Layout, debug_assert, ptr::NonNull, unimplemented};
+use core::{alloc::Layout, debug_assert, fmt, ptr::NonNull, unimplemented};
 
 use super::{
+    corruption::{report_corruption, HeapError},
     int::BinInteger,
     utils::{
         nonnull_slice_end, nonnull_slice_from_raw_parts, nonnull_slice_len, nonnull_slice_start,
+        ptr_map_addr, ptr_with_addr,
     },
-    Tlsf, GRANULARITY,
+    ClassOccupancy, LayoutMismatch, Tlsf, GRANULARITY,
 };
 
+#[cfg(feature = "debug-leak-check")]
+use super::leak_check::{LeakCheck, LeakReporter};
+
+#[cfg(feature = "randomize")]
+use super::RandomSource;
+
+#[cfg(feature = "stats")]
+use super::HeapStats;
+
+#[cfg(feature = "oom-report")]
+use super::OomHook;
+
+#[cfg(feature = "linker-heap")]
+mod linker;
+#[cfg(feature = "linker-heap")]
+pub use self::linker::*;
+
+#[cfg(unix)]
+mod mlock;
+#[cfg(unix)]
+pub use self::mlock::*;
+
+#[cfg(target_os = "linux")]
+mod numa;
+#[cfg(target_os = "linux")]
+pub use self::numa::*;
+
 /// The trait for dynamic storage allocators that can back [`FlexTlsf`].
 pub unsafe trait FlexSource {
     /// Allocate a memory block of the requested minimum size.
@@ -26,6 +55,23 @@ pub unsafe trait FlexSource {
         None
     }
 
+    /// Allocate a memory block of the requested minimum size, reporting the
+    /// reason of failure.
+    ///
+    /// [`FlexTlsf`] calls this method instead of [`Self::alloc`]. The
+    /// default implementation calls `alloc` and converts `None` to
+    /// [`SourceError::Exhausted`]. Implementations that can tell why they
+    /// failed (e.g., by examining `errno`) should override this method and
+    /// implement `alloc` in terms of it.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::alloc`].
+    #[inline]
+    unsafe fn try_alloc(&mut self, min_size: usize) -> Result<NonNull<[u8]>, SourceError> {
+        self.alloc(min_size).ok_or(SourceError::Exhausted)
+    }
+
     /// Attempt to grow the specified allocation without moving it. Returns
     /// the final allocation size (which must be greater than or equal to
     /// `min_new_len`) on success.
@@ -44,6 +90,40 @@ pub unsafe trait FlexSource {
         None
     }
 
+    /// Attempt to allocate a memory block of at least `min_extra` bytes that
+    /// immediately follows `existing`. Returns the address range of the
+    /// allocated memory block on success.
+    ///
+    /// The returned memory block must start at the ending address of
+    /// `existing`. On success, `existing` and the returned memory block are
+    /// merged into one allocation, which is passed to subsequent calls to
+    /// this method, [`Self::realloc_inplace_grow`], and [`Self::dealloc`] as
+    /// a whole.
+    ///
+    /// The default implementation calls [`Self::realloc_inplace_grow`].
+    /// Implement this method if the source can grow `existing` only by making
+    /// a new allocation, which it can guarantee to be adjacent to `existing`.
+    ///
+    /// # Safety
+    ///
+    /// `existing` must be an existing allocation made by this allocator.
+    /// `min_extra` must not be zero.
+    #[inline]
+    unsafe fn alloc_contiguous_with(
+        &mut self,
+        existing: NonNull<[u8]>,
+        min_extra: usize,
+    ) -> Option<NonNull<[u8]>> {
+        let len = nonnull_slice_len(existing);
+        let new_len = self.realloc_inplace_grow(existing, len.checked_add(min_extra)?)?;
+        // Safety: `existing` is followed by `new_len - len` bytes of memory
+        //         that we now own
+        Some(nonnull_slice_from_raw_parts(
+            NonNull::new_unchecked(nonnull_slice_end(existing)),
+            new_len - len,
+        ))
+    }
+
     /// Deallocate a previously allocated memory block.
     ///
     /// # Safety
@@ -66,11 +146,12 @@ pub unsafe trait FlexSource {
         false
     }
 
-    /// Check if this allocator implements [`Self::realloc_inplace_grow`].
+    /// Check if this allocator implements [`Self::realloc_inplace_grow`] or
+    /// [`Self::alloc_contiguous_with`].
     ///
     /// If this method returns `false`, [`FlexTlsf`] will not call
-    /// `realloc_inplace_grow` to attempt to grow memory blocks. It also applies
-    /// some optimizations.
+    /// `alloc_contiguous_with` to attempt to grow memory blocks. It also
+    /// applies some optimizations.
     ///
     /// The returned value must be constant for a particular instance of `Self`.
     #[inline]
@@ -108,6 +189,99 @@ pub unsafe trait FlexSource {
     }
 }
 
+/// The error type returned by [`FlexSource::try_alloc`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum SourceError {
+    /// The source has no more memory to provide, or the reason of failure is
+    /// unknown.
+    Exhausted,
+    /// The operating system refused the request. Contains the raw error code
+    /// (e.g., `errno` on Unix).
+    Os(i32),
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exhausted => f.write_str("the memory source is exhausted"),
+            Self::Os(code) => write!(f, "the memory source failed with OS error {}", code),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SourceError {}
+
+/// The error type returned by [`FlexTlsf::try_allocate`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum AllocError {
+    /// The requested layout is too large to be represented by a memory pool.
+    SizeOverflow,
+    /// The memory pool could not be grown because the source failed.
+    Source(SourceError),
+    /// The memory pool could not be grown without exceeding the limit set by
+    /// [`FlexTlsf::set_max_capacity`].
+    CapacityExceeded,
+}
+
+impl From<SourceError> for AllocError {
+    #[inline]
+    fn from(x: SourceError) -> Self {
+        Self::Source(x)
+    }
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SizeOverflow => f.write_str("the requested allocation size is too large"),
+            Self::Source(e) => fmt::Display::fmt(e, f),
+            Self::CapacityExceeded => f.write_str("the capacity limit would be exceeded"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AllocError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::SizeOverflow | Self::CapacityExceeded => None,
+            Self::Source(e) => Some(e),
+        }
+    }
+}
+
+/// The error type returned by the non-blocking `try_allocate` methods of the
+/// synchronized allocators (e.g., [`SyncTlsf::try_allocate`]).
+///
+/// [`SyncTlsf::try_allocate`]: crate::SyncTlsf::try_allocate
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum TryAllocError {
+    /// The lock couldn't be acquired without waiting.
+    Contended,
+    /// The lock was acquired, but the allocator has no memory block to satisfy
+    /// the request.
+    Exhausted,
+}
+
+impl fmt::Display for TryAllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Contended => f.write_str("the allocator is locked by someone else"),
+            Self::Exhausted => f.write_str("the allocator is out of memory"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TryAllocError {}
+
 trait FlexSourceExt: FlexSource {
     #[inline]
     fn use_growable_pool(&self) -> bool {
@@ -116,10 +290,75 @@ trait FlexSourceExt: FlexSource {
         // returns `false`.
         self.supports_dealloc() || self.supports_realloc_inplace_grow()
     }
+
+    /// Check if the allocations should be linked together by `PoolFtr` and
+    /// `growable_pool`. This is necessary for deallocation and enumerating
+    /// memory pools.
+    #[inline]
+    fn use_pool_ftr(&self) -> bool {
+        self.supports_dealloc() || cfg!(feature = "unstable")
+    }
 }
 
 impl<T: FlexSource> FlexSourceExt for T {}
 
+/// Forwards all calls to the referenced [`FlexSource`]. This allows a
+/// short-lived [`FlexTlsf`] to borrow a source owned by someone else.
+unsafe impl<T: FlexSource + ?Sized> FlexSource for &mut T {
+    #[inline]
+    unsafe fn alloc(&mut self, min_size: usize) -> Option<NonNull<[u8]>> {
+        (**self).alloc(min_size)
+    }
+
+    #[inline]
+    unsafe fn try_alloc(&mut self, min_size: usize) -> Result<NonNull<[u8]>, SourceError> {
+        (**self).try_alloc(min_size)
+    }
+
+    #[inline]
+    unsafe fn realloc_inplace_grow(
+        &mut self,
+        ptr: NonNull<[u8]>,
+        min_new_len: usize,
+    ) -> Option<usize> {
+        (**self).realloc_inplace_grow(ptr, min_new_len)
+    }
+
+    #[inline]
+    unsafe fn alloc_contiguous_with(
+        &mut self,
+        existing: NonNull<[u8]>,
+        min_extra: usize,
+    ) -> Option<NonNull<[u8]>> {
+        (**self).alloc_contiguous_with(existing, min_extra)
+    }
+
+    #[inline]
+    unsafe fn dealloc(&mut self, ptr: NonNull<[u8]>) {
+        (**self).dealloc(ptr)
+    }
+
+    #[inline]
+    fn supports_dealloc(&self) -> bool {
+        (**self).supports_dealloc()
+    }
+
+    #[inline]
+    fn supports_realloc_inplace_grow(&self) -> bool {
+        (**self).supports_realloc_inplace_grow()
+    }
+
+    #[inline]
+    fn is_contiguous_growable(&self) -> bool {
+        (**self).is_contiguous_growable()
+    }
+
+    #[inline]
+    fn min_align(&self) -> usize {
+        (**self).min_align()
+    }
+}
+
 /// Wraps [`core::alloc::GlobalAlloc`] to implement the [`FlexSource`] trait.
 ///
 /// Since this type does not implement [`FlexSource::realloc_inplace_grow`],
@@ -179,13 +418,85 @@ unsafe impl<T: core::alloc::GlobalAlloc, const ALIGN: usize> FlexSource
 
 /// A wrapper of [`Tlsf`] that automatically acquires fresh memory pools from
 /// [`FlexSource`].
+///
+/// `Source` doesn't have to be `'static`. A `FlexTlsf` can't outlive its
+/// `Source`, so a source borrowing a stack or arena buffer can be used to
+/// create a scoped heap:
+///
+/// ```
+/// use rlsf::{FlexSource, FlexTlsf};
+/// use std::{alloc::Layout, mem::MaybeUninit, ptr::NonNull};
+///
+/// /// Hands out a borrowed buffer once.
+/// struct BufferSource<'a>(Option<&'a mut [MaybeUninit<u8>]>);
+///
+/// unsafe impl FlexSource for BufferSource<'_> {
+///     unsafe fn alloc(&mut self, min_size: usize) -> Option<NonNull<[u8]>> {
+///         let buffer = self.0.take().filter(|b| b.len() >= min_size)?;
+///         NonNull::new(buffer as *mut [MaybeUninit<u8>] as *mut [u8])
+///     }
+/// }
+///
+/// let mut buffer = [MaybeUninit::uninit(); 1024];
+/// let mut tlsf: FlexTlsf<_, u8, u8, 8, 8> =
+///     FlexTlsf::new(BufferSource(Some(&mut buffer)));
+/// let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+/// unsafe { tlsf.deallocate(ptr, Layout::new::<u64>().align()) };
+/// ```
+///
+/// The buffer must outlive the `FlexTlsf`:
+///
+/// ```rust,compile_fail
+/// # use rlsf::{FlexSource, FlexTlsf};
+/// # use std::{mem::MaybeUninit, ptr::NonNull};
+/// # struct BufferSource<'a>(Option<&'a mut [MaybeUninit<u8>]>);
+/// # unsafe impl FlexSource for BufferSource<'_> {}
+/// let tlsf: FlexTlsf<_, u8, u8, 8, 8> = {
+///     let mut buffer = [MaybeUninit::uninit(); 1024];
+///     FlexTlsf::new(BufferSource(Some(&mut buffer)))
+/// };
+/// ```
+///
+/// A `FlexTlsf` can also borrow a source owned by someone else through the
+/// [`FlexSource`] implementation of `&mut T`.
 #[derive(Debug)]
 pub struct FlexTlsf<Source: FlexSource, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize>
 {
     /// The lastly created memory pool.
     growable_pool: Option<Pool>,
+    /// The lastly adopted memory pool that should be passed to
+    /// `source.dealloc` on drop. Forms a separate singly-linked list through
+    /// `PoolFtr`.
+    adopted_pool: Option<Pool>,
     source: Source,
     tlsf: Tlsf<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>,
+    /// The total number of bytes obtained from `source`.
+    capacity: usize,
+    /// The upper limit of `capacity`.
+    max_capacity: usize,
+    /// Allocations larger than this are satisfied by dedicated memory blocks
+    /// obtained from `source`.
+    huge_threshold: usize,
+    /// The number of live allocations made by `allocate_huge`.
+    num_huge_allocations: usize,
+    /// Checks the heap integrity on drop. See [`Self::set_verify_on_drop`].
+    /// This is a function pointer because `Drop` can't require
+    /// `FLBitmap: BinInteger`.
+    verify_on_drop: Option<fn(&Self)>,
+    /// Indicates that some memory pools are not reachable through
+    /// `PoolFtr`, so they can't be enumerated.
+    has_unlinked_pools: bool,
+    /// The number of calls to `allocate_untraced` that failed. This isn't
+    /// taken from `Tlsf::counters`, which also counts the attempts made
+    /// before growing the memory pool.
+    #[cfg(feature = "stats")]
+    failed_allocations: usize,
+    /// Called when `allocate_untraced` fails. This isn't registered to
+    /// `tlsf`, which fails before growing the memory pool.
+    #[cfg(feature = "oom-report")]
+    oom_hook: Option<OomHook>,
+    #[cfg(feature = "debug-leak-check")]
+    leak_check: LeakCheck,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -204,7 +515,7 @@ unsafe impl Send for Pool {}
 unsafe impl Sync for Pool {}
 
 /// Pool footer stored at the end of each pool. It's only used when
-/// supports_dealloc() == true`.
+/// `use_pool_ftr() == true`.
 ///
 /// The footer is stored in the sentinel block's unused space or any padding
 /// present at the end of each pool. This is why `PoolFtr` can't be larger than
@@ -229,10 +540,84 @@ impl PoolFtr {
         // If `alloc_end` is not well-aligned, we need to adjust the location
         // of `PoolFtr`
         if alloc_align < core::mem::align_of::<Self>() {
-            ptr = (ptr as usize & !(core::mem::align_of::<Self>() - 1)) as _;
+            ptr = ptr_map_addr(ptr, |addr| addr & !(core::mem::align_of::<Self>() - 1));
         }
         ptr as _
     }
+
+    /// Get the part of `alloc` that a memory pool can be created in.
+    ///
+    /// With the `pool-guards` feature, this excludes `PoolFtr` so that it
+    /// doesn't overwrite the guard in the unused space of the memory pool's
+    /// sentinel block.
+    #[inline]
+    fn pool_area(alloc: NonNull<[u8]>, alloc_align: usize) -> NonNull<[u8]> {
+        if cfg!(feature = "pool-guards") {
+            let start = nonnull_slice_start(alloc);
+            let ftr = Self::get_for_alloc(alloc, alloc_align) as *mut u8;
+            nonnull_slice_from_raw_parts(
+                start,
+                (ftr as usize).saturating_sub(start.as_ptr() as usize),
+            )
+        } else {
+            alloc
+        }
+    }
+}
+
+/// Footer of a huge allocation, stored in the unused space of its sentinel
+/// block.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct HugeFtr {
+    /// The memory block obtained from the source for the allocation.
+    alloc: NonNull<[u8]>,
+}
+
+const _: () = if core::mem::size_of::<HugeFtr>() != GRANULARITY / 2 {
+    panic!("bad `HugeFtr` size");
+};
+
+/// The memory usage of a memory pool, returned by
+/// [`FlexTlsf::pool_overheads`].
+#[cfg(feature = "unstable")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "unstable")))]
+#[derive(Debug, Copy, Clone)]
+pub struct PoolOverhead {
+    /// The address range of the memory block obtained from the source,
+    /// which contains the memory pool.
+    pub alloc: NonNull<[u8]>,
+    /// The number of live allocations in the memory pool.
+    pub num_allocations: usize,
+    /// The total usable size of the live allocations in the memory pool.
+    ///
+    /// This includes the padding inserted before allocations with an
+    /// alignment requirement larger than or equal to [`GRANULARITY`],
+    /// which can't be told apart from the payload.
+    pub allocated_bytes: usize,
+    /// The total size of the free blocks in the memory pool.
+    pub free_bytes: usize,
+    /// The number of bytes consumed by block headers, sentinel blocks, and
+    /// the unused bytes at the end of `alloc`, i.e.,
+    /// `alloc.len() - allocated_bytes - free_bytes`.
+    pub overhead_bytes: usize,
+}
+
+#[cfg(all(feature = "unstable", feature = "defmt"))]
+impl defmt::Format for PoolOverhead {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(
+            f,
+            "PoolOverhead {{ alloc: {}..+{}, num_allocations: {}, allocated_bytes: {}, \
+            free_bytes: {}, overhead_bytes: {} }}",
+            self.alloc.as_ptr() as *mut u8,
+            nonnull_slice_len(self.alloc),
+            self.num_allocations,
+            self.allocated_bytes,
+            self.free_bytes,
+            self.overhead_bytes
+        );
+    }
 }
 
 /// Initialization with a [`FlexSource`] provided by [`Default::default`]
@@ -278,6 +663,19 @@ impl<
             source,
             tlsf: Tlsf::new(),
             growable_pool: None,
+            adopted_pool: None,
+            capacity: 0,
+            max_capacity: usize::MAX,
+            huge_threshold: usize::MAX,
+            num_huge_allocations: 0,
+            verify_on_drop: None,
+            has_unlinked_pools: false,
+            #[cfg(feature = "stats")]
+            failed_allocations: 0,
+            #[cfg(feature = "oom-report")]
+            oom_hook: None,
+            #[cfg(feature = "debug-leak-check")]
+            leak_check: LeakCheck::new(Some("FlexTlsf")),
         }
     }
 
@@ -298,6 +696,513 @@ impl<
         &mut self.source
     }
 
+    /// Create a new memory pool at the location specified by a slice pointer
+    /// that wasn't obtained through [`FlexSource::alloc`].
+    ///
+    /// This can be used to manage memory handed over by someone else (e.g., a
+    /// bootloader or a shared memory segment) alongside the memory pools
+    /// obtained from the source. Adopted memory pools are never grown and are
+    /// not counted toward [`Self::capacity`].
+    ///
+    /// If `owned` is `true`, the memory block will be passed to
+    /// [`FlexSource::dealloc`] when `self` is dropped. (This has no effect if
+    /// [`FlexSource::supports_dealloc`] returns `false`.)
+    ///
+    /// Returns the actual number of bytes used to create the memory pool.
+    /// This method does nothing and returns `None` if the given memory block
+    /// is too small, in which case the ownership of the memory block is not
+    /// taken.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(ptr.len())`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::{FlexTlsf, GlobalAllocAsFlexSource};
+    /// use std::{alloc::{Layout, System}, mem::MaybeUninit, ptr::NonNull};
+    ///
+    /// static mut POOL: MaybeUninit<[u8; 1024]> = MaybeUninit::uninit();
+    ///
+    /// let mut tlsf: FlexTlsf<GlobalAllocAsFlexSource<System, 1024>, u16, u16, 12, 16> =
+    ///     FlexTlsf::new(GlobalAllocAsFlexSource(System));
+    /// unsafe { tlsf.adopt_pool(NonNull::new(POOL.as_mut_ptr()).unwrap(), false) };
+    ///
+    /// // Served by `POOL`
+    /// let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+    /// assert_eq!(tlsf.capacity(), 0);
+    /// unsafe { tlsf.deallocate(ptr, 8) };
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// The memory block will be considered owned by `self`. The memory block
+    /// must outlive `self`. If `owned` is `true`, the memory block must be
+    /// one that can be passed to `self.source_ref()`'s [`FlexSource::dealloc`].
+    pub unsafe fn adopt_pool(
+        &mut self,
+        ptr: NonNull<[u8]>,
+        owned: bool,
+    ) -> Option<core::num::NonZeroUsize> {
+        let use_pool_ftr = owned && self.source.supports_dealloc();
+        let pool_area = if use_pool_ftr {
+            PoolFtr::pool_area(ptr, 1)
+        } else {
+            ptr
+        };
+
+        // Safety: Upheld by the caller
+        let pool_len = self.tlsf.insert_free_block_ptr(pool_area)?;
+
+        if use_pool_ftr {
+            // Link the pool to `self.adopted_pool`. The alignment of `ptr`
+            // is unknown, so let `get_for_alloc` align the footer.
+            let pool_ftr = PoolFtr::get_for_alloc(ptr, 1);
+            let prev_alloc = self
+                .adopted_pool
+                .map(|p| nonnull_slice_from_raw_parts(p.alloc_start, p.alloc_len));
+            // Safety: `(*pool_ftr).prev_alloc` is within a pool footer
+            //         we control
+            (*pool_ftr).prev_alloc = prev_alloc;
+
+            self.adopted_pool = Some(Pool {
+                alloc_start: nonnull_slice_start(ptr),
+                alloc_len: nonnull_slice_len(ptr),
+                pool_len: pool_len.get(),
+            });
+        } else {
+            self.has_unlinked_pools = true;
+        }
+
+        Some(pool_len)
+    }
+
+    /// Limit the total number of bytes that `self` obtains from the source.
+    ///
+    /// Once the limit is reached, allocations that can't be satisfied by the
+    /// existing memory pools fail with [`AllocError::CapacityExceeded`]
+    /// without consulting the source. Setting a limit lower than
+    /// [`Self::capacity`] does not release any memory.
+    ///
+    /// The limit is enforced on the sizes requested from the source. Since a
+    /// source may return a larger memory block than requested (e.g., rounded
+    /// up to the page size), the capacity may exceed the limit by the amount
+    /// of such rounding in the last growth.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::{AllocError, FlexTlsf, GlobalAllocAsFlexSource};
+    /// use std::alloc::{Layout, System};
+    ///
+    /// let mut tlsf: FlexTlsf<GlobalAllocAsFlexSource<System, 1024>, u16, u16, 12, 16> =
+    ///     FlexTlsf::new(GlobalAllocAsFlexSource(System));
+    /// tlsf.set_max_capacity(4096);
+    /// assert_eq!(
+    ///     tlsf.try_allocate(Layout::new::<[u8; 8192]>()),
+    ///     Err(AllocError::CapacityExceeded),
+    /// );
+    /// assert_eq!(tlsf.capacity(), 0);
+    /// ```
+    #[inline]
+    pub fn set_max_capacity(&mut self, bytes: usize) {
+        self.max_capacity = bytes;
+    }
+
+    /// Get the limit set by [`Self::set_max_capacity`]. Defaults to
+    /// `usize::MAX`.
+    #[inline]
+    pub fn max_capacity(&self) -> usize {
+        self.max_capacity
+    }
+
+    /// Get the total number of bytes obtained from the source so far.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get a snapshot of the statistics of `self`. See [`Tlsf::heap_stats`].
+    ///
+    /// [`HeapStats::pool_bytes`] is [`Self::capacity`]. The memory blocks
+    /// obtained directly from the source (see [`Self::set_huge_threshold`])
+    /// are only counted there.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::{FlexTlsf, GlobalAllocAsFlexSource};
+    /// use std::alloc::{Layout, System};
+    ///
+    /// let mut tlsf: FlexTlsf<GlobalAllocAsFlexSource<System, 1024>, u16, u16, 12, 16> =
+    ///     FlexTlsf::new(GlobalAllocAsFlexSource(System));
+    /// let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+    ///
+    /// let stats = tlsf.heap_stats();
+    /// assert_eq!(stats.num_allocations, 1);
+    /// assert_eq!(stats.failed_allocations, 0);
+    /// assert_eq!(stats.pool_bytes, tlsf.capacity());
+    /// # unsafe { tlsf.deallocate(ptr, 8) };
+    /// ```
+    #[cfg(feature = "stats")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "stats")))]
+    #[inline]
+    pub fn heap_stats(&self) -> HeapStats {
+        HeapStats {
+            pool_bytes: self.capacity,
+            failed_allocations: self.failed_allocations,
+            ..self.tlsf.heap_stats()
+        }
+    }
+
+    /// Call `f` for the part of each free memory block that doesn't store
+    /// allocator metadata. See [`Tlsf::for_each_free_payload`].
+    #[inline]
+    pub(crate) fn for_each_free_payload(&mut self, f: impl FnMut(NonNull<[u8]>)) {
+        self.tlsf.for_each_free_payload(f);
+    }
+
+    /// Get the number of free memory blocks and their total size. See
+    /// [`Tlsf::free_blocks_summary`].
+    #[inline]
+    pub(crate) fn free_blocks_summary(&self) -> (usize, usize) {
+        self.tlsf.free_blocks_summary()
+    }
+
+    /// Count the free and allocated memory blocks in each size class. See
+    /// [`Tlsf::occupancy`].
+    ///
+    /// Returns `None` if the memory pools can't be enumerated, i.e., if
+    /// `Source` doesn't use pool footers (see [`FlexSource::use_pool_ftr`])
+    /// or [`Self::adopt_pool`] was given a memory block that isn't owned or
+    /// can't be deallocated by `Source`.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(num_blocks)`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::{FlexTlsf, GlobalAllocAsFlexSource};
+    /// use std::alloc::{Layout, System};
+    ///
+    /// let mut tlsf: FlexTlsf<GlobalAllocAsFlexSource<System, 1024>, u16, u16, 12, 16> =
+    ///     FlexTlsf::new(GlobalAllocAsFlexSource(System));
+    /// let ptr = tlsf.allocate(Layout::new::<[u8; 100]>()).unwrap();
+    ///
+    /// let occupancy = tlsf.occupancy().unwrap();
+    /// let used_blocks: usize = occupancy.iter().flatten().map(|c| c.used_blocks).sum();
+    /// assert_eq!(used_blocks, 1);
+    ///
+    /// unsafe { tlsf.deallocate(ptr, 1) };
+    /// ```
+    pub fn occupancy(&self) -> Option<[[ClassOccupancy; SLLEN]; FLLEN]> {
+        if !self.source.use_pool_ftr() || self.has_unlinked_pools {
+            return None;
+        }
+
+        let pools = Self::iter_alloc_list(self.growable_pool, self.source.min_align())
+            .chain(Self::iter_alloc_list(self.adopted_pool, 1));
+
+        // Safety: `pools` contains all memory pools owned by `self.tlsf`, and
+        //         the trailing bytes after each memory pool are shorter than
+        //         `GRANULARITY * 2` bytes
+        Some(unsafe { self.tlsf.occupancy_iter(pools) })
+    }
+
+    /// Register a function to be called with [`Tlsf::oom_report`] when an
+    /// allocation or reallocation fails after attempting to grow the memory
+    /// pool. See [`Tlsf::set_oom_hook`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::{FlexTlsf, GlobalAllocAsFlexSource, OomReport};
+    /// use std::alloc::{Layout, System};
+    ///
+    /// fn on_oom(report: OomReport) {
+    ///     eprintln!("{}", report);
+    /// }
+    ///
+    /// let mut tlsf: FlexTlsf<GlobalAllocAsFlexSource<System, 1024>, u16, u16, 12, 16> =
+    ///     FlexTlsf::new(GlobalAllocAsFlexSource(System));
+    /// tlsf.set_max_capacity(4096);
+    /// tlsf.set_oom_hook(on_oom);
+    ///
+    /// // Calls `on_oom`
+    /// assert!(tlsf.allocate(Layout::new::<[u8; 8192]>()).is_none());
+    /// ```
+    #[cfg(feature = "oom-report")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "oom-report")))]
+    #[inline]
+    pub fn set_oom_hook(&mut self, hook: OomHook) {
+        self.oom_hook = Some(hook);
+    }
+
+    /// Unregister the function registered by [`Self::set_oom_hook`].
+    #[cfg(feature = "oom-report")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "oom-report")))]
+    #[inline]
+    pub fn clear_oom_hook(&mut self) {
+        self.oom_hook = None;
+    }
+
+    /// Attach an observer that receives the events of the underlying
+    /// [`Tlsf`]. See [`Tlsf::set_observer`].
+    #[cfg(feature = "observer")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "observer")))]
+    #[inline]
+    pub fn set_observer(&mut self, observer: &'static dyn crate::AllocObserver) {
+        self.tlsf.set_observer(observer);
+    }
+
+    /// Count the free memory blocks in each free block list. See
+    /// [`Tlsf::free_histogram`].
+    #[inline]
+    pub fn free_histogram(&self) -> [[usize; SLLEN]; FLLEN] {
+        self.tlsf.free_histogram()
+    }
+
+    /// Get the number of bytes that can still be obtained from the source
+    /// before reaching [`Self::max_capacity`].
+    #[inline]
+    pub fn remaining_capacity(&self) -> usize {
+        self.max_capacity.saturating_sub(self.capacity)
+    }
+
+    /// Satisfy allocations larger than `bytes` bytes by dedicated memory
+    /// blocks obtained from the source, bypassing the memory pools.
+    ///
+    /// Such allocations are returned to the source as soon as they are
+    /// deallocated, so a few giant buffers don't permanently bloat the
+    /// memory pools. This has no effect if [`FlexSource::supports_dealloc`]
+    /// returns `false`. The memory blocks obtained for such allocations count
+    /// toward [`Self::capacity`] while they are live.
+    ///
+    /// Defaults to `usize::MAX`, meaning that no allocations are routed to the
+    /// source this way. Regardless of this setting, allocations too large to
+    /// fit in a memory pool (i.e., exceeding the maximum block size determined
+    /// by `FLLEN`) are routed this way if the source supports deallocation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::{FlexTlsf, GlobalAllocAsFlexSource};
+    /// use std::alloc::{Layout, System};
+    ///
+    /// let mut tlsf: FlexTlsf<GlobalAllocAsFlexSource<System, 1024>, u16, u16, 12, 16> =
+    ///     FlexTlsf::new(GlobalAllocAsFlexSource(System));
+    /// tlsf.set_huge_threshold(16384);
+    ///
+    /// let layout = Layout::new::<[u8; 65536]>();
+    /// let ptr = tlsf.allocate(layout).unwrap();
+    /// assert!(tlsf.capacity() >= 65536);
+    ///
+    /// // The memory block is returned to the source right away
+    /// unsafe { tlsf.deallocate(ptr, layout.align()) };
+    /// assert_eq!(tlsf.capacity(), 0);
+    /// ```
+    #[inline]
+    pub fn set_huge_threshold(&mut self, bytes: usize) {
+        self.huge_threshold = bytes;
+    }
+
+    /// Get the threshold set by [`Self::set_huge_threshold`].
+    #[inline]
+    pub fn huge_threshold(&self) -> usize {
+        self.huge_threshold
+    }
+
+    /// Check if an allocation with `layout` should be made by
+    /// [`Self::allocate_huge`].
+    #[inline]
+    fn is_huge(&self, layout: Layout) -> bool {
+        layout.size() > self.huge_threshold && self.source.supports_dealloc()
+    }
+
+    /// Register a function to be called instead of panicking when `self` is
+    /// dropped with live allocations.
+    #[cfg(feature = "debug-leak-check")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "debug-leak-check")))]
+    #[inline]
+    pub fn set_leak_reporter(&mut self, reporter: LeakReporter) {
+        self.leak_check.set_reporter(reporter);
+    }
+
+    /// Set the secret the links of the free block lists are encoded with.
+    /// See [`Tlsf::set_link_secret`].
+    #[cfg(feature = "safe-linking")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "safe-linking")))]
+    #[inline]
+    pub fn set_link_secret(&mut self, secret: usize) {
+        self.tlsf.set_link_secret(secret);
+    }
+
+    /// Make allocations randomize the heap layout using `random_source`.
+    /// See [`Tlsf::set_random_source`].
+    #[cfg(feature = "randomize")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "randomize")))]
+    #[inline]
+    pub fn set_random_source(&mut self, random_source: RandomSource) {
+        self.tlsf.set_random_source(random_source);
+    }
+
+    /// Limit the number of bytes zeroed when a memory block is freed.
+    /// See [`Tlsf::set_zero_on_free_limit`].
+    #[cfg(feature = "zero-on-free")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "zero-on-free")))]
+    #[inline]
+    pub fn set_zero_on_free_limit(&mut self, limit: usize) {
+        self.tlsf.set_zero_on_free_limit(limit);
+    }
+
+    /// Check the integrity of the heap when `self` is dropped.
+    ///
+    /// This only has an effect in builds with debug assertions enabled. When
+    /// enabled, [`Tlsf::check_integrity`] runs over all memory pools before
+    /// they are released, and a corrupted heap is reported through the
+    /// corruption handler (a panic by default). This catches programs that
+    /// corrupt the allocator's data structures without ever tripping over
+    /// them.
+    ///
+    /// The check is skipped if the source doesn't support deallocation (in
+    /// which case the memory pools can't be enumerated), if [`Self::adopt_pool`]
+    /// was called with a memory pool that isn't passed to the source on drop,
+    /// or if the thread is already panicking.
+    ///
+    /// [`Tlsf`] doesn't own its memory pools and thus can't do this on drop.
+    /// Use [`Tlsf::check_integrity`] or [`CheckedTlsf`](crate::CheckedTlsf)
+    /// instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::{FlexTlsf, GlobalAllocAsFlexSource};
+    /// use std::alloc::{Layout, System};
+    ///
+    /// let mut tlsf: FlexTlsf<GlobalAllocAsFlexSource<System, 1024>, u16, u16, 12, 16> =
+    ///     FlexTlsf::new(GlobalAllocAsFlexSource(System));
+    /// tlsf.set_verify_on_drop(true);
+    ///
+    /// let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+    /// unsafe { tlsf.deallocate(ptr, 8) };
+    ///
+    /// // The heap is checked here
+    /// drop(tlsf);
+    /// ```
+    #[inline]
+    pub fn set_verify_on_drop(&mut self, enable: bool) {
+        self.verify_on_drop = if enable {
+            Some(Self::verify_heap_on_drop)
+        } else {
+            None
+        };
+    }
+
+    /// Perform the check enabled by [`Self::set_verify_on_drop`].
+    fn verify_heap_on_drop(&self) {
+        if !cfg!(debug_assertions) || !self.source.use_pool_ftr() || self.has_unlinked_pools {
+            return;
+        }
+
+        #[cfg(feature = "std")]
+        if std::thread::panicking() {
+            return;
+        }
+
+        let pools = Self::iter_alloc_list(self.growable_pool, self.source.min_align())
+            .chain(Self::iter_alloc_list(self.adopted_pool, 1));
+
+        // Safety: `pools` contains all memory pools owned by `self.tlsf`, and
+        //         the trailing bytes after each memory pool are shorter than
+        //         `GRANULARITY * 2` bytes
+        if let Err(error) = unsafe { self.tlsf.check_integrity_iter(pools) } {
+            report_corruption(HeapError::IntegrityViolation { error });
+        }
+    }
+
+    /// Enumerate the memory blocks obtained from `self.source`, starting from
+    /// the most recent one.
+    #[cfg(feature = "unstable")]
+    fn iter_allocs(&self) -> impl Iterator<Item = NonNull<[u8]>> + '_ {
+        debug_assert!(self.source.use_pool_ftr());
+        Self::iter_alloc_list(self.growable_pool, self.source.min_align())
+    }
+
+    /// Enumerate the memory blocks in a list formed through `PoolFtr`,
+    /// starting from `pool`.
+    fn iter_alloc_list(
+        pool: Option<Pool>,
+        align: usize,
+    ) -> impl Iterator<Item = NonNull<[u8]>> + Clone {
+        let mut cur_alloc_or_none =
+            pool.map(|p| nonnull_slice_from_raw_parts(p.alloc_start, p.alloc_len));
+
+        core::iter::from_fn(move || {
+            let cur_alloc = cur_alloc_or_none?;
+            // Safety: We control the referenced pool footer
+            cur_alloc_or_none = unsafe { *PoolFtr::get_for_alloc(cur_alloc, align) }.prev_alloc;
+            Some(cur_alloc)
+        })
+    }
+
+    /// Report how much of each memory pool is handed out to the callers
+    /// and how much is consumed by the allocator's metadata and padding.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(num_blocks)`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::{FlexTlsf, GlobalAllocAsFlexSource};
+    /// use std::alloc::{Layout, System};
+    ///
+    /// let mut tlsf: FlexTlsf<GlobalAllocAsFlexSource<System, 1024>, u16, u16, 12, 16> =
+    ///     FlexTlsf::new(GlobalAllocAsFlexSource(System));
+    /// let ptr = tlsf.allocate(Layout::new::<[u8; 100]>()).unwrap();
+    ///
+    /// let (allocated_bytes, overhead_bytes) = tlsf
+    ///     .pool_overheads()
+    ///     .fold((0, 0), |(a, o), p| (a + p.allocated_bytes, o + p.overhead_bytes));
+    /// assert!(allocated_bytes >= 100);
+    /// assert!(overhead_bytes > 0);
+    ///
+    /// unsafe { tlsf.deallocate(ptr, 1) };
+    /// ```
+    #[cfg(feature = "unstable")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "unstable")))]
+    pub fn pool_overheads(&self) -> impl Iterator<Item = PoolOverhead> + '_ {
+        self.iter_allocs().map(move |alloc| {
+            let mut overhead = PoolOverhead {
+                alloc,
+                num_allocations: 0,
+                allocated_bytes: 0,
+                free_bytes: 0,
+                overhead_bytes: 0,
+            };
+
+            // Safety: `alloc` contains a memory pool that belongs to
+            //         `self.tlsf`, followed by less than `GRANULARITY * 2`
+            //         unused bytes
+            for block in unsafe { self.tlsf.iter_blocks(alloc) } {
+                if block.is_occupied() {
+                    overhead.num_allocations += 1;
+                    overhead.allocated_bytes += block.max_payload_size();
+                } else {
+                    overhead.free_bytes += block.size();
+                }
+            }
+
+            overhead.overhead_bytes =
+                nonnull_slice_len(alloc) - overhead.allocated_bytes - overhead.free_bytes;
+            overhead
+        })
+    }
+
     /// Attempt to allocate a block of memory.
     ///
     /// Returns the starting address of the allocated memory block on success;
@@ -309,13 +1214,171 @@ impl<
     /// do so as well).
     #[cfg_attr(target_arch = "wasm32", inline(never))]
     pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        self.try_allocate(layout).ok()
+    }
+
+    /// Attempt to allocate a block of memory, reporting the reason of failure.
+    ///
+    /// This is identical to [`Self::allocate`] except for the return type.
+    /// The error includes the one reported by [`FlexSource::try_alloc`] when
+    /// the memory pool could not be grown.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in constant time (assuming `Source`'s methods
+    /// do so as well).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::{AllocError, FlexTlsf, SourceError};
+    /// use std::alloc::Layout;
+    ///
+    /// // A source that never provides memory
+    /// struct NoSource;
+    /// unsafe impl rlsf::FlexSource for NoSource {}
+    ///
+    /// let mut tlsf: FlexTlsf<NoSource, u16, u16, 12, 16> = FlexTlsf::new(NoSource);
+    /// assert_eq!(
+    ///     tlsf.try_allocate(Layout::new::<u64>()),
+    ///     Err(AllocError::Source(SourceError::Exhausted)),
+    /// );
+    /// ```
+    #[inline]
+    pub fn try_allocate(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        self.allocate_tracked(layout, 0)
+    }
+
+    /// Attempt to allocate a block of memory, letting the memory pool grow
+    /// by `expected_future_bytes` more bytes than necessary if it needs to be
+    /// grown.
+    ///
+    /// A caller that is about to make a burst of allocations can use this
+    /// method to have the first allocation obtain memory for the whole burst
+    /// in one go instead of having the source called for each of them.
+    /// `expected_future_bytes` is only a hint. If the source can't provide
+    /// the extra bytes, this method falls back to growing the memory pool
+    /// just enough for `layout`.
+    ///
+    /// Returns the starting address of the allocated memory block on success;
+    /// `None` otherwise.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in constant time (assuming `Source`'s methods
+    /// do so as well).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::{FlexTlsf, GlobalAllocAsFlexSource};
+    /// use std::alloc::{Layout, System};
+    ///
+    /// let mut tlsf: FlexTlsf<GlobalAllocAsFlexSource<System, 1024>, u16, u16, 12, 16> =
+    ///     FlexTlsf::new(GlobalAllocAsFlexSource(System));
+    /// let layout = Layout::new::<[u8; 256]>();
+    ///
+    /// // The first allocation obtains memory for all of them
+    /// let first = tlsf.allocate_with_hint(layout, 256 * 16).unwrap();
+    /// let capacity = tlsf.capacity();
+    /// let rest: Vec<_> = (0..8).map(|_| tlsf.allocate(layout).unwrap()).collect();
+    /// assert_eq!(tlsf.capacity(), capacity);
+    ///
+    /// for ptr in rest.into_iter().chain([first]) {
+    ///     unsafe { tlsf.deallocate(ptr, layout.align()) };
+    /// }
+    /// ```
+    #[cfg_attr(target_arch = "wasm32", inline(never))]
+    pub fn allocate_with_hint(
+        &mut self,
+        layout: Layout,
+        expected_future_bytes: usize,
+    ) -> Option<NonNull<u8>> {
+        self.allocate_tracked(layout, expected_future_bytes).ok()
+    }
+
+    #[inline]
+    fn allocate_tracked(
+        &mut self,
+        layout: Layout,
+        future_bytes: usize,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let result = self.allocate_untraced(layout, future_bytes);
+        trace_op!(
+            "FlexTlsf::allocate(size={}, align={}) -> {:#x}",
+            layout.size(),
+            layout.align(),
+            result.map_or(0, |p| p.as_ptr() as usize)
+        );
+        result
+    }
+
+    /// [`Self::allocate_tracked`] without emitting a trace record.
+    #[inline]
+    fn allocate_untraced(
+        &mut self,
+        layout: Layout,
+        future_bytes: usize,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let result = self.allocate_inner(layout, future_bytes);
+        #[cfg(feature = "stats")]
+        if result.is_err() {
+            self.failed_allocations = self.failed_allocations.wrapping_add(1);
+        }
+        #[cfg(feature = "oom-report")]
+        if let (Err(_), Some(hook)) = (&result, self.oom_hook) {
+            hook(self.tlsf.oom_report(layout));
+        }
+        let ptr = result?;
+
+        // Safety: `ptr` was just allocated with `layout`
+        #[cfg(feature = "debug-leak-check")]
+        self.leak_check.on_alloc(unsafe {
+            Tlsf::<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(
+                ptr,
+                layout.align(),
+            )
+        });
+
+        Ok(ptr)
+    }
+
+    #[inline]
+    fn allocate_inner(
+        &mut self,
+        layout: Layout,
+        future_bytes: usize,
+    ) -> Result<NonNull<u8>, AllocError> {
+        if self.is_huge(layout) {
+            return self.allocate_huge(layout);
+        }
+
         if let Some(x) = self.tlsf.allocate(layout) {
-            return Some(x);
+            return Ok(x);
         }
 
-        self.increase_pool_to_contain_allocation(layout)?;
+        // Allocations too large for any memory pool can only be satisfied by
+        // a dedicated memory block
+        if self.source.supports_dealloc()
+            && Tlsf::<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>::pool_size_to_contain_allocation(
+                layout,
+            )
+            .is_none()
+        {
+            return self.allocate_huge(layout);
+        }
 
-        self.tlsf.allocate(layout).or_else(|| {
+        // Try to make room for the future allocations as well. If that's not
+        // possible, settle for the current one.
+        if future_bytes == 0
+            || self
+                .increase_pool_to_contain_allocation(layout, future_bytes)
+                .is_err()
+        {
+            self.increase_pool_to_contain_allocation(layout, 0)?;
+        }
+
+        self.tlsf.allocate(layout).ok_or_else(|| {
             // Not a hard error, but it's still unexpected because
             // `increase_pool_to_contain_allocation` was supposed to make this
             // allocation possible
@@ -324,14 +1387,18 @@ impl<
                 "the allocation failed despite the effort by \
                 `increase_pool_to_contain_allocation`"
             );
-            None
+            SourceError::Exhausted.into()
         })
     }
 
     /// Increase the amount of memory pool to guarantee the success of the
-    /// given allocation. Returns `Some(())` on success.
+    /// given allocation, plus `future_bytes` bytes of headroom.
     #[inline]
-    fn increase_pool_to_contain_allocation(&mut self, layout: Layout) -> Option<()> {
+    fn increase_pool_to_contain_allocation(
+        &mut self,
+        layout: Layout,
+        future_bytes: usize,
+    ) -> Result<(), AllocError> {
         let use_growable_pool = self.source.use_growable_pool();
 
         // How many extra bytes we need to get from the source for the
@@ -339,7 +1406,23 @@ impl<
         let extra_bytes_well_aligned =
             Tlsf::<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>::pool_size_to_contain_allocation(
                 layout,
-            )?;
+            )
+            .and_then(|x| {
+                // Round `future_bytes` up to a multiple of `GRANULARITY` to
+                // keep `extra_bytes_well_aligned` well-aligned
+                let future_bytes = future_bytes.checked_add(GRANULARITY - 1)? & !(GRANULARITY - 1);
+                x.checked_add(future_bytes)
+            })
+            .and_then(|x| {
+                // `PoolFtr` is excluded from the memory pool (see
+                // `PoolFtr::pool_area`)
+                if cfg!(feature = "pool-guards") && self.source.use_pool_ftr() {
+                    x.checked_add(GRANULARITY)
+                } else {
+                    Some(x)
+                }
+            })
+            .ok_or(AllocError::SizeOverflow)?;
 
         // The sentinel block + the block to store the allocation
         debug_assert!(extra_bytes_well_aligned >= GRANULARITY * 2);
@@ -348,7 +1431,8 @@ impl<
             // Try to extend an existing memory pool first.
             let new_pool_len_desired = growable_pool
                 .pool_len
-                .checked_add(extra_bytes_well_aligned)?;
+                .checked_add(extra_bytes_well_aligned)
+                .ok_or(AllocError::SizeOverflow)?;
 
             // The following assertion should not trip because...
             //  - `extra_bytes_well_aligned` returns a value that is at least
@@ -356,21 +1440,41 @@ impl<
             //  - `growable_pool.alloc_len - growable_pool.pool_len` must be
             //    less than `GRANULARITY * 2` because of
             //    `insert_free_block_ptr`'s implementation.
-            debug_assert!(new_pool_len_desired >= growable_pool.alloc_len);
+            debug_assert!(new_pool_len_desired > growable_pool.alloc_len);
 
-            // Safety: `new_pool_end_desired >= growable_pool.alloc_len`, and
+            let min_extra = new_pool_len_desired - growable_pool.alloc_len;
+            let within_capacity = min_extra <= self.remaining_capacity();
+
+            // Safety: `new_pool_end_desired > growable_pool.alloc_len`, and
             //         `(growable_pool.alloc_start, growable_pool.alloc_len)`
             //         represents a previous allocation.
-            if let Some(new_alloc_len) = unsafe {
-                self.source.realloc_inplace_grow(
-                    nonnull_slice_from_raw_parts(
-                        growable_pool.alloc_start,
-                        growable_pool.alloc_len,
-                    ),
-                    new_pool_len_desired,
-                )
-            } {
-                if self.source.supports_dealloc() {
+            let appended = if within_capacity {
+                unsafe {
+                    self.source.alloc_contiguous_with(
+                        nonnull_slice_from_raw_parts(
+                            growable_pool.alloc_start,
+                            growable_pool.alloc_len,
+                        ),
+                        min_extra,
+                    )
+                }
+            } else {
+                None
+            };
+
+            if let Some(appended) = appended {
+                debug_assert_eq!(
+                    nonnull_slice_start(appended).as_ptr(),
+                    growable_pool
+                        .alloc_start
+                        .as_ptr()
+                        .wrapping_add(growable_pool.alloc_len),
+                    "`alloc_contiguous_with` returned a non-adjacent memory block"
+                );
+                let new_alloc_len = growable_pool.alloc_len + nonnull_slice_len(appended);
+                self.capacity = self.capacity.saturating_add(nonnull_slice_len(appended));
+
+                if self.source.use_pool_ftr() {
                     // Move `PoolFtr`. Note that `PoolFtr::alloc_start` is
                     // still uninitialized because this allocation is still in
                     // `self.growable_pool`, so we only have to move
@@ -401,19 +1505,26 @@ impl<
                             .as_ptr()
                             .wrapping_add(growable_pool.pool_len),
                     );
+                    let mut append_len = new_alloc_len - growable_pool.pool_len;
+                    if self.source.use_pool_ftr() {
+                        append_len = nonnull_slice_len(PoolFtr::pool_area(
+                            nonnull_slice_from_raw_parts(growable_pool.alloc_start, new_alloc_len),
+                            self.source.min_align(),
+                        )) - growable_pool.pool_len;
+                    }
                     // Safety: `append_start` follows an existing memory pool,
                     //         and the contained bytes are owned by us
                     self.tlsf
                         .append_free_block_ptr(nonnull_slice_from_raw_parts(
                             append_start,
-                            new_alloc_len - growable_pool.pool_len,
+                            append_len,
                         ))
                 };
 
                 // This assumption is based on `extra_bytes_well_aligned`'s
                 // implementation. The `debug_assert!` above depends on this.
                 debug_assert!(
-                    (growable_pool.pool_len + num_appended_len) - new_alloc_len < GRANULARITY * 2
+                    new_alloc_len - (growable_pool.pool_len + num_appended_len) < GRANULARITY * 2
                 );
 
                 self.growable_pool = Some(Pool {
@@ -422,14 +1533,18 @@ impl<
                     pool_len: growable_pool.pool_len + num_appended_len,
                 });
 
-                return Some(());
-            } // if let Some(new_alloc_len) = ... realloc_inplace_grow
+                return Ok(());
+            } // if let Some(appended) = ... alloc_contiguous_with
 
             if self.source.is_contiguous_growable() {
                 // `is_contiguous_growable`
                 // indicates that `alloc` will also be fruitless because
-                // `realloc_inplace_grow` failed.
-                return None;
+                // `alloc_contiguous_with` failed.
+                return Err(if within_capacity {
+                    SourceError::Exhausted.into()
+                } else {
+                    AllocError::CapacityExceeded
+                });
             }
         } // if let Some(growable_pool) = self.growable_pool
 
@@ -451,23 +1566,37 @@ impl<
             //                       ╰───┬───╯
             //                      GRANULARITY
             //
-            extra_bytes_well_aligned.checked_add(GRANULARITY)?
+            extra_bytes_well_aligned
+                .checked_add(GRANULARITY)
+                .ok_or(AllocError::SizeOverflow)?
         } else {
             extra_bytes_well_aligned
         };
 
-        // Safety: `extra_bytes` is non-zero and aligned to `GRANULARITY` bytes
-        let alloc = unsafe { self.source.alloc(extra_bytes)? };
+        if extra_bytes > self.remaining_capacity() {
+            return Err(AllocError::CapacityExceeded);
+        }
 
-        let is_well_aligned = self.source.min_align() >= super::GRANULARITY;
+        // Safety: `extra_bytes` is non-zero and aligned to `GRANULARITY` bytes
+        let alloc = unsafe { self.source.try_alloc(extra_bytes)? };
+        self.capacity = self.capacity.saturating_add(nonnull_slice_len(alloc));
+
+        // `PoolFtr::pool_area` doesn't preserve the alignment of the end
+        let is_well_aligned = self.source.min_align() >= super::GRANULARITY
+            && !(cfg!(feature = "pool-guards") && self.source.use_pool_ftr());
+        let pool_area = if self.source.use_pool_ftr() {
+            PoolFtr::pool_area(alloc, self.source.min_align())
+        } else {
+            alloc
+        };
 
         // Safety: The passed memory block is what we acquired from
         //         `self.source`, so we have the ownership
         let pool_len = unsafe {
             if is_well_aligned {
-                self.tlsf.insert_free_block_ptr_aligned(alloc)
+                self.tlsf.insert_free_block_ptr_aligned(pool_area)
             } else {
-                self.tlsf.insert_free_block_ptr(alloc)
+                self.tlsf.insert_free_block_ptr(pool_area)
             }
         }
         .unwrap_or_else(|| unsafe {
@@ -477,7 +1606,7 @@ impl<
         })
         .get();
 
-        if self.source.supports_dealloc() {
+        if self.source.use_pool_ftr() {
             // Link the new memory pool's `PoolFtr::prev_alloc_end` to the
             // previous pool (`self.growable_pool`).
             let pool_ftr = PoolFtr::get_for_alloc(alloc, self.source.min_align());
@@ -489,7 +1618,7 @@ impl<
             unsafe { (*pool_ftr).prev_alloc = prev_alloc };
         }
 
-        if use_growable_pool {
+        if use_growable_pool || self.source.use_pool_ftr() {
             self.growable_pool = Some(Pool {
                 alloc_start: nonnull_slice_start(alloc),
                 alloc_len: nonnull_slice_len(alloc),
@@ -497,7 +1626,74 @@ impl<
             });
         }
 
-        Some(())
+        Ok(())
+    }
+
+    /// Allocate a memory block from a dedicated memory block obtained from the
+    /// source.
+    #[cold]
+    fn allocate_huge(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        let size =
+            Tlsf::<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>::standalone_allocation_size(layout)
+                .ok_or(AllocError::SizeOverflow)?;
+
+        // Allocate extra bytes if the allocation might not be well-aligned.
+        // See `increase_pool_to_contain_allocation`.
+        let alloc_size = if self.source.min_align() < GRANULARITY {
+            size.checked_add(GRANULARITY)
+                .ok_or(AllocError::SizeOverflow)?
+        } else {
+            size
+        };
+
+        if alloc_size > self.remaining_capacity() {
+            return Err(AllocError::CapacityExceeded);
+        }
+
+        // Safety: `alloc_size` is non-zero and aligned to `GRANULARITY` bytes
+        let alloc = unsafe { self.source.try_alloc(alloc_size)? };
+        self.capacity = self.capacity.saturating_add(nonnull_slice_len(alloc));
+
+        // Round up the starting address
+        let unaligned_start = nonnull_slice_start(alloc).as_ptr() as usize;
+        let start = unaligned_start.wrapping_add(GRANULARITY - 1) & !(GRANULARITY - 1);
+
+        // Safety: `start..start + size` is within `alloc`, which we own
+        let (ptr, ftr) = unsafe {
+            Tlsf::<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>::create_standalone_allocation(
+                nonnull_slice_from_raw_parts(
+                    NonNull::new_unchecked(ptr_with_addr(
+                        nonnull_slice_start(alloc).as_ptr(),
+                        start,
+                    )),
+                    size,
+                ),
+                layout,
+            )
+        };
+
+        // Safety: `ftr` points to `GRANULARITY / 2` bytes we control
+        unsafe { *ftr.cast::<HugeFtr>().as_ptr() = HugeFtr { alloc } };
+
+        self.num_huge_allocations += 1;
+
+        Ok(ptr)
+    }
+
+    /// Return an allocation made by [`Self::allocate_huge`] to the source.
+    ///
+    /// # Safety
+    ///
+    /// `ftr` must be the footer of a live allocation made by
+    /// `Self::allocate_huge`.
+    #[cold]
+    unsafe fn deallocate_huge(&mut self, ftr: NonNull<u8>) {
+        let HugeFtr { alloc } = *ftr.cast::<HugeFtr>().as_ptr();
+        self.num_huge_allocations -= 1;
+        self.capacity = self.capacity.saturating_sub(nonnull_slice_len(alloc));
+
+        // Safety: It's an allocation we allocated from `self.source`
+        self.source.dealloc(alloc);
     }
 
     /// Deallocate a previously allocated memory block.
@@ -515,6 +1711,37 @@ impl<
     ///
     #[cfg_attr(target_arch = "wasm32", inline(never))]
     pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, align: usize) {
+        trace_op!(
+            "FlexTlsf::deallocate(ptr={:#x}, align={})",
+            ptr.as_ptr() as usize,
+            align
+        );
+        self.deallocate_untraced(ptr, align);
+    }
+
+    /// [`Self::deallocate`] without emitting a trace record.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::deallocate`].
+    #[inline]
+    unsafe fn deallocate_untraced(&mut self, ptr: NonNull<u8>, align: usize) {
+        #[cfg(feature = "debug-leak-check")]
+        self.leak_check.on_dealloc(
+            Tlsf::<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(ptr, align),
+        );
+
+        if self.num_huge_allocations != 0 {
+            // Safety: Upheld by the caller
+            if let Some(ftr) =
+                Tlsf::<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>::standalone_allocation_ftr(
+                    ptr, align,
+                )
+            {
+                return self.deallocate_huge(ftr);
+            }
+        }
+
         // Safety: Upheld by the caller
         self.tlsf.deallocate(ptr, align)
     }
@@ -534,10 +1761,69 @@ impl<
     ///  - `ptr` must denote a memory block previously allocated via `self`.
     ///
     pub(crate) unsafe fn deallocate_unknown_align(&mut self, ptr: NonNull<u8>) {
+        trace_op!(
+            "FlexTlsf::deallocate(ptr={:#x}, align=0)",
+            ptr.as_ptr() as usize
+        );
+        #[cfg(feature = "debug-leak-check")]
+        self.leak_check
+            .on_dealloc(Self::size_of_allocation_unknown_align(ptr));
+
+        if self.num_huge_allocations != 0 {
+            // Safety: Upheld by the caller
+            if let Some(ftr) = Tlsf::<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>::standalone_allocation_ftr_unknown_align(ptr) {
+                return self.deallocate_huge(ftr);
+            }
+        }
+
         // Safety: Upheld by the caller
         self.tlsf.deallocate_unknown_align(ptr)
     }
 
+    /// Deallocate a previously allocated memory block with alignment `align`
+    /// (or an unknown alignment if `None`), and return the payload of the
+    /// free memory block it was merged into. Returns `None` if the memory
+    /// block was returned to the source instead.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::deallocate`] and [`Self::deallocate_unknown_align`].
+    pub(crate) unsafe fn deallocate_and_get_free_payload(
+        &mut self,
+        ptr: NonNull<u8>,
+        align: Option<usize>,
+    ) -> Option<NonNull<[u8]>> {
+        trace_op!(
+            "FlexTlsf::deallocate(ptr={:#x}, align={})",
+            ptr.as_ptr() as usize,
+            align.unwrap_or(0)
+        );
+        #[cfg(feature = "debug-leak-check")]
+        self.leak_check.on_dealloc(if let Some(align) = align {
+            Self::size_of_allocation(ptr, align)
+        } else {
+            Self::size_of_allocation_unknown_align(ptr)
+        });
+
+        if self.num_huge_allocations != 0 {
+            // Safety: Upheld by the caller
+            let ftr = if let Some(align) = align {
+                Tlsf::<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>::standalone_allocation_ftr(
+                    ptr, align,
+                )
+            } else {
+                Tlsf::<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>::standalone_allocation_ftr_unknown_align(ptr)
+            };
+            if let Some(ftr) = ftr {
+                self.deallocate_huge(ftr);
+                return None;
+            }
+        }
+
+        // Safety: Upheld by the caller
+        Some(self.tlsf.deallocate_and_get_free_payload(ptr, align))
+    }
+
     /// Get the actual usable size of a previously allocated memory block.
     ///
     /// # Safety
@@ -573,6 +1859,28 @@ impl<
         &mut self,
         ptr: NonNull<u8>,
         new_layout: Layout,
+    ) -> Option<NonNull<u8>> {
+        let new_ptr = self.reallocate_untraced(ptr, new_layout);
+        trace_op!(
+            "FlexTlsf::reallocate(ptr={:#x}, size={}, align={}) -> {:#x}",
+            ptr.as_ptr() as usize,
+            new_layout.size(),
+            new_layout.align(),
+            new_ptr.map_or(0, |p| p.as_ptr() as usize)
+        );
+        new_ptr
+    }
+
+    /// [`Self::reallocate`] without emitting a trace record.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::reallocate`].
+    #[inline]
+    unsafe fn reallocate_untraced(
+        &mut self,
+        ptr: NonNull<u8>,
+        new_layout: Layout,
     ) -> Option<NonNull<u8>> {
         // Do this early so that the compiler can de-duplicate the evaluation of
         // `size_of_allocation`, which is done here as well as in
@@ -582,27 +1890,106 @@ impl<
             new_layout.align(),
         );
 
+        // Huge allocations are moved to a new place unconditionally
+        let is_huge = self.num_huge_allocations != 0
+            && Tlsf::<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>::standalone_allocation_ftr(
+                ptr,
+                new_layout.align(),
+            )
+            .is_some();
+        if is_huge || self.is_huge(new_layout) {
+            let new_ptr = self.allocate_untraced(new_layout, 0).ok()?;
+            core::ptr::copy_nonoverlapping(
+                ptr.as_ptr(),
+                new_ptr.as_ptr(),
+                old_size.min(new_layout.size()),
+            );
+            self.deallocate_untraced(ptr, new_layout.align());
+            return Some(new_ptr);
+        }
+
         // Safety: Upheld by the caller
         if let Some(x) = self.tlsf.reallocate(ptr, new_layout) {
+            #[cfg(feature = "debug-leak-check")]
+            {
+                self.leak_check.on_dealloc(old_size);
+                self.leak_check.on_alloc(
+                    Tlsf::<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(
+                        x,
+                        new_layout.align(),
+                    ),
+                );
+            }
+
             return Some(x);
         }
 
         // Allocate a whole new memory block. The following code section looks
         // the same as the one in `Tlsf::reallocate`, but `self.allocation`
-        // here refers to `FlexTlsf::allocate`, which inserts new meory pools
-        // as necessary.
-        let new_ptr = self.allocate(new_layout)?;
+        // here refers to `FlexTlsf::allocate_untraced`, which inserts new
+        // meory pools as necessary.
+        let new_ptr = self.allocate_untraced(new_layout, 0).ok()?;
 
         // Move the existing data into the new location
         debug_assert!(new_layout.size() >= old_size);
         core::ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), old_size);
 
         // Deallocate the old memory block.
-        self.deallocate(ptr, new_layout.align());
+        self.deallocate_untraced(ptr, new_layout.align());
 
         Some(new_ptr)
     }
 
+    /// Shrink or grow a previously allocated memory block like
+    /// [`Self::reallocate`] after checking that `old_layout` agrees with its
+    /// header. See [`Tlsf::reallocate_checked`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a memory block previously allocated via `self`.
+    pub unsafe fn reallocate_checked(
+        &mut self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<Option<NonNull<u8>>, LayoutMismatch> {
+        Tlsf::<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>::check_layout(
+            ptr, old_layout, new_layout,
+        )?;
+        Ok(self.reallocate(ptr, new_layout))
+    }
+
+    /// Check the allocation `ptr` for signs of heap corruption. See
+    /// [`Tlsf::check_allocation`].
+    ///
+    /// # Safety
+    ///
+    /// See [`Tlsf::check_allocation`].
+    #[cfg(feature = "hardened")]
+    #[inline]
+    pub(crate) unsafe fn check_allocation(
+        ptr: NonNull<u8>,
+        align: Option<usize>,
+    ) -> Result<(), &'static str> {
+        // Safety: Upheld by the caller
+        Tlsf::<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>::check_allocation(ptr, align)
+    }
+
+    /// Get the payload size of the allocation. The returned size might be
+    /// larger than the size specified at the allocation time.
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `Self`.
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `align`.
+    ///
+    #[inline]
+    pub(crate) unsafe fn size_of_allocation(ptr: NonNull<u8>, align: usize) -> usize {
+        // Safety: Upheld by the caller
+        Tlsf::<'static, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(ptr, align)
+    }
+
     /// Get the payload size of the allocation with an unknown alignment. The
     /// returned size might be larger than the size specified at the allocation
     /// time.
@@ -622,23 +2009,32 @@ impl<Source: FlexSource, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: us
     for FlexTlsf<Source, FLBitmap, SLBitmap, FLLEN, SLLEN>
 {
     fn drop(&mut self) {
+        if let Some(verify_on_drop) = self.verify_on_drop {
+            verify_on_drop(self);
+        }
+
         if self.source.supports_dealloc() {
             debug_assert!(self.source.use_growable_pool());
 
-            // Deallocate all memory pools
-            let align = self.source.min_align();
-            let mut cur_alloc_or_none = self
-                .growable_pool
-                .map(|p| nonnull_slice_from_raw_parts(p.alloc_start, p.alloc_len));
+            // Deallocate all memory pools, including the adopted ones
+            for (pool, align) in [
+                (self.growable_pool, self.source.min_align()),
+                (self.adopted_pool, 1),
+            ] {
+                let mut cur_alloc_or_none =
+                    pool.map(|p| nonnull_slice_from_raw_parts(p.alloc_start, p.alloc_len));
 
-            while let Some(cur_alloc) = cur_alloc_or_none {
-                // Safety: We control the referenced pool footer
-                let cur_ftr = unsafe { *PoolFtr::get_for_alloc(cur_alloc, align) };
+                while let Some(cur_alloc) = cur_alloc_or_none {
+                    // Safety: We control the referenced pool footer
+                    let cur_ftr = unsafe { *PoolFtr::get_for_alloc(cur_alloc, align) };
 
-                // Safety: It's an allocation we allocated from `self.source`
-                unsafe { self.source.dealloc(cur_alloc) };
+                    // Safety: It's an allocation we allocated from
+                    //         `self.source` or one that was handed over to
+                    //         us with its ownership
+                    unsafe { self.source.dealloc(cur_alloc) };
 
-                cur_alloc_or_none = cur_ftr.prev_alloc;
+                    cur_alloc_or_none = cur_ftr.prev_alloc;
+                }
             }
         }
     }
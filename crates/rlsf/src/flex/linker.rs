@@ -0,0 +1,140 @@
+//! A [`FlexSource`] backed by a linker-defined heap region
+use const_default1::ConstDefault;
+use core::{
+    ptr::{addr_of, NonNull},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::FlexSource;
+use crate::utils::nonnull_slice_from_raw_parts;
+
+extern "C" {
+    /// The start of the heap region. Provided by the linker script.
+    static __sheap: u8;
+    /// The end of the heap region. Provided by the linker script.
+    static __eheap: u8;
+}
+
+/// Set when the heap region is handed out to a [`LinkerHeapSource`].
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+cfg_if::cfg_if! {
+    if #[cfg(target_has_atomic = "8")] {
+        /// Set [`TAKEN`] and return its previous value.
+        #[inline]
+        fn take() -> bool {
+            TAKEN.swap(true, Ordering::Relaxed)
+        }
+    } else if #[cfg(feature = "critical-section")] {
+        /// Set [`TAKEN`] and return its previous value.
+        ///
+        /// This target lacks compare-and-swap instructions (e.g., `thumbv6m`,
+        /// `riscv32i`), so only loads and stores are atomic.
+        #[inline]
+        fn take() -> bool {
+            critical_section::with(|_| {
+                let taken = TAKEN.load(Ordering::Relaxed);
+                TAKEN.store(true, Ordering::Relaxed);
+                taken
+            })
+        }
+    } else {
+        compile_error!(
+            "`LinkerHeapSource` requires the `critical-section` feature on \
+            targets without atomic compare-and-swap instructions"
+        );
+    }
+}
+
+/// A [`FlexSource`] that provides the memory region between the linker
+/// symbols `__sheap` and `__eheap`.
+///
+/// The whole region is handed out by the first successful call to
+/// [`FlexSource::alloc`]. The region is handed out at most once per program,
+/// even if there are multiple instances of `LinkerHeapSource`, and is never
+/// grown or released afterwards.
+///
+/// The symbols must be defined by the linker script, for example:
+///
+/// ```text
+/// SECTIONS {
+///     .heap (NOLOAD) : ALIGN(16) {
+///         __sheap = .;
+///         . += 0x8000;
+///         __eheap = .;
+///     } > RAM
+/// }
+/// ```
+///
+/// The linker script must guarantee that the region is not used for any other
+/// purposes and that `__sheap <= __eheap`.
+///
+/// On targets without atomic compare-and-swap instructions (e.g.,
+/// `thumbv6m-none-eabi`, `riscv32i-unknown-none-elf`), the `critical-section`
+/// feature must be enabled as well.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use rlsf::{FlexTlsf, LinkerHeapSource};
+/// use std::alloc::Layout;
+///
+/// let mut tlsf: FlexTlsf<LinkerHeapSource, u16, u16, 12, 16> =
+///     FlexTlsf::new(LinkerHeapSource::new());
+/// let ptr = tlsf.allocate(Layout::new::<u64>());
+/// ```
+#[derive(Debug)]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "linker-heap")))]
+pub struct LinkerHeapSource(());
+
+impl LinkerHeapSource {
+    /// Construct a `LinkerHeapSource`.
+    #[inline]
+    pub const fn new() -> Self {
+        Self(())
+    }
+
+    /// Get the address range of the heap region.
+    #[inline]
+    fn region() -> (usize, usize) {
+        // Safety: We are only taking the symbols' addresses
+        unsafe { (addr_of!(__sheap) as usize, addr_of!(__eheap) as usize) }
+    }
+}
+
+impl Default for LinkerHeapSource {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConstDefault for LinkerHeapSource {
+    const DEFAULT: Self = Self::new();
+}
+
+unsafe impl FlexSource for LinkerHeapSource {
+    #[inline]
+    unsafe fn alloc(&mut self, min_size: usize) -> Option<NonNull<[u8]>> {
+        let (start, end) = Self::region();
+        let len = end.checked_sub(start)?;
+
+        // Don't consume the region if it can't satisfy the request. A later,
+        // smaller request might still fit.
+        if len < min_size || take() {
+            return None;
+        }
+
+        Some(nonnull_slice_from_raw_parts(
+            NonNull::new(start as *mut u8)?,
+            len,
+        ))
+    }
+
+    #[inline]
+    fn min_align(&self) -> usize {
+        let (start, _) = Self::region();
+        // The largest power of two dividing `start`
+        (start & start.wrapping_neg()).max(1)
+    }
+}
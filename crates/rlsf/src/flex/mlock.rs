@@ -0,0 +1,109 @@
+//! A [`FlexSource`] wrapper locking memory blocks into RAM
+use const_default1::ConstDefault;
+use core::ptr::NonNull;
+
+use super::{FlexSource, SourceError};
+use crate::utils::{last_os_error, nonnull_slice_len, nonnull_slice_start, ptr_with_addr};
+
+/// Wraps a [`FlexSource`] to lock every memory block obtained from it into RAM
+/// by `mlock`, so that the memory pools never cause page faults.
+///
+/// A memory block is unlocked by `munlock` before it's returned to the inner
+/// source. Memory pages shared with other memory blocks stay locked.
+///
+/// If a memory block can't be locked (e.g., because `RLIMIT_MEMLOCK` was
+/// exceeded), the memory block is returned to the inner source (if it
+/// [supports deallocation](FlexSource::supports_dealloc)), and the allocation
+/// fails with [`SourceError::Os`].
+///
+/// In-place growth is disabled because a failure to lock the grown part
+/// couldn't be reported without leaving the inner source in an inconsistent
+/// state. Therefore, [`FlexSource::realloc_inplace_grow`] and
+/// [`FlexSource::alloc_contiguous_with`] are not forwarded to the inner
+/// source.
+///
+/// # Examples
+///
+/// ```
+/// use rlsf::{FlexTlsf, GlobalAllocAsFlexSource, MlockedSource};
+/// use std::alloc::{Layout, System};
+///
+/// type Source = MlockedSource<GlobalAllocAsFlexSource<System, 4096>>;
+/// let mut tlsf: FlexTlsf<Source, u16, u16, 12, 16> =
+///     FlexTlsf::new(MlockedSource(GlobalAllocAsFlexSource(System)));
+///
+/// // Fails if the process is not allowed to lock any more memory
+/// if let Some(ptr) = tlsf.allocate(Layout::new::<u64>()) {
+///     unsafe { tlsf.deallocate(ptr, Layout::new::<u64>().align()) };
+/// }
+/// ```
+#[derive(Default, Debug, Copy, Clone)]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(unix)))]
+pub struct MlockedSource<T>(pub T);
+
+impl<T: ConstDefault> ConstDefault for MlockedSource<T> {
+    const DEFAULT: Self = Self(ConstDefault::DEFAULT);
+}
+
+/// Get the memory page size.
+#[inline]
+fn page_size() -> usize {
+    // Safety: `sysconf` has no safety requirements
+    (unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize).max(1)
+}
+
+unsafe impl<T: FlexSource> FlexSource for MlockedSource<T> {
+    #[inline]
+    unsafe fn alloc(&mut self, min_size: usize) -> Option<NonNull<[u8]>> {
+        self.try_alloc(min_size).ok()
+    }
+
+    unsafe fn try_alloc(&mut self, min_size: usize) -> Result<NonNull<[u8]>, SourceError> {
+        let alloc = self.0.try_alloc(min_size)?;
+
+        if libc::mlock(
+            nonnull_slice_start(alloc).as_ptr() as *const libc::c_void,
+            nonnull_slice_len(alloc),
+        ) != 0
+        {
+            let error = last_os_error();
+            if self.0.supports_dealloc() {
+                // Safety: `alloc` is an allocation we just made
+                self.0.dealloc(alloc);
+            }
+            return Err(error);
+        }
+
+        Ok(alloc)
+    }
+
+    unsafe fn dealloc(&mut self, ptr: NonNull<[u8]>) {
+        // Only unlock the pages entirely covered by `ptr`. The other pages
+        // might be shared with other memory blocks, which must remain locked.
+        let page_size_m1 = page_size() - 1;
+        let start_ptr = nonnull_slice_start(ptr).as_ptr();
+        let start = start_ptr as usize;
+        let end = start + nonnull_slice_len(ptr);
+        let unlock_start = start.wrapping_add(page_size_m1) & !page_size_m1;
+        let unlock_end = end & !page_size_m1;
+        if unlock_start < unlock_end {
+            libc::munlock(
+                ptr_with_addr(start_ptr, unlock_start) as *const libc::c_void,
+                unlock_end - unlock_start,
+            );
+        }
+
+        // Safety: Upheld by the caller
+        self.0.dealloc(ptr);
+    }
+
+    #[inline]
+    fn supports_dealloc(&self) -> bool {
+        self.0.supports_dealloc()
+    }
+
+    #[inline]
+    fn min_align(&self) -> usize {
+        self.0.min_align()
+    }
+}
@@ -0,0 +1,191 @@
+//! A [`FlexSource`] providing memory bound to a NUMA node
+use core::ptr::{null_mut, NonNull};
+
+use super::{FlexSource, SourceError};
+use crate::utils::{last_os_error, nonnull_slice_len, nonnull_slice_start};
+
+/// `MPOL_BIND` from `<linux/mempolicy.h>`
+const MPOL_BIND: libc::c_int = 2;
+
+/// The number of bits in the node mask passed to `mbind`. This matches the
+/// maximum value of the kernel's `CONFIG_NODES_SHIFT`.
+const MAX_NUM_NODES: usize = 1024;
+
+const ULONG_BITS: usize = libc::c_ulong::BITS as usize;
+
+/// A [`FlexSource`] that maps anonymous memory pages by `mmap` and binds them
+/// to a particular NUMA node by `mbind(MPOL_BIND)`.
+///
+/// Constructing one [`FlexTlsf`] per NUMA node lets threads running on a node
+/// allocate memory local to that node.
+///
+/// [`FlexTlsf`]: super::FlexTlsf
+///
+/// # Examples
+///
+/// ```
+/// use rlsf::{FlexTlsf, NumaSource};
+/// use std::alloc::Layout;
+///
+/// let mut tlsf: FlexTlsf<NumaSource, u32, u32, 28, 32> = FlexTlsf::new(NumaSource::new(0));
+///
+/// // Fails if the system doesn't support NUMA memory policies
+/// if let Some(ptr) = tlsf.allocate(Layout::new::<u64>()) {
+///     unsafe { tlsf.deallocate(ptr, Layout::new::<u64>().align()) };
+/// }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(target_os = "linux")))]
+pub struct NumaSource {
+    node: usize,
+}
+
+impl NumaSource {
+    /// Construct a `NumaSource` that binds memory to the NUMA node `node`.
+    ///
+    /// Allocations will fail if `node` is not a valid NUMA node.
+    #[inline]
+    pub const fn new(node: usize) -> Self {
+        Self { node }
+    }
+
+    /// Get the NUMA node to which memory is bound.
+    #[inline]
+    pub const fn node(&self) -> usize {
+        self.node
+    }
+
+    /// Bind the memory pages in the specified range to `self.node`.
+    unsafe fn bind(&self, start: *mut libc::c_void, len: usize) -> Result<(), SourceError> {
+        if self.node >= MAX_NUM_NODES {
+            return Err(SourceError::Os(libc::EINVAL));
+        }
+
+        let mut node_mask = [0 as libc::c_ulong; MAX_NUM_NODES / ULONG_BITS];
+        node_mask[self.node / ULONG_BITS] |= 1 << (self.node % ULONG_BITS);
+
+        // `maxnode` is one more than the number of bits in the mask to work
+        // around the kernel's off-by-one behavior
+        if libc::syscall(
+            libc::SYS_mbind,
+            start,
+            len,
+            MPOL_BIND,
+            node_mask.as_ptr(),
+            MAX_NUM_NODES + 1,
+            0 as libc::c_uint,
+        ) != 0
+        {
+            return Err(last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+/// Get the memory page size minus one.
+#[inline]
+fn page_size_m1() -> usize {
+    // Safety: `sysconf` has no safety requirements
+    (unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize).max(crate::GRANULARITY) - 1
+}
+
+unsafe impl FlexSource for NumaSource {
+    #[inline]
+    unsafe fn alloc(&mut self, min_size: usize) -> Option<NonNull<[u8]>> {
+        self.try_alloc(min_size).ok()
+    }
+
+    unsafe fn try_alloc(&mut self, min_size: usize) -> Result<NonNull<[u8]>, SourceError> {
+        let page_size_m1 = page_size_m1();
+        let num_bytes = min_size
+            .checked_add(page_size_m1)
+            .ok_or(SourceError::Exhausted)?
+            & !page_size_m1;
+
+        let ptr = libc::mmap(
+            null_mut(),
+            num_bytes,
+            libc::PROT_WRITE | libc::PROT_READ,
+            libc::MAP_ANONYMOUS | libc::MAP_PRIVATE,
+            -1,
+            0,
+        );
+
+        if ptr == libc::MAP_FAILED {
+            return Err(last_os_error());
+        }
+
+        // The pages are not populated yet, so binding them now ensures that
+        // they will be allocated on `self.node`
+        if let Err(e) = self.bind(ptr, num_bytes) {
+            libc::munmap(ptr, num_bytes);
+            return Err(e);
+        }
+
+        NonNull::new(core::ptr::slice_from_raw_parts_mut(
+            ptr as *mut u8,
+            num_bytes,
+        ))
+        .ok_or(SourceError::Exhausted)
+    }
+
+    unsafe fn realloc_inplace_grow(
+        &mut self,
+        ptr: NonNull<[u8]>,
+        min_new_len: usize,
+    ) -> Option<usize> {
+        let page_size_m1 = page_size_m1();
+        let num_bytes = min_new_len.checked_add(page_size_m1)? & !page_size_m1;
+        let num_growth_bytes = num_bytes - nonnull_slice_len(ptr);
+
+        let ptr_end = nonnull_slice_start(ptr)
+            .as_ptr()
+            .wrapping_add(nonnull_slice_len(ptr));
+
+        let ptr_growth_start = libc::mmap(
+            ptr_end as _,
+            num_growth_bytes,
+            libc::PROT_WRITE | libc::PROT_READ,
+            libc::MAP_ANONYMOUS | libc::MAP_PRIVATE | libc::MAP_FIXED_NOREPLACE,
+            -1,
+            0,
+        );
+
+        if ptr_growth_start == libc::MAP_FAILED {
+            None
+        } else if ptr_growth_start != ptr_end as _
+            || self.bind(ptr_growth_start, num_growth_bytes).is_err()
+        {
+            // Either we are on an old Linux kernel, and `MAP_FIXED_NOREPLACE`
+            // was not respected, or the new pages couldn't be bound
+            libc::munmap(ptr_growth_start, num_growth_bytes);
+            None
+        } else {
+            Some(num_bytes)
+        }
+    }
+
+    #[inline]
+    unsafe fn dealloc(&mut self, ptr: NonNull<[u8]>) {
+        libc::munmap(
+            nonnull_slice_start(ptr).as_ptr() as *mut libc::c_void,
+            nonnull_slice_len(ptr),
+        );
+    }
+
+    #[inline]
+    fn supports_dealloc(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn supports_realloc_inplace_grow(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn min_align(&self) -> usize {
+        page_size_m1() + 1
+    }
+}
@@ -4,7 +4,7 @@ use std::{fmt, prelude::v1::*};
 use super::*;
 use crate::{
     tests::ShadowAllocator,
-    utils::{nonnull_slice_end, nonnull_slice_len},
+    utils::{nonnull_slice_end, nonnull_slice_len, nonnull_slice_start},
 };
 
 trait TestFlexSource: FlexSource {
@@ -77,6 +77,26 @@ unsafe impl<T: FlexSource> FlexSource for TrackingFlexSource<T> {
         Some(new_len)
     }
 
+    unsafe fn alloc_contiguous_with(
+        &mut self,
+        existing: NonNull<[u8]>,
+        min_extra: usize,
+    ) -> Option<NonNull<[u8]>> {
+        log::trace!(
+            "FlexSource::alloc_contiguous_with{:?}",
+            (existing, min_extra)
+        );
+        let range = self.inner.alloc_contiguous_with(existing, min_extra)?;
+        log::trace!(" FlexSource::alloc_contiguous_with(...) = {:?}", range);
+        assert_eq!(
+            nonnull_slice_start(range).as_ptr(),
+            nonnull_slice_end(existing)
+        );
+        assert!(nonnull_slice_len(range) >= min_extra);
+        self.sa.append_free_block(range.as_ptr());
+        Some(range)
+    }
+
     #[inline]
     fn min_align(&self) -> usize {
         self.inner.min_align()
@@ -143,13 +163,17 @@ unsafe impl FlexSource for CgFlexSource {
         Some(NonNull::from(&mut self.pool[allocated..new_allocated]))
     }
 
-    unsafe fn realloc_inplace_grow(
+    unsafe fn alloc_contiguous_with(
         &mut self,
-        ptr: NonNull<[u8]>,
-        min_new_len: usize,
-    ) -> Option<usize> {
-        self.alloc(min_new_len - nonnull_slice_len(ptr))
-            .map(|s| nonnull_slice_len(s) + nonnull_slice_len(ptr))
+        existing: NonNull<[u8]>,
+        min_extra: usize,
+    ) -> Option<NonNull<[u8]>> {
+        // We can only guarantee adjacency if nothing has been allocated after
+        // `existing`
+        if nonnull_slice_end(existing) != self.pool.as_mut_ptr().wrapping_add(self.allocated) {
+            return None;
+        }
+        self.alloc(min_extra)
     }
 
     fn is_contiguous_growable(&self) -> bool {
@@ -226,8 +250,9 @@ macro_rules! gen_test {
                 }
                 let ptr5 = tlsf.allocate(Layout::from_size_align(12, 8).unwrap());
                 log::trace!("ptr5 = {:?}", ptr5);
-                let ptr3 = ptr3.and_then(|ptr3| unsafe {
+                let ptr3 = ptr3.map(|ptr3| unsafe {
                     tlsf.reallocate(ptr3, Layout::from_size_align(0, 32).unwrap())
+                        .unwrap_or(ptr3)
                 });
                 log::trace!("ptr3 = {:?}", ptr3);
                 let ptr6 = tlsf.allocate(Layout::from_size_align(24, 2).unwrap());
@@ -240,21 +265,42 @@ macro_rules! gen_test {
                     unsafe { tlsf.deallocate(ptr5, 8) };
                     log::trace!("deallocate(ptr5)");
                 }
-                let ptr3 = ptr3.and_then(|ptr3| unsafe {
+                let ptr3 = ptr3.map(|ptr3| unsafe {
                     tlsf.reallocate(ptr3, Layout::from_size_align(4, 32).unwrap())
+                        .unwrap_or(ptr3)
                 });
                 log::trace!("ptr3 = {:?}", ptr3);
+
+                // Deallocate all remaining allocations
+                for (ptr, align) in [(ptr3, 32), (ptr4, 8), (ptr6, 2), (ptr7, 16), (ptr8, 32)] {
+                    if let Some(ptr) = ptr {
+                        unsafe { tlsf.deallocate(ptr, align) };
+                    }
+                }
             }
 
             #[quickcheck]
             fn random(source_options: <$source as TestFlexSource>::Options, max_alloc_size: usize, bytecode: Vec<u8>) {
-                random_inner(source_options, max_alloc_size, bytecode);
+                let mut tlsf = TheTlsf::new(TrackingFlexSource::new(source_options));
+
+                let mut allocs = Vec::new();
+                random_inner(&mut tlsf, &mut allocs, max_alloc_size, bytecode);
+
+                // Deallocate all remaining allocations
+                for alloc in allocs.iter() {
+                    unsafe { tlsf.deallocate(alloc.ptr, alloc.layout.align()) };
+                }
+            }
+
+            #[derive(Debug)]
+            struct Alloc {
+                ptr: NonNull<u8>,
+                layout: Layout,
             }
 
-            fn random_inner(source_options: <$source as TestFlexSource>::Options, max_alloc_size: usize, bytecode: Vec<u8>) -> Option<()> {
+            fn random_inner(tlsf: &mut TheTlsf, allocs: &mut Vec<Alloc>, max_alloc_size: usize, bytecode: Vec<u8>) -> Option<()> {
                 let max_alloc_size = max_alloc_size % 0x10000;
 
-                let mut tlsf = TheTlsf::new(TrackingFlexSource::new(source_options));
                 macro_rules! sa {
                     () => {
                         unsafe { tlsf.source_mut_unchecked() }.sa
@@ -263,13 +309,6 @@ macro_rules! gen_test {
 
                 log::trace!("tlsf = {:?}", tlsf);
 
-                #[derive(Debug)]
-                struct Alloc {
-                    ptr: NonNull<u8>,
-                    layout: Layout,
-                }
-                let mut allocs = Vec::new();
-
                 let mut it = bytecode.iter().cloned();
                 loop {
                     match it.next()? % 8 {
@@ -305,8 +344,10 @@ macro_rules! gen_test {
                                 // Make sure the stored dummy data is not corrupted
                                 verify_data(crate::utils::nonnull_slice_from_raw_parts(alloc.ptr, alloc.layout.size()));
 
-                                unsafe { tlsf.deallocate(alloc.ptr, alloc.layout.align()) };
+                                // Update `sa` first because `deallocate` might
+                                // return the memory block to the source
                                 sa!().deallocate(alloc.layout, alloc.ptr);
+                                unsafe { tlsf.deallocate(alloc.ptr, alloc.layout.align()) };
                             }
                         }
                         6..=7 => {
@@ -326,6 +367,8 @@ macro_rules! gen_test {
 
                                 let new_layout = Layout::from_size_align(len, alloc.layout.align()).unwrap();
 
+                                sa!().deallocate(alloc.layout, alloc.ptr);
+
                                 if let Some(ptr) = unsafe { tlsf.reallocate(alloc.ptr, new_layout) } {
                                     log::trace!(" {:?} → {:?}", alloc.ptr, ptr);
 
@@ -333,14 +376,13 @@ macro_rules! gen_test {
                                     verify_data(crate::utils::nonnull_slice_from_raw_parts(ptr, len.min(alloc.layout.size())));
                                     fill_data(crate::utils::nonnull_slice_from_raw_parts(ptr, len));
 
-                                    sa!().deallocate(alloc.layout, alloc.ptr);
                                     alloc.ptr = ptr;
                                     alloc.layout = new_layout;
-                                    sa!().allocate(alloc.layout, alloc.ptr);
                                 } else {
                                     log::trace!(" {:?} → fail", alloc.ptr);
-
                                 }
+
+                                sa!().allocate(alloc.layout, alloc.ptr);
                             }
                         }
                         _ => unreachable!(),
@@ -413,3 +455,403 @@ gen_test!(tlsf_cg_u64_u8_59_8, CgFlexSource, u64, u64, 59, 8);
 gen_test!(tlsf_cg_u64_u8_60_8, CgFlexSource, u64, u64, 60, 8);
 gen_test!(tlsf_cg_u64_u8_61_8, CgFlexSource, u64, u64, 61, 8);
 gen_test!(tlsf_cg_u64_u8_64_8, CgFlexSource, u64, u64, 64, 8);
+
+#[cfg(feature = "debug-leak-check")]
+mod leak_check {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    type TheTlsf = FlexTlsf<SysSource, u32, u32, 20, 32>;
+
+    #[test]
+    fn no_leak() {
+        let mut tlsf = TheTlsf::new(SysSource::default());
+        let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+        let ptr = unsafe { tlsf.reallocate(ptr, Layout::new::<[u64; 64]>()) }.unwrap();
+        unsafe { tlsf.deallocate(ptr, Layout::new::<u64>().align()) };
+    }
+
+    #[test]
+    #[should_panic = "live allocation"]
+    fn leak_panics() {
+        let mut tlsf = TheTlsf::new(SysSource::default());
+        tlsf.allocate(Layout::new::<u64>()).unwrap();
+    }
+
+    #[test]
+    fn leak_reported() {
+        static REPORTED_COUNT: AtomicUsize = AtomicUsize::new(0);
+        static REPORTED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+        let mut tlsf = TheTlsf::new(SysSource::default());
+        tlsf.set_leak_reporter(|report| {
+            REPORTED_COUNT.store(report.count, Ordering::Relaxed);
+            REPORTED_BYTES.store(report.bytes, Ordering::Relaxed);
+        });
+        tlsf.allocate(Layout::new::<u64>()).unwrap();
+        tlsf.allocate(Layout::new::<[u8; 100]>()).unwrap();
+        let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+        unsafe { tlsf.deallocate(ptr, Layout::new::<u64>().align()) };
+        drop(tlsf);
+
+        assert_eq!(REPORTED_COUNT.load(Ordering::Relaxed), 2);
+        assert!(REPORTED_BYTES.load(Ordering::Relaxed) >= 108);
+    }
+}
+
+#[test]
+fn borrowed_source() {
+    let mut source = TrackingFlexSource::<SysSource>::new(());
+    for _ in 0..2 {
+        let mut tlsf: FlexTlsf<_, u32, u32, 20, 32> = FlexTlsf::new(&mut source);
+        let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+        unsafe { tlsf.deallocate(ptr, Layout::new::<u64>().align()) };
+    }
+    // All pools should have been returned to `source`
+    source.sa.assert_no_pools();
+}
+
+#[cfg(feature = "unstable")]
+#[test]
+fn pool_overheads() {
+    let mut tlsf: FlexTlsf<SysSource, u32, u32, 20, 32> = FlexTlsf::new(SysSource::default());
+    let layouts = [
+        Layout::new::<u64>(),
+        Layout::new::<[u8; 100]>(),
+        Layout::from_size_align(4096, 1).unwrap(),
+        Layout::from_size_align(200000, 8).unwrap(),
+    ];
+    let ptrs: Vec<_> = layouts
+        .iter()
+        .map(|&layout| (tlsf.allocate(layout).unwrap(), layout))
+        .collect();
+
+    let mut num_allocations = 0;
+    let mut allocated_bytes = 0;
+    for overhead in tlsf.pool_overheads() {
+        log::trace!("{:?}", overhead);
+        assert_eq!(
+            overhead.allocated_bytes + overhead.free_bytes + overhead.overhead_bytes,
+            nonnull_slice_len(overhead.alloc)
+        );
+        assert!(overhead.overhead_bytes > 0);
+        num_allocations += overhead.num_allocations;
+        allocated_bytes += overhead.allocated_bytes;
+    }
+    assert_eq!(num_allocations, layouts.len());
+    assert!(allocated_bytes >= layouts.iter().map(|l| l.size()).sum::<usize>());
+
+    for (ptr, layout) in ptrs {
+        unsafe { tlsf.deallocate(ptr, layout.align()) };
+    }
+    assert_eq!(
+        tlsf.pool_overheads()
+            .map(|o| o.num_allocations)
+            .sum::<usize>(),
+        0
+    );
+}
+
+#[test]
+fn try_allocate_errors() {
+    struct FailingSource;
+
+    unsafe impl FlexSource for FailingSource {
+        unsafe fn try_alloc(&mut self, _min_size: usize) -> Result<NonNull<[u8]>, SourceError> {
+            Err(SourceError::Os(12))
+        }
+    }
+
+    let mut tlsf: FlexTlsf<FailingSource, u32, u32, 20, 32> = FlexTlsf::new(FailingSource);
+    assert_eq!(
+        tlsf.try_allocate(Layout::new::<u64>()),
+        Err(AllocError::Source(SourceError::Os(12)))
+    );
+    assert_eq!(tlsf.allocate(Layout::new::<u64>()), None);
+    assert_eq!(
+        tlsf.try_allocate(Layout::from_size_align(usize::MAX / 2, 1).unwrap()),
+        Err(AllocError::SizeOverflow)
+    );
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn heap_stats() {
+    let mut tlsf: FlexTlsf<TrackingFlexSource<SysSource>, u32, u32, 20, 32> =
+        FlexTlsf::new(TrackingFlexSource::new(()));
+    tlsf.set_max_capacity(1 << 16);
+    assert_eq!(tlsf.heap_stats(), crate::HeapStats::default());
+
+    // Growing the memory pool isn't counted as a failure
+    let layout = Layout::from_size_align(1024, 8).unwrap();
+    let ptr = tlsf.allocate(layout).unwrap();
+    let stats = tlsf.heap_stats();
+    assert_eq!(stats.num_allocations, 1);
+    assert_eq!(stats.failed_allocations, 0);
+    assert_eq!(stats.pool_bytes, tlsf.capacity());
+    assert!(stats.bytes_in_use >= 1024);
+    assert_eq!(stats.peak_bytes_in_use, stats.bytes_in_use);
+    assert!(stats.pool_bytes >= stats.bytes_in_use + stats.free_bytes);
+
+    assert!(tlsf.allocate(Layout::new::<[u8; 1 << 17]>()).is_none());
+    unsafe { tlsf.deallocate(ptr, layout.align()) };
+    let stats = tlsf.heap_stats();
+    assert_eq!(stats.num_allocations, 0);
+    assert_eq!(stats.failed_allocations, 1);
+    assert_eq!(stats.bytes_in_use, 0);
+}
+
+#[test]
+fn max_capacity() {
+    let mut tlsf: FlexTlsf<TrackingFlexSource<SysSource>, u32, u32, 20, 32> =
+        FlexTlsf::new(TrackingFlexSource::new(()));
+    tlsf.set_max_capacity(1 << 16);
+    assert_eq!(tlsf.max_capacity(), 1 << 16);
+    assert_eq!(tlsf.remaining_capacity(), 1 << 16);
+
+    let mut ptrs = Vec::new();
+    let layout = Layout::from_size_align(1024, 8).unwrap();
+    let err = loop {
+        match tlsf.try_allocate(layout) {
+            Ok(ptr) => ptrs.push(ptr),
+            Err(e) => break e,
+        }
+    };
+    assert_eq!(err, AllocError::CapacityExceeded);
+    assert!(!ptrs.is_empty());
+    assert!(tlsf.capacity() <= 1 << 16);
+    assert_eq!(tlsf.remaining_capacity(), (1 << 16) - tlsf.capacity());
+
+    for ptr in ptrs {
+        unsafe { tlsf.deallocate(ptr, layout.align()) };
+    }
+}
+
+#[test]
+fn adopt_pool() {
+    let mut source = TrackingFlexSource::<SysSource>::new(());
+    let mut tlsf: FlexTlsf<_, u32, u32, 20, 32> = FlexTlsf::new(&mut source);
+
+    // An owned pool, which should be returned to the source on drop
+    let owned = unsafe { tlsf.source_mut_unchecked().alloc(4096) }.unwrap();
+    assert!(unsafe { tlsf.adopt_pool(owned, true) }.is_some());
+
+    // A borrowed pool
+    let mut borrowed = std::vec![0u8; 4096];
+    let borrowed =
+        nonnull_slice_from_raw_parts(NonNull::new(borrowed.as_mut_ptr()).unwrap(), borrowed.len());
+    assert!(unsafe { tlsf.adopt_pool(borrowed, false) }.is_some());
+
+    // Too small to adopt
+    let mut tiny = [0u8; 8];
+    let tiny = nonnull_slice_from_raw_parts(NonNull::new(tiny.as_mut_ptr()).unwrap(), 8);
+    assert!(unsafe { tlsf.adopt_pool(tiny, false) }.is_none());
+
+    // Both pools should be used before the source is consulted
+    let layout = Layout::from_size_align(2048, 8).unwrap();
+    let ptrs = [
+        tlsf.allocate(layout).unwrap(),
+        tlsf.allocate(layout).unwrap(),
+    ];
+    assert_eq!(tlsf.capacity(), 0);
+
+    for ptr in ptrs {
+        unsafe { tlsf.deallocate(ptr, layout.align()) };
+    }
+    drop(tlsf);
+
+    // The owned pool should have been returned to `source`
+    source.sa.assert_no_pools();
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn verify_on_drop() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let new_tlsf = || {
+        let mut tlsf: FlexTlsf<SysSource, u32, u32, 20, 32> = FlexTlsf::new(SysSource::new(()));
+        tlsf.set_verify_on_drop(true);
+
+        // An owned pool, which should be checked as well
+        let owned = unsafe { tlsf.source_mut_unchecked().alloc(4096) }.unwrap();
+        assert!(unsafe { tlsf.adopt_pool(owned, true) }.is_some());
+        tlsf
+    };
+
+    // A consistent heap
+    let mut tlsf = new_tlsf();
+    let layout = Layout::from_size_align(100, 8).unwrap();
+    let ptrs: Vec<_> = (0..64).map(|_| tlsf.allocate(layout).unwrap()).collect();
+    for &ptr in ptrs.iter().step_by(2).chain(ptrs.iter().skip(1).step_by(2)) {
+        unsafe { tlsf.deallocate(ptr, layout.align()) };
+    }
+    drop(tlsf);
+
+    // A corrupted free block header
+    let mut tlsf = new_tlsf();
+    let ptr = tlsf.allocate(layout).unwrap();
+    unsafe { tlsf.deallocate(ptr, layout.align()) };
+    unsafe { *ptr.as_ptr().sub(GRANULARITY / 2).cast::<usize>() ^= GRANULARITY };
+
+    let payload = catch_unwind(AssertUnwindSafe(|| drop(tlsf))).unwrap_err();
+    let message = payload
+        .downcast_ref::<String>()
+        .cloned()
+        .unwrap_or_default();
+    assert!(
+        message.starts_with("heap corruption detected"),
+        "{:?}",
+        message
+    );
+}
+
+#[test]
+fn allocate_with_hint() {
+    let mut tlsf: FlexTlsf<TrackingFlexSource<SysSource>, u32, u32, 20, 32> =
+        FlexTlsf::new(TrackingFlexSource::new(()));
+    let layout = Layout::from_size_align(100, 8).unwrap();
+
+    // The first allocation should obtain enough memory for the whole burst
+    let mut ptrs = std::vec![tlsf.allocate_with_hint(layout, 100 * 64).unwrap()];
+    let capacity = tlsf.capacity();
+    for _ in 0..32 {
+        ptrs.push(tlsf.allocate(layout).unwrap());
+    }
+    assert_eq!(tlsf.capacity(), capacity);
+
+    // An unsatisfiable hint should not cause the allocation to fail
+    tlsf.set_max_capacity(capacity + 8192);
+    let big_layout = Layout::from_size_align(4096, 8).unwrap();
+    let big = tlsf
+        .allocate_with_hint(big_layout, usize::MAX - 64)
+        .unwrap();
+    assert!(tlsf.capacity() > capacity);
+
+    unsafe { tlsf.deallocate(big, big_layout.align()) };
+    for ptr in ptrs {
+        unsafe { tlsf.deallocate(ptr, layout.align()) };
+    }
+}
+
+#[test]
+fn huge_allocations() {
+    let mut source = TrackingFlexSource::<SysSource>::new(());
+    let mut tlsf: FlexTlsf<_, u32, u32, 20, 32> = FlexTlsf::new(&mut source);
+    tlsf.set_huge_threshold(4096);
+    assert_eq!(tlsf.huge_threshold(), 4096);
+
+    let small = tlsf.allocate(Layout::new::<u64>()).unwrap();
+
+    for &align in &[1, 8, 64, 4096] {
+        let capacity = tlsf.capacity();
+        let layout = Layout::from_size_align(100000, align).unwrap();
+        let ptr = tlsf.allocate(layout).unwrap();
+        assert_eq!(ptr.as_ptr() as usize % align, 0);
+        assert!(tlsf.capacity() >= capacity + layout.size());
+        fill_data(nonnull_slice_from_raw_parts(ptr, layout.size()));
+
+        // Grow it. It should be moved to another dedicated memory block.
+        let new_layout = Layout::from_size_align(200000, align).unwrap();
+        let ptr = unsafe { tlsf.reallocate(ptr, new_layout) }.unwrap();
+        verify_data(nonnull_slice_from_raw_parts(ptr, layout.size()));
+        unsafe { tlsf.deallocate(ptr, align) };
+        assert_eq!(tlsf.capacity(), capacity);
+
+        // Deallocate a huge allocation without knowing its alignment
+        let ptr = tlsf.allocate(layout).unwrap();
+        unsafe { tlsf.deallocate_unknown_align(ptr) };
+        assert_eq!(tlsf.capacity(), capacity);
+
+        // Shrink it. It should be moved to the memory pool.
+        let ptr = tlsf.allocate(layout).unwrap();
+        fill_data(nonnull_slice_from_raw_parts(ptr, layout.size()));
+        let new_layout = Layout::from_size_align(1000, align).unwrap();
+        let ptr = unsafe { tlsf.reallocate(ptr, new_layout) }.unwrap();
+        verify_data(nonnull_slice_from_raw_parts(ptr, new_layout.size()));
+        unsafe { tlsf.deallocate(ptr, align) };
+    }
+
+    unsafe { tlsf.deallocate(small, 8) };
+    drop(tlsf);
+
+    // All memory blocks should have been returned to `source`
+    source.sa.assert_no_pools();
+}
+
+#[test]
+fn oversized_allocations() {
+    let mut source = TrackingFlexSource::<SysSource>::new(());
+    // The maximum block size is `GRANULARITY << 12`
+    let mut tlsf: FlexTlsf<_, u16, u16, 12, 16> = FlexTlsf::new(&mut source);
+
+    let layout = Layout::from_size_align(GRANULARITY << 13, 64).unwrap();
+    let ptr = tlsf.allocate(layout).unwrap();
+    assert_eq!(ptr.as_ptr() as usize % 64, 0);
+    fill_data(nonnull_slice_from_raw_parts(ptr, layout.size()));
+    verify_data(nonnull_slice_from_raw_parts(ptr, layout.size()));
+    unsafe { tlsf.deallocate(ptr, layout.align()) };
+    assert_eq!(tlsf.capacity(), 0);
+
+    drop(tlsf);
+    source.sa.assert_no_pools();
+}
+
+#[cfg(unix)]
+#[test]
+fn mlocked_source() {
+    let mut source = TrackingFlexSource::<SysSource>::new(());
+    let mut tlsf: FlexTlsf<_, u32, u32, 20, 32> = FlexTlsf::new(MlockedSource(&mut source));
+    let layout = Layout::new::<[u8; 100]>();
+
+    match tlsf.try_allocate(layout) {
+        Ok(ptr) => unsafe { tlsf.deallocate(ptr, layout.align()) },
+        // The process might not be allowed to lock memory
+        Err(e) => assert!(
+            matches!(e, AllocError::Source(SourceError::Os(_))),
+            "{:?}",
+            e
+        ),
+    }
+    drop(tlsf);
+
+    // Memory blocks that couldn't be locked should have been returned, too
+    source.sa.assert_no_pools();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn numa_source() {
+    let mut tlsf: FlexTlsf<NumaSource, u32, u32, 28, 32> = FlexTlsf::new(NumaSource::new(0));
+    assert_eq!(tlsf.source_ref().node(), 0);
+
+    let mut layout = Layout::from_size_align(100, 8).unwrap();
+    let mut ptr = match tlsf.try_allocate(layout) {
+        Ok(ptr) => ptr,
+        Err(e) => {
+            // The system might not support NUMA memory policies
+            assert!(
+                matches!(e, AllocError::Source(SourceError::Os(_))),
+                "{:?}",
+                e
+            );
+            return;
+        }
+    };
+
+    // Grow the allocation to exercise `realloc_inplace_grow`
+    for _ in 0..4 {
+        layout = Layout::from_size_align(layout.size() * 16, 8).unwrap();
+        ptr = unsafe { tlsf.reallocate(ptr, layout) }.unwrap();
+        fill_data(nonnull_slice_from_raw_parts(ptr, layout.size()));
+    }
+    unsafe { tlsf.deallocate(ptr, layout.align()) };
+
+    // An invalid node should be rejected
+    let mut tlsf: FlexTlsf<NumaSource, u32, u32, 28, 32> =
+        FlexTlsf::new(NumaSource::new(usize::MAX));
+    assert_eq!(
+        tlsf.try_allocate(Layout::new::<u64>()),
+        Err(AllocError::Source(SourceError::Os(libc::EINVAL)))
+    );
+}
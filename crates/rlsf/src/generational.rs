@@ -0,0 +1,272 @@
+//! `GenerationalTlsf`: a [`Tlsf`] tagging each allocation with a generation
+//! number
+use core::{alloc::Layout, fmt, mem, ptr::NonNull};
+
+use crate::{int::BinInteger, Tlsf, GRANULARITY};
+
+#[cfg(feature = "asan")]
+use crate::asan;
+
+/// The generation number of an allocation made by [`GenerationalTlsf`].
+pub type Generation = u32;
+
+/// The generation number stored in front of deallocated memory blocks. Never
+/// assigned to an allocation.
+const FREED_GENERATION: Generation = 0;
+
+/// [`Tlsf`] that records a generation number in front of each allocation, so
+/// that handle-based systems (e.g., entity-component systems and asset
+/// managers) can cheaply check whether a handle consisting of a pointer and a
+/// generation number still refers to a live allocation.
+///
+/// Each allocation gets a generation number from a counter that is bumped on
+/// every allocation, so a memory block reused by a new allocation has a
+/// different generation number. [`Self::deallocate`] overwrites the recorded
+/// generation number, and [`Self::validate_handle`] compares it with the
+/// handle's.
+///
+/// The check is best-effort: after the memory is reused in a different way
+/// (e.g., by an allocation starting elsewhere), the location of the recorded
+/// generation number contains arbitrary data, which may happen to match the
+/// handle's. The counter also wraps around after `u32::MAX` allocations.
+///
+/// Every allocation is enlarged by `GRANULARITY / 2 + size_of::<Generation>()`
+/// bytes rounded up to its alignment. The generation number is placed after
+/// the first `GRANULARITY / 2` bytes of the underlying allocation, where a
+/// deallocated memory block stores its free list links, so that they don't
+/// overwrite the generation number recorded on deallocation.
+///
+/// # Examples
+///
+/// ```rust
+/// use rlsf::GenerationalTlsf;
+/// use std::{alloc::Layout, mem::MaybeUninit};
+///
+/// let mut tlsf: GenerationalTlsf<'_, u16, u16, 12, 16> = GenerationalTlsf::new();
+/// let mut pool = [MaybeUninit::uninit(); 4096];
+/// tlsf.tlsf().insert_free_block(&mut pool);
+///
+/// let (ptr, generation) = tlsf.allocate(Layout::new::<u64>()).unwrap();
+/// unsafe {
+///     assert!(tlsf.validate_handle(ptr, generation));
+///     tlsf.deallocate(ptr, 8);
+///     assert!(!tlsf.validate_handle(ptr, generation));
+/// }
+///
+/// // The memory block is likely to be reused, but the stale handle is
+/// // still rejected
+/// let (ptr2, generation2) = tlsf.allocate(Layout::new::<u64>()).unwrap();
+/// assert_eq!(ptr2, ptr);
+/// unsafe {
+///     assert!(!tlsf.validate_handle(ptr, generation));
+///     assert!(tlsf.validate_handle(ptr2, generation2));
+/// }
+/// ```
+pub struct GenerationalTlsf<'pool, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize> {
+    tlsf: Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>,
+    next_generation: Generation,
+}
+
+impl<FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, const SLLEN: usize> Default
+    for GenerationalTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, const SLLEN: usize>
+    GenerationalTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    /// Construct an empty instance of `Self`.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            tlsf: Tlsf::new(),
+            next_generation: FREED_GENERATION + 1,
+        }
+    }
+
+    /// Get the underlying [`Tlsf`], e.g., to supply memory pools.
+    ///
+    /// The memory blocks allocated through it directly don't have generation
+    /// numbers and must not be passed to the methods of `self`.
+    #[inline]
+    pub fn tlsf(&mut self) -> &mut Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN> {
+        &mut self.tlsf
+    }
+
+    /// Get the distance from the start of the underlying allocation to the
+    /// payload. It must be a multiple of `align` and be able to accommodate
+    /// the free list links and the generation number.
+    #[inline]
+    fn front_len(align: usize) -> usize {
+        (GRANULARITY / 2 + mem::size_of::<Generation>() + align - 1) & !(align - 1)
+    }
+
+    /// Get the layout of the underlying allocation for an allocation of
+    /// `layout`.
+    #[inline]
+    fn inner_layout(layout: Layout) -> Option<Layout> {
+        let size = Self::front_len(layout.align()).checked_add(layout.size())?;
+        let align = layout.align().max(mem::align_of::<Generation>());
+        Layout::from_size_align(size, align).ok()
+    }
+
+    /// Get the location of the generation number of the allocation `ptr`.
+    #[inline]
+    fn generation_ptr(ptr: NonNull<u8>) -> *mut Generation {
+        ptr.as_ptr()
+            .wrapping_sub(mem::size_of::<Generation>())
+            .cast()
+    }
+
+    /// Take a generation number from the counter.
+    #[inline]
+    fn new_generation(&mut self) -> Generation {
+        let generation = self.next_generation;
+        self.next_generation = match generation.wrapping_add(1) {
+            FREED_GENERATION => FREED_GENERATION + 1,
+            next => next,
+        };
+        generation
+    }
+
+    /// Attempt to allocate a block of memory with a new generation number.
+    ///
+    /// Returns the starting address of the allocated memory block and its
+    /// generation number on success; `None` otherwise.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in constant time.
+    pub fn allocate(&mut self, layout: Layout) -> Option<(NonNull<u8>, Generation)> {
+        let inner = self.tlsf.allocate(Self::inner_layout(layout)?)?;
+        let generation = self.new_generation();
+        unsafe {
+            // Safety: The allocation is large enough to contain the
+            //         generation number
+            let ptr = NonNull::new_unchecked(inner.as_ptr().add(Self::front_len(layout.align())));
+            Self::generation_ptr(ptr).write(generation);
+            Some((ptr, generation))
+        }
+    }
+
+    /// Get the generation number of a previously allocated memory block.
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `self`.
+    ///  - The call must happen-before the deallocation or reallocation of the
+    ///    memory block.
+    ///
+    #[inline]
+    pub unsafe fn generation(&self, ptr: NonNull<u8>) -> Generation {
+        *Self::generation_ptr(ptr)
+    }
+
+    /// Check whether the handle consisting of `ptr` and `generation` refers
+    /// to a live allocation (on a best-effort basis; see the type-level
+    /// documentation).
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in constant time.
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must have been returned by [`Self::allocate`] or
+    ///    [`Self::reallocate`] of `self`. It may have been deallocated since.
+    ///  - The memory pool containing it must still be accessible.
+    ///
+    pub unsafe fn validate_handle(&self, ptr: NonNull<u8>, generation: Generation) -> bool {
+        let generation_ptr = Self::generation_ptr(ptr);
+
+        // The location may be in free memory, which AddressSanitizer reports
+        // accesses to
+        #[cfg(feature = "asan")]
+        if asan::is_poisoned(generation_ptr.cast()) {
+            return false;
+        }
+
+        generation != FREED_GENERATION && generation_ptr.read_volatile() == generation
+    }
+
+    /// Deallocate a previously allocated memory block, invalidating the
+    /// handles referring to it.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in constant time.
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `self`.
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `align`.
+    ///
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, align: usize) {
+        Self::generation_ptr(ptr).write(FREED_GENERATION);
+        let inner = NonNull::new_unchecked(ptr.as_ptr().sub(Self::front_len(align)));
+        self.tlsf
+            .deallocate(inner, align.max(mem::align_of::<Generation>()));
+    }
+
+    /// Shrink or grow a previously allocated memory block, keeping its
+    /// generation number. If the memory block is moved, the handles referring
+    /// to the old location are invalidated.
+    ///
+    /// Returns the new starting address of the memory block on success;
+    /// `None` otherwise.
+    ///
+    /// # Time Complexity
+    ///
+    /// Unlike other methods, this method will complete in linear time
+    /// (`O(old_size)`).
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `self`.
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `new_layout`.
+    ///
+    pub unsafe fn reallocate(
+        &mut self,
+        ptr: NonNull<u8>,
+        new_layout: Layout,
+    ) -> Option<NonNull<u8>> {
+        let inner_layout = Self::inner_layout(new_layout)?;
+        let front_len = Self::front_len(new_layout.align());
+        let inner = NonNull::new_unchecked(ptr.as_ptr().sub(front_len));
+
+        // Invalidate the old location in case the memory block is moved
+        let generation = Self::generation_ptr(ptr).replace(FREED_GENERATION);
+
+        match self.tlsf.reallocate(inner, inner_layout) {
+            Some(new_inner) => {
+                let new_ptr = NonNull::new_unchecked(new_inner.as_ptr().add(front_len));
+                Self::generation_ptr(new_ptr).write(generation);
+                Some(new_ptr)
+            }
+            None => {
+                // The original allocation is still live
+                Self::generation_ptr(ptr).write(generation);
+                None
+            }
+        }
+    }
+}
+
+impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize> fmt::Debug
+    for GenerationalTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GenerationalTlsf")
+            .field("next_generation", &self.next_generation)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,89 @@
+use core::mem::MaybeUninit;
+use std::{prelude::v1::*, vec};
+
+use super::*;
+
+type TheGenerationalTlsf = GenerationalTlsf<'static, u16, u16, 12, 16>;
+
+fn new_pool(size: usize) -> &'static mut [MaybeUninit<u8>] {
+    Box::leak(vec![MaybeUninit::uninit(); size].into_boxed_slice())
+}
+
+fn new_tlsf() -> TheGenerationalTlsf {
+    let mut tlsf = TheGenerationalTlsf::new();
+    tlsf.tlsf().insert_free_block(new_pool(65536));
+    tlsf
+}
+
+#[test]
+fn stale_handles() {
+    let mut tlsf = new_tlsf();
+    for &(size, align) in &[(0, 1), (1, 1), (10, 1), (100, 8), (100, 64), (4000, 256)] {
+        let layout = Layout::from_size_align(size, align).unwrap();
+        let (ptr, generation) = tlsf.allocate(layout).unwrap();
+        assert_eq!(ptr.as_ptr() as usize % align, 0);
+        unsafe {
+            // The whole payload is writable
+            ptr.as_ptr().write_bytes(0x5a, size);
+            assert_eq!(tlsf.generation(ptr), generation);
+            assert!(tlsf.validate_handle(ptr, generation));
+            assert!(!tlsf.validate_handle(ptr, generation.wrapping_add(1)));
+
+            tlsf.deallocate(ptr, align);
+            assert!(!tlsf.validate_handle(ptr, generation));
+        }
+
+        // Reusing the memory block gives it a new generation number
+        let (ptr2, generation2) = tlsf.allocate(layout).unwrap();
+        assert_eq!(ptr2, ptr);
+        assert_ne!(generation2, generation);
+        unsafe {
+            assert!(!tlsf.validate_handle(ptr, generation));
+            assert!(tlsf.validate_handle(ptr2, generation2));
+            tlsf.deallocate(ptr2, align);
+        }
+    }
+}
+
+#[test]
+fn reallocate_keeps_generation() {
+    let mut tlsf = new_tlsf();
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    let (ptr, generation) = tlsf.allocate(layout).unwrap();
+
+    // Keep `ptr` from growing in place
+    let (blocker, _) = tlsf.allocate(layout).unwrap();
+
+    unsafe {
+        ptr.as_ptr().write_bytes(0x5a, 64);
+        let new_layout = Layout::from_size_align(1024, 8).unwrap();
+        let new_ptr = tlsf.reallocate(ptr, new_layout).unwrap();
+        assert_ne!(new_ptr, ptr);
+        assert!((0..64).all(|i| *new_ptr.as_ptr().add(i) == 0x5a));
+        assert!(tlsf.validate_handle(new_ptr, generation));
+        assert!(!tlsf.validate_handle(ptr, generation));
+
+        // Failed reallocation leaves the handle valid
+        let huge_layout = Layout::from_size_align(1 << 20, 8).unwrap();
+        assert!(tlsf.reallocate(new_ptr, huge_layout).is_none());
+        assert!(tlsf.validate_handle(new_ptr, generation));
+
+        tlsf.deallocate(new_ptr, 8);
+        tlsf.deallocate(blocker, 8);
+    }
+}
+
+#[test]
+fn generation_wraps_around() {
+    let mut tlsf = new_tlsf();
+    tlsf.next_generation = Generation::MAX;
+    let layout = Layout::new::<u64>();
+    let (ptr1, generation1) = tlsf.allocate(layout).unwrap();
+    let (ptr2, generation2) = tlsf.allocate(layout).unwrap();
+    assert_eq!(generation1, Generation::MAX);
+    assert_ne!(generation2, FREED_GENERATION);
+    unsafe {
+        tlsf.deallocate(ptr1, 8);
+        tlsf.deallocate(ptr2, 8);
+    }
+}
@@ -2,33 +2,179 @@ use const_default1::ConstDefault;
 use core::{
     alloc,
     cell::UnsafeCell,
+    fmt,
     marker::PhantomData,
     ops,
     ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, Ordering},
 };
 
 use super::FlexTlsf;
+use crate::{
+    contention::ContentionCounters, utils::nonnull_slice_len, ContentionStats, HeapStats,
+    ThreadCacheBackend, TryAllocError, GRANULARITY,
+};
+#[cfg(all(feature = "std", any(unix, windows)))]
+use crate::ThreadCache;
+#[cfg(feature = "allocator-api")]
+use crate::utils::nonnull_slice_from_raw_parts;
+#[cfg(feature = "hardened")]
+use crate::{corruption::corruption_handler, HeapError};
 
 // `doc(cfg(...))` needs to be attached to the type for it to be displayed
 // on the docs.
 if_supported_target! {
     /// [`Tlsf`] as a global allocator.
     ///
+    /// Memory is acquired through a platform-specific backend:
+    ///
+    ///  - Unix: `mmap` with a `pthread` mutex. With the `std` feature, the
+    ///    mutex of an instance created by [`Self::new_with_thread_cache`] or
+    ///    passed to [`Self::hold_lock_across_fork`] is held across `fork` so
+    ///    that the child process can't inherit it in a locked state.
+    ///  - Windows: `VirtualAlloc` with an SRW lock
+    ///  - Hermit: `sys_alloc` with a futex-based mutex
+    ///  - SGX enclaves (`x86_64-fortanix-unknown-sgx`): The heap region
+    ///    reserved at enclave build time with a spinlock. The region is
+    ///    handed out as a whole to the first instance that allocates, so
+    ///    other instances can't allocate memory, and the `System` allocator
+    ///    of `std` must not be used either because it manages the same
+    ///    region.
+    ///  - UEFI (`x86_64-unknown-uefi`): `AllocatePages` from boot services,
+    ///    excluding event notification functions by raising the task
+    ///    priority level to `TPL_NOTIFY`. The system table must be
+    ///    registered by [`set_uefi_system_table`] first.
+    ///  - `wasm32` without the `atomics` target feature: `memory.grow`
+    ///    without locking (this is a drop-in replacement for `wee_alloc`)
+    ///
+    /// The bin counts `FLLEN` and `SLLEN` (see [`Tlsf`]) default to the
+    /// maximum values, which support all allocation sizes with a fine
+    /// granularity. Applications with small allocations only can reduce them
+    /// to shrink the allocator state and speed up searches. Allocations
+    /// exceeding the resulting maximum block size are still possible (except
+    /// on `wasm32`); they are served by dedicated memory blocks obtained from
+    /// the system, which are returned to the system on deallocation.
+    ///
     /// [`Tlsf`]: crate::Tlsf
-    pub struct GlobalTlsf<Options: GlobalTlsfOptions = ()> {
-        inner: UnsafeCell<TheTlsf<Options>>,
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::{GlobalTlsf, GlobalTlsfConfig};
+    ///
+    /// // Request memory from the system in 1MiB units and limit the
+    /// // segregated lists to allocations of up to 2²⁴ × `GRANULARITY` bytes
+    /// type MyGlobalTlsf = GlobalTlsf<GlobalTlsfConfig<{ 1 << 20 }>, 24, 8>;
+    ///
+    /// #[global_allocator]
+    /// static A: MyGlobalTlsf = MyGlobalTlsf::new();
+    ///
+    /// let mut v = vec![1u32; 4];
+    /// v.push(5);
+    /// ```
+    ///
+    /// # Multiple Heaps
+    ///
+    /// Each instance of `GlobalTlsf` is an independent heap with its own
+    /// memory pools, lock, and [statistics](Self::stats). Besides the one
+    /// registered as `#[global_allocator]`, an application can declare
+    /// additional instances to isolate the memory usage of its subsystems.
+    ///
+    /// ```rust
+    /// use rlsf::GlobalTlsf;
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// static NET_HEAP: GlobalTlsf = GlobalTlsf::new();
+    /// static GENERAL_HEAP: GlobalTlsf = GlobalTlsf::new();
+    ///
+    /// let layout = Layout::from_size_align(1500, 8).unwrap();
+    /// unsafe {
+    ///     let packet = NET_HEAP.alloc(layout);
+    ///     assert!(!packet.is_null());
+    ///     assert_eq!(NET_HEAP.stats().num_allocations, 1);
+    ///     assert_eq!(GENERAL_HEAP.stats().num_allocations, 0);
+    ///     NET_HEAP.dealloc(packet, layout);
+    /// }
+    /// ```
+    ///
+    /// # Reentrancy
+    ///
+    /// With the `std` feature on Unix and Windows, each thread tracks
+    /// which `GlobalTlsf` locks it holds. An allocator call made on an
+    /// instance whose lock the thread already holds (e.g., by a signal
+    /// handler interrupting the allocator, or by a hook) would deadlock on
+    /// the non-reentrant lock, so it takes a fallback path instead:
+    /// allocations and reallocations fail, and deallocations are deferred
+    /// until the lock is taken next time. Under [`GlobalAlloc`] a failed
+    /// allocation usually aborts the program, so such code should allocate
+    /// from another instance, which is unaffected.
+    ///
+    /// [`GlobalAlloc`]: alloc::GlobalAlloc
+    pub struct GlobalTlsf<
+        Options: GlobalTlsfOptions = (),
+        const FLLEN: usize = { usize::BITS as usize },
+        const SLLEN: usize = { usize::BITS as usize },
+    > {
+        inner: UnsafeCell<TheTlsf<Options, FLLEN, SLLEN>>,
+        stats: UnsafeCell<GlobalTlsfStats>,
         #[cfg(not(doc))]
         mutex: os::Mutex,
+        #[cfg(all(feature = "std", any(unix, windows)))]
+        thread_cache: bool,
+        #[cfg(all(feature = "std", unix))]
+        debug_pool: bool,
+        #[cfg(all(feature = "std", unix, not(doc)))]
+        large_free_quarantine: UnsafeCell<large_free_quarantine::LargeFreeQuarantine>,
+        hooks: AtomicPtr<GlobalTlsfHooks>,
+        /// The memory blocks deallocated by nested calls, which are
+        /// deallocated the next time the lock is taken
+        deferred_frees: AtomicPtr<DeferredFree>,
+        contention: ContentionCounters,
         _phantom: PhantomData<fn() -> Options>,
     }
 }
 
+#[cfg(all(feature = "std", any(unix, windows)))]
+mod reentrancy;
+#[cfg(all(feature = "std", any(unix, windows)))]
+mod thread_cache;
+#[cfg(all(feature = "std", unix, not(doc)))]
+mod debug_pool;
+#[cfg(all(feature = "std", unix, not(doc)))]
+mod large_free_quarantine;
+
+/// A memory block in [`GlobalTlsf::deferred_frees`], written over its
+/// payload. The payload of an allocation is at least `GRANULARITY / 2` bytes
+/// large and aligned, so it can hold this.
+struct DeferredFree {
+    next: *mut DeferredFree,
+    /// The alignment of the allocation, or zero if unknown
+    align: usize,
+}
+
+#[cfg(feature = "arenas")]
+mod arenas;
+#[cfg(feature = "arenas")]
+pub use self::arenas::*;
+
 cfg_if::cfg_if! {
     if #[cfg(doc)] {
         // don't compile `os` in rustdoc
     } else if #[cfg(unix)] {
         mod unix;
         use self::unix as os;
+    } else if #[cfg(windows)] {
+        mod windows;
+        use self::windows as os;
+    } else if #[cfg(target_os = "hermit")] {
+        mod hermit;
+        use self::hermit as os;
+    } else if #[cfg(all(target_arch = "x86_64", target_env = "sgx"))] {
+        mod sgx;
+        use self::sgx as os;
+    } else if #[cfg(all(target_os = "uefi", target_arch = "x86_64"))] {
+        mod uefi;
+        use self::uefi as os;
     } else if #[cfg(target_arch = "wasm32")] {
         mod wasm32;
         use self::wasm32 as os;
@@ -40,13 +186,61 @@ cfg_if::cfg_if! {
     }
 }
 
+/// Register the UEFI system table, through which [`GlobalTlsf`] obtains
+/// memory from boot services, or unregister it by passing `None`.
+///
+/// `GlobalTlsf` can't allocate memory until the system table is registered.
+/// The UEFI specification doesn't provide a way to find the system table
+/// other than the entry point's parameter, so this should be done at the
+/// beginning of the entry point.
+///
+/// # Safety
+///
+///  - `system_table` must point to a valid `EFI_SYSTEM_TABLE`.
+///  - The system table must be unregistered before calling
+///    `ExitBootServices`. The memory obtained until then stays valid, but
+///    no more memory can be obtained.
+///  - The allocator must not be used by code running at a task priority
+///    level higher than `TPL_NOTIFY`.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// #![no_std]
+/// #![no_main]
+/// use core::{ffi::c_void, ptr::NonNull};
+///
+/// #[global_allocator]
+/// static A: rlsf::GlobalTlsf = rlsf::GlobalTlsf::new();
+///
+/// #[no_mangle]
+/// extern "efiapi" fn efi_main(image: *mut c_void, system_table: *mut c_void) -> usize {
+///     unsafe { rlsf::set_uefi_system_table(NonNull::new(system_table)) };
+///     // ...
+/// #   0
+/// }
+/// ```
+#[cfg(any(all(target_os = "uefi", target_arch = "x86_64"), doc))]
+#[cfg_attr(
+    feature = "doc_cfg",
+    doc(cfg(all(target_os = "uefi", target_arch = "x86_64")))
+)]
+pub unsafe fn set_uefi_system_table(system_table: Option<NonNull<core::ffi::c_void>>) {
+    #[cfg(not(doc))]
+    os::set_system_table(system_table);
+    #[cfg(doc)]
+    let _ = system_table;
+}
+
 #[cfg(doc)]
-type TheTlsf<Options> = Options;
+type TheTlsf<Options, const FLLEN: usize, const SLLEN: usize> = Options;
 #[cfg(not(doc))]
-type TheTlsf<Options> =
-    FlexTlsf<os::Source<Options>, usize, usize, { usize::BITS as usize }, { usize::BITS as usize }>;
+type TheTlsf<Options, const FLLEN: usize, const SLLEN: usize> =
+    FlexTlsf<os::Source<Options>, usize, usize, FLLEN, SLLEN>;
 
-impl<Options: GlobalTlsfOptions> ConstDefault for GlobalTlsf<Options> {
+impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize> ConstDefault
+    for GlobalTlsf<Options, FLLEN, SLLEN>
+{
     #[allow(clippy::declare_interior_mutable_const)]
     const DEFAULT: Self = Self::new();
 }
@@ -70,6 +264,41 @@ if_supported_target! {
         ///
         /// It's enabled by default.
         const COALESCE_POOLS: bool = true;
+
+        /// The minimum number of bytes to request from the system at once.
+        /// This is rounded up to a power of two no smaller than the system's
+        /// allocation granularity (e.g., the page size).
+        ///
+        /// A larger value reduces the number of system calls and memory
+        /// pools at the cost of memory usage.
+        ///
+        /// It's `65536` by default.
+        const ALLOC_UNIT: usize = 1 << 16;
+
+        /// The minimum size of a free memory block to return the physical
+        /// memory of to the system when a deallocation produces it.
+        ///
+        /// The memory stays mapped and in the free lists, so this bounds the
+        /// resident set size without unmapping memory pools, like calling
+        /// [`GlobalTlsf::trim`] on every large free memory block. The
+        /// deallocations producing such blocks take time proportional to
+        /// their sizes. On platforms where `trim` isn't supported, this
+        /// option has no effect.
+        ///
+        /// It's `usize::MAX` (disabled) by default.
+        const DECOMMIT_THRESHOLD: usize = usize::MAX;
+
+        /// The minimum size of a memory pool to back with transparent huge
+        /// pages on Linux.
+        ///
+        /// Memory pools reaching this size are aligned to and grown in
+        /// multiples of 2MiB, and `madvise(MADV_HUGEPAGE)` is applied to
+        /// them, so that the kernel can map them with huge pages. This
+        /// reduces TLB misses for large heaps at the cost of coarser memory
+        /// usage. On other platforms, this option has no effect.
+        ///
+        /// It's `usize::MAX` (disabled) by default.
+        const HUGEPAGE_THRESHOLD: usize = usize::MAX;
     }
 }
 
@@ -91,84 +320,1307 @@ impl GlobalTlsfOptions for SmallGlobalTlsfOptions {
     const COALESCE_POOLS: bool = false;
 }
 
-unsafe impl<Options: GlobalTlsfOptions> Send for GlobalTlsf<Options> {}
-unsafe impl<Options: GlobalTlsfOptions> Sync for GlobalTlsf<Options> {}
+if_supported_target! {
+    /// [`GlobalTlsfOptions`] for applications with large heaps.
+    #[derive(Debug)]
+    pub struct LargeGlobalTlsfOptions;
+}
+
+if_supported_target! {
+    /// An instantiation of [`GlobalTlsf`] for applications with large heaps,
+    /// such as servers.
+    ///
+    /// Memory is requested from the system in 4MiB units, which are backed by
+    /// transparent huge pages on Linux (see
+    /// [`GlobalTlsfOptions::HUGEPAGE_THRESHOLD`]). The segregated
+    /// lists cover block sizes up to `GRANULARITY << 31` bytes (64GiB on
+    /// 64-bit targets) with 32 subdivisions per power of two; larger
+    /// allocations are served by dedicated memory blocks obtained from the
+    /// system.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// #[global_allocator]
+    /// static A: rlsf::LargeGlobalTlsf = rlsf::LargeGlobalTlsf::new();
+    ///
+    /// let v = vec![0u8; 100 << 20];
+    /// assert_eq!(v.len(), 100 << 20);
+    /// ```
+    pub type LargeGlobalTlsf = GlobalTlsf<LargeGlobalTlsfOptions, 31, 32>;
+}
+
+impl GlobalTlsfOptions for LargeGlobalTlsfOptions {
+    const ALLOC_UNIT: usize = 1 << 22;
+    const HUGEPAGE_THRESHOLD: usize = 1 << 21;
+}
 
-impl<Options: GlobalTlsfOptions> GlobalTlsf<Options> {
+if_supported_target! {
+    /// [`GlobalTlsfOptions`] specified by const generic parameters.
+    ///
+    /// The parameters correspond to [`GlobalTlsfOptions::ALLOC_UNIT`],
+    /// [`GlobalTlsfOptions::ENABLE_REALLOCATION`],
+    /// [`GlobalTlsfOptions::COALESCE_POOLS`], and
+    /// [`GlobalTlsfOptions::DECOMMIT_THRESHOLD`], respectively.
+    #[derive(Debug)]
+    pub struct GlobalTlsfConfig<
+        const ALLOC_UNIT: usize,
+        const ENABLE_REALLOCATION: bool = true,
+        const COALESCE_POOLS: bool = true,
+        const DECOMMIT_THRESHOLD: usize = { usize::MAX },
+    >;
+}
+
+impl<
+        const ALLOC_UNIT: usize,
+        const ENABLE_REALLOCATION: bool,
+        const COALESCE_POOLS: bool,
+        const DECOMMIT_THRESHOLD: usize,
+    > GlobalTlsfOptions
+    for GlobalTlsfConfig<ALLOC_UNIT, ENABLE_REALLOCATION, COALESCE_POOLS, DECOMMIT_THRESHOLD>
+{
+    const ENABLE_REALLOCATION: bool = ENABLE_REALLOCATION;
+    const COALESCE_POOLS: bool = COALESCE_POOLS;
+    const ALLOC_UNIT: usize = ALLOC_UNIT;
+    const DECOMMIT_THRESHOLD: usize = DECOMMIT_THRESHOLD;
+}
+
+unsafe impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize> Send
+    for GlobalTlsf<Options, FLLEN, SLLEN>
+{
+}
+unsafe impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize> Sync
+    for GlobalTlsf<Options, FLLEN, SLLEN>
+{
+}
+
+impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize>
+    GlobalTlsf<Options, FLLEN, SLLEN>
+{
     /// Construct an empty instance of `Self`.
     #[inline]
     pub const fn new() -> Self {
         Self {
             inner: UnsafeCell::new(ConstDefault::DEFAULT),
+            stats: UnsafeCell::new(GlobalTlsfStats::EMPTY),
             mutex: ConstDefault::DEFAULT,
+            #[cfg(all(feature = "std", any(unix, windows)))]
+            thread_cache: false,
+            #[cfg(all(feature = "std", unix))]
+            debug_pool: false,
+            #[cfg(all(feature = "std", unix, not(doc)))]
+            large_free_quarantine: UnsafeCell::new(
+                large_free_quarantine::LargeFreeQuarantine::NEW,
+            ),
+            hooks: AtomicPtr::new(ptr::null_mut()),
+            deferred_frees: AtomicPtr::new(ptr::null_mut()),
+            contention: ContentionCounters::NEW,
             _phantom: PhantomData,
         }
     }
+
+    /// Construct an empty instance of `Self` with per-thread caches.
+    ///
+    /// Each thread keeps up to a few dozen recently freed small memory blocks
+    /// (up to `8 * GRANULARITY` bytes, with an alignment smaller than
+    /// [`GRANULARITY`]) per size class and reuses them without taking the
+    /// lock. The caches are refilled from and flushed back to the heap in
+    /// batches, and flushed entirely when their threads exit. This removes
+    /// the lock from most small allocations and deallocations made through
+    /// [`GlobalAlloc`] in multithreaded programs, at the cost of some memory
+    /// held by each thread.
+    ///
+    /// A thread's cache is bound to the first instance created by this
+    /// method it uses. Other instances used by the same thread bypass the
+    /// cache. The blocks held by caches count as being in use in
+    /// [`Self::stats`] and aren't released by [`Self::trim`].
+    /// [`Self::flush_thread_cache`] empties the calling thread's cache.
+    ///
+    /// The caches are [`ThreadCache`]s. `GlobalTlsf` also implements
+    /// [`ThreadCacheBackend`], so a `ThreadCache` can be managed by the
+    /// application instead, e.g., for each task of an executor.
+    ///
+    /// # Safety
+    ///
+    /// The constructed value must not be moved once it has been used for
+    /// allocation, and must outlive all threads that have used it. Storing
+    /// it in a `static` satisfies these requirements. On Unix, this also
+    /// makes its lock held across `fork` as if by
+    /// [`Self::hold_lock_across_fork`].
+    ///
+    /// [`GRANULARITY`]: crate::GRANULARITY
+    /// [`GlobalAlloc`]: core::alloc::GlobalAlloc
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::GlobalTlsf;
+    ///
+    /// #[global_allocator]
+    /// // Safety: `A` is a `static`
+    /// static A: GlobalTlsf = unsafe { GlobalTlsf::new_with_thread_cache() };
+    ///
+    /// let threads: Vec<_> = (0..4)
+    ///     .map(|i| std::thread::spawn(move || vec![i; 4].into_iter().sum::<u32>()))
+    ///     .collect();
+    /// for thread in threads {
+    ///     thread.join().unwrap();
+    /// }
+    /// ```
+    #[cfg(all(feature = "std", any(unix, windows)))]
+    #[cfg_attr(
+        feature = "doc_cfg",
+        doc(cfg(all(feature = "std", any(unix, windows))))
+    )]
+    #[inline]
+    pub const unsafe fn new_with_thread_cache() -> Self {
+        let mut this = Self::new();
+        this.thread_cache = true;
+        this
+    }
+
+    /// Construct an empty instance of `Self` in the debug pool mode, which
+    /// trades memory and speed for catching memory errors as they happen
+    /// (like Electric Fence).
+    ///
+    /// Each allocation gets its own pages obtained by `mmap`, placed as
+    /// close to their end as its alignment permits, and surrounded by guard
+    /// pages mapped with `PROT_NONE`. Deallocation returns the physical
+    /// memory to the system and re-protects the pages with `PROT_NONE`
+    /// without ever reusing their addresses. As a result, the following
+    /// errors fault with `SIGSEGV` at the offending access:
+    ///
+    ///  - Buffer overflows past the end of an allocation's usable size,
+    ///    which exceeds the requested size only by the padding for the
+    ///    alignment (at least that of `usize`) and the minimum size
+    ///    (`GRANULARITY / 2`)
+    ///  - Large buffer underflows (reaching the guard page before the
+    ///    allocation)
+    ///  - Uses after free and double frees
+    ///
+    /// Every allocation occupies at least two pages of address space and one
+    /// page of physical memory, and the address space of deallocated memory
+    /// is never released, so this mode is only suitable for reproducing
+    /// elusive memory corruption. Since the mode is selected per instance,
+    /// it can be applied to the heap of a suspect subsystem only (see
+    /// [Multiple Heaps](Self#multiple-heaps)). The memory used by this mode
+    /// isn't counted in [`GlobalTlsfStats::bytes_mapped`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::GlobalTlsf;
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// static SUSPECT_HEAP: GlobalTlsf = GlobalTlsf::new_debug_pool();
+    ///
+    /// let layout = Layout::new::<[u8; 100]>();
+    /// unsafe {
+    ///     let ptr = SUSPECT_HEAP.alloc(layout);
+    ///     ptr.write_bytes(1, 100);
+    ///     // `ptr.add(100).write(1)` would fault here
+    ///     SUSPECT_HEAP.dealloc(ptr, layout);
+    ///     // So would `ptr.write(1)`
+    /// }
+    /// ```
+    #[cfg(all(feature = "std", unix))]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(all(feature = "std", unix))))]
+    #[inline]
+    pub const fn new_debug_pool() -> Self {
+        let mut this = Self::new();
+        this.debug_pool = true;
+        this
+    }
+}
+
+impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize>
+    GlobalTlsf<Options, FLLEN, SLLEN>
+{
+    /// Get the statistics of `self`.
+    ///
+    /// The counters are updated on every allocator call, so calling this
+    /// method is cheap.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::SmallGlobalTlsf;
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// static A: SmallGlobalTlsf = SmallGlobalTlsf::new();
+    ///
+    /// let layout = Layout::from_size_align(100, 1).unwrap();
+    /// unsafe {
+    ///     let ptr = A.alloc(layout);
+    ///     let stats = A.stats();
+    ///     assert_eq!(stats.num_allocations, 1);
+    ///     assert!(stats.bytes_in_use >= 100);
+    ///     assert!(stats.bytes_mapped >= stats.bytes_in_use);
+    ///     A.dealloc(ptr, layout);
+    /// }
+    ///
+    /// let stats = A.stats();
+    /// assert_eq!(stats.num_allocations, 0);
+    /// assert_eq!(stats.bytes_in_use, 0);
+    /// assert!(stats.peak_bytes_in_use >= 100);
+    /// ```
+    pub fn stats(&self) -> GlobalTlsfStats {
+        let mut inner = self.lock_inner();
+        GlobalTlsfStats {
+            bytes_mapped: inner.capacity(),
+            ..*inner.stats_mut()
+        }
+    }
+
+    /// Get a snapshot of the statistics of `self` in the same format as
+    /// [`Tlsf::heap_stats`](crate::Tlsf::heap_stats).
+    ///
+    /// This combines [`Self::stats`] with the total size of the free memory
+    /// blocks (see [`Self::mallinfo`]), which are gathered under the same
+    /// lock. [`HeapStats::pool_bytes`] is [`GlobalTlsfStats::bytes_mapped`].
+    /// Like [`Self::mallinfo`], this walks the free lists.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::SmallGlobalTlsf;
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// static A: SmallGlobalTlsf = SmallGlobalTlsf::new();
+    ///
+    /// let layout = Layout::from_size_align(100, 1).unwrap();
+    /// let before = A.heap_stats();
+    /// let ptr = unsafe { A.alloc(layout) };
+    /// let after = A.heap_stats();
+    /// assert_eq!(after.num_allocations, before.num_allocations + 1);
+    /// assert!(after.bytes_in_use >= before.bytes_in_use + 100);
+    /// assert!(after.pool_bytes >= after.bytes_in_use + after.free_bytes);
+    /// unsafe { A.dealloc(ptr, layout) };
+    /// ```
+    pub fn heap_stats(&self) -> HeapStats {
+        let mut inner = self.lock_inner();
+        let (_, free_bytes) = inner.free_blocks_summary();
+        let stats = *inner.stats_mut();
+        HeapStats {
+            bytes_in_use: stats.bytes_in_use,
+            peak_bytes_in_use: stats.peak_bytes_in_use,
+            pool_bytes: inner.capacity(),
+            free_bytes,
+            num_allocations: stats.num_allocations,
+            failed_allocations: stats.failed_allocations,
+        }
+    }
+
+    /// Attempt to allocate memory without waiting for the lock.
+    ///
+    /// Returns [`TryAllocError::Contended`] if another thread holds the lock,
+    /// letting soft real-time code fall back to another memory source (e.g.,
+    /// a per-thread pool) instead of blocking. The thread cache is bypassed.
+    /// Growing the heap might still make a system call. The returned memory
+    /// block can be deallocated through [`GlobalAlloc`].
+    ///
+    /// [`GlobalAlloc`]: core::alloc::GlobalAlloc
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::GlobalTlsf;
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// static A: GlobalTlsf = GlobalTlsf::new();
+    ///
+    /// let layout = Layout::new::<u64>();
+    /// let ptr = A.try_allocate(layout).unwrap();
+    /// unsafe { A.dealloc(ptr.as_ptr(), layout) };
+    /// ```
+    pub fn try_allocate(&self, layout: alloc::Layout) -> Result<NonNull<u8>, TryAllocError> {
+        let ptr = self
+            .try_lock_inner()
+            .ok_or(TryAllocError::Contended)?
+            .allocate(layout)
+            .ok_or(TryAllocError::Exhausted)?;
+        self.hook_alloc(ptr.as_ptr(), layout);
+        Ok(ptr)
+    }
+
+    /// Get the contention counters of `self`. [`ContentionStats::cache_misses`]
+    /// counts the refills of the thread caches.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::GlobalTlsf;
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// static A: GlobalTlsf = GlobalTlsf::new();
+    ///
+    /// let layout = Layout::new::<u64>();
+    /// unsafe { A.dealloc(A.alloc(layout), layout) };
+    /// let stats = A.contention_stats();
+    /// assert_eq!(stats.lock_acquisitions, 2);
+    /// assert!(stats.lock_waits <= 2);
+    /// ```
+    pub fn contention_stats(&self) -> ContentionStats {
+        self.contention.load()
+    }
+
+    /// Emit the statistics of `self` ([`Self::stats`] and
+    /// [`Self::contention_stats`]) through the [`metrics`] facade.
+    ///
+    /// The gauges `rlsf.bytes_in_use`, `rlsf.peak_bytes_in_use`,
+    /// `rlsf.bytes_mapped`, and `rlsf.live_allocations` and the counters
+    /// `rlsf.alloc_errors`, `rlsf.lock_acquisitions`, `rlsf.lock_waits`,
+    /// `rlsf.remote_frees`, and `rlsf.cache_misses` are updated.
+    ///
+    /// Nothing is emitted by the allocator itself because metrics recorders
+    /// usually allocate memory. Call this method periodically instead, e.g.,
+    /// from the thread that exports the other metrics of the application.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::GlobalTlsf;
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// static A: GlobalTlsf = GlobalTlsf::new();
+    ///
+    /// let layout = Layout::new::<u64>();
+    /// unsafe { A.dealloc(A.alloc(layout), layout) };
+    /// A.report_metrics();
+    /// ```
+    #[cfg(feature = "metrics")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "metrics")))]
+    pub fn report_metrics(&self) {
+        self.stats().report_metrics();
+        self.contention_stats().report_metrics();
+    }
+
+    /// Get a summary of the heap in the format of glibc's `mallinfo2`, which
+    /// monitoring tools commonly expect.
+    ///
+    /// Unlike [`Self::stats`], this walks the free lists, so it takes time
+    /// proportional to the number of free memory blocks.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::GlobalTlsf;
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// static A: GlobalTlsf = GlobalTlsf::new();
+    ///
+    /// let layout = Layout::from_size_align(100, 1).unwrap();
+    /// unsafe {
+    ///     let ptr = A.alloc(layout);
+    ///     let info = A.mallinfo();
+    ///     assert!(info.uordblks >= 100);
+    ///     assert!(info.ordblks >= 1);
+    ///     assert!(info.arena >= info.uordblks + info.fordblks);
+    ///     A.dealloc(ptr, layout);
+    /// }
+    /// ```
+    pub fn mallinfo(&self) -> Mallinfo {
+        let mut inner = self.lock_inner();
+        let (ordblks, fordblks) = inner.free_blocks_summary();
+        Mallinfo {
+            arena: inner.capacity(),
+            ordblks,
+            uordblks: inner.stats_mut().bytes_in_use,
+            fordblks,
+        }
+    }
+
+    /// Write a human-readable summary of the heap to `w`, e.g., for a debug
+    /// HTTP endpoint or a `SIGUSR1` handler.
+    ///
+    /// The report includes [`Self::stats`], the free memory blocks (see
+    /// [`Self::mallinfo`]), and [`Self::contention_stats`]. The lock is
+    /// released before anything is written, so `w` may allocate memory from
+    /// `self`. Like [`Self::mallinfo`], this walks the free lists.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::GlobalTlsf;
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// static A: GlobalTlsf = GlobalTlsf::new();
+    ///
+    /// let layout = Layout::from_size_align(100, 1).unwrap();
+    /// let ptr = unsafe { A.alloc(layout) };
+    ///
+    /// let mut report = String::new();
+    /// A.write_report(&mut report).unwrap();
+    /// print!("{}", report);
+    /// assert!(report.contains("1 allocations"));
+    ///
+    /// unsafe { A.dealloc(ptr, layout) };
+    /// ```
+    pub fn write_report(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        let (stats, (free_blocks, free_bytes)) = {
+            let mut inner = self.lock_inner();
+            let free = inner.free_blocks_summary();
+            let stats = GlobalTlsfStats {
+                bytes_mapped: inner.capacity(),
+                ..*inner.stats_mut()
+            };
+            (stats, free)
+        };
+        let contention = self.contention_stats();
+
+        writeln!(w, "mapped:   {} bytes", stats.bytes_mapped)?;
+        writeln!(
+            w,
+            "in use:   {} bytes in {} allocations",
+            stats.bytes_in_use, stats.num_allocations
+        )?;
+        writeln!(w, "peak:     {} bytes", stats.peak_bytes_in_use)?;
+        writeln!(w, "free:     {} bytes in {} blocks", free_bytes, free_blocks)?;
+        writeln!(w, "failures: {}", stats.failed_allocations)?;
+        writeln!(
+            w,
+            "lock:     {} acquisitions, {} waits",
+            contention.lock_acquisitions, contention.lock_waits
+        )
+    }
+
+    /// Return the physical memory backing the free space to the system.
+    ///
+    /// The memory stays mapped and is transparently reused by subsequent
+    /// allocations. Returns the number of bytes released, which can be
+    /// smaller than the free space because only whole memory pages can be
+    /// released.
+    ///
+    /// This method is useful for reducing the resident set size after a
+    /// temporary spike in memory usage. It takes time proportional to the
+    /// number of free memory blocks.
+    ///
+    ///  - Unix: `madvise(MADV_DONTNEED)`
+    ///  - Windows: `VirtualAlloc(MEM_RESET)`
+    ///  - Hermit, SGX, UEFI, `wasm32`: Not supported; this method always
+    ///    returns `0`.
+    ///
+    /// With the `poison` feature, this method does nothing and returns `0`
+    /// because it would erase the poison pattern of the free memory blocks.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::GlobalTlsf;
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// static A: GlobalTlsf = GlobalTlsf::new();
+    ///
+    /// let layout = Layout::from_size_align(1 << 20, 1).unwrap();
+    /// unsafe {
+    ///     let ptr = A.alloc(layout);
+    ///     ptr.write_bytes(1, layout.size());
+    ///     A.dealloc(ptr, layout);
+    /// }
+    ///
+    /// A.trim();
+    /// ```
+    pub fn trim(&self) -> usize {
+        let mut inner = self.lock_inner();
+        let mut num_released_bytes = 0;
+        inner.for_each_free_payload(|region| {
+            // Safety: The contents of `region` are irrelevant to the allocator
+            num_released_bytes += unsafe { os::discard(region) };
+        });
+        num_released_bytes
+    }
+
+    /// Quarantine the memory blocks of subsequent large deallocations for
+    /// `period` subsequent large deallocations, or stop quarantining them
+    /// by passing zero.
+    ///
+    /// A deallocated memory block containing at least one whole page (not
+    /// counting the first few words, which hold the quarantine's
+    /// bookkeeping) isn't returned to the free lists right away. Instead,
+    /// its whole pages are remapped with `PROT_NONE`, so a use after free
+    /// through a dangling pointer to a large buffer faults with `SIGSEGV`
+    /// at the offending address. The memory block is made accessible again
+    /// and deallocated once `period` more memory blocks have entered the
+    /// quarantine. Reducing the period releases the excess memory blocks
+    /// immediately.
+    ///
+    /// Quarantined memory blocks don't count as being in use in
+    /// [`Self::stats`], but they aren't available for allocations either.
+    /// The memory blocks freed by reallocations aren't quarantined, and
+    /// neither are the allocations made in the
+    /// [debug pool mode](Self::new_debug_pool), which provides stronger
+    /// guarantees.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::GlobalTlsf;
+    /// use std::alloc::{GlobalAlloc, Layout};
+    ///
+    /// static A: GlobalTlsf = GlobalTlsf::new();
+    /// A.set_large_free_quarantine(4);
+    ///
+    /// let layout = Layout::from_size_align(1 << 16, 8).unwrap();
+    /// unsafe {
+    ///     let ptr = A.alloc(layout);
+    ///     A.dealloc(ptr, layout);
+    ///     // `ptr.add(1 << 15).write(1)` would fault here
+    /// }
+    /// ```
+    #[cfg(all(feature = "std", unix))]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(all(feature = "std", unix))))]
+    pub fn set_large_free_quarantine(&self, period: usize) {
+        let mut inner = self.lock_inner();
+        // Safety: Protected by `mutex`
+        unsafe { (*self.large_free_quarantine.get()).set_period(period) };
+        inner.release_expired_large_frees();
+    }
+
+    /// Hold the lock of `self` across `fork`, so that a child process forked
+    /// while another thread is allocating from `self` doesn't inherit the
+    /// lock in a locked state and deadlock on its first allocation.
+    ///
+    /// The `fork` handlers keep track of `self` by its address, which is why
+    /// this method requires `&'static self`. The instances created by
+    /// [`Self::new_with_thread_cache`] are held across `fork` without
+    /// calling this method.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::GlobalTlsf;
+    ///
+    /// #[global_allocator]
+    /// static A: GlobalTlsf = GlobalTlsf::new();
+    ///
+    /// A.hold_lock_across_fork();
+    /// ```
+    #[cfg(all(feature = "std", unix))]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(all(feature = "std", unix))))]
+    #[inline]
+    pub fn hold_lock_across_fork(&'static self) {
+        // Safety: `self` is borrowed forever, so it can't be moved
+        unsafe { self.mutex.hold_across_fork() };
+    }
+
+    /// Call [`Self::hold_lock_across_fork`] if `self` was created by
+    /// [`Self::new_with_thread_cache`], whose caller guarantees that `self`
+    /// isn't moved.
+    #[inline]
+    fn hold_lock_across_fork_if_pinned(&self) {
+        #[cfg(all(feature = "std", unix))]
+        if self.thread_cache {
+            // Safety: See above
+            unsafe { self.mutex.hold_across_fork() };
+        }
+    }
+
+    /// Register the hooks invoked on every allocation and deallocation made
+    /// through [`GlobalAlloc`], or unregister them by passing `None`.
+    ///
+    /// This lets an external heap profiler observe the heap without patching
+    /// this crate. The hooks are invoked without holding the lock, so they
+    /// can use the allocator (including `self`) to record the events, but
+    /// the resulting allocations invoke the hooks as well, so they must
+    /// guard against unbounded recursion themselves.
+    ///
+    /// [`GlobalAlloc`]: core::alloc::GlobalAlloc
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::{GlobalTlsf, GlobalTlsfHooks};
+    /// use std::{
+    ///     alloc::{GlobalAlloc, Layout},
+    ///     ptr::NonNull,
+    ///     sync::atomic::{AtomicUsize, Ordering},
+    /// };
+    ///
+    /// static A: GlobalTlsf = GlobalTlsf::new();
+    /// static NUM_ALLOCS: AtomicUsize = AtomicUsize::new(0);
+    /// static HOOKS: GlobalTlsfHooks = GlobalTlsfHooks {
+    ///     on_alloc: |_, _| {
+    ///         NUM_ALLOCS.fetch_add(1, Ordering::Relaxed);
+    ///     },
+    ///     on_dealloc: |_, _| {},
+    /// };
+    ///
+    /// A.set_hooks(Some(&HOOKS));
+    /// let layout = Layout::new::<u64>();
+    /// unsafe { A.dealloc(A.alloc(layout), layout) };
+    /// assert_eq!(NUM_ALLOCS.load(Ordering::Relaxed), 1);
+    /// ```
+    pub fn set_hooks(&self, hooks: Option<&'static GlobalTlsfHooks>) {
+        let hooks = hooks.map_or(ptr::null(), |hooks| hooks as *const _);
+        self.hooks.store(hooks as *mut _, Ordering::Release);
+    }
+
+    #[inline]
+    fn hooks(&self) -> Option<&'static GlobalTlsfHooks> {
+        // Safety: Only `&'static GlobalTlsfHooks` are stored
+        unsafe { self.hooks.load(Ordering::Acquire).as_ref() }
+    }
+
+    /// Invoke [`GlobalTlsfHooks::on_alloc`] if `ptr` is non-null. Returns
+    /// `ptr`.
+    #[inline]
+    fn hook_alloc(&self, ptr: *mut u8, layout: alloc::Layout) -> *mut u8 {
+        if let (Some(hooks), Some(ptr)) = (self.hooks(), NonNull::new(ptr)) {
+            (hooks.on_alloc)(ptr, layout);
+        }
+        ptr
+    }
+
+    #[inline]
+    fn lock_inner(&self) -> LockGuard<'_, Options, FLLEN, SLLEN> {
+        self.hold_lock_across_fork_if_pinned();
+        let waited = !self.mutex.try_lock();
+        if waited {
+            self.mutex.lock();
+        }
+        self.contention.record_lock(waited);
+        LockGuard::new(self)
+    }
+
+    #[inline]
+    fn try_lock_inner(&self) -> Option<LockGuard<'_, Options, FLLEN, SLLEN>> {
+        if self.is_reentered() {
+            return None;
+        }
+        self.hold_lock_across_fork_if_pinned();
+        if !self.mutex.try_lock() {
+            return None;
+        }
+        self.contention.record_lock(false);
+        Some(LockGuard::new(self))
+    }
+
+    /// Acquire the lock for an allocator call. Returns `None` instead of
+    /// deadlocking if the call is nested in another allocator call on the
+    /// same thread (see [`Self::is_reentered`]).
+    #[inline]
+    fn enter_inner(&self) -> Option<LockGuard<'_, Options, FLLEN, SLLEN>> {
+        if self.is_reentered() {
+            return None;
+        }
+        Some(self.lock_inner())
+    }
+
+    /// Check if the calling thread already holds the lock of `self`, e.g.,
+    /// because a signal handler or a hook called from inside the allocator
+    /// is allocating memory. Taking the non-reentrant
+    /// lock again would deadlock, so such nested calls take a fallback path
+    /// instead: allocations fail, and deallocations are deferred until the
+    /// lock is taken next time.
+    ///
+    /// Always returns `false` without the `std` feature, where there is no
+    /// thread-local storage to track the held locks.
+    #[inline]
+    fn is_reentered(&self) -> bool {
+        #[cfg(all(feature = "std", any(unix, windows)))]
+        {
+            reentrancy::is_locked(self.reentrancy_owner())
+        }
+        #[cfg(not(all(feature = "std", any(unix, windows))))]
+        {
+            false
+        }
+    }
+
+    /// Get the key identifying `self` in [`reentrancy`].
+    #[cfg(all(feature = "std", any(unix, windows)))]
+    #[inline]
+    fn reentrancy_owner(&self) -> *const () {
+        self as *const Self as *const ()
+    }
+
+    /// Deallocate `ptr` the next time the lock is taken.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a previous allocation with alignment `align` (or
+    /// any alignment if `None`).
+    #[cold]
+    unsafe fn defer_deallocate(&self, ptr: NonNull<u8>, align: Option<usize>) {
+        let node = ptr.as_ptr() as *mut DeferredFree;
+        let align = align.unwrap_or(0);
+        let mut next = self.deferred_frees.load(Ordering::Relaxed);
+        loop {
+            // Safety: The payload can hold `DeferredFree` (see its
+            //         documentation), and we own it now
+            node.write(DeferredFree { next, align });
+            // The consumer takes the whole list at once, so ABA can't happen
+            match self.deferred_frees.compare_exchange_weak(
+                next,
+                node,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(actual) => next = actual,
+            }
+        }
+    }
+
+    /// Get the usable size of the allocation `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a previous allocation of `self` with alignment
+    /// `align` (or any alignment if `None`).
+    #[inline]
+    unsafe fn size_of_allocation(&self, ptr: NonNull<u8>, align: Option<usize>) -> usize {
+        #[cfg(all(feature = "std", unix))]
+        if self.debug_pool {
+            return debug_pool::size_of_allocation(ptr);
+        }
+        match align {
+            Some(align) => TheTlsf::<Options, FLLEN, SLLEN>::size_of_allocation(ptr, align),
+            None => TheTlsf::<Options, FLLEN, SLLEN>::size_of_allocation_unknown_align(ptr),
+        }
+    }
+
+    /// Abort the process if the allocation `ptr` shows signs of heap
+    /// corruption. Allocations made in the debug pool mode have no block
+    /// headers to check.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a pointer returned by an allocation function of `self`.
+    #[cfg(feature = "hardened")]
+    #[inline]
+    unsafe fn check_allocation(&self, ptr: NonNull<u8>, align: Option<usize>) {
+        #[cfg(all(feature = "std", unix))]
+        if self.debug_pool {
+            return;
+        }
+        if let Err(reason) = TheTlsf::<Options, FLLEN, SLLEN>::check_allocation(ptr, align) {
+            if let Some(handler) = corruption_handler() {
+                handler(HeapError::BadAllocation { ptr, reason });
+            }
+            os::heap_corruption(reason);
+        }
+    }
 }
 
-impl<Options: GlobalTlsfOptions> GlobalTlsf<Options> {
+#[cfg(all(feature = "std", any(unix, windows)))]
+impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize>
+    GlobalTlsf<Options, FLLEN, SLLEN>
+{
+    /// Return the memory blocks held by the calling thread's cache to `self`.
+    ///
+    /// This does nothing if `self` wasn't created by
+    /// [`Self::new_with_thread_cache`] or the cache is bound to another
+    /// instance.
+    #[cfg_attr(
+        feature = "doc_cfg",
+        doc(cfg(all(feature = "std", any(unix, windows))))
+    )]
+    pub fn flush_thread_cache(&self) {
+        if self.thread_cache {
+            thread_cache::with_cache(self.cache_owner(), Self::flush_cache, |cache| {
+                cache.flush(self)
+            });
+        }
+    }
+
     #[inline]
-    fn lock_inner(&self) -> impl ops::DerefMut<Target = TheTlsf<Options>> + '_ {
-        struct LockGuard<'a, Options: GlobalTlsfOptions>(&'a GlobalTlsf<Options>);
+    fn cache_owner(&self) -> *const () {
+        self as *const Self as *const ()
+    }
+
+    /// Implements [`thread_cache::FlushFn`].
+    ///
+    /// # Safety
+    ///
+    /// `owner` must point to a live `Self`, which the memory blocks in
+    /// `cache` belong to.
+    unsafe fn flush_cache(owner: *const (), cache: &mut ThreadCache) {
+        cache.flush(&*(owner as *const Self));
+    }
+}
 
-        impl<Options: GlobalTlsfOptions> ops::Deref for LockGuard<'_, Options> {
-            type Target = TheTlsf<Options>;
+struct LockGuard<'a, Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize>(
+    &'a GlobalTlsf<Options, FLLEN, SLLEN>,
+);
 
-            #[inline]
-            fn deref(&self) -> &Self::Target {
-                // Safety: Protected by `mutex`
-                unsafe { &*self.0.inner.get() }
+impl<'a, Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize>
+    LockGuard<'a, Options, FLLEN, SLLEN>
+{
+    /// Construct a `LockGuard` after acquiring `tlsf.mutex`.
+    #[inline]
+    fn new(tlsf: &'a GlobalTlsf<Options, FLLEN, SLLEN>) -> Self {
+        #[cfg(all(feature = "std", any(unix, windows)))]
+        reentrancy::enter(tlsf.reentrancy_owner());
+        let mut guard = Self(tlsf);
+        if !tlsf.deferred_frees.load(Ordering::Relaxed).is_null() {
+            guard.drain_deferred_frees();
+        }
+        guard
+    }
+
+    /// Deallocate the memory blocks in [`GlobalTlsf::deferred_frees`].
+    #[cold]
+    fn drain_deferred_frees(&mut self) {
+        let mut node = self.0.deferred_frees.swap(ptr::null_mut(), Ordering::Acquire);
+        while let Some(ptr) = NonNull::new(node) {
+            // Safety: `node` was written by `defer_deallocate`
+            let DeferredFree { next, align } = unsafe { ptr.as_ptr().read() };
+            // Safety: `node` denotes a previous allocation with alignment
+            //         `align` (or an unknown alignment if zero)
+            unsafe {
+                if align == 0 {
+                    self.deallocate_unknown_align(ptr.cast());
+                } else {
+                    self.deallocate(ptr.cast(), align);
+                }
             }
+            node = next;
         }
+    }
+
+    #[inline]
+    fn stats_mut(&mut self) -> &mut GlobalTlsfStats {
+        // Safety: Protected by `mutex`
+        unsafe { &mut *self.0.stats.get() }
+    }
+}
+
+impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize> ops::Deref
+    for LockGuard<'_, Options, FLLEN, SLLEN>
+{
+    type Target = TheTlsf<Options, FLLEN, SLLEN>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // Safety: Protected by `mutex`
+        unsafe { &*self.0.inner.get() }
+    }
+}
+
+impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize> ops::DerefMut
+    for LockGuard<'_, Options, FLLEN, SLLEN>
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: Protected by `mutex`
+        unsafe { &mut *self.0.inner.get() }
+    }
+}
+
+impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize> Drop
+    for LockGuard<'_, Options, FLLEN, SLLEN>
+{
+    #[inline]
+    fn drop(&mut self) {
+        self.0.mutex.unlock();
+        #[cfg(all(feature = "std", any(unix, windows)))]
+        reentrancy::exit(self.0.reentrancy_owner());
+    }
+}
+
+if_supported_target! {
+    /// Statistics of a [`GlobalTlsf`], returned by [`GlobalTlsf::stats`].
+    ///
+    /// Allocation sizes are counted in terms of the usable sizes of memory
+    /// blocks, which might be larger than the requested sizes.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[non_exhaustive]
+    pub struct GlobalTlsfStats {
+        /// The total size of the live allocations.
+        pub bytes_in_use: usize,
+        /// The highest value [`Self::bytes_in_use`] has ever reached.
+        pub peak_bytes_in_use: usize,
+        /// The total number of bytes obtained from the system.
+        pub bytes_mapped: usize,
+        /// The number of live allocations.
+        pub num_allocations: usize,
+        /// The number of allocation and reallocation requests that couldn't
+        /// be satisfied.
+        pub failed_allocations: usize,
+    }
+}
 
-        impl<Options: GlobalTlsfOptions> ops::DerefMut for LockGuard<'_, Options> {
-            #[inline]
-            fn deref_mut(&mut self) -> &mut Self::Target {
-                // Safety: Protected by `mutex`
-                unsafe { &mut *self.0.inner.get() }
+if_supported_target! {
+    /// Hooks invoked by the [`GlobalAlloc`] implementation of [`GlobalTlsf`],
+    /// registered by [`GlobalTlsf::set_hooks`].
+    ///
+    /// [`GlobalAlloc`]: core::alloc::GlobalAlloc
+    #[derive(Debug, Clone, Copy)]
+    pub struct GlobalTlsfHooks {
+        /// Called after a successful allocation with the returned pointer and
+        /// the requested layout. A successful reallocation is reported as a
+        /// deallocation followed by an allocation.
+        pub on_alloc: fn(ptr: NonNull<u8>, layout: alloc::Layout),
+        /// Called with the pointer and the layout passed to the allocator
+        /// before a deallocation, and after a successful reallocation (the
+        /// pointer is dangling in the latter case).
+        pub on_dealloc: fn(ptr: NonNull<u8>, layout: alloc::Layout),
+    }
+}
+
+if_supported_target! {
+    /// A summary of a [`GlobalTlsf`] in the format of glibc's `mallinfo2`,
+    /// returned by [`GlobalTlsf::mallinfo`].
+    ///
+    /// The fields are named after their counterparts in `struct mallinfo2`.
+    /// The fields that don't apply to this allocator are omitted.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[non_exhaustive]
+    pub struct Mallinfo {
+        /// The total number of bytes obtained from the system (the same as
+        /// [`GlobalTlsfStats::bytes_mapped`]).
+        pub arena: usize,
+        /// The number of free memory blocks.
+        pub ordblks: usize,
+        /// The total size of the live allocations (the same as
+        /// [`GlobalTlsfStats::bytes_in_use`]).
+        pub uordblks: usize,
+        /// The total size of the free memory blocks.
+        pub fordblks: usize,
+    }
+}
+
+impl GlobalTlsfStats {
+    const EMPTY: Self = Self {
+        bytes_in_use: 0,
+        peak_bytes_in_use: 0,
+        bytes_mapped: 0,
+        num_allocations: 0,
+        failed_allocations: 0,
+    };
+
+    #[inline]
+    fn record_alloc(&mut self, size: usize) {
+        self.bytes_in_use += size;
+        self.peak_bytes_in_use = self.peak_bytes_in_use.max(self.bytes_in_use);
+        self.num_allocations += 1;
+    }
+
+    #[inline]
+    fn record_dealloc(&mut self, size: usize) {
+        self.bytes_in_use -= size;
+        self.num_allocations -= 1;
+    }
+
+    /// Emit the statistics through the [`metrics`] facade.
+    #[cfg(feature = "metrics")]
+    fn report_metrics(&self) {
+        metrics::gauge!("rlsf.bytes_in_use", self.bytes_in_use as f64);
+        metrics::gauge!("rlsf.peak_bytes_in_use", self.peak_bytes_in_use as f64);
+        metrics::gauge!("rlsf.bytes_mapped", self.bytes_mapped as f64);
+        metrics::gauge!("rlsf.live_allocations", self.num_allocations as f64);
+        metrics::absolute_counter!("rlsf.alloc_errors", self.failed_allocations as u64);
+    }
+}
+
+impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize>
+    LockGuard<'_, Options, FLLEN, SLLEN>
+{
+    #[inline]
+    fn allocate(&mut self, layout: alloc::Layout) -> Option<NonNull<u8>> {
+        #[cfg(all(feature = "std", unix))]
+        let ptr = if self.0.debug_pool {
+            debug_pool::allocate(layout)
+        } else {
+            (**self).allocate(layout)
+        };
+        #[cfg(not(all(feature = "std", unix)))]
+        let ptr = (**self).allocate(layout);
+        let ptr = if let Some(ptr) = ptr {
+            ptr
+        } else {
+            self.stats_mut().failed_allocations += 1;
+            return None;
+        };
+        // Safety: `ptr` denotes a previous allocation
+        let size = unsafe { self.0.size_of_allocation(ptr, None) };
+        self.stats_mut().record_alloc(size);
+        Some(ptr)
+    }
+
+    /// # Safety
+    ///
+    /// `ptr` must denote a previous allocation with alignment `align`.
+    #[inline]
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, align: usize) {
+        #[cfg(all(feature = "std", unix))]
+        if self.0.debug_pool {
+            return self.deallocate_debug_pool(ptr);
+        }
+        #[cfg(feature = "hardened")]
+        self.0.check_allocation(ptr, Some(align));
+        let size = TheTlsf::<Options, FLLEN, SLLEN>::size_of_allocation(ptr, align);
+        self.stats_mut().record_dealloc(size);
+        #[cfg(all(feature = "std", unix))]
+        if self.quarantines_large_frees() && self.quarantine_large_free(ptr, size, Some(align)) {
+            return;
+        }
+        self.deallocate_and_decommit(ptr, Some(align));
+    }
+
+    /// # Safety
+    ///
+    /// `ptr` must denote a previous allocation.
+    #[inline]
+    unsafe fn deallocate_unknown_align(&mut self, ptr: NonNull<u8>) {
+        #[cfg(all(feature = "std", unix))]
+        if self.0.debug_pool {
+            return self.deallocate_debug_pool(ptr);
+        }
+        #[cfg(feature = "hardened")]
+        self.0.check_allocation(ptr, None);
+        let size = TheTlsf::<Options, FLLEN, SLLEN>::size_of_allocation_unknown_align(ptr);
+        self.stats_mut().record_dealloc(size);
+        #[cfg(all(feature = "std", unix))]
+        if self.quarantines_large_frees() && self.quarantine_large_free(ptr, size, None) {
+            return;
+        }
+        self.deallocate_and_decommit(ptr, None);
+    }
+
+    /// Deallocate `ptr` in the debug pool mode.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a previous allocation.
+    #[cfg(all(feature = "std", unix))]
+    #[cold]
+    unsafe fn deallocate_debug_pool(&mut self, ptr: NonNull<u8>) {
+        let size = debug_pool::size_of_allocation(ptr);
+        self.stats_mut().record_dealloc(size);
+        debug_pool::deallocate(ptr);
+    }
+
+    #[cfg(all(feature = "std", unix))]
+    #[inline]
+    fn quarantines_large_frees(&mut self) -> bool {
+        // Safety: Protected by `mutex`
+        unsafe { (*self.0.large_free_quarantine.get()).period() != 0 }
+    }
+
+    /// Make the whole pages in the allocation `ptr` inaccessible and hold it
+    /// back until enough subsequent large deallocations have been made (see
+    /// [`GlobalTlsf::set_large_free_quarantine`]). Returns `false` if `ptr`
+    /// doesn't contain a whole page and must be deallocated right away.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a previous allocation with alignment `align` (or
+    /// any alignment if `None`) and usable size `size`.
+    #[cfg(all(feature = "std", unix))]
+    #[cold]
+    unsafe fn quarantine_large_free(
+        &mut self,
+        ptr: NonNull<u8>,
+        size: usize,
+        align: Option<usize>,
+    ) -> bool {
+        // Safety: Protected by `mutex`
+        if !(*self.0.large_free_quarantine.get()).push(ptr, size, align) {
+            return false;
+        }
+        self.release_expired_large_frees();
+        true
+    }
+
+    /// Deallocate the memory blocks exceeding the quarantine period.
+    #[cfg(all(feature = "std", unix))]
+    fn release_expired_large_frees(&mut self) {
+        // Safety: Protected by `mutex`, and the quarantined memory blocks
+        //         are owned by the quarantine
+        while let Some((ptr, align)) = unsafe { (*self.0.large_free_quarantine.get()).pop_expired() }
+        {
+            // Safety: `ptr` denotes a previous allocation with alignment
+            //         `align` (or an unknown alignment if `None`)
+            unsafe { self.deallocate_and_decommit(ptr, align) };
+        }
+    }
+
+    /// Deallocate `ptr` and return the physical memory of the resulting free
+    /// memory block to the system if it's at least
+    /// [`GlobalTlsfOptions::DECOMMIT_THRESHOLD`] bytes large.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a previous allocation with alignment `align` (or
+    /// any alignment if `None`).
+    #[inline]
+    unsafe fn deallocate_and_decommit(&mut self, ptr: NonNull<u8>, align: Option<usize>) {
+        // Discarding a free block's payload would erase its poison pattern
+        if Options::DECOMMIT_THRESHOLD == usize::MAX || cfg!(feature = "poison") {
+            match align {
+                Some(align) => (**self).deallocate(ptr, align),
+                None => (**self).deallocate_unknown_align(ptr),
             }
+            return;
         }
 
-        impl<Options: GlobalTlsfOptions> Drop for LockGuard<'_, Options> {
-            #[inline]
-            fn drop(&mut self) {
-                self.0.mutex.unlock();
+        if let Some(payload) = (**self).deallocate_and_get_free_payload(ptr, align) {
+            if nonnull_slice_len(payload) >= Options::DECOMMIT_THRESHOLD {
+                // Safety: The contents of `payload` are irrelevant to the
+                //         allocator
+                os::discard(payload);
             }
         }
+    }
 
-        self.mutex.lock();
-        LockGuard(self)
+    /// # Safety
+    ///
+    /// `ptr` must denote a previous allocation with alignment
+    /// `new_layout.align()`.
+    #[inline]
+    unsafe fn reallocate(
+        &mut self,
+        ptr: NonNull<u8>,
+        new_layout: alloc::Layout,
+    ) -> Option<NonNull<u8>> {
+        #[cfg(feature = "hardened")]
+        self.0.check_allocation(ptr, Some(new_layout.align()));
+        let old_size = self.0.size_of_allocation(ptr, None);
+        #[cfg(all(feature = "std", unix))]
+        let new_ptr = if self.0.debug_pool {
+            debug_pool::reallocate(ptr, new_layout)
+        } else {
+            (**self).reallocate(ptr, new_layout)
+        };
+        #[cfg(not(all(feature = "std", unix)))]
+        let new_ptr = (**self).reallocate(ptr, new_layout);
+        let new_ptr = if let Some(new_ptr) = new_ptr {
+            new_ptr
+        } else {
+            self.stats_mut().failed_allocations += 1;
+            return None;
+        };
+        let new_size = self.0.size_of_allocation(new_ptr, None);
+        let stats = self.stats_mut();
+        stats.record_dealloc(old_size);
+        stats.record_alloc(new_size);
+        Some(new_ptr)
     }
 }
 
-unsafe impl<Options: GlobalTlsfOptions> alloc::GlobalAlloc for GlobalTlsf<Options> {
+unsafe impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize> alloc::GlobalAlloc
+    for GlobalTlsf<Options, FLLEN, SLLEN>
+{
     #[inline]
     unsafe fn alloc(&self, layout: alloc::Layout) -> *mut u8 {
-        let mut inner = self.lock_inner();
-        inner
-            .allocate(layout)
-            .map(NonNull::as_ptr)
-            .unwrap_or(ptr::null_mut())
+        let ptr = self.alloc_untraced(layout);
+        trace_op!(
+            "GlobalTlsf::alloc(size={}, align={}) -> {:#x}",
+            layout.size(),
+            layout.align(),
+            ptr as usize
+        );
+        ptr
     }
 
     #[inline]
     unsafe fn dealloc(&self, ptr: *mut u8, layout: alloc::Layout) {
-        let mut inner = self.lock_inner();
+        trace_op!(
+            "GlobalTlsf::dealloc(ptr={:#x}, size={}, align={})",
+            ptr as usize,
+            layout.size(),
+            layout.align()
+        );
         // Safety: All allocations are non-null
         let ptr = NonNull::new_unchecked(ptr);
+
+        if let Some(hooks) = self.hooks() {
+            (hooks.on_dealloc)(ptr, layout);
+        }
+
+        #[cfg(all(feature = "std", any(unix, windows)))]
+        if self.thread_cache && crate::thread_cache::is_cacheable(layout) {
+            #[cfg(feature = "hardened")]
+            self.check_allocation(ptr, Some(layout.align()));
+            // Safety: `ptr` denotes a previous allocation with `layout`
+            if thread_cache::with_cache(self.cache_owner(), Self::flush_cache, |cache| {
+                cache.deallocate(self, ptr, layout)
+            })
+            .is_some()
+            {
+                return;
+            }
+        }
+
         // Safety: `ptr` denotes a previous allocation with alignment
         //         `layout.align()`
-        inner.deallocate(ptr, layout.align());
+        match self.enter_inner() {
+            Some(mut inner) => inner.deallocate(ptr, layout.align()),
+            None => self.defer_deallocate(ptr, Some(layout.align())),
+        }
     }
 
     #[inline]
     unsafe fn realloc(&self, ptr: *mut u8, layout: alloc::Layout, new_size: usize) -> *mut u8 {
-        let mut inner = self.lock_inner();
         // Safety: All allocations are non-null
         let ptr = NonNull::new_unchecked(ptr);
         // Safety: `layout.align()` is a power of two, and the size parameter's
         //         validity is upheld by the caller
         let new_layout = alloc::Layout::from_size_align_unchecked(new_size, layout.align());
+
+        let new_ptr = self.realloc_inner(ptr, layout, new_layout);
+        trace_op!(
+            "GlobalTlsf::realloc(ptr={:#x}, size={}, align={}, new_size={}) -> {:#x}",
+            ptr.as_ptr() as usize,
+            layout.size(),
+            layout.align(),
+            new_size,
+            new_ptr as usize
+        );
+
+        if let (Some(hooks), false) = (self.hooks(), new_ptr.is_null()) {
+            (hooks.on_dealloc)(ptr, layout);
+            (hooks.on_alloc)(NonNull::new_unchecked(new_ptr), new_layout);
+        }
+        new_ptr
+    }
+}
+
+impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize>
+    GlobalTlsf<Options, FLLEN, SLLEN>
+{
+    /// The implementation of [`alloc::GlobalAlloc::alloc`] without a trace
+    /// record.
+    #[inline]
+    fn alloc_untraced(&self, layout: alloc::Layout) -> *mut u8 {
+        #[cfg(all(feature = "std", any(unix, windows)))]
+        if self.thread_cache && crate::thread_cache::is_cacheable(layout) {
+            if let Some(ptr) = thread_cache::with_cache(self.cache_owner(), Self::flush_cache, |cache| {
+                cache.allocate(self, layout)
+            }) {
+                return self.hook_alloc(ptr.map(NonNull::as_ptr).unwrap_or(ptr::null_mut()), layout);
+            }
+        }
+
+        let ptr = self
+            .enter_inner()
+            .and_then(|mut inner| inner.allocate(layout))
+            .map(NonNull::as_ptr)
+            .unwrap_or(ptr::null_mut());
+        self.hook_alloc(ptr, layout)
+    }
+
+    /// The implementation of [`alloc::GlobalAlloc::realloc`] without hooks.
+    ///
+    /// # Safety
+    ///
+    /// See [`alloc::GlobalAlloc::realloc`].
+    #[inline]
+    unsafe fn realloc_inner(
+        &self,
+        ptr: NonNull<u8>,
+        layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> *mut u8 {
+        let new_size = new_layout.size();
+        let mut inner = match self.enter_inner() {
+            Some(inner) => inner,
+            None => return ptr::null_mut(),
+        };
         if Options::ENABLE_REALLOCATION {
             // Safety: `ptr` denotes a previous allocation with alignment
             //         `layout.align()`
@@ -178,10 +1630,12 @@ unsafe impl<Options: GlobalTlsfOptions> alloc::GlobalAlloc for GlobalTlsf<Option
                 .unwrap_or(ptr::null_mut())
         } else if let Some(new_ptr) = inner.allocate(new_layout) {
             // Safety: the previously allocated block cannot overlap the
-            //         newly allocated block.
+            //         newly allocated block. The old size is the recorded
+            //         one, which doesn't trust `layout.size()`.
             //         The safety contract for `deallocate` must be upheld
             //         by the caller.
-            ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), layout.size().min(new_size));
+            let old_size = self.size_of_allocation(ptr, Some(layout.align()));
+            ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), old_size.min(new_size));
             inner.deallocate(ptr, layout.align());
             new_ptr.as_ptr()
         } else {
@@ -190,6 +1644,110 @@ unsafe impl<Options: GlobalTlsfOptions> alloc::GlobalAlloc for GlobalTlsf<Option
     }
 }
 
+#[cfg(feature = "allocator-api")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "allocator-api")))]
+unsafe impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize> alloc::Allocator
+    for &GlobalTlsf<Options, FLLEN, SLLEN>
+{
+    #[inline]
+    fn allocate(&self, layout: alloc::Layout) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        let mut inner = self.enter_inner().ok_or(alloc::AllocError)?;
+        let ptr = inner.allocate(layout).ok_or(alloc::AllocError)?;
+        // Safety: `ptr` denotes a previous allocation
+        let size = unsafe { self.size_of_allocation(ptr, None) };
+        Ok(nonnull_slice_from_raw_parts(ptr, size))
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: alloc::Layout) {
+        // Safety: `ptr` denotes a previous allocation with alignment
+        //         `layout.align()`
+        match self.enter_inner() {
+            Some(mut inner) => inner.deallocate(ptr, layout.align()),
+            None => self.defer_deallocate(ptr, Some(layout.align())),
+        }
+    }
+
+    #[inline]
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        self.reallocate_for_allocator(ptr, old_layout, new_layout)
+    }
+
+    #[inline]
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        let new_ptr = self.reallocate_for_allocator(ptr, old_layout, new_layout)?;
+        let new_size = nonnull_slice_len(new_ptr);
+        // Safety: The range is in the allocation
+        (new_ptr.as_ptr() as *mut u8)
+            .add(old_layout.size())
+            .write_bytes(0, new_size - old_layout.size());
+        Ok(new_ptr)
+    }
+
+    #[inline]
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        self.reallocate_for_allocator(ptr, old_layout, new_layout)
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize>
+    GlobalTlsf<Options, FLLEN, SLLEN>
+{
+    /// Implements [`alloc::Allocator::grow`] and [`alloc::Allocator::shrink`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a previous allocation with `old_layout`.
+    unsafe fn reallocate_for_allocator(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        let mut inner = self.enter_inner().ok_or(alloc::AllocError)?;
+        let new_ptr = if old_layout.align() == new_layout.align() {
+            // Safety: `ptr` denotes a previous allocation with alignment
+            //         `new_layout.align()`
+            inner.reallocate(ptr, new_layout).ok_or(alloc::AllocError)?
+        } else {
+            // `FlexTlsf::reallocate` can't change the alignment
+            let new_ptr = inner.allocate(new_layout).ok_or(alloc::AllocError)?;
+            // Safety: the previously allocated block cannot overlap the
+            //         newly allocated block. The old size is the recorded
+            //         one, which doesn't trust `old_layout.size()`.
+            let old_size = self.size_of_allocation(ptr, Some(old_layout.align()));
+            ptr::copy_nonoverlapping(
+                ptr.as_ptr(),
+                new_ptr.as_ptr(),
+                old_size.min(new_layout.size()),
+            );
+            // Safety: `ptr` denotes a previous allocation with alignment
+            //         `old_layout.align()`
+            inner.deallocate(ptr, old_layout.align());
+            new_ptr
+        };
+        // Safety: `new_ptr` denotes a previous allocation
+        let size = self.size_of_allocation(new_ptr, None);
+        Ok(nonnull_slice_from_raw_parts(new_ptr, size))
+    }
+}
+
 /// Provides allocation functions modelled after the standard C and POSIX
 /// allocation functions (e.g., `malloc`, `memalign`).
 ///
@@ -234,16 +1792,76 @@ pub unsafe trait CAlloc {
     unsafe fn allocation_usable_size(&self, ptr: NonNull<u8>) -> usize;
 }
 
-unsafe impl<Options: GlobalTlsfOptions> CAlloc for GlobalTlsf<Options> {
+unsafe impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize> ThreadCacheBackend
+    for GlobalTlsf<Options, FLLEN, SLLEN>
+{
+    fn allocate_batch(
+        &self,
+        layout: alloc::Layout,
+        count: usize,
+        mut f: impl FnMut(NonNull<u8>),
+    ) {
+        self.contention.record_cache_miss();
+        let mut inner = match self.enter_inner() {
+            Some(inner) => inner,
+            None => return,
+        };
+        for _ in 0..count {
+            match inner.allocate(layout) {
+                Some(ptr) => f(ptr),
+                None => break,
+            }
+        }
+    }
+
+    unsafe fn deallocate_batch(&self, align: usize, ptrs: impl Iterator<Item = NonNull<u8>>) {
+        match self.enter_inner() {
+            Some(mut inner) => {
+                for ptr in ptrs {
+                    // The memory blocks with alignments smaller than
+                    // `GRANULARITY` are interchangeable, but their recorded
+                    // alignments (debug builds) might differ from `align`
+                    if align < GRANULARITY {
+                        // Safety: `ptr` denotes a previous allocation
+                        inner.deallocate_unknown_align(ptr);
+                    } else {
+                        // Safety: `ptr` denotes a previous allocation with
+                        //         alignment `align`
+                        inner.deallocate(ptr, align);
+                    }
+                }
+            }
+            None => {
+                let align = Some(align).filter(|&align| align >= GRANULARITY);
+                for ptr in ptrs {
+                    // Safety: `ptr` denotes a previous allocation with
+                    //         alignment `align`
+                    self.defer_deallocate(ptr, align);
+                }
+            }
+        }
+    }
+
+    #[inline]
+    unsafe fn usable_size(&self, ptr: NonNull<u8>, align: usize) -> usize {
+        // Safety: `ptr` denotes a previous allocation with alignment `align`
+        self.size_of_allocation(ptr, Some(align))
+    }
+}
+
+unsafe impl<Options: GlobalTlsfOptions, const FLLEN: usize, const SLLEN: usize> CAlloc
+    for GlobalTlsf<Options, FLLEN, SLLEN>
+{
     fn allocate(&self, layout: alloc::Layout) -> Option<NonNull<u8>> {
-        let mut inner = self.lock_inner();
-        inner.allocate(layout)
+        self.enter_inner()?.allocate(layout)
     }
 
     unsafe fn deallocate(&self, ptr: NonNull<u8>) {
-        let mut inner = self.lock_inner();
         // Safety: `ptr` denotes a previous allocation
-        inner.deallocate_unknown_align(ptr);
+        match self.enter_inner() {
+            Some(mut inner) => inner.deallocate_unknown_align(ptr),
+            None => self.defer_deallocate(ptr, None),
+        }
     }
 
     unsafe fn reallocate(
@@ -251,10 +1869,12 @@ unsafe impl<Options: GlobalTlsfOptions> CAlloc for GlobalTlsf<Options> {
         ptr: NonNull<u8>,
         new_layout: alloc::Layout,
     ) -> Option<NonNull<u8>> {
-        let mut inner = self.lock_inner();
+        #[cfg(feature = "hardened")]
+        self.check_allocation(ptr, None);
+        let mut inner = self.enter_inner()?;
         if let Some(new_ptr) = inner.allocate(new_layout) {
             // Safety: `ptr` denotes a previous allocation
-            let old_size = TheTlsf::<Options>::size_of_allocation_unknown_align(ptr);
+            let old_size = self.size_of_allocation(ptr, None);
             // Safety: the previously allocated block cannot overlap the
             //         newly allocated block.
             //         The safety contract for `deallocate` must be upheld
@@ -273,7 +1893,7 @@ unsafe impl<Options: GlobalTlsfOptions> CAlloc for GlobalTlsf<Options> {
 
     unsafe fn allocation_usable_size(&self, ptr: NonNull<u8>) -> usize {
         // Safety: `ptr` denotes a previous allocation
-        TheTlsf::<Options>::size_of_allocation_unknown_align(ptr)
+        self.size_of_allocation(ptr, None)
     }
 }
 
@@ -0,0 +1,246 @@
+//! Multiple heaps serving different threads
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cell::Cell,
+    hint, mem, ptr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::{GlobalTlsf, GlobalTlsfOptions, GlobalTlsfStats};
+use crate::ContentionStats;
+
+if_supported_target! {
+    /// A global allocator consisting of `NUM_ARENAS` independent
+    /// [`GlobalTlsf`]s (*arenas*), each with its own memory pools and lock.
+    ///
+    /// Threads are assigned to arenas in a round-robin fashion when they
+    /// first allocate memory, and allocate from their arenas from then on,
+    /// so threads assigned to different arenas don't contend for a lock.
+    /// Each arena retains the bounded response time of TLSF. Memory blocks
+    /// can be deallocated by any thread; they are returned to the arenas
+    /// they were allocated from.
+    ///
+    /// This scales allocation throughput on many-core systems at the cost
+    /// of memory usage: every allocation is prefixed by a word identifying
+    /// its arena (or by its alignment if it's larger), and free space in an
+    /// arena can't be used by the threads assigned to other arenas.
+    ///
+    /// `NUM_ARENAS` must not be zero. The other parameters are passed to
+    /// each [`GlobalTlsf`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::MultiArenaGlobalTlsf;
+    ///
+    /// #[global_allocator]
+    /// static A: MultiArenaGlobalTlsf<8> = MultiArenaGlobalTlsf::new();
+    ///
+    /// let threads: Vec<_> = (0..16)
+    ///     .map(|i| std::thread::spawn(move || vec![i; 4]))
+    ///     .collect();
+    /// for thread in threads {
+    ///     // Deallocated by another thread
+    ///     drop(thread.join().unwrap());
+    /// }
+    /// ```
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "arenas")))]
+    pub struct MultiArenaGlobalTlsf<
+        const NUM_ARENAS: usize,
+        Options: GlobalTlsfOptions = (),
+        const FLLEN: usize = { usize::BITS as usize },
+        const SLLEN: usize = { usize::BITS as usize },
+    > {
+        arenas: [GlobalTlsf<Options, FLLEN, SLLEN>; NUM_ARENAS],
+    }
+}
+
+/// The size of the arena index stored before each allocation
+const HEADER_SIZE: usize = mem::size_of::<usize>();
+
+/// The source of arena indices assigned to threads
+static NEXT_ARENA: AtomicUsize = AtomicUsize::new(0);
+
+std::thread_local! {
+    /// The arena index assigned to the current thread, or `usize::MAX` if
+    /// it's yet to be assigned
+    static THREAD_ARENA: Cell<usize> = const { Cell::new(usize::MAX) };
+}
+
+/// Get the arena index assigned to the current thread, modulo `num_arenas`.
+#[inline]
+fn current_arena(num_arenas: usize) -> usize {
+    THREAD_ARENA
+        .try_with(|arena| {
+            if arena.get() == usize::MAX {
+                arena.set(NEXT_ARENA.fetch_add(1, Ordering::Relaxed) & (usize::MAX >> 1));
+            }
+            arena.get()
+        })
+        // The thread is exiting. Any arena will do.
+        .unwrap_or(0)
+        % num_arenas
+}
+
+/// Get the layout to allocate from an arena to serve an allocation with
+/// `layout`, and the offset of the returned pointer in it.
+#[inline]
+fn outer_layout(layout: Layout) -> Option<(Layout, usize)> {
+    let offset = layout.align().max(HEADER_SIZE);
+    let size = layout.size().checked_add(offset)?;
+    let outer = Layout::from_size_align(size, layout.align()).ok()?;
+    Some((outer, offset))
+}
+
+impl<
+        const NUM_ARENAS: usize,
+        Options: GlobalTlsfOptions,
+        const FLLEN: usize,
+        const SLLEN: usize,
+    > MultiArenaGlobalTlsf<NUM_ARENAS, Options, FLLEN, SLLEN>
+{
+    const ARENA: GlobalTlsf<Options, FLLEN, SLLEN> = GlobalTlsf::new();
+
+    const VALID_NUM_ARENAS: () = assert!(NUM_ARENAS != 0, "`NUM_ARENAS` must not be zero");
+
+    /// Construct an empty instance of `Self`.
+    #[inline]
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID_NUM_ARENAS;
+        Self {
+            arenas: [Self::ARENA; NUM_ARENAS],
+        }
+    }
+
+    /// Get the arenas.
+    #[inline]
+    pub fn arenas(&self) -> &[GlobalTlsf<Options, FLLEN, SLLEN>; NUM_ARENAS] {
+        &self.arenas
+    }
+
+    /// Get the sum of the statistics of all arenas. See
+    /// [`GlobalTlsf::stats`].
+    ///
+    /// [`GlobalTlsfStats::peak_bytes_in_use`] is the sum of the arenas'
+    /// peaks, which might be larger than the actual peak. The allocation
+    /// sizes include the prefixes identifying arenas.
+    pub fn stats(&self) -> GlobalTlsfStats {
+        self.arenas
+            .iter()
+            .map(GlobalTlsf::stats)
+            .fold(GlobalTlsfStats::EMPTY, |sum, stats| GlobalTlsfStats {
+                bytes_in_use: sum.bytes_in_use + stats.bytes_in_use,
+                peak_bytes_in_use: sum.peak_bytes_in_use + stats.peak_bytes_in_use,
+                bytes_mapped: sum.bytes_mapped + stats.bytes_mapped,
+                num_allocations: sum.num_allocations + stats.num_allocations,
+                failed_allocations: sum.failed_allocations + stats.failed_allocations,
+            })
+    }
+
+    /// Emit the sums of the statistics of all arenas through the
+    /// [`metrics`] facade. See [`GlobalTlsf::report_metrics`].
+    #[cfg(feature = "metrics")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "metrics")))]
+    pub fn report_metrics(&self) {
+        self.stats().report_metrics();
+        self.contention_stats().report_metrics();
+    }
+
+    /// Get the sum of the contention counters of all arenas. See
+    /// [`GlobalTlsf::contention_stats`].
+    pub fn contention_stats(&self) -> ContentionStats {
+        self.arenas
+            .iter()
+            .map(GlobalTlsf::contention_stats)
+            .fold(ContentionStats::default(), ContentionStats::merge)
+    }
+
+    /// Return the physical memory backing the free space of all arenas to
+    /// the system. See [`GlobalTlsf::trim`].
+    pub fn trim(&self) -> usize {
+        self.arenas.iter().map(GlobalTlsf::trim).sum()
+    }
+
+    /// Get the arena index stored before the allocation `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a live allocation made by `self`.
+    #[inline]
+    unsafe fn arena_of(ptr: *mut u8) -> usize {
+        let index = ptr.sub(HEADER_SIZE).cast::<usize>().read_unaligned();
+        debug_assert!(index < NUM_ARENAS);
+        index
+    }
+}
+
+impl<
+        const NUM_ARENAS: usize,
+        Options: GlobalTlsfOptions,
+        const FLLEN: usize,
+        const SLLEN: usize,
+    > Default for MultiArenaGlobalTlsf<NUM_ARENAS, Options, FLLEN, SLLEN>
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl<
+        const NUM_ARENAS: usize,
+        Options: GlobalTlsfOptions,
+        const FLLEN: usize,
+        const SLLEN: usize,
+    > GlobalAlloc for MultiArenaGlobalTlsf<NUM_ARENAS, Options, FLLEN, SLLEN>
+{
+    #[inline]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let (outer, offset) = match outer_layout(layout) {
+            Some(x) => x,
+            None => return ptr::null_mut(),
+        };
+        let index = current_arena(NUM_ARENAS);
+        let base = self.arenas[index].alloc(outer);
+        if base.is_null() {
+            return base;
+        }
+        let ptr = base.add(offset);
+        ptr.sub(HEADER_SIZE).cast::<usize>().write_unaligned(index);
+        ptr
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // Safety: `layout` was accepted by `alloc`
+        let (outer, offset) = outer_layout(layout).unwrap_or_else(|| hint::unreachable_unchecked());
+        let index = Self::arena_of(ptr);
+        self.arenas
+            .get_unchecked(index)
+            .dealloc(ptr.sub(offset), outer);
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // Safety: `layout` was accepted by `alloc`
+        let (outer, offset) = outer_layout(layout).unwrap_or_else(|| hint::unreachable_unchecked());
+        let new_outer_size = match new_size.checked_add(offset) {
+            // `Layout::from_size_align` rejects these sizes
+            Some(size) if size <= isize::MAX as usize - (layout.align() - 1) => size,
+            _ => return ptr::null_mut(),
+        };
+
+        // The memory block stays in the same arena, and the arena index is
+        // carried over to the new memory block
+        let index = Self::arena_of(ptr);
+        let new_base =
+            self.arenas
+                .get_unchecked(index)
+                .realloc(ptr.sub(offset), outer, new_outer_size);
+        if new_base.is_null() {
+            return new_base;
+        }
+        new_base.add(offset)
+    }
+}
@@ -0,0 +1,145 @@
+//! The debug pool mode of `GlobalTlsf` (see [`GlobalTlsf::new_debug_pool`])
+//!
+//! [`GlobalTlsf::new_debug_pool`]: super::GlobalTlsf::new_debug_pool
+use core::{
+    alloc::Layout,
+    mem,
+    ptr::{self, null_mut, NonNull},
+};
+
+use super::{os::ensure_page_size_m1, DeferredFree};
+use crate::utils::ptr_with_addr;
+
+/// The minimum alignment of allocations. Like the minimum size, this lets
+/// every allocation hold [`DeferredFree`].
+const MIN_ALIGN: usize = mem::align_of::<DeferredFree>();
+
+/// The minimum usable size of allocations
+const MIN_SIZE: usize = mem::size_of::<DeferredFree>();
+
+/// Stored immediately before each allocation (unaligned)
+#[derive(Clone, Copy)]
+struct Header {
+    /// The start of the mapping, including the leading guard page
+    mapping: *mut u8,
+    /// The length of the mapping, including both guard pages
+    mapping_len: usize,
+    /// The usable size of the allocation
+    size: usize,
+}
+
+/// Map a memory block for an allocation of `layout`.
+///
+/// The allocation is placed at the end of its own pages (as close to the end
+/// as the alignment permits) and surrounded by inaccessible guard pages, so
+/// that an access past the end faults immediately.
+pub fn allocate(layout: Layout) -> Option<NonNull<u8>> {
+    let page_size_m1 = ensure_page_size_m1();
+    let size = layout.size().max(MIN_SIZE);
+    let align = layout.align().max(MIN_ALIGN);
+
+    // The pages between the guard pages must accommodate the header, the
+    // padding for the alignment, and the allocation
+    let data_len = size
+        .checked_add(mem::size_of::<Header>())?
+        .checked_add(align - 1)?
+        .checked_add(page_size_m1)?
+        & !page_size_m1;
+    let mapping_len = data_len.checked_add((page_size_m1 + 1) * 2)?;
+
+    unsafe {
+        let mapping = libc::mmap(
+            null_mut(),
+            mapping_len,
+            libc::PROT_NONE,
+            libc::MAP_ANONYMOUS | libc::MAP_PRIVATE,
+            -1,
+            0,
+        );
+        if mapping == libc::MAP_FAILED {
+            return None;
+        }
+        let mapping = mapping as *mut u8;
+
+        let data = mapping.add(page_size_m1 + 1);
+        if libc::mprotect(
+            data as *mut libc::c_void,
+            data_len,
+            libc::PROT_READ | libc::PROT_WRITE,
+        ) != 0
+        {
+            libc::munmap(mapping as *mut libc::c_void, mapping_len);
+            return None;
+        }
+
+        let data_end = data as usize + data_len;
+        let ptr = ptr_with_addr(data, (data_end - size) & !(align - 1));
+        (ptr as *mut Header).sub(1).write_unaligned(Header {
+            mapping,
+            mapping_len,
+            size: data_end - ptr as usize,
+        });
+        NonNull::new(ptr)
+    }
+}
+
+/// Read the header of a previous allocation.
+///
+/// # Safety
+///
+/// `ptr` must denote a live allocation made by [`allocate`].
+#[inline]
+unsafe fn header(ptr: NonNull<u8>) -> Header {
+    (ptr.as_ptr() as *const Header).sub(1).read_unaligned()
+}
+
+/// Get the usable size of a previous allocation.
+///
+/// # Safety
+///
+/// `ptr` must denote a live allocation made by [`allocate`].
+#[inline]
+pub unsafe fn size_of_allocation(ptr: NonNull<u8>) -> usize {
+    header(ptr).size
+}
+
+/// Release the physical memory of a previous allocation and make its pages
+/// inaccessible. The address range is never reused, so any later access
+/// through `ptr`, including a double free, faults.
+///
+/// # Safety
+///
+/// `ptr` must denote a live allocation made by [`allocate`].
+pub unsafe fn deallocate(ptr: NonNull<u8>) {
+    let Header {
+        mapping,
+        mapping_len,
+        ..
+    } = header(ptr);
+    libc::madvise(
+        mapping as *mut libc::c_void,
+        mapping_len,
+        libc::MADV_DONTNEED,
+    );
+    if libc::mprotect(mapping as *mut libc::c_void, mapping_len, libc::PROT_NONE) != 0 {
+        // The memory would remain accessible; unmapping it at least makes
+        // accesses fault until the address range is reused
+        libc::munmap(mapping as *mut libc::c_void, mapping_len);
+    }
+}
+
+/// Move a previous allocation to a new memory block of `new_layout`.
+///
+/// # Safety
+///
+/// `ptr` must denote a live allocation made by [`allocate`].
+pub unsafe fn reallocate(ptr: NonNull<u8>, new_layout: Layout) -> Option<NonNull<u8>> {
+    let new_ptr = allocate(new_layout)?;
+    ptr::copy_nonoverlapping(
+        ptr.as_ptr(),
+        new_ptr.as_ptr(),
+        size_of_allocation(ptr).min(new_layout.size()),
+    );
+    deallocate(ptr);
+    Some(new_ptr)
+}
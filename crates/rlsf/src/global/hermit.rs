@@ -0,0 +1,152 @@
+use const_default1::ConstDefault;
+use core::{
+    marker::PhantomData,
+    ptr::{null, NonNull},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use super::GlobalTlsfOptions;
+use crate::{flex::SourceError, utils::nonnull_slice_len};
+
+/// The page size of the Hermit kernel on all supported architectures.
+const PAGE_SIZE: usize = 1 << 12;
+
+/// Get the allocation unit minus 1.
+#[inline]
+fn alloc_unit_m1<Options: GlobalTlsfOptions>() -> usize {
+    Options::ALLOC_UNIT.next_power_of_two().max(PAGE_SIZE) - 1
+}
+
+// The system calls provided by the Hermit kernel (see the `hermit-abi` crate)
+extern "C" {
+    fn sys_alloc(size: usize, align: usize) -> *mut u8;
+    fn sys_dealloc(ptr: *mut u8, size: usize, align: usize);
+    fn sys_futex_wait(address: *mut u32, expected: u32, timeout: *const Timespec, flags: u32)
+        -> i32;
+    fn sys_futex_wake(address: *mut u32, count: i32) -> i32;
+    #[cfg(feature = "hardened")]
+    fn sys_write(fd: i32, buf: *const u8, len: usize) -> isize;
+    #[cfg(feature = "hardened")]
+    fn sys_abort() -> !;
+}
+
+/// `timespec`
+#[repr(C)]
+struct Timespec {
+    tv_sec: i64,
+    tv_nsec: i64,
+}
+
+/// A futex-based mutex.
+pub struct Mutex(AtomicU32);
+
+/// The states of [`Mutex`].
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+/// Locked, and there might be threads waiting for the mutex.
+const CONTENDED: u32 = 2;
+
+impl ConstDefault for Mutex {
+    const DEFAULT: Self = Self(AtomicU32::new(UNLOCKED));
+}
+
+impl Mutex {
+    #[inline]
+    fn futex(&self) -> *mut u32 {
+        &self.0 as *const AtomicU32 as *mut u32
+    }
+
+    #[inline]
+    pub fn lock(&self) {
+        if self
+            .0
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            self.lock_contended();
+        }
+    }
+
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        self.0
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    #[cold]
+    fn lock_contended(&self) {
+        while self.0.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            // Spurious wake-ups and interruptions are fine because we retry
+            unsafe { sys_futex_wait(self.futex(), CONTENDED, null(), 0) };
+        }
+    }
+
+    #[inline]
+    pub fn unlock(&self) {
+        if self.0.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            unsafe { sys_futex_wake(self.futex(), 1) };
+        }
+    }
+}
+
+/// Report a heap corruption on the standard error and abort the program.
+#[cfg(feature = "hardened")]
+#[cold]
+pub fn heap_corruption(message: &str) -> ! {
+    for part in ["rlsf: heap corruption detected: ", message, "\n"] {
+        unsafe { sys_write(2, part.as_ptr(), part.len()) };
+    }
+    unsafe { sys_abort() }
+}
+
+pub struct Source<Options>(PhantomData<fn() -> Options>);
+
+impl<Options> ConstDefault for Source<Options> {
+    const DEFAULT: Self = Self(PhantomData);
+}
+
+/// The kernel provides no way to release physical memory without
+/// deallocating it, so this does nothing.
+#[inline]
+pub unsafe fn discard(_region: NonNull<[u8]>) -> usize {
+    0
+}
+
+unsafe impl<Options: GlobalTlsfOptions> crate::flex::FlexSource for Source<Options> {
+    #[inline]
+    unsafe fn alloc(&mut self, min_size: usize) -> Option<NonNull<[u8]>> {
+        self.try_alloc(min_size).ok()
+    }
+
+    #[inline]
+    unsafe fn try_alloc(&mut self, min_size: usize) -> Result<NonNull<[u8]>, SourceError> {
+        let alloc_unit_m1 = alloc_unit_m1::<Options>();
+        let num_bytes = min_size
+            .checked_add(alloc_unit_m1)
+            .ok_or(SourceError::Exhausted)?
+            & !alloc_unit_m1;
+
+        let ptr = sys_alloc(num_bytes, PAGE_SIZE);
+
+        NonNull::new(core::ptr::slice_from_raw_parts_mut(ptr, num_bytes)).ok_or(SourceError::Exhausted)
+    }
+
+    // `dealloc` is used to return huge allocations to the kernel
+    #[inline]
+    unsafe fn dealloc(&mut self, ptr: NonNull<[u8]>) {
+        // `ptr` is exactly what `try_alloc` returned because we don't
+        // implement `realloc_inplace_grow`
+        sys_dealloc(ptr.as_ptr() as *mut u8, nonnull_slice_len(ptr), PAGE_SIZE);
+    }
+
+    #[inline]
+    fn supports_dealloc(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn min_align(&self) -> usize {
+        PAGE_SIZE
+    }
+}
@@ -0,0 +1,137 @@
+//! The quarantine of large freed memory blocks (see
+//! [`GlobalTlsf::set_large_free_quarantine`])
+//!
+//! [`GlobalTlsf::set_large_free_quarantine`]: super::GlobalTlsf::set_large_free_quarantine
+use core::{mem, ptr::NonNull};
+
+use super::os::ensure_page_size_m1;
+use crate::utils::ptr_with_addr;
+
+/// Written at the start of the payload of a quarantined memory block, which
+/// remains accessible
+struct QuarantinedFree {
+    next: *mut QuarantinedFree,
+    /// The alignment of the allocation, or zero if unknown
+    align: usize,
+    /// The usable size of the allocation
+    size: usize,
+}
+
+/// A FIFO queue of quarantined memory blocks. The whole pages in each memory
+/// block's payload (except for its [`QuarantinedFree`]) are mapped with
+/// `PROT_NONE` while it's in the queue.
+pub struct LargeFreeQuarantine {
+    head: *mut QuarantinedFree,
+    tail: *mut QuarantinedFree,
+    len: usize,
+    /// The number of memory blocks to keep in the queue
+    period: usize,
+}
+
+impl LargeFreeQuarantine {
+    pub const NEW: Self = Self {
+        head: core::ptr::null_mut(),
+        tail: core::ptr::null_mut(),
+        len: 0,
+        period: 0,
+    };
+
+    #[inline]
+    pub fn period(&self) -> usize {
+        self.period
+    }
+
+    /// Set the number of memory blocks to keep in the queue. The excess is
+    /// released by [`Self::pop_expired`].
+    #[inline]
+    pub fn set_period(&mut self, period: usize) {
+        self.period = period;
+    }
+
+    /// Make the whole pages in the allocation `ptr` inaccessible and append
+    /// it to the queue. Returns `false` (leaving the allocation intact) if
+    /// there are no such pages.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a previous allocation with alignment `align` (or
+    /// any alignment if `None`) and usable size `size`, which the caller
+    /// won't access until it's returned by [`Self::pop_expired`].
+    pub unsafe fn push(&mut self, ptr: NonNull<u8>, size: usize, align: Option<usize>) -> bool {
+        let (start, len) = match protected_pages(ptr, size) {
+            Some(pages) => pages,
+            None => return false,
+        };
+        let start = ptr_with_addr(ptr.as_ptr(), start) as *mut libc::c_void;
+        if libc::mprotect(start, len, libc::PROT_NONE) != 0 {
+            return false;
+        }
+
+        let node = ptr.as_ptr() as *mut QuarantinedFree;
+        // Safety: `protected_pages` leaves room for `QuarantinedFree`, and
+        //         the payload is aligned to `usize`
+        node.write(QuarantinedFree {
+            next: core::ptr::null_mut(),
+            align: align.unwrap_or(0),
+            size,
+        });
+        if let Some(tail) = self.tail.as_mut() {
+            tail.next = node;
+        } else {
+            self.head = node;
+        }
+        self.tail = node;
+        self.len += 1;
+        true
+    }
+
+    /// Remove the oldest memory block if the queue holds more than
+    /// [`Self::period`] memory blocks and make it accessible again. Returns
+    /// the allocation and its alignment (`None` if unknown).
+    ///
+    /// # Safety
+    ///
+    /// The memory blocks in the queue must still be owned by it.
+    pub unsafe fn pop_expired(&mut self) -> Option<(NonNull<u8>, Option<usize>)> {
+        while self.len > self.period {
+            let node = self.head;
+            let QuarantinedFree { next, align, size } = node.read();
+            self.head = next;
+            if next.is_null() {
+                self.tail = core::ptr::null_mut();
+            }
+            self.len -= 1;
+
+            let ptr = NonNull::new_unchecked(node as *mut u8);
+            let (start, len) = protected_pages(ptr, size).unwrap();
+            if libc::mprotect(
+                ptr_with_addr(ptr.as_ptr(), start) as *mut libc::c_void,
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+            ) != 0
+            {
+                // Returning the memory block to the heap would make it fault
+                // when it's reused, so leak it instead
+                continue;
+            }
+            return Some((ptr, if align == 0 { None } else { Some(align) }));
+        }
+        None
+    }
+}
+
+/// Get the whole pages in the allocation `ptr` of usable size `size`
+/// following the room for [`QuarantinedFree`].
+#[inline]
+fn protected_pages(ptr: NonNull<u8>, size: usize) -> Option<(usize, usize)> {
+    let page_size_m1 = ensure_page_size_m1();
+    let start = ptr.as_ptr() as usize;
+    let end = start + size;
+    let start = (start + mem::size_of::<QuarantinedFree>() + page_size_m1) & !page_size_m1;
+    let end = end & !page_size_m1;
+    if end > start {
+        Some((start, end - start))
+    } else {
+        None
+    }
+}
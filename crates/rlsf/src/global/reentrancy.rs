@@ -0,0 +1,65 @@
+//! Detection of nested allocator calls on the same thread
+use core::{cell::Cell, ptr};
+
+/// The number of locks [`HeldLocks`] can identify
+const MAX_HELD_LOCKS: usize = 8;
+
+/// The `GlobalTlsf` locks held by a thread
+struct HeldLocks {
+    /// The addresses of the instances whose locks are held, or null
+    owners: [Cell<*const ()>; MAX_HELD_LOCKS],
+    /// The number of held locks that didn't fit in `owners`
+    num_unidentified: Cell<usize>,
+}
+
+std::thread_local! {
+    static HELD_LOCKS: HeldLocks = const {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const NONE: Cell<*const ()> = Cell::new(ptr::null());
+        HeldLocks {
+            owners: [NONE; MAX_HELD_LOCKS],
+            num_unidentified: Cell::new(0),
+        }
+    };
+}
+
+/// Check if the current thread holds the lock of the `GlobalTlsf` at
+/// `owner`, in which case the current allocator call on it is nested in
+/// another (e.g., made by a signal handler) and waiting for the lock would
+/// deadlock.
+///
+/// The locks of other instances don't count, except when the thread holds
+/// more than [`MAX_HELD_LOCKS`] locks at once, in which case the extra ones
+/// can't be told apart and this function conservatively returns `true`.
+#[inline]
+pub fn is_locked(owner: *const ()) -> bool {
+    HELD_LOCKS
+        .try_with(|held| {
+            held.num_unidentified.get() != 0 || held.owners.iter().any(|o| o.get() == owner)
+        })
+        .unwrap_or(false)
+}
+
+/// Record that the current thread has acquired the lock of the
+/// `GlobalTlsf` at `owner`.
+#[inline]
+pub fn enter(owner: *const ()) {
+    let _ = HELD_LOCKS.try_with(|held| {
+        match held.owners.iter().find(|o| o.get().is_null()) {
+            Some(slot) => slot.set(owner),
+            None => held.num_unidentified.set(held.num_unidentified.get() + 1),
+        }
+    });
+}
+
+/// Record that the current thread has released the lock of the
+/// `GlobalTlsf` at `owner`.
+#[inline]
+pub fn exit(owner: *const ()) {
+    let _ = HELD_LOCKS.try_with(|held| {
+        match held.owners.iter().find(|o| o.get() == owner) {
+            Some(slot) => slot.set(ptr::null()),
+            None => held.num_unidentified.set(held.num_unidentified.get() - 1),
+        }
+    });
+}
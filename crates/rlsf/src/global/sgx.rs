@@ -0,0 +1,137 @@
+use const_default1::ConstDefault;
+use core::{
+    arch::asm,
+    marker::PhantomData,
+    ptr::NonNull,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::GlobalTlsfOptions;
+use crate::{
+    flex::SourceError, utils::nonnull_slice_from_raw_parts, SpinLock, StaticGlobalTlsfLock,
+};
+
+// The heap region reserved at enclave build time. These symbols are defined
+// by the Fortanix EDP enclave runtime (`entry.S` in `std`) and filled in by
+// `ftxsgx-elf2sgxs`.
+extern "C" {
+    /// The offset of the heap region from the enclave's base address
+    static HEAP_BASE: u64;
+    /// The size of the heap region
+    static HEAP_SIZE: usize;
+}
+
+/// Set when the heap region is handed out to a [`Source`].
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Get the address at which the enclave is loaded.
+// Don't remove `inline(always)`: the relocation of `IMAGE_BASE` must be
+// resolved within the enclave image
+#[inline(always)]
+fn image_base() -> usize {
+    let base: usize;
+    // Safety: Only computes an address
+    unsafe {
+        asm!(
+            "lea IMAGE_BASE(%rip), {}",
+            lateout(reg) base,
+            options(att_syntax, nostack, preserves_flags, nomem, pure),
+        );
+    }
+    base
+}
+
+/// Get the address range of the heap region.
+#[inline]
+fn heap_region() -> (usize, usize) {
+    // Safety: The symbols are immutable after the enclave is initialized
+    unsafe { (image_base() + HEAP_BASE as usize, HEAP_SIZE) }
+}
+
+/// A spinlock. Enclaves can't block threads without a round trip to the
+/// untrusted host, and the allocator's critical sections are short.
+pub struct Mutex(SpinLock);
+
+impl ConstDefault for Mutex {
+    const DEFAULT: Self = Self(SpinLock::INIT);
+}
+
+impl Mutex {
+    #[inline]
+    pub fn lock(&self) {
+        self.0.lock();
+    }
+
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        self.0.try_lock().is_some()
+    }
+
+    #[inline]
+    pub fn unlock(&self) {
+        // Safety: Only called by the holder of the lock
+        unsafe { self.0.unlock(()) };
+    }
+}
+
+/// Report a heap corruption on the standard error (if available) and abort
+/// the enclave.
+#[cfg(feature = "hardened")]
+#[cold]
+pub fn heap_corruption(message: &str) -> ! {
+    #[cfg(feature = "std")]
+    {
+        use std::io::Write;
+        let _ = writeln!(std::io::stderr(), "rlsf: heap corruption detected: {}", message);
+        std::process::abort()
+    }
+
+    #[cfg(not(feature = "std"))]
+    {
+        let _ = message;
+        // Safety: Raises `#UD`, which the enclave can't recover from
+        unsafe { asm!("ud2", options(noreturn, nomem, nostack)) }
+    }
+}
+
+pub struct Source<Options>(PhantomData<fn() -> Options>);
+
+impl<Options> ConstDefault for Source<Options> {
+    const DEFAULT: Self = Self(PhantomData);
+}
+
+/// Enclave memory can't be released or reset, so this does nothing.
+#[inline]
+pub unsafe fn discard(_region: NonNull<[u8]>) -> usize {
+    0
+}
+
+unsafe impl<Options: GlobalTlsfOptions> crate::flex::FlexSource for Source<Options> {
+    #[inline]
+    unsafe fn alloc(&mut self, min_size: usize) -> Option<NonNull<[u8]>> {
+        self.try_alloc(min_size).ok()
+    }
+
+    /// Hand out the whole heap region. This succeeds at most once per
+    /// enclave, even if there are multiple instances of `GlobalTlsf`.
+    #[inline]
+    unsafe fn try_alloc(&mut self, min_size: usize) -> Result<NonNull<[u8]>, SourceError> {
+        let (start, len) = heap_region();
+
+        // Don't consume the region if it can't satisfy the request. A later,
+        // smaller request might still fit.
+        if len < min_size || TAKEN.swap(true, Ordering::Relaxed) {
+            return Err(SourceError::Exhausted);
+        }
+
+        let start = NonNull::new(start as *mut u8).ok_or(SourceError::Exhausted)?;
+        Ok(nonnull_slice_from_raw_parts(start, len))
+    }
+
+    #[inline]
+    fn min_align(&self) -> usize {
+        let (start, _) = heap_region();
+        // The largest power of two dividing `start`
+        (start & start.wrapping_neg()).max(1)
+    }
+}
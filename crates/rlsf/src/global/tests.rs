@@ -2,7 +2,7 @@ use quickcheck_macros::quickcheck;
 use std::{alloc::Layout, prelude::v1::*};
 
 use super::*;
-use crate::tests::ShadowAllocator;
+use crate::{tests::ShadowAllocator, GRANULARITY};
 
 #[derive(Debug)]
 struct Alloc {
@@ -27,6 +27,159 @@ macro_rules! gen_test {
                 for alloc in allocs.iter() {
                     unsafe { CAlloc::deallocate(&tlsf, alloc.ptr) };
                 }
+
+                let stats = tlsf.stats();
+                assert_eq!(stats.num_allocations, 0);
+                assert_eq!(stats.bytes_in_use, 0);
+                assert!(stats.bytes_mapped >= stats.peak_bytes_in_use);
+            }
+
+            #[test]
+            fn global_alloc_stats() {
+                let tlsf: TheTlsf = TheTlsf::DEFAULT;
+                let layout = Layout::from_size_align(100, 8).unwrap();
+                unsafe {
+                    let ptr = alloc::GlobalAlloc::alloc(&tlsf, layout);
+                    assert!(!ptr.is_null());
+                    let stats = tlsf.stats();
+                    assert_eq!(stats.num_allocations, 1);
+                    assert!(stats.bytes_in_use >= 100);
+
+                    let ptr = alloc::GlobalAlloc::realloc(&tlsf, ptr, layout, 1000);
+                    assert!(!ptr.is_null());
+                    let stats = tlsf.stats();
+                    assert_eq!(stats.num_allocations, 1);
+                    assert!(stats.bytes_in_use >= 1000);
+                    assert!(stats.peak_bytes_in_use >= stats.bytes_in_use);
+
+                    let layout = Layout::from_size_align(1000, 8).unwrap();
+                    alloc::GlobalAlloc::dealloc(&tlsf, ptr, layout);
+                }
+                let stats = tlsf.stats();
+                assert_eq!(stats.num_allocations, 0);
+                assert_eq!(stats.bytes_in_use, 0);
+                assert!(stats.peak_bytes_in_use >= 1000);
+            }
+
+            #[test]
+            fn mallinfo() {
+                let tlsf: TheTlsf = TheTlsf::DEFAULT;
+                assert_eq!(tlsf.mallinfo(), Mallinfo::default());
+
+                let layout = Layout::from_size_align(100, 8).unwrap();
+                unsafe {
+                    let ptrs: Vec<_> = (0..4).map(|_| alloc::GlobalAlloc::alloc(&tlsf, layout)).collect();
+                    alloc::GlobalAlloc::dealloc(&tlsf, ptrs[1], layout);
+
+                    let info = tlsf.mallinfo();
+                    log::debug!("mallinfo() = {:?}", info);
+                    assert_eq!(info.arena, tlsf.stats().bytes_mapped);
+                    assert_eq!(info.uordblks, tlsf.stats().bytes_in_use);
+                    assert!(info.uordblks >= 300);
+                    assert!(info.ordblks >= 2);
+                    assert!(info.fordblks >= 100);
+                    assert!(info.arena >= info.uordblks + info.fordblks);
+
+                    for &i in &[0, 2, 3] {
+                        alloc::GlobalAlloc::dealloc(&tlsf, ptrs[i], layout);
+                    }
+                }
+
+                let info = tlsf.mallinfo();
+                assert_eq!(info.uordblks, 0);
+                assert!(info.ordblks >= 1);
+            }
+
+            #[test]
+            fn write_report() {
+                let tlsf: TheTlsf = TheTlsf::DEFAULT;
+                let layout = Layout::from_size_align(100, 8).unwrap();
+                let ptrs: Vec<_> = (0..3)
+                    .map(|_| unsafe { alloc::GlobalAlloc::alloc(&tlsf, layout) })
+                    .collect();
+
+                let mut report = String::new();
+                tlsf.write_report(&mut report).unwrap();
+                log::debug!("write_report() = {}", report);
+
+                let stats = tlsf.stats();
+                let info = tlsf.mallinfo();
+                let lines: Vec<_> = report.lines().collect();
+                assert_eq!(lines.len(), 6);
+                assert_eq!(lines[0], format!("mapped:   {} bytes", stats.bytes_mapped));
+                assert_eq!(
+                    lines[1],
+                    format!("in use:   {} bytes in 3 allocations", stats.bytes_in_use)
+                );
+                assert_eq!(lines[2], format!("peak:     {} bytes", stats.peak_bytes_in_use));
+                assert_eq!(
+                    lines[3],
+                    format!("free:     {} bytes in {} blocks", info.fordblks, info.ordblks)
+                );
+                assert_eq!(lines[4], "failures: 0");
+                assert!(lines[5].starts_with("lock:     "));
+
+                for ptr in ptrs {
+                    unsafe { alloc::GlobalAlloc::dealloc(&tlsf, ptr, layout) };
+                }
+            }
+
+            #[test]
+            fn heap_stats() {
+                let tlsf: TheTlsf = TheTlsf::DEFAULT;
+                assert_eq!(tlsf.heap_stats(), HeapStats::default());
+
+                let layout = Layout::from_size_align(100, 8).unwrap();
+                let ptrs: Vec<_> = (0..3)
+                    .map(|_| unsafe { alloc::GlobalAlloc::alloc(&tlsf, layout) })
+                    .collect();
+
+                let heap_stats = tlsf.heap_stats();
+                log::debug!("heap_stats() = {:?}", heap_stats);
+                let stats = tlsf.stats();
+                let info = tlsf.mallinfo();
+                assert_eq!(heap_stats.bytes_in_use, stats.bytes_in_use);
+                assert_eq!(heap_stats.peak_bytes_in_use, stats.peak_bytes_in_use);
+                assert_eq!(heap_stats.pool_bytes, stats.bytes_mapped);
+                assert_eq!(heap_stats.free_bytes, info.fordblks);
+                assert_eq!(heap_stats.num_allocations, 3);
+                assert_eq!(heap_stats.failed_allocations, 0);
+
+                for ptr in ptrs {
+                    unsafe { alloc::GlobalAlloc::dealloc(&tlsf, ptr, layout) };
+                }
+                assert_eq!(tlsf.heap_stats().num_allocations, 0);
+            }
+
+            #[test]
+            fn trim() {
+                let tlsf: TheTlsf = TheTlsf::DEFAULT;
+                let small = Layout::from_size_align(64, 8).unwrap();
+                let big = Layout::from_size_align(1 << 20, 8).unwrap();
+                unsafe {
+                    let ptr1 = alloc::GlobalAlloc::alloc(&tlsf, small);
+                    ptr1.write_bytes(0x5a, small.size());
+                    let ptr2 = alloc::GlobalAlloc::alloc(&tlsf, big);
+                    ptr2.write_bytes(0xa5, big.size());
+                    alloc::GlobalAlloc::dealloc(&tlsf, ptr2, big);
+
+                    let num_released_bytes = tlsf.trim();
+                    log::debug!("trim() = {}", num_released_bytes);
+                    assert!(num_released_bytes <= tlsf.stats().bytes_mapped);
+                    if cfg!(feature = "poison") {
+                        assert_eq!(num_released_bytes, 0);
+                    } else if cfg!(target_os = "linux") {
+                        assert!(num_released_bytes >= big.size() / 2);
+                    }
+
+                    // Released memory is reusable, and the live allocation
+                    // is intact
+                    let ptr2 = alloc::GlobalAlloc::alloc(&tlsf, big);
+                    ptr2.write_bytes(0xa5, big.size());
+                    assert!((0..small.size()).all(|i| *ptr1.add(i) == 0x5a));
+                    alloc::GlobalAlloc::dealloc(&tlsf, ptr2, big);
+                    alloc::GlobalAlloc::dealloc(&tlsf, ptr1, small);
+                }
             }
 
             fn calloc_random_inner(tlsf: &TheTlsf, allocs: &mut Vec<Alloc>, bytecode: Vec<u8>) -> Option<()> {
@@ -108,3 +261,664 @@ macro_rules! gen_test {
 
 gen_test!(default_globaltlsf, ());
 gen_test!(small_globaltlsf, SmallGlobalTlsfOptions);
+gen_test!(config_globaltlsf, GlobalTlsfConfig<{ 1 << 20 }, false>, 16, 8);
+gen_test!(large_globaltlsf, LargeGlobalTlsfOptions, 31, 32);
+gen_test!(decommit_globaltlsf, GlobalTlsfConfig<{ 1 << 16 }, true, true, 4096>);
+
+#[cfg(target_os = "linux")]
+#[test]
+fn decommit_threshold() {
+    unsafe fn check<Options: GlobalTlsfOptions>(expected: u8) {
+        let tlsf: GlobalTlsf<Options> = GlobalTlsf::new();
+        let small = Layout::from_size_align(64, 8).unwrap();
+        let big = Layout::from_size_align(1 << 20, 8).unwrap();
+
+        // `ptr1` keeps the memory pool from being empty
+        let ptr1 = alloc::GlobalAlloc::alloc(&tlsf, small);
+        let ptr2 = alloc::GlobalAlloc::alloc(&tlsf, big);
+        ptr2.write_bytes(0xa5, big.size());
+        alloc::GlobalAlloc::dealloc(&tlsf, ptr2, big);
+
+        // The middle of a free memory block is never touched by the
+        // allocator (unless it's poisoned or zeroed), but `MADV_DONTNEED`
+        // zero-fills it
+        #[cfg(feature = "asan")]
+        assert!(crate::asan::is_poisoned(ptr2.add(big.size() / 2)));
+        #[cfg(not(feature = "asan"))]
+        if cfg!(feature = "poison") {
+            assert_eq!(*ptr2.add(big.size() / 2), 0xdd);
+        } else if cfg!(feature = "zero-on-free") {
+            assert_eq!(*ptr2.add(big.size() / 2), 0);
+        } else {
+            assert_eq!(*ptr2.add(big.size() / 2), expected);
+        }
+
+        alloc::GlobalAlloc::dealloc(&tlsf, ptr1, small);
+    }
+
+    unsafe {
+        check::<GlobalTlsfConfig<{ 1 << 16 }>>(0xa5);
+        check::<GlobalTlsfConfig<{ 1 << 16 }, true, true, { 1 << 19 }>>(0);
+        check::<GlobalTlsfConfig<{ 1 << 16 }, true, true, { 1 << 21 }>>(0xa5);
+    }
+}
+
+#[test]
+fn multiple_heaps() {
+    static HEAPS: [GlobalTlsf; 4] = [GlobalTlsf::new(), GlobalTlsf::new(), GlobalTlsf::new(), GlobalTlsf::new()];
+
+    let threads: Vec<_> = (0..HEAPS.len())
+        .map(|i| {
+            std::thread::spawn(move || unsafe {
+                let heap = &HEAPS[i];
+                let layout = Layout::from_size_align(64 << i, 8).unwrap();
+                let ptrs: Vec<_> = (0..100).map(|_| alloc::GlobalAlloc::alloc(heap, layout)).collect();
+                assert!(ptrs.iter().all(|ptr| !ptr.is_null()));
+                assert_eq!(heap.stats().num_allocations, 100);
+                for ptr in ptrs {
+                    alloc::GlobalAlloc::dealloc(heap, ptr, layout);
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    for heap in HEAPS.iter() {
+        let stats = heap.stats();
+        assert_eq!(stats.num_allocations, 0);
+        assert!(stats.peak_bytes_in_use > 0);
+    }
+}
+
+#[test]
+fn oversized_allocation() {
+    // The maximum block size is `GRANULARITY << 8`
+    let tlsf: GlobalTlsf<(), 8, 8> = GlobalTlsf::new();
+    let layout = Layout::from_size_align(GRANULARITY << 10, 8).unwrap();
+    unsafe {
+        let ptr = alloc::GlobalAlloc::alloc(&tlsf, layout);
+        assert!(!ptr.is_null());
+        ptr.write_bytes(0x5a, layout.size());
+        assert!(tlsf.stats().bytes_mapped >= layout.size());
+
+        let new_size = layout.size() * 2;
+        let ptr = alloc::GlobalAlloc::realloc(&tlsf, ptr, layout, new_size);
+        assert!(!ptr.is_null());
+        assert!((0..layout.size()).all(|i| *ptr.add(i) == 0x5a));
+
+        let layout = Layout::from_size_align(new_size, 8).unwrap();
+        alloc::GlobalAlloc::dealloc(&tlsf, ptr, layout);
+    }
+
+    // The memory is returned to the system immediately
+    let stats = tlsf.stats();
+    assert_eq!(stats.num_allocations, 0);
+    assert!(stats.bytes_mapped < GRANULARITY << 10);
+}
+
+#[test]
+fn hooks() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static TLSF: GlobalTlsf = GlobalTlsf::new();
+    static NUM_ALLOCS: AtomicUsize = AtomicUsize::new(0);
+    static NUM_DEALLOCS: AtomicUsize = AtomicUsize::new(0);
+    static BYTES: AtomicUsize = AtomicUsize::new(0);
+    static HOOKS: GlobalTlsfHooks = GlobalTlsfHooks {
+        on_alloc: |_, layout| {
+            NUM_ALLOCS.fetch_add(1, Ordering::Relaxed);
+            BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        },
+        on_dealloc: |_, layout| {
+            NUM_DEALLOCS.fetch_add(1, Ordering::Relaxed);
+            BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        },
+    };
+
+    let layout = Layout::from_size_align(100, 8).unwrap();
+    unsafe {
+        // Not observed
+        let ptr0 = alloc::GlobalAlloc::alloc(&TLSF, layout);
+
+        TLSF.set_hooks(Some(&HOOKS));
+        let ptr1 = alloc::GlobalAlloc::alloc(&TLSF, layout);
+        assert_eq!((NUM_ALLOCS.load(Ordering::Relaxed), BYTES.load(Ordering::Relaxed)), (1, 100));
+
+        let ptr1 = alloc::GlobalAlloc::realloc(&TLSF, ptr1, layout, 300);
+        assert!(!ptr1.is_null());
+        assert_eq!(NUM_ALLOCS.load(Ordering::Relaxed), 2);
+        assert_eq!(NUM_DEALLOCS.load(Ordering::Relaxed), 1);
+        assert_eq!(BYTES.load(Ordering::Relaxed), 300);
+
+        alloc::GlobalAlloc::dealloc(&TLSF, ptr1, Layout::from_size_align(300, 8).unwrap());
+        assert_eq!(NUM_DEALLOCS.load(Ordering::Relaxed), 2);
+        assert_eq!(BYTES.load(Ordering::Relaxed), 0);
+
+        // Failed allocations aren't reported
+        let huge = Layout::from_size_align(isize::MAX as usize / 2, 8).unwrap();
+        assert!(alloc::GlobalAlloc::alloc(&TLSF, huge).is_null());
+        assert_eq!(NUM_ALLOCS.load(Ordering::Relaxed), 2);
+
+        TLSF.set_hooks(None);
+        alloc::GlobalAlloc::dealloc(&TLSF, ptr0, layout);
+        assert_eq!(NUM_DEALLOCS.load(Ordering::Relaxed), 2);
+    }
+}
+
+#[test]
+fn try_allocate() {
+    static TLSF: GlobalTlsf = GlobalTlsf::new();
+
+    let layout = Layout::from_size_align(100, 8).unwrap();
+    {
+        let _guard = TLSF.lock_inner();
+        assert_eq!(TLSF.try_allocate(layout), Err(TryAllocError::Contended));
+    }
+
+    // Each instance has its own mutex, so no other test can be holding it
+    let ptr = TLSF.try_allocate(layout).unwrap();
+    assert_eq!(TLSF.stats().num_allocations, 1);
+    unsafe { alloc::GlobalAlloc::dealloc(&TLSF, ptr.as_ptr(), layout) };
+    assert_eq!(TLSF.stats().num_allocations, 0);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn hugepage_threshold() {
+    struct Options;
+    impl GlobalTlsfOptions for Options {
+        const HUGEPAGE_THRESHOLD: usize = 1 << 21;
+    }
+
+    let tlsf: GlobalTlsf<Options> = GlobalTlsf::new();
+    unsafe {
+        // A small pool isn't affected
+        let small = Layout::from_size_align(64, 8).unwrap();
+        let ptr1 = alloc::GlobalAlloc::alloc(&tlsf, small);
+        assert!(!ptr1.is_null());
+        assert!(tlsf.stats().bytes_mapped < 1 << 21);
+
+        // The pool is grown (or a new pool is created) beyond the threshold
+        let big = Layout::from_size_align(3 << 20, 8).unwrap();
+        let ptr2 = alloc::GlobalAlloc::alloc(&tlsf, big);
+        assert!(!ptr2.is_null());
+        ptr2.write_bytes(0x5a, big.size());
+
+        // A new pool beyond the threshold is aligned to huge pages. The
+        // allocation is placed at the beginning of the pool.
+        let tlsf2: GlobalTlsf<Options> = GlobalTlsf::new();
+        let ptr3 = alloc::GlobalAlloc::alloc(&tlsf2, big);
+        assert!(!ptr3.is_null());
+        assert!(ptr3 as usize % (1 << 21) < GRANULARITY * 4);
+        assert_eq!(tlsf2.stats().bytes_mapped % (1 << 21), 0);
+        ptr3.write_bytes(0xa5, big.size());
+
+        alloc::GlobalAlloc::dealloc(&tlsf2, ptr3, big);
+        alloc::GlobalAlloc::dealloc(&tlsf, ptr2, big);
+        alloc::GlobalAlloc::dealloc(&tlsf, ptr1, small);
+    }
+}
+
+#[cfg(feature = "arenas")]
+#[test]
+fn multi_arena() {
+    static TLSF: MultiArenaGlobalTlsf<3> = MultiArenaGlobalTlsf::new();
+
+    // Allocate in some threads and deallocate in others
+    let threads: Vec<_> = (0..6)
+        .map(|i| {
+            std::thread::spawn(move || unsafe {
+                (0..50)
+                    .map(|k| {
+                        let layout = Layout::from_size_align(8 + k * 24, 1 << (k % 8)).unwrap();
+                        let ptr = alloc::GlobalAlloc::alloc(&TLSF, layout);
+                        assert!(!ptr.is_null());
+                        assert_eq!(ptr as usize % layout.align(), 0);
+                        ptr.write_bytes(i as u8, layout.size());
+                        (ptr as usize, layout)
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    let allocs: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+
+    // Threads are distributed evenly across the arenas
+    assert!(TLSF.arenas().iter().all(|arena| arena.stats().num_allocations == 100));
+    assert_eq!(TLSF.stats().num_allocations, 300);
+
+    let threads: Vec<_> = allocs
+        .into_iter()
+        .enumerate()
+        .map(|(i, allocs)| {
+            std::thread::spawn(move || unsafe {
+                for (ptr, layout) in allocs {
+                    let ptr = ptr as *mut u8;
+                    assert!((0..layout.size()).all(|k| *ptr.add(k) == i as u8));
+                    let new_size = layout.size() * 2;
+                    let ptr = alloc::GlobalAlloc::realloc(&TLSF, ptr, layout, new_size);
+                    assert!(!ptr.is_null());
+                    assert_eq!(ptr as usize % layout.align(), 0);
+                    assert!((0..layout.size()).all(|k| *ptr.add(k) == i as u8));
+                    let layout = Layout::from_size_align(new_size, layout.align()).unwrap();
+                    alloc::GlobalAlloc::dealloc(&TLSF, ptr, layout);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    assert_eq!(TLSF.stats().num_allocations, 0);
+    assert_eq!(TLSF.stats().bytes_in_use, 0);
+}
+
+#[cfg(all(unix, feature = "std"))]
+#[test]
+fn fork_while_locked() {
+    static TLSF: GlobalTlsf = GlobalTlsf::new();
+    TLSF.hold_lock_across_fork();
+
+    let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+    let holder = std::thread::spawn(move || {
+        let _guard = TLSF.lock_inner();
+        locked_tx.send(()).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    });
+    locked_rx.recv().unwrap();
+
+    unsafe {
+        // `fork` waits for `holder` to release the lock
+        let pid = libc::fork();
+        assert!(pid >= 0);
+        if pid == 0 {
+            // This would deadlock if the lock was still held in the child
+            let ptr = alloc::GlobalAlloc::alloc(&TLSF, Layout::new::<u64>());
+            libc::_exit(ptr.is_null() as libc::c_int);
+        }
+
+        let mut status = 0;
+        assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+        assert_eq!(status, 0);
+    }
+
+    holder.join().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn separate_mutexes() {
+    static HEAPS: [GlobalTlsf; 16] = [GlobalTlsf::DEFAULT; 16];
+
+    // Every other instance can be locked while one is
+    for (i, heap) in HEAPS.iter().enumerate() {
+        heap.mutex.lock();
+        for (k, other) in HEAPS.iter().enumerate() {
+            if k != i {
+                assert!(other.mutex.try_lock(), "{} and {} share a mutex", i, k);
+                other.mutex.unlock();
+            }
+        }
+        heap.mutex.unlock();
+    }
+}
+
+#[cfg(all(unix, feature = "std"))]
+#[test]
+fn fork_after_dropping_instance() {
+    // A dropped instance is removed from the `fork` handlers' list
+    for _ in 0..4 {
+        // Safety: `tlsf` isn't moved, and it outlives the thread using it
+        let tlsf: GlobalTlsf = unsafe { GlobalTlsf::new_with_thread_cache() };
+        let tlsf_ptr = &tlsf as *const GlobalTlsf as usize;
+        std::thread::spawn(move || unsafe {
+            let tlsf = &*(tlsf_ptr as *const GlobalTlsf);
+            let ptr = alloc::GlobalAlloc::alloc(tlsf, Layout::new::<u64>());
+            alloc::GlobalAlloc::dealloc(tlsf, ptr, Layout::new::<u64>());
+            assert!(tlsf.mutex.is_held_across_fork());
+        })
+        .join()
+        .unwrap();
+        // `tlsf` is dropped in place here; `drop(tlsf)` would move it
+    }
+
+    unsafe {
+        let pid = libc::fork();
+        assert!(pid >= 0);
+        if pid == 0 {
+            libc::_exit(0);
+        }
+
+        let mut status = 0;
+        assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+        assert_eq!(status, 0);
+    }
+}
+
+/// Run `f` in a child process, which must be killed by `SIGSEGV`
+#[cfg(all(unix, feature = "std"))]
+unsafe fn assert_faults(f: impl FnOnce()) {
+    let pid = libc::fork();
+    assert!(pid >= 0);
+    if pid == 0 {
+        f();
+        libc::_exit(0);
+    }
+
+    let mut status = 0;
+    assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+    assert_eq!(status & 0x7f, libc::SIGSEGV);
+}
+
+#[cfg(all(unix, feature = "std"))]
+#[test]
+fn debug_pool() {
+    static TLSF: GlobalTlsf = GlobalTlsf::new_debug_pool();
+
+    unsafe {
+        let layout = Layout::from_size_align(96, 4).unwrap();
+        let ptr = alloc::GlobalAlloc::alloc(&TLSF, layout);
+        assert!(!ptr.is_null());
+        let size = CAlloc::allocation_usable_size(&TLSF, NonNull::new_unchecked(ptr));
+        assert_eq!(size, 96);
+        assert_eq!(TLSF.stats().bytes_in_use, 96);
+        ptr.write_bytes(1, size);
+
+        // Writing past the end hits the guard page
+        assert_faults(|| ptr.add(size).write(1));
+
+        let new_ptr = alloc::GlobalAlloc::realloc(&TLSF, ptr, layout, 5000);
+        assert!(!new_ptr.is_null());
+        assert_ne!(new_ptr, ptr);
+        assert!((0..96).all(|i| *new_ptr.add(i) == 1));
+        assert_eq!(TLSF.stats().num_allocations, 1);
+
+        // The old memory block is inaccessible
+        assert_faults(|| ptr.write(1));
+
+        let layout = Layout::from_size_align(5000, 4).unwrap();
+        alloc::GlobalAlloc::dealloc(&TLSF, new_ptr, layout);
+        assert_faults(|| new_ptr.write(1));
+        assert_faults(|| alloc::GlobalAlloc::dealloc(&TLSF, new_ptr, layout));
+
+        // Large alignments are respected
+        let layout = Layout::from_size_align(24, 1 << 14).unwrap();
+        let ptr = alloc::GlobalAlloc::alloc(&TLSF, layout);
+        assert_eq!(ptr as usize % (1 << 14), 0);
+        alloc::GlobalAlloc::dealloc(&TLSF, ptr, layout);
+    }
+
+    let stats = TLSF.stats();
+    assert_eq!(stats.num_allocations, 0);
+    assert_eq!(stats.bytes_in_use, 0);
+}
+
+#[cfg(all(unix, feature = "std"))]
+#[test]
+fn large_free_quarantine() {
+    static TLSF: GlobalTlsf = GlobalTlsf::new();
+    TLSF.set_large_free_quarantine(2);
+
+    let layout = Layout::from_size_align(1 << 16, 8).unwrap();
+    unsafe {
+        let ptrs: Vec<_> = (0..3)
+            .map(|_| {
+                let ptr = alloc::GlobalAlloc::alloc(&TLSF, layout);
+                assert!(!ptr.is_null());
+                ptr.write_bytes(1, layout.size());
+                ptr
+            })
+            .collect();
+        for &ptr in &ptrs {
+            alloc::GlobalAlloc::dealloc(&TLSF, ptr, layout);
+        }
+        assert_eq!(TLSF.stats().bytes_in_use, 0);
+
+        // The last two memory blocks are quarantined
+        assert_faults(|| ptrs[1].add(1 << 15).write(1));
+        assert_faults(|| ptrs[2].add(1 << 15).write(1));
+
+        // The first one has been released and can be reused
+        let ptr = alloc::GlobalAlloc::alloc(&TLSF, layout);
+        ptr.write_bytes(1, layout.size());
+        alloc::GlobalAlloc::dealloc(&TLSF, ptr, layout);
+
+        // Small memory blocks aren't quarantined
+        let small_layout = Layout::new::<u64>();
+        let ptr = alloc::GlobalAlloc::alloc(&TLSF, small_layout);
+        alloc::GlobalAlloc::dealloc(&TLSF, ptr, small_layout);
+
+        // Stopping the quarantine releases everything
+        TLSF.set_large_free_quarantine(0);
+        let ptrs: Vec<_> = (0..3)
+            .map(|_| alloc::GlobalAlloc::alloc(&TLSF, layout))
+            .collect();
+        for ptr in ptrs {
+            ptr.write_bytes(1, layout.size());
+            alloc::GlobalAlloc::dealloc(&TLSF, ptr, layout);
+        }
+    }
+}
+
+#[cfg(all(feature = "std", any(unix, windows)))]
+#[test]
+fn thread_cache() {
+    static TLSF: GlobalTlsf = unsafe { GlobalTlsf::new_with_thread_cache() };
+
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            std::thread::spawn(move || unsafe {
+                let mut ptrs = Vec::new();
+                for k in 0..1000 {
+                    let layout = Layout::from_size_align(1 + (i * 1000 + k) % 300, 8).unwrap();
+                    let ptr = alloc::GlobalAlloc::alloc(&TLSF, layout);
+                    assert!(!ptr.is_null());
+                    ptr.write_bytes(i as u8, layout.size());
+                    ptrs.push((ptr, layout));
+                    if k % 3 == 0 {
+                        let (ptr, layout) = ptrs.swap_remove(k % ptrs.len());
+                        assert!((0..layout.size()).all(|j| *ptr.add(j) == i as u8));
+                        alloc::GlobalAlloc::dealloc(&TLSF, ptr, layout);
+                    }
+                }
+                for (ptr, layout) in ptrs {
+                    assert!((0..layout.size()).all(|j| *ptr.add(j) == i as u8));
+                    alloc::GlobalAlloc::dealloc(&TLSF, ptr, layout);
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // The caches are flushed on thread exit
+    assert_eq!(TLSF.stats().num_allocations, 0);
+
+    unsafe {
+        let layout = Layout::from_size_align(24, 8).unwrap();
+        let ptr = alloc::GlobalAlloc::alloc(&TLSF, layout);
+        alloc::GlobalAlloc::dealloc(&TLSF, ptr, layout);
+        assert_ne!(TLSF.stats().num_allocations, 0);
+
+        // The cache is refilled in batches, so this doesn't need the lock
+        let num_misses = TLSF.contention_stats().cache_misses;
+        assert_ne!(num_misses, 0);
+        let guard = TLSF.lock_inner();
+        let ptr = alloc::GlobalAlloc::alloc(&TLSF, layout);
+        assert!(!ptr.is_null());
+        drop(guard);
+        assert_eq!(TLSF.contention_stats().cache_misses, num_misses);
+        alloc::GlobalAlloc::dealloc(&TLSF, ptr, layout);
+    }
+
+    TLSF.flush_thread_cache();
+    assert_eq!(TLSF.stats().num_allocations, 0);
+}
+
+#[cfg(all(unix, feature = "hardened"))]
+#[test]
+fn hardened_double_free() {
+    static TLSF: GlobalTlsf = GlobalTlsf::new();
+
+    unsafe {
+        let pid = libc::fork();
+        assert!(pid >= 0);
+        if pid == 0 {
+            let layout = Layout::new::<u64>();
+            let ptr = alloc::GlobalAlloc::alloc(&TLSF, layout);
+            alloc::GlobalAlloc::dealloc(&TLSF, ptr, layout);
+            alloc::GlobalAlloc::dealloc(&TLSF, ptr, layout);
+            libc::_exit(0);
+        }
+
+        // The child process must be killed by `SIGABRT`
+        let mut status = 0;
+        assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+        assert_eq!(status & 0x7f, libc::SIGABRT);
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+#[test]
+fn allocator_api() {
+    let tlsf: GlobalTlsf = GlobalTlsf::new();
+
+    let mut v: Vec<u32, _> = Vec::new_in(&tlsf);
+    v.extend(0..10000);
+    assert!(v.iter().copied().eq(0..10000));
+    assert_eq!(tlsf.stats().num_allocations, 1);
+
+    v.truncate(10);
+    v.shrink_to_fit();
+    assert!(v.iter().copied().eq(0..10));
+
+    let b = Box::new_in([1u8; 100], &tlsf);
+    assert_eq!(tlsf.stats().num_allocations, 2);
+    drop(b);
+    drop(v);
+    assert_eq!(tlsf.stats().num_allocations, 0);
+
+    // Changing the alignment
+    unsafe {
+        use alloc::Allocator;
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let ptr = (&tlsf).allocate_zeroed(layout).unwrap();
+        let ptr = ptr.as_ptr() as *mut u8;
+        ptr.write_bytes(1, layout.size());
+        let new_layout = Layout::from_size_align(200, 256).unwrap();
+        let new_ptr = (&tlsf)
+            .grow_zeroed(NonNull::new_unchecked(ptr), layout, new_layout)
+            .unwrap();
+        let new_ptr = new_ptr.as_ptr() as *mut u8;
+        assert_eq!(new_ptr as usize % 256, 0);
+        assert!((0..100).all(|i| *new_ptr.add(i) == 1));
+        assert!((100..200).all(|i| *new_ptr.add(i) == 0));
+        Allocator::deallocate(&&tlsf, NonNull::new_unchecked(new_ptr), new_layout);
+    }
+    assert_eq!(tlsf.stats().num_allocations, 0);
+}
+
+#[cfg(all(feature = "std", any(unix, windows)))]
+#[test]
+fn reentrancy() {
+    static TLSF: GlobalTlsf = GlobalTlsf::new();
+
+    let layout = Layout::from_size_align(100, 8).unwrap();
+    unsafe {
+        let ptr = alloc::GlobalAlloc::alloc(&TLSF, layout);
+        assert!(!ptr.is_null());
+
+        {
+            // Simulate a nested call, e.g., from a signal handler
+            let _guard = TLSF.lock_inner();
+            assert!(alloc::GlobalAlloc::alloc(&TLSF, layout).is_null());
+            assert!(alloc::GlobalAlloc::realloc(&TLSF, ptr, layout, 200).is_null());
+            assert_eq!(TLSF.try_allocate(layout), Err(TryAllocError::Contended));
+            alloc::GlobalAlloc::dealloc(&TLSF, ptr, layout);
+        }
+
+        // The deferred deallocation is completed by the next lock holder
+        assert_eq!(TLSF.stats().num_allocations, 0);
+        assert!(TLSF.deferred_frees.load(Ordering::Relaxed).is_null());
+    }
+}
+
+#[cfg(all(feature = "std", any(unix, windows)))]
+#[test]
+fn reentrancy_is_per_instance() {
+    static TLSF1: GlobalTlsf = GlobalTlsf::new();
+    static TLSF2: GlobalTlsf = GlobalTlsf::new();
+
+    let layout = Layout::from_size_align(100, 8).unwrap();
+    unsafe {
+        let _guard = TLSF1.lock_inner();
+        assert!(alloc::GlobalAlloc::alloc(&TLSF1, layout).is_null());
+
+        // Another instance has its own lock, which is free
+        let ptr = alloc::GlobalAlloc::alloc(&TLSF2, layout);
+        assert!(!ptr.is_null());
+        let ptr = alloc::GlobalAlloc::realloc(&TLSF2, ptr, layout, 200);
+        assert!(!ptr.is_null());
+        alloc::GlobalAlloc::dealloc(&TLSF2, ptr, Layout::from_size_align(200, 8).unwrap());
+        assert_eq!(TLSF2.stats().num_allocations, 0);
+    }
+}
+
+#[cfg(all(feature = "std", any(unix, windows)))]
+#[test]
+fn reentrancy_many_locks() {
+    static TLSFS: [GlobalTlsf; 12] = {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const TLSF: GlobalTlsf = GlobalTlsf::new();
+        [TLSF; 12]
+    };
+    static OTHER: GlobalTlsf = GlobalTlsf::new();
+
+    let layout = Layout::from_size_align(100, 8).unwrap();
+    unsafe {
+        let guards: Vec<_> = TLSFS.iter().map(|tlsf| tlsf.lock_inner()).collect();
+        // Holding more locks than can be identified is handled conservatively
+        for tlsf in TLSFS.iter().chain([&OTHER]) {
+            assert!(alloc::GlobalAlloc::alloc(tlsf, layout).is_null());
+        }
+        drop(guards);
+
+        for tlsf in TLSFS.iter().chain([&OTHER]) {
+            let ptr = alloc::GlobalAlloc::alloc(tlsf, layout);
+            assert!(!ptr.is_null());
+            alloc::GlobalAlloc::dealloc(tlsf, ptr, layout);
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "std"))]
+#[test]
+fn move_after_use() {
+    // An instance that isn't held across `fork` can be moved after use
+    let tlsf: GlobalTlsf = GlobalTlsf::new();
+    let layout = Layout::new::<u64>();
+    unsafe { alloc::GlobalAlloc::dealloc(&tlsf, alloc::GlobalAlloc::alloc(&tlsf, layout), layout) };
+    assert!(!tlsf.mutex.is_held_across_fork());
+
+    let tlsf = Box::new(tlsf);
+    unsafe { alloc::GlobalAlloc::dealloc(&*tlsf, alloc::GlobalAlloc::alloc(&*tlsf, layout), layout) };
+    drop(tlsf);
+
+    unsafe {
+        let pid = libc::fork();
+        assert!(pid >= 0);
+        if pid == 0 {
+            libc::_exit(0);
+        }
+
+        let mut status = 0;
+        assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+        assert_eq!(status, 0);
+    }
+}
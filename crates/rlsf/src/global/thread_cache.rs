@@ -0,0 +1,82 @@
+//! Per-thread caches of small memory blocks
+use core::{
+    cell::{Cell, UnsafeCell},
+    ptr,
+};
+
+use crate::ThreadCache;
+
+/// Returns the memory blocks in a [`ThreadCache`] to the heap `owner`.
+pub type FlushFn = unsafe fn(owner: *const (), cache: &mut ThreadCache);
+
+struct BoundThreadCache {
+    /// The heap the cached memory blocks belong to
+    owner: Cell<*const ()>,
+    flush: Cell<Option<FlushFn>>,
+    cache: UnsafeCell<ThreadCache>,
+}
+
+impl Drop for BoundThreadCache {
+    fn drop(&mut self) {
+        if let Some(flush) = self.flush.get() {
+            // Safety: `owner` outlives the threads using it (upheld by the
+            //         caller of `GlobalTlsf::new_with_thread_cache`)
+            unsafe { flush(self.owner.get(), self.cache.get_mut()) };
+        }
+    }
+}
+
+std::thread_local! {
+    /// Set while [`CACHE`] is in use. Accessing `CACHE` for the first time
+    /// might allocate memory to register its destructor, and this flag
+    /// prevents such nested allocations from re-entering `CACHE`.
+    static BUSY: Cell<bool> = const { Cell::new(false) };
+
+    static CACHE: BoundThreadCache = const {
+        BoundThreadCache {
+            owner: Cell::new(ptr::null()),
+            flush: Cell::new(None),
+            cache: UnsafeCell::new(ThreadCache::new()),
+        }
+    };
+}
+
+/// Call `f` with the calling thread's cache if it's available for `owner`.
+/// The cache is bound to `owner` on first use, and `flush` is called with it
+/// when the thread exits.
+///
+/// Returns `None` without calling `f` if the cache is bound to another
+/// heap, has been destroyed because the thread is exiting, or is already in
+/// use by an outer call.
+#[inline]
+pub fn with_cache<R>(
+    owner: *const (),
+    flush: FlushFn,
+    f: impl FnOnce(&mut ThreadCache) -> R,
+) -> Option<R> {
+    BUSY.try_with(|busy| {
+        if busy.replace(true) {
+            return None;
+        }
+
+        let result = CACHE
+            .try_with(|cache| {
+                if cache.owner.get() != owner {
+                    if !cache.owner.get().is_null() {
+                        return None;
+                    }
+                    cache.owner.set(owner);
+                    cache.flush.set(Some(flush));
+                }
+                // Safety: `BUSY` guarantees exclusive access
+                Some(f(unsafe { &mut *cache.cache.get() }))
+            })
+            .ok()
+            .flatten();
+
+        busy.set(false);
+        result
+    })
+    .ok()
+    .flatten()
+}
@@ -0,0 +1,253 @@
+use const_default1::ConstDefault;
+use core::{
+    arch::asm,
+    cell::UnsafeCell,
+    ffi::c_void,
+    marker::PhantomData,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use super::GlobalTlsfOptions;
+use crate::{
+    flex::SourceError,
+    utils::{nonnull_slice_from_raw_parts, nonnull_slice_len},
+};
+
+const PAGE_SIZE: usize = 1 << 12;
+
+/// Get the allocation unit minus 1.
+#[inline]
+fn alloc_unit_m1<Options: GlobalTlsfOptions>() -> usize {
+    Options::ALLOC_UNIT.next_power_of_two().max(PAGE_SIZE) - 1
+}
+
+/// `EFI_STATUS` indicating success
+const EFI_SUCCESS: usize = 0;
+/// `EFI_TPL`, the task priority level at which the allocator runs
+const TPL_NOTIFY: usize = 16;
+/// `EFI_ALLOCATE_TYPE::AllocateAnyPages`
+const ALLOCATE_ANY_PAGES: u32 = 0;
+/// `EFI_MEMORY_TYPE::EfiLoaderData`
+const EFI_LOADER_DATA: u32 = 2;
+
+/// `EFI_TABLE_HEADER`
+#[allow(dead_code)]
+#[repr(C)]
+struct TableHeader {
+    signature: u64,
+    revision: u32,
+    header_size: u32,
+    crc32: u32,
+    reserved: u32,
+}
+
+/// The prefix of `EFI_SYSTEM_TABLE` used by the allocator
+#[allow(dead_code)]
+#[repr(C)]
+struct SystemTable {
+    hdr: TableHeader,
+    firmware_vendor: *const u16,
+    firmware_revision: u32,
+    console_in_handle: *mut c_void,
+    con_in: *mut c_void,
+    console_out_handle: *mut c_void,
+    con_out: *mut SimpleTextOutputProtocol,
+    standard_error_handle: *mut c_void,
+    std_err: *mut SimpleTextOutputProtocol,
+    runtime_services: *mut c_void,
+    boot_services: *mut BootServices,
+}
+
+/// The prefix of `EFI_BOOT_SERVICES` used by the allocator
+#[allow(dead_code)]
+#[repr(C)]
+struct BootServices {
+    hdr: TableHeader,
+    raise_tpl: unsafe extern "win64" fn(new_tpl: usize) -> usize,
+    restore_tpl: unsafe extern "win64" fn(old_tpl: usize),
+    allocate_pages: unsafe extern "win64" fn(
+        allocate_type: u32,
+        memory_type: u32,
+        pages: usize,
+        memory: *mut u64,
+    ) -> usize,
+    free_pages: unsafe extern "win64" fn(memory: u64, pages: usize) -> usize,
+}
+
+/// The prefix of `EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL` used by the allocator
+#[allow(dead_code)]
+#[repr(C)]
+struct SimpleTextOutputProtocol {
+    reset: *mut c_void,
+    output_string: unsafe extern "win64" fn(this: *mut Self, string: *const u16) -> usize,
+}
+
+/// The system table registered by [`set_system_table`]
+static SYSTEM_TABLE: AtomicPtr<SystemTable> = AtomicPtr::new(ptr::null_mut());
+
+/// Implements [`crate::set_uefi_system_table`].
+///
+/// # Safety
+///
+/// See [`crate::set_uefi_system_table`].
+#[inline]
+pub unsafe fn set_system_table(system_table: Option<NonNull<c_void>>) {
+    let system_table = system_table.map_or(ptr::null_mut(), |p| p.as_ptr().cast());
+    SYSTEM_TABLE.store(system_table, Ordering::Release);
+}
+
+/// Get the boot services if they're available.
+#[inline]
+fn boot_services() -> Option<&'static BootServices> {
+    // Safety: The registered system table and its boot services are valid
+    //         until the registration is revoked (upheld by the caller of
+    //         `set_system_table`)
+    unsafe {
+        let system_table = SYSTEM_TABLE.load(Ordering::Acquire).as_ref()?;
+        system_table.boot_services.as_ref()
+    }
+}
+
+/// Excludes the event notification functions running at `TPL_NOTIFY` or
+/// lower by raising the task priority level. UEFI boot services run on a
+/// single processor, so there is nothing else to exclude.
+pub struct Mutex {
+    /// The task priority level to restore on unlock
+    old_tpl: UnsafeCell<Option<usize>>,
+}
+
+impl ConstDefault for Mutex {
+    const DEFAULT: Self = Self {
+        old_tpl: UnsafeCell::new(None),
+    };
+}
+
+impl Mutex {
+    #[inline]
+    pub fn lock(&self) {
+        if let Some(boot_services) = boot_services() {
+            // Safety: The caller runs at `TPL_NOTIFY` or lower, and
+            //         `old_tpl` is only accessed by the lock holder
+            unsafe { *self.old_tpl.get() = Some((boot_services.raise_tpl)(TPL_NOTIFY)) };
+        }
+    }
+
+    /// Raising the task priority level never waits, so this always succeeds.
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        self.lock();
+        true
+    }
+
+    #[inline]
+    pub fn unlock(&self) {
+        // Safety: `old_tpl` is only accessed by the lock holder
+        if let Some(old_tpl) = unsafe { (*self.old_tpl.get()).take() } {
+            if let Some(boot_services) = boot_services() {
+                // Safety: Restores the level saved by `lock`
+                unsafe { (boot_services.restore_tpl)(old_tpl) };
+            }
+        }
+    }
+}
+
+/// Report a heap corruption on the standard error output (if available)
+/// and abort the program.
+#[cfg(feature = "hardened")]
+#[cold]
+pub fn heap_corruption(message: &str) -> ! {
+    // Safety: See `boot_services`
+    if let Some(std_err) = unsafe { SYSTEM_TABLE.load(Ordering::Acquire).as_ref() }
+        .and_then(|system_table| NonNull::new(system_table.std_err))
+    {
+        // `OutputString` takes null-terminated UCS-2 strings
+        let mut buf = [0u16; 64];
+        let chars = "rlsf: heap corruption detected: "
+            .chars()
+            .chain(message.chars())
+            .chain("\r\n".chars());
+        let mut len = 0;
+        for c in chars {
+            buf[len] = if (c as u32) < 0x10000 { c as u16 } else { '?' as u16 };
+            len += 1;
+            if len == buf.len() - 1 || c == '\n' {
+                buf[len] = 0;
+                // Safety: `buf` is a null-terminated string
+                unsafe { (std_err.as_ref().output_string)(std_err.as_ptr(), buf.as_ptr()) };
+                len = 0;
+            }
+        }
+    }
+
+    loop {
+        // Safety: Raises `#UD`, which is handled by the firmware
+        unsafe { asm!("ud2", options(nomem, nostack)) };
+    }
+}
+
+pub struct Source<Options>(PhantomData<fn() -> Options>);
+
+impl<Options> ConstDefault for Source<Options> {
+    const DEFAULT: Self = Self(PhantomData);
+}
+
+/// Boot services provide no way to release physical memory without freeing
+/// it, so this does nothing.
+#[inline]
+pub unsafe fn discard(_region: NonNull<[u8]>) -> usize {
+    0
+}
+
+unsafe impl<Options: GlobalTlsfOptions> crate::flex::FlexSource for Source<Options> {
+    #[inline]
+    unsafe fn alloc(&mut self, min_size: usize) -> Option<NonNull<[u8]>> {
+        self.try_alloc(min_size).ok()
+    }
+
+    #[inline]
+    unsafe fn try_alloc(&mut self, min_size: usize) -> Result<NonNull<[u8]>, SourceError> {
+        let boot_services = boot_services().ok_or(SourceError::Exhausted)?;
+
+        let alloc_unit_m1 = alloc_unit_m1::<Options>();
+        let num_bytes = min_size
+            .checked_add(alloc_unit_m1)
+            .ok_or(SourceError::Exhausted)?
+            & !alloc_unit_m1;
+
+        let mut address = 0u64;
+        let status = (boot_services.allocate_pages)(
+            ALLOCATE_ANY_PAGES,
+            EFI_LOADER_DATA,
+            num_bytes / PAGE_SIZE,
+            &mut address,
+        );
+        if status != EFI_SUCCESS {
+            return Err(SourceError::Exhausted);
+        }
+
+        let ptr = NonNull::new(address as usize as *mut u8).ok_or(SourceError::Exhausted)?;
+        Ok(nonnull_slice_from_raw_parts(ptr, num_bytes))
+    }
+
+    // `dealloc` is used to return huge allocations to the firmware
+    #[inline]
+    unsafe fn dealloc(&mut self, ptr: NonNull<[u8]>) {
+        if let Some(boot_services) = boot_services() {
+            // `ptr` is exactly what `try_alloc` returned because we don't
+            // implement `realloc_inplace_grow`
+            let address = ptr.as_ptr() as *mut u8 as usize as u64;
+            (boot_services.free_pages)(address, nonnull_slice_len(ptr) / PAGE_SIZE);
+        }
+    }
+
+    #[inline]
+    fn supports_dealloc(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn min_align(&self) -> usize {
+        PAGE_SIZE
+    }
+}
@@ -1,38 +1,189 @@
 use const_default1::ConstDefault;
+#[cfg(feature = "std")]
 use core::{
+    ptr::{addr_of_mut, null},
+    sync::atomic::{AtomicBool, Ordering},
+};
+use core::{
+    cell::UnsafeCell,
     marker::PhantomData,
     ptr::{null_mut, NonNull},
 };
 
 use super::GlobalTlsfOptions;
+use crate::{
+    flex::SourceError,
+    utils::{last_os_error, nonnull_slice_len, ptr_with_addr},
+};
 
 const MIN_ALIGN: usize = crate::GRANULARITY;
 
-/// The allocation unit, which is intentionally set to be larger than the usual
-/// page sizes to reduce overhead. TODO: Make this adjustable
-const ALLOC_UNIT: usize = 1 << 16;
-
-pub struct Mutex(());
+/// `pthread_mutex_t` must not be moved while it's in use, which is guaranteed
+/// by the fact that `Mutex` is borrowed while it's locked.
+///
+/// With the `std` feature, [`Self::hold_across_fork`] adds a `Mutex` to
+/// [`REGISTRY`] so that the `fork` handlers can find it, and it removes itself
+/// when it's dropped. It must not be moved in between, which is why the
+/// method is `unsafe`.
+pub struct Mutex {
+    raw: UnsafeCell<libc::pthread_mutex_t>,
+    /// The next `Mutex` in [`REGISTRY`]. Protected by [`REGISTRY_MUTEX`].
+    #[cfg(feature = "std")]
+    next: UnsafeCell<*const Mutex>,
+    /// Whether `self` is in [`REGISTRY`]
+    #[cfg(feature = "std")]
+    registered: AtomicBool,
+}
 
 impl ConstDefault for Mutex {
-    const DEFAULT: Self = Self(());
+    const DEFAULT: Self = Self {
+        raw: UnsafeCell::new(libc::PTHREAD_MUTEX_INITIALIZER),
+        #[cfg(feature = "std")]
+        next: UnsafeCell::new(null()),
+        #[cfg(feature = "std")]
+        registered: AtomicBool::new(false),
+    };
 }
 
-/// `pthread_mutex_t` might be unsafe to move, so we can't put it in `Mutex`.
-static mut MUTEX: libc::pthread_mutex_t = libc::PTHREAD_MUTEX_INITIALIZER;
-
 impl Mutex {
     #[inline]
     pub fn lock(&self) {
-        unsafe { libc::pthread_mutex_lock(&mut MUTEX) };
+        unsafe { libc::pthread_mutex_lock(self.raw.get()) };
+    }
+
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        unsafe { libc::pthread_mutex_trylock(self.raw.get()) == 0 }
     }
 
     #[inline]
     pub fn unlock(&self) {
-        unsafe { libc::pthread_mutex_unlock(&mut MUTEX) };
+        unsafe { libc::pthread_mutex_unlock(self.raw.get()) };
+    }
+
+    /// Add `self` to [`REGISTRY`] and register the `fork` handlers if they
+    /// haven't been yet, so that `self` is held across `fork`.
+    ///
+    /// # Safety
+    ///
+    /// `self` must not be moved until it's dropped.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub unsafe fn hold_across_fork(&self) {
+        if !self.registered.load(Ordering::Relaxed) {
+            self.register();
+        }
+    }
+
+    /// Check if [`Self::hold_across_fork`] has been called on `self`.
+    #[cfg(all(test, feature = "std"))]
+    pub fn is_held_across_fork(&self) -> bool {
+        self.registered.load(Ordering::Relaxed)
+    }
+
+    #[cfg(feature = "std")]
+    #[cold]
+    fn register(&self) {
+        // This might allocate memory and re-enter `self.lock`
+        ensure_atfork_handlers();
+
+        unsafe {
+            libc::pthread_mutex_lock(addr_of_mut!(REGISTRY_MUTEX));
+            if !self.registered.load(Ordering::Relaxed) {
+                *self.next.get() = REGISTRY;
+                REGISTRY = self;
+                self.registered.store(true, Ordering::Relaxed);
+            }
+            libc::pthread_mutex_unlock(addr_of_mut!(REGISTRY_MUTEX));
+        }
+    }
+}
+
+impl Drop for Mutex {
+    fn drop(&mut self) {
+        #[cfg(feature = "std")]
+        if *self.registered.get_mut() {
+            unsafe {
+                libc::pthread_mutex_lock(addr_of_mut!(REGISTRY_MUTEX));
+                let mut link: *mut *const Mutex = addr_of_mut!(REGISTRY);
+                while !core::ptr::eq(*link, self) {
+                    link = (**link).next.get();
+                }
+                *link = *self.next.get();
+                libc::pthread_mutex_unlock(addr_of_mut!(REGISTRY_MUTEX));
+            }
+        }
+        unsafe { libc::pthread_mutex_destroy(self.raw.get()) };
+    }
+}
+
+/// The head of the linked list of the [`Mutex`]es that have been locked
+/// (`std` feature). Protected by [`REGISTRY_MUTEX`].
+#[cfg(feature = "std")]
+static mut REGISTRY: *const Mutex = null();
+
+/// Protects [`REGISTRY`]. Held across `fork` along with the registered
+/// mutexes, so that the list doesn't change while they're being locked.
+#[cfg(feature = "std")]
+static mut REGISTRY_MUTEX: libc::pthread_mutex_t = libc::PTHREAD_MUTEX_INITIALIZER;
+
+/// Whether [`ensure_atfork_handlers`] has registered the `fork` handlers.
+#[cfg(feature = "std")]
+static ATFORK_REGISTERED: AtomicBool = AtomicBool::new(false);
+
+/// Register `fork` handlers that hold all of the mutexes in [`REGISTRY`]
+/// across `fork`.
+///
+/// Only the forking thread survives in the child process. Without the
+/// handlers, if another thread held a mutex at the time of `fork`, the mutex
+/// would stay locked forever in the child process, and the child process
+/// would deadlock on its first allocation.
+#[cfg(feature = "std")]
+#[inline]
+fn ensure_atfork_handlers() {
+    if !ATFORK_REGISTERED.load(Ordering::Relaxed) {
+        register_atfork_handlers();
+    }
+}
+
+#[cfg(feature = "std")]
+#[cold]
+fn register_atfork_handlers() {
+    unsafe extern "C" fn prepare() {
+        libc::pthread_mutex_lock(addr_of_mut!(REGISTRY_MUTEX));
+        let mut mutex = REGISTRY;
+        while let Some(m) = mutex.as_ref() {
+            libc::pthread_mutex_lock(m.raw.get());
+            mutex = *m.next.get();
+        }
+    }
+
+    unsafe extern "C" fn release() {
+        let mut mutex = REGISTRY;
+        while let Some(m) = mutex.as_ref() {
+            libc::pthread_mutex_unlock(m.raw.get());
+            mutex = *m.next.get();
+        }
+        libc::pthread_mutex_unlock(addr_of_mut!(REGISTRY_MUTEX));
+    }
+
+    // Set the flag first because `pthread_atfork` might allocate memory and
+    // re-enter this function
+    if !ATFORK_REGISTERED.swap(true, Ordering::Relaxed) {
+        unsafe { libc::pthread_atfork(Some(prepare), Some(release), Some(release)) };
     }
 }
 
+/// Report a heap corruption on the standard error and abort the process.
+#[cfg(feature = "hardened")]
+#[cold]
+pub fn heap_corruption(message: &str) -> ! {
+    for part in ["rlsf: heap corruption detected: ", message, "\n"] {
+        unsafe { libc::write(2, part.as_ptr() as *const libc::c_void, part.len()) };
+    }
+    unsafe { libc::abort() }
+}
+
 pub struct Source<Options>(PhantomData<fn() -> Options>);
 
 impl<Options> ConstDefault for Source<Options> {
@@ -44,7 +195,7 @@ static mut PAGE_SIZE_M1: usize = 0;
 #[cold]
 fn init_page_size() -> usize {
     unsafe {
-        let page_size = (libc::sysconf(libc::_SC_PAGESIZE) as usize).max(ALLOC_UNIT);
+        let page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
         if !page_size.is_power_of_two() {
             libc::abort();
         }
@@ -60,7 +211,7 @@ fn init_page_size() -> usize {
 }
 
 #[inline]
-fn ensure_page_size_m1() -> usize {
+pub fn ensure_page_size_m1() -> usize {
     let page_size_m1 = unsafe { PAGE_SIZE_M1 };
     if page_size_m1 == 0 {
         // `init_page_size` returns the initialized value for
@@ -71,11 +222,114 @@ fn ensure_page_size_m1() -> usize {
     }
 }
 
+/// Get the allocation unit minus 1. The allocation unit is the larger of the
+/// page size and `Options::ALLOC_UNIT`, the latter of which is intentionally
+/// set to be larger than the usual page sizes by default to reduce overhead.
+#[inline]
+fn alloc_unit_m1<Options: GlobalTlsfOptions>() -> usize {
+    ensure_page_size_m1().max(Options::ALLOC_UNIT.next_power_of_two() - 1)
+}
+
+/// The size of transparent huge pages (PMD-sized pages on x86_64 and arm64
+/// with 4KiB base pages)
+#[cfg(target_os = "linux")]
+const HUGE_PAGE_SIZE: usize = 1 << 21;
+
+/// Map `num_bytes` bytes (rounded up to [`HUGE_PAGE_SIZE`]) aligned to
+/// [`HUGE_PAGE_SIZE`] and advise the kernel to back them with transparent
+/// huge pages.
+#[cfg(target_os = "linux")]
+#[cold]
+unsafe fn mmap_huge_pages(num_bytes: usize) -> Result<NonNull<[u8]>, SourceError> {
+    let num_bytes = num_bytes
+        .checked_add(HUGE_PAGE_SIZE - 1)
+        .ok_or(SourceError::Exhausted)?
+        & !(HUGE_PAGE_SIZE - 1);
+
+    // Over-allocate to find an aligned range
+    let num_mapped_bytes = num_bytes
+        .checked_add(HUGE_PAGE_SIZE.saturating_sub(ensure_page_size_m1() + 1))
+        .ok_or(SourceError::Exhausted)?;
+    let ptr = libc::mmap(
+        null_mut(),
+        num_mapped_bytes,
+        libc::PROT_WRITE | libc::PROT_READ,
+        libc::MAP_ANONYMOUS | libc::MAP_PRIVATE,
+        -1,
+        0,
+    );
+
+    if ptr == libc::MAP_FAILED {
+        return Err(last_os_error());
+    }
+
+    // Unmap the excess
+    let mapped_start = ptr as usize;
+    let mapped_end = mapped_start + num_mapped_bytes;
+    let start = (mapped_start + HUGE_PAGE_SIZE - 1) & !(HUGE_PAGE_SIZE - 1);
+    let end = start + num_bytes;
+    if start > mapped_start {
+        libc::munmap(ptr, start - mapped_start);
+    }
+    if mapped_end > end {
+        libc::munmap(ptr_with_addr(ptr, end), mapped_end - end);
+    }
+
+    // This is only advisory; transparent huge pages might be disabled
+    let start = ptr_with_addr(ptr, start);
+    libc::madvise(start, num_bytes, libc::MADV_HUGEPAGE);
+
+    NonNull::new(core::ptr::slice_from_raw_parts_mut(
+        start as *mut u8,
+        num_bytes,
+    ))
+    .ok_or(SourceError::Exhausted)
+}
+
+/// Release the physical memory pages fully contained in `region`. Returns the
+/// number of released bytes.
+///
+/// # Safety
+///
+/// `region` must be a part of a memory block returned by `Source` whose
+/// contents can be discarded.
+pub unsafe fn discard(region: NonNull<[u8]>) -> usize {
+    let page_size_m1 = ensure_page_size_m1();
+    let region_ptr = region.as_ptr() as *mut u8;
+    let start = region_ptr as usize;
+    let end = start + nonnull_slice_len(region);
+    let start = start.wrapping_add(page_size_m1) & !page_size_m1;
+    let end = end & !page_size_m1;
+    if end <= start {
+        return 0;
+    }
+
+    let start_ptr = ptr_with_addr(region_ptr, start) as *mut libc::c_void;
+    if libc::madvise(start_ptr, end - start, libc::MADV_DONTNEED) == 0 {
+        end - start
+    } else {
+        0
+    }
+}
+
 unsafe impl<Options: GlobalTlsfOptions> crate::flex::FlexSource for Source<Options> {
     #[inline]
     unsafe fn alloc(&mut self, min_size: usize) -> Option<NonNull<[u8]>> {
-        let page_size_m1 = ensure_page_size_m1();
-        let num_bytes = min_size.checked_add(page_size_m1)? & !page_size_m1;
+        self.try_alloc(min_size).ok()
+    }
+
+    #[inline]
+    unsafe fn try_alloc(&mut self, min_size: usize) -> Result<NonNull<[u8]>, SourceError> {
+        let alloc_unit_m1 = alloc_unit_m1::<Options>();
+        let num_bytes = min_size
+            .checked_add(alloc_unit_m1)
+            .ok_or(SourceError::Exhausted)?
+            & !alloc_unit_m1;
+
+        #[cfg(target_os = "linux")]
+        if num_bytes >= Options::HUGEPAGE_THRESHOLD {
+            return mmap_huge_pages(num_bytes);
+        }
 
         let ptr = libc::mmap(
             null_mut(),
@@ -87,13 +341,14 @@ unsafe impl<Options: GlobalTlsfOptions> crate::flex::FlexSource for Source<Optio
         );
 
         if ptr == libc::MAP_FAILED {
-            return None;
+            return Err(last_os_error());
         }
 
         NonNull::new(core::ptr::slice_from_raw_parts_mut(
             ptr as *mut u8,
             num_bytes,
         ))
+        .ok_or(SourceError::Exhausted)
     }
 
     #[inline]
@@ -104,14 +359,22 @@ unsafe impl<Options: GlobalTlsfOptions> crate::flex::FlexSource for Source<Optio
         ptr: NonNull<[u8]>,
         min_new_len: usize,
     ) -> Option<usize> {
-        use crate::utils::nonnull_slice_len;
-
         if !Options::COALESCE_POOLS {
             return None;
         }
 
-        let page_size_m1 = ensure_page_size_m1();
-        let num_bytes = min_new_len.checked_add(page_size_m1)? & !page_size_m1;
+        let alloc_unit_m1 = alloc_unit_m1::<Options>();
+        let mut num_bytes = min_new_len.checked_add(alloc_unit_m1)? & !alloc_unit_m1;
+
+        let use_huge_pages = num_bytes >= Options::HUGEPAGE_THRESHOLD;
+        if use_huge_pages {
+            // Make the pool end at a huge page boundary
+            let start = ptr.as_ptr() as *mut u8 as usize;
+            let end = start.checked_add(num_bytes)?.checked_add(HUGE_PAGE_SIZE - 1)?
+                & !(HUGE_PAGE_SIZE - 1);
+            num_bytes = end - start;
+        }
+
         let num_growth_bytes = num_bytes - nonnull_slice_len(ptr);
 
         let ptr_end = (ptr.as_ptr() as *mut u8).wrapping_add(nonnull_slice_len(ptr));
@@ -133,6 +396,11 @@ unsafe impl<Options: GlobalTlsfOptions> crate::flex::FlexSource for Source<Optio
         } else if ptr_growth_start == libc::MAP_FAILED {
             None
         } else {
+            if use_huge_pages {
+                // This is only advisory; transparent huge pages might be
+                // disabled
+                libc::madvise(ptr.as_ptr() as *mut libc::c_void, num_bytes, libc::MADV_HUGEPAGE);
+            }
             Some(num_bytes)
         }
     }
@@ -143,8 +411,18 @@ unsafe impl<Options: GlobalTlsfOptions> crate::flex::FlexSource for Source<Optio
         Options::COALESCE_POOLS
     }
 
-    // Not implementing `dealloc` because there is no safe way to destruct
-    // a registered global allocator anyway.
+    // `dealloc` is used to return huge allocations to the system. It can also
+    // be called on memory pools grown by `realloc_inplace_grow` because
+    // `munmap` can unmap multiple adjacent mappings at once.
+    #[inline]
+    unsafe fn dealloc(&mut self, ptr: NonNull<[u8]>) {
+        libc::munmap(ptr.as_ptr() as *mut libc::c_void, nonnull_slice_len(ptr));
+    }
+
+    #[inline]
+    fn supports_dealloc(&self) -> bool {
+        true
+    }
 
     #[inline]
     fn min_align(&self) -> usize {
@@ -15,10 +15,23 @@ impl Mutex {
     #[inline]
     pub fn lock(&self) {}
 
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        true
+    }
+
     #[inline]
     pub fn unlock(&self) {}
 }
 
+/// Abort the program on a heap corruption. There's no standard error to
+/// report it to.
+#[cfg(feature = "hardened")]
+#[cold]
+pub fn heap_corruption(_message: &str) -> ! {
+    wasm32::unreachable()
+}
+
 pub struct Source<Options>(PhantomData<fn() -> Options>);
 
 impl<Options> ConstDefault for Source<Options> {
@@ -29,10 +42,24 @@ const MEM: u32 = 0;
 const PAGE_SIZE_LOG2: u32 = 16;
 const PAGE_SIZE: usize = 1 << PAGE_SIZE_LOG2;
 
+/// Get the allocation unit in pages.
+#[inline]
+fn alloc_unit_pages<Options: GlobalTlsfOptions>() -> usize {
+    Options::ALLOC_UNIT.next_power_of_two().max(PAGE_SIZE) / PAGE_SIZE
+}
+
+/// The linear memory can't shrink, so this does nothing.
+#[inline]
+pub unsafe fn discard(_region: NonNull<[u8]>) -> usize {
+    0
+}
+
 unsafe impl<Options: GlobalTlsfOptions> crate::flex::FlexSource for Source<Options> {
     #[inline]
     unsafe fn alloc(&mut self, min_size: usize) -> Option<NonNull<[u8]>> {
+        let unit_pages = alloc_unit_pages::<Options>();
         let num_pages = min_size.checked_add(PAGE_SIZE - 1)? / PAGE_SIZE;
+        let num_pages = num_pages.checked_add(unit_pages - 1)? & !(unit_pages - 1);
         let num_bytes = num_pages * PAGE_SIZE;
 
         let old_num_pages = wasm32::memory_grow(MEM, num_pages);
@@ -70,7 +97,9 @@ unsafe impl<Options: GlobalTlsfOptions> crate::flex::FlexSource for Source<Optio
             return None;
         }
 
+        let unit_pages = alloc_unit_pages::<Options>();
         let new_num_pages = min_new_len.checked_add(PAGE_SIZE - 1)? / PAGE_SIZE;
+        let new_num_pages = new_num_pages.checked_add(unit_pages - 1)? & !(unit_pages - 1);
         let new_len = new_num_pages * PAGE_SIZE;
 
         if wasm32::memory_grow(MEM, new_num_pages - ptr_page) == usize::MAX {
@@ -0,0 +1,272 @@
+use const_default1::ConstDefault;
+use core::{
+    cell::UnsafeCell,
+    ffi::c_void,
+    marker::PhantomData,
+    mem::{self, MaybeUninit},
+    ptr::{null_mut, NonNull},
+};
+
+use super::GlobalTlsfOptions;
+use crate::{flex::SourceError, utils::nonnull_slice_len};
+
+/// The allocation granularity of `VirtualAlloc` on all supported versions of
+/// Windows. Reserving less than this wastes the rest of the address space
+/// range.
+const ALLOC_GRANULARITY: usize = 1 << 16;
+
+/// Get the allocation unit minus 1.
+#[inline]
+fn alloc_unit_m1<Options: GlobalTlsfOptions>() -> usize {
+    Options::ALLOC_UNIT.next_power_of_two().max(ALLOC_GRANULARITY) - 1
+}
+
+/// The page size on all architectures supported by Windows.
+const PAGE_SIZE: usize = 1 << 12;
+
+const MEM_COMMIT: u32 = 0x1000;
+const MEM_RESERVE: u32 = 0x2000;
+const MEM_RESET: u32 = 0x80000;
+const MEM_RELEASE: u32 = 0x8000;
+const PAGE_READWRITE: u32 = 0x04;
+
+#[repr(C)]
+struct SrwLock(*mut c_void);
+
+/// `MEMORY_BASIC_INFORMATION`
+#[repr(C)]
+#[allow(dead_code)]
+struct MemoryBasicInformation {
+    base_address: *mut c_void,
+    allocation_base: *mut c_void,
+    allocation_protect: u32,
+    #[cfg(target_pointer_width = "64")]
+    partition_id: u16,
+    region_size: usize,
+    state: u32,
+    protect: u32,
+    ty: u32,
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn VirtualAlloc(
+        lpAddress: *mut c_void,
+        dwSize: usize,
+        flAllocationType: u32,
+        flProtect: u32,
+    ) -> *mut c_void;
+    fn VirtualFree(lpAddress: *mut c_void, dwSize: usize, dwFreeType: u32) -> i32;
+    fn VirtualQuery(
+        lpAddress: *const c_void,
+        lpBuffer: *mut MemoryBasicInformation,
+        dwLength: usize,
+    ) -> usize;
+    fn AcquireSRWLockExclusive(SRWLock: *mut SrwLock);
+    fn TryAcquireSRWLockExclusive(SRWLock: *mut SrwLock) -> u8;
+    fn ReleaseSRWLockExclusive(SRWLock: *mut SrwLock);
+    fn GetLastError() -> u32;
+    #[cfg(feature = "hardened")]
+    fn GetStdHandle(nStdHandle: u32) -> *mut c_void;
+    #[cfg(feature = "hardened")]
+    fn WriteFile(
+        hFile: *mut c_void,
+        lpBuffer: *const c_void,
+        nNumberOfBytesToWrite: u32,
+        lpNumberOfBytesWritten: *mut u32,
+        lpOverlapped: *mut c_void,
+    ) -> i32;
+    #[cfg(feature = "hardened")]
+    fn RaiseFailFastException(
+        pExceptionRecord: *mut c_void,
+        pContextRecord: *mut c_void,
+        dwFlags: u32,
+    ) -> !;
+}
+
+/// `SRWLOCK` must not be moved while it's in use, which is guaranteed by the
+/// fact that `Mutex` is borrowed while it's locked.
+pub struct Mutex(UnsafeCell<SrwLock>);
+
+impl ConstDefault for Mutex {
+    const DEFAULT: Self = Self(UnsafeCell::new(SrwLock(null_mut()))); // `SRWLOCK_INIT`
+}
+
+impl Mutex {
+    #[inline]
+    pub fn lock(&self) {
+        unsafe { AcquireSRWLockExclusive(self.0.get()) };
+    }
+
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        unsafe { TryAcquireSRWLockExclusive(self.0.get()) != 0 }
+    }
+
+    #[inline]
+    pub fn unlock(&self) {
+        unsafe { ReleaseSRWLockExclusive(self.0.get()) };
+    }
+}
+
+/// Report a heap corruption on the standard error and terminate the process.
+#[cfg(feature = "hardened")]
+#[cold]
+pub fn heap_corruption(message: &str) -> ! {
+    const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+    unsafe {
+        let stderr = GetStdHandle(STD_ERROR_HANDLE);
+        for part in ["rlsf: heap corruption detected: ", message, "\n"] {
+            let mut written = 0;
+            WriteFile(
+                stderr,
+                part.as_ptr() as *const c_void,
+                part.len() as u32,
+                &mut written,
+                null_mut(),
+            );
+        }
+        RaiseFailFastException(null_mut(), null_mut(), 0)
+    }
+}
+
+pub struct Source<Options>(PhantomData<fn() -> Options>);
+
+impl<Options> ConstDefault for Source<Options> {
+    const DEFAULT: Self = Self(PhantomData);
+}
+
+/// Get the error code of the last failed system call.
+#[inline]
+fn last_os_error() -> SourceError {
+    SourceError::Os(unsafe { GetLastError() } as i32)
+}
+
+/// Release the physical memory pages fully contained in `region`. Returns the
+/// number of released bytes.
+///
+/// # Safety
+///
+/// `region` must be a part of a memory block returned by `Source` whose
+/// contents can be discarded.
+pub unsafe fn discard(region: NonNull<[u8]>) -> usize {
+    let start = region.as_ptr() as *mut u8 as usize;
+    let end = start + nonnull_slice_len(region);
+    let start = start.wrapping_add(PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    let end = end & !(PAGE_SIZE - 1);
+    if end <= start {
+        return 0;
+    }
+
+    // The pages stay committed, but their contents don't have to be
+    // preserved, so the system can reclaim them without writing them to the
+    // paging file.
+    if VirtualAlloc(start as *mut c_void, end - start, MEM_RESET, PAGE_READWRITE).is_null() {
+        0
+    } else {
+        end - start
+    }
+}
+
+unsafe impl<Options: GlobalTlsfOptions> crate::flex::FlexSource for Source<Options> {
+    #[inline]
+    unsafe fn alloc(&mut self, min_size: usize) -> Option<NonNull<[u8]>> {
+        self.try_alloc(min_size).ok()
+    }
+
+    #[inline]
+    unsafe fn try_alloc(&mut self, min_size: usize) -> Result<NonNull<[u8]>, SourceError> {
+        let alloc_unit_m1 = alloc_unit_m1::<Options>();
+        let num_bytes = min_size
+            .checked_add(alloc_unit_m1)
+            .ok_or(SourceError::Exhausted)?
+            & !alloc_unit_m1;
+
+        let ptr = VirtualAlloc(
+            null_mut(),
+            num_bytes,
+            MEM_RESERVE | MEM_COMMIT,
+            PAGE_READWRITE,
+        );
+
+        NonNull::new(core::ptr::slice_from_raw_parts_mut(
+            ptr as *mut u8,
+            num_bytes,
+        ))
+        .ok_or_else(last_os_error)
+    }
+
+    #[inline]
+    unsafe fn realloc_inplace_grow(
+        &mut self,
+        ptr: NonNull<[u8]>,
+        min_new_len: usize,
+    ) -> Option<usize> {
+        if !Options::COALESCE_POOLS {
+            return None;
+        }
+
+        let alloc_unit_m1 = alloc_unit_m1::<Options>();
+        let num_bytes = min_new_len.checked_add(alloc_unit_m1)? & !alloc_unit_m1;
+        let num_growth_bytes = num_bytes - nonnull_slice_len(ptr);
+
+        let ptr_end = (ptr.as_ptr() as *mut u8).wrapping_add(nonnull_slice_len(ptr));
+
+        // `VirtualAlloc` fails if the requested range is already reserved.
+        // The new reservation can't be released together with the existing
+        // one, but that's okay because we never release memory.
+        let ptr_growth_start = VirtualAlloc(
+            ptr_end as _,
+            num_growth_bytes,
+            MEM_RESERVE | MEM_COMMIT,
+            PAGE_READWRITE,
+        );
+
+        if ptr_growth_start == ptr_end as _ {
+            Some(num_bytes)
+        } else {
+            debug_assert!(ptr_growth_start.is_null());
+            None
+        }
+    }
+
+    #[inline]
+    fn supports_realloc_inplace_grow(&self) -> bool {
+        Options::COALESCE_POOLS
+    }
+
+    // `dealloc` is used to return huge allocations to the system
+    #[inline]
+    unsafe fn dealloc(&mut self, ptr: NonNull<[u8]>) {
+        // A memory pool grown by `realloc_inplace_grow` consists of multiple
+        // reservations, each of which must be released separately
+        let mut cur = ptr.as_ptr() as *mut u8;
+        let end = cur.wrapping_add(nonnull_slice_len(ptr));
+        while cur < end {
+            let mut info = MaybeUninit::<MemoryBasicInformation>::uninit();
+            if VirtualQuery(
+                cur as _,
+                info.as_mut_ptr(),
+                mem::size_of::<MemoryBasicInformation>(),
+            ) == 0
+            {
+                break;
+            }
+            let info = info.assume_init();
+            debug_assert_eq!(info.allocation_base, cur as *mut c_void);
+
+            VirtualFree(cur as _, 0, MEM_RELEASE);
+            cur = cur.wrapping_add(info.region_size);
+        }
+    }
+
+    #[inline]
+    fn supports_dealloc(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn min_align(&self) -> usize {
+        ALLOC_GRANULARITY
+    }
+}
@@ -0,0 +1,93 @@
+//! The leak check performed when a [`Tlsf`](crate::Tlsf) or
+//! [`FlexTlsf`](crate::FlexTlsf) is dropped
+
+/// The number and total size of memory blocks that were not deallocated
+/// when a [`Tlsf`](crate::Tlsf) or [`FlexTlsf`](crate::FlexTlsf) was
+/// dropped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LeakReport {
+    /// The number of leaked allocations.
+    pub count: usize,
+    /// The total usable size of the leaked allocations, in bytes.
+    pub bytes: usize,
+}
+
+/// A function that receives a [`LeakReport`] when a [`Tlsf`](crate::Tlsf) or
+/// [`FlexTlsf`](crate::FlexTlsf) is dropped with live allocations.
+pub type LeakReporter = fn(LeakReport);
+
+/// Tracks live allocations and reports the remaining ones on drop.
+///
+/// This performs the check in its own `Drop` implementation so that the
+/// containing heap doesn't need one, which would require the memory pools
+/// to outlive the heap's destructor.
+#[derive(Debug)]
+pub(crate) struct LeakCheck {
+    live: LeakReport,
+    reporter: Option<LeakReporter>,
+    /// The type name to include in the panic message if `reporter` is
+    /// `None`. If this is `None` too, leaks are silently ignored.
+    owner: Option<&'static str>,
+}
+
+impl LeakCheck {
+    #[inline]
+    pub(crate) const fn new(owner: Option<&'static str>) -> Self {
+        Self {
+            live: LeakReport { count: 0, bytes: 0 },
+            reporter: None,
+            owner,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set_reporter(&mut self, reporter: LeakReporter) {
+        self.reporter = Some(reporter);
+    }
+
+    #[inline]
+    pub(crate) fn on_alloc(&mut self, bytes: usize) {
+        self.live.count += 1;
+        self.live.bytes += bytes;
+    }
+
+    #[inline]
+    pub(crate) fn on_dealloc(&mut self, bytes: usize) {
+        self.live.count -= 1;
+        self.live.bytes -= bytes;
+    }
+}
+
+impl Drop for LeakCheck {
+    fn drop(&mut self) {
+        if self.live.count == 0 {
+            return;
+        }
+
+        if let Some(reporter) = self.reporter {
+            reporter(self.live);
+        } else if let Some(owner) = self.owner {
+            if !panicking() {
+                panic!(
+                    "`{}` dropped with {} live allocation(s) ({} bytes)",
+                    owner, self.live.count, self.live.bytes
+                );
+            }
+        }
+    }
+}
+
+/// Check if the current thread is panicking. Always returns `false` if this
+/// information isn't available.
+#[inline]
+fn panicking() -> bool {
+    #[cfg(feature = "std")]
+    {
+        std::thread::panicking()
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        false
+    }
+}
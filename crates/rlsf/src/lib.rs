@@ -1,21 +1,78 @@
 #![doc = include_str!("../README.md")]
 #![no_std]
 #![cfg_attr(feature = "doc_cfg", feature(doc_cfg))]
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
 
 #[cfg(doc)]
 #[doc = include_str!("../CHANGELOG.md")]
 pub mod _changelog_ {}
 
+#[macro_use]
+mod primitives;
+#[macro_use]
+mod trace;
+
+#[cfg(feature = "asan")]
+mod asan;
+mod checked;
+mod corruption;
+mod cross_core;
 mod flex;
+mod generational;
 pub mod int;
+mod quarantine;
+mod redzone;
+mod thread_cache;
 mod tlsf;
 mod utils;
 pub use self::{
+    checked::*,
+    corruption::{set_corruption_handler, CorruptionHandler, HeapError},
+    cross_core::*,
     flex::*,
-    tlsf::{Tlsf, GRANULARITY},
+    generational::*,
+    quarantine::*,
+    redzone::*,
+    thread_cache::*,
+    tlsf::{
+        ClassOccupancy, HeapStats, IntegrityError, InvalidPointer, LayoutMismatch, Tlsf,
+        GRANULARITY,
+    },
 };
 #[cfg(feature = "unstable")]
 pub use tlsf::BlockInfo;
+#[cfg(feature = "test-utils")]
+pub use tlsf::BlockState;
+#[cfg(feature = "pool-stats")]
+pub use tlsf::PoolUsage;
+#[cfg(feature = "randomize")]
+pub use tlsf::RandomSource;
+#[cfg(feature = "oom-report")]
+pub use tlsf::{OomHook, OomReport};
+#[cfg(feature = "stats")]
+pub use tlsf::{TlsfCounters, TlsfUtilization};
+
+#[cfg(all(feature = "mte", target_arch = "aarch64"))]
+mod mte;
+#[cfg(all(feature = "mte", target_arch = "aarch64"))]
+pub use self::mte::MteTlsf;
+
+#[cfg(feature = "observer")]
+mod observer;
+#[cfg(feature = "observer")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "observer")))]
+pub use self::observer::AllocObserver;
+
+#[cfg(feature = "timing")]
+mod timing;
+#[cfg(feature = "timing")]
+pub use self::timing::{Latency, TlsfTiming};
+
+#[cfg(feature = "debug-leak-check")]
+mod leak_check;
+#[cfg(feature = "debug-leak-check")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "debug-leak-check")))]
+pub use self::leak_check::{LeakReport, LeakReporter};
 
 /// Attaches `#[cfg(...)]` and `#[doc(cfg(...))]` to a given item definition
 /// to conditionally compile it only when we have a `GlobalTlsf` implementation
@@ -27,6 +84,10 @@ macro_rules! if_supported_target {
         #[cfg(any(
             all(target_arch = "wasm32", not(target_feature = "atomics")),
             unix,
+            windows,
+            target_os = "hermit",
+            all(target_arch = "x86_64", target_env = "sgx"),
+            all(target_os = "uefi", target_arch = "x86_64"),
             doc,
         ))]
         #[cfg_attr(
@@ -34,6 +95,10 @@ macro_rules! if_supported_target {
             doc(cfg(any(
                 all(target_arch = "wasm32", not(target_feature = "atomics")),
                 unix,
+                windows,
+                target_os = "hermit",
+                all(target_arch = "x86_64", target_env = "sgx"),
+                all(target_os = "uefi", target_arch = "x86_64"),
                 // no `doc` here
             )))
         )]
@@ -41,6 +106,88 @@ macro_rules! if_supported_target {
     };
 }
 
+#[cfg(any(
+    target_has_atomic = "8",
+    feature = "critical-section",
+    all(feature = "cortex-m", target_arch = "arm"),
+))]
+mod static_global;
+#[cfg(any(
+    target_has_atomic = "8",
+    feature = "critical-section",
+    all(feature = "cortex-m", target_arch = "arm"),
+))]
+pub use self::static_global::*;
+
+#[cfg(any(
+    target_has_atomic = "8",
+    feature = "critical-section",
+    all(feature = "cortex-m", target_arch = "arm"),
+))]
+mod sync;
+#[cfg(any(
+    target_has_atomic = "8",
+    feature = "critical-section",
+    all(feature = "cortex-m", target_arch = "arm"),
+))]
+pub use self::sync::*;
+
+#[cfg(any(
+    target_has_atomic = "8",
+    feature = "critical-section",
+    all(feature = "cortex-m", target_arch = "arm"),
+))]
+mod async_tlsf;
+#[cfg(any(
+    target_has_atomic = "8",
+    feature = "critical-section",
+    all(feature = "cortex-m", target_arch = "arm"),
+))]
+pub use self::async_tlsf::*;
+
+#[cfg(any(
+    target_has_atomic = "8",
+    feature = "critical-section",
+    all(feature = "cortex-m", target_arch = "arm"),
+))]
+mod class_locked;
+#[cfg(any(
+    target_has_atomic = "8",
+    feature = "critical-section",
+    all(feature = "cortex-m", target_arch = "arm"),
+))]
+pub use self::class_locked::*;
+
+#[cfg(target_has_atomic = "ptr")]
+mod contention;
+#[cfg(target_has_atomic = "ptr")]
+pub use self::contention::ContentionStats;
+
+#[cfg(target_has_atomic = "ptr")]
+mod concurrent;
+#[cfg(target_has_atomic = "ptr")]
+pub use self::concurrent::*;
+
+#[cfg(target_has_atomic = "ptr")]
+mod epoch;
+#[cfg(target_has_atomic = "ptr")]
+pub use self::epoch::*;
+
+#[cfg(target_has_atomic = "ptr")]
+mod sharded;
+#[cfg(target_has_atomic = "ptr")]
+pub use self::sharded::*;
+
+#[cfg(target_has_atomic = "ptr")]
+mod send_guard;
+#[cfg(target_has_atomic = "ptr")]
+pub use self::send_guard::*;
+
+#[cfg(all(feature = "test-utils", target_has_atomic = "ptr"))]
+mod fault_injection;
+#[cfg(all(feature = "test-utils", target_has_atomic = "ptr"))]
+pub use self::fault_injection::{FaultInjector, FaultPolicy};
+
 if_supported_target! { mod global; }
 if_supported_target! { pub use self::global::*; }
 
@@ -0,0 +1,303 @@
+//! `MteTlsf`: a [`Tlsf`] tagging each allocation with the Arm Memory Tagging
+//! Extension
+use core::{alloc::Layout, arch::asm, fmt, ptr::NonNull};
+
+use crate::{
+    corruption::{report_corruption, HeapError},
+    int::BinInteger,
+    Tlsf,
+};
+
+/// The size of a tag granule.
+const TAG_GRANULE: usize = 16;
+
+/// The bit position of the logical address tag in a pointer.
+const TAG_SHIFT: u32 = 56;
+
+/// The tag of the memory not owned by any allocation, including the memory
+/// blocks' headers. Never assigned to an allocation.
+const FREE_TAG: u64 = 0;
+
+/// [`Tlsf`] that tags the memory of each allocation with a random tag using
+/// the Arm Memory Tagging Extension (MTE) and returns pointers carrying the
+/// same tag, so that the hardware catches use-after-free and buffer overflows.
+///
+/// Every allocation is rounded up to whole 16-byte tag granules and is given
+/// a fresh nonzero tag. Deallocation retags its granules with tag 0, which is
+/// also the tag of the allocator's own metadata. As a result, an access
+/// through a dangling pointer or past the end of an allocation (into a
+/// neighbor or a block header) faults with a tag check fault, with the
+/// exception of the padding in the last granule and accidental tag
+/// collisions (one in 15). [`Self::deallocate`] and [`Self::reallocate`]
+/// panic if the pointer's tag doesn't match the memory, which catches double
+/// frees.
+///
+/// The caller is responsible for setting up MTE:
+///
+///  - The memory pools must be mapped with tagging enabled (e.g.,
+///    `mmap(..., PROT_READ | PROT_WRITE | PROT_MTE, ...)` on Linux) and have
+///    tag 0 initially.
+///  - Tag checking must be enabled for the current thread (e.g.,
+///    `prctl(PR_SET_TAGGED_ADDR_CTRL, PR_TAGGED_ADDR_ENABLE | PR_MTE_TCF_SYNC
+///    | (0xfffe << PR_MTE_TAG_SHIFT), 0, 0, 0)` on Linux). Tag 0 should be
+///    excluded from the random tag generation, though `self` doesn't rely on
+///    it.
+///  - The memory blocks must be accessed only through the pointers returned
+///    by `self`. Pointers into the pools obtained in other ways carry tag 0
+///    and fault on access to allocated memory.
+///
+/// Allocation, deallocation, and reallocation take time proportional to the
+/// allocation size.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use rlsf::MteTlsf;
+/// use std::alloc::Layout;
+///
+/// let mut tlsf: MteTlsf<'_, u16, u16, 12, 16> = MteTlsf::new();
+/// // `pool` is mapped with `PROT_MTE`
+/// tlsf.tlsf().insert_free_block(pool);
+///
+/// let ptr = tlsf.allocate(Layout::new::<[u8; 16]>()).unwrap();
+/// unsafe {
+///     tlsf.deallocate(ptr, 1);
+///
+///     // Raises `SIGSEGV` (`SEGV_MTESERR`)
+///     ptr.as_ptr().write(0);
+/// }
+/// ```
+#[cfg_attr(
+    feature = "doc_cfg",
+    doc(cfg(all(feature = "mte", target_arch = "aarch64")))
+)]
+pub struct MteTlsf<'pool, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize> {
+    tlsf: Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>,
+}
+
+impl<FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, const SLLEN: usize> Default
+    for MteTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, const SLLEN: usize>
+    MteTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    /// Construct an empty instance of `Self`.
+    #[inline]
+    pub const fn new() -> Self {
+        Self { tlsf: Tlsf::new() }
+    }
+
+    /// Get the underlying [`Tlsf`], e.g., to supply memory pools.
+    ///
+    /// The memory blocks allocated through it directly aren't tagged and must
+    /// not be passed to the methods of `self`.
+    #[inline]
+    pub fn tlsf(&mut self) -> &mut Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN> {
+        &mut self.tlsf
+    }
+
+    /// Get the layout of the underlying allocation for an allocation of
+    /// `layout`, which covers whole tag granules.
+    #[inline]
+    fn inner_layout(layout: Layout) -> Option<Layout> {
+        let size = layout.size().checked_add(TAG_GRANULE - 1)? & !(TAG_GRANULE - 1);
+        Layout::from_size_align(size, layout.align().max(TAG_GRANULE)).ok()
+    }
+
+    /// Attempt to allocate a block of memory with a fresh tag.
+    ///
+    /// Returns the tagged starting address of the allocated memory block on
+    /// success; `None` otherwise.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(layout.size())`).
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let inner_layout = Self::inner_layout(layout)?;
+        let ptr = self.tlsf.allocate(inner_layout)?;
+        // Safety: The allocation covers `inner_layout.size()` bytes of tagged
+        //         memory
+        Some(unsafe { tag_new(ptr, inner_layout.size()) })
+    }
+
+    /// Retag a previously allocated memory block with tag 0 after checking the
+    /// pointer's tag. Returns the untagged pointer and the retagged length.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::deallocate`].
+    unsafe fn release_tags(ptr: NonNull<u8>, align: usize) -> (NonNull<u8>, usize) {
+        let untagged = with_tag(ptr, FREE_TAG);
+        if load_tag(untagged) != tag_of(ptr) {
+            tag_mismatch(ptr);
+        }
+
+        let len = Tlsf::<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(
+            untagged,
+            align.max(TAG_GRANULE),
+        );
+        store_tags(untagged, len);
+        (untagged, len)
+    }
+
+    /// Deallocate a previously allocated memory block, retagging its memory
+    /// so that further accesses through `ptr` fault.
+    ///
+    /// Panics if the tag of `ptr` doesn't match the memory, e.g., because the
+    /// memory block has already been deallocated.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(size)`).
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `self`.
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `align`.
+    ///
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, align: usize) {
+        let (untagged, _) = Self::release_tags(ptr, align);
+        self.tlsf.deallocate(untagged, align.max(TAG_GRANULE));
+    }
+
+    /// Shrink or grow a previously allocated memory block, giving it a fresh
+    /// tag.
+    ///
+    /// Returns the new tagged starting address of the memory block on
+    /// success; `None` otherwise. Panics like [`Self::deallocate`].
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(old_size + new_size)`).
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `self`.
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `new_layout`.
+    ///
+    pub unsafe fn reallocate(
+        &mut self,
+        ptr: NonNull<u8>,
+        new_layout: Layout,
+    ) -> Option<NonNull<u8>> {
+        let inner_layout = Self::inner_layout(new_layout)?;
+
+        // The underlying allocator accesses the memory block and its
+        // surroundings with tag 0
+        let (untagged, old_len) = Self::release_tags(ptr, new_layout.align());
+        match self.tlsf.reallocate(untagged, inner_layout) {
+            Some(new_ptr) => Some(tag_new(new_ptr, inner_layout.size())),
+            None => {
+                // The original allocation is still live
+                store_tags(ptr, old_len);
+                None
+            }
+        }
+    }
+}
+
+impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize> fmt::Debug
+    for MteTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MteTlsf").finish_non_exhaustive()
+    }
+}
+
+/// Get the logical address tag of `ptr`.
+#[inline]
+fn tag_of(ptr: NonNull<u8>) -> u64 {
+    (ptr.as_ptr() as usize as u64 >> TAG_SHIFT) & 0xf
+}
+
+/// Replace the logical address tag of `ptr` with `tag`.
+#[inline]
+fn with_tag(ptr: NonNull<u8>, tag: u64) -> NonNull<u8> {
+    let addr = ptr.as_ptr() as usize as u64;
+    let addr = (addr & !(0xf << TAG_SHIFT)) | (tag << TAG_SHIFT);
+    // Safety: Only the tag bits have changed, which doesn't make it null
+    unsafe { NonNull::new_unchecked(addr as usize as *mut u8) }
+}
+
+/// Give the granules in `ptr..ptr + len` a random nonzero tag. Returns `ptr`
+/// with the tag.
+///
+/// # Safety
+///
+/// `ptr..ptr + len` must be a range of tagged memory owned by the caller.
+/// `ptr` and `len` must be multiples of [`TAG_GRANULE`].
+#[inline]
+unsafe fn tag_new(ptr: NonNull<u8>, len: usize) -> NonNull<u8> {
+    let tagged = random_tag(ptr, 1 << FREE_TAG);
+    store_tags(tagged, len);
+    tagged
+}
+
+/// Get `ptr` with a random logical address tag not in `exclude` (a bit mask
+/// of tags).
+#[inline]
+fn random_tag(ptr: NonNull<u8>, exclude: u64) -> NonNull<u8> {
+    // Safety: `irg` only computes a value
+    unsafe { irg(ptr.as_ptr(), exclude) }
+}
+
+#[target_feature(enable = "mte")]
+unsafe fn irg(ptr: *mut u8, exclude: u64) -> NonNull<u8> {
+    let out: usize;
+    asm!(
+        "irg {out}, {ptr}, {exclude}",
+        out = lateout(reg) out,
+        ptr = in(reg) ptr as usize,
+        exclude = in(reg) exclude,
+        options(nomem, nostack, preserves_flags),
+    );
+    // Safety: `irg` only changes the tag bits
+    NonNull::new_unchecked(out as *mut u8)
+}
+
+/// Set the allocation tags of the granules in `ptr..ptr + len` to the
+/// logical address tag of `ptr`.
+///
+/// # Safety
+///
+/// See [`tag_new`].
+#[target_feature(enable = "mte")]
+unsafe fn store_tags(ptr: NonNull<u8>, len: usize) {
+    let mut cursor = ptr.as_ptr();
+    let end = cursor.add(len);
+    while cursor < end {
+        asm!("stg {0}, [{0}]", in(reg) cursor, options(nostack, preserves_flags));
+        cursor = cursor.add(TAG_GRANULE);
+    }
+}
+
+/// Get the allocation tag of the granule containing `ptr`.
+///
+/// # Safety
+///
+/// `ptr` must point to tagged memory.
+#[target_feature(enable = "mte")]
+unsafe fn load_tag(ptr: NonNull<u8>) -> u64 {
+    let mut out = ptr.as_ptr();
+    asm!(
+        "ldg {0}, [{0}]",
+        inout(reg) out,
+        options(readonly, nostack, preserves_flags),
+    );
+    tag_of(NonNull::new_unchecked(out))
+}
+
+/// Report a pointer whose tag doesn't match the memory.
+#[cold]
+#[inline(never)]
+fn tag_mismatch(ptr: NonNull<u8>) -> ! {
+    report_corruption(HeapError::TagMismatch { ptr })
+}
@@ -0,0 +1,86 @@
+//! The heap events reported to an observer attached to a
+//! [`Tlsf`](crate::Tlsf) or [`FlexTlsf`](crate::FlexTlsf)
+use core::{alloc::Layout, fmt, ptr::NonNull};
+
+/// Receives the events of a [`Tlsf`](crate::Tlsf) or
+/// [`FlexTlsf`](crate::FlexTlsf) attached by
+/// [`Tlsf::set_observer`](crate::Tlsf::set_observer) or
+/// [`FlexTlsf::set_observer`](crate::FlexTlsf::set_observer).
+///
+/// This can be used to layer metrics, tracing, or custom policies on top of
+/// the allocator. All methods do nothing by default. They are called while
+/// the heap is borrowed mutably, so they can't call back into the heap.
+/// Since the observer is shared, it needs interior mutability (e.g.,
+/// atomics) to record anything.
+///
+/// Only the calls made to the `Tlsf` are reported. Memory blocks that
+/// `FlexTlsf` obtains directly from its source (see
+/// [`FlexTlsf::set_huge_threshold`](crate::FlexTlsf::set_huge_threshold))
+/// are not.
+///
+/// # Examples
+///
+/// ```
+/// use rlsf::{AllocObserver, Tlsf};
+/// use std::{
+///     alloc::Layout,
+///     mem::MaybeUninit,
+///     ptr::NonNull,
+///     sync::atomic::{AtomicUsize, Ordering},
+/// };
+///
+/// #[derive(Default)]
+/// struct CountAllocs(AtomicUsize);
+///
+/// impl AllocObserver for CountAllocs {
+///     fn on_alloc(&self, _ptr: NonNull<u8>, _layout: Layout) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let observer = CountAllocs::default();
+/// let mut pool = [MaybeUninit::uninit(); 1024];
+/// let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+/// tlsf.set_observer(&observer);
+/// tlsf.insert_free_block(&mut pool);
+///
+/// let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+/// assert_eq!(observer.0.load(Ordering::Relaxed), 1);
+/// # unsafe { tlsf.deallocate(ptr, 8) };
+/// ```
+pub trait AllocObserver: Sync {
+    /// Called after a successful allocation with the returned pointer and
+    /// the requested layout.
+    #[inline]
+    fn on_alloc(&self, ptr: NonNull<u8>, layout: Layout) {
+        let _ = (ptr, layout);
+    }
+
+    /// Called before a deallocation with the pointer and the usable size of
+    /// the memory block.
+    #[inline]
+    fn on_dealloc(&self, ptr: NonNull<u8>, size: usize) {
+        let _ = (ptr, size);
+    }
+
+    /// Called after a successful reallocation with the old pointer (which is
+    /// dangling if it differs from `new_ptr`), the new pointer, and the
+    /// requested layout.
+    #[inline]
+    fn on_realloc(&self, old_ptr: NonNull<u8>, new_ptr: NonNull<u8>, new_layout: Layout) {
+        let _ = (old_ptr, new_ptr, new_layout);
+    }
+
+    /// Called after a memory pool is created or an existing one is extended
+    /// with the memory region that was added.
+    #[inline]
+    fn on_pool_added(&self, pool: NonNull<[u8]>) {
+        let _ = pool;
+    }
+}
+
+impl fmt::Debug for dyn AllocObserver + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AllocObserver")
+    }
+}
@@ -0,0 +1,95 @@
+//! Synchronization primitives of the concurrent allocators
+//!
+//! Under `cfg(loom)` (e.g., `RUSTFLAGS="--cfg loom" cargo test --release
+//! --test loom`), they're replaced with [`loom`]'s so that the allocators, and
+//! wrappers built on them, can be model-checked. Since loom's atomics can't be
+//! constructed in constant contexts, the constructors of the affected types
+//! aren't `const fn` in this configuration, and [`StaticGlobalTlsfLock::INIT`]
+//! is replaced with `StaticGlobalTlsfLock::init`. The statistics counters
+//! (e.g., [`ContentionStats`]) don't synchronize anything and always use
+//! `core`'s atomics.
+//!
+//! [`loom`]: https://crates.io/crates/loom
+//! [`StaticGlobalTlsfLock::INIT`]: crate::StaticGlobalTlsfLock::INIT
+//! [`ContentionStats`]: crate::ContentionStats
+#![allow(unused_macros)]
+
+#[cfg(not(loom))]
+#[allow(unused_imports)]
+pub(crate) use core::{hint, sync::atomic};
+#[cfg(loom)]
+#[allow(unused_imports)]
+pub(crate) use loom::{hint, sync::atomic};
+
+/// Define a `const fn`, which is demoted to a non-`const` function under
+/// `cfg(loom)`.
+macro_rules! const_fn {
+    (
+        $(#[$meta:meta])*
+        $vis:vis const unsafe fn $($rest:tt)*
+    ) => {
+        #[cfg(not(loom))]
+        $(#[$meta])*
+        $vis const unsafe fn $($rest)*
+
+        #[cfg(loom)]
+        $(#[$meta])*
+        $vis unsafe fn $($rest)*
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis const fn $($rest:tt)*
+    ) => {
+        #[cfg(not(loom))]
+        $(#[$meta])*
+        $vis const fn $($rest)*
+
+        #[cfg(loom)]
+        $(#[$meta])*
+        $vis fn $($rest)*
+    };
+}
+
+/// Define [`StaticGlobalTlsfLock::INIT`] (or `StaticGlobalTlsfLock::init`
+/// under `cfg(loom)`) in an implementation of [`StaticGlobalTlsfLock`].
+///
+/// [`StaticGlobalTlsfLock`]: crate::StaticGlobalTlsfLock
+/// [`StaticGlobalTlsfLock::INIT`]: crate::StaticGlobalTlsfLock::INIT
+macro_rules! lock_init {
+    ($init:expr) => {
+        #[cfg(not(loom))]
+        #[allow(clippy::declare_interior_mutable_const)]
+        const INIT: Self = $init;
+
+        #[cfg(loom)]
+        #[inline]
+        fn init() -> Self {
+            $init
+        }
+    };
+}
+
+/// `[$init; $len]`, where `$init` names a constant. Under `cfg(loom)`,
+/// `$ctor` is called for each element instead.
+macro_rules! array_repeat {
+    ($init:path, $ctor:expr; $len:expr) => {{
+        #[cfg(not(loom))]
+        let array = [$init; $len];
+        #[cfg(loom)]
+        let array = core::array::from_fn(|_| $ctor);
+        array
+    }};
+}
+
+/// The unlocked state of `$Lock`, a [`StaticGlobalTlsfLock`].
+///
+/// [`StaticGlobalTlsfLock`]: crate::StaticGlobalTlsfLock
+macro_rules! lock_init_value {
+    ($Lock:ty) => {{
+        #[cfg(not(loom))]
+        let lock = <$Lock as crate::StaticGlobalTlsfLock>::INIT;
+        #[cfg(loom)]
+        let lock = <$Lock as crate::StaticGlobalTlsfLock>::init();
+        lock
+    }};
+}
@@ -0,0 +1,305 @@
+//! `QuarantineTlsf`: a [`Tlsf`] delaying the reuse of deallocated memory
+use core::{alloc::Layout, fmt, ptr::NonNull};
+
+use crate::{
+    corruption::{report_corruption, HeapError},
+    int::BinInteger,
+    Tlsf,
+};
+
+#[cfg(feature = "asan")]
+use crate::asan;
+
+/// The byte quarantined memory blocks are filled with. This is the same
+/// pattern as the one used by the `poison` feature.
+const QUARANTINE_BYTE: u8 = 0xdd;
+
+/// [`Tlsf`] that holds back deallocated memory blocks in a quarantine before
+/// making them reusable, for tracking down use-after-free bugs.
+///
+/// A deallocated memory block is filled with `0xdd` and stays allocated in
+/// the underlying [`Tlsf`] until `MAX_BLOCKS` subsequent deallocations have
+/// been made or the quarantined memory blocks exceed the byte budget
+/// specified by [`Self::new`], whichever comes first. This way, a dangling
+/// pointer keeps pointing to the pattern for a long time instead of an
+/// unrelated allocation. When a memory block leaves the quarantine, it's
+/// checked for modifications, and a panic is raised if any are found.
+///
+/// [`Self::reallocate`] always moves the allocation so that the old memory
+/// block is quarantined as well. When an allocation fails, the quarantine is
+/// flushed before retrying it.
+///
+/// This is a debugging aid; deallocation takes time proportional to the size
+/// of the memory block, and the quarantined memory blocks reduce the memory
+/// available for allocation.
+///
+/// # Examples
+///
+/// ```rust,should_panic
+/// use rlsf::QuarantineTlsf;
+/// use std::{alloc::Layout, mem::MaybeUninit};
+///
+/// let mut tlsf: QuarantineTlsf<'_, u16, u16, 12, 16, 8> = QuarantineTlsf::new(1024);
+/// let mut pool = [MaybeUninit::uninit(); 4096];
+/// tlsf.tlsf().insert_free_block(&mut pool);
+///
+/// let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+/// unsafe {
+///     tlsf.deallocate(ptr, 8);
+///
+///     // Use after free
+///     ptr.as_ptr().write(42);
+///
+///     // panics with "use after free detected: the memory block at 0x...
+///     // (... bytes) was modified at 0x... while in quarantine"
+///     tlsf.flush();
+/// }
+/// ```
+pub struct QuarantineTlsf<
+    'pool,
+    FLBitmap,
+    SLBitmap,
+    const FLLEN: usize,
+    const SLLEN: usize,
+    const MAX_BLOCKS: usize,
+> {
+    tlsf: Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>,
+    /// A ring buffer of the quarantined memory blocks and their alignments.
+    /// The oldest one is `blocks[head]`.
+    blocks: [(NonNull<u8>, usize); MAX_BLOCKS],
+    head: usize,
+    num_blocks: usize,
+    /// The total usable size of the quarantined memory blocks.
+    num_bytes: usize,
+    max_bytes: usize,
+}
+
+// Safety: The quarantined memory blocks are logically owned by `tlsf`
+unsafe impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, const MAX_BLOCKS: usize>
+    Send for QuarantineTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, MAX_BLOCKS>
+{
+}
+
+// Safety: `blocks` is only accessed through `&mut self`
+unsafe impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, const MAX_BLOCKS: usize>
+    Sync for QuarantineTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, MAX_BLOCKS>
+{
+}
+
+impl<
+        'pool,
+        FLBitmap: BinInteger,
+        SLBitmap: BinInteger,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        const MAX_BLOCKS: usize,
+    > QuarantineTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, MAX_BLOCKS>
+{
+    /// Construct an empty instance of `Self` whose quarantine holds up to
+    /// `MAX_BLOCKS` memory blocks totaling up to `max_bytes` bytes.
+    #[inline]
+    pub const fn new(max_bytes: usize) -> Self {
+        Self {
+            tlsf: Tlsf::new(),
+            blocks: [(NonNull::dangling(), 0); MAX_BLOCKS],
+            head: 0,
+            num_blocks: 0,
+            num_bytes: 0,
+            max_bytes,
+        }
+    }
+
+    /// Get the underlying [`Tlsf`], e.g., to supply memory pools.
+    ///
+    /// The memory blocks deallocated through it directly aren't quarantined.
+    #[inline]
+    pub fn tlsf(&mut self) -> &mut Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN> {
+        &mut self.tlsf
+    }
+
+    /// Get the number of the quarantined memory blocks.
+    #[inline]
+    pub fn num_quarantined_blocks(&self) -> usize {
+        self.num_blocks
+    }
+
+    /// Get the total usable size of the quarantined memory blocks, in bytes.
+    #[inline]
+    pub fn num_quarantined_bytes(&self) -> usize {
+        self.num_bytes
+    }
+
+    /// Attempt to allocate a block of memory, flushing the quarantine and
+    /// retrying if it fails.
+    ///
+    /// Returns the starting address of the allocated memory block on success;
+    /// `None` otherwise.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in constant time if it doesn't flush the
+    /// quarantine.
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        if let Some(ptr) = self.tlsf.allocate(layout) {
+            return Some(ptr);
+        }
+        if self.num_blocks == 0 {
+            return None;
+        }
+        self.flush();
+        self.tlsf.allocate(layout)
+    }
+
+    /// Quarantine a previously allocated memory block, releasing the oldest
+    /// quarantined memory blocks as needed to stay within the limits.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(size)`, where `size` is
+    /// the total size of the memory blocks involved).
+    ///
+    /// # Panics
+    ///
+    /// Panics if a memory block being released from the quarantine has been
+    /// modified.
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `self`.
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `align`.
+    ///
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, align: usize) {
+        // Safety: Upheld by the caller
+        let size = Tlsf::<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(ptr, align);
+
+        if MAX_BLOCKS == 0 || size > self.max_bytes {
+            // It would be released immediately anyway
+            self.tlsf.deallocate(ptr, align);
+            return;
+        }
+
+        ptr.as_ptr().write_bytes(QUARANTINE_BYTE, size);
+
+        // Make AddressSanitizer report any access while in quarantine
+        #[cfg(feature = "asan")]
+        asan::poison(ptr.as_ptr(), ptr.as_ptr().add(size));
+
+        while self.num_blocks == MAX_BLOCKS || size > self.max_bytes - self.num_bytes {
+            self.release_oldest();
+        }
+
+        self.blocks[(self.head + self.num_blocks) % MAX_BLOCKS] = (ptr, align);
+        self.num_blocks += 1;
+        self.num_bytes += size;
+    }
+
+    /// Shrink or grow a previously allocated memory block by moving it to a
+    /// new memory block and quarantining the old one.
+    ///
+    /// Returns the new starting address of the memory block on success;
+    /// `None` otherwise.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(old_size)`).
+    ///
+    /// # Panics
+    ///
+    /// See [`Self::deallocate`].
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `self`.
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `new_layout`.
+    ///
+    pub unsafe fn reallocate(
+        &mut self,
+        ptr: NonNull<u8>,
+        new_layout: Layout,
+    ) -> Option<NonNull<u8>> {
+        // Safety: Upheld by the caller
+        let old_size = Tlsf::<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(
+            ptr,
+            new_layout.align(),
+        );
+
+        let new_ptr = self.allocate(new_layout)?;
+        core::ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr(),
+            old_size.min(new_layout.size()),
+        );
+        self.deallocate(ptr, new_layout.align());
+        Some(new_ptr)
+    }
+
+    /// Release all quarantined memory blocks.
+    ///
+    /// # Panics
+    ///
+    /// See [`Self::deallocate`].
+    pub fn flush(&mut self) {
+        while self.num_blocks != 0 {
+            self.release_oldest();
+        }
+    }
+
+    /// Check and deallocate the oldest quarantined memory block.
+    fn release_oldest(&mut self) {
+        debug_assert_ne!(self.num_blocks, 0);
+        let (ptr, align) = self.blocks[self.head];
+        self.head = (self.head + 1) % MAX_BLOCKS;
+        self.num_blocks -= 1;
+
+        // Safety: `ptr` is a quarantined memory block allocated with `align`,
+        //         and no one is supposed to access it
+        unsafe {
+            let size =
+                Tlsf::<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(ptr, align);
+            self.num_bytes -= size;
+
+            #[cfg(feature = "asan")]
+            asan::unpoison(ptr.as_ptr(), ptr.as_ptr().add(size));
+
+            let mut cursor = ptr.as_ptr();
+            let end = cursor.add(size);
+            while cursor < end {
+                if *cursor != QUARANTINE_BYTE {
+                    quarantine_violation(ptr, size, cursor);
+                }
+                cursor = cursor.add(1);
+            }
+
+            self.tlsf.deallocate(ptr, align);
+        }
+    }
+}
+
+impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, const MAX_BLOCKS: usize> fmt::Debug
+    for QuarantineTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, MAX_BLOCKS>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuarantineTlsf")
+            .field("num_blocks", &self.num_blocks)
+            .field("num_bytes", &self.num_bytes)
+            .field("max_bytes", &self.max_bytes)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Report a modified quarantined memory block.
+#[cold]
+#[inline(never)]
+fn quarantine_violation(ptr: NonNull<u8>, size: usize, at: *const u8) -> ! {
+    report_corruption(HeapError::QuarantineViolation {
+        ptr,
+        size,
+        // Safety: `at` points to the memory block
+        at: unsafe { NonNull::new_unchecked(at as *mut u8) },
+    })
+}
+
+#[cfg(test)]
+mod tests;
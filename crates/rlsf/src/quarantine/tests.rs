@@ -0,0 +1,153 @@
+use core::mem::MaybeUninit;
+use std::{prelude::v1::*, vec};
+
+use super::*;
+
+type TheQuarantineTlsf = QuarantineTlsf<'static, u16, u16, 12, 16, 4>;
+
+fn new_pool(size: usize) -> &'static mut [MaybeUninit<u8>] {
+    Box::leak(vec![MaybeUninit::uninit(); size].into_boxed_slice())
+}
+
+fn new_tlsf(max_bytes: usize) -> TheQuarantineTlsf {
+    let mut tlsf = TheQuarantineTlsf::new(max_bytes);
+    tlsf.tlsf().insert_free_block(new_pool(65536));
+    tlsf
+}
+
+#[test]
+fn held_back_for_max_blocks() {
+    let mut tlsf = new_tlsf(usize::MAX);
+    let layout = Layout::new::<[u8; 64]>();
+    let ptr = tlsf.allocate(layout).unwrap();
+    unsafe { tlsf.deallocate(ptr, 1) };
+    assert_eq!(tlsf.num_quarantined_blocks(), 1);
+
+    // `ptr` isn't reused while it's in the quarantine
+    for _ in 0..3 {
+        let other = tlsf.allocate(layout).unwrap();
+        assert_ne!(other, ptr);
+        unsafe { tlsf.deallocate(other, 1) };
+    }
+    assert_eq!(tlsf.num_quarantined_blocks(), 4);
+
+    // The fifth deallocation releases `ptr`
+    let other = tlsf.allocate(layout).unwrap();
+    unsafe { tlsf.deallocate(other, 1) };
+    assert_eq!(tlsf.num_quarantined_blocks(), 4);
+
+    tlsf.flush();
+    assert_eq!(tlsf.num_quarantined_blocks(), 0);
+    assert_eq!(tlsf.num_quarantined_bytes(), 0);
+}
+
+#[test]
+fn held_back_for_max_bytes() {
+    let mut tlsf = new_tlsf(200);
+    let layout = Layout::new::<[u8; 64]>();
+    let ptrs: Vec<_> = (0..4).map(|_| tlsf.allocate(layout).unwrap()).collect();
+    for &ptr in &ptrs {
+        unsafe { tlsf.deallocate(ptr, 1) };
+        assert!(tlsf.num_quarantined_bytes() <= 200);
+    }
+    assert!(tlsf.num_quarantined_blocks() < 4);
+    assert!(tlsf.num_quarantined_blocks() >= 2);
+
+    // Too large to be quarantined
+    let ptr = tlsf.allocate(Layout::new::<[u8; 256]>()).unwrap();
+    let num_blocks = tlsf.num_quarantined_blocks();
+    unsafe { tlsf.deallocate(ptr, 1) };
+    assert_eq!(tlsf.num_quarantined_blocks(), num_blocks);
+}
+
+#[test]
+fn no_quarantine() {
+    let mut tlsf: QuarantineTlsf<'static, u16, u16, 12, 16, 0> = QuarantineTlsf::new(1024);
+    tlsf.tlsf().insert_free_block(new_pool(65536));
+    let layout = Layout::new::<u64>();
+    let ptr = tlsf.allocate(layout).unwrap();
+    unsafe { tlsf.deallocate(ptr, 8) };
+    assert_eq!(tlsf.num_quarantined_blocks(), 0);
+    assert_eq!(tlsf.allocate(layout), Some(ptr));
+}
+
+// AddressSanitizer would abort on the use-after-free write
+#[cfg(not(feature = "asan"))]
+#[test]
+fn use_after_free() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let mut tlsf = new_tlsf(usize::MAX);
+    let ptr = tlsf.allocate(Layout::new::<[u8; 64]>()).unwrap();
+    unsafe {
+        tlsf.deallocate(ptr, 1);
+        ptr.as_ptr().add(10).write(42);
+    }
+
+    let payload = catch_unwind(AssertUnwindSafe(|| tlsf.flush())).unwrap_err();
+    let message = payload.downcast_ref::<String>().unwrap();
+    assert!(
+        message.starts_with("use after free detected: the memory block at"),
+        "{:?}",
+        message
+    );
+    assert!(message.contains(&std::format!("{:p}", unsafe { ptr.as_ptr().add(10) })));
+}
+
+#[cfg(feature = "asan")]
+#[test]
+fn asan_poisoned() {
+    let mut tlsf = new_tlsf(usize::MAX);
+    let ptr = tlsf.allocate(Layout::new::<[u8; 64]>()).unwrap();
+    assert!(!crate::asan::is_poisoned(ptr.as_ptr()));
+    unsafe { tlsf.deallocate(ptr, 1) };
+    assert!(crate::asan::is_poisoned(ptr.as_ptr()));
+    assert!(crate::asan::is_poisoned(unsafe { ptr.as_ptr().add(63) }));
+    tlsf.flush();
+}
+
+#[test]
+fn reallocate_moves() {
+    let mut tlsf = new_tlsf(usize::MAX);
+    let ptr = tlsf.allocate(Layout::new::<[u8; 64]>()).unwrap();
+    unsafe {
+        for i in 0..64 {
+            ptr.as_ptr().add(i).write(i as u8);
+        }
+
+        // Even shrinking moves the allocation
+        let new_ptr = tlsf.reallocate(ptr, Layout::new::<[u8; 32]>()).unwrap();
+        assert_ne!(new_ptr, ptr);
+        assert_eq!(tlsf.num_quarantined_blocks(), 1);
+        for i in 0..32 {
+            assert_eq!(*new_ptr.as_ptr().add(i), i as u8);
+        }
+
+        let new_ptr = tlsf
+            .reallocate(new_ptr, Layout::new::<[u8; 128]>())
+            .unwrap();
+        for i in 0..32 {
+            assert_eq!(*new_ptr.as_ptr().add(i), i as u8);
+        }
+        tlsf.deallocate(new_ptr, 1);
+    }
+    tlsf.flush();
+}
+
+#[test]
+fn allocation_failure_flushes() {
+    let mut tlsf = new_tlsf(usize::MAX);
+    let layout = Layout::new::<[u8; 16384]>();
+    let mut ptrs = Vec::new();
+    while let Some(ptr) = tlsf.tlsf().allocate(layout) {
+        ptrs.push(ptr);
+    }
+    for ptr in ptrs {
+        unsafe { tlsf.deallocate(ptr, 1) };
+    }
+    assert_ne!(tlsf.num_quarantined_blocks(), 0);
+
+    // The quarantined memory blocks are released to satisfy this
+    assert!(tlsf.allocate(layout).is_some());
+    assert_eq!(tlsf.num_quarantined_blocks(), 0);
+}
@@ -0,0 +1,308 @@
+//! `RedzoneTlsf`: a [`Tlsf`] surrounding each allocation with canaries
+use core::{alloc::Layout, fmt, mem, ptr::NonNull};
+
+use crate::{
+    corruption::{report_corruption, HeapError},
+    int::BinInteger,
+    Tlsf,
+};
+
+#[cfg(feature = "asan")]
+use crate::asan;
+
+/// The byte the redzones are filled with.
+const REDZONE_BYTE: u8 = 0xfd;
+
+/// The length of the canary part of each redzone.
+const REDZONE_LEN: usize = 16;
+
+/// [`Tlsf`] that places a redzone (a region filled with a known pattern)
+/// before and after each allocation and checks them on deallocation, for
+/// tracking down heap buffer overflows.
+///
+/// Each allocation is enlarged by the redzones, and the requested size is
+/// recorded in the redzone before it. [`Self::deallocate`] and
+/// [`Self::reallocate`] panic with the allocation's address and size if
+/// either redzone has been modified. [`Self::check`] does the same without
+/// deallocating the memory block.
+///
+/// This is a debugging aid; the redzones make every allocation at least
+/// `size_of::<usize>() + 32` bytes larger, and allocation and deallocation
+/// take time proportional to the alignment.
+///
+/// # Examples
+///
+/// ```rust,should_panic
+/// use rlsf::RedzoneTlsf;
+/// use std::{alloc::Layout, mem::MaybeUninit};
+///
+/// let mut tlsf: RedzoneTlsf<'_, u16, u16, 12, 16> = RedzoneTlsf::new();
+/// let mut pool = [MaybeUninit::uninit(); 4096];
+/// tlsf.tlsf().insert_free_block(&mut pool);
+///
+/// let ptr = tlsf.allocate(Layout::new::<[u8; 10]>()).unwrap();
+/// unsafe {
+///     // Off-by-one write
+///     ptr.as_ptr().add(10).write(0);
+///
+///     // panics with "the redzone after the 10-byte allocation at 0x... was
+///     // modified at 0x..."
+///     tlsf.deallocate(ptr, 1);
+/// }
+/// ```
+pub struct RedzoneTlsf<'pool, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize> {
+    tlsf: Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>,
+}
+
+impl<FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, const SLLEN: usize> Default
+    for RedzoneTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, const SLLEN: usize>
+    RedzoneTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    /// Construct an empty instance of `Self`.
+    #[inline]
+    pub const fn new() -> Self {
+        Self { tlsf: Tlsf::new() }
+    }
+
+    /// Get the underlying [`Tlsf`], e.g., to supply memory pools.
+    ///
+    /// The memory blocks allocated through it directly don't have redzones
+    /// and must not be passed to the methods of `self`.
+    #[inline]
+    pub fn tlsf(&mut self) -> &mut Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN> {
+        &mut self.tlsf
+    }
+
+    /// Get the distance from the start of the underlying allocation to the
+    /// payload. It must be a multiple of `align` and be able to accommodate
+    /// the recorded size and a canary.
+    #[inline]
+    fn front_len(align: usize) -> usize {
+        (mem::size_of::<usize>() + REDZONE_LEN + align - 1) & !(align - 1)
+    }
+
+    /// Get the layout of the underlying allocation for an allocation of
+    /// `layout`.
+    #[inline]
+    fn inner_layout(layout: Layout) -> Option<Layout> {
+        let size = Self::front_len(layout.align())
+            .checked_add(layout.size())?
+            .checked_add(REDZONE_LEN)?;
+        let align = layout.align().max(mem::align_of::<usize>());
+        Layout::from_size_align(size, align).ok()
+    }
+
+    /// Fill the redzones of the allocation `ptr` of `size` bytes.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a memory block allocated via `self` with enough room
+    /// for the redzones.
+    #[inline]
+    unsafe fn init_redzones(ptr: NonNull<u8>, size: usize, align: usize) {
+        let front_len = Self::front_len(align);
+        let inner = ptr.as_ptr().sub(front_len);
+        inner.cast::<usize>().write(size);
+        let canary = inner.add(mem::size_of::<usize>());
+        canary.write_bytes(REDZONE_BYTE, front_len - mem::size_of::<usize>());
+        ptr.as_ptr()
+            .add(size)
+            .write_bytes(REDZONE_BYTE, REDZONE_LEN);
+
+        #[cfg(feature = "asan")]
+        Self::asan_poison_redzones(ptr, size, align);
+    }
+
+    /// Make the canaries of the allocation `ptr` of `size` bytes inaccessible
+    /// to AddressSanitizer, so that it reports overflows as they happen. The
+    /// recorded size stays accessible.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::init_redzones`].
+    #[cfg(feature = "asan")]
+    #[inline]
+    unsafe fn asan_poison_redzones(ptr: NonNull<u8>, size: usize, align: usize) {
+        let inner = ptr.as_ptr().sub(Self::front_len(align));
+        asan::poison(inner.add(mem::size_of::<usize>()), ptr.as_ptr());
+        let back = ptr.as_ptr().add(size);
+        asan::poison(back, back.add(REDZONE_LEN));
+    }
+
+    /// Attempt to allocate a block of memory surrounded by redzones.
+    ///
+    /// Returns the starting address of the allocated memory block on success;
+    /// `None` otherwise.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(layout.align())`).
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let inner = self.tlsf.allocate(Self::inner_layout(layout)?)?;
+        unsafe {
+            // Safety: The allocation is large enough to contain the redzones
+            let ptr = NonNull::new_unchecked(inner.as_ptr().add(Self::front_len(layout.align())));
+            Self::init_redzones(ptr, layout.size(), layout.align());
+            Some(ptr)
+        }
+    }
+
+    /// Check the redzones of a previously allocated memory block, panicking
+    /// with the allocation's address and size if they have been modified.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(align)`).
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `self`.
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `align`.
+    ///
+    pub unsafe fn check(&self, ptr: NonNull<u8>, align: usize) {
+        let _size = Self::check_and_unpoison(ptr, align);
+
+        #[cfg(feature = "asan")]
+        Self::asan_poison_redzones(ptr, _size, align);
+    }
+
+    /// Check the redzones like [`Self::check`], leaving them accessible to
+    /// AddressSanitizer. Returns the recorded size.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::check`].
+    unsafe fn check_and_unpoison(ptr: NonNull<u8>, align: usize) -> usize {
+        let front_len = Self::front_len(align);
+        let inner = NonNull::new_unchecked(ptr.as_ptr().sub(front_len));
+        let size = inner.cast::<usize>().as_ptr().read();
+
+        // If the recorded size is corrupted, the tail redzone's location is
+        // unknown
+        let capacity = Tlsf::<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(
+            inner,
+            align.max(mem::align_of::<usize>()),
+        );
+        let valid_size = size
+            .checked_add(front_len + REDZONE_LEN)
+            .map_or(false, |len| len <= capacity);
+        if !valid_size {
+            redzone_violation(ptr, None, inner.as_ptr());
+        }
+
+        let front = inner.as_ptr().add(mem::size_of::<usize>())..ptr.as_ptr();
+        let back = ptr.as_ptr().add(size)..ptr.as_ptr().add(size + REDZONE_LEN);
+        for (is_back, range) in [(false, front), (true, back)] {
+            #[cfg(feature = "asan")]
+            asan::unpoison(range.start, range.end);
+
+            let mut cursor = range.start;
+            while cursor < range.end {
+                if *cursor != REDZONE_BYTE {
+                    redzone_violation(ptr, Some((size, is_back)), cursor);
+                }
+                cursor = cursor.add(1);
+            }
+        }
+
+        size
+    }
+
+    /// Deallocate a previously allocated memory block after checking its
+    /// redzones (see [`Self::check`]).
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(align)`).
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `self`.
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `align`.
+    ///
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, align: usize) {
+        Self::check_and_unpoison(ptr, align);
+        let inner = NonNull::new_unchecked(ptr.as_ptr().sub(Self::front_len(align)));
+        self.tlsf
+            .deallocate(inner, align.max(mem::align_of::<usize>()));
+    }
+
+    /// Shrink or grow a previously allocated memory block after checking its
+    /// redzones (see [`Self::check`]).
+    ///
+    /// Returns the new starting address of the memory block on success;
+    /// `None` otherwise.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(old_size + align)`).
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `self`.
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `new_layout`.
+    ///
+    pub unsafe fn reallocate(
+        &mut self,
+        ptr: NonNull<u8>,
+        new_layout: Layout,
+    ) -> Option<NonNull<u8>> {
+        let _old_size = Self::check_and_unpoison(ptr, new_layout.align());
+        let front_len = Self::front_len(new_layout.align());
+        let inner = NonNull::new_unchecked(ptr.as_ptr().sub(front_len));
+        let new_inner = Self::inner_layout(new_layout)
+            .and_then(|inner_layout| self.tlsf.reallocate(inner, inner_layout));
+        let new_inner = match new_inner {
+            Some(new_inner) => new_inner,
+            None => {
+                // The original allocation is still live
+                #[cfg(feature = "asan")]
+                Self::asan_poison_redzones(ptr, _old_size, new_layout.align());
+                return None;
+            }
+        };
+        let new_ptr = NonNull::new_unchecked(new_inner.as_ptr().add(front_len));
+        Self::init_redzones(new_ptr, new_layout.size(), new_layout.align());
+        Some(new_ptr)
+    }
+}
+
+impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize> fmt::Debug
+    for RedzoneTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedzoneTlsf").finish_non_exhaustive()
+    }
+}
+
+/// Report a modified redzone. `size_and_is_back` is `None` if the recorded
+/// size is corrupted.
+#[cold]
+#[inline(never)]
+fn redzone_violation(
+    ptr: NonNull<u8>,
+    size_and_is_back: Option<(usize, bool)>,
+    at: *const u8,
+) -> ! {
+    report_corruption(HeapError::RedzoneViolation {
+        ptr,
+        size: size_and_is_back.map(|(size, _)| size),
+        after: size_and_is_back.map_or(false, |(_, is_back)| is_back),
+        // Safety: `at` points to a redzone
+        at: unsafe { NonNull::new_unchecked(at as *mut u8) },
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,124 @@
+use core::mem::MaybeUninit;
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    prelude::v1::*,
+    vec,
+};
+
+use super::*;
+
+type TheRedzoneTlsf = RedzoneTlsf<'static, u16, u16, 12, 16>;
+
+fn new_pool(size: usize) -> &'static mut [MaybeUninit<u8>] {
+    Box::leak(vec![MaybeUninit::uninit(); size].into_boxed_slice())
+}
+
+fn new_tlsf() -> TheRedzoneTlsf {
+    let mut tlsf = TheRedzoneTlsf::new();
+    tlsf.tlsf().insert_free_block(new_pool(65536));
+    tlsf
+}
+
+/// Get the panic message of `f`, or `None` if it doesn't panic.
+fn panic_message(f: impl FnOnce()) -> Option<String> {
+    let payload = catch_unwind(AssertUnwindSafe(f)).err()?;
+    Some(
+        payload
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_default(),
+    )
+}
+
+#[test]
+fn intact() {
+    let mut tlsf = new_tlsf();
+    for &(size, align) in &[(0, 1), (1, 1), (10, 1), (100, 8), (100, 64), (4000, 256)] {
+        let layout = Layout::from_size_align(size, align).unwrap();
+        let ptr = tlsf.allocate(layout).unwrap();
+        assert_eq!(ptr.as_ptr() as usize % align, 0);
+        unsafe {
+            // The whole payload is writable
+            ptr.as_ptr().write_bytes(0x5a, size);
+            tlsf.check(ptr, align);
+
+            // Grow and shrink
+            let new_layout = Layout::from_size_align(size * 2 + 1, align).unwrap();
+            let ptr = tlsf.reallocate(ptr, new_layout).unwrap();
+            assert!((0..size).all(|i| *ptr.as_ptr().add(i) == 0x5a));
+            ptr.as_ptr().write_bytes(0x5a, new_layout.size());
+            let ptr = tlsf.reallocate(ptr, layout).unwrap();
+            assert!((0..size).all(|i| *ptr.as_ptr().add(i) == 0x5a));
+
+            tlsf.deallocate(ptr, align);
+        }
+    }
+}
+
+// AddressSanitizer would abort on the write to the redzone
+#[cfg(not(feature = "asan"))]
+#[test]
+fn overflow() {
+    for &align in &[1, 8, 64] {
+        let mut tlsf = new_tlsf();
+        let layout = Layout::from_size_align(10, align).unwrap();
+        let ptr = tlsf.allocate(layout).unwrap();
+        unsafe { ptr.as_ptr().add(10).write(0) };
+
+        let message = panic_message(|| unsafe { tlsf.deallocate(ptr, align) }).unwrap();
+        log::debug!("{}", message);
+        assert!(message.contains("after the 10-byte allocation"));
+        assert!(message.contains(&std::format!("{:p}", ptr)));
+    }
+}
+
+// AddressSanitizer would abort on the write to the redzone
+#[cfg(not(feature = "asan"))]
+#[test]
+fn underflow() {
+    let mut tlsf = new_tlsf();
+    let layout = Layout::from_size_align(10, 8).unwrap();
+    let ptr = tlsf.allocate(layout).unwrap();
+    unsafe { ptr.as_ptr().sub(1).write(0) };
+
+    let message = panic_message(|| unsafe { tlsf.check(ptr, 8) }).unwrap();
+    assert!(message.contains("before the 10-byte allocation"));
+}
+
+#[cfg(feature = "asan")]
+#[test]
+fn asan_poisoned() {
+    let mut tlsf = new_tlsf();
+    let layout = Layout::from_size_align(10, 8).unwrap();
+    let ptr = tlsf.allocate(layout).unwrap();
+    unsafe {
+        assert!(!crate::asan::is_poisoned(ptr.as_ptr().add(9)));
+        assert!(crate::asan::is_poisoned(ptr.as_ptr().add(10)));
+        assert!(crate::asan::is_poisoned(ptr.as_ptr().sub(1)));
+
+        // The redzones stay inaccessible after the check
+        tlsf.check(ptr, 8);
+        assert!(crate::asan::is_poisoned(ptr.as_ptr().add(10)));
+
+        tlsf.deallocate(ptr, 8);
+    }
+}
+
+#[test]
+fn corrupted_size() {
+    let mut tlsf = new_tlsf();
+    let layout = Layout::from_size_align(10, 8).unwrap();
+    let ptr = tlsf.allocate(layout).unwrap();
+    unsafe {
+        ptr.as_ptr()
+            .sub(TheRedzoneTlsf::front_len(8))
+            .cast::<usize>()
+            .write(usize::MAX)
+    };
+
+    let message = panic_message(|| unsafe {
+        tlsf.reallocate(ptr, layout).unwrap();
+    })
+    .unwrap();
+    assert!(message.contains("recorded size is invalid"));
+}
@@ -0,0 +1,202 @@
+//! `TlsfSendGuard`: moving a heap and its allocations between threads
+use core::{
+    alloc::Layout,
+    fmt,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ptr::{self, NonNull},
+    slice,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{int::BinInteger, Tlsf};
+
+/// The identifier of the next [`TlsfSendGuard`]
+static NEXT_GUARD_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A [`Tlsf`] that hands out [`TlsfToken`]s instead of pointers, for programs
+/// that build a heap on one thread and use it on another in a later phase.
+///
+/// [`Tlsf`] itself is `Send`, but the pointers to its allocations aren't, so
+/// a structure holding a heap together with its live allocations can't be
+/// moved to another thread. `TlsfSendGuard` takes ownership of a `Tlsf`, and
+/// its allocations are identified by tokens that give access to their memory
+/// only through the guard that issued them ([`Self::get`] and
+/// [`Self::get_mut`]). The guard and the tokens are `Send`, and the borrow
+/// checker ensures that the memory is accessed by the guard's current owner
+/// only. Using a token with another guard panics.
+///
+/// # Examples
+///
+/// ```rust
+/// use rlsf::{Tlsf, TlsfSendGuard, TlsfToken};
+/// use std::{alloc::Layout, mem::MaybeUninit};
+///
+/// struct Heap {
+///     guard: TlsfSendGuard<'static, u8, u8, 8, 8>,
+///     live: Vec<TlsfToken<'static>>,
+/// }
+///
+/// // Phase 1: Build the heap on this thread
+/// let mut tlsf = Tlsf::new();
+/// let pool = Box::leak(Box::new([MaybeUninit::uninit(); 1024]));
+/// tlsf.insert_free_block(pool);
+/// let mut heap = Heap { guard: TlsfSendGuard::new(tlsf), live: Vec::new() };
+/// let token = heap.guard.allocate(Layout::new::<u64>()).unwrap();
+/// heap.guard.get_mut(&token).copy_from_slice(&42u64.to_ne_bytes());
+/// heap.live.push(token);
+///
+/// // Phase 2: Use it on another thread
+/// std::thread::spawn(move || {
+///     let mut heap = heap;
+///     for token in heap.live.drain(..) {
+///         assert_eq!(heap.guard.get(&token), &42u64.to_ne_bytes());
+///         heap.guard.deallocate(token);
+///     }
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+pub struct TlsfSendGuard<'pool, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize> {
+    tlsf: Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>,
+    /// The identifier recorded in the tokens issued by `self`
+    id: usize,
+}
+
+/// An allocation made by [`TlsfSendGuard::allocate`]. Its memory can be
+/// accessed through the guard that issued it.
+///
+/// Dropping a token leaks the allocation; pass it to
+/// [`TlsfSendGuard::deallocate`] instead.
+#[must_use = "dropping a token leaks the allocation"]
+pub struct TlsfToken<'pool> {
+    ptr: NonNull<u8>,
+    layout: Layout,
+    guard_id: usize,
+    _pool: PhantomData<&'pool mut [MaybeUninit<u8>]>,
+}
+
+// Safety: A token gives access to the allocation's memory only through the
+//         guard that issued it
+unsafe impl Send for TlsfToken<'_> {}
+unsafe impl Sync for TlsfToken<'_> {}
+
+impl TlsfToken<'_> {
+    /// Get the layout the allocation was made with.
+    #[inline]
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+}
+
+impl fmt::Debug for TlsfToken<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsfToken")
+            .field("ptr", &self.ptr)
+            .field("layout", &self.layout)
+            .finish()
+    }
+}
+
+impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, const SLLEN: usize>
+    TlsfSendGuard<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    /// Take ownership of `tlsf`.
+    #[inline]
+    pub fn new(tlsf: Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>) -> Self {
+        Self {
+            tlsf,
+            id: NEXT_GUARD_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Get the underlying [`Tlsf`], e.g., to supply memory pools.
+    ///
+    /// The memory blocks allocated through it directly can't be accessed
+    /// through `self`.
+    #[inline]
+    pub fn tlsf(&mut self) -> &mut Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN> {
+        &mut self.tlsf
+    }
+
+    /// Return the underlying [`Tlsf`]. The allocations represented by the
+    /// outstanding tokens are leaked.
+    #[inline]
+    pub fn into_inner(self) -> Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN> {
+        self.tlsf
+    }
+
+    /// Attempt to allocate a zero-filled block of memory.
+    ///
+    /// Returns a token representing the allocation on success; `None`
+    /// otherwise.
+    pub fn allocate(&mut self, layout: Layout) -> Option<TlsfToken<'pool>> {
+        let ptr = self.tlsf.allocate(layout)?;
+        // Safety: The allocation is `layout.size()` bytes long
+        unsafe { ptr::write_bytes(ptr.as_ptr(), 0, layout.size()) };
+        Some(TlsfToken {
+            ptr,
+            layout,
+            guard_id: self.id,
+            _pool: PhantomData,
+        })
+    }
+
+    /// Deallocate the allocation represented by `token`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `token` wasn't issued by `self`.
+    pub fn deallocate(&mut self, token: TlsfToken<'pool>) {
+        self.check_token(&token);
+        // Safety: `token` represents a live allocation of `self.tlsf` with
+        //         alignment `token.layout.align()`, and it's consumed here
+        unsafe { self.tlsf.deallocate(token.ptr, token.layout.align()) };
+    }
+
+    /// Borrow the memory of the allocation represented by `token`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `token` wasn't issued by `self`.
+    #[inline]
+    pub fn get(&self, token: &TlsfToken<'pool>) -> &[u8] {
+        self.check_token(token);
+        // Safety: `token` represents a live, initialized allocation of
+        //         `self.tlsf`, whose memory is accessed only through `self`
+        unsafe { slice::from_raw_parts(token.ptr.as_ptr(), token.layout.size()) }
+    }
+
+    /// Mutably borrow the memory of the allocation represented by `token`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `token` wasn't issued by `self`.
+    #[inline]
+    pub fn get_mut(&mut self, token: &TlsfToken<'pool>) -> &mut [u8] {
+        self.check_token(token);
+        // Safety: `token` represents a live, initialized allocation of
+        //         `self.tlsf`, whose memory is accessed only through `self`,
+        //         which is borrowed mutably
+        unsafe { slice::from_raw_parts_mut(token.ptr.as_ptr(), token.layout.size()) }
+    }
+
+    #[inline]
+    fn check_token(&self, token: &TlsfToken<'pool>) {
+        assert_eq!(
+            token.guard_id, self.id,
+            "the token was issued by another `TlsfSendGuard`"
+        );
+    }
+}
+
+impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize> fmt::Debug
+    for TlsfSendGuard<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsfSendGuard").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests;
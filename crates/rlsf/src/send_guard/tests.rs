@@ -0,0 +1,81 @@
+use core::{alloc::Layout, mem::MaybeUninit};
+use std::{prelude::v1::*, thread, vec};
+
+use super::*;
+
+type TheGuard = TlsfSendGuard<'static, u16, u16, 12, 16>;
+
+/// A heap and its live allocations
+struct Heap {
+    guard: TheGuard,
+    live: Vec<TlsfToken<'static>>,
+}
+
+fn new_guard(size: usize) -> TheGuard {
+    let mut tlsf = Tlsf::new();
+    tlsf.insert_free_block(Box::leak(
+        vec![MaybeUninit::uninit(); size].into_boxed_slice(),
+    ));
+    TlsfSendGuard::new(tlsf)
+}
+
+#[test]
+fn phases() {
+    let layout = Layout::new::<u64>();
+    let mut heap = Heap {
+        guard: new_guard(4096),
+        live: Vec::new(),
+    };
+    for i in 0..16u64 {
+        let token = heap.guard.allocate(layout).unwrap();
+        assert_eq!(heap.guard.get(&token), &[0; 8]);
+        heap.guard.get_mut(&token).copy_from_slice(&i.to_ne_bytes());
+        heap.live.push(token);
+    }
+
+    // Each phase runs on a different thread
+    for phase in 0..4 {
+        heap = thread::spawn(move || {
+            for (i, token) in heap.live.iter().enumerate() {
+                assert_eq!(heap.guard.get(token), &(i as u64).to_ne_bytes());
+            }
+            let token = heap.live.pop().unwrap();
+            heap.guard.deallocate(token);
+            assert_eq!(heap.live.len(), 15 - phase);
+            heap
+        })
+        .join()
+        .unwrap();
+    }
+
+    for token in heap.live.drain(..) {
+        heap.guard.deallocate(token);
+    }
+    assert!(heap
+        .guard
+        .tlsf()
+        .allocate(Layout::new::<[u8; 2048]>())
+        .is_some());
+}
+
+#[test]
+fn tokens_move_separately() {
+    let mut guard = new_guard(4096);
+    let token = guard.allocate(Layout::new::<[u8; 100]>()).unwrap();
+    assert_eq!(token.layout(), Layout::new::<[u8; 100]>());
+
+    let token = thread::spawn(move || token).join().unwrap();
+    let mut guard = thread::spawn(move || guard).join().unwrap();
+    guard.get_mut(&token)[99] = 1;
+    assert_eq!(guard.get(&token)[99], 1);
+    guard.deallocate(token);
+}
+
+#[test]
+#[should_panic = "the token was issued by another `TlsfSendGuard`"]
+fn foreign_token() {
+    let mut guard1 = new_guard(1024);
+    let guard2 = new_guard(1024);
+    let token = guard1.allocate(Layout::new::<u64>()).unwrap();
+    let _ = guard2.get(&token);
+}
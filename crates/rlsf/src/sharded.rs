@@ -0,0 +1,385 @@
+//! `ShardedTlsf`: multiple independently locked [`Tlsf`]s
+//!
+//! [`Tlsf`]: crate::Tlsf
+use core::{
+    alloc, fmt,
+    mem::MaybeUninit,
+    ptr::{self, NonNull},
+};
+
+#[cfg(feature = "allocator-api")]
+use crate::utils::nonnull_slice_from_raw_parts;
+use crate::{
+    int::BinInteger,
+    primitives::atomic::{AtomicPtr, AtomicUsize, Ordering},
+    ContentionStats, DefaultLock, StaticGlobalTlsfLock, SyncTlsf, SyncTlsfGuard,
+};
+
+/// A memory block in a remote free queue, written over its payload. The
+/// payload of an allocation made by [`Tlsf`](crate::Tlsf) is at least
+/// `GRANULARITY / 2` bytes large and aligned, so it can hold this.
+struct RemoteFree {
+    next: *mut RemoteFree,
+    align: usize,
+}
+
+/// `NUM_SHARDS` independently locked [`Tlsf`]s (*shards*) sharing a memory
+/// pool.
+///
+/// The memory pool supplied by [`Self::insert_free_block`] is divided evenly
+/// among the shards. Allocations are served by the shard chosen by the
+/// `shard_hint` function passed to [`Self::new`] (e.g., one returning the
+/// current CPU's ID) or by the hint passed to [`Self::allocate_in`], and
+/// overflow to the other shards if it's exhausted. Memory blocks can be
+/// deallocated by anyone; the owning shard is identified by the address.
+///
+/// A memory block deallocated by a thread whose `shard_hint` doesn't point
+/// to the owning shard is pushed to the shard's lock-free *remote free
+/// queue* instead of taking the shard's lock. The queue is drained the next
+/// time the shard's lock is taken (e.g., by an allocation). This keeps
+/// producer-consumer pipelines, where memory blocks are deallocated by
+/// another thread, from contending for the lock. Until then, the memory
+/// blocks in the queue can't be reused.
+///
+/// Allocations and deallocations on different shards don't contend for a
+/// lock, which is a middle ground between [`SyncTlsf`] and
+/// [`ConcurrentTlsf`]. Each shard retains the bounded response time of TLSF,
+/// but free space in one shard can't be merged with that of another.
+///
+/// [`Tlsf`]: crate::Tlsf
+/// [`ConcurrentTlsf`]: crate::ConcurrentTlsf
+///
+/// # Examples
+///
+/// ```rust
+/// use rlsf::ShardedTlsf;
+/// use std::{alloc::{GlobalAlloc, Layout}, mem::MaybeUninit};
+///
+/// fn cpu_id() -> usize {
+///     // e.g., read `mhartid` on RISC-V
+///     0
+/// }
+///
+/// static TLSF: ShardedTlsf<'static, 4, u16, u16, 12, 16> = ShardedTlsf::new(cpu_id);
+///
+/// static mut POOL: [MaybeUninit<u8>; 16384] = [MaybeUninit::uninit(); 16384];
+/// assert!(TLSF.insert_free_block(unsafe { &mut POOL }));
+///
+/// let layout = Layout::new::<u64>();
+/// let ptr = TLSF.allocate_in(2, layout).unwrap();
+/// unsafe { TLSF.dealloc(ptr.as_ptr(), layout) };
+/// ```
+pub struct ShardedTlsf<
+    'pool,
+    const NUM_SHARDS: usize,
+    FLBitmap,
+    SLBitmap,
+    const FLLEN: usize,
+    const SLLEN: usize,
+    Lock = DefaultLock,
+> {
+    shards: [SyncTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>; NUM_SHARDS],
+    /// `remote_frees[i]` is the head of the remote free queue of `shards[i]`
+    remote_frees: [AtomicPtr<RemoteFree>; NUM_SHARDS],
+    /// The start address of the memory pool, or zero if it's yet to be
+    /// supplied
+    pool_start: AtomicUsize,
+    /// The size of the portion of the memory pool given to each shard (but
+    /// the last shard also receives the remainder)
+    shard_len: AtomicUsize,
+    shard_hint: fn() -> usize,
+}
+
+impl<
+        'pool,
+        const NUM_SHARDS: usize,
+        FLBitmap,
+        SLBitmap,
+        const FLLEN: usize,
+        const SLLEN: usize,
+        Lock,
+    > ShardedTlsf<'pool, NUM_SHARDS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[cfg(not(loom))]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const SHARD: SyncTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock> = SyncTlsf::new();
+
+    #[cfg(not(loom))]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_QUEUE: AtomicPtr<RemoteFree> = AtomicPtr::new(ptr::null_mut());
+
+    const VALID_NUM_SHARDS: () = assert!(NUM_SHARDS != 0, "`NUM_SHARDS` must not be zero");
+
+    const_fn! {
+        /// Construct an empty instance of `Self`. `shard_hint` chooses the shard
+        /// to serve allocations made through [`GlobalAlloc`]; its result is
+        /// reduced modulo `NUM_SHARDS`.
+        ///
+        /// [`GlobalAlloc`]: core::alloc::GlobalAlloc
+        #[inline]
+        pub const fn new(shard_hint: fn() -> usize) -> Self {
+            #[allow(clippy::let_unit_value)]
+            let () = Self::VALID_NUM_SHARDS;
+            Self {
+                shards: array_repeat!(Self::SHARD, SyncTlsf::new(); NUM_SHARDS),
+                remote_frees: array_repeat!(
+                    Self::EMPTY_QUEUE,
+                    AtomicPtr::new(ptr::null_mut());
+                    NUM_SHARDS
+                ),
+                pool_start: AtomicUsize::new(0),
+                shard_len: AtomicUsize::new(0),
+                shard_hint,
+            }
+        }
+    }
+
+    /// Divide `block` among the shards. Returns `false` without doing
+    /// anything if a memory pool has already been supplied or `block` is too
+    /// small to divide.
+    pub fn insert_free_block(&self, block: &'pool mut [MaybeUninit<u8>]) -> bool {
+        let shard_len = block.len() / NUM_SHARDS;
+        if shard_len == 0 {
+            return false;
+        }
+
+        let start = block.as_ptr() as usize;
+        if self
+            .pool_start
+            .compare_exchange(0, start, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+        // Published before any memory block becomes allocatable. The shards'
+        // locks order this before any deallocation.
+        self.shard_len.store(shard_len, Ordering::Relaxed);
+
+        let mut rest = block;
+        for (i, shard) in self.shards.iter().enumerate() {
+            let part = if i == NUM_SHARDS - 1 {
+                core::mem::take(&mut rest)
+            } else {
+                let (part, new_rest) = rest.split_at_mut(shard_len);
+                rest = new_rest;
+                part
+            };
+            shard.lock().insert_free_block(part);
+        }
+        true
+    }
+
+    /// Get the index of the shard owning the allocation `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a live allocation made by `self`.
+    #[inline]
+    unsafe fn shard_of(&self, ptr: NonNull<u8>) -> usize {
+        let offset = ptr.as_ptr() as usize - self.pool_start.load(Ordering::Relaxed);
+        let index = offset / self.shard_len.load(Ordering::Relaxed);
+        // The last shard also owns the remainder
+        index.min(NUM_SHARDS - 1)
+    }
+
+    /// Acquire the lock of the shard `shard` (modulo `NUM_SHARDS`), e.g., to
+    /// inspect it. This drains the shard's remote free queue.
+    #[inline]
+    pub fn lock_shard(
+        &self,
+        shard: usize,
+    ) -> SyncTlsfGuard<'_, 'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock> {
+        let shard = shard % NUM_SHARDS;
+        let mut guard = self.shards[shard].lock();
+        self.drain_remote_frees(shard, &mut guard);
+        guard
+    }
+
+    /// Get the sum of the contention counters of all shards.
+    pub fn contention_stats(&self) -> ContentionStats {
+        self.shards
+            .iter()
+            .map(SyncTlsf::contention_stats)
+            .fold(ContentionStats::default(), ContentionStats::merge)
+    }
+
+    /// Deallocate the memory blocks in the remote free queue of the shard
+    /// `shard`, whose lock is held by `guard`.
+    #[inline]
+    fn drain_remote_frees(
+        &self,
+        shard: usize,
+        guard: &mut SyncTlsfGuard<'_, 'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>,
+    ) {
+        let queue = &self.remote_frees[shard];
+        if queue.load(Ordering::Relaxed).is_null() {
+            return;
+        }
+        let mut node = queue.swap(ptr::null_mut(), Ordering::Acquire);
+        while let Some(ptr) = NonNull::new(node) {
+            // Safety: `node` was written by `push_remote_free`
+            let RemoteFree { next, align } = unsafe { ptr.as_ptr().read() };
+            // Safety: `node` denotes a previous allocation of the shard with
+            //         alignment `align`
+            unsafe { guard.deallocate(ptr.cast(), align) };
+            node = next;
+        }
+    }
+
+    /// Push a memory block to the remote free queue of the shard `shard`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a live allocation of the shard `shard` with
+    /// alignment `align`.
+    #[inline]
+    unsafe fn push_remote_free(&self, shard: usize, ptr: NonNull<u8>, align: usize) {
+        self.shards[shard].contention().record_remote_free();
+        let node = ptr.as_ptr() as *mut RemoteFree;
+        let queue = &self.remote_frees[shard];
+        let mut next = queue.load(Ordering::Relaxed);
+        loop {
+            // Safety: The payload can hold `RemoteFree` (see its
+            //         documentation), and we own it now
+            node.write(RemoteFree { next, align });
+            // The consumer takes the whole queue at once, so ABA can't happen
+            match queue.compare_exchange_weak(next, node, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(new_next) => next = new_next,
+            }
+        }
+    }
+
+    /// Allocate memory from the shard `shard` (modulo `NUM_SHARDS`), or from
+    /// the others if it's exhausted.
+    pub fn allocate_in(&self, shard: usize, layout: alloc::Layout) -> Option<NonNull<u8>> {
+        let first = shard % NUM_SHARDS;
+        (0..NUM_SHARDS).find_map(|i| self.lock_shard(first + i).allocate(layout))
+    }
+
+    /// Deallocate a memory block allocated by `self`, returning it to the
+    /// owning shard directly if `shard_hint` points to it, or through its
+    /// remote free queue otherwise.
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `self`.
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `align`.
+    ///
+    /// [`Layout::align`]: core::alloc::Layout::align
+    #[inline]
+    pub unsafe fn deallocate(&self, ptr: NonNull<u8>, align: usize) {
+        let shard = self.shard_of(ptr);
+        if shard == (self.shard_hint)() % NUM_SHARDS {
+            // Safety: Upheld by the caller
+            self.lock_shard(shard).deallocate(ptr, align);
+        } else {
+            // Safety: Upheld by the caller
+            self.push_remote_free(shard, ptr, align);
+        }
+    }
+}
+
+impl<const NUM_SHARDS: usize, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock>
+    fmt::Debug for ShardedTlsf<'_, NUM_SHARDS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardedTlsf")
+            .field("pool_start", &self.pool_start)
+            .field("shard_len", &self.shard_len)
+            .finish_non_exhaustive()
+    }
+}
+
+unsafe impl<const NUM_SHARDS: usize, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock>
+    alloc::GlobalAlloc for ShardedTlsf<'_, NUM_SHARDS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    unsafe fn alloc(&self, layout: alloc::Layout) -> *mut u8 {
+        self.allocate_in((self.shard_hint)(), layout)
+            .map(NonNull::as_ptr)
+            .unwrap_or(ptr::null_mut())
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: alloc::Layout) {
+        // Safety: All allocations are non-null
+        let ptr = NonNull::new_unchecked(ptr);
+        // Safety: `ptr` denotes a previous allocation with alignment
+        //         `layout.align()`
+        self.deallocate(ptr, layout.align());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: alloc::Layout, new_size: usize) -> *mut u8 {
+        // Safety: All allocations are non-null
+        let ptr = NonNull::new_unchecked(ptr);
+        // Safety: `layout.align()` is a power of two, and the size parameter's
+        //         validity is upheld by the caller
+        let new_layout = alloc::Layout::from_size_align_unchecked(new_size, layout.align());
+
+        // Try the owning shard first
+        // Safety: `ptr` denotes a previous allocation with alignment
+        //         `layout.align()`
+        if let Some(new_ptr) = self
+            .lock_shard(self.shard_of(ptr))
+            .reallocate(ptr, new_layout)
+        {
+            return new_ptr.as_ptr();
+        }
+
+        // Move the memory block to another shard
+        let new_ptr = match self.allocate_in((self.shard_hint)(), new_layout) {
+            Some(new_ptr) => new_ptr,
+            None => return ptr::null_mut(),
+        };
+        // Safety: the previously allocated block cannot overlap the newly
+        //         allocated block. The old size is the recorded one, which
+        //         doesn't trust `layout.size()`.
+        let old_size = crate::Tlsf::<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(
+            ptr,
+            layout.align(),
+        );
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), old_size.min(new_size));
+        // Safety: `ptr` denotes a previous allocation with alignment
+        //         `layout.align()`
+        self.deallocate(ptr, layout.align());
+        new_ptr.as_ptr()
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "allocator-api")))]
+unsafe impl<const NUM_SHARDS: usize, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock>
+    alloc::Allocator for &ShardedTlsf<'_, NUM_SHARDS, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    fn allocate(&self, layout: alloc::Layout) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        let ptr = self
+            .allocate_in((self.shard_hint)(), layout)
+            .ok_or(alloc::AllocError)?;
+        Ok(nonnull_slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: alloc::Layout) {
+        // Safety: `ptr` denotes a previous allocation with alignment
+        //         `layout.align()`
+        ShardedTlsf::deallocate(self, ptr, layout.align());
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,145 @@
+use std::{alloc::GlobalAlloc, prelude::v1::*, thread, vec};
+
+use super::*;
+
+type TheShardedTlsf = ShardedTlsf<'static, 4, u16, u16, 12, 16>;
+
+fn new_pool(size: usize) -> &'static mut [MaybeUninit<u8>] {
+    Box::leak(vec![MaybeUninit::uninit(); size].into_boxed_slice())
+}
+
+fn zero() -> usize {
+    0
+}
+
+#[test]
+fn insert_once() {
+    let tlsf = TheShardedTlsf::new(zero);
+    assert!(!tlsf.insert_free_block(new_pool(3)));
+    assert!(tlsf.insert_free_block(new_pool(4096)));
+    assert!(!tlsf.insert_free_block(new_pool(4096)));
+}
+
+#[test]
+fn route_by_address() {
+    let pool = new_pool(4 * 1024 + 100);
+    let range = pool.as_ptr_range();
+    let range = range.start as usize..range.end as usize;
+    let tlsf = TheShardedTlsf::new(zero);
+    assert!(tlsf.insert_free_block(pool));
+
+    let layout = alloc::Layout::from_size_align(256, 8).unwrap();
+    let ptrs: Vec<_> = (0..4)
+        .map(|shard| tlsf.allocate_in(shard, layout).unwrap())
+        .collect();
+    for (shard, ptr) in ptrs.iter().enumerate() {
+        let addr = ptr.as_ptr() as usize;
+        assert!(range.contains(&addr));
+        assert_eq!(((addr - range.start) / 1024).min(3), shard);
+        assert_eq!(unsafe { tlsf.shard_of(*ptr) }, shard);
+    }
+
+    for ptr in ptrs {
+        unsafe { tlsf.deallocate(ptr, layout.align()) };
+    }
+}
+
+#[test]
+fn overflow_to_other_shards() {
+    let tlsf = TheShardedTlsf::new(zero);
+    assert!(tlsf.insert_free_block(new_pool(4 * 1024)));
+
+    let layout = alloc::Layout::from_size_align(512, 8).unwrap();
+    let mut ptrs = Vec::new();
+    while let Some(ptr) = NonNull::new(unsafe { tlsf.alloc(layout) }) {
+        ptrs.push(ptr);
+    }
+    // All shards were used
+    assert!(ptrs.len() >= 4);
+    assert!(tlsf.lock_shard(3).allocate(layout).is_none());
+
+    unsafe {
+        // Reallocation moves the memory block to another shard if needed
+        let ptr = ptrs.pop().unwrap();
+        ptr.as_ptr().write_bytes(42, layout.size());
+        tlsf.dealloc(ptrs.pop().unwrap().as_ptr(), layout);
+        let ptr = tlsf.realloc(ptr.as_ptr(), layout, 300);
+        assert!(!ptr.is_null());
+        assert!((0..300).all(|i| *ptr.add(i) == 42));
+        tlsf.dealloc(ptr, alloc::Layout::from_size_align(300, 8).unwrap());
+
+        for ptr in ptrs {
+            tlsf.dealloc(ptr.as_ptr(), layout);
+        }
+    }
+}
+
+#[test]
+fn cross_shard_free() {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    static TLSF: TheShardedTlsf = ShardedTlsf::new(|| NEXT.fetch_add(1, Ordering::Relaxed));
+    assert!(TLSF.insert_free_block(new_pool(1 << 16)));
+
+    let (tx, rx) = std::sync::mpsc::sync_channel::<usize>(64);
+    let consumer = thread::spawn(move || {
+        for addr in rx {
+            unsafe { TLSF.dealloc(addr as *mut u8, alloc::Layout::new::<u64>()) };
+        }
+    });
+    let producers: Vec<_> = (0..4)
+        .map(|_| {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    let ptr = unsafe { TLSF.alloc(alloc::Layout::new::<u64>()) };
+                    assert!(!ptr.is_null());
+                    tx.send(ptr as usize).unwrap();
+                }
+            })
+        })
+        .collect();
+    drop(tx);
+    for thread in producers {
+        thread.join().unwrap();
+    }
+    consumer.join().unwrap();
+
+    // Everything has been returned to its shard
+    let layout = alloc::Layout::from_size_align(1 << 13, 8).unwrap();
+    for shard in 0..4 {
+        let ptr = TLSF.lock_shard(shard).allocate(layout).unwrap();
+        unsafe { TLSF.lock_shard(shard).deallocate(ptr, layout.align()) };
+    }
+}
+
+#[test]
+fn remote_free() {
+    let tlsf = TheShardedTlsf::new(zero);
+    assert!(tlsf.insert_free_block(new_pool(4 * 1024)));
+
+    // Fill the shard 1
+    let layout = alloc::Layout::from_size_align(256, 8).unwrap();
+    let mut ptrs = Vec::new();
+    while let Some(ptr) = tlsf.shards[1].lock().allocate(layout) {
+        ptrs.push(ptr);
+    }
+
+    // The shard 0 is the current thread's, so these are queued
+    for &ptr in ptrs.iter() {
+        unsafe { tlsf.deallocate(ptr, layout.align()) };
+    }
+    assert!(tlsf.shards[1].lock().allocate(layout).is_none());
+    assert!(!tlsf.remote_frees[1].load(Ordering::Relaxed).is_null());
+    assert_eq!(tlsf.contention_stats().remote_frees, ptrs.len());
+
+    // The queue is drained by the next allocation from the shard
+    let ptr = tlsf.allocate_in(1, layout).unwrap();
+    assert_eq!(unsafe { tlsf.shard_of(ptr) }, 1);
+    assert!(tlsf.remote_frees[1].load(Ordering::Relaxed).is_null());
+    unsafe { tlsf.deallocate(ptr, layout.align()) };
+
+    // Deallocations in the current thread's shard aren't queued
+    let ptr = tlsf.allocate_in(0, layout).unwrap();
+    unsafe { tlsf.deallocate(ptr, layout.align()) };
+    assert!(tlsf.remote_frees[0].load(Ordering::Relaxed).is_null());
+}
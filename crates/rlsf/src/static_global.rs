@@ -0,0 +1,676 @@
+//! `StaticGlobalTlsf`: a global allocator backed by a static memory pool
+use core::{
+    alloc,
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ops,
+    ptr::{self, NonNull},
+};
+
+#[cfg(target_has_atomic = "8")]
+use crate::primitives::{
+    atomic::{AtomicBool, Ordering},
+    hint,
+};
+
+#[cfg(feature = "allocator-api")]
+use crate::utils::{nonnull_slice_from_raw_parts, nonnull_slice_len};
+use crate::{Tlsf, TryAllocError};
+
+#[cfg(all(feature = "cortex-m", target_arch = "arm"))]
+mod cortex_m;
+#[cfg(all(feature = "cortex-m", target_arch = "arm"))]
+pub use self::cortex_m::*;
+
+type TheTlsf = Tlsf<'static, u32, u16, 28, 16>;
+
+/// A mutual exclusion primitive used by [`StaticGlobalTlsf`] and [`SyncTlsf`].
+///
+/// # Safety
+///
+/// Between a call to [`Self::lock`] (or a successful call to
+/// [`Self::try_lock`]) and the matching call to [`Self::unlock`], no other
+/// call to `lock` or `try_lock` on the same object may succeed, whichever
+/// thread or interrupt handler makes it.
+///
+/// [`SyncTlsf`]: crate::SyncTlsf
+pub unsafe trait StaticGlobalTlsfLock: Sync {
+    /// The unlocked state.
+    #[cfg(not(loom))]
+    const INIT: Self;
+
+    /// Construct the lock in the unlocked state. This replaces
+    /// [`Self::INIT`] under `cfg(loom)`, where atomics can't be constructed
+    /// in constant contexts.
+    #[cfg(loom)]
+    fn init() -> Self;
+
+    /// A value passed from [`Self::lock`] to the matching [`Self::unlock`].
+    type State: Copy;
+
+    /// Acquire the lock.
+    fn lock(&self) -> Self::State;
+
+    /// Acquire the lock if it can be done without waiting for its current
+    /// holder. Returns `None` otherwise.
+    ///
+    /// This is used to allocate memory from interrupt handlers (see
+    /// [`SyncTlsf::allocate_from_isr`]). The default implementation always
+    /// returns `None`.
+    ///
+    /// [`SyncTlsf::allocate_from_isr`]: crate::SyncTlsf::allocate_from_isr
+    #[inline]
+    fn try_lock(&self) -> Option<Self::State> {
+        None
+    }
+
+    /// Release the lock.
+    ///
+    /// # Safety
+    ///
+    /// `state` must be the value returned by the matching call to
+    /// [`Self::lock`], and locks must be released in the reverse order of
+    /// acquisition.
+    unsafe fn unlock(&self, state: Self::State);
+}
+
+/// A spin lock for [`StaticGlobalTlsf`] and [`SyncTlsf`]. Waiters back off
+/// exponentially under contention.
+///
+/// It's not safe to allocate memory from an interrupt handler that might
+/// preempt another allocator call on the same core; doing so will cause a
+/// deadlock.
+///
+/// [`SyncTlsf`]: crate::SyncTlsf
+#[cfg(target_has_atomic = "8")]
+#[derive(Debug)]
+pub struct SpinLock(AtomicBool);
+
+/// The maximum number of [`hint::spin_loop`] calls between polls of
+/// [`SpinLock`]
+#[cfg(target_has_atomic = "8")]
+const MAX_SPIN_BACKOFF: u32 = 64;
+
+#[cfg(target_has_atomic = "8")]
+unsafe impl StaticGlobalTlsfLock for SpinLock {
+    lock_init!(Self(AtomicBool::new(false)));
+
+    type State = ();
+
+    #[inline]
+    fn lock(&self) {
+        let mut backoff = 1;
+        while self
+            .0
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // Wait until the lock looks free without writing to the cache
+            // line, backing off exponentially to reduce contention
+            while self.0.load(Ordering::Relaxed) {
+                for _ in 0..backoff {
+                    hint::spin_loop();
+                }
+                backoff = (backoff * 2).min(MAX_SPIN_BACKOFF);
+            }
+        }
+    }
+
+    #[inline]
+    fn try_lock(&self) -> Option<()> {
+        self.0
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| ())
+    }
+
+    #[inline]
+    unsafe fn unlock(&self, (): ()) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// Mutual exclusion for [`StaticGlobalTlsf`] provided by the
+/// [`critical-section`] crate.
+///
+/// [`critical-section`]: https://crates.io/crates/critical-section
+#[cfg(feature = "critical-section")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "critical-section")))]
+#[derive(Debug)]
+pub struct CriticalSectionLock(());
+
+#[cfg(feature = "critical-section")]
+unsafe impl StaticGlobalTlsfLock for CriticalSectionLock {
+    lock_init!(Self(()));
+
+    type State = critical_section::RestoreState;
+
+    #[inline]
+    fn lock(&self) -> Self::State {
+        // Safety: The returned state is passed to `unlock`, and lock
+        //         guards are dropped in the reverse order of creation
+        unsafe { critical_section::acquire() }
+    }
+
+    /// Critical sections exclude everything that could be holding the lock
+    /// on the current core, so this always succeeds.
+    #[inline]
+    fn try_lock(&self) -> Option<Self::State> {
+        Some(self.lock())
+    }
+
+    #[inline]
+    unsafe fn unlock(&self, state: Self::State) {
+        critical_section::release(state);
+    }
+}
+
+/// Mutual exclusion for [`StaticGlobalTlsf`] and [`SyncTlsf`] provided by a
+/// [`lock_api::RawMutex`] implementation, such as `parking_lot::RawMutex`,
+/// `spin::mutex::SpinMutex<()>`, or a mutex of an operating system.
+///
+/// [`SyncTlsf`]: crate::SyncTlsf
+#[cfg(feature = "lock_api")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "lock_api")))]
+pub struct RawMutexLock<R>(R);
+
+#[cfg(feature = "lock_api")]
+unsafe impl<R: lock_api::RawMutex + Sync> StaticGlobalTlsfLock for RawMutexLock<R> {
+    lock_init!(Self(R::INIT));
+
+    type State = ();
+
+    #[inline]
+    fn lock(&self) {
+        self.0.lock();
+    }
+
+    #[inline]
+    fn try_lock(&self) -> Option<()> {
+        self.0.try_lock().then(|| ())
+    }
+
+    #[inline]
+    unsafe fn unlock(&self, (): ()) {
+        // Safety: The lock is held by the current context (upheld by the
+        //         caller)
+        self.0.unlock();
+    }
+}
+
+#[cfg(feature = "lock_api")]
+impl<R> fmt::Debug for RawMutexLock<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawMutexLock").finish_non_exhaustive()
+    }
+}
+
+/// [`RawMutexLock`] backed by [`parking_lot::RawMutex`], which occupies a
+/// single byte, doesn't poison, and spins adaptively before parking the
+/// thread.
+///
+/// This lock must not protect the global allocator (including a
+/// [`SyncTlsf`] registered as `#[global_allocator]`) because `parking_lot`
+/// allocates its thread-parking hash table from the global allocator while
+/// holding internal locks. For the same reason, [`GlobalTlsf`] keeps using
+/// the mutexes of the operating system.
+///
+/// [`SyncTlsf`]: crate::SyncTlsf
+/// [`GlobalTlsf`]: crate::GlobalTlsf
+#[cfg(feature = "parking_lot")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "parking_lot")))]
+pub type ParkingLotLock = RawMutexLock<parking_lot::RawMutex>;
+
+/// Raises and restores the current task's priority for
+/// [`PriorityCeilingLock`], typically by calling into an RTOS.
+///
+/// # Safety
+///
+/// While the priority set by [`Self::raise`] is in effect, no other task or
+/// interrupt handler that uses the allocator may run until the matching call
+/// to [`Self::restore`]. On a single-core system with a fixed-priority
+/// preemptive scheduler, this holds if the ceiling priority is at least the
+/// highest priority of all tasks using the allocator and no interrupt handler
+/// uses it.
+pub unsafe trait PriorityCeiling {
+    /// The priority to restore in [`Self::restore`].
+    type Priority: Copy;
+
+    /// Raise the current task's priority to the ceiling priority, and return
+    /// its previous priority.
+    ///
+    /// This must not lower the priority if the current task's priority is
+    /// already higher than the ceiling.
+    fn raise() -> Self::Priority;
+
+    /// Restore the current task's priority to `priority`.
+    ///
+    /// # Safety
+    ///
+    /// `priority` must be the value returned by the matching call to
+    /// [`Self::raise`].
+    unsafe fn restore(priority: Self::Priority);
+}
+
+/// Mutual exclusion for [`StaticGlobalTlsf`] and [`SyncTlsf`] by the
+/// immediate priority ceiling protocol.
+///
+/// Acquiring the lock raises the current task's priority to a ceiling
+/// priority, which is at least the priority of any task using the allocator,
+/// by [`PriorityCeiling::raise`]. Consequently, once a task holds the lock,
+/// no other task using the allocator can preempt it, and a task that wants
+/// the lock is blocked (by not being scheduled) for at most one allocator
+/// call made by a lower-priority task. Since TLSF runs in bounded time, the
+/// worst-case blocking time is bounded and can be included in a schedulability
+/// analysis. Unlike `CortexMBasepriLock`, tasks with priorities above the
+/// ceiling and interrupt handlers keep running, but must not use the
+/// allocator.
+///
+/// This only excludes tasks on the same core.
+///
+/// [`SyncTlsf`]: crate::SyncTlsf
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// /// The highest priority of the tasks using the allocator
+/// const HEAP_CEILING: u32 = 5;
+///
+/// struct HeapCeiling;
+///
+/// unsafe impl rlsf::PriorityCeiling for HeapCeiling {
+///     type Priority = u32;
+///
+///     fn raise() -> u32 {
+///         let old = rtos::current_task_priority();
+///         rtos::set_current_task_priority(old.max(HEAP_CEILING));
+///         old
+///     }
+///
+///     unsafe fn restore(priority: u32) {
+///         rtos::set_current_task_priority(priority);
+///     }
+/// }
+///
+/// rlsf::static_global_tlsf! {
+///     #[global_allocator]
+///     static A: [u8; 16 * 1024] with rlsf::PriorityCeilingLock<HeapCeiling>;
+/// }
+/// ```
+pub struct PriorityCeilingLock<P>(PhantomData<fn() -> P>);
+
+unsafe impl<P: PriorityCeiling> StaticGlobalTlsfLock for PriorityCeilingLock<P> {
+    lock_init!(Self(PhantomData));
+
+    /// The previous priority of the current task
+    type State = P::Priority;
+
+    #[inline]
+    fn lock(&self) -> Self::State {
+        P::raise()
+    }
+
+    /// Raising the priority excludes all tasks that could be holding the lock
+    /// on the current core, so this always succeeds.
+    #[inline]
+    fn try_lock(&self) -> Option<Self::State> {
+        Some(self.lock())
+    }
+
+    #[inline]
+    unsafe fn unlock(&self, state: Self::State) {
+        // Safety: `state` was returned by the matching call to `raise`
+        //         (upheld by the caller)
+        P::restore(state);
+    }
+}
+
+impl<P> fmt::Debug for PriorityCeilingLock<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PriorityCeilingLock")
+            .finish_non_exhaustive()
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "critical-section")] {
+        /// The default lock type of [`StaticGlobalTlsf`] and [`SyncTlsf`](crate::SyncTlsf): [`CriticalSectionLock`]
+        pub type DefaultLock = CriticalSectionLock;
+    } else if #[cfg(target_has_atomic = "8")] {
+        /// The default lock type of [`StaticGlobalTlsf`] and [`SyncTlsf`](crate::SyncTlsf): [`SpinLock`]
+        pub type DefaultLock = SpinLock;
+    } else {
+        /// The default lock type of [`StaticGlobalTlsf`] and [`SyncTlsf`](crate::SyncTlsf): [`CortexMPrimaskLock`]
+        pub type DefaultLock = CortexMPrimaskLock;
+    }
+}
+
+/// [`Tlsf`] as a global allocator, managing a memory pool of `SIZE` bytes
+/// embedded in `self`.
+///
+/// Unlike [`GlobalTlsf`], this type doesn't need an operating system. The
+/// mutual exclusion is provided by `Lock`, which defaults to [`SpinLock`].
+/// With a spin lock, it's not safe to allocate memory from an interrupt
+/// handler that might preempt another allocator call on the same core; doing
+/// so will cause a deadlock. If the `critical-section` feature is enabled,
+/// the default is [`CriticalSectionLock`], which uses a critical section
+/// provided by the [`critical-section`] crate instead, making it safe to use
+/// from interrupt handlers and on targets without atomic compare-and-swap
+/// instructions. The `cortex-m` feature provides interrupt-masking locks for
+/// Arm Cortex-M (`CortexMPrimaskLock` and `CortexMBasepriLock`).
+///
+/// The memory pool is handed over to the allocator on the first use, after
+/// which `self` must not be moved. Use [`static_global_tlsf!`] to declare an
+/// instance in a `static` without writing `unsafe`.
+///
+/// [`Tlsf`]: crate::Tlsf
+/// [`GlobalTlsf`]: crate::GlobalTlsf
+/// [`critical-section`]: https://crates.io/crates/critical-section
+///
+/// # Examples
+///
+/// ```rust
+/// rlsf::static_global_tlsf! {
+///     #[global_allocator]
+///     static A: [u8; 64 * 1024];
+/// }
+///
+/// let mut v = Vec::new();
+/// v.push(42);
+/// assert_eq!(v[0], 42);
+/// ```
+pub struct StaticGlobalTlsf<const SIZE: usize, Lock = DefaultLock> {
+    inner: UnsafeCell<Inner<SIZE>>,
+    lock: Lock,
+}
+
+struct Inner<const SIZE: usize> {
+    tlsf: TheTlsf,
+    pool_inserted: bool,
+    pool: [MaybeUninit<u8>; SIZE],
+}
+
+unsafe impl<const SIZE: usize, Lock: StaticGlobalTlsfLock + Send> Send
+    for StaticGlobalTlsf<SIZE, Lock>
+{
+}
+unsafe impl<const SIZE: usize, Lock: StaticGlobalTlsfLock> Sync for StaticGlobalTlsf<SIZE, Lock> {}
+
+impl<const SIZE: usize, Lock: StaticGlobalTlsfLock> StaticGlobalTlsf<SIZE, Lock> {
+    const_fn! {
+        /// Construct an instance of `Self` with an untouched memory pool.
+        ///
+        /// # Safety
+        ///
+        /// The constructed value must not be moved once it has been used for
+        /// allocation. Storing it in a `static` satisfies this requirement.
+        #[inline]
+        pub const unsafe fn new() -> Self {
+            Self {
+                inner: UnsafeCell::new(Inner {
+                    tlsf: Tlsf::new(),
+                    pool_inserted: false,
+                    pool: [MaybeUninit::uninit(); SIZE],
+                }),
+                lock: lock_init_value!(Lock),
+            }
+        }
+    }
+
+    /// Attempt to allocate memory without waiting for the lock.
+    ///
+    /// Returns [`TryAllocError::Contended`] if the lock is held by someone
+    /// else (or `Lock` doesn't implement [`StaticGlobalTlsfLock::try_lock`]),
+    /// letting soft real-time code fall back to another memory source instead
+    /// of blocking. The returned memory block can be deallocated through
+    /// [`GlobalAlloc`](core::alloc::GlobalAlloc).
+    #[inline]
+    pub fn try_allocate(&self, layout: alloc::Layout) -> Result<NonNull<u8>, TryAllocError> {
+        let state = self.lock.try_lock().ok_or(TryAllocError::Contended)?;
+        self.guard(state)
+            .allocate(layout)
+            .ok_or(TryAllocError::Exhausted)
+    }
+
+    #[inline]
+    fn lock_inner(&self) -> impl ops::DerefMut<Target = TheTlsf> + '_ {
+        self.guard(self.lock.lock())
+    }
+
+    /// Wrap the lock state `state` in a guard, inserting the memory pool if
+    /// it's the first use.
+    #[inline]
+    fn guard(&self, state: Lock::State) -> impl ops::DerefMut<Target = TheTlsf> + '_ {
+        struct LockGuard<'a, const SIZE: usize, Lock: StaticGlobalTlsfLock>(
+            &'a StaticGlobalTlsf<SIZE, Lock>,
+            Lock::State,
+        );
+
+        impl<const SIZE: usize, Lock: StaticGlobalTlsfLock> ops::Deref for LockGuard<'_, SIZE, Lock> {
+            type Target = TheTlsf;
+
+            #[inline]
+            fn deref(&self) -> &Self::Target {
+                // Safety: Protected by `lock`
+                unsafe { &(*self.0.inner.get()).tlsf }
+            }
+        }
+
+        impl<const SIZE: usize, Lock: StaticGlobalTlsfLock> ops::DerefMut for LockGuard<'_, SIZE, Lock> {
+            #[inline]
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                // Safety: Protected by `lock`
+                unsafe { &mut (*self.0.inner.get()).tlsf }
+            }
+        }
+
+        impl<const SIZE: usize, Lock: StaticGlobalTlsfLock> Drop for LockGuard<'_, SIZE, Lock> {
+            #[inline]
+            fn drop(&mut self) {
+                // Safety: `self.1` was returned by `lock`
+                unsafe { self.0.lock.unlock(self.1) };
+            }
+        }
+
+        // Safety: Protected by `lock`
+        let inner = unsafe { &mut *self.inner.get() };
+        if !inner.pool_inserted {
+            inner.pool_inserted = true;
+            let pool = ptr::slice_from_raw_parts_mut(inner.pool.as_mut_ptr() as *mut u8, SIZE);
+            // Safety: `pool` is owned by `self`, which won't move (upheld by
+            //         the caller of `new`) and outlives all allocations
+            //         because they can't outlive a `GlobalAlloc`
+            unsafe {
+                inner
+                    .tlsf
+                    .insert_free_block_ptr(NonNull::new_unchecked(pool))
+            };
+        }
+
+        LockGuard(self, state)
+    }
+}
+
+unsafe impl<const SIZE: usize, Lock: StaticGlobalTlsfLock> alloc::GlobalAlloc
+    for StaticGlobalTlsf<SIZE, Lock>
+{
+    #[inline]
+    unsafe fn alloc(&self, layout: alloc::Layout) -> *mut u8 {
+        let mut inner = self.lock_inner();
+        inner
+            .allocate(layout)
+            .map(NonNull::as_ptr)
+            .unwrap_or(ptr::null_mut())
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: alloc::Layout) {
+        let mut inner = self.lock_inner();
+        // Safety: All allocations are non-null
+        let ptr = NonNull::new_unchecked(ptr);
+        // Safety: `ptr` denotes a previous allocation with alignment
+        //         `layout.align()`
+        inner.deallocate(ptr, layout.align());
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: alloc::Layout, new_size: usize) -> *mut u8 {
+        let mut inner = self.lock_inner();
+        // Safety: All allocations are non-null
+        let ptr = NonNull::new_unchecked(ptr);
+        // Safety: `layout.align()` is a power of two, and the size parameter's
+        //         validity is upheld by the caller
+        let new_layout = alloc::Layout::from_size_align_unchecked(new_size, layout.align());
+        // Safety: `ptr` denotes a previous allocation with alignment
+        //         `layout.align()`
+        inner
+            .reallocate(ptr, new_layout)
+            .map(NonNull::as_ptr)
+            .unwrap_or(ptr::null_mut())
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "allocator-api")))]
+unsafe impl<const SIZE: usize, Lock: StaticGlobalTlsfLock> alloc::Allocator
+    for &StaticGlobalTlsf<SIZE, Lock>
+{
+    #[inline]
+    fn allocate(&self, layout: alloc::Layout) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        let mut inner = self.lock_inner();
+        let ptr = inner.allocate(layout).ok_or(alloc::AllocError)?;
+        // Safety: `ptr` denotes a previous allocation with alignment
+        //         `layout.align()`
+        let size = unsafe { TheTlsf::size_of_allocation(ptr, layout.align()) };
+        Ok(nonnull_slice_from_raw_parts(ptr, size))
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: alloc::Layout) {
+        let mut inner = self.lock_inner();
+        // Safety: `ptr` denotes a previous allocation with alignment
+        //         `layout.align()`
+        inner.deallocate(ptr, layout.align());
+    }
+
+    #[inline]
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        self.reallocate_for_allocator(ptr, old_layout, new_layout)
+    }
+
+    #[inline]
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        let new_ptr = self.reallocate_for_allocator(ptr, old_layout, new_layout)?;
+        let new_size = nonnull_slice_len(new_ptr);
+        // Safety: The range is in the allocation
+        (new_ptr.as_ptr() as *mut u8)
+            .add(old_layout.size())
+            .write_bytes(0, new_size - old_layout.size());
+        Ok(new_ptr)
+    }
+
+    #[inline]
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        self.reallocate_for_allocator(ptr, old_layout, new_layout)
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+impl<const SIZE: usize, Lock: StaticGlobalTlsfLock> StaticGlobalTlsf<SIZE, Lock> {
+    /// Implements [`alloc::Allocator::grow`] and [`alloc::Allocator::shrink`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a previous allocation with `old_layout`.
+    unsafe fn reallocate_for_allocator(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        let mut inner = self.lock_inner();
+        let new_ptr = if old_layout.align() == new_layout.align() {
+            // Safety: `ptr` denotes a previous allocation with alignment
+            //         `new_layout.align()`
+            inner.reallocate(ptr, new_layout).ok_or(alloc::AllocError)?
+        } else {
+            // `Tlsf::reallocate` can't change the alignment
+            let new_ptr = inner.allocate(new_layout).ok_or(alloc::AllocError)?;
+            // Safety: the previously allocated block cannot overlap the
+            //         newly allocated block. The old size is the recorded
+            //         one, which doesn't trust `old_layout.size()`.
+            let old_size = TheTlsf::size_of_allocation(ptr, old_layout.align());
+            ptr::copy_nonoverlapping(
+                ptr.as_ptr(),
+                new_ptr.as_ptr(),
+                old_size.min(new_layout.size()),
+            );
+            // Safety: `ptr` denotes a previous allocation with alignment
+            //         `old_layout.align()`
+            inner.deallocate(ptr, old_layout.align());
+            new_ptr
+        };
+        // Safety: `new_ptr` denotes a previous allocation with alignment
+        //         `new_layout.align()`
+        let size = TheTlsf::size_of_allocation(new_ptr, new_layout.align());
+        Ok(nonnull_slice_from_raw_parts(new_ptr, size))
+    }
+}
+
+/// Declare a [`StaticGlobalTlsf`] in a `static` item.
+///
+/// The memory pool size is specified in the form of an array type. A lock
+/// type other than [`DefaultLock`] can be specified by appending
+/// `with LockType`.
+///
+/// # Examples
+///
+/// ```rust
+/// rlsf::static_global_tlsf! {
+///     /// The heap for the network stack
+///     pub static NET_HEAP: [u8; 4096];
+/// }
+///
+/// use std::alloc::{GlobalAlloc, Layout};
+/// unsafe {
+///     let layout = Layout::new::<u64>();
+///     let ptr = NET_HEAP.alloc(layout);
+///     assert!(!ptr.is_null());
+///     NET_HEAP.dealloc(ptr, layout);
+/// }
+/// ```
+#[macro_export]
+macro_rules! static_global_tlsf {
+    ($( #[$meta:meta] )* $vis:vis static $name:ident: [u8; $size:expr];) => {
+        $crate::static_global_tlsf! {
+            $( #[$meta] )*
+            $vis static $name: [u8; $size] with $crate::DefaultLock;
+        }
+    };
+    ($( #[$meta:meta] )* $vis:vis static $name:ident: [u8; $size:expr] with $lock:ty;) => {
+        $( #[$meta] )*
+        $vis static $name: $crate::StaticGlobalTlsf<{ $size }, $lock> =
+            // Safety: `$name` is a `static` and never moves
+            unsafe { $crate::StaticGlobalTlsf::new() };
+    };
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,140 @@
+//! Interrupt-masking locks for Arm Cortex-M
+use core::arch::asm;
+
+use super::StaticGlobalTlsfLock;
+
+/// Mutual exclusion for [`StaticGlobalTlsf`] by masking all interrupts with
+/// configurable priority through `PRIMASK`.
+///
+/// This makes the allocator usable from any interrupt handler. It only
+/// excludes code running on the same core, so it must not be used on
+/// multi-core systems where more than one core can access the allocator.
+/// Supported by all Cortex-M processors.
+///
+/// [`StaticGlobalTlsf`]: crate::StaticGlobalTlsf
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// rlsf::static_global_tlsf! {
+///     #[global_allocator]
+///     static A: [u8; 16 * 1024] with rlsf::CortexMPrimaskLock;
+/// }
+/// ```
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "cortex-m")))]
+#[derive(Debug)]
+pub struct CortexMPrimaskLock(());
+
+unsafe impl StaticGlobalTlsfLock for CortexMPrimaskLock {
+    lock_init!(Self(()));
+
+    /// Whether interrupts were enabled before locking
+    type State = bool;
+
+    #[inline]
+    fn lock(&self) -> bool {
+        let primask: u32;
+        // Safety: Reading `PRIMASK` and disabling interrupts have no memory
+        //         safety implications. The second `asm!` isn't `nomem` so
+        //         that it acts as a compiler fence.
+        unsafe {
+            asm!("mrs {}, PRIMASK", out(reg) primask, options(nomem, nostack, preserves_flags));
+            asm!("cpsid i", options(nostack, preserves_flags));
+        }
+        primask & 1 == 0
+    }
+
+    /// Interrupts are masked while the lock is held, so this always succeeds
+    /// on a single-core system.
+    #[inline]
+    fn try_lock(&self) -> Option<bool> {
+        Some(self.lock())
+    }
+
+    #[inline]
+    unsafe fn unlock(&self, was_enabled: bool) {
+        if was_enabled {
+            asm!("cpsie i", options(nostack, preserves_flags));
+        }
+    }
+}
+
+/// Mutual exclusion for [`StaticGlobalTlsf`] by raising `BASEPRI` to the
+/// priority ceiling `CEILING`.
+///
+/// `CEILING` is the raw 8-bit priority value (as written to the NVIC priority
+/// registers, i.e., with the implemented priority bits left-aligned) and must
+/// be non-zero. Only the interrupts with priority values numerically greater
+/// than or equal to `CEILING` are masked while the lock is held, so interrupt
+/// handlers in this range can use the allocator, whereas handlers with more
+/// urgent priorities keep running with no added latency but must not call
+/// the allocator.
+///
+/// Like [`CortexMPrimaskLock`], this only excludes code running on the same
+/// core. `BASEPRI` is available on ARMv7-M, ARMv7E-M, and ARMv8-M Mainline
+/// (e.g., Cortex-M3, M4, M7, M33), but not on ARMv6-M or ARMv8-M Baseline.
+///
+/// This lock doesn't implement [`StaticGlobalTlsfLock::try_lock`], so
+/// [`SyncTlsf::allocate_from_isr`] always uses the emergency reserve.
+///
+/// [`StaticGlobalTlsf`]: crate::StaticGlobalTlsf
+/// [`SyncTlsf::allocate_from_isr`]: crate::SyncTlsf::allocate_from_isr
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// // With 4 priority bits implemented, mask priority levels 4–15
+/// rlsf::static_global_tlsf! {
+///     #[global_allocator]
+///     static A: [u8; 16 * 1024] with rlsf::CortexMBasepriLock<{ 4 << 4 }>;
+/// }
+/// ```
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "cortex-m")))]
+#[derive(Debug)]
+pub struct CortexMBasepriLock<const CEILING: u8>(());
+
+impl<const CEILING: u8> CortexMBasepriLock<CEILING> {
+    const VALID_CEILING: () = assert!(
+        CEILING != 0,
+        "`BASEPRI = 0` doesn't mask any interrupts; use `CortexMPrimaskLock` instead"
+    );
+}
+
+unsafe impl<const CEILING: u8> StaticGlobalTlsfLock for CortexMBasepriLock<CEILING> {
+    lock_init!({
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID_CEILING;
+        Self(())
+    });
+
+    /// The previous value of `BASEPRI`
+    type State = u8;
+
+    #[inline]
+    fn lock(&self) -> u8 {
+        let basepri: u32;
+        // Safety: Reading and raising `BASEPRI` have no memory safety
+        //         implications. `BASEPRI_MAX` never lowers the current
+        //         masking level, so nested locks are fine. The second `asm!`
+        //         isn't `nomem` so that it acts as a compiler fence.
+        unsafe {
+            asm!("mrs {}, BASEPRI", out(reg) basepri, options(nomem, nostack, preserves_flags));
+            asm!(
+                "msr BASEPRI_MAX, {}",
+                "isb",
+                in(reg) CEILING as u32,
+                options(nostack, preserves_flags),
+            );
+        }
+        basepri as u8
+    }
+
+    #[inline]
+    unsafe fn unlock(&self, basepri: u8) {
+        asm!(
+            "msr BASEPRI, {}",
+            in(reg) basepri as u32,
+            options(nostack, preserves_flags),
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use std::{alloc::GlobalAlloc, prelude::v1::*, thread};
+
+use super::*;
+
+#[test]
+fn exhaust_and_reuse() {
+    static_global_tlsf! {
+        static A: [u8; 4096];
+    }
+
+    unsafe {
+        let layout = alloc::Layout::from_size_align(64, 8).unwrap();
+        let mut ptrs = Vec::new();
+        loop {
+            let ptr = A.alloc(layout);
+            if ptr.is_null() {
+                break;
+            }
+            let addr = ptr as usize;
+            let pool = (*A.inner.get()).pool.as_ptr() as usize;
+            assert!(addr >= pool && addr + layout.size() <= pool + 4096);
+            ptrs.push(ptr);
+        }
+        assert!(!ptrs.is_empty());
+
+        for ptr in ptrs.drain(..) {
+            A.dealloc(ptr, layout);
+        }
+
+        // The entire pool is available again
+        let big = alloc::Layout::from_size_align(2048, 8).unwrap();
+        let ptr = A.alloc(big);
+        assert!(!ptr.is_null());
+        let ptr = A.realloc(ptr, big, 1024);
+        assert!(!ptr.is_null());
+        A.dealloc(ptr, alloc::Layout::from_size_align(1024, 8).unwrap());
+    }
+}
+
+#[test]
+fn threads() {
+    static_global_tlsf! {
+        static A: [u8; 65536];
+    }
+
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            thread::spawn(move || unsafe {
+                let layout = alloc::Layout::from_size_align(16 + i * 8, 8).unwrap();
+                for _ in 0..1000 {
+                    let ptr = A.alloc(layout);
+                    assert!(!ptr.is_null());
+                    ptr.write_bytes(i as u8, layout.size());
+                    assert!((0..layout.size()).all(|k| *ptr.add(k) == i as u8));
+                    A.dealloc(ptr, layout);
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+#[test]
+fn custom_lock() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts lock acquisitions and checks that they're released in order
+    struct CountingLock(SpinLock, AtomicUsize);
+
+    unsafe impl StaticGlobalTlsfLock for CountingLock {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const INIT: Self = Self(SpinLock::INIT, AtomicUsize::new(0));
+
+        type State = usize;
+
+        fn lock(&self) -> usize {
+            self.0.lock();
+            self.1.fetch_add(1, Ordering::Relaxed)
+        }
+
+        unsafe fn unlock(&self, state: usize) {
+            assert_eq!(self.1.load(Ordering::Relaxed), state + 1);
+            self.0.unlock(());
+        }
+    }
+
+    static_global_tlsf! {
+        static A: [u8; 4096] with CountingLock;
+    }
+
+    unsafe {
+        let layout = alloc::Layout::from_size_align(64, 8).unwrap();
+        let ptr = A.alloc(layout);
+        assert!(!ptr.is_null());
+        A.dealloc(ptr, layout);
+    }
+    assert_eq!(A.lock.1.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn priority_ceiling_lock() {
+    use std::{cell::Cell, thread_local};
+
+    const CEILING: u32 = 5;
+
+    thread_local! {
+        static PRIORITY: Cell<u32> = Cell::new(1);
+        static NUM_RAISES: Cell<usize> = Cell::new(0);
+    }
+
+    struct TestCeiling;
+
+    unsafe impl PriorityCeiling for TestCeiling {
+        type Priority = u32;
+
+        fn raise() -> u32 {
+            NUM_RAISES.with(|x| x.set(x.get() + 1));
+            PRIORITY.with(|p| p.replace(p.get().max(CEILING)))
+        }
+
+        unsafe fn restore(priority: u32) {
+            assert_eq!(PRIORITY.with(Cell::get), CEILING.max(priority));
+            PRIORITY.with(|p| p.set(priority));
+        }
+    }
+
+    static_global_tlsf! {
+        static A: [u8; 4096] with PriorityCeilingLock<TestCeiling>;
+    }
+
+    unsafe {
+        let layout = alloc::Layout::from_size_align(64, 8).unwrap();
+        let ptr = A.alloc(layout);
+        assert!(!ptr.is_null());
+        assert_eq!(PRIORITY.with(Cell::get), 1);
+
+        // A task above the ceiling keeps its priority
+        PRIORITY.with(|p| p.set(9));
+        A.dealloc(ptr, layout);
+        assert_eq!(PRIORITY.with(Cell::get), 9);
+    }
+    assert_eq!(NUM_RAISES.with(Cell::get), 2);
+
+    let layout = alloc::Layout::from_size_align(64, 8).unwrap();
+    let ptr = A.try_allocate(layout).unwrap();
+    unsafe { A.dealloc(ptr.as_ptr(), layout) };
+    assert_eq!(NUM_RAISES.with(Cell::get), 4);
+}
+
+#[cfg(feature = "allocator-api")]
+#[test]
+fn allocator_api() {
+    static_global_tlsf! {
+        static A: [u8; 65536];
+    }
+
+    let mut v: Vec<u32, _> = Vec::new_in(&A);
+    v.extend(0..1000);
+    assert!(v.iter().copied().eq(0..1000));
+    v.truncate(10);
+    v.shrink_to_fit();
+    assert!(v.iter().copied().eq(0..10));
+    drop(v);
+
+    // The entire pool is available again
+    let b = Box::new_in([1u8; 32768], &A);
+    assert!(b.iter().all(|&x| x == 1));
+}
+
+#[test]
+fn try_allocate() {
+    static_global_tlsf! {
+        static A: [u8; 4096] with SpinLock;
+    }
+
+    let layout = alloc::Layout::from_size_align(1024, 8).unwrap();
+    {
+        let _guard = A.lock_inner();
+        assert_eq!(A.try_allocate(layout), Err(TryAllocError::Contended));
+    }
+
+    let ptrs: Vec<_> = std::iter::from_fn(|| A.try_allocate(layout).ok()).collect();
+    assert!(!ptrs.is_empty());
+    assert_eq!(A.try_allocate(layout), Err(TryAllocError::Exhausted));
+    for ptr in ptrs {
+        unsafe { A.dealloc(ptr.as_ptr(), layout) };
+    }
+}
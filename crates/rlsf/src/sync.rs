@@ -0,0 +1,730 @@
+//! `SyncTlsf`: a [`Tlsf`] shareable between threads
+#[cfg(target_has_atomic = "ptr")]
+use core::mem::MaybeUninit;
+use core::{alloc, cell::UnsafeCell, fmt, ops, ptr, ptr::NonNull};
+
+#[cfg(feature = "allocator-api")]
+use crate::utils::{nonnull_slice_from_raw_parts, nonnull_slice_len};
+use crate::{
+    int::BinInteger, DefaultLock, StaticGlobalTlsfLock, ThreadCacheBackend, Tlsf, TryAllocError,
+    GRANULARITY,
+};
+
+#[cfg(target_has_atomic = "ptr")]
+use crate::{contention::ContentionCounters, ContentionStats};
+
+#[cfg(target_has_atomic = "ptr")]
+mod isr_reserve;
+
+/// [`Tlsf`] protected by a lock, which can be shared between threads and
+/// interrupt handlers.
+///
+/// `SyncTlsf` implements [`GlobalAlloc`] and, with the `allocator-api`
+/// feature, [`Allocator`] for shared references. Memory pools are supplied
+/// through [`Self::lock`] like with a bare `Tlsf`. Unlike
+/// [`StaticGlobalTlsf`], it doesn't own the memory pools, so it can manage
+/// memory regions provided by the platform at runtime.
+///
+/// The mutual exclusion is provided by `Lock`, which defaults to
+/// [`DefaultLock`] (see [`StaticGlobalTlsf`] for the alternatives). The
+/// default [`SpinLock`] backs off exponentially under contention and is
+/// suitable for `no_std` environments, but it's not safe to allocate memory
+/// from an interrupt handler that might preempt another allocator call on the
+/// same core.
+///
+/// Interrupt handlers can use [`Self::allocate_from_isr`] instead, which
+/// never waits for the lock. It falls back to an emergency reserve of
+/// fixed-size memory blocks, supplied by [`Self::reserve_for_isr`], when the
+/// lock is held by someone else or the heap is exhausted. The reserved memory
+/// blocks can be deallocated from anywhere without taking the lock.
+///
+/// [`GlobalAlloc`]: core::alloc::GlobalAlloc
+/// [`Allocator`]: core::alloc::Allocator
+/// [`StaticGlobalTlsf`]: crate::StaticGlobalTlsf
+/// [`SpinLock`]: crate::SpinLock
+///
+/// # Examples
+///
+/// ```rust
+/// use rlsf::SyncTlsf;
+/// use std::{alloc::{GlobalAlloc, Layout}, mem::MaybeUninit};
+///
+/// static TLSF: SyncTlsf<'static, u16, u16, 12, 16> = SyncTlsf::new();
+///
+/// static mut POOL: [MaybeUninit<u8>; 8192] = [MaybeUninit::uninit(); 8192];
+/// TLSF.lock().insert_free_block(unsafe { &mut POOL });
+///
+/// std::thread::spawn(|| unsafe {
+///     let layout = Layout::new::<u64>();
+///     let ptr = TLSF.alloc(layout);
+///     assert!(!ptr.is_null());
+///     TLSF.dealloc(ptr, layout);
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+pub struct SyncTlsf<
+    'pool,
+    FLBitmap,
+    SLBitmap,
+    const FLLEN: usize,
+    const SLLEN: usize,
+    Lock = DefaultLock,
+> {
+    tlsf: UnsafeCell<Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>>,
+    lock: Lock,
+    #[cfg(target_has_atomic = "ptr")]
+    isr_reserve: isr_reserve::IsrReserve,
+    #[cfg(target_has_atomic = "ptr")]
+    contention: ContentionCounters,
+}
+
+/// [`SyncTlsf`] protected by [`ParkingLotLock`](crate::ParkingLotLock), for
+/// `std` targets where threads should sleep instead of spinning while the
+/// lock is held by someone else.
+///
+/// It must not be used as the global allocator; see `ParkingLotLock`.
+///
+/// # Examples
+///
+/// ```rust
+/// use rlsf::ParkingLotTlsf;
+/// use std::{alloc::Layout, mem::MaybeUninit};
+///
+/// static TLSF: ParkingLotTlsf<'static, u16, u16, 12, 16> = ParkingLotTlsf::new();
+///
+/// static mut POOL: [MaybeUninit<u8>; 8192] = [MaybeUninit::uninit(); 8192];
+/// TLSF.lock().insert_free_block(unsafe { &mut POOL });
+///
+/// let layout = Layout::new::<u64>();
+/// let ptr = TLSF.lock().allocate(layout).unwrap();
+/// unsafe { TLSF.lock().deallocate(ptr, layout.align()) };
+/// ```
+#[cfg(feature = "parking_lot")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "parking_lot")))]
+pub type ParkingLotTlsf<'pool, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize> =
+    SyncTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, crate::ParkingLotLock>;
+
+// Safety: `Tlsf` is `Send`, and `lock` serializes accesses to it
+unsafe impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock: StaticGlobalTlsfLock>
+    Sync for SyncTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+{
+}
+
+impl<'pool, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock>
+    SyncTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    const_fn! {
+        /// Construct an empty instance of `Self`.
+        #[inline]
+        pub const fn new() -> Self {
+            Self::from_tlsf(Tlsf::new())
+        }
+    }
+
+    const_fn! {
+        /// Wrap an existing [`Tlsf`].
+        #[inline]
+        pub const fn from_tlsf(tlsf: Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>) -> Self {
+            Self {
+                tlsf: UnsafeCell::new(tlsf),
+                lock: lock_init_value!(Lock),
+                #[cfg(target_has_atomic = "ptr")]
+                isr_reserve: isr_reserve::IsrReserve::new(),
+                #[cfg(target_has_atomic = "ptr")]
+                contention: ContentionCounters::NEW,
+            }
+        }
+    }
+
+    /// Unwrap the [`Tlsf`]. The emergency reserve is discarded.
+    #[inline]
+    pub fn into_inner(self) -> Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN> {
+        self.tlsf.into_inner()
+    }
+
+    /// Get a mutable reference to the [`Tlsf`] without locking.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN> {
+        self.tlsf.get_mut()
+    }
+
+    /// Acquire the lock and get access to the [`Tlsf`], e.g., to supply
+    /// memory pools. The lock is released when the returned guard is dropped.
+    #[inline]
+    pub fn lock(&self) -> SyncTlsfGuard<'_, 'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock> {
+        #[cfg(target_has_atomic = "ptr")]
+        let state = match self.lock.try_lock() {
+            Some(state) => {
+                self.contention.record_lock(false);
+                state
+            }
+            None => {
+                self.contention.record_lock(true);
+                self.lock.lock()
+            }
+        };
+        #[cfg(not(target_has_atomic = "ptr"))]
+        let state = self.lock.lock();
+        SyncTlsfGuard { owner: self, state }
+    }
+
+    /// Acquire the lock if it's available without waiting.
+    ///
+    /// This always returns `None` if `Lock` doesn't implement
+    /// [`StaticGlobalTlsfLock::try_lock`].
+    #[inline]
+    pub fn try_lock(
+        &self,
+    ) -> Option<SyncTlsfGuard<'_, 'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>> {
+        let state = self.lock.try_lock()?;
+        #[cfg(target_has_atomic = "ptr")]
+        self.contention.record_lock(false);
+        Some(SyncTlsfGuard { owner: self, state })
+    }
+
+    /// Get the contention counters of `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::SyncTlsf;
+    ///
+    /// let tlsf: SyncTlsf<'_, u16, u16, 12, 16> = SyncTlsf::new();
+    /// drop(tlsf.lock());
+    /// let stats = tlsf.contention_stats();
+    /// assert_eq!(stats.lock_acquisitions, 1);
+    /// assert!(stats.lock_waits <= 1);
+    /// ```
+    #[cfg(target_has_atomic = "ptr")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(target_has_atomic = "ptr")))]
+    pub fn contention_stats(&self) -> ContentionStats {
+        self.contention.load()
+    }
+
+    /// Get the contention counters of `self` to update.
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline]
+    pub(crate) fn contention(&self) -> &ContentionCounters {
+        &self.contention
+    }
+
+    /// Emit the statistics of `self` through the [`metrics`] facade.
+    ///
+    /// The gauges `rlsf.bytes_in_use`, `rlsf.peak_bytes_in_use`,
+    /// `rlsf.bytes_mapped` (the total size of the memory pools),
+    /// `rlsf.free_bytes`, and `rlsf.live_allocations` and the counter
+    /// `rlsf.alloc_errors` are updated from [`Tlsf::counters`] and the
+    /// related methods, as well as the counters of
+    /// [`Self::contention_stats`] if it's available. See
+    /// [`GlobalTlsf::report_metrics`](crate::GlobalTlsf::report_metrics) for
+    /// the full list.
+    ///
+    /// The lock is released before the metrics are emitted, so the metrics
+    /// recorder may allocate from `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::SyncTlsf;
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 1024];
+    /// let tlsf: SyncTlsf<'_, u16, u16, 12, 16> = SyncTlsf::new();
+    /// tlsf.lock().insert_free_block(&mut pool);
+    ///
+    /// let ptr = tlsf.lock().allocate(Layout::new::<u64>()).unwrap();
+    /// tlsf.report_metrics();
+    /// # unsafe { tlsf.lock().deallocate(ptr, 8) };
+    /// ```
+    #[cfg(feature = "metrics")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "metrics")))]
+    pub fn report_metrics(&self) {
+        let (in_use, peak_in_use, utilization, counters) = {
+            let tlsf = self.lock();
+            (
+                tlsf.in_use(),
+                tlsf.peak_in_use(),
+                tlsf.utilization(),
+                tlsf.counters(),
+            )
+        };
+        metrics::gauge!("rlsf.bytes_in_use", in_use as f64);
+        metrics::gauge!("rlsf.peak_bytes_in_use", peak_in_use as f64);
+        metrics::gauge!("rlsf.bytes_mapped", utilization.pool_bytes as f64);
+        metrics::gauge!("rlsf.free_bytes", utilization.free_bytes as f64);
+        metrics::gauge!(
+            "rlsf.live_allocations",
+            counters.allocations.wrapping_sub(counters.deallocations) as f64
+        );
+        metrics::absolute_counter!("rlsf.alloc_errors", counters.failed_allocations as u64);
+        #[cfg(target_has_atomic = "ptr")]
+        self.contention_stats().report_metrics();
+    }
+
+    /// Attempt to allocate memory without waiting for the lock.
+    ///
+    /// Returns [`TryAllocError::Contended`] if the lock is held by someone
+    /// else (or `Lock` doesn't implement [`StaticGlobalTlsfLock::try_lock`]),
+    /// letting soft real-time code fall back to another memory source instead
+    /// of blocking. The returned memory block can be deallocated in the same
+    /// ways as one allocated through [`GlobalAlloc`].
+    ///
+    /// [`GlobalAlloc`]: core::alloc::GlobalAlloc
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::{SpinLock, SyncTlsf, TryAllocError};
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// static TLSF: SyncTlsf<'static, u16, u16, 12, 16, SpinLock> = SyncTlsf::new();
+    ///
+    /// static mut POOL: [MaybeUninit<u8>; 4096] = [MaybeUninit::uninit(); 4096];
+    /// TLSF.lock().insert_free_block(unsafe { &mut POOL });
+    ///
+    /// let layout = Layout::new::<u64>();
+    /// let guard = TLSF.lock();
+    /// assert_eq!(TLSF.try_allocate(layout), Err(TryAllocError::Contended));
+    /// drop(guard);
+    ///
+    /// let ptr = TLSF.try_allocate(layout).unwrap();
+    /// unsafe { TLSF.lock().deallocate(ptr, layout.align()) };
+    /// ```
+    #[inline]
+    pub fn try_allocate(&self, layout: alloc::Layout) -> Result<NonNull<u8>, TryAllocError> {
+        self.try_lock()
+            .ok_or(TryAllocError::Contended)?
+            .allocate(layout)
+            .ok_or(TryAllocError::Exhausted)
+    }
+
+    /// Supply the emergency reserve for [`Self::allocate_from_isr`] by
+    /// dividing `region` into memory blocks of `block_size` bytes (rounded
+    /// up to [`GRANULARITY`]), up to `usize::BITS` of them.
+    ///
+    /// Returns the number of the memory blocks, or zero if `region` is too
+    /// small or the reserve has already been supplied.
+    #[cfg(target_has_atomic = "ptr")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(target_has_atomic = "ptr")))]
+    pub fn reserve_for_isr(
+        &self,
+        region: &'pool mut [MaybeUninit<u8>],
+        block_size: usize,
+    ) -> usize {
+        self.isr_reserve.init(region, block_size)
+    }
+
+    /// Allocate memory without waiting for the lock, e.g., in an interrupt
+    /// handler.
+    ///
+    /// This allocates from the heap if the lock can be acquired immediately
+    /// (see [`StaticGlobalTlsfLock::try_lock`]), and otherwise (or if the
+    /// heap is exhausted) from the emergency reserve supplied by
+    /// [`Self::reserve_for_isr`]. Allocations with sizes up to the reserve's
+    /// block size and alignments up to [`GRANULARITY`] are guaranteed to
+    /// succeed while fewer than that many memory blocks from the reserve are
+    /// in use.
+    ///
+    /// The returned memory block can be deallocated through
+    /// [`GlobalAlloc`] or [`Allocator`], but not through the [`Tlsf`]
+    /// returned by [`Self::lock`].
+    ///
+    /// [`GlobalAlloc`]: core::alloc::GlobalAlloc
+    /// [`Allocator`]: core::alloc::Allocator
+    #[cfg(target_has_atomic = "ptr")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(target_has_atomic = "ptr")))]
+    pub fn allocate_from_isr(&self, layout: alloc::Layout) -> Option<NonNull<u8>> {
+        self.try_allocate(layout)
+            .ok()
+            .or_else(|| self.isr_reserve.allocate(layout))
+    }
+
+    /// Get the size of the memory block `ptr` if it's in the emergency
+    /// reserve.
+    #[inline]
+    fn reserved_block_size(&self, ptr: NonNull<u8>) -> Option<usize> {
+        #[cfg(target_has_atomic = "ptr")]
+        {
+            self.isr_reserve.block_size_of(ptr)
+        }
+        #[cfg(not(target_has_atomic = "ptr"))]
+        {
+            let _ = ptr;
+            None
+        }
+    }
+
+    /// Deallocate the memory block `ptr` if it's in the emergency reserve.
+    /// Returns `false` if it isn't.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a live allocation made by `self`.
+    #[inline]
+    unsafe fn deallocate_reserved(&self, ptr: NonNull<u8>) -> bool {
+        #[cfg(target_has_atomic = "ptr")]
+        {
+            self.isr_reserve.deallocate(ptr)
+        }
+        #[cfg(not(target_has_atomic = "ptr"))]
+        {
+            let _ = ptr;
+            false
+        }
+    }
+
+    /// Reallocate the memory block `ptr` of `block_size` bytes in the
+    /// emergency reserve, moving it to the heap if `new_layout` doesn't fit
+    /// in it.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a live allocation in the emergency reserve made by
+    /// `self`.
+    unsafe fn reallocate_reserved(
+        &self,
+        ptr: NonNull<u8>,
+        block_size: usize,
+        new_layout: alloc::Layout,
+    ) -> Option<NonNull<u8>> {
+        if new_layout.size() <= block_size && new_layout.align() <= GRANULARITY {
+            return Some(ptr);
+        }
+        let new_ptr = self.lock().allocate(new_layout)?;
+        // Safety: the previously allocated block cannot overlap the newly
+        //         allocated block. `block_size` is the recorded size, which
+        //         doesn't trust `old_layout.size()`.
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr(),
+            block_size.min(new_layout.size()),
+        );
+        self.deallocate_reserved(ptr);
+        Some(new_ptr)
+    }
+}
+
+impl<'pool, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock> Default
+    for SyncTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock> fmt::Debug
+    for SyncTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyncTlsf").finish_non_exhaustive()
+    }
+}
+
+/// The lock guard of [`SyncTlsf`], returned by [`SyncTlsf::lock`].
+pub struct SyncTlsfGuard<
+    'a,
+    'pool,
+    FLBitmap,
+    SLBitmap,
+    const FLLEN: usize,
+    const SLLEN: usize,
+    Lock: StaticGlobalTlsfLock,
+> {
+    owner: &'a SyncTlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>,
+    state: Lock::State,
+}
+
+impl<'pool, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock> ops::Deref
+    for SyncTlsfGuard<'_, 'pool, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    Lock: StaticGlobalTlsfLock,
+{
+    type Target = Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // Safety: Protected by `lock`
+        unsafe { &*self.owner.tlsf.get() }
+    }
+}
+
+impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock> ops::DerefMut
+    for SyncTlsfGuard<'_, '_, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: Protected by `lock`
+        unsafe { &mut *self.owner.tlsf.get() }
+    }
+}
+
+impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock> Drop
+    for SyncTlsfGuard<'_, '_, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    fn drop(&mut self) {
+        // Safety: `state` was returned by `lock`
+        unsafe { self.owner.lock.unlock(self.state) };
+    }
+}
+
+impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock> fmt::Debug
+    for SyncTlsfGuard<'_, '_, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    Lock: StaticGlobalTlsfLock,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyncTlsfGuard").finish_non_exhaustive()
+    }
+}
+
+unsafe impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock> alloc::GlobalAlloc
+    for SyncTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    unsafe fn alloc(&self, layout: alloc::Layout) -> *mut u8 {
+        self.lock()
+            .allocate(layout)
+            .map(NonNull::as_ptr)
+            .unwrap_or(ptr::null_mut())
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: alloc::Layout) {
+        // Safety: All allocations are non-null
+        let ptr = NonNull::new_unchecked(ptr);
+        if self.deallocate_reserved(ptr) {
+            return;
+        }
+        // Safety: `ptr` denotes a previous allocation with alignment
+        //         `layout.align()`
+        self.lock().deallocate(ptr, layout.align());
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: alloc::Layout, new_size: usize) -> *mut u8 {
+        // Safety: All allocations are non-null
+        let ptr = NonNull::new_unchecked(ptr);
+        // Safety: `layout.align()` is a power of two, and the size parameter's
+        //         validity is upheld by the caller
+        let new_layout = alloc::Layout::from_size_align_unchecked(new_size, layout.align());
+        if let Some(block_size) = self.reserved_block_size(ptr) {
+            return self
+                .reallocate_reserved(ptr, block_size, new_layout)
+                .map(NonNull::as_ptr)
+                .unwrap_or(ptr::null_mut());
+        }
+        // Safety: `ptr` denotes a previous allocation with alignment
+        //         `layout.align()`
+        self.lock()
+            .reallocate(ptr, new_layout)
+            .map(NonNull::as_ptr)
+            .unwrap_or(ptr::null_mut())
+    }
+}
+
+unsafe impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock> ThreadCacheBackend
+    for SyncTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    fn allocate_batch(&self, layout: alloc::Layout, count: usize, mut f: impl FnMut(NonNull<u8>)) {
+        let mut tlsf = self.lock();
+        for _ in 0..count {
+            match tlsf.allocate(layout) {
+                Some(ptr) => f(ptr),
+                None => break,
+            }
+        }
+    }
+
+    unsafe fn deallocate_batch(&self, align: usize, ptrs: impl Iterator<Item = NonNull<u8>>) {
+        let mut tlsf = self.lock();
+        for ptr in ptrs {
+            if self.deallocate_reserved(ptr) {
+                continue;
+            }
+            // The memory blocks with alignments smaller than `GRANULARITY`
+            // are interchangeable, but their recorded alignments (debug
+            // builds) might differ from `align`
+            if align < GRANULARITY {
+                // Safety: `ptr` denotes a previous allocation
+                tlsf.deallocate_unknown_align(ptr);
+            } else {
+                // Safety: `ptr` denotes a previous allocation with alignment
+                //         `align`
+                tlsf.deallocate(ptr, align);
+            }
+        }
+    }
+
+    #[inline]
+    unsafe fn usable_size(&self, ptr: NonNull<u8>, align: usize) -> usize {
+        if let Some(block_size) = self.reserved_block_size(ptr) {
+            return block_size;
+        }
+        // Safety: `ptr` denotes a previous allocation with alignment `align`
+        Tlsf::<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(ptr, align)
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "allocator-api")))]
+unsafe impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock> alloc::Allocator
+    for &SyncTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    #[inline]
+    fn allocate(&self, layout: alloc::Layout) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        let ptr = self.lock().allocate(layout).ok_or(alloc::AllocError)?;
+        // Safety: `ptr` denotes a previous allocation with alignment
+        //         `layout.align()`
+        let size = unsafe {
+            Tlsf::<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(ptr, layout.align())
+        };
+        Ok(nonnull_slice_from_raw_parts(ptr, size))
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: alloc::Layout) {
+        if self.deallocate_reserved(ptr) {
+            return;
+        }
+        // Safety: `ptr` denotes a previous allocation with alignment
+        //         `layout.align()`
+        self.lock().deallocate(ptr, layout.align());
+    }
+
+    #[inline]
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        self.reallocate_for_allocator(ptr, old_layout, new_layout)
+    }
+
+    #[inline]
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        let new_ptr = self.reallocate_for_allocator(ptr, old_layout, new_layout)?;
+        let new_size = nonnull_slice_len(new_ptr);
+        // Safety: The range is in the allocation
+        (new_ptr.as_ptr() as *mut u8)
+            .add(old_layout.size())
+            .write_bytes(0, new_size - old_layout.size());
+        Ok(new_ptr)
+    }
+
+    #[inline]
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        self.reallocate_for_allocator(ptr, old_layout, new_layout)
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize, Lock>
+    SyncTlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN, Lock>
+where
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    Lock: StaticGlobalTlsfLock,
+{
+    /// Implements [`alloc::Allocator::grow`] and [`alloc::Allocator::shrink`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a previous allocation with `old_layout`.
+    unsafe fn reallocate_for_allocator(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: alloc::Layout,
+        new_layout: alloc::Layout,
+    ) -> Result<NonNull<[u8]>, alloc::AllocError> {
+        if let Some(block_size) = self.reserved_block_size(ptr) {
+            let new_ptr = self
+                .reallocate_reserved(ptr, block_size, new_layout)
+                .ok_or(alloc::AllocError)?;
+            let size = if new_ptr == ptr {
+                block_size
+            } else {
+                // Safety: `new_ptr` denotes a previous allocation with
+                //         alignment `new_layout.align()`
+                Tlsf::<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(
+                    new_ptr,
+                    new_layout.align(),
+                )
+            };
+            return Ok(nonnull_slice_from_raw_parts(new_ptr, size));
+        }
+
+        let mut inner = self.lock();
+        let new_ptr = if old_layout.align() == new_layout.align() {
+            // Safety: `ptr` denotes a previous allocation with alignment
+            //         `new_layout.align()`
+            inner.reallocate(ptr, new_layout).ok_or(alloc::AllocError)?
+        } else {
+            // `Tlsf::reallocate` can't change the alignment
+            let new_ptr = inner.allocate(new_layout).ok_or(alloc::AllocError)?;
+            // Safety: the previously allocated block cannot overlap the
+            //         newly allocated block. The old size is the recorded
+            //         one, which doesn't trust `old_layout.size()`.
+            let old_size = Tlsf::<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(
+                ptr,
+                old_layout.align(),
+            );
+            ptr::copy_nonoverlapping(
+                ptr.as_ptr(),
+                new_ptr.as_ptr(),
+                old_size.min(new_layout.size()),
+            );
+            // Safety: `ptr` denotes a previous allocation with alignment
+            //         `old_layout.align()`
+            inner.deallocate(ptr, old_layout.align());
+            new_ptr
+        };
+        // Safety: `new_ptr` denotes a previous allocation with alignment
+        //         `new_layout.align()`
+        let size = Tlsf::<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>::size_of_allocation(
+            new_ptr,
+            new_layout.align(),
+        );
+        Ok(nonnull_slice_from_raw_parts(new_ptr, size))
+    }
+}
+
+#[cfg(test)]
+mod tests;
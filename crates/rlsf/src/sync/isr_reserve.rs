@@ -0,0 +1,141 @@
+//! The emergency reserve of [`SyncTlsf`](super::SyncTlsf)
+use core::{
+    alloc::Layout,
+    mem::MaybeUninit,
+    ptr::{null_mut, NonNull},
+};
+
+use crate::{
+    primitives::atomic::{AtomicPtr, AtomicUsize, Ordering},
+    utils::ptr_with_addr,
+    GRANULARITY,
+};
+
+/// A lock-free pool of up to `usize::BITS` fixed-size memory blocks.
+pub(super) struct IsrReserve {
+    /// The first memory block, or null if the region is yet to be supplied
+    start: AtomicPtr<u8>,
+    /// The end address of the last memory block. Stored after the other
+    /// fields, so a non-zero value indicates they're valid.
+    end: AtomicUsize,
+    block_size: AtomicUsize,
+    /// Bit `i` is set iff the `i`-th memory block is free
+    free: AtomicUsize,
+}
+
+impl IsrReserve {
+    const_fn! {
+        pub(super) const fn new() -> Self {
+            Self {
+                start: AtomicPtr::new(null_mut()),
+                end: AtomicUsize::new(0),
+                block_size: AtomicUsize::new(0),
+                free: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    /// Divide `region` into memory blocks of `block_size` bytes (rounded up
+    /// to `GRANULARITY`). Returns the number of the memory blocks, or zero if
+    /// `region` is too small or a region has already been supplied.
+    pub(super) fn init(&self, region: &mut [MaybeUninit<u8>], block_size: usize) -> usize {
+        let block_size = match block_size.max(1).checked_add(GRANULARITY - 1) {
+            Some(x) => x & !(GRANULARITY - 1),
+            None => return 0,
+        };
+        let region_ptr = region.as_mut_ptr() as *mut u8;
+        let region_start = region_ptr as usize;
+        let region_end = region_start + region.len();
+        let start = match region_start.checked_add(GRANULARITY - 1) {
+            Some(x) => x & !(GRANULARITY - 1),
+            None => return 0,
+        };
+        let num_blocks = (region_end.saturating_sub(start) / block_size).min(usize::BITS as usize);
+        if num_blocks == 0 {
+            return 0;
+        }
+
+        if self
+            .start
+            .compare_exchange(
+                null_mut(),
+                ptr_with_addr(region_ptr, start),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            return 0;
+        }
+        self.block_size.store(block_size, Ordering::Relaxed);
+        self.end
+            .store(start + num_blocks * block_size, Ordering::Release);
+        self.free.store(
+            usize::MAX >> (usize::BITS as usize - num_blocks),
+            Ordering::Release,
+        );
+        num_blocks
+    }
+
+    /// Allocate a memory block. Returns `None` if `layout` doesn't fit in a
+    /// memory block or they are all in use.
+    #[inline]
+    pub(super) fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let mut free = self.free.load(Ordering::Acquire);
+        if free == 0 {
+            return None;
+        }
+        let block_size = self.block_size.load(Ordering::Relaxed);
+        if layout.size() > block_size || layout.align() > GRANULARITY {
+            return None;
+        }
+
+        loop {
+            let i = free.trailing_zeros() as usize;
+            match self.free.compare_exchange_weak(
+                free,
+                free & !(1 << i),
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let start = self.start.load(Ordering::Relaxed);
+                    return NonNull::new(start.wrapping_add(i * block_size));
+                }
+                Err(new_free) if new_free != 0 => free = new_free,
+                Err(_) => return None,
+            }
+        }
+    }
+
+    /// Get the size of the memory block `ptr` if it's in `self`.
+    #[inline]
+    pub(super) fn block_size_of(&self, ptr: NonNull<u8>) -> Option<usize> {
+        let addr = ptr.as_ptr() as usize;
+        // `Acquire` ensures `start` is valid if `end` is non-zero
+        if addr < self.end.load(Ordering::Acquire)
+            && addr >= self.start.load(Ordering::Relaxed) as usize
+        {
+            Some(self.block_size.load(Ordering::Relaxed))
+        } else {
+            None
+        }
+    }
+
+    /// Deallocate the memory block `ptr` if it's in `self`. Returns `false`
+    /// if it isn't.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a live allocation.
+    #[inline]
+    pub(super) unsafe fn deallocate(&self, ptr: NonNull<u8>) -> bool {
+        let block_size = match self.block_size_of(ptr) {
+            Some(x) => x,
+            None => return false,
+        };
+        let i = (ptr.as_ptr() as usize - self.start.load(Ordering::Relaxed) as usize) / block_size;
+        self.free.fetch_or(1 << i, Ordering::Release);
+        true
+    }
+}
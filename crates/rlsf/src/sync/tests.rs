@@ -0,0 +1,356 @@
+use std::{alloc::GlobalAlloc, mem::MaybeUninit, prelude::v1::*, thread, vec};
+
+use super::*;
+
+type TheSyncTlsf = SyncTlsf<'static, u16, u16, 12, 16>;
+
+/// `DefaultLock` might always succeed in `try_lock` (e.g.,
+/// `CriticalSectionLock`)
+type SpinSyncTlsf = SyncTlsf<'static, u16, u16, 12, 16, crate::SpinLock>;
+
+fn new_pool(size: usize) -> &'static mut [MaybeUninit<u8>] {
+    Box::leak(vec![MaybeUninit::uninit(); size].into_boxed_slice())
+}
+
+#[test]
+fn concurrent_alloc() {
+    static TLSF: TheSyncTlsf = SyncTlsf::new();
+    TLSF.lock().insert_free_block(new_pool(1 << 16));
+
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            thread::spawn(move || unsafe {
+                let layout = alloc::Layout::from_size_align(16 + i * 8, 8).unwrap();
+                for _ in 0..1000 {
+                    let ptr = TLSF.alloc(layout);
+                    assert!(!ptr.is_null());
+                    ptr.write_bytes(i as u8, layout.size());
+                    let ptr = TLSF.realloc(ptr, layout, layout.size() * 2);
+                    assert!(!ptr.is_null());
+                    for k in 0..layout.size() {
+                        assert_eq!(*ptr.add(k), i as u8);
+                    }
+                    TLSF.dealloc(
+                        ptr,
+                        alloc::Layout::from_size_align(layout.size() * 2, 8).unwrap(),
+                    );
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // Everything has been deallocated
+    let layout = alloc::Layout::from_size_align(1 << 15, 8).unwrap();
+    unsafe {
+        let ptr = TLSF.alloc(layout);
+        assert!(!ptr.is_null());
+        TLSF.dealloc(ptr, layout);
+    }
+}
+
+#[test]
+fn into_inner() {
+    let mut tlsf = TheSyncTlsf::new();
+    tlsf.get_mut().insert_free_block(new_pool(4096));
+
+    let layout = alloc::Layout::new::<u64>();
+    let ptr = unsafe { tlsf.alloc(layout) };
+    assert!(!ptr.is_null());
+
+    let mut tlsf = tlsf.into_inner();
+    unsafe { tlsf.deallocate(NonNull::new(ptr).unwrap(), layout.align()) };
+}
+
+#[cfg(feature = "allocator-api")]
+#[test]
+fn allocator() {
+    let tlsf = TheSyncTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(4096));
+
+    let mut v = Vec::new_in(&tlsf);
+    v.extend(0..100u32);
+    v.shrink_to_fit();
+    assert_eq!(v.iter().sum::<u32>(), 4950);
+
+    let b = Box::new_in(42u64, &tlsf);
+    assert_eq!(*b, 42);
+}
+
+#[cfg(feature = "lock_api")]
+#[test]
+fn raw_mutex() {
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    static NUM_ACQUISITIONS: AtomicUsize = AtomicUsize::new(0);
+
+    /// A spin mutex counting lock acquisitions
+    struct CountingRawMutex(AtomicBool);
+
+    unsafe impl lock_api::RawMutex for CountingRawMutex {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const INIT: Self = Self(AtomicBool::new(false));
+
+        type GuardMarker = lock_api::GuardSend;
+
+        fn lock(&self) {
+            while !self.try_lock() {
+                std::hint::spin_loop();
+            }
+        }
+
+        fn try_lock(&self) -> bool {
+            let acquired = self
+                .0
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok();
+            if acquired {
+                NUM_ACQUISITIONS.fetch_add(1, Ordering::Relaxed);
+            }
+            acquired
+        }
+
+        unsafe fn unlock(&self) {
+            self.0.store(false, Ordering::Release);
+        }
+    }
+
+    static TLSF: SyncTlsf<'static, u16, u16, 12, 16, crate::RawMutexLock<CountingRawMutex>> =
+        SyncTlsf::new();
+    TLSF.lock().insert_free_block(new_pool(1 << 14));
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            thread::spawn(|| unsafe {
+                let layout = alloc::Layout::new::<u64>();
+                for _ in 0..100 {
+                    let ptr = TLSF.alloc(layout);
+                    assert!(!ptr.is_null());
+                    TLSF.dealloc(ptr, layout);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(NUM_ACQUISITIONS.load(Ordering::Relaxed), 1 + 4 * 100 * 2);
+}
+
+#[test]
+fn isr_reserve() {
+    let tlsf = SpinSyncTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(4096));
+    assert_eq!(
+        tlsf.reserve_for_isr(new_pool(4 * 64 + GRANULARITY - 1), 64),
+        4
+    );
+    assert_eq!(tlsf.reserve_for_isr(new_pool(4096), 64), 0);
+
+    let layout = alloc::Layout::from_size_align(48, 8).unwrap();
+
+    // The heap is used if the lock is free
+    let ptr = tlsf.allocate_from_isr(layout).unwrap();
+    assert!(tlsf.reserved_block_size(ptr).is_none());
+    unsafe { tlsf.dealloc(ptr.as_ptr(), layout) };
+
+    // The reserve is used while the lock is held
+    let ptrs: Vec<_> = {
+        let _guard = tlsf.lock();
+        let ptrs: Vec<_> = (0..4)
+            .map(|_| tlsf.allocate_from_isr(layout).unwrap())
+            .collect();
+        assert!(tlsf.allocate_from_isr(layout).is_none());
+        ptrs
+    };
+    for &ptr in ptrs.iter() {
+        assert_eq!(tlsf.reserved_block_size(ptr), Some(64));
+        unsafe { ptr.as_ptr().write_bytes(0xa5, layout.size()) };
+    }
+
+    // Reallocation within the memory block doesn't move it
+    let ptr = unsafe { tlsf.realloc(ptrs[0].as_ptr(), layout, 64) };
+    assert_eq!(ptr, ptrs[0].as_ptr());
+
+    // Reallocation beyond the memory block moves it to the heap and frees
+    // the reserved memory block
+    let big_ptr = unsafe { tlsf.realloc(ptr, layout, 256) };
+    assert!(!big_ptr.is_null());
+    assert!(tlsf
+        .reserved_block_size(NonNull::new(big_ptr).unwrap())
+        .is_none());
+    assert_eq!(unsafe { *big_ptr.add(47) }, 0xa5);
+    unsafe { tlsf.dealloc(big_ptr, alloc::Layout::from_size_align(256, 8).unwrap()) };
+
+    // Deallocation returns the memory blocks to the reserve
+    for &ptr in ptrs[1..].iter() {
+        unsafe { tlsf.dealloc(ptr.as_ptr(), layout) };
+    }
+    {
+        let _guard = tlsf.lock();
+        for _ in 0..4 {
+            assert!(tlsf.allocate_from_isr(layout).is_some());
+        }
+    }
+}
+
+#[test]
+fn isr_reserve_oversized() {
+    let tlsf = SpinSyncTlsf::new();
+    assert_eq!(tlsf.reserve_for_isr(new_pool(8), 64), 0);
+    assert!(tlsf.reserve_for_isr(new_pool(1024), 64) >= 1024 / 64 - 1);
+
+    let _guard = tlsf.lock();
+    let big = alloc::Layout::from_size_align(65, 8).unwrap();
+    assert!(tlsf.allocate_from_isr(big).is_none());
+    let aligned = alloc::Layout::from_size_align(8, GRANULARITY * 2).unwrap();
+    assert!(tlsf.allocate_from_isr(aligned).is_none());
+}
+
+#[test]
+fn try_allocate() {
+    let tlsf = SpinSyncTlsf::new();
+    let layout = alloc::Layout::from_size_align(64, 8).unwrap();
+    assert_eq!(tlsf.try_allocate(layout), Err(TryAllocError::Exhausted));
+
+    tlsf.lock().insert_free_block(new_pool(4096));
+    {
+        let _guard = tlsf.lock();
+        assert!(tlsf.try_lock().is_none());
+        assert_eq!(tlsf.try_allocate(layout), Err(TryAllocError::Contended));
+    }
+
+    let ptr = tlsf.try_allocate(layout).unwrap();
+    unsafe { tlsf.dealloc(ptr.as_ptr(), layout) };
+}
+
+#[test]
+fn contention_stats() {
+    static TLSF: SpinSyncTlsf = SyncTlsf::new();
+    drop(TLSF.lock());
+    assert_eq!(
+        (
+            TLSF.contention_stats().lock_acquisitions,
+            TLSF.contention_stats().lock_waits
+        ),
+        (1, 0)
+    );
+
+    let guard = TLSF.lock();
+    let thread = thread::spawn(|| drop(TLSF.lock()));
+    while TLSF.contention_stats().lock_waits == 0 {
+        thread::yield_now();
+    }
+    drop(guard);
+    thread.join().unwrap();
+
+    let stats = TLSF.contention_stats();
+    assert_eq!((stats.lock_acquisitions, stats.lock_waits), (3, 1));
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn report_metrics() {
+    use metrics::{
+        Counter, CounterFn, Gauge, GaugeFn, Histogram, Key, KeyName, Recorder, SharedString, Unit,
+    };
+    use std::{collections::HashMap, sync::Arc, sync::Mutex};
+
+    type Values = Mutex<HashMap<String, f64>>;
+
+    struct Handle(&'static Values, String);
+
+    impl CounterFn for Handle {
+        fn increment(&self, _: u64) {
+            unreachable!()
+        }
+        fn absolute(&self, value: u64) {
+            self.0.lock().unwrap().insert(self.1.clone(), value as f64);
+        }
+    }
+
+    impl GaugeFn for Handle {
+        fn increment(&self, _: f64) {
+            unreachable!()
+        }
+        fn decrement(&self, _: f64) {
+            unreachable!()
+        }
+        fn set(&self, value: f64) {
+            self.0.lock().unwrap().insert(self.1.clone(), value);
+        }
+    }
+
+    struct TestRecorder(&'static Values);
+
+    impl Recorder for TestRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn register_counter(&self, key: &Key) -> Counter {
+            Counter::from_arc(Arc::new(Handle(self.0, key.name().to_owned())))
+        }
+        fn register_gauge(&self, key: &Key) -> Gauge {
+            Gauge::from_arc(Arc::new(Handle(self.0, key.name().to_owned())))
+        }
+        fn register_histogram(&self, _: &Key) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    let values: &'static Values = Box::leak(Box::default());
+    metrics::set_boxed_recorder(Box::new(TestRecorder(values))).unwrap();
+
+    let tlsf = TheSyncTlsf::new();
+    tlsf.lock().insert_free_block(new_pool(1 << 16));
+    let layout = alloc::Layout::from_size_align(100, 1).unwrap();
+    let ptr = tlsf.lock().allocate(layout).unwrap();
+    assert!(tlsf
+        .lock()
+        .allocate(alloc::Layout::from_size_align(1 << 20, 1).unwrap())
+        .is_none());
+
+    tlsf.report_metrics();
+
+    let (in_use, utilization) = {
+        let tlsf = tlsf.lock();
+        (tlsf.in_use(), tlsf.utilization())
+    };
+    let values = values.lock().unwrap();
+    assert_eq!(values["rlsf.bytes_in_use"], in_use as f64);
+    assert_eq!(values["rlsf.peak_bytes_in_use"], in_use as f64);
+    assert_eq!(values["rlsf.bytes_mapped"], utilization.pool_bytes as f64);
+    assert_eq!(values["rlsf.free_bytes"], utilization.free_bytes as f64);
+    assert_eq!(values["rlsf.live_allocations"], 1.0);
+    assert_eq!(values["rlsf.alloc_errors"], 1.0);
+    #[cfg(target_has_atomic = "ptr")]
+    assert_eq!(values["rlsf.lock_acquisitions"], 4.0);
+
+    unsafe { tlsf.lock().deallocate(ptr, 1) };
+}
+
+#[cfg(feature = "parking_lot")]
+#[test]
+fn parking_lot() {
+    static TLSF: ParkingLotTlsf<'static, u16, u16, 12, 16> = ParkingLotTlsf::new();
+    TLSF.lock().insert_free_block(new_pool(1 << 14));
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            thread::spawn(|| unsafe {
+                let layout = alloc::Layout::new::<u64>();
+                for _ in 0..100 {
+                    let ptr = TLSF.alloc(layout);
+                    assert!(!ptr.is_null());
+                    TLSF.dealloc(ptr, layout);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert!(TLSF.try_lock().is_some());
+}
@@ -0,0 +1,293 @@
+//! `ThreadCache`: a cache of small memory blocks fronting a shared allocator
+use core::{
+    alloc::Layout,
+    fmt,
+    ptr::{self, NonNull},
+};
+
+use crate::GRANULARITY;
+
+/// The number of size classes. Class `i` holds memory blocks with at least
+/// `(i + 1) * GRANULARITY` usable bytes.
+const NUM_CLASSES: usize = 8;
+
+/// The maximum number of memory blocks in a magazine.
+const MAGAZINE_CAPACITY: usize = 32;
+
+/// The number of memory blocks moved between a magazine and the backend at
+/// once.
+const BATCH_SIZE: usize = MAGAZINE_CAPACITY / 2;
+
+/// The alignment of the memory blocks managed by thread caches. Allocations
+/// with an alignment smaller than `GRANULARITY` have their headers right
+/// before their payloads, so they are interchangeable.
+const BLOCK_ALIGN: usize = 1;
+
+/// A shared allocator that [`ThreadCache`] can front.
+///
+/// # Safety
+///
+///  - The memory blocks passed to the callback of [`Self::allocate_batch`]
+///    must be valid allocations with the given layout.
+///  - [`Self::deallocate_batch`] must accept memory blocks allocated by
+///    `allocate_batch` with any layout whose alignment is smaller than
+///    [`GRANULARITY`] by specifying an alignment smaller than
+///    `GRANULARITY`.
+///  - [`Self::usable_size`] must not return a value larger than the actual
+///    usable size of the memory block.
+pub unsafe trait ThreadCacheBackend {
+    /// Allocate up to `count` memory blocks with `layout`, passing each of
+    /// them to `f`. This is expected to take a lock just once.
+    fn allocate_batch(&self, layout: Layout, count: usize, f: impl FnMut(NonNull<u8>));
+
+    /// Deallocate the memory blocks yielded by `ptrs`. This is expected to
+    /// take a lock just once.
+    ///
+    /// # Safety
+    ///
+    /// The memory blocks must have been allocated by `self` with alignment
+    /// `align`.
+    unsafe fn deallocate_batch(&self, align: usize, ptrs: impl Iterator<Item = NonNull<u8>>);
+
+    /// Get the usable size of a memory block.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a live memory block allocated by `self` with
+    /// alignment `align`.
+    unsafe fn usable_size(&self, ptr: NonNull<u8>, align: usize) -> usize;
+}
+
+#[derive(Clone, Copy)]
+struct Magazine {
+    len: usize,
+    blocks: [*mut u8; MAGAZINE_CAPACITY],
+}
+
+impl Magazine {
+    const EMPTY: Self = Self {
+        len: 0,
+        blocks: [ptr::null_mut(); MAGAZINE_CAPACITY],
+    };
+
+    #[inline]
+    fn pop(&mut self) -> Option<NonNull<u8>> {
+        self.len = self.len.checked_sub(1)?;
+        // Safety: Only non-null pointers are pushed
+        Some(unsafe { NonNull::new_unchecked(self.blocks[self.len]) })
+    }
+
+    /// Push `ptr`. Returns `false` if the magazine is full.
+    #[inline]
+    fn push(&mut self, ptr: NonNull<u8>) -> bool {
+        if let Some(slot) = self.blocks.get_mut(self.len) {
+            *slot = ptr.as_ptr();
+            self.len += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Pop up to `count` memory blocks.
+    #[inline]
+    fn drain(&mut self, count: usize) -> impl Iterator<Item = NonNull<u8>> + '_ {
+        (0..count).map_while(move |_| self.pop())
+    }
+}
+
+/// Check if an allocation with `layout` can be served by the magazines of
+/// [`ThreadCache`].
+#[cfg(all(feature = "std", any(unix, windows)))]
+#[inline]
+pub(crate) fn is_cacheable(layout: Layout) -> bool {
+    class_for_layout(layout).is_some()
+}
+
+/// Get the size class to serve an allocation with `layout` from.
+#[inline]
+fn class_for_layout(layout: Layout) -> Option<usize> {
+    if layout.align() >= GRANULARITY || layout.size() > NUM_CLASSES * GRANULARITY {
+        return None;
+    }
+    Some(layout.size().saturating_sub(1) / GRANULARITY)
+}
+
+/// Get the size class to cache a memory block with `usable_size` usable
+/// bytes in.
+#[inline]
+fn class_for_usable_size(usable_size: usize) -> Option<usize> {
+    match usable_size / GRANULARITY {
+        0 => None,
+        n if n <= NUM_CLASSES => Some(n - 1),
+        _ => None,
+    }
+}
+
+/// The layout to allocate a memory block of `class` with.
+#[inline]
+fn class_layout(class: usize) -> Layout {
+    // Safety: `BLOCK_ALIGN` is a power of two, and the size is small
+    unsafe { Layout::from_size_align_unchecked((class + 1) * GRANULARITY, BLOCK_ALIGN) }
+}
+
+/// A cache of small memory blocks (*magazines*) fronting a shared allocator
+/// (the *backend*), typically owned by a thread or a CPU core.
+///
+/// Allocations of up to `8 * GRANULARITY` bytes with alignments smaller than
+/// [`GRANULARITY`] are served from per-size-class magazines, which are
+/// refilled from and flushed to the backend in batches. This reduces the
+/// number of times the backend's lock is taken by a factor of the batch
+/// size. Other allocations are forwarded to the backend.
+///
+/// A `ThreadCache` doesn't remember its backend; all memory blocks passed to
+/// a `ThreadCache` must belong to the same backend, which is passed to each
+/// method. The memory blocks held by a `ThreadCache` count as being in use
+/// by the backend. Call [`Self::flush`] before dropping a `ThreadCache`;
+/// otherwise, they are leaked.
+///
+/// [`GlobalTlsf::new_with_thread_cache`] uses a `ThreadCache` per thread.
+///
+/// [`GlobalTlsf::new_with_thread_cache`]: crate::GlobalTlsf::new_with_thread_cache
+///
+/// # Examples
+///
+/// ```rust
+/// use rlsf::{SyncTlsf, ThreadCache};
+/// use std::{alloc::Layout, cell::RefCell, mem::MaybeUninit};
+///
+/// static TLSF: SyncTlsf<'static, u16, u16, 12, 16> = SyncTlsf::new();
+///
+/// thread_local! {
+///     static CACHE: RefCell<ThreadCache> = RefCell::new(ThreadCache::new());
+/// }
+///
+/// static mut POOL: [MaybeUninit<u8>; 8192] = [MaybeUninit::uninit(); 8192];
+/// TLSF.lock().insert_free_block(unsafe { &mut POOL });
+///
+/// CACHE.with(|cache| {
+///     let mut cache = cache.borrow_mut();
+///     let layout = Layout::new::<u64>();
+///     let ptr = cache.allocate(&TLSF, layout).unwrap();
+///     unsafe { cache.deallocate(&TLSF, ptr, layout) };
+///     cache.flush(&TLSF);
+/// });
+/// ```
+pub struct ThreadCache {
+    magazines: [Magazine; NUM_CLASSES],
+}
+
+impl ThreadCache {
+    /// Construct an empty `ThreadCache`.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            magazines: [Magazine::EMPTY; NUM_CLASSES],
+        }
+    }
+
+    /// Return all memory blocks held by `self` to `backend`.
+    pub fn flush<B: ThreadCacheBackend + ?Sized>(&mut self, backend: &B) {
+        if self.magazines.iter().all(|magazine| magazine.len == 0) {
+            return;
+        }
+        let ptrs = self
+            .magazines
+            .iter_mut()
+            .flat_map(|magazine| magazine.drain(MAGAZINE_CAPACITY));
+        // Safety: The memory blocks in the magazines were allocated by
+        //         `backend` with alignments smaller than `GRANULARITY`
+        unsafe { backend.deallocate_batch(BLOCK_ALIGN, ptrs) };
+    }
+
+    /// Allocate a memory block with `layout`, refilling the magazine of the
+    /// corresponding size class from `backend` if it's empty.
+    #[inline]
+    pub fn allocate<B: ThreadCacheBackend + ?Sized>(
+        &mut self,
+        backend: &B,
+        layout: Layout,
+    ) -> Option<NonNull<u8>> {
+        let class = match class_for_layout(layout) {
+            Some(class) => class,
+            None => {
+                let mut result = None;
+                backend.allocate_batch(layout, 1, |ptr| result = Some(ptr));
+                return result;
+            }
+        };
+
+        let magazine = &mut self.magazines[class];
+        if let Some(ptr) = magazine.pop() {
+            return Some(ptr);
+        }
+        backend.allocate_batch(class_layout(class), BATCH_SIZE, |ptr| {
+            magazine.push(ptr);
+        });
+        magazine.pop()
+    }
+
+    /// Deallocate a memory block, flushing a batch of memory blocks from the
+    /// magazine of the corresponding size class to `backend` if it's full.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a memory block allocated by `backend` (through any
+    /// `ThreadCache` or directly) with `layout`.
+    #[inline]
+    pub unsafe fn deallocate<B: ThreadCacheBackend + ?Sized>(
+        &mut self,
+        backend: &B,
+        ptr: NonNull<u8>,
+        layout: Layout,
+    ) {
+        // The memory block might not have been allocated by a `ThreadCache`,
+        // so its class is determined by its usable size
+        let class = class_for_layout(layout)
+            .and_then(|_| class_for_usable_size(backend.usable_size(ptr, layout.align())));
+        let class = match class {
+            Some(class) => class,
+            None => {
+                // Safety: `ptr` was allocated with `layout`
+                backend.deallocate_batch(layout.align(), core::iter::once(ptr));
+                return;
+            }
+        };
+
+        let magazine = &mut self.magazines[class];
+        if !magazine.push(ptr) {
+            // Safety: The memory blocks in the magazines were allocated by
+            //         `backend` with alignments smaller than `GRANULARITY`
+            backend.deallocate_batch(BLOCK_ALIGN, magazine.drain(BATCH_SIZE));
+            magazine.push(ptr);
+        }
+    }
+}
+
+impl Default for ThreadCache {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ThreadCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lens: [usize; NUM_CLASSES] = {
+            let mut lens = [0; NUM_CLASSES];
+            for (len, magazine) in lens.iter_mut().zip(self.magazines.iter()) {
+                *len = magazine.len;
+            }
+            lens
+        };
+        f.debug_struct("ThreadCache")
+            .field("magazine_lens", &lens)
+            .finish()
+    }
+}
+
+// Safety: The memory blocks held by `ThreadCache` are owned by it
+unsafe impl Send for ThreadCache {}
+
+#[cfg(test)]
+mod tests;
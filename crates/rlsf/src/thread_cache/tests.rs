@@ -0,0 +1,148 @@
+use std::{cell::Cell, mem::MaybeUninit, prelude::v1::*, vec};
+
+use super::*;
+use crate::{Tlsf, GRANULARITY};
+
+/// A backend counting the calls to its methods
+struct CountingBackend {
+    tlsf: core::cell::RefCell<Tlsf<'static, u16, u16, 12, 16>>,
+    num_allocate_batch: Cell<usize>,
+    num_deallocate_batch: Cell<usize>,
+}
+
+impl CountingBackend {
+    fn new(size: usize) -> Self {
+        let pool = Box::leak(vec![MaybeUninit::uninit(); size].into_boxed_slice());
+        let mut tlsf = Tlsf::new();
+        tlsf.insert_free_block(pool);
+        Self {
+            tlsf: core::cell::RefCell::new(tlsf),
+            num_allocate_batch: Cell::new(0),
+            num_deallocate_batch: Cell::new(0),
+        }
+    }
+}
+
+unsafe impl ThreadCacheBackend for CountingBackend {
+    fn allocate_batch(&self, layout: Layout, count: usize, mut f: impl FnMut(NonNull<u8>)) {
+        self.num_allocate_batch
+            .set(self.num_allocate_batch.get() + 1);
+        let mut tlsf = self.tlsf.borrow_mut();
+        for _ in 0..count {
+            match tlsf.allocate(layout) {
+                Some(ptr) => f(ptr),
+                None => break,
+            }
+        }
+    }
+
+    unsafe fn deallocate_batch(&self, align: usize, ptrs: impl Iterator<Item = NonNull<u8>>) {
+        self.num_deallocate_batch
+            .set(self.num_deallocate_batch.get() + 1);
+        let mut tlsf = self.tlsf.borrow_mut();
+        for ptr in ptrs {
+            if align < GRANULARITY {
+                tlsf.deallocate_unknown_align(ptr);
+            } else {
+                tlsf.deallocate(ptr, align);
+            }
+        }
+    }
+
+    unsafe fn usable_size(&self, ptr: NonNull<u8>, align: usize) -> usize {
+        Tlsf::<'static, u16, u16, 12, 16>::size_of_allocation(ptr, align)
+    }
+}
+
+#[test]
+fn size_classes() {
+    let layout = |size, align| Layout::from_size_align(size, align).unwrap();
+    assert_eq!(class_for_layout(layout(0, 1)), Some(0));
+    assert_eq!(class_for_layout(layout(GRANULARITY, 8)), Some(0));
+    assert_eq!(class_for_layout(layout(GRANULARITY + 1, 1)), Some(1));
+    assert_eq!(
+        class_for_layout(layout(NUM_CLASSES * GRANULARITY + 1, 1)),
+        None
+    );
+    assert_eq!(class_for_layout(layout(8, GRANULARITY)), None);
+
+    assert_eq!(class_for_usable_size(GRANULARITY - 1), None);
+    assert_eq!(class_for_usable_size(GRANULARITY), Some(0));
+    assert_eq!(class_for_usable_size(GRANULARITY * 2 - 1), Some(0));
+    assert_eq!(
+        class_for_usable_size(NUM_CLASSES * GRANULARITY),
+        Some(NUM_CLASSES - 1)
+    );
+    assert_eq!(class_for_usable_size(NUM_CLASSES * GRANULARITY * 2), None);
+}
+
+#[test]
+fn batching() {
+    let backend = CountingBackend::new(1 << 15);
+    let mut cache = ThreadCache::new();
+    let layout = Layout::from_size_align(24, 8).unwrap();
+
+    // Refilled in batches
+    let mut ptrs: Vec<_> = (0..BATCH_SIZE * 2)
+        .map(|_| cache.allocate(&backend, layout).unwrap())
+        .collect();
+    assert_eq!(backend.num_allocate_batch.get(), 2);
+
+    // Flushed in batches
+    for _ in 0..2 {
+        for &ptr in &ptrs {
+            unsafe { cache.deallocate(&backend, ptr, layout) };
+        }
+        for ptr in ptrs.iter_mut() {
+            *ptr = cache.allocate(&backend, layout).unwrap();
+        }
+    }
+    assert_eq!(backend.num_allocate_batch.get(), 2);
+    assert_eq!(backend.num_deallocate_batch.get(), 0);
+    for &ptr in &ptrs {
+        unsafe { cache.deallocate(&backend, ptr, layout) };
+    }
+    let extra = backend.tlsf.borrow_mut().allocate(layout).unwrap();
+    unsafe { cache.deallocate(&backend, extra, layout) };
+    assert_eq!(backend.num_deallocate_batch.get(), 1);
+
+    cache.flush(&backend);
+    assert_eq!(backend.num_deallocate_batch.get(), 2);
+    assert!(cache.magazines.iter().all(|magazine| magazine.len == 0));
+
+    // Everything has been returned to the backend
+    let big = Layout::from_size_align(1 << 14, 8).unwrap();
+    assert!(backend.tlsf.borrow_mut().allocate(big).is_some());
+}
+
+#[test]
+fn forward_uncacheable() {
+    let backend = CountingBackend::new(1 << 15);
+    let mut cache = ThreadCache::new();
+    for layout in [
+        Layout::from_size_align(1024, 8).unwrap(),
+        Layout::from_size_align(8, GRANULARITY).unwrap(),
+    ] {
+        let ptr = cache.allocate(&backend, layout).unwrap();
+        unsafe { cache.deallocate(&backend, ptr, layout) };
+    }
+    assert_eq!(backend.num_allocate_batch.get(), 2);
+    assert_eq!(backend.num_deallocate_batch.get(), 2);
+    cache.flush(&backend);
+    assert_eq!(backend.num_deallocate_batch.get(), 2);
+}
+
+#[test]
+fn sync_tlsf_backend() {
+    let tlsf: crate::SyncTlsf<'static, u16, u16, 12, 16> = crate::SyncTlsf::new();
+    tlsf.lock().insert_free_block(Box::leak(
+        vec![MaybeUninit::uninit(); 4096].into_boxed_slice(),
+    ));
+    let mut cache = ThreadCache::new();
+    let layout = Layout::new::<[u64; 3]>();
+    let ptr = cache.allocate(&tlsf, layout).unwrap();
+    unsafe { cache.deallocate(&tlsf, ptr, layout) };
+    assert_eq!(cache.allocate(&tlsf, layout), Some(ptr));
+    unsafe { cache.deallocate(&tlsf, ptr, layout) };
+    cache.flush(&tlsf);
+}
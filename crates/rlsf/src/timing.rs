@@ -0,0 +1,167 @@
+//! Cycle-accurate timing of the `Tlsf` operations
+use core::fmt;
+
+/// The latencies of the operations of a [`Tlsf`](crate::Tlsf), returned by
+/// [`Tlsf::timing`](crate::Tlsf::timing).
+///
+/// The latencies are measured in the cycles of the processor's cycle
+/// counter:
+///
+///  - x86 (with SSE2) and x86_64: The time-stamp counter (`rdtsc`), which usually ticks
+///    at a constant rate independent of the core frequency.
+///
+///  - Arm Cortex-M (with the `cortex-m` feature): The cycle counter of the
+///    Data Watchpoint and Trace unit (`DWT.CYCCNT`). The program must enable
+///    it beforehand by setting `DEMCR.TRCENA` and `DWT_CTRL.CYCCNTENA`;
+///    otherwise, all latencies read zero. Latencies longer than `u32::MAX`
+///    cycles wrap around.
+///
+///  - Other targets: No cycle counter is available, and all latencies read
+///    zero.
+///
+/// Only the time spent in the allocator is measured; the observer calls and
+/// trace records are excluded.
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "timing")))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct TlsfTiming {
+    /// The latencies of [`Tlsf::allocate`](crate::Tlsf::allocate), including
+    /// the failed calls.
+    pub allocate: Latency,
+    /// The latencies of [`Tlsf::deallocate`](crate::Tlsf::deallocate) and
+    /// the deallocations made by the wrappers of `Tlsf`.
+    pub deallocate: Latency,
+}
+
+impl TlsfTiming {
+    pub(crate) const ZERO: Self = Self {
+        allocate: Latency::ZERO,
+        deallocate: Latency::ZERO,
+    };
+}
+
+/// The latency distribution of an operation, in cycles. See [`TlsfTiming`].
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "timing")))]
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Latency {
+    count: u64,
+    total: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Latency {
+    const ZERO: Self = Self {
+        count: 0,
+        total: 0,
+        min: 0,
+        max: 0,
+    };
+
+    /// The number of measured calls.
+    #[inline]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The shortest latency, or `None` if nothing has been measured.
+    #[inline]
+    pub fn min(&self) -> Option<u64> {
+        (self.count != 0).then(|| self.min)
+    }
+
+    /// The longest latency, or `None` if nothing has been measured.
+    #[inline]
+    pub fn max(&self) -> Option<u64> {
+        (self.count != 0).then(|| self.max)
+    }
+
+    /// The mean latency (rounded down), or `None` if nothing has been
+    /// measured.
+    #[inline]
+    pub fn mean(&self) -> Option<u64> {
+        self.total.checked_div(self.count)
+    }
+
+    #[inline]
+    fn record(&mut self, cycles: u64) {
+        if self.count == 0 {
+            self.min = cycles;
+            self.max = cycles;
+        } else {
+            self.min = self.min.min(cycles);
+            self.max = self.max.max(cycles);
+        }
+        self.count += 1;
+        self.total = self.total.saturating_add(cycles);
+    }
+}
+
+impl fmt::Debug for Latency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Latency")
+            .field("count", &self.count)
+            .field("min", &self.min())
+            .field("max", &self.max())
+            .field("mean", &self.mean())
+            .finish()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Latency {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(
+            f,
+            "Latency {{ count: {}, min: {}, max: {}, mean: {} }}",
+            self.count,
+            self.min(),
+            self.max(),
+            self.mean()
+        );
+    }
+}
+
+/// Measures the cycles elapsed since its creation.
+pub(crate) struct Stopwatch(u64);
+
+impl Stopwatch {
+    #[inline]
+    pub(crate) fn start() -> Self {
+        Self(read_cycle_counter())
+    }
+
+    /// Record the elapsed cycles to `latency`.
+    #[inline]
+    pub(crate) fn stop(self, latency: &mut Latency) {
+        let now = read_cycle_counter();
+        // `DWT.CYCCNT` is 32 bits wide
+        #[cfg(all(feature = "cortex-m", target_arch = "arm"))]
+        let elapsed = (now as u32).wrapping_sub(self.0 as u32) as u64;
+        #[cfg(not(all(feature = "cortex-m", target_arch = "arm")))]
+        let elapsed = now.wrapping_sub(self.0);
+        latency.record(elapsed);
+    }
+}
+
+#[inline(always)]
+fn read_cycle_counter() -> u64 {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "x86_64")] {
+            // Safety: `rdtsc` is available on all x86_64 processors
+            unsafe { core::arch::x86_64::_rdtsc() }
+        } else if #[cfg(all(target_arch = "x86", target_feature = "sse2"))] {
+            // Safety: `rdtsc` is available on all processors with SSE2
+            unsafe { core::arch::x86::_rdtsc() }
+        } else if #[cfg(all(feature = "cortex-m", target_arch = "arm"))] {
+            /// `DWT.CYCCNT`
+            const DWT_CYCCNT: *const u32 = 0xe000_1004 as *const u32;
+            // Safety: The DWT is present in all Cortex-M processors with the
+            //         cycle counter, and reading `CYCCNT` has no side effects
+            unsafe { DWT_CYCCNT.read_volatile() as u64 }
+        } else {
+            0
+        }
+    }
+}
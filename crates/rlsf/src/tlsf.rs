@@ -11,10 +11,30 @@ use core::{
 };
 
 use crate::{
+    corruption::{report_corruption, HeapError},
     int::BinInteger,
-    utils::{nonnull_slice_from_raw_parts, nonnull_slice_len, nonnull_slice_start},
+    utils::{nonnull_slice_from_raw_parts, nonnull_slice_len, nonnull_slice_start, ptr_with_addr},
 };
 
+#[cfg(any(feature = "safe-linking", feature = "zero-on-free"))]
+use crate::utils::ptr_map_addr;
+
+#[cfg(feature = "debug-leak-check")]
+use crate::leak_check::{LeakCheck, LeakReporter};
+
+#[cfg(feature = "track-allocations")]
+use {core::panic::Location, std::collections::HashMap};
+
+#[cfg(feature = "asan")]
+use crate::asan;
+
+#[cfg(feature = "pool-stats")]
+use crate::utils::nonnull_slice_end;
+#[cfg(feature = "observer")]
+use crate::AllocObserver;
+#[cfg(feature = "timing")]
+use crate::{timing::Stopwatch, TlsfTiming};
+
 #[cfg_attr(doc, svgbobdoc::transform)]
 /// The TLSF header (top-level) data structure.
 ///
@@ -70,7 +90,48 @@ pub struct Tlsf<'pool, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usiz
     /// `sl_bitmap[fl].get_bit(sl)` is set iff `first_free[fl][sl].is_some()`
     sl_bitmap: [SLBitmap; FLLEN],
     first_free: [[Option<NonNull<FreeBlockHdr>>; SLLEN]; FLLEN],
+    link_key: LinkKey,
+    #[cfg(feature = "randomize")]
+    random_source: Option<RandomSource>,
+    /// The maximum number of bytes zeroed in each memory region freed.
+    #[cfg(feature = "zero-on-free")]
+    zero_on_free_limit: usize,
     _phantom: PhantomData<&'pool ()>,
+    #[cfg(feature = "debug-leak-check")]
+    leak_check: LeakCheck,
+    /// The call site of each live allocation, keyed by the address of its
+    /// block header. `None` if the tracking is disabled.
+    #[cfg(feature = "track-allocations")]
+    allocation_sites: Option<HashMap<usize, &'static Location<'static>>>,
+    #[cfg(feature = "stats")]
+    counters: TlsfCounters,
+    /// The total usable size of the live allocations.
+    #[cfg(feature = "stats")]
+    bytes_in_use: usize,
+    /// The highest value `bytes_in_use` has reached since the last
+    /// `reset_peak`.
+    #[cfg(feature = "stats")]
+    peak_bytes_in_use: usize,
+    /// The total size of the memory pools.
+    #[cfg(feature = "stats")]
+    pool_bytes: usize,
+    /// The total size of the free memory blocks.
+    #[cfg(feature = "stats")]
+    free_bytes: usize,
+    /// The usage of the first `MAX_TRACKED_POOLS` memory pools. See
+    /// `pool_usage`.
+    #[cfg(feature = "pool-stats")]
+    pool_usage: [Option<PoolUsage>; MAX_TRACKED_POOLS],
+    /// The number of requests for each size class. See
+    /// `allocation_histogram`.
+    #[cfg(feature = "allocation-histogram")]
+    allocation_histogram: [[usize; SLLEN]; FLLEN],
+    #[cfg(feature = "observer")]
+    observer: Option<&'pool dyn AllocObserver>,
+    #[cfg(feature = "timing")]
+    timing: TlsfTiming,
+    #[cfg(feature = "oom-report")]
+    oom_hook: Option<OomHook>,
 }
 
 // Safety: All memory block headers directly or indirectly referenced by a
@@ -86,6 +147,175 @@ unsafe impl<FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize> Sync
 {
 }
 
+/// A function returning a random number, used by [`Tlsf`] to randomize the
+/// heap layout (`randomize` feature). See [`Tlsf::set_random_source`].
+#[cfg(feature = "randomize")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "randomize")))]
+pub type RandomSource = fn() -> usize;
+
+/// Operation counters of a [`Tlsf`] (`stats` feature), returned by
+/// [`Tlsf::counters`].
+///
+/// Only the calls made directly to the `Tlsf` are counted. For example, when
+/// [`FlexTlsf`](crate::FlexTlsf) grows the heap to retry a failed allocation,
+/// the first attempt is counted in [`Self::failed_allocations`]. The counters
+/// wrap around on overflow.
+#[cfg(feature = "stats")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "stats")))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct TlsfCounters {
+    /// The number of successful calls to [`Tlsf::allocate`].
+    pub allocations: usize,
+    /// The number of calls to [`Tlsf::deallocate`]. The memory blocks
+    /// released by [`Tlsf::reallocate`] are not counted.
+    pub deallocations: usize,
+    /// The number of successful calls to [`Tlsf::reallocate`] that had to
+    /// move the memory block to a new location.
+    pub reallocation_moves: usize,
+    /// The number of calls to [`Tlsf::allocate`] and [`Tlsf::reallocate`]
+    /// that failed.
+    pub failed_allocations: usize,
+}
+
+#[cfg(feature = "stats")]
+impl TlsfCounters {
+    const ZERO: Self = Self {
+        allocations: 0,
+        deallocations: 0,
+        reallocation_moves: 0,
+        failed_allocations: 0,
+    };
+}
+
+/// The fill ratio of the memory pools of a [`Tlsf`] (`stats` feature),
+/// returned by [`Tlsf::utilization`].
+#[cfg(feature = "stats")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "stats")))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct TlsfUtilization {
+    /// The total size of the memory pools, including the sentinel blocks.
+    pub pool_bytes: usize,
+    /// The total size of the free memory blocks, including their headers.
+    pub free_bytes: usize,
+}
+
+#[cfg(feature = "stats")]
+impl TlsfUtilization {
+    /// Get the percentage of [`Self::pool_bytes`] not occupied by free memory
+    /// blocks, rounded down. Returns `0` if there are no memory pools.
+    #[inline]
+    pub fn percent(&self) -> usize {
+        if self.pool_bytes == 0 {
+            0
+        } else {
+            let used_bytes = (self.pool_bytes - self.free_bytes) as u128;
+            (used_bytes * 100 / self.pool_bytes as u128) as usize
+        }
+    }
+}
+
+/// A snapshot of the statistics of a heap, returned by [`Tlsf::heap_stats`],
+/// [`FlexTlsf::heap_stats`](crate::FlexTlsf::heap_stats), and
+/// `GlobalTlsf::heap_stats`.
+///
+/// This is a plain value type, so snapshots taken at different times can be
+/// compared, logged, or (with the `serde` feature) serialized the same way
+/// regardless of the kind of heap they were taken from. Allocation sizes are
+/// counted in terms of the usable sizes of memory blocks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct HeapStats {
+    /// The total size of the live allocations.
+    pub bytes_in_use: usize,
+    /// The highest value [`Self::bytes_in_use`] has reached since the heap
+    /// was created or its peak was last reset.
+    pub peak_bytes_in_use: usize,
+    /// The total size of the memory managed by the heap.
+    pub pool_bytes: usize,
+    /// The total size of the free memory blocks, including their headers.
+    pub free_bytes: usize,
+    /// The number of live allocations.
+    pub num_allocations: usize,
+    /// The number of allocation and reallocation requests that couldn't be
+    /// satisfied.
+    pub failed_allocations: usize,
+}
+
+/// The maximum number of memory pools whose usage is tracked by a [`Tlsf`]
+/// (`pool-stats` feature).
+#[cfg(feature = "pool-stats")]
+const MAX_TRACKED_POOLS: usize = 8;
+
+/// The usage of a memory pool of a [`Tlsf`] (`pool-stats` feature), returned
+/// by [`Tlsf::pool_usage`].
+#[cfg(feature = "pool-stats")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "pool-stats")))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PoolUsage {
+    /// The address range of the memory pool, excluding the alignment padding
+    /// at the start.
+    pub pool: NonNull<[u8]>,
+    /// The total usable size of the live allocations in the memory pool.
+    pub in_use: usize,
+    /// The highest value [`Self::in_use`] has reached since the last
+    /// [`Tlsf::reset_peak`].
+    pub peak_in_use: usize,
+}
+
+#[cfg(all(feature = "pool-stats", feature = "defmt"))]
+impl defmt::Format for PoolUsage {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(
+            f,
+            "PoolUsage {{ pool: {}..+{}, in_use: {}, peak_in_use: {} }}",
+            self.pool.as_ptr() as *mut u8,
+            nonnull_slice_len(self.pool),
+            self.in_use,
+            self.peak_in_use
+        );
+    }
+}
+
+/// The memory blocks of a size class of a [`Tlsf`], returned by
+/// [`Tlsf::occupancy`].
+///
+/// The sizes are those of the whole memory blocks, including their headers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct ClassOccupancy {
+    /// The number of free memory blocks.
+    pub free_blocks: usize,
+    /// The total size of the free memory blocks.
+    pub free_bytes: usize,
+    /// The number of allocated memory blocks.
+    pub used_blocks: usize,
+    /// The total size of the allocated memory blocks.
+    pub used_bytes: usize,
+}
+
+impl ClassOccupancy {
+    const ZERO: Self = Self {
+        free_blocks: 0,
+        free_bytes: 0,
+        used_blocks: 0,
+        used_bytes: 0,
+    };
+}
+
+/// The number of memory blocks at the front of a free block list from which
+/// [`Tlsf::allocate`] chooses one at random (`randomize` feature). This bounds
+/// the time taken to find it.
+#[cfg(feature = "randomize")]
+const RANDOM_CANDIDATES: usize = 8;
+
 /// The allocation granularity.
 ///
 /// It is `size_of::<usize>() * 4` bytes, which is the minimum size of a TLSF
@@ -112,6 +342,10 @@ struct BlockHdr {
     ///  - `bit[1]` ([`SIZE_LAST_IN_POOL`]) indicates whether the block is the
     ///    last one of the pool or not.
     ///
+    ///  - `bit[2..GRANULARITY_LOG2]` ([`SIZE_CHECKSUM_MASK`]) holds the
+    ///    header's checksum (`header-checksum` feature), the alignment of a
+    ///    used memory block ([`SIZE_ALIGN_RECORDED`]), or zero.
+    ///
     ///  - `bit[GRANULARITY_LOG2..]` ([`SIZE_SIZE_MASK`]) represents the size.
     ///
     size: usize,
@@ -127,6 +361,22 @@ const SIZE_USED: usize = 1;
 const SIZE_SENTINEL: usize = 2;
 /// The bits of [`BlockHdr::size`] indicating the block's size.
 const SIZE_SIZE_MASK: usize = !((1 << GRANULARITY_LOG2) - 1);
+/// The bits of [`BlockHdr::size`] holding the header's checksum
+/// (`header-checksum` feature) or the log2 of a used memory block's alignment
+/// ([`SIZE_ALIGN_RECORDED`]). A free block's size has no other flags, so
+/// masking them out yields its size; this is a no-op if neither applies.
+const SIZE_CHECKSUM_MASK: usize = if cfg!(feature = "header-checksum") || SIZE_ALIGN_RECORDED {
+    !SIZE_SIZE_MASK & !(SIZE_USED | SIZE_SENTINEL)
+} else {
+    0
+};
+/// Whether a used memory block with an alignment smaller than [`GRANULARITY`]
+/// records its log2 in [`SIZE_CHECKSUM_MASK`] for `Tlsf::debug_check_align`.
+/// This is the case in debug builds unless the bits hold the checksum or are
+/// too few (16-bit targets).
+const SIZE_ALIGN_RECORDED: bool = cfg!(debug_assertions)
+    && !cfg!(feature = "header-checksum")
+    && (1 << (GRANULARITY_LOG2 - 2)) > GRANULARITY_LOG2 - 1;
 
 impl BlockHdr {
     /// Get the next block, assuming it exists.
@@ -146,6 +396,55 @@ impl BlockHdr {
         //         next block should exist at a non-null location.
         NonNull::new_unchecked((self as *const _ as *mut u8).add(self.size & SIZE_SIZE_MASK)).cast()
     }
+
+    /// Calculate the checksum of the size, flags, `prev_phys_block`, and
+    /// location of `self`, positioned at [`SIZE_CHECKSUM_MASK`].
+    #[cfg(feature = "header-checksum")]
+    #[inline]
+    fn checksum(&self) -> usize {
+        let x = (self.size & !SIZE_CHECKSUM_MASK)
+            ^ (self
+                .prev_phys_block
+                .map_or(0, |block| block.as_ptr() as usize))
+            .rotate_left(usize::BITS / 2)
+            ^ (self as *const Self as usize).rotate_left(usize::BITS / 4);
+        // Fibonacci hashing; the topmost bits depend on all bits of `x`
+        let hash = x.wrapping_mul(0x9e37_79b9_7f4a_7c15_u64 as usize);
+        (hash >> (usize::BITS - SIZE_CHECKSUM_MASK.count_ones())) << 2
+    }
+
+    /// Update the checksum of `self` (`header-checksum` feature). This must
+    /// be called whenever `size` or `prev_phys_block` is modified.
+    #[inline(always)]
+    fn seal(&mut self) {
+        #[cfg(feature = "header-checksum")]
+        {
+            self.size = (self.size & !SIZE_CHECKSUM_MASK) | self.checksum();
+        }
+    }
+
+    /// Check the checksum of `self`. Always returns `true` without the
+    /// `header-checksum` feature.
+    #[inline(always)]
+    fn checksum_matches(&self) -> bool {
+        #[cfg(feature = "header-checksum")]
+        {
+            (self.size & SIZE_CHECKSUM_MASK) == self.checksum()
+        }
+        #[cfg(not(feature = "header-checksum"))]
+        {
+            true
+        }
+    }
+
+    /// Report a heap corruption if the checksum of `self` doesn't match
+    /// (`header-checksum` feature).
+    #[inline(always)]
+    fn verify(&self) {
+        if !self.checksum_matches() {
+            corrupted_block_header(self);
+        }
+    }
 }
 
 /// The header of a free memory block.
@@ -156,8 +455,129 @@ impl BlockHdr {
 #[derive(Debug)]
 struct FreeBlockHdr {
     common: BlockHdr,
-    next_free: Option<NonNull<FreeBlockHdr>>,
-    prev_free: Option<NonNull<FreeBlockHdr>>,
+    next_free: FreeLink,
+    prev_free: FreeLink,
+}
+
+/// A link of a free block list, encoded by [`LinkKey`].
+#[cfg(not(feature = "safe-linking"))]
+type FreeLink = Option<NonNull<FreeBlockHdr>>;
+/// A link of a free block list, encoded by [`LinkKey`].
+#[cfg(feature = "safe-linking")]
+type FreeLink = *mut FreeBlockHdr;
+
+/// The key the links of a heap's free block lists are encoded with.
+///
+/// With the `safe-linking` feature, a link is stored XOR-ed with a mask
+/// derived from the heap's secret and the link's own address (like glibc's
+/// safe-linking), so overwriting it with a chosen pointer requires knowing
+/// both. A decoded link that isn't aligned to [`GRANULARITY`] is reported as
+/// heap corruption. Without the feature, links are stored as they are.
+#[derive(Debug, Clone, Copy)]
+struct LinkKey {
+    #[cfg(feature = "safe-linking")]
+    secret: usize,
+}
+
+impl LinkKey {
+    const ZERO: Self = Self {
+        #[cfg(feature = "safe-linking")]
+        secret: 0,
+    };
+
+    /// Encode `ptr` to be stored at `location`.
+    #[cfg(not(feature = "safe-linking"))]
+    #[inline(always)]
+    fn encode(self, ptr: Option<NonNull<FreeBlockHdr>>, _location: *const FreeLink) -> FreeLink {
+        ptr
+    }
+
+    /// Decode `link` stored at `location` without validating it.
+    #[cfg(not(feature = "safe-linking"))]
+    #[inline(always)]
+    fn decode_unchecked(
+        self,
+        link: FreeLink,
+        _location: *const FreeLink,
+    ) -> Option<NonNull<FreeBlockHdr>> {
+        link
+    }
+
+    /// Encode `ptr` to be stored at `location`.
+    #[cfg(feature = "safe-linking")]
+    #[inline]
+    fn encode(self, ptr: Option<NonNull<FreeBlockHdr>>, location: *const FreeLink) -> FreeLink {
+        let ptr = ptr.map_or(core::ptr::null_mut(), NonNull::as_ptr);
+        ptr_map_addr(ptr, |addr| addr ^ self.mask(location))
+    }
+
+    /// Get the mask for a link stored at `location`. Its lowest two bits are
+    /// `0b10`, so an encoded link is never mistaken for a block header
+    /// pointer or a used memory block's size (see
+    /// [`Tlsf::used_block_hdr_for_allocation_unknown_align`]), and a link
+    /// overwritten with a plain pointer is always detected.
+    #[cfg(feature = "safe-linking")]
+    #[inline]
+    fn mask(self, location: *const FreeLink) -> usize {
+        (self.secret ^ (location as usize >> 12)) & !3 | 2
+    }
+
+    /// Decode `link` stored at `location` without validating it.
+    #[cfg(feature = "safe-linking")]
+    #[inline]
+    fn decode_unchecked(
+        self,
+        link: FreeLink,
+        location: *const FreeLink,
+    ) -> Option<NonNull<FreeBlockHdr>> {
+        NonNull::new(ptr_map_addr(link, |addr| addr ^ self.mask(location)))
+    }
+
+    /// Decode `link` stored at `location`. Panics if it has been corrupted
+    /// (`safe-linking` feature).
+    #[inline]
+    fn decode(self, link: FreeLink, location: *const FreeLink) -> Option<NonNull<FreeBlockHdr>> {
+        let ptr = self.decode_unchecked(link, location);
+        #[cfg(feature = "safe-linking")]
+        if ptr.map_or(false, |ptr| ptr.as_ptr() as usize % GRANULARITY != 0) {
+            corrupted_free_link(location);
+        }
+        ptr
+    }
+}
+
+impl FreeBlockHdr {
+    #[inline]
+    fn next_free(&self, key: LinkKey) -> Option<NonNull<FreeBlockHdr>> {
+        key.decode(self.next_free, &self.next_free)
+    }
+
+    #[inline]
+    fn prev_free(&self, key: LinkKey) -> Option<NonNull<FreeBlockHdr>> {
+        key.decode(self.prev_free, &self.prev_free)
+    }
+
+    /// Get `next_free` without validating it.
+    #[inline]
+    fn next_free_unchecked(&self, key: LinkKey) -> Option<NonNull<FreeBlockHdr>> {
+        key.decode_unchecked(self.next_free, &self.next_free)
+    }
+
+    /// Get `prev_free` without validating it.
+    #[inline]
+    fn prev_free_unchecked(&self, key: LinkKey) -> Option<NonNull<FreeBlockHdr>> {
+        key.decode_unchecked(self.prev_free, &self.prev_free)
+    }
+
+    #[inline]
+    fn set_next_free(&mut self, ptr: Option<NonNull<FreeBlockHdr>>, key: LinkKey) {
+        self.next_free = key.encode(ptr, &self.next_free);
+    }
+
+    #[inline]
+    fn set_prev_free(&mut self, ptr: Option<NonNull<FreeBlockHdr>>, key: LinkKey) {
+        self.prev_free = key.encode(ptr, &self.prev_free);
+    }
 }
 
 /// The header of a used memory block. It's `GRANULARITY / 2` bytes long.
@@ -188,6 +608,133 @@ impl UsedBlockPad {
     }
 }
 
+/// The byte the payloads of free memory blocks are filled with (`poison`
+/// feature).
+#[cfg(feature = "poison")]
+const POISON_BYTE: u8 = 0xdd;
+
+/// A `usize` filled with [`POISON_BYTE`].
+#[cfg(feature = "poison")]
+const POISON_WORD: usize = usize::MAX / 0xff * POISON_BYTE as usize;
+
+/// The word the unused space of the sentinel blocks at both ends of each
+/// memory pool is filled with (`pool-guards` feature). It's odd, so it's
+/// never a valid free list link.
+#[cfg(feature = "pool-guards")]
+const POOL_GUARD_WORD: usize = 0x9e6a_4d2f_c5b1_a7f3_u64 as usize;
+
+/// Get the unused space following the header of the sentinel block `block`,
+/// which is filled with [`POOL_GUARD_WORD`] (`pool-guards` feature).
+#[cfg(feature = "pool-guards")]
+#[inline]
+fn pool_guard_words(block: NonNull<BlockHdr>) -> *mut [usize; 2] {
+    block.as_ptr().wrapping_add(1).cast()
+}
+
+#[cfg(feature = "pool-guards")]
+const _: () = if mem::size_of::<BlockHdr>() + mem::size_of::<[usize; 2]>() != GRANULARITY {
+    panic!("bad pool guard size");
+};
+
+/// Poison `start..end`, a part of a free memory block not storing its
+/// header: fill it with [`POISON_BYTE`] (`poison` feature) and make it
+/// inaccessible to AddressSanitizer (`asan` feature). Does nothing if
+/// `start >= end`.
+///
+/// # Safety
+///
+/// `start..end` must be writable.
+#[cfg(any(feature = "poison", feature = "asan"))]
+#[inline]
+unsafe fn poison(start: *mut u8, end: *mut u8) {
+    if start < end {
+        // Parts of the range might already be inaccessible to
+        // AddressSanitizer
+        #[cfg(all(feature = "poison", feature = "asan"))]
+        asan::unpoison(start, end);
+        #[cfg(feature = "poison")]
+        start.write_bytes(POISON_BYTE, end as usize - start as usize);
+        #[cfg(feature = "asan")]
+        asan::poison(start, end);
+    }
+}
+
+/// Undo [`poison`] before `start..end` is handed out or stores a header.
+/// Panics unless it's filled with [`POISON_BYTE`] (`poison` feature), i.e.,
+/// nothing has written to this part of a free memory block since it was
+/// poisoned.
+///
+/// # Safety
+///
+/// `start..end` must be readable and initialized.
+#[cfg(any(feature = "poison", feature = "asan"))]
+#[inline]
+unsafe fn unpoison(start: *const u8, end: *const u8) {
+    #[cfg(feature = "asan")]
+    asan::unpoison(start, end);
+
+    #[cfg(feature = "poison")]
+    {
+        let mut cursor = start;
+        while cursor < end {
+            if *cursor != POISON_BYTE {
+                poison_violation(cursor);
+            }
+            cursor = cursor.add(1);
+        }
+    }
+}
+
+#[cfg(feature = "poison")]
+#[cold]
+#[inline(never)]
+fn poison_violation(ptr: *const u8) -> ! {
+    report_corruption(HeapError::UseAfterFree {
+        // Safety: `ptr` points to a memory block
+        at: unsafe { NonNull::new_unchecked(ptr as *mut u8) },
+    })
+}
+
+#[cold]
+#[inline(never)]
+fn double_free(ptr: NonNull<u8>) -> ! {
+    report_corruption(HeapError::DoubleFree { ptr })
+}
+
+#[cfg(debug_assertions)]
+#[cold]
+#[inline(never)]
+fn align_mismatch(ptr: NonNull<u8>, align: usize) -> ! {
+    report_corruption(HeapError::AlignMismatch { ptr, align })
+}
+
+#[cold]
+#[inline(never)]
+fn corrupted_block_header(block: &BlockHdr) -> ! {
+    report_corruption(HeapError::CorruptedBlockHeader {
+        block: NonNull::from(block).cast(),
+    })
+}
+
+#[cfg(feature = "pool-guards")]
+#[cold]
+#[inline(never)]
+fn pool_guard_violation(block: NonNull<BlockHdr>) -> ! {
+    report_corruption(HeapError::PoolGuardViolation {
+        block: block.cast(),
+    })
+}
+
+#[cfg(feature = "safe-linking")]
+#[cold]
+#[inline(never)]
+fn corrupted_free_link(location: *const FreeLink) -> ! {
+    report_corruption(HeapError::CorruptedFreeLink {
+        // Safety: `location` points to a free block header
+        location: unsafe { NonNull::new_unchecked(location as *mut u8) },
+    })
+}
+
 impl<FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, const SLLEN: usize> Default
     for Tlsf<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>
 {
@@ -212,10 +759,39 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
             fl_bitmap: FLBitmap::ZERO,
             sl_bitmap: [SLBitmap::ZERO; FLLEN],
             first_free: [[None; SLLEN]; FLLEN],
+            link_key: LinkKey::ZERO,
+            #[cfg(feature = "randomize")]
+            random_source: None,
+            #[cfg(feature = "zero-on-free")]
+            zero_on_free_limit: usize::MAX,
             _phantom: {
                 let () = Self::VALID;
                 PhantomData
             },
+            #[cfg(feature = "debug-leak-check")]
+            leak_check: LeakCheck::new(None),
+            #[cfg(feature = "track-allocations")]
+            allocation_sites: None,
+            #[cfg(feature = "stats")]
+            counters: TlsfCounters::ZERO,
+            #[cfg(feature = "stats")]
+            bytes_in_use: 0,
+            #[cfg(feature = "stats")]
+            peak_bytes_in_use: 0,
+            #[cfg(feature = "stats")]
+            pool_bytes: 0,
+            #[cfg(feature = "stats")]
+            free_bytes: 0,
+            #[cfg(feature = "pool-stats")]
+            pool_usage: [None; MAX_TRACKED_POOLS],
+            #[cfg(feature = "allocation-histogram")]
+            allocation_histogram: [[0; SLLEN]; FLLEN],
+            #[cfg(feature = "observer")]
+            observer: None,
+            #[cfg(feature = "timing")]
+            timing: TlsfTiming::ZERO,
+            #[cfg(feature = "oom-report")]
+            oom_hook: None,
         }
     }
 
@@ -260,6 +836,815 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         panic!("`SLLEN` is not power of two")
     };
 
+    /// Register a function to be called when `self` is dropped with live
+    /// allocations.
+    ///
+    /// Unlike [`FlexTlsf`](crate::FlexTlsf), `Tlsf` doesn't panic on leaks
+    /// if no reporter is registered because it's often deliberately dropped
+    /// together with its memory pools, e.g., when used as an arena.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::{LeakReport, Tlsf};
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 1024];
+    /// let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut pool);
+    /// tlsf.set_leak_reporter(|report: LeakReport| {
+    ///     eprintln!("leaked {} allocation(s) ({} bytes)", report.count, report.bytes);
+    /// });
+    ///
+    /// tlsf.allocate(Layout::new::<u64>()).unwrap();
+    /// drop(tlsf); // prints "leaked 1 allocation(s) (... bytes)"
+    /// ```
+    #[cfg(feature = "debug-leak-check")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "debug-leak-check")))]
+    #[inline]
+    pub fn set_leak_reporter(&mut self, reporter: LeakReporter) {
+        self.leak_check.set_reporter(reporter);
+    }
+
+    /// Start recording the call site of each allocation made by
+    /// [`Self::allocate`] and [`Self::reallocate`], so that live allocations
+    /// (e.g., leaked ones) can be attributed to code by
+    /// [`Self::allocation_site`] or [`BlockInfo::allocation_site`].
+    ///
+    /// The call sites are kept in a side table allocated from the global
+    /// allocator, so this must not be called on a `Tlsf` serving as the
+    /// global allocator. Allocations made before calling this method aren't
+    /// tracked.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::Tlsf;
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 1024];
+    /// let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut pool);
+    /// tlsf.enable_allocation_tracking();
+    ///
+    /// let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+    /// let site = unsafe { tlsf.allocation_site(ptr, 8) }.unwrap();
+    /// assert_eq!(site.line(), line!() - 2);
+    /// ```
+    #[cfg(feature = "track-allocations")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "track-allocations")))]
+    pub fn enable_allocation_tracking(&mut self) {
+        self.allocation_sites.get_or_insert_with(HashMap::new);
+    }
+
+    /// Get the call site of [`Self::allocate`] or [`Self::reallocate`] that
+    /// created a live allocation. Returns `None` if the allocation was made
+    /// while the tracking was disabled (see
+    /// [`Self::enable_allocation_tracking`]).
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via `self`.
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `align`.
+    ///
+    #[cfg(feature = "track-allocations")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "track-allocations")))]
+    pub unsafe fn allocation_site(
+        &self,
+        ptr: NonNull<u8>,
+        align: usize,
+    ) -> Option<&'static Location<'static>> {
+        // Safety: Upheld by the caller
+        let block = Self::used_block_hdr_for_allocation(ptr, align);
+        self.allocation_sites
+            .as_ref()?
+            .get(&(block.as_ptr() as usize))
+            .copied()
+    }
+
+    /// Set the secret the links of the free block lists are encoded with.
+    /// It should be unpredictable to an attacker (e.g., taken from a hardware
+    /// random number generator at startup) and is zero until this method is
+    /// called.
+    ///
+    /// The links of the existing free memory blocks are re-encoded, which
+    /// takes time proportional to the number of free memory blocks.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::Tlsf;
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 1024];
+    /// let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut pool);
+    /// tlsf.set_link_secret(0x5eed_cafe);
+    ///
+    /// let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+    /// unsafe { tlsf.deallocate(ptr, 8) };
+    /// ```
+    #[cfg(feature = "safe-linking")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "safe-linking")))]
+    pub fn set_link_secret(&mut self, secret: usize) {
+        let old_key = self.link_key;
+        let new_key = LinkKey { secret };
+        for first_free in self.first_free.iter().flatten() {
+            let mut next_free = *first_free;
+            while let Some(mut block) = next_free {
+                // Safety: `block` is a free block in one of the free lists
+                unsafe {
+                    let block = block.as_mut();
+                    next_free = block.next_free(old_key);
+                    let prev_free = block.prev_free(old_key);
+                    block.set_next_free(next_free, new_key);
+                    block.set_prev_free(prev_free, new_key);
+                }
+            }
+        }
+        self.link_key = new_key;
+    }
+
+    /// Make [`Self::allocate`] randomize the heap layout using
+    /// `random_source`, so that the addresses of allocations are harder to
+    /// predict. It chooses one of the first few memory blocks of a free block
+    /// list at random instead of the first one, and places the allocation at
+    /// a random position in a memory block larger than needed, leaving the
+    /// part before it free. This increases fragmentation.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::Tlsf;
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// fn xorshift() -> usize {
+    ///     use std::sync::atomic::{AtomicUsize, Ordering};
+    ///     static STATE: AtomicUsize = AtomicUsize::new(0x2545_f491);
+    ///     let mut x = STATE.load(Ordering::Relaxed);
+    ///     x ^= x << 13;
+    ///     x ^= x >> 7;
+    ///     x ^= x << 17;
+    ///     STATE.store(x, Ordering::Relaxed);
+    ///     x
+    /// }
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 1024];
+    /// let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut pool);
+    /// tlsf.set_random_source(xorshift);
+    ///
+    /// let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+    /// unsafe { tlsf.deallocate(ptr, 8) };
+    /// ```
+    #[cfg(feature = "randomize")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "randomize")))]
+    #[inline]
+    pub fn set_random_source(&mut self, random_source: RandomSource) {
+        self.random_source = Some(random_source);
+    }
+
+    /// Limit the number of bytes zeroed when a memory block is deallocated
+    /// (or shrunk by [`Self::reallocate`]) to the first `limit` bytes of the
+    /// freed region, bounding the time taken by deallocation. The default
+    /// limit is `usize::MAX`, i.e., the whole freed region is zeroed. Zero
+    /// disables the zeroing.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::Tlsf;
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 1024];
+    /// let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut pool);
+    ///
+    /// // Only zero the first cache line of each deallocated memory block
+    /// tlsf.set_zero_on_free_limit(64);
+    ///
+    /// let ptr = tlsf.allocate(Layout::new::<[u8; 256]>()).unwrap();
+    /// unsafe { tlsf.deallocate(ptr, 1) };
+    /// ```
+    #[cfg(feature = "zero-on-free")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "zero-on-free")))]
+    #[inline]
+    pub fn set_zero_on_free_limit(&mut self, limit: usize) {
+        self.zero_on_free_limit = limit;
+    }
+
+    /// Attach an observer that receives the events of `self`. See
+    /// [`AllocObserver`] for an example.
+    #[cfg(feature = "observer")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "observer")))]
+    #[inline]
+    pub fn set_observer(&mut self, observer: &'pool dyn AllocObserver) {
+        self.observer = Some(observer);
+    }
+
+    /// Report the memory region `pool` added to the memory pools to the
+    /// observer.
+    #[cfg(feature = "observer")]
+    #[inline]
+    fn observe_pool_added(&self, start: NonNull<u8>, len: usize) {
+        if let (Some(observer), true) = (self.observer, len != 0) {
+            observer.on_pool_added(nonnull_slice_from_raw_parts(start, len));
+        }
+    }
+
+    /// Zero the memory region `start..end` being freed, up to the limit set
+    /// by [`Self::set_zero_on_free_limit`].
+    ///
+    /// # Safety
+    ///
+    /// `start..end` must be writable.
+    #[cfg(feature = "zero-on-free")]
+    #[inline]
+    unsafe fn zero_on_free(&self, start: *mut u8, end: *mut u8) {
+        let len = (end as usize - start as usize).min(self.zero_on_free_limit);
+        start.write_bytes(0, len);
+    }
+
+    /// Get the size of the largest free memory block, including its header.
+    /// Returns `0` if there are no free memory blocks.
+    ///
+    /// Only the non-empty free block list for the largest size class is
+    /// searched, as every memory block in it is at least as large as the
+    /// ones in the other lists.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method takes time proportional to the number of free memory
+    /// blocks in the largest size class.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::Tlsf;
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 1024];
+    /// let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+    /// assert_eq!(tlsf.largest_free_block_size(), 0);
+    ///
+    /// tlsf.insert_free_block(&mut pool);
+    /// let size = tlsf.largest_free_block_size();
+    /// assert!(size > 768 && size <= 1024);
+    /// ```
+    pub fn largest_free_block_size(&self) -> usize {
+        if self.fl_bitmap == FLBitmap::ZERO {
+            return 0;
+        }
+        let fl = FLBitmap::BITS - 1 - self.fl_bitmap.leading_zeros();
+        let sl = SLBitmap::BITS - 1 - self.sl_bitmap[fl as usize].leading_zeros();
+
+        let mut largest = 0;
+        let mut next_free = self.first_free[fl as usize][sl as usize];
+        while let Some(block) = next_free {
+            // Safety: `block` is a free block in one of the free lists
+            unsafe {
+                largest = largest.max(block.as_ref().common.size & SIZE_SIZE_MASK);
+                next_free = block.as_ref().next_free(self.link_key);
+            }
+        }
+        largest
+    }
+
+    /// Get the minimum size of the memory blocks stored in the free block
+    /// list `(fl, sl)`, including their headers. The list stores the free
+    /// memory blocks at least as large as this and smaller than the next
+    /// list's minimum size.
+    ///
+    /// When `SLLEN` is large, some lists of the first few first-level
+    /// indices are never used. For these lists, the minimum size of the
+    /// preceding used list is returned.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `fl >= FLLEN` or `sl >= SLLEN`.
+    pub fn free_list_min_size(fl: usize, sl: usize) -> usize {
+        assert!(fl < FLLEN && sl < SLLEN, "invalid free block list index");
+
+        // Invert `map_floor`
+        let size = SLLEN | sl;
+        let shift = fl as u32 + GRANULARITY_LOG2;
+        if shift >= Self::SLI {
+            size << (shift - Self::SLI)
+        } else {
+            size >> (Self::SLI - shift)
+        }
+    }
+
+    /// Count the free memory blocks in each free block list.
+    ///
+    /// `free_histogram()[fl][sl]` is the number of free memory blocks in the
+    /// free block list `(fl, sl)`, whose sizes start from
+    /// [`Self::free_list_min_size`]`(fl, sl)`. This shows how the free memory
+    /// is fragmented and how well `FLLEN` and `SLLEN` suit the workload.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(num_free_blocks)`).
+    /// Empty free block lists are skipped by consulting the bitmaps.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::Tlsf;
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 4096];
+    /// let mut tlsf: Tlsf<'_, u16, u16, 12, 16> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut pool);
+    ///
+    /// let layout = Layout::new::<[u8; 100]>();
+    /// let ptrs: Vec<_> = (0..6).map(|_| tlsf.allocate(layout).unwrap()).collect();
+    /// for &ptr in ptrs.iter().step_by(2) {
+    ///     unsafe { tlsf.deallocate(ptr, 1) };
+    /// }
+    ///
+    /// // The three memory blocks freed and the rest of the memory pool
+    /// let histogram = tlsf.free_histogram();
+    /// assert_eq!(histogram.iter().flatten().sum::<usize>(), 4);
+    /// ```
+    pub fn free_histogram(&self) -> [[usize; SLLEN]; FLLEN] {
+        let mut histogram = [[0; SLLEN]; FLLEN];
+        for (fl, counts) in histogram.iter_mut().enumerate() {
+            if !self.fl_bitmap.get_bit(fl as u32) {
+                continue;
+            }
+            for (sl, count) in counts.iter_mut().enumerate() {
+                if !self.sl_bitmap[fl].get_bit(sl as u32) {
+                    continue;
+                }
+                let mut next_free = self.first_free[fl][sl];
+                while let Some(block) = next_free {
+                    *count += 1;
+                    // Safety: `block` is a free block in one of the free lists
+                    next_free = unsafe { block.as_ref().next_free(self.link_key) };
+                }
+            }
+        }
+        histogram
+    }
+
+    /// Count the free and allocated memory blocks in each size class.
+    ///
+    /// `occupancy(pools)[fl][sl]` describes the memory blocks whose sizes
+    /// fall in the range of the free block list `(fl, sl)` (see
+    /// [`Self::free_list_min_size`]), whether they are free or not. Classes
+    /// with many free blocks but few allocated ones are over-provisioned,
+    /// and classes with allocated blocks but no free ones are starved, which
+    /// helps in choosing `FLLEN` and `SLLEN`.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(num_blocks)`).
+    ///
+    /// # Safety
+    ///
+    /// `pools` must satisfy the requirements of [`Self::check_integrity`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::Tlsf;
+    /// use std::{mem::MaybeUninit, alloc::Layout, ptr::{NonNull, slice_from_raw_parts_mut}};
+    ///
+    /// static mut POOL: MaybeUninit<[u8; 4096]> = MaybeUninit::uninit();
+    /// let pool_ptr = NonNull::new(unsafe { POOL.as_mut_ptr() }).unwrap();
+    ///
+    /// let mut tlsf: Tlsf<'_, u16, u16, 12, 16> = Tlsf::new();
+    /// let pool_len = unsafe { tlsf.insert_free_block_ptr(pool_ptr) }.unwrap().get();
+    /// let pool_ptr = NonNull::new(
+    ///     slice_from_raw_parts_mut(pool_ptr.as_ptr() as *mut u8, pool_len)
+    /// ).unwrap();
+    ///
+    /// let ptrs: Vec<_> = (0..3)
+    ///     .map(|_| tlsf.allocate(Layout::new::<[u8; 100]>()).unwrap())
+    ///     .collect();
+    ///
+    /// let occupancy = unsafe { tlsf.occupancy(&[pool_ptr]) };
+    /// let used_blocks: usize = occupancy.iter().flatten().map(|c| c.used_blocks).sum();
+    /// let free_blocks: usize = occupancy.iter().flatten().map(|c| c.free_blocks).sum();
+    /// assert_eq!(used_blocks, 3);
+    /// assert_eq!(free_blocks, 1);
+    ///
+    /// for ptr in ptrs {
+    ///     unsafe { tlsf.deallocate(ptr, 1) };
+    /// }
+    /// ```
+    pub unsafe fn occupancy(&self, pools: &[NonNull<[u8]>]) -> [[ClassOccupancy; SLLEN]; FLLEN] {
+        self.occupancy_iter(pools.iter().copied())
+    }
+
+    /// [`Self::occupancy`] taking an iterator.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::occupancy`].
+    pub(crate) unsafe fn occupancy_iter(
+        &self,
+        pools: impl Iterator<Item = NonNull<[u8]>>,
+    ) -> [[ClassOccupancy; SLLEN]; FLLEN] {
+        let mut occupancy = [[ClassOccupancy::ZERO; SLLEN]; FLLEN];
+        for pool in pools {
+            for (_, size_and_flags) in Self::pool_blocks(pool) {
+                let size = size_and_flags & SIZE_SIZE_MASK;
+                let class = if let Some((fl, sl)) = Self::map_floor(size) {
+                    &mut occupancy[fl][sl]
+                } else {
+                    continue;
+                };
+                if (size_and_flags & SIZE_USED) != 0 {
+                    class.used_blocks += 1;
+                    class.used_bytes += size;
+                } else {
+                    class.free_blocks += 1;
+                    class.free_bytes += size;
+                }
+            }
+        }
+        occupancy
+    }
+
+    /// Get the number of allocation requests made to `self` in each size
+    /// class since `self` was created.
+    ///
+    /// Each call to [`Self::allocate`] or [`Self::reallocate`] is counted,
+    /// whether it succeeds or not, in the size class of the smallest memory
+    /// block that can store the requested size and a block header, i.e.,
+    /// `allocation_histogram()[fl][sl]` counts the requests whose sizes plus
+    /// `GRANULARITY / 2`, rounded up to `GRANULARITY`, are within the free
+    /// block list `(fl, sl)`'s range (see [`Self::free_list_min_size`]).
+    /// Requests too large for any size class are not counted. The padding
+    /// required by large alignments is not taken into account.
+    ///
+    /// Comparing this with the size classes shows how well `FLLEN` and
+    /// `SLLEN` suit the workload.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::{Tlsf, GRANULARITY};
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// type MyTlsf<'pool> = Tlsf<'pool, u16, u16, 12, 16>;
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 4096];
+    /// let mut tlsf: MyTlsf<'_> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut pool);
+    ///
+    /// for size in [1, 10, 10, 100] {
+    ///     tlsf.allocate(Layout::from_size_align(size, 1).unwrap());
+    /// }
+    ///
+    /// let histogram = tlsf.allocation_histogram();
+    /// assert_eq!(histogram[0][0], 3);
+    /// assert_eq!(histogram.iter().flatten().sum::<usize>(), 4);
+    /// assert_eq!(MyTlsf::free_list_min_size(0, 0), GRANULARITY);
+    /// ```
+    #[cfg(feature = "allocation-histogram")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "allocation-histogram")))]
+    #[inline]
+    pub fn allocation_histogram(&self) -> [[usize; SLLEN]; FLLEN] {
+        self.allocation_histogram
+    }
+
+    /// Get the latencies of the operations of `self` measured so far.
+    ///
+    /// This lets hard real-time applications verify the bounded execution
+    /// time of the allocator on their exact configuration. See
+    /// [`TlsfTiming`] for how the latencies are measured.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rlsf::Tlsf;
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 65536];
+    /// let mut tlsf: Tlsf<'_, u16, u16, 12, 16> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut pool);
+    ///
+    /// for i in 1..100 {
+    ///     let ptr = tlsf.allocate(Layout::from_size_align(i * 8, 8).unwrap()).unwrap();
+    ///     unsafe { tlsf.deallocate(ptr, 8) };
+    /// }
+    ///
+    /// let timing = tlsf.timing();
+    /// assert_eq!(timing.allocate.count(), 99);
+    /// assert!(timing.allocate.min() <= timing.allocate.mean());
+    /// assert!(timing.allocate.mean() <= timing.allocate.max());
+    /// println!("worst-case allocation: {:?} cycles", timing.allocate.max());
+    /// ```
+    #[cfg(feature = "timing")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "timing")))]
+    #[inline]
+    pub fn timing(&self) -> TlsfTiming {
+        self.timing
+    }
+
+    /// Reset the latencies returned by [`Self::timing`], e.g., to exclude a
+    /// warm-up phase.
+    #[cfg(feature = "timing")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "timing")))]
+    #[inline]
+    pub fn reset_timing(&mut self) {
+        self.timing = TlsfTiming::ZERO;
+    }
+
+    /// Record an allocation request of `size` bytes in
+    /// `self.allocation_histogram`.
+    #[cfg(feature = "allocation-histogram")]
+    #[inline]
+    fn record_allocation_request(&mut self, size: usize) {
+        let block_size = size
+            .checked_add(mem::size_of::<UsedBlockHdr>() + GRANULARITY - 1)
+            .map(|x| x & !(GRANULARITY - 1));
+        if let Some((fl, sl)) = block_size.and_then(Self::map_floor) {
+            let count = &mut self.allocation_histogram[fl][sl];
+            *count = count.wrapping_add(1);
+        }
+    }
+
+    /// Get the operation counters of `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::Tlsf;
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 1024];
+    /// let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut pool);
+    ///
+    /// let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+    /// assert!(tlsf.allocate(Layout::new::<[u8; 4096]>()).is_none());
+    /// unsafe { tlsf.deallocate(ptr, 8) };
+    ///
+    /// let counters = tlsf.counters();
+    /// assert_eq!(counters.allocations, 1);
+    /// assert_eq!(counters.deallocations, 1);
+    /// assert_eq!(counters.failed_allocations, 1);
+    /// ```
+    #[cfg(feature = "stats")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "stats")))]
+    #[inline]
+    pub fn counters(&self) -> TlsfCounters {
+        self.counters
+    }
+
+    /// Get the total usable size of the live allocations, in bytes.
+    ///
+    /// This is smaller than the memory consumed by the allocations, which
+    /// includes the block headers and the paddings.
+    #[cfg(feature = "stats")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "stats")))]
+    #[inline]
+    pub fn in_use(&self) -> usize {
+        self.bytes_in_use
+    }
+
+    /// Get the highest value [`Self::in_use`] has reached since `self` was
+    /// created or [`Self::reset_peak`] was last called.
+    ///
+    /// Gathered from a representative workload, this high-water mark helps to
+    /// size the memory pools. Note that the memory pools need some extra room
+    /// for the block headers and fragmentation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::Tlsf;
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 1024];
+    /// let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut pool);
+    ///
+    /// let ptr1 = tlsf.allocate(Layout::new::<[u8; 200]>()).unwrap();
+    /// let ptr2 = tlsf.allocate(Layout::new::<[u8; 100]>()).unwrap();
+    /// unsafe { tlsf.deallocate(ptr1, 1) };
+    /// assert!(tlsf.in_use() >= 100);
+    /// assert!(tlsf.peak_in_use() >= 300);
+    ///
+    /// // Start a new measurement
+    /// tlsf.reset_peak();
+    /// assert_eq!(tlsf.peak_in_use(), tlsf.in_use());
+    /// # unsafe { tlsf.deallocate(ptr2, 1) };
+    /// ```
+    #[cfg(feature = "stats")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "stats")))]
+    #[inline]
+    pub fn peak_in_use(&self) -> usize {
+        self.peak_bytes_in_use
+    }
+
+    /// Get the total size of the memory pools and how much of it is free.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in constant time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::Tlsf;
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 1024];
+    /// let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut pool);
+    ///
+    /// let empty = tlsf.utilization();
+    /// let ptr = tlsf.allocate(Layout::new::<[u8; 500]>()).unwrap();
+    /// let used = tlsf.utilization();
+    /// assert_eq!(used.pool_bytes, empty.pool_bytes);
+    /// assert!(used.free_bytes <= empty.free_bytes - 500);
+    /// assert!(used.percent() >= 50);
+    /// # unsafe { tlsf.deallocate(ptr, 1) };
+    /// ```
+    #[cfg(feature = "stats")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "stats")))]
+    #[inline]
+    pub fn utilization(&self) -> TlsfUtilization {
+        TlsfUtilization {
+            pool_bytes: self.pool_bytes,
+            free_bytes: self.free_bytes,
+        }
+    }
+
+    /// Get a snapshot of the statistics of `self`.
+    ///
+    /// [`HeapStats::pool_bytes`] includes the sentinel blocks, and
+    /// [`HeapStats::num_allocations`] is derived from [`Self::counters`],
+    /// so it wraps around on overflow like them.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in constant time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::Tlsf;
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 1024];
+    /// let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut pool);
+    ///
+    /// let before = tlsf.heap_stats();
+    /// let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+    /// let after = tlsf.heap_stats();
+    /// assert_eq!(after.num_allocations - before.num_allocations, 1);
+    /// assert!(after.bytes_in_use >= 8);
+    /// assert!(after.free_bytes < before.free_bytes);
+    /// # unsafe { tlsf.deallocate(ptr, 8) };
+    /// ```
+    #[cfg(feature = "stats")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "stats")))]
+    #[inline]
+    pub fn heap_stats(&self) -> HeapStats {
+        HeapStats {
+            bytes_in_use: self.bytes_in_use,
+            peak_bytes_in_use: self.peak_bytes_in_use,
+            pool_bytes: self.pool_bytes,
+            free_bytes: self.free_bytes,
+            num_allocations: self
+                .counters
+                .allocations
+                .wrapping_sub(self.counters.deallocations),
+            failed_allocations: self.counters.failed_allocations,
+        }
+    }
+
+    /// Reset [`Self::peak_in_use`] to the current value of [`Self::in_use`].
+    #[cfg(feature = "stats")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "stats")))]
+    #[inline]
+    pub fn reset_peak(&mut self) {
+        self.peak_bytes_in_use = self.bytes_in_use;
+        #[cfg(feature = "pool-stats")]
+        for usage in self.pool_usage.iter_mut().flatten() {
+            usage.peak_in_use = usage.in_use;
+        }
+    }
+
+    /// Get the usage of each memory pool, in the order in which they were
+    /// created.
+    ///
+    /// This tells which memory pool (e.g., which memory bank of an embedded
+    /// system) is the bottleneck, which a heap-wide [`Self::peak_in_use`]
+    /// hides. A memory pool extended by [`Self::append_free_block_ptr`] is
+    /// reported as one. Only the first 8 memory pools are tracked; the
+    /// allocations in the others are only counted by [`Self::in_use`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::Tlsf;
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// let mut bank0 = [MaybeUninit::uninit(); 1024];
+    /// let mut bank1 = [MaybeUninit::uninit(); 1024];
+    /// let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut bank0);
+    /// let ptr = tlsf.allocate(Layout::new::<[u8; 800]>()).unwrap();
+    /// tlsf.insert_free_block(&mut bank1);
+    /// let ptr2 = tlsf.allocate(Layout::new::<[u8; 500]>()).unwrap();
+    /// unsafe { tlsf.deallocate(ptr2, 1) };
+    ///
+    /// let usage: Vec<_> = tlsf.pool_usage().collect();
+    /// assert!(usage[0].in_use >= 800);
+    /// assert_eq!(usage[1].in_use, 0);
+    /// assert!(usage[1].peak_in_use >= 500);
+    /// # unsafe { tlsf.deallocate(ptr, 1) };
+    /// ```
+    #[cfg(feature = "pool-stats")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "pool-stats")))]
+    pub fn pool_usage(&self) -> impl Iterator<Item = PoolUsage> + '_ {
+        self.pool_usage.iter().flatten().copied()
+    }
+
+    /// Start tracking the usage of the memory pool `start..start + len`.
+    #[cfg(feature = "pool-stats")]
+    fn track_pool(&mut self, start: NonNull<u8>, len: usize) {
+        if let Some(slot) = self.pool_usage.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(PoolUsage {
+                pool: nonnull_slice_from_raw_parts(start, len),
+                in_use: 0,
+                peak_in_use: 0,
+            });
+        }
+    }
+
+    /// Extend the tracked memory pool ending at `start` by `len` bytes.
+    #[cfg(feature = "pool-stats")]
+    fn track_pool_extension(&mut self, start: NonNull<u8>, len: usize) {
+        for usage in self.pool_usage.iter_mut().flatten() {
+            if nonnull_slice_end(usage.pool) == start.as_ptr() {
+                usage.pool = nonnull_slice_from_raw_parts(
+                    nonnull_slice_start(usage.pool),
+                    nonnull_slice_len(usage.pool) + len,
+                );
+                break;
+            }
+        }
+    }
+
+    /// Get the usage of the tracked memory pool containing `ptr`.
+    #[cfg(feature = "pool-stats")]
+    #[inline]
+    fn pool_usage_mut(&mut self, ptr: NonNull<u8>) -> Option<&mut PoolUsage> {
+        let addr = ptr.as_ptr() as usize;
+        self.pool_usage.iter_mut().flatten().find(|usage| {
+            addr.wrapping_sub(usage.pool.as_ptr() as *mut u8 as usize)
+                < nonnull_slice_len(usage.pool)
+        })
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn record_alloc_bytes(&mut self, ptr: NonNull<u8>, bytes: usize) {
+        self.bytes_in_use += bytes;
+        self.peak_bytes_in_use = self.peak_bytes_in_use.max(self.bytes_in_use);
+        #[cfg(feature = "pool-stats")]
+        if let Some(usage) = self.pool_usage_mut(ptr) {
+            usage.in_use += bytes;
+            usage.peak_in_use = usage.peak_in_use.max(usage.in_use);
+        }
+        #[cfg(not(feature = "pool-stats"))]
+        let _ = ptr;
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn record_dealloc_bytes(&mut self, ptr: NonNull<u8>, bytes: usize) {
+        self.bytes_in_use -= bytes;
+        #[cfg(feature = "pool-stats")]
+        if let Some(usage) = self.pool_usage_mut(ptr) {
+            usage.in_use -= bytes;
+        }
+        #[cfg(not(feature = "pool-stats"))]
+        let _ = ptr;
+    }
+
+    #[cfg(feature = "track-allocations")]
+    #[inline]
+    fn record_allocation_site(
+        &mut self,
+        block: NonNull<UsedBlockHdr>,
+        site: &'static Location<'static>,
+    ) {
+        if let Some(sites) = &mut self.allocation_sites {
+            sites.insert(block.as_ptr() as usize, site);
+        }
+    }
+
     /// Find the free block list to store a free block of the specified size.
     #[inline]
     fn map_floor(size: usize) -> Option<(usize, usize)> {
@@ -371,16 +1756,22 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
             // Safety: It's unreachable
             unreachable_unchecked()
         });
+        let key = self.link_key;
         let first_free = &mut self.first_free[fl][sl];
         let next_free = mem::replace(first_free, Some(block));
-        block.as_mut().next_free = next_free;
-        block.as_mut().prev_free = None;
+        block.as_mut().set_next_free(next_free, key);
+        block.as_mut().set_prev_free(None, key);
         if let Some(mut next_free) = next_free {
-            next_free.as_mut().prev_free = Some(block);
+            next_free.as_mut().set_prev_free(Some(block), key);
         }
 
         self.fl_bitmap.set_bit(fl as u32);
         self.sl_bitmap[fl].set_bit(sl as u32);
+
+        #[cfg(feature = "stats")]
+        {
+            self.free_bytes += size;
+        }
     }
 
     /// Remove the specified free block from the corresponding free block list.
@@ -391,16 +1782,22 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
     ///  - The free block must be currently included in a free block list.
     ///
     #[cfg_attr(target_arch = "wasm32", inline(never))]
-    unsafe fn unlink_free_block(&mut self, mut block: NonNull<FreeBlockHdr>, size: usize) {
-        let next_free = block.as_mut().next_free;
-        let prev_free = block.as_mut().prev_free;
+    unsafe fn unlink_free_block(&mut self, block: NonNull<FreeBlockHdr>, size: usize) {
+        #[cfg(feature = "stats")]
+        {
+            self.free_bytes -= size;
+        }
+
+        let key = self.link_key;
+        let next_free = block.as_ref().next_free(key);
+        let prev_free = block.as_ref().prev_free(key);
 
         if let Some(mut next_free) = next_free {
-            next_free.as_mut().prev_free = prev_free;
+            next_free.as_mut().set_prev_free(prev_free, key);
         }
 
         if let Some(mut prev_free) = prev_free {
-            prev_free.as_mut().next_free = next_free;
+            prev_free.as_mut().set_next_free(next_free, key);
         } else {
             let (fl, sl) = Self::map_floor(size).unwrap_or_else(|| {
                 debug_assert!(false, "could not map size {}", size);
@@ -477,7 +1874,10 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
 
         // Safety: The slice being created here
         let pool_len = self.insert_free_block_ptr_aligned(NonNull::new_unchecked(
-            core::ptr::slice_from_raw_parts_mut(start as *mut u8, len),
+            core::ptr::slice_from_raw_parts_mut(
+                ptr_with_addr(block.as_ptr() as *mut u8, start),
+                len,
+            ),
         ))?;
 
         // Safety: The sum should not wrap around because it represents the size
@@ -487,34 +1887,141 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         ))
     }
 
-    /// [`insert_free_block_ptr`] with a well-aligned slice passed by `block`.
-    pub(crate) unsafe fn insert_free_block_ptr_aligned(
+    /// Make the sentinel block `block` a guard of an end of a memory pool by
+    /// filling its unused space with [`POOL_GUARD_WORD`] (`pool-guards`
+    /// feature).
+    #[cfg(feature = "pool-guards")]
+    #[inline]
+    unsafe fn write_pool_guard(block: NonNull<BlockHdr>) {
+        *pool_guard_words(block) = [POOL_GUARD_WORD; 2];
+    }
+
+    /// Check that the guard at `block` is intact, i.e., it's still a
+    /// sentinel block linked to `prev_phys_block` (`None` for the guard at
+    /// the start of a memory pool) and its unused space is still filled with
+    /// [`POOL_GUARD_WORD`] (`pool-guards` feature).
+    #[cfg(feature = "pool-guards")]
+    #[inline]
+    unsafe fn is_intact_pool_guard(
+        block: NonNull<BlockHdr>,
+        prev_phys_block: Option<NonNull<BlockHdr>>,
+    ) -> bool {
+        let hdr = block.as_ref();
+        (hdr.size & !SIZE_CHECKSUM_MASK) == GRANULARITY | SIZE_USED | SIZE_SENTINEL
+            && hdr.prev_phys_block == prev_phys_block
+            && hdr.checksum_matches()
+            && *pool_guard_words(block) == [POOL_GUARD_WORD; 2]
+    }
+
+    /// Report a heap corruption if `next_phys_block`, the memory block
+    /// following `block`, is the guard at the end of a memory pool and has
+    /// been modified, e.g., by a buffer overflow from `block` (`pool-guards`
+    /// feature). A guard whose flags have been overwritten to make it look
+    /// like a free memory block is still recognized by its unused space.
+    #[cfg(feature = "pool-guards")]
+    #[inline]
+    unsafe fn verify_trailing_pool_guard(
+        block: NonNull<BlockHdr>,
+        next_phys_block: NonNull<BlockHdr>,
+    ) {
+        let size = next_phys_block.as_ref().size;
+        // A free memory block is at least `GRANULARITY` bytes long, so this
+        // doesn't read past it
+        let is_guard = (size & SIZE_SENTINEL) != 0
+            || ((size & SIZE_USED) == 0
+                && *pool_guard_words(next_phys_block) == [POOL_GUARD_WORD; 2]);
+        if is_guard && !Self::is_intact_pool_guard(next_phys_block, Some(block)) {
+            pool_guard_violation(next_phys_block);
+        }
+    }
+
+    /// Report a heap corruption if the guard preceding `block`, the first
+    /// memory block in a memory pool, has been modified (`pool-guards`
+    /// feature).
+    #[cfg(feature = "pool-guards")]
+    #[inline]
+    unsafe fn verify_leading_pool_guard(block: NonNull<BlockHdr>) {
+        let guard = NonNull::new_unchecked(block.as_ptr().cast::<u8>().sub(GRANULARITY)).cast();
+        if !Self::is_intact_pool_guard(guard, None) {
+            pool_guard_violation(guard);
+        }
+    }
+
+    /// [`insert_free_block_ptr`] with a well-aligned slice passed by `block`.
+    #[inline]
+    pub(crate) unsafe fn insert_free_block_ptr_aligned(
+        &mut self,
+        block: NonNull<[u8]>,
+    ) -> Option<NonZeroUsize> {
+        let pool_len = self.insert_free_block_ptr_aligned_inner(block, true);
+        #[cfg(feature = "observer")]
+        self.observe_pool_added(
+            nonnull_slice_start(block),
+            pool_len.map_or(0, NonZeroUsize::get),
+        );
+        pool_len
+    }
+
+    /// [`Self::insert_free_block_ptr_aligned`] that doesn't start the first
+    /// memory pool with a guard (`pool-guards` feature) if `leading_guard` is
+    /// `false`, for continuing an existing memory pool.
+    unsafe fn insert_free_block_ptr_aligned_inner(
         &mut self,
         block: NonNull<[u8]>,
+        leading_guard: bool,
     ) -> Option<NonZeroUsize> {
-        let start = block.as_ptr() as *mut u8 as usize;
+        let pool_ptr = block.as_ptr() as *mut u8;
+        let start = pool_ptr as usize;
         let mut size = nonnull_slice_len(block);
 
         let mut cursor = start;
 
-        while size >= GRANULARITY * 2 {
+        // The size of the sentinel block starting the current memory pool
+        // (`pool-guards` feature)
+        let mut guard_size = if cfg!(feature = "pool-guards") && leading_guard {
+            GRANULARITY
+        } else {
+            0
+        };
+
+        while size >= GRANULARITY * 2 + guard_size {
+            // The guard doesn't count towards `MAX_POOL_SIZE`, which
+            // limits the free memory block's size
             let chunk_size = if let Some(max_pool_size) = Self::MAX_POOL_SIZE {
-                size.min(max_pool_size)
+                size.min(max_pool_size.saturating_add(guard_size))
             } else {
                 size
             };
 
             debug_assert_eq!(chunk_size % GRANULARITY, 0);
 
+            // Start with a sentinel block (a permanently-used block) that
+            // guards the memory pool against the preceding memory
+            #[cfg(feature = "pool-guards")]
+            if guard_size != 0 {
+                let mut guard =
+                    NonNull::new_unchecked(ptr_with_addr(pool_ptr, cursor).cast::<BlockHdr>());
+                *guard.as_mut() = BlockHdr {
+                    size: GRANULARITY | SIZE_USED | SIZE_SENTINEL,
+                    prev_phys_block: None,
+                };
+                guard.as_mut().seal();
+                Self::write_pool_guard(guard);
+            }
+
             // The new free block
-            // Safety: `cursor` is not zero.
-            let mut block = NonNull::new_unchecked(cursor as *mut FreeBlockHdr);
+            let block_start = cursor.wrapping_add(guard_size);
+            let block_size = chunk_size - GRANULARITY - guard_size;
+            // Safety: `block_start` is not zero.
+            let mut block =
+                NonNull::new_unchecked(ptr_with_addr(pool_ptr, block_start).cast::<FreeBlockHdr>());
 
             // Initialize the new free block
             block.as_mut().common = BlockHdr {
-                size: chunk_size - GRANULARITY,
+                size: block_size,
                 prev_phys_block: None,
             };
+            block.as_mut().common.seal();
 
             // Cap the end with a sentinel block (a permanently-used block)
             let mut sentinel_block = block
@@ -527,15 +2034,44 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
                 size: GRANULARITY | SIZE_USED | SIZE_SENTINEL,
                 prev_phys_block: Some(block.cast()),
             };
+            sentinel_block.as_mut().common.seal();
+
+            #[cfg(feature = "pool-guards")]
+            Self::write_pool_guard(sentinel_block.cast());
+
+            #[cfg(any(feature = "poison", feature = "asan"))]
+            poison(
+                ptr_with_addr(pool_ptr, block_start).add(mem::size_of::<FreeBlockHdr>()),
+                sentinel_block.as_ptr() as *mut u8,
+            );
 
             // Link the free block to the corresponding free list
-            self.link_free_block(block, chunk_size - GRANULARITY);
+            self.link_free_block(block, block_size);
 
             // `cursor` can reach `usize::MAX + 1`, but in such a case, this
             // iteration must be the last one
             debug_assert!(cursor.checked_add(chunk_size).is_some() || size == chunk_size);
             size -= chunk_size;
             cursor = cursor.wrapping_add(chunk_size);
+
+            // Every memory pool but the first one is new
+            if cfg!(feature = "pool-guards") {
+                guard_size = GRANULARITY;
+            }
+        }
+
+        #[cfg(feature = "stats")]
+        {
+            self.pool_bytes += cursor.wrapping_sub(start);
+        }
+        // `append_free_block_ptr` (`!leading_guard`) extends the tracked
+        // memory pool instead
+        #[cfg(feature = "pool-stats")]
+        if leading_guard && cursor != start {
+            self.track_pool(
+                NonNull::new_unchecked(ptr_with_addr(pool_ptr, start)),
+                cursor.wrapping_sub(start),
+            );
         }
 
         NonZeroUsize::new(cursor.wrapping_sub(start))
@@ -618,10 +2154,13 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
             // memory pools of unknown sizes, so fall back to calling
             // `insert_free_block_ptr_aligned`.
             let block = nonnull_slice_from_raw_parts(start, len);
-            return self
-                .insert_free_block_ptr_aligned(block)
+            let pool_len = self
+                .insert_free_block_ptr_aligned_inner(block, true)
                 .map(NonZeroUsize::get)
                 .unwrap_or(0);
+            #[cfg(feature = "observer")]
+            self.observe_pool_added(start, pool_len);
+            return pool_len;
         } else if len == 0 {
             // `block` is so short that the `insert_free_block_ptr` will not
             // even create a sentinel block. We'll corrupt the structure if we
@@ -638,7 +2177,7 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         start = start.wrapping_sub(super::GRANULARITY);
         let sentinel_block = start as *mut UsedBlockHdr;
         debug_assert_eq!(
-            (*sentinel_block).common.size,
+            (*sentinel_block).common.size & !SIZE_CHECKSUM_MASK,
             GRANULARITY | SIZE_USED | SIZE_SENTINEL
         );
 
@@ -652,7 +2191,8 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         let last_nonassimilated_block;
         if (penultimate_block.as_ref().size & SIZE_USED) == 0 {
             let free_block = penultimate_block.cast::<FreeBlockHdr>();
-            let free_block_size = free_block.as_ref().common.size;
+            free_block.as_ref().common.verify();
+            let free_block_size = free_block.as_ref().common.size & !SIZE_CHECKSUM_MASK;
             debug_assert_eq!(
                 free_block_size,
                 free_block.as_ref().common.size & SIZE_SIZE_MASK
@@ -674,9 +2214,10 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
             end.wrapping_sub(start as usize),
         );
 
-        // Create a memory pool
+        // Create a memory pool. It continues the preceding one, so it doesn't
+        // need a guard at the start.
         let pool_len = self
-            .insert_free_block_ptr_aligned(block)
+            .insert_free_block_ptr_aligned_inner(block, false)
             .unwrap_or_else(|| {
                 debug_assert!(false, "`pool_size_to_contain_allocation` is an impostor");
                 // Safety: It's unreachable
@@ -688,9 +2229,23 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         // last non-assimilated block to form one continuous memory pool
         let mut first_block = nonnull_slice_start(block).cast::<FreeBlockHdr>();
         first_block.as_mut().common.prev_phys_block = last_nonassimilated_block;
+        first_block.as_mut().common.seal();
 
         // Exclude the assimilated part from the returned value
-        pool_len - (original_start as usize).wrapping_sub(start as usize)
+        let appended_len = pool_len - (original_start as usize).wrapping_sub(start as usize);
+
+        // The assimilated part was already counted
+        #[cfg(feature = "stats")]
+        {
+            self.pool_bytes -= pool_len - appended_len;
+        }
+        #[cfg(feature = "pool-stats")]
+        self.track_pool_extension(NonNull::new_unchecked(original_start), appended_len);
+
+        #[cfg(feature = "observer")]
+        self.observe_pool_added(NonNull::new_unchecked(original_start), appended_len);
+
+        appended_len
     }
 
     /// Create a new memory pool at the location specified by a slice.
@@ -756,8 +2311,143 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         let search_size = search_size.checked_add(GRANULARITY - 1)? & !(GRANULARITY - 1);
         let list_min_size = Self::map_ceil_and_unmap(search_size)?;
 
+        // Add the sentinel block size (twice if there's one at each end)
+        let num_sentinels = if cfg!(feature = "pool-guards") { 2 } else { 1 };
+        list_min_size.checked_add(GRANULARITY * num_sentinels)
+    }
+
+    /// Calculate the minimum size of a `GRANULARITY`-byte aligned memory block
+    /// to be passed to [`Self::create_standalone_allocation`].
+    ///
+    /// Returns `None` if the size is not representable in `usize`.
+    #[inline]
+    pub(crate) fn standalone_allocation_size(layout: Layout) -> Option<usize> {
+        // The extra bytes consumed by the header and padding. See
+        // `Tlsf::allocate` for details.
+        let max_overhead =
+            layout.align().saturating_sub(GRANULARITY / 2) + mem::size_of::<UsedBlockHdr>();
+
+        let size = layout.size().checked_add(max_overhead)?;
+        let size = size.checked_add(GRANULARITY - 1)? & !(GRANULARITY - 1);
+
         // Add the sentinel block size
-        list_min_size.checked_add(GRANULARITY)
+        size.checked_add(GRANULARITY)
+    }
+
+    /// Create a used memory block that doesn't belong to any memory pool at
+    /// the beginning of `block`.
+    ///
+    /// Returns the starting address of the payload and the location of
+    /// `GRANULARITY / 2` bytes that the caller can use to store arbitrary
+    /// data. The latter can be retrieved later by
+    /// [`Self::standalone_allocation_ftr`].
+    ///
+    /// The created allocation is compatible with [`Self::size_of_allocation`]
+    /// and [`Self::size_of_allocation_unknown_align`]. It must not be passed
+    /// to any methods of `Tlsf` that take `&mut self`.
+    ///
+    /// # Safety
+    ///
+    /// `block` must be aligned to `GRANULARITY` bytes and must be at least
+    /// [`Self::standalone_allocation_size`]`(layout)` bytes long. The caller
+    /// must have the ownership of `block`.
+    pub(crate) unsafe fn create_standalone_allocation(
+        block: NonNull<[u8]>,
+        layout: Layout,
+    ) -> (NonNull<u8>, NonNull<u8>) {
+        let mut block_hdr = nonnull_slice_start(block).cast::<UsedBlockHdr>();
+        debug_assert_eq!(block_hdr.as_ptr() as usize % GRANULARITY, 0);
+
+        // Decide the starting address of the payload
+        let unaligned_ptr = block_hdr.as_ptr() as usize + mem::size_of::<UsedBlockHdr>();
+        let ptr = NonNull::new_unchecked(ptr_with_addr(
+            block_hdr.as_ptr() as *mut u8,
+            unaligned_ptr.wrapping_add(layout.align() - 1) & !(layout.align() - 1),
+        ));
+
+        let overhead = ptr.as_ptr() as usize - block_hdr.as_ptr() as usize;
+        let size = (overhead + layout.size() + GRANULARITY - 1) & !(GRANULARITY - 1);
+        debug_assert!(size + GRANULARITY <= nonnull_slice_len(block));
+
+        block_hdr.as_mut().common = BlockHdr {
+            size: size | SIZE_USED,
+            prev_phys_block: None,
+        };
+        Self::record_align(ptr, block_hdr, layout.align());
+        block_hdr.as_mut().common.seal();
+
+        // Cap the end with a sentinel block. Unlike the ones in memory pools,
+        // its `prev_phys_block` is `None`, which is how
+        // `standalone_block_ftr` tells this block apart.
+        let mut sentinel_block = block_hdr
+            .as_ref()
+            .common
+            .next_phys_block()
+            .cast::<UsedBlockHdr>();
+        sentinel_block.as_mut().common = BlockHdr {
+            size: GRANULARITY | SIZE_USED | SIZE_SENTINEL,
+            prev_phys_block: None,
+        };
+        sentinel_block.as_mut().common.seal();
+
+        // Place a `UsedBlockPad` (used by `used_block_hdr_for_allocation`)
+        if layout.align() >= GRANULARITY {
+            (*UsedBlockPad::get_for_allocation(ptr)).block_hdr = block_hdr;
+        }
+
+        (ptr, Self::standalone_block_ftr(block_hdr).unwrap())
+    }
+
+    /// Get the location of the caller-defined data of an allocation created by
+    /// [`Self::create_standalone_allocation`]. Returns `None` if `ptr` is not
+    /// such an allocation.
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via
+    ///    `Self::{allocate, reallocate, create_standalone_allocation}`.
+    ///  - The memory block must have been allocated with the same alignment
+    ///    ([`Layout::align`]) as `align`.
+    ///
+    #[inline]
+    pub(crate) unsafe fn standalone_allocation_ftr(
+        ptr: NonNull<u8>,
+        align: usize,
+    ) -> Option<NonNull<u8>> {
+        Self::standalone_block_ftr(Self::used_block_hdr_for_allocation(ptr, align))
+    }
+
+    /// [`Self::standalone_allocation_ftr`] for an allocation with an unknown
+    /// alignment.
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must denote a memory block previously allocated via
+    ///    `Self::{allocate, reallocate, create_standalone_allocation}`.
+    ///
+    #[inline]
+    pub(crate) unsafe fn standalone_allocation_ftr_unknown_align(
+        ptr: NonNull<u8>,
+    ) -> Option<NonNull<u8>> {
+        Self::standalone_block_ftr(Self::used_block_hdr_for_allocation_unknown_align(ptr))
+    }
+
+    #[inline]
+    unsafe fn standalone_block_ftr(block: NonNull<UsedBlockHdr>) -> Option<NonNull<u8>> {
+        let next_phys_block = block.as_ref().common.next_phys_block();
+        if (next_phys_block.as_ref().size & SIZE_SENTINEL) != 0
+            && next_phys_block.as_ref().prev_phys_block.is_none()
+        {
+            // The sentinel block's unused space
+            Some(NonNull::new_unchecked(
+                next_phys_block
+                    .cast::<u8>()
+                    .as_ptr()
+                    .add(mem::size_of::<UsedBlockHdr>()),
+            ))
+        } else {
+            None
+        }
     }
 
     /// Attempt to allocate a block of memory.
@@ -768,7 +2458,42 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
     /// # Time Complexity
     ///
     /// This method will complete in constant time.
+    #[cfg_attr(feature = "track-allocations", track_caller)]
     pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        #[cfg(feature = "allocation-histogram")]
+        self.record_allocation_request(layout.size());
+        #[cfg(feature = "timing")]
+        let stopwatch = Stopwatch::start();
+        let ptr = self.allocate_untraced(layout);
+        #[cfg(feature = "timing")]
+        stopwatch.stop(&mut self.timing.allocate);
+        #[cfg(feature = "observer")]
+        if let (Some(observer), Some(ptr)) = (self.observer, ptr) {
+            observer.on_alloc(ptr, layout);
+        }
+        #[cfg(feature = "stats")]
+        if ptr.is_some() {
+            self.counters.allocations = self.counters.allocations.wrapping_add(1);
+        } else {
+            self.counters.failed_allocations = self.counters.failed_allocations.wrapping_add(1);
+        }
+        #[cfg(feature = "oom-report")]
+        if ptr.is_none() {
+            self.report_oom(layout);
+        }
+        trace_op!(
+            "Tlsf::allocate(size={}, align={}) -> {:#x}",
+            layout.size(),
+            layout.align(),
+            ptr.map_or(0, |p| p.as_ptr() as usize)
+        );
+        ptr
+    }
+
+    /// [`Self::allocate`] without emitting a trace record.
+    #[cfg_attr(feature = "track-allocations", track_caller)]
+    #[inline]
+    fn allocate_untraced(&mut self, layout: Layout) -> Option<NonNull<u8>> {
         unsafe {
             // The extra bytes consumed by the header and padding.
             //
@@ -787,38 +2512,58 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
             let (fl, sl) = self.search_suitable_free_block_list_for_allocation(search_size)?;
 
             // Get a free block: `block`
-            let first_free = self.first_free.get_unchecked_mut(fl).get_unchecked_mut(sl);
-            let block = first_free.unwrap_or_else(|| {
+            let block = self.first_free.get_unchecked(fl).get_unchecked(sl);
+            let block = block.unwrap_or_else(|| {
                 debug_assert!(false, "bitmap outdated");
                 // Safety: It's unreachable
                 unreachable_unchecked()
             });
+            #[cfg(feature = "randomize")]
+            let block = self.choose_free_block_randomly(block);
             let mut next_phys_block = block.as_ref().common.next_phys_block();
             let size_and_flags = block.as_ref().common.size;
-            let size = size_and_flags /* size_and_flags & SIZE_SIZE_MASK */;
+            let size = size_and_flags & !SIZE_CHECKSUM_MASK /* & SIZE_SIZE_MASK */;
             debug_assert_eq!(size, size_and_flags & SIZE_SIZE_MASK);
 
             debug_assert!(size >= search_size);
 
             // Unlink the free block. We are not using `unlink_free_block` because
-            // we already know `(fl, sl)` and that `block.prev_free` is `None`.
-            *first_free = block.as_ref().next_free;
-            if let Some(mut next_free) = *first_free {
-                next_free.as_mut().prev_free = None;
-            } else {
-                // The free list is now empty - update the bitmap
-                let sl_bitmap = self.sl_bitmap.get_unchecked_mut(fl);
-                sl_bitmap.clear_bit(sl as u32);
-                if *sl_bitmap == SLBitmap::ZERO {
-                    self.fl_bitmap.clear_bit(fl as u32);
+            // we already know `(fl, sl)` and that `block.prev_free` is `None`
+            // (unless `block` was chosen randomly).
+            #[cfg(feature = "randomize")]
+            self.unlink_free_block(block, size);
+            #[cfg(not(feature = "randomize"))]
+            {
+                let key = self.link_key;
+                let first_free = self.first_free.get_unchecked_mut(fl).get_unchecked_mut(sl);
+                *first_free = block.as_ref().next_free(key);
+                if let Some(mut next_free) = *first_free {
+                    next_free.as_mut().set_prev_free(None, key);
+                } else {
+                    // The free list is now empty - update the bitmap
+                    let sl_bitmap = self.sl_bitmap.get_unchecked_mut(fl);
+                    sl_bitmap.clear_bit(sl as u32);
+                    if *sl_bitmap == SLBitmap::ZERO {
+                        self.fl_bitmap.clear_bit(fl as u32);
+                    }
+                }
+
+                #[cfg(feature = "stats")]
+                {
+                    self.free_bytes -= size;
                 }
             }
 
+            // Leave a random part of `block` before the allocation free
+            #[cfg(feature = "randomize")]
+            let (block, size) = self.split_front_randomly(block, size, size - search_size);
+
             // Decide the starting address of the payload
             let unaligned_ptr = block.as_ptr() as *mut u8 as usize + mem::size_of::<UsedBlockHdr>();
-            let ptr = NonNull::new_unchecked(
-                (unaligned_ptr.wrapping_add(layout.align() - 1) & !(layout.align() - 1)) as *mut u8,
-            );
+            let ptr = NonNull::new_unchecked(ptr_with_addr(
+                block.as_ptr() as *mut u8,
+                unaligned_ptr.wrapping_add(layout.align() - 1) & !(layout.align() - 1),
+            ));
 
             if layout.align() < GRANULARITY {
                 debug_assert_eq!(unaligned_ptr, ptr.as_ptr() as usize);
@@ -835,6 +2580,15 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
             let new_size = (new_size + GRANULARITY - 1) & !(GRANULARITY - 1);
             debug_assert!(new_size <= search_size);
 
+            // Verify the poisoned part that is being handed out or will store
+            // the header of the remaining free block. The rest is verified
+            // when it's reused.
+            #[cfg(any(feature = "poison", feature = "asan"))]
+            unpoison(
+                (block.as_ptr() as *const u8).add(mem::size_of::<FreeBlockHdr>()),
+                (block.as_ptr() as *const u8).add(size.min(new_size + GRANULARITY)),
+            );
+
             if new_size == size {
                 // The allocation completely fills this free block.
                 // Updating `next_phys_block.prev_phys_block` is unnecessary in this
@@ -852,12 +2606,14 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
                 // Invariant: No two adjacent free blocks
                 debug_assert!((next_phys_block.as_ref().size & SIZE_USED) != 0);
                 next_phys_block.as_mut().prev_phys_block = Some(new_free_block.cast());
+                next_phys_block.as_mut().seal();
 
                 // Create the new free block header
                 new_free_block.as_mut().common = BlockHdr {
                     size: new_free_block_size,
                     prev_phys_block: Some(block.cast()),
                 };
+                new_free_block.as_mut().common.seal();
                 self.link_free_block(new_free_block, new_free_block_size);
             }
 
@@ -865,16 +2621,110 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
             // header. `prev_phys_block` is already set.
             let mut block = block.cast::<UsedBlockHdr>();
             block.as_mut().common.size = new_size | SIZE_USED;
+            Self::record_align(ptr, block, layout.align());
+            block.as_mut().common.seal();
 
             // Place a `UsedBlockPad` (used by `used_block_hdr_for_allocation`)
             if layout.align() >= GRANULARITY {
                 (*UsedBlockPad::get_for_allocation(ptr)).block_hdr = block;
             }
 
+            #[cfg(feature = "debug-leak-check")]
+            self.leak_check
+                .on_alloc(Self::size_of_allocation(ptr, layout.align()));
+            #[cfg(feature = "stats")]
+            self.record_alloc_bytes(ptr, Self::size_of_allocation(ptr, layout.align()));
+
+            #[cfg(feature = "track-allocations")]
+            self.record_allocation_site(block, Location::caller());
+
             Some(ptr)
         }
     }
 
+    /// Choose a free memory block from the free block list starting with
+    /// `first`: one of its first [`RANDOM_CANDIDATES`] memory blocks at random
+    /// if a random source is set, or `first` otherwise.
+    ///
+    /// # Safety
+    ///
+    /// `first` must be the first memory block of a free block list.
+    #[cfg(feature = "randomize")]
+    #[inline]
+    unsafe fn choose_free_block_randomly(
+        &self,
+        first: NonNull<FreeBlockHdr>,
+    ) -> NonNull<FreeBlockHdr> {
+        let mut block = first;
+        if let Some(random_source) = self.random_source {
+            for _ in 0..random_source() % RANDOM_CANDIDATES {
+                match block.as_ref().next_free(self.link_key) {
+                    Some(next_free) => block = next_free,
+                    None => break,
+                }
+            }
+        }
+        block
+    }
+
+    /// Split a random number of granules, up to `slack` bytes, off the front
+    /// of the unlinked free memory block `block` of `size` bytes and link
+    /// them as a free memory block. Returns the remaining part, whose header
+    /// is initialized as a free memory block's header but which is not
+    /// linked. Does nothing if no random source is set.
+    ///
+    /// # Safety
+    ///
+    ///  - `block` must be a free memory block of `size` bytes owned by
+    ///    `self` that isn't linked to any free block list.
+    ///  - `slack` must be a multiple of [`GRANULARITY`] not exceeding `size`.
+    ///
+    #[cfg(feature = "randomize")]
+    #[inline]
+    unsafe fn split_front_randomly(
+        &mut self,
+        mut block: NonNull<FreeBlockHdr>,
+        size: usize,
+        slack: usize,
+    ) -> (NonNull<FreeBlockHdr>, usize) {
+        let random_source = if let Some(random_source) = self.random_source {
+            random_source
+        } else {
+            return (block, size);
+        };
+
+        let offset = random_source() % (slack / GRANULARITY + 1) * GRANULARITY;
+        if offset == 0 {
+            return (block, size);
+        }
+
+        let mut rest: NonNull<FreeBlockHdr> =
+            NonNull::new_unchecked(block.cast::<u8>().as_ptr().add(offset)).cast();
+        let rest_size = size - offset;
+
+        // The header of `rest` is in the poisoned part of `block`
+        #[cfg(any(feature = "poison", feature = "asan"))]
+        unpoison(
+            rest.as_ptr() as *const u8,
+            (rest.as_ptr() as *const u8).add(mem::size_of::<FreeBlockHdr>()),
+        );
+
+        rest.as_mut().common = BlockHdr {
+            size: rest_size,
+            prev_phys_block: Some(block.cast()),
+        };
+        rest.as_mut().common.seal();
+        let mut next_phys_block = rest.as_ref().common.next_phys_block();
+        next_phys_block.as_mut().prev_phys_block = Some(rest.cast());
+        next_phys_block.as_mut().seal();
+
+        block.as_mut().common.size = offset;
+        block.as_mut().common.seal();
+        self.link_free_block(block, offset);
+
+        (rest, rest_size)
+    }
+
     /// Search for a non-empty free block list for allocation.
     #[inline]
     fn search_suitable_free_block_list_for_allocation(
@@ -965,10 +2815,26 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         );
 
         // Read it as `Option<NonNull<BlockHdr>>`.
-        if let Some(block_ptr) = *c2_prev_phys_block_ptr {
+        let prev_phys_block = *c2_prev_phys_block_ptr;
+
+        // If `ptr` has been deallocated, it might be poisoned. Choose Case 2
+        // so that `used_block_hdr_for_deallocation` finds the poisoned
+        // header.
+        #[cfg(feature = "poison")]
+        let prev_phys_block =
+            prev_phys_block.filter(|block_ptr| block_ptr.as_ptr() as usize != POISON_WORD);
+
+        // Likewise, it might be an encoded free list link, which is never
+        // aligned, and then Case 2 finds a size without `SIZE_USED`.
+        #[cfg(feature = "safe-linking")]
+        let prev_phys_block =
+            prev_phys_block.filter(|block_ptr| block_ptr.as_ptr() as usize % GRANULARITY == 0);
+
+        if let Some(block_ptr) = prev_phys_block {
             // Where does the block represented by `block_ptr` end?
             // (Note: `block_ptr.size` might include `SIZE_USED`.)
-            let block_end = block_ptr.as_ptr() as usize + block_ptr.as_ref().size;
+            let block_end =
+                block_ptr.as_ptr() as usize + (block_ptr.as_ref().size & !SIZE_CHECKSUM_MASK);
 
             if ptr.as_ptr() as usize > block_end {
                 // The block represented by `block_ptr` does not include `ptr`.
@@ -986,6 +2852,151 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         }
     }
 
+    /// Find the `UsedBlockHdr` for an allocation being deallocated or
+    /// reallocated with alignment `align` (or an unknown alignment if
+    /// `None`). Panics if the allocation has already been deallocated.
+    ///
+    /// A memory block's `SIZE_USED` bit is cleared on deallocation even if
+    /// the block is merged into the preceding free memory block (see
+    /// [`Self::deallocate_block`]), so a double free is detected unless the
+    /// memory has been allocated again since.
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must have been returned by an allocation function of `self`
+    ///    with alignment `align`. It may have been deallocated since, but
+    ///    its memory must not have been returned to the memory pool's owner.
+    ///
+    #[inline]
+    unsafe fn used_block_hdr_for_deallocation(
+        ptr: NonNull<u8>,
+        align: Option<usize>,
+    ) -> NonNull<UsedBlockHdr> {
+        let block = match align {
+            Some(align) => {
+                #[cfg(debug_assertions)]
+                Self::debug_check_align(ptr, align);
+
+                let block = Self::used_block_hdr_for_allocation(ptr, align);
+
+                // If the allocation has been deallocated, the header pointer
+                // in `UsedBlockPad` might have been overwritten by
+                // `FreeBlockHdr::prev_free` (or the poison pattern)
+                let overhead = (ptr.as_ptr() as usize).wrapping_sub(block.as_ptr() as usize);
+                if align >= GRANULARITY && !(GRANULARITY / 2..=align).contains(&overhead) {
+                    double_free(ptr);
+                }
+
+                // An encoded free list link is never aligned
+                #[cfg(feature = "safe-linking")]
+                if block.as_ptr() as usize % GRANULARITY != 0 {
+                    double_free(ptr);
+                }
+
+                block
+            }
+            None => Self::used_block_hdr_for_allocation_unknown_align(ptr),
+        };
+
+        let size = block.as_ref().common.size;
+        let is_freed = (size & SIZE_USED) == 0;
+        #[cfg(feature = "poison")]
+        let is_freed = is_freed || size == POISON_WORD;
+        if is_freed {
+            double_free(ptr);
+        }
+
+        block
+    }
+
+    /// Panic if the allocated memory block `ptr` wasn't allocated with
+    /// alignment `align` (as recorded by [`Self::record_align`]), which might
+    /// make `used_block_hdr_for_allocation` compute a wrong header location.
+    /// A freed memory block is left to the double free check.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::used_block_hdr_for_deallocation`].
+    #[cfg(debug_assertions)]
+    unsafe fn debug_check_align(ptr: NonNull<u8>, align: usize) {
+        let block = Self::used_block_hdr_for_allocation_unknown_align(ptr);
+        let size = block.as_ref().common.size;
+        let is_freed = (size & SIZE_USED) == 0;
+        #[cfg(feature = "poison")]
+        let is_freed = is_freed || size == POISON_WORD;
+        if !is_freed && !Self::is_allocated_with_align(ptr, block, align) {
+            align_mismatch(ptr, align);
+        }
+    }
+
+    /// Return whether the allocation `ptr` with the header `block` could
+    /// have been allocated with alignment `align`.
+    ///
+    /// This compares `align` with the alignment recorded by
+    /// [`Self::record_align`] if there is one. Otherwise, it's inferred from
+    /// the padding before the payload, which is exactly `GRANULARITY / 2`
+    /// bytes long (the header) if and only if the allocation's alignment is
+    /// smaller than `GRANULARITY`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote an allocated memory block with the header `block`.
+    #[inline]
+    unsafe fn is_allocated_with_align(
+        ptr: NonNull<u8>,
+        block: NonNull<UsedBlockHdr>,
+        align: usize,
+    ) -> bool {
+        let overhead = ptr.as_ptr() as usize - block.as_ptr() as usize;
+
+        #[cfg(debug_assertions)]
+        if overhead != GRANULARITY / 2 {
+            return *Self::recorded_align_slot(ptr) == align;
+        } else if SIZE_ALIGN_RECORDED {
+            let align_log2 = (block.as_ref().common.size & SIZE_CHECKSUM_MASK) >> 2;
+            return 1 << align_log2 == align;
+        }
+
+        ptr.as_ptr() as usize % align == 0
+            && (align >= GRANULARITY) == (overhead != GRANULARITY / 2)
+    }
+
+    /// Record the alignment `align` of the allocation `ptr` with the header
+    /// `block` in debug builds, so that `debug_check_align` can detect any
+    /// mismatch. This must be called whenever `block`'s size is modified.
+    ///
+    /// An alignment smaller than `GRANULARITY` is stored as its log2 in
+    /// `block`'s size if [`SIZE_ALIGN_RECORDED`]. A larger one is stored in
+    /// the padding before [`UsedBlockPad`], which is at least
+    /// `GRANULARITY / 2` bytes long in this case.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote an allocated memory block with the header `block`
+    /// and the alignment `align`.
+    #[inline(always)]
+    unsafe fn record_align(ptr: NonNull<u8>, block: NonNull<UsedBlockHdr>, align: usize) {
+        #[cfg(debug_assertions)]
+        if align >= GRANULARITY {
+            *Self::recorded_align_slot(ptr) = align;
+        } else if SIZE_ALIGN_RECORDED {
+            let size = &mut (*block.as_ptr()).common.size;
+            *size = (*size & !SIZE_CHECKSUM_MASK) | ((align.trailing_zeros() as usize) << 2);
+        }
+        #[cfg(not(debug_assertions))]
+        let _ = (ptr, block, align);
+    }
+
+    /// Get the location where [`Self::record_align`] stores the alignment of
+    /// the allocation `ptr` if it's larger than or equal to `GRANULARITY`.
+    #[cfg(debug_assertions)]
+    #[inline]
+    fn recorded_align_slot(ptr: NonNull<u8>) -> *mut usize {
+        UsedBlockPad::get_for_allocation(ptr)
+            .cast::<usize>()
+            .wrapping_sub(1)
+    }
+
     /// Deallocate a previously allocated memory block.
     ///
     /// # Time Complexity
@@ -998,11 +3009,62 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
     ///  - The memory block must have been allocated with the same alignment
     ///    ([`Layout::align`]) as `align`.
     ///
+    /// # Panics
+    ///
+    /// As a safety net, this method panics if it detects that `ptr` has
+    /// already been deallocated. This is guaranteed to happen if `ptr`'s
+    /// memory hasn't been allocated again since the previous deallocation.
     pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, align: usize) {
+        trace_op!(
+            "Tlsf::deallocate(ptr={:#x}, align={})",
+            ptr.as_ptr() as usize,
+            align
+        );
+        // Safety: `ptr` is a previously allocated memory block with the same
+        //         alignment as `align`. This is upheld by the caller.
+        let block = Self::used_block_hdr_for_deallocation(ptr, Some(align));
+        // Report the size taken from the validated header
+        #[cfg(feature = "observer")]
+        if let Some(observer) = self.observer {
+            observer.on_dealloc(ptr, Self::payload_size(ptr, block));
+        }
+        #[cfg(feature = "timing")]
+        let stopwatch = Stopwatch::start();
+        self.deallocate_used_block(ptr, block);
+        #[cfg(feature = "timing")]
+        stopwatch.stop(&mut self.timing.deallocate);
+        #[cfg(feature = "stats")]
+        self.count_deallocation();
+    }
+
+    /// [`Self::deallocate`] without emitting a trace record.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::deallocate`].
+    #[inline]
+    unsafe fn deallocate_untraced(&mut self, ptr: NonNull<u8>, align: usize) {
         // Safety: `ptr` is a previously allocated memory block with the same
         //         alignment as `align`. This is upheld by the caller.
-        let block = Self::used_block_hdr_for_allocation(ptr, align).cast::<BlockHdr>();
-        self.deallocate_block(block);
+        let block = Self::used_block_hdr_for_deallocation(ptr, Some(align));
+        self.deallocate_used_block(ptr, block);
+    }
+
+    /// Deallocate the allocation `ptr` whose header `block` has been
+    /// validated by [`Self::used_block_hdr_for_deallocation`].
+    ///
+    /// # Safety
+    ///
+    /// `block` must be the header of the live allocation `ptr` of `self`.
+    #[inline]
+    unsafe fn deallocate_used_block(&mut self, ptr: NonNull<u8>, block: NonNull<UsedBlockHdr>) {
+        #[cfg(feature = "debug-leak-check")]
+        self.leak_check.on_dealloc(Self::payload_size(ptr, block));
+        #[cfg(feature = "stats")]
+        self.record_dealloc_bytes(ptr, Self::payload_size(ptr, block));
+        #[cfg(not(any(feature = "debug-leak-check", feature = "stats")))]
+        let _ = ptr;
+        self.deallocate_block(block.cast());
     }
 
     /// Deallocate a previously allocated memory block with an unknown alignment.
@@ -1019,19 +3081,98 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
     ///  - `ptr` must denote a memory block previously allocated via `self`.
     ///
     pub(crate) unsafe fn deallocate_unknown_align(&mut self, ptr: NonNull<u8>) {
+        trace_op!(
+            "Tlsf::deallocate(ptr={:#x}, align=0)",
+            ptr.as_ptr() as usize
+        );
         // Safety: `ptr` is a previously allocated memory block. This is upheld
         //         by the caller.
-        let block = Self::used_block_hdr_for_allocation_unknown_align(ptr).cast::<BlockHdr>();
-        self.deallocate_block(block);
+        let block = Self::used_block_hdr_for_deallocation(ptr, None);
+        #[cfg(feature = "observer")]
+        if let Some(observer) = self.observer {
+            observer.on_dealloc(ptr, Self::payload_size(ptr, block));
+        }
+        #[cfg(feature = "timing")]
+        let stopwatch = Stopwatch::start();
+        self.deallocate_used_block(ptr, block);
+        #[cfg(feature = "timing")]
+        stopwatch.stop(&mut self.timing.deallocate);
+        #[cfg(feature = "stats")]
+        self.count_deallocation();
+    }
+
+    /// Deallocate a previously allocated memory block with alignment `align`
+    /// (or an unknown alignment if `None`), and return the payload of the
+    /// free memory block it was merged into (see
+    /// [`Self::for_each_free_payload`]).
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::deallocate`] and [`Self::deallocate_unknown_align`].
+    pub(crate) unsafe fn deallocate_and_get_free_payload(
+        &mut self,
+        ptr: NonNull<u8>,
+        align: Option<usize>,
+    ) -> NonNull<[u8]> {
+        trace_op!(
+            "Tlsf::deallocate(ptr={:#x}, align={})",
+            ptr.as_ptr() as usize,
+            align.unwrap_or(0)
+        );
+        // Safety: Upheld by the caller
+        let block = Self::used_block_hdr_for_deallocation(ptr, align);
+        #[cfg(feature = "debug-leak-check")]
+        self.leak_check.on_dealloc(Self::payload_size(ptr, block));
+        #[cfg(feature = "stats")]
+        self.record_dealloc_bytes(ptr, Self::payload_size(ptr, block));
+        #[cfg(feature = "observer")]
+        if let Some(observer) = self.observer {
+            observer.on_dealloc(ptr, Self::payload_size(ptr, block));
+        }
+        #[cfg(feature = "timing")]
+        let stopwatch = Stopwatch::start();
+        let block = self.deallocate_block(block.cast());
+        #[cfg(feature = "timing")]
+        stopwatch.stop(&mut self.timing.deallocate);
+        #[cfg(feature = "stats")]
+        self.count_deallocation();
+        Self::free_payload(block)
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn count_deallocation(&mut self) {
+        self.counters.deallocations = self.counters.deallocations.wrapping_add(1);
     }
 
     /// Deallocate a previously allocated memory block. Takes a pointer to
-    /// `BlockHdr` instead of a payload pointer.
+    /// `BlockHdr` instead of a payload pointer. Returns the resulting free
+    /// memory block.
     #[inline]
-    unsafe fn deallocate_block(&mut self, mut block: NonNull<BlockHdr>) {
-        let mut size = block.as_ref().size & !SIZE_USED;
+    unsafe fn deallocate_block(&mut self, mut block: NonNull<BlockHdr>) -> NonNull<FreeBlockHdr> {
+        block.as_ref().verify();
+        let mut size = block.as_ref().size & !(SIZE_USED | SIZE_CHECKSUM_MASK);
         debug_assert!((block.as_ref().size & SIZE_USED) != 0);
 
+        #[cfg(feature = "track-allocations")]
+        if let Some(sites) = &mut self.allocation_sites {
+            sites.remove(&(block.as_ptr() as usize));
+        }
+
+        #[cfg(feature = "zero-on-free")]
+        {
+            let start = block.as_ptr() as *mut u8;
+            self.zero_on_free(start.add(mem::size_of::<UsedBlockHdr>()), start.add(size));
+        }
+
+        // The part of the resulting free block's payload to be poisoned. It
+        // will be extended to include the headers of the merged blocks.
+        #[cfg(any(feature = "poison", feature = "asan"))]
+        let (mut poison_start, mut poison_end) = {
+            let start = block.as_ptr() as *mut u8;
+            (start.add(mem::size_of::<FreeBlockHdr>()), start.add(size))
+        };
+
         // This variable tracks whose `prev_phys_block` we should update.
         let mut new_next_phys_block;
 
@@ -1039,9 +3180,12 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         // free block
         // Safety: `block.common` should be fully up-to-date and valid
         let next_phys_block = block.as_ref().next_phys_block();
+        #[cfg(feature = "pool-guards")]
+        Self::verify_trailing_pool_guard(block, next_phys_block);
+        next_phys_block.as_ref().verify();
         let next_phys_block_size_and_flags = next_phys_block.as_ref().size;
         if (next_phys_block_size_and_flags & SIZE_USED) == 0 {
-            let next_phys_block_size = next_phys_block_size_and_flags;
+            let next_phys_block_size = next_phys_block_size_and_flags & !SIZE_CHECKSUM_MASK;
             debug_assert_eq!(
                 next_phys_block_size_and_flags & SIZE_SIZE_MASK,
                 next_phys_block_size
@@ -1056,18 +3200,27 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
             // sentinel block
             new_next_phys_block = next_phys_block.as_ref().next_phys_block();
 
+            #[cfg(feature = "pool-guards")]
+            Self::verify_trailing_pool_guard(next_phys_block, new_next_phys_block);
+
             // Unlink `next_phys_block`.
             self.unlink_free_block(next_phys_block.cast(), next_phys_block_size);
+
+            #[cfg(any(feature = "poison", feature = "asan"))]
+            {
+                poison_end = poison_end.add(mem::size_of::<FreeBlockHdr>());
+            }
         } else {
             new_next_phys_block = next_phys_block;
         }
 
         // Merge with the previous block if it's a free block.
         if let Some(prev_phys_block) = block.as_ref().prev_phys_block {
+            prev_phys_block.as_ref().verify();
             let prev_phys_block_size_and_flags = prev_phys_block.as_ref().size;
 
             if (prev_phys_block_size_and_flags & SIZE_USED) == 0 {
-                let prev_phys_block_size = prev_phys_block_size_and_flags;
+                let prev_phys_block_size = prev_phys_block_size_and_flags & !SIZE_CHECKSUM_MASK;
                 debug_assert_eq!(
                     prev_phys_block_size_and_flags & SIZE_SIZE_MASK,
                     prev_phys_block_size
@@ -1079,6 +3232,15 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
                 // Unlink `prev_phys_block`.
                 self.unlink_free_block(prev_phys_block.cast(), prev_phys_block_size);
 
+                // Mark the original header as not in use so that deallocating
+                // it again is detected by `used_block_hdr_for_deallocation`
+                block.as_mut().size &= !SIZE_USED;
+
+                #[cfg(any(feature = "poison", feature = "asan"))]
+                {
+                    poison_start = block.as_ptr() as *mut u8;
+                }
+
                 // Move `block` to where `prev_phys_block` is located. By doing
                 // this, `block` will implicitly inherit `prev_phys_block.
                 // as_ref().prev_phys_block`.
@@ -1086,9 +3248,18 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
             }
         }
 
+        #[cfg(feature = "pool-guards")]
+        if block.as_ref().prev_phys_block.is_none() {
+            Self::verify_leading_pool_guard(block);
+        }
+
+        #[cfg(any(feature = "poison", feature = "asan"))]
+        poison(poison_start, poison_end);
+
         // Write the new free block's size and flags.
         debug_assert!((size & SIZE_USED) == 0);
         block.as_mut().size = size;
+        block.as_mut().seal();
 
         // Link this free block to the corresponding free list
         let block = block.cast::<FreeBlockHdr>();
@@ -1097,6 +3268,78 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         // Link `new_next_phys_block.prev_phys_block` to `block`
         debug_assert_eq!(new_next_phys_block, block.as_ref().common.next_phys_block());
         new_next_phys_block.as_mut().prev_phys_block = Some(block.cast());
+        new_next_phys_block.as_mut().seal();
+
+        block
+    }
+
+    /// Check the header of the memory block containing the allocation `ptr`
+    /// for signs of corruption, such as a double free, an invalid pointer,
+    /// or a buffer overflow from the preceding memory block. `align` is the
+    /// allocation's alignment if known.
+    ///
+    /// Returns a description of the first problem found. Only the memory
+    /// block and the header of the next memory block are examined, so this
+    /// can be called without excluding concurrent operations on other
+    /// allocations.
+    ///
+    /// # Safety
+    ///
+    ///  - `ptr` must be a pointer that was returned by an allocation function
+    ///    of some instance of `Self` (it might have been deallocated since).
+    ///  - The memory pool containing it must still be accessible.
+    ///
+    #[cfg(feature = "hardened")]
+    pub(crate) unsafe fn check_allocation(
+        ptr: NonNull<u8>,
+        align: Option<usize>,
+    ) -> Result<(), &'static str> {
+        let payload_start = ptr.as_ptr() as usize;
+        if payload_start % (GRANULARITY / 2) != 0 {
+            return Err("invalid pointer (misaligned)");
+        }
+
+        let block = match align {
+            Some(align) => Self::used_block_hdr_for_allocation(ptr, align),
+            None => Self::used_block_hdr_for_allocation_unknown_align(ptr),
+        }
+        .cast::<BlockHdr>();
+        let block_start = block.as_ptr() as usize;
+        if block_start % GRANULARITY != 0 || block_start >= payload_start {
+            return Err("corrupted padding of an over-aligned allocation");
+        }
+
+        let size = block.as_ref().size;
+        if (size & SIZE_USED) == 0 {
+            return Err("double free or corrupted block header (block not in use)");
+        }
+        if (size & SIZE_SENTINEL) != 0 {
+            return Err("invalid pointer or corrupted block header (sentinel block)");
+        }
+        let block_size = size & SIZE_SIZE_MASK;
+        if block_size == 0 || payload_start > block_start + block_size {
+            return Err("corrupted block header (bad size)");
+        }
+        if !block.as_ref().checksum_matches() {
+            return Err("corrupted block header (checksum mismatch)");
+        }
+
+        // The next block must link back to this block, unless it's the
+        // sentinel of a standalone allocation (see
+        // `create_standalone_allocation`)
+        let next_phys_block = block.as_ref().next_phys_block();
+        let links_back = match next_phys_block.as_ref().prev_phys_block {
+            Some(prev_phys_block) => prev_phys_block == block,
+            None => {
+                (next_phys_block.as_ref().size & SIZE_SENTINEL) != 0
+                    && block.as_ref().prev_phys_block.is_none()
+            }
+        };
+        if !links_back {
+            return Err("corrupted block header (next block doesn't link back)");
+        }
+
+        Ok(())
     }
 
     /// Get the payload size of the allocation. The returned size might be
@@ -1112,9 +3355,17 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
     pub(crate) unsafe fn size_of_allocation(ptr: NonNull<u8>, align: usize) -> usize {
         // Safety: `ptr` is a previously allocated memory block with the same
         //         alignment as `align`. This is upheld by the caller.
-        let block = Self::used_block_hdr_for_allocation(ptr, align);
+        Self::payload_size(ptr, Self::used_block_hdr_for_allocation(ptr, align))
+    }
 
-        let size = block.as_ref().common.size - SIZE_USED;
+    /// Get the payload size of the allocation `ptr` whose header is `block`.
+    ///
+    /// # Safety
+    ///
+    /// `block` must be the header of the allocation `ptr`.
+    #[inline]
+    unsafe fn payload_size(ptr: NonNull<u8>, block: NonNull<UsedBlockHdr>) -> usize {
+        let size = (block.as_ref().common.size - SIZE_USED) & !SIZE_CHECKSUM_MASK;
         debug_assert_eq!(size, block.as_ref().common.size & SIZE_SIZE_MASK);
 
         let block_end = block.as_ptr() as usize + size;
@@ -1136,7 +3387,7 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         //         This is upheld by the caller.
         let block = Self::used_block_hdr_for_allocation_unknown_align(ptr);
 
-        let size = block.as_ref().common.size - SIZE_USED;
+        let size = (block.as_ref().common.size - SIZE_USED) & !SIZE_CHECKSUM_MASK;
         debug_assert_eq!(size, block.as_ref().common.size & SIZE_SIZE_MASK);
 
         let block_end = block.as_ptr() as usize + size;
@@ -1178,14 +3429,48 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
     ///  - The memory block must have been allocated with the same alignment
     ///    ([`Layout::align`]) as `new_layout`.
     ///
+    /// # Panics
+    ///
+    /// Like [`Self::deallocate`], this method panics if it detects that `ptr`
+    /// has already been deallocated.
+    #[cfg_attr(feature = "track-allocations", track_caller)]
     pub unsafe fn reallocate(
         &mut self,
         ptr: NonNull<u8>,
         new_layout: Layout,
+    ) -> Option<NonNull<u8>> {
+        #[cfg(feature = "allocation-histogram")]
+        self.record_allocation_request(new_layout.size());
+        let new_ptr = self.reallocate_untraced(ptr, new_layout);
+        #[cfg(feature = "observer")]
+        if let (Some(observer), Some(new_ptr)) = (self.observer, new_ptr) {
+            observer.on_realloc(ptr, new_ptr, new_layout);
+        }
+        trace_op!(
+            "Tlsf::reallocate(ptr={:#x}, size={}, align={}) -> {:#x}",
+            ptr.as_ptr() as usize,
+            new_layout.size(),
+            new_layout.align(),
+            new_ptr.map_or(0, |p| p.as_ptr() as usize)
+        );
+        new_ptr
+    }
+
+    /// [`Self::reallocate`] without emitting a trace record.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::reallocate`].
+    #[cfg_attr(feature = "track-allocations", track_caller)]
+    #[inline]
+    unsafe fn reallocate_untraced(
+        &mut self,
+        ptr: NonNull<u8>,
+        new_layout: Layout,
     ) -> Option<NonNull<u8>> {
         // Safety: `ptr` is a previously allocated memory block with the same
         //         alignment as `align`. This is upheld by the caller.
-        let block = Self::used_block_hdr_for_allocation(ptr, new_layout.align());
+        let block = Self::used_block_hdr_for_deallocation(ptr, Some(new_layout.align()));
 
         // Do this early so that the compiler can de-duplicate common
         // subexpressions such as `block.as_ref().common.size - SIZE_USED`
@@ -1194,22 +3479,140 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         // First try to shrink or grow the block in-place (i.e., without
         // allocating a whole new memory block).
         if let Some(x) = self.reallocate_inplace(ptr, block, new_layout) {
+            #[cfg(feature = "debug-leak-check")]
+            {
+                self.leak_check.on_dealloc(old_size);
+                self.leak_check
+                    .on_alloc(Self::size_of_allocation(x, new_layout.align()));
+            }
+
+            #[cfg(feature = "stats")]
+            {
+                self.record_dealloc_bytes(ptr, old_size);
+                self.record_alloc_bytes(x, Self::size_of_allocation(x, new_layout.align()));
+            }
+
+            #[cfg(feature = "track-allocations")]
+            self.record_allocation_site(block, Location::caller());
+
             return Some(x);
         }
 
         // Allocate a whole new memory block
-        let new_ptr = self.allocate(new_layout)?;
+        let new_ptr = self.allocate_untraced(new_layout);
+        #[cfg(feature = "stats")]
+        if new_ptr.is_some() {
+            self.counters.reallocation_moves = self.counters.reallocation_moves.wrapping_add(1);
+        } else {
+            self.counters.failed_allocations = self.counters.failed_allocations.wrapping_add(1);
+        }
+        #[cfg(feature = "oom-report")]
+        if new_ptr.is_none() {
+            self.report_oom(new_layout);
+        }
+        let new_ptr = new_ptr?;
 
         // Move the existing data into the new location
         debug_assert!(new_layout.size() >= old_size);
         core::ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), old_size);
 
         // Deallocate the old memory block.
-        self.deallocate(ptr, new_layout.align());
+        self.deallocate_untraced(ptr, new_layout.align());
 
         Some(new_ptr)
     }
 
+    /// Shrink or grow a previously allocated memory block like
+    /// [`Self::reallocate`] after checking that `old_layout`, the layout the
+    /// caller believes the memory block to have, agrees with its header.
+    ///
+    /// Returns `Err(_)` without touching the memory block if `old_layout`'s
+    /// alignment differs from `new_layout`'s, `ptr` wasn't allocated with
+    /// `old_layout`'s alignment, or `old_layout`'s size exceeds the memory
+    /// block's usable size. Otherwise, returns `Ok(_)` with the result of
+    /// [`Self::reallocate`]. This catches the bookkeeping errors of callers
+    /// that track allocation sizes themselves (e.g., C code behind a shim),
+    /// which would otherwise lead to out-of-bounds copies.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(old_size)`).
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a memory block previously allocated via `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::{LayoutMismatch, Tlsf};
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 4096];
+    /// let mut tlsf: Tlsf<'_, u16, u16, 12, 16> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut pool);
+    ///
+    /// let layout = Layout::from_size_align(64, 8).unwrap();
+    /// let ptr = tlsf.allocate(layout).unwrap();
+    ///
+    /// // The caller lost track of the size
+    /// let wrong_layout = Layout::from_size_align(1000, 8).unwrap();
+    /// let new_layout = Layout::from_size_align(2000, 8).unwrap();
+    /// assert!(matches!(
+    ///     unsafe { tlsf.reallocate_checked(ptr, wrong_layout, new_layout) },
+    ///     Err(LayoutMismatch::Size { claimed: 1000, .. })
+    /// ));
+    ///
+    /// let ptr = unsafe { tlsf.reallocate_checked(ptr, layout, new_layout) }
+    ///     .unwrap()
+    ///     .unwrap();
+    /// ```
+    #[cfg_attr(feature = "track-allocations", track_caller)]
+    pub unsafe fn reallocate_checked(
+        &mut self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<Option<NonNull<u8>>, LayoutMismatch> {
+        Self::check_layout(ptr, old_layout, new_layout)?;
+        Ok(self.reallocate(ptr, new_layout))
+    }
+
+    /// Check the layouts passed to [`Self::reallocate_checked`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a memory block previously allocated via `Self`.
+    pub(crate) unsafe fn check_layout(
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<(), LayoutMismatch> {
+        if old_layout.align() != new_layout.align() {
+            return Err(LayoutMismatch::AlignChange);
+        }
+
+        // Find the header without trusting `old_layout`, and then check that
+        // `old_layout.align()` would lead to the same header
+        let block = Self::used_block_hdr_for_deallocation(ptr, None);
+        if !Self::is_allocated_with_align(ptr, block, old_layout.align()) {
+            return Err(LayoutMismatch::Align {
+                claimed: old_layout.align(),
+            });
+        }
+
+        let usable = block.as_ptr() as usize + (block.as_ref().common.size & SIZE_SIZE_MASK)
+            - ptr.as_ptr() as usize;
+        if old_layout.size() > usable {
+            return Err(LayoutMismatch::Size {
+                claimed: old_layout.size(),
+                usable,
+            });
+        }
+
+        Ok(())
+    }
+
     /// A subroutine of [`Self::reallocate`] that tries to reallocate a memory
     /// block in-place.
     #[inline]
@@ -1230,7 +3633,8 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         let new_size = overhead.checked_add(new_layout.size())?;
         let new_size = new_size.checked_add(GRANULARITY - 1)? & !(GRANULARITY - 1);
 
-        let old_size = block.as_ref().common.size - SIZE_USED;
+        block.as_ref().common.verify();
+        let old_size = (block.as_ref().common.size - SIZE_USED) & !SIZE_CHECKSUM_MASK;
         debug_assert_eq!(old_size, block.as_ref().common.size & SIZE_SIZE_MASK);
 
         // Shrinking
@@ -1248,11 +3652,20 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
                     NonNull::new_unchecked(block.cast::<u8>().as_ptr().add(new_size)).cast();
                 let mut new_free_block_size = shrink_by;
 
+                #[cfg(feature = "zero-on-free")]
+                self.zero_on_free(
+                    new_free_block.as_ptr() as *mut u8,
+                    (new_free_block.as_ptr() as *mut u8).add(shrink_by),
+                );
+
                 // If the next block is a free block...
                 let mut next_phys_block = block.as_ref().common.next_phys_block();
+                next_phys_block.as_ref().verify();
                 let next_phys_block_size_and_flags = next_phys_block.as_ref().size;
+                #[cfg(any(feature = "poison", feature = "asan"))]
+                let mut poison_end = next_phys_block.as_ptr() as *mut u8;
                 if (next_phys_block_size_and_flags & SIZE_USED) == 0 {
-                    let next_phys_block_size = next_phys_block_size_and_flags;
+                    let next_phys_block_size = next_phys_block_size_and_flags & !SIZE_CHECKSUM_MASK;
                     debug_assert_eq!(
                         next_phys_block_size,
                         next_phys_block_size_and_flags & SIZE_SIZE_MASK
@@ -1265,19 +3678,35 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
 
                     let mut next_next_phys_block = next_phys_block.as_ref().next_phys_block();
                     next_next_phys_block.as_mut().prev_phys_block = Some(new_free_block.cast());
+                    next_next_phys_block.as_mut().seal();
+
+                    #[cfg(any(feature = "poison", feature = "asan"))]
+                    {
+                        poison_end = poison_end.add(mem::size_of::<FreeBlockHdr>());
+                    }
                 } else {
                     // We can't merge a used block (`next_phys_block`) and
                     // a free block (`new_free_block`).
                     next_phys_block.as_mut().prev_phys_block = Some(new_free_block.cast());
+                    next_phys_block.as_mut().seal();
                 }
 
+                #[cfg(any(feature = "poison", feature = "asan"))]
+                poison(
+                    (new_free_block.as_ptr() as *mut u8).add(mem::size_of::<FreeBlockHdr>()),
+                    poison_end,
+                );
+
                 new_free_block.as_mut().common = BlockHdr {
                     size: new_free_block_size,
                     prev_phys_block: Some(block.cast()),
                 };
+                new_free_block.as_mut().common.seal();
                 self.link_free_block(new_free_block, new_free_block_size);
 
                 block.as_mut().common.size = new_size | SIZE_USED;
+                Self::record_align(ptr, block, new_layout.align());
+                block.as_mut().common.seal();
             }
 
             return Some(ptr);
@@ -1299,6 +3728,7 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         // Grow into the next free block. Fail if there isn't such a block.
         #[allow(clippy::never_loop)]
         'nonmoving: loop {
+            next_phys_block.as_ref().verify();
             let next_phys_block_size_and_flags = next_phys_block.as_ref().size;
 
             // Fail it isn't a free block.
@@ -1306,7 +3736,7 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
                 break 'nonmoving;
             }
 
-            let mut next_phys_block_size = next_phys_block_size_and_flags;
+            let mut next_phys_block_size = next_phys_block_size_and_flags & !SIZE_CHECKSUM_MASK;
             debug_assert_eq!(
                 next_phys_block_size,
                 next_phys_block_size_and_flags & SIZE_SIZE_MASK
@@ -1324,6 +3754,15 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
                 break 'nonmoving;
             }
 
+            // Verify the poisoned part that is being handed out or will store
+            // the header of the remaining free block
+            #[cfg(any(feature = "poison", feature = "asan"))]
+            unpoison(
+                (next_phys_block.as_ptr() as *const u8).add(mem::size_of::<FreeBlockHdr>()),
+                (next_phys_block.as_ptr() as *const u8)
+                    .add(next_phys_block_size.min(grow_by + GRANULARITY)),
+            );
+
             self.unlink_free_block(next_phys_block, next_phys_block_size);
 
             if grow_by < next_phys_block_size {
@@ -1337,6 +3776,7 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
                     size: next_phys_block_size,
                     prev_phys_block: Some(block.cast()),
                 };
+                next_phys_block.as_mut().common.seal();
                 self.link_free_block(next_phys_block, next_phys_block_size);
 
                 // Update `next_next_phys_block.prev_phys_block` accordingly
@@ -1348,8 +3788,11 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
                 // Update `next_next_phys_block.prev_phys_block` accordingly
                 next_next_phys_block.as_mut().prev_phys_block = Some(block.cast());
             }
+            next_next_phys_block.as_mut().seal();
 
             block.as_mut().common.size = new_size | SIZE_USED;
+            Self::record_align(ptr, block, new_layout.align());
+            block.as_mut().common.seal();
 
             return Some(ptr);
         }
@@ -1362,6 +3805,7 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         // Get the previous block. If there isn't such a block, the moving
         // approach will not improve the situation anyway, so return `None`.
         let prev_phys_block = block.as_ref().common.prev_phys_block?;
+        prev_phys_block.as_ref().verify();
         let prev_phys_block_size_and_flags = prev_phys_block.as_ref().size;
 
         // Fail it isn't a free block.
@@ -1369,7 +3813,7 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
             return None;
         }
 
-        let prev_phys_block_size = prev_phys_block_size_and_flags;
+        let prev_phys_block_size = prev_phys_block_size_and_flags & !SIZE_CHECKSUM_MASK;
         debug_assert_eq!(
             prev_phys_block_size,
             prev_phys_block_size_and_flags & SIZE_SIZE_MASK
@@ -1381,9 +3825,10 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         // Decide the starting address of the payload
         let unaligned_ptr =
             prev_phys_block.as_ptr() as *mut u8 as usize + mem::size_of::<UsedBlockHdr>();
-        let new_ptr = NonNull::new_unchecked(
-            ((unaligned_ptr + new_layout.align() - 1) & !(new_layout.align() - 1)) as *mut u8,
-        );
+        let new_ptr = NonNull::new_unchecked(ptr_with_addr(
+            prev_phys_block.as_ptr() as *mut u8,
+            (unaligned_ptr + new_layout.align() - 1) & !(new_layout.align() - 1),
+        ));
 
         // Calculate the new block size
         let new_overhead = new_ptr.as_ptr() as usize - prev_phys_block.as_ptr() as usize;
@@ -1394,16 +3839,28 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
             return None;
         }
 
-        // Unlink the existing free blocks included in `moving_clearance`
+        // Unlink the existing free blocks included in `moving_clearance`.
+        // Their payloads will be either handed out or poisoned again, so
+        // verify them now.
         self.unlink_free_block(prev_phys_block.cast(), prev_phys_block_size);
+        #[cfg(any(feature = "poison", feature = "asan"))]
+        unpoison(
+            (prev_phys_block.as_ptr() as *const u8).add(mem::size_of::<FreeBlockHdr>()),
+            (prev_phys_block.as_ptr() as *const u8).add(prev_phys_block_size),
+        );
         let next_phys_block_size_and_flags = next_phys_block.as_ref().size;
         if (next_phys_block_size_and_flags & SIZE_USED) == 0 {
-            let next_phys_block_size = next_phys_block_size_and_flags;
+            let next_phys_block_size = next_phys_block_size_and_flags & !SIZE_CHECKSUM_MASK;
             debug_assert_eq!(
                 next_phys_block_size,
                 next_phys_block_size_and_flags & SIZE_SIZE_MASK
             );
             self.unlink_free_block(next_phys_block.cast(), next_phys_block_size);
+            #[cfg(any(feature = "poison", feature = "asan"))]
+            unpoison(
+                (next_phys_block.as_ptr() as *const u8).add(mem::size_of::<FreeBlockHdr>()),
+                (next_phys_block.as_ptr() as *const u8).add(next_phys_block_size),
+            );
         }
 
         // Move the existing data into the new memory block.
@@ -1413,6 +3870,18 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
             new_layout.size().min(old_size - overhead),
         );
 
+        // Zero the part of the old payload not overwritten by the new memory
+        // block
+        #[cfg(feature = "zero-on-free")]
+        {
+            let new_block_end = prev_phys_block.as_ptr() as *mut u8 as usize + new_size;
+            let start = ptr_map_addr(ptr.as_ptr(), |addr| addr.max(new_block_end));
+            let end = (block.as_ptr() as *mut u8).add(old_size);
+            if start < end {
+                self.zero_on_free(start, end);
+            }
+        }
+
         // We'll replace `prev_phys_block` with a new used block.
         let mut new_block = prev_phys_block.cast::<UsedBlockHdr>();
 
@@ -1420,6 +3889,7 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
             // The allocation completely fills this free block.
             // Update `prev_phys_block` accordingly
             moving_clearance_end.as_mut().prev_phys_block = Some(new_block.cast());
+            moving_clearance_end.as_mut().seal();
         } else {
             // The allocation partially fills this free block. Create a new
             // free block header at `new_block + new_size..new_block
@@ -1429,9 +3899,13 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
             let mut new_free_block_size = moving_clearance - new_size;
 
             // If the following block (`moving_clearance_end`) is a free block...
+            moving_clearance_end.as_ref().verify();
             let moving_clearance_end_size_and_flags = moving_clearance_end.as_ref().size;
+            #[cfg(any(feature = "poison", feature = "asan"))]
+            let mut poison_end = moving_clearance_end.as_ptr() as *mut u8;
             if (moving_clearance_end_size_and_flags & SIZE_USED) == 0 {
-                let moving_clearance_end_size = moving_clearance_end_size_and_flags;
+                let moving_clearance_end_size =
+                    moving_clearance_end_size_and_flags & !SIZE_CHECKSUM_MASK;
                 debug_assert_eq!(
                     moving_clearance_end_size,
                     moving_clearance_end_size_and_flags & SIZE_SIZE_MASK
@@ -1440,26 +3914,42 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
                 // Then we should merge this existing free block (`moving_clearance_end`)
                 // into the new one (`new_free_block`).
                 self.unlink_free_block(moving_clearance_end.cast(), moving_clearance_end_size);
-                new_free_block_size += moving_clearance_end_size_and_flags;
+                new_free_block_size += moving_clearance_end_size;
 
                 let mut next_next_phys_block = moving_clearance_end.as_ref().next_phys_block();
                 next_next_phys_block.as_mut().prev_phys_block = Some(new_free_block.cast());
+                next_next_phys_block.as_mut().seal();
+
+                #[cfg(any(feature = "poison", feature = "asan"))]
+                {
+                    poison_end = poison_end.add(mem::size_of::<FreeBlockHdr>());
+                }
             } else {
                 // We can't merge a used block (`moving_clearance_end`) and
                 // a free block (`new_free_block`).
                 moving_clearance_end.as_mut().prev_phys_block = Some(new_free_block.cast());
+                moving_clearance_end.as_mut().seal();
             }
 
+            #[cfg(any(feature = "poison", feature = "asan"))]
+            poison(
+                (new_free_block.as_ptr() as *mut u8).add(mem::size_of::<FreeBlockHdr>()),
+                poison_end,
+            );
+
             new_free_block.as_mut().common = BlockHdr {
                 size: new_free_block_size,
                 prev_phys_block: Some(new_block.cast()),
             };
+            new_free_block.as_mut().common.seal();
             self.link_free_block(new_free_block, new_free_block_size);
         }
 
         // Turn `new_block` into a used memory block and initialize the used block
         // header. `prev_phys_block` is already set.
         new_block.as_mut().common.size = new_size | SIZE_USED;
+        Self::record_align(new_ptr, new_block, new_layout.align());
+        new_block.as_mut().common.seal();
 
         // Place a header pointer (used by `used_block_hdr_for_allocation`)
         if new_layout.align() >= GRANULARITY {
@@ -1469,6 +3959,446 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         Some(new_ptr)
     }
 
+    /// Call `f` for the part of each free memory block that doesn't store
+    /// the block's header. The allocator doesn't care about the contents of
+    /// these regions, so `f` may discard them (e.g., by `madvise`).
+    ///
+    /// With the `poison` feature, the allocator does care about them, so
+    /// this method does nothing.
+    pub(crate) fn for_each_free_payload(&mut self, mut f: impl FnMut(NonNull<[u8]>)) {
+        if cfg!(feature = "poison") {
+            return;
+        }
+        for first_free in self.first_free.iter().flatten() {
+            let mut next_free = *first_free;
+            while let Some(block) = next_free {
+                // Safety: `block` is a free block in one of the free lists
+                unsafe {
+                    f(Self::free_payload(block));
+                    next_free = block.as_ref().next_free(self.link_key);
+                }
+            }
+        }
+    }
+
+    /// Get the number of free memory blocks and their total size in bytes
+    /// (including their headers). This takes time proportional to the
+    /// number of free memory blocks.
+    pub(crate) fn free_blocks_summary(&self) -> (usize, usize) {
+        let mut num_blocks = 0;
+        let mut num_bytes = 0;
+        for first_free in self.first_free.iter().flatten() {
+            let mut next_free = *first_free;
+            while let Some(block) = next_free {
+                // Safety: `block` is a free block in one of the free lists
+                unsafe {
+                    num_blocks += 1;
+                    num_bytes += block.as_ref().common.size & SIZE_SIZE_MASK;
+                    next_free = block.as_ref().next_free(self.link_key);
+                }
+            }
+        }
+        (num_blocks, num_bytes)
+    }
+
+    /// Get the part of the free memory block `block` that doesn't store the
+    /// block's header.
+    ///
+    /// # Safety
+    ///
+    /// `block` must be a valid free memory block.
+    #[inline]
+    unsafe fn free_payload(block: NonNull<FreeBlockHdr>) -> NonNull<[u8]> {
+        let size = block.as_ref().common.size & SIZE_SIZE_MASK;
+        let hdr_size = mem::size_of::<FreeBlockHdr>();
+        debug_assert!(size >= hdr_size);
+        let payload = NonNull::new_unchecked((block.as_ptr() as *mut u8).add(hdr_size));
+        nonnull_slice_from_raw_parts(payload, size - hdr_size)
+    }
+
+    /// Get the starting address and length of the memory pool `pool`, whose
+    /// starting address is rounded up in the same way as
+    /// [`Self::insert_free_block_ptr`] does.
+    #[inline]
+    fn pool_range(pool: NonNull<[u8]>) -> (usize, usize) {
+        let len = nonnull_slice_len(pool);
+        let unaligned_start = pool.as_ptr() as *mut u8 as usize;
+        let start = unaligned_start.wrapping_add(GRANULARITY - 1) & !(GRANULARITY - 1);
+        (
+            start,
+            len.saturating_sub(start.wrapping_sub(unaligned_start)),
+        )
+    }
+
+    /// Enumerate the memory blocks in the memory pool `pool` as pairs of the
+    /// starting address and the size and flags, excluding the sentinel
+    /// blocks. Stops at a memory block with an invalid size.
+    ///
+    /// # Safety
+    ///
+    /// `pool` must satisfy the requirements of [`Self::check_integrity`].
+    unsafe fn pool_blocks(pool: NonNull<[u8]>) -> impl Iterator<Item = (usize, usize)> {
+        let pool_ptr = pool.as_ptr() as *mut u8;
+        let (mut start, mut len) = Self::pool_range(pool);
+
+        core::iter::from_fn(move || {
+            // A memory pool is at least `GRANULARITY * 2` bytes long, so
+            // anything shorter must be trailing bytes. The sentinel block at
+            // the end, which would be skipped by this, is excluded from the
+            // output anyway.
+            while len >= GRANULARITY * 2 {
+                let block = start;
+                let size_and_flags = (*ptr_with_addr(pool_ptr, block).cast::<BlockHdr>()).size;
+                let size = size_and_flags & SIZE_SIZE_MASK;
+                if size == 0 || size > len {
+                    break;
+                }
+
+                // Advance the cursor
+                start += size;
+                len -= size;
+
+                if (size_and_flags & SIZE_SENTINEL) == 0 {
+                    return Some((block, size_and_flags));
+                }
+            }
+            len = 0;
+            None
+        })
+    }
+
+    /// Validate the memory blocks in the specified memory pools and the free
+    /// lists. Returns the first inconsistency found.
+    ///
+    /// The following properties are checked:
+    ///
+    ///  - Each memory block header has a valid size and flags and links back
+    ///    to the preceding memory block. Each memory pool ends with a
+    ///    sentinel block.
+    ///  - No two free memory blocks are adjacent to each other (i.e., they
+    ///    have been coalesced).
+    ///  - Each free memory block is in the free list for its size, and the
+    ///    free lists contain nothing else.
+    ///  - The bitmaps match the free lists.
+    ///
+    /// This is meant for tracking down heap corruption. Calling this method
+    /// periodically or around suspicious operations narrows down when an
+    /// out-of-bounds write or a use-after-free happened.
+    ///
+    /// Only the memory inside `pools` is read, so a corrupted pointer in a
+    /// block header doesn't make this method access memory elsewhere.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in `O(num_blocks * max_free_list_len)` time.
+    ///
+    /// # Safety
+    ///
+    /// `pools` must precisely represent all memory pools that belong to
+    /// `self`. Specifically, each element's starting address must be the one
+    /// that was previously passed to [`Self::insert_free_block_ptr`], and its
+    /// length must be the sum of the return values of that call to
+    /// `insert_free_block_ptr` and all subsequent calls to
+    /// [`Self::append_free_block_ptr`] that have been made to expand this
+    /// memory pool. (Each element may also include up to
+    /// `GRANULARITY * 2 - 1` trailing bytes that are not part of the memory
+    /// pool.)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::Tlsf;
+    /// use std::{mem::MaybeUninit, alloc::Layout, ptr::{NonNull, slice_from_raw_parts_mut}};
+    ///
+    /// static mut POOL: MaybeUninit<[u8; 1024]> = MaybeUninit::uninit();
+    /// let pool_ptr = NonNull::new(unsafe { POOL.as_mut_ptr() }).unwrap();
+    ///
+    /// let mut tlsf: Tlsf<'_, u16, u16, 12, 16> = Tlsf::new();
+    ///
+    /// // Insert a memory pool. We need to remember the actual pool size
+    /// // to call `Tlsf::check_integrity` later.
+    /// let pool_len = unsafe { tlsf.insert_free_block_ptr(pool_ptr) }.unwrap().get();
+    /// let pool_ptr = NonNull::new(
+    ///     slice_from_raw_parts_mut(pool_ptr.as_ptr() as *mut u8, pool_len)
+    /// ).unwrap();
+    ///
+    /// let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+    /// assert_eq!(unsafe { tlsf.check_integrity(&[pool_ptr]) }, Ok(()));
+    ///
+    /// unsafe { tlsf.deallocate(ptr, 8) };
+    /// assert_eq!(unsafe { tlsf.check_integrity(&[pool_ptr]) }, Ok(()));
+    /// ```
+    pub unsafe fn check_integrity(&self, pools: &[NonNull<[u8]>]) -> Result<(), IntegrityError> {
+        self.check_integrity_iter(pools.iter().copied())
+    }
+
+    /// [`Self::check_integrity`] taking the memory pools as an iterator, for
+    /// callers that enumerate them without storing them in one place.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::check_integrity`].
+    pub(crate) unsafe fn check_integrity_iter(
+        &self,
+        pools: impl Iterator<Item = NonNull<[u8]>> + Clone,
+    ) -> Result<(), IntegrityError> {
+        let is_in_pools = |block: usize| {
+            block % GRANULARITY == 0
+                && pools.clone().any(|pool| {
+                    let (start, len) = Self::pool_range(pool);
+                    let offset = block.wrapping_sub(start);
+                    len >= mem::size_of::<FreeBlockHdr>()
+                        && offset <= len - mem::size_of::<FreeBlockHdr>()
+                })
+        };
+
+        // A free list can't contain more blocks than this unless it's cyclic
+        let max_num_blocks: usize = pools
+            .clone()
+            .map(|pool| Self::pool_range(pool).1 / GRANULARITY)
+            .sum();
+
+        // Validate the free lists and the bitmaps first so that the free lists
+        // can be safely searched later
+        let mut num_listed_blocks = 0;
+        let mut fl_bitmap = FLBitmap::ZERO;
+        for (fl, first_free) in self.first_free.iter().enumerate() {
+            let mut sl_bitmap = SLBitmap::ZERO;
+            for (sl, &first_free) in first_free.iter().enumerate() {
+                let mut prev_free = None;
+                let mut next_free = first_free;
+                while let Some(block) = next_free {
+                    if !is_in_pools(block.as_ptr() as usize) {
+                        return Err(IntegrityError::ForeignFreeBlock {
+                            fl,
+                            sl,
+                            block: block.cast(),
+                        });
+                    }
+
+                    num_listed_blocks += 1;
+                    if num_listed_blocks > max_num_blocks {
+                        return Err(IntegrityError::FreeListCycle { fl, sl });
+                    }
+
+                    let size_and_flags = block.as_ref().common.size;
+                    if (size_and_flags & SIZE_USED) != 0 {
+                        return Err(IntegrityError::UsedBlockInFreeList {
+                            fl,
+                            sl,
+                            block: block.cast(),
+                        });
+                    }
+
+                    let size = size_and_flags & SIZE_SIZE_MASK;
+                    if size == 0 || Self::map_floor(size) != Some((fl, sl)) {
+                        return Err(IntegrityError::MisfiledFreeBlock {
+                            fl,
+                            sl,
+                            block: block.cast(),
+                        });
+                    }
+
+                    // Corrupted links are reported instead of panicking
+                    if block.as_ref().prev_free_unchecked(self.link_key) != prev_free {
+                        return Err(IntegrityError::BadPrevFree {
+                            fl,
+                            sl,
+                            block: block.cast(),
+                        });
+                    }
+
+                    prev_free = Some(block);
+                    next_free = block.as_ref().next_free_unchecked(self.link_key);
+                }
+
+                if first_free.is_some() {
+                    sl_bitmap.set_bit(sl as u32);
+                }
+            }
+
+            if sl_bitmap != self.sl_bitmap[fl] {
+                return Err(IntegrityError::BitmapMismatch { fl });
+            }
+            if sl_bitmap != SLBitmap::ZERO {
+                fl_bitmap.set_bit(fl as u32);
+            }
+        }
+
+        if fl_bitmap != self.fl_bitmap {
+            let fl = (0..FLBitmap::BITS)
+                .find(|&i| fl_bitmap.get_bit(i) != self.fl_bitmap.get_bit(i))
+                .unwrap_or(0);
+            return Err(IntegrityError::BitmapMismatch { fl: fl as usize });
+        }
+
+        // Walk the memory blocks in each memory pool
+        let mut num_found_blocks = 0;
+        for (pool_index, pool) in pools.enumerate() {
+            let pool_ptr = pool.as_ptr() as *mut u8;
+            let (mut start, mut len) = Self::pool_range(pool);
+
+            // The preceding memory block, or `None` at the start of a memory
+            // pool (or of a chunk created by `insert_free_block_ptr_aligned`)
+            let mut prev_phys_block = None;
+            let mut prev_phys_block_is_free = false;
+
+            loop {
+                if prev_phys_block.is_none() {
+                    // A memory pool is at least `GRANULARITY * 2` bytes long,
+                    // so anything shorter must be trailing bytes
+                    if len < GRANULARITY * 2 {
+                        break;
+                    }
+                } else if len < GRANULARITY {
+                    return Err(IntegrityError::UnterminatedPool { pool: pool_index });
+                }
+
+                let block =
+                    NonNull::new_unchecked(ptr_with_addr(pool_ptr, start).cast::<BlockHdr>());
+                let size_and_flags = block.as_ref().size;
+                let size = size_and_flags & SIZE_SIZE_MASK;
+                let is_used = (size_and_flags & SIZE_USED) != 0;
+                let is_sentinel = (size_and_flags & SIZE_SENTINEL) != 0;
+
+                if size == 0 || size > len || (is_sentinel && (!is_used || size != GRANULARITY)) {
+                    return Err(IntegrityError::BadBlockHeader {
+                        block: block.cast(),
+                    });
+                }
+
+                #[cfg(feature = "pool-guards")]
+                if is_sentinel && *pool_guard_words(block) != [POOL_GUARD_WORD; 2] {
+                    return Err(IntegrityError::BadBlockHeader {
+                        block: block.cast(),
+                    });
+                }
+
+                if block.as_ref().prev_phys_block != prev_phys_block {
+                    return Err(IntegrityError::BadPrevPhysBlock {
+                        block: block.cast(),
+                    });
+                }
+
+                if !is_used {
+                    if prev_phys_block_is_free {
+                        return Err(IntegrityError::AdjacentFreeBlocks {
+                            block: block.cast(),
+                        });
+                    }
+
+                    let (fl, sl) = Self::map_floor(size).ok_or(IntegrityError::BadBlockHeader {
+                        block: block.cast(),
+                    })?;
+
+                    // Safety: The free lists were validated above
+                    let mut listed = self.first_free[fl][sl];
+                    while let Some(listed_block) = listed {
+                        if listed_block.cast() == block {
+                            break;
+                        }
+                        listed = listed_block.as_ref().next_free(self.link_key);
+                    }
+                    if listed.is_none() {
+                        return Err(IntegrityError::UnlistedFreeBlock {
+                            block: block.cast(),
+                        });
+                    }
+
+                    num_found_blocks += 1;
+                }
+
+                // Checked last so that the above checks can describe the
+                // corruption more specifically (`header-checksum` feature)
+                if !block.as_ref().checksum_matches() {
+                    return Err(IntegrityError::BadBlockHeader {
+                        block: block.cast(),
+                    });
+                }
+
+                // Advance the cursor
+                len -= size;
+                start = start.wrapping_add(size);
+                prev_phys_block = if is_sentinel { None } else { Some(block) };
+                prev_phys_block_is_free = !is_used;
+            }
+        }
+
+        if num_listed_blocks != num_found_blocks {
+            return Err(IntegrityError::FreeBlockCountMismatch {
+                num_listed_blocks,
+                num_found_blocks,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Check that `ptr` is the starting address of a memory block allocated
+    /// with alignment `align` in one of `pools`, without reading anything
+    /// outside `pools`.
+    ///
+    /// The memory pool containing `ptr` is walked from its start, so only the
+    /// block headers are read and `ptr` is never dereferenced.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(num_blocks)`).
+    ///
+    /// # Safety
+    ///
+    /// `pools` must satisfy the requirements of [`Self::check_integrity`].
+    pub(crate) unsafe fn check_pointer(
+        &self,
+        pools: &[NonNull<[u8]>],
+        ptr: NonNull<u8>,
+        align: usize,
+    ) -> Result<(), InvalidPointer> {
+        let addr = ptr.as_ptr() as usize;
+        let (pool_ptr, (mut start, mut len)) = pools
+            .iter()
+            .map(|&pool| (pool.as_ptr() as *mut u8, Self::pool_range(pool)))
+            .find(|&(_, (start, len))| addr.wrapping_sub(start) < len)
+            .ok_or(InvalidPointer::OutsidePools)?;
+
+        // Find the memory block containing `ptr`. See `iter_blocks` for why
+        // `GRANULARITY * 2` is the cut-off.
+        while len >= GRANULARITY * 2 {
+            let block = NonNull::new_unchecked(ptr_with_addr(pool_ptr, start));
+            let block_hdr = block.cast::<BlockHdr>().as_ref();
+            let size = block_hdr.size & SIZE_SIZE_MASK;
+            if size == 0 || size > len {
+                return Err(InvalidPointer::BadBlockHeader { block });
+            }
+
+            if addr - start < size {
+                if (block_hdr.size & SIZE_SENTINEL) != 0 {
+                    break;
+                }
+                if (block_hdr.size & SIZE_USED) == 0 {
+                    return Err(InvalidPointer::FreeBlock { block });
+                }
+
+                // `allocate` places the allocation at the first suitably
+                // aligned address after the header
+                let unaligned_ptr = start + mem::size_of::<UsedBlockHdr>();
+                let expected_ptr = unaligned_ptr.wrapping_add(align - 1) & !(align - 1);
+                if addr != expected_ptr {
+                    return Err(InvalidPointer::InsideBlock { block });
+                }
+
+                return Ok(());
+            }
+
+            // Advance the cursor
+            len -= size;
+            start = start.wrapping_add(size);
+        }
+
+        // `ptr` is in a sentinel block or the trailing bytes
+        Err(InvalidPointer::OutsidePools)
+    }
+
     /// Enumerate memory blocks in the specified memory pool.
     ///
     /// # Safety
@@ -1478,7 +4408,9 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
     /// passed to [`Self::insert_free_block_ptr`], and its length must be the
     /// sum of the return values of that call to `insert_free_block_ptr` and
     /// all subsequent calls to [`Self::append_free_block_ptr`] that have been
-    /// made to expand this memory pool.
+    /// made to expand this memory pool. (`pool` may also include up to
+    /// `GRANULARITY * 2 - 1` trailing bytes that are not part of the memory
+    /// pool.)
     ///
     /// # Examples
     ///
@@ -1518,33 +4450,35 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
         &self,
         pool: NonNull<[u8]>,
     ) -> impl Iterator<Item = BlockInfo<'_>> + Send + '_ {
-        let len = nonnull_slice_len(pool);
-
-        // Round up the starting address in the same way as
-        // `insert_free_block_ptr` does.
-        //
         // In `insert_free_block_ptr` there's a minimum pool size cut-off, and
         // when that happens, `insert_free_block_ptr` returns `None`. In such a
         // case, as per this method's safety requirements, "the sum of the
         // return values of ..." is undefined, so the user is not supposed to
         // even call this method. This means this method don't have to repeat
         // this cut-off step from `insert_free_block_ptr`.
-        let unaligned_start = pool.as_ptr() as *mut u8 as usize;
-        let mut start = unaligned_start.wrapping_add(GRANULARITY - 1) & !(GRANULARITY - 1);
-        let mut len = len.saturating_sub(start.wrapping_sub(unaligned_start));
+        let pool_ptr = PoolPtr(pool.as_ptr() as *mut u8);
+        let (mut start, mut len) = Self::pool_range(pool);
 
         core::iter::from_fn(move || {
-            if len == 0 {
+            // A memory pool is at least `GRANULARITY * 2` bytes long, so
+            // anything shorter must be trailing bytes. The sentinel block
+            // at the end, which would be skipped by this, is excluded from
+            // the output anyway.
+            if len < GRANULARITY * 2 {
                 None
             } else {
-                let block_hdr = &*(start as *const BlockHdr);
+                let block_hdr = &*ptr_with_addr(pool_ptr.get(), start).cast::<BlockHdr>();
                 let block_size = block_hdr.size & SIZE_SIZE_MASK;
 
                 // Advance the cursor
                 len -= block_size;
                 start = start.wrapping_add(block_size);
 
-                Some(BlockInfo { block_hdr })
+                Some(BlockInfo {
+                    block_hdr,
+                    #[cfg(feature = "track-allocations")]
+                    allocation_sites: self.allocation_sites.as_ref(),
+                })
             }
         })
         .filter(|block_info| {
@@ -1554,6 +4488,207 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
     }
 }
 
+/// A pointer to a memory pool captured by the iterator returned by
+/// [`Tlsf::iter_blocks`], which keeps the pointer's provenance while
+/// letting the iterator be sent to another thread.
+#[cfg(feature = "unstable")]
+#[derive(Clone, Copy)]
+struct PoolPtr(*mut u8);
+
+// Safety: The pointer is only used to read the block headers in the memory
+//         pool, which may be accessed from any thread that can access the
+//         `Tlsf` owning it
+#[cfg(feature = "unstable")]
+unsafe impl Send for PoolPtr {}
+
+#[cfg(feature = "unstable")]
+impl PoolPtr {
+    /// Get the pointer. Closures must call this instead of accessing the
+    /// field so that they capture the whole `PoolPtr`, which is `Send`.
+    #[inline]
+    fn get(self) -> *mut u8 {
+        self.0
+    }
+}
+
+/// The reason [`CheckedTlsf`](crate::CheckedTlsf) rejected a pointer
+/// ([`HeapError::InvalidPointer`]). `block` is the starting address of the
+/// memory block containing the pointer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum InvalidPointer {
+    /// The pointer isn't inside any of the memory pools.
+    OutsidePools,
+    /// The memory block's header has an invalid size.
+    BadBlockHeader { block: NonNull<u8> },
+    /// The memory block isn't allocated.
+    FreeBlock { block: NonNull<u8> },
+    /// The pointer isn't the starting address of the allocation.
+    InsideBlock { block: NonNull<u8> },
+}
+
+/// The error type returned by [`Tlsf::check_integrity`], describing the first
+/// inconsistency found. `block` is the starting address of the offending
+/// memory block's header.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum IntegrityError {
+    /// The memory block's header has an invalid size or flags.
+    BadBlockHeader { block: NonNull<u8> },
+    /// The memory block's header doesn't point to the preceding memory block.
+    BadPrevPhysBlock { block: NonNull<u8> },
+    /// The memory pool at index `pool` doesn't end with a sentinel block.
+    UnterminatedPool { pool: usize },
+    /// The free memory block is preceded by another free memory block.
+    AdjacentFreeBlocks { block: NonNull<u8> },
+    /// The free memory block is missing from the free list for its size.
+    UnlistedFreeBlock { block: NonNull<u8> },
+    /// The free list `(fl, sl)` contains a pointer to outside the memory
+    /// pools.
+    ForeignFreeBlock {
+        fl: usize,
+        sl: usize,
+        block: NonNull<u8>,
+    },
+    /// The free list `(fl, sl)` contains a used memory block.
+    UsedBlockInFreeList {
+        fl: usize,
+        sl: usize,
+        block: NonNull<u8>,
+    },
+    /// The free list `(fl, sl)` contains a free memory block belonging to
+    /// another free list.
+    MisfiledFreeBlock {
+        fl: usize,
+        sl: usize,
+        block: NonNull<u8>,
+    },
+    /// The free memory block in the free list `(fl, sl)` doesn't point to
+    /// the preceding element of the list.
+    BadPrevFree {
+        fl: usize,
+        sl: usize,
+        block: NonNull<u8>,
+    },
+    /// The free list `(fl, sl)` is cyclic.
+    FreeListCycle { fl: usize, sl: usize },
+    /// The first- or second-level bitmap for the first level `fl` doesn't
+    /// match the free lists.
+    BitmapMismatch { fl: usize },
+    /// The free lists contain memory blocks that aren't found in the memory
+    /// pools.
+    FreeBlockCountMismatch {
+        num_listed_blocks: usize,
+        num_found_blocks: usize,
+    },
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::BadBlockHeader { block } => {
+                write!(
+                    f,
+                    "corrupted block header (bad size or flags) at {:p}",
+                    block
+                )
+            }
+            Self::BadPrevPhysBlock { block } => write!(
+                f,
+                "corrupted block header (bad link to the preceding block) at {:p}",
+                block
+            ),
+            Self::UnterminatedPool { pool } => {
+                write!(f, "memory pool #{} doesn't end with a sentinel block", pool)
+            }
+            Self::AdjacentFreeBlocks { block } => {
+                write!(f, "free block at {:p} follows another free block", block)
+            }
+            Self::UnlistedFreeBlock { block } => {
+                write!(f, "free block at {:p} is missing from its free list", block)
+            }
+            Self::ForeignFreeBlock { fl, sl, block } => write!(
+                f,
+                "free list ({}, {}) points to {:p}, which is outside the memory pools",
+                fl, sl, block
+            ),
+            Self::UsedBlockInFreeList { fl, sl, block } => write!(
+                f,
+                "free list ({}, {}) contains a used block at {:p}",
+                fl, sl, block
+            ),
+            Self::MisfiledFreeBlock { fl, sl, block } => write!(
+                f,
+                "free list ({}, {}) contains a block of a wrong size at {:p}",
+                fl, sl, block
+            ),
+            Self::BadPrevFree { fl, sl, block } => write!(
+                f,
+                "free list ({}, {}) has a bad backward link at {:p}",
+                fl, sl, block
+            ),
+            Self::FreeListCycle { fl, sl } => write!(f, "free list ({}, {}) is cyclic", fl, sl),
+            Self::BitmapMismatch { fl } => {
+                write!(
+                    f,
+                    "bitmap for first level {} doesn't match the free lists",
+                    fl
+                )
+            }
+            Self::FreeBlockCountMismatch {
+                num_listed_blocks,
+                num_found_blocks,
+            } => write!(
+                f,
+                "free lists contain {} blocks, but the memory pools contain {} free blocks",
+                num_listed_blocks, num_found_blocks
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for IntegrityError {}
+
+/// The error type returned by [`Tlsf::reallocate_checked`], describing how
+/// the claimed layout of a memory block disagrees with its header.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum LayoutMismatch {
+    /// The new layout's alignment differs from the old layout's, which
+    /// reallocation can't change.
+    AlignChange,
+    /// The memory block wasn't allocated with the alignment `claimed`.
+    Align { claimed: usize },
+    /// The size `claimed` exceeds the usable size `usable` recorded in the
+    /// memory block's header.
+    Size { claimed: usize, usable: usize },
+}
+
+impl fmt::Display for LayoutMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::AlignChange => write!(f, "reallocation can't change the alignment"),
+            Self::Align { claimed } => write!(
+                f,
+                "the memory block wasn't allocated with alignment {}",
+                claimed
+            ),
+            Self::Size { claimed, usable } => write!(
+                f,
+                "the claimed size {} exceeds the usable size {} of the memory block",
+                claimed, usable
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LayoutMismatch {}
+
 /// Allows the caller of [`Tlsf::iter_blocks`] to examine the properties of a
 /// memory block in a [`Tlsf`] memory pool.
 #[derive(Clone, Copy)]
@@ -1561,6 +4696,8 @@ impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, cons
 #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "unstable")))]
 pub struct BlockInfo<'a> {
     block_hdr: &'a BlockHdr,
+    #[cfg(feature = "track-allocations")]
+    allocation_sites: Option<&'a HashMap<usize, &'static Location<'static>>>,
 }
 
 #[cfg(feature = "unstable")]
@@ -1574,6 +4711,21 @@ impl fmt::Debug for BlockInfo<'_> {
     }
 }
 
+#[cfg(all(feature = "unstable", feature = "defmt"))]
+impl defmt::Format for BlockInfo<'_> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        let ptr = self.as_ptr_range();
+        defmt::write!(
+            f,
+            "BlockInfo {{ ptr: {}..{}, size: {}, is_occupied: {} }}",
+            ptr.start,
+            ptr.end,
+            self.size(),
+            self.is_occupied()
+        );
+    }
+}
+
 #[cfg(feature = "unstable")]
 impl BlockInfo<'_> {
     /// Get this block's size, including the header.
@@ -1610,7 +4762,31 @@ impl BlockInfo<'_> {
     pub fn is_occupied(&self) -> bool {
         (self.block_hdr.size & SIZE_USED) != 0
     }
+
+    /// Get the call site that created the allocation occupying this memory
+    /// block. Returns `None` if the memory block is free or the allocation
+    /// wasn't tracked (see [`Tlsf::enable_allocation_tracking`]).
+    #[cfg(feature = "track-allocations")]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "track-allocations")))]
+    pub fn allocation_site(&self) -> Option<&'static Location<'static>> {
+        self.allocation_sites?
+            .get(&(self.block_hdr as *const BlockHdr as usize))
+            .copied()
+    }
 }
 
+#[cfg(feature = "std")]
+mod snapshot;
+
+#[cfg(feature = "test-utils")]
+mod test_utils;
+#[cfg(feature = "test-utils")]
+pub use self::test_utils::BlockState;
+
+#[cfg(feature = "oom-report")]
+mod oom_report;
+#[cfg(feature = "oom-report")]
+pub use self::oom_report::{OomHook, OomReport};
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,196 @@
+//! Describing allocation failures (the `oom-report` feature)
+use super::*;
+
+/// A function called with a description of a failed allocation. See
+/// [`Tlsf::set_oom_hook`].
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "oom-report")))]
+pub type OomHook = fn(OomReport);
+
+/// A description of why an allocation request couldn't be satisfied,
+/// returned by [`Tlsf::oom_report`].
+///
+/// The [`Display`](fmt::Display) implementation produces a one-line summary
+/// suitable for logging.
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "oom-report")))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct OomReport {
+    /// The requested size.
+    pub size: usize,
+    /// The requested alignment.
+    pub align: usize,
+    /// The free block list `(fl, sl)` searched first, i.e., the size class
+    /// of the smallest free memory block guaranteed to fit the request
+    /// (including the header and the alignment padding). The allocation
+    /// failed because this and all larger free block lists are empty.
+    ///
+    /// `None` if the request is larger than the largest size class.
+    pub size_class: Option<(usize, usize)>,
+    /// The size of the largest free memory block, including its header.
+    pub largest_free_block: usize,
+    /// The total size of the free memory blocks, including their headers.
+    pub free_bytes: usize,
+    /// The number of free memory blocks.
+    pub free_blocks: usize,
+}
+
+impl OomReport {
+    /// Get the percentage of [`Self::free_bytes`] outside the largest free
+    /// memory block, rounded down. A high value means that the request
+    /// failed because the free memory is split into small pieces rather than
+    /// because there is too little of it. Returns `0` if there are no free
+    /// memory blocks.
+    #[inline]
+    pub fn fragmentation_percent(&self) -> usize {
+        if self.free_bytes == 0 {
+            0
+        } else {
+            let scattered_bytes = (self.free_bytes - self.largest_free_block) as u128;
+            (scattered_bytes * 100 / self.free_bytes as u128) as usize
+        }
+    }
+}
+
+impl fmt::Display for OomReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to allocate {} bytes aligned to {}: ",
+            self.size, self.align
+        )?;
+        if let Some((fl, sl)) = self.size_class {
+            write!(f, "no free blocks in size class ({}, {}) or above", fl, sl)?;
+        } else {
+            write!(f, "larger than the largest size class")?;
+        }
+        write!(
+            f,
+            "; largest free block {} bytes, {} bytes free in {} blocks \
+            ({}% fragmented)",
+            self.largest_free_block,
+            self.free_bytes,
+            self.free_blocks,
+            self.fragmentation_percent()
+        )
+    }
+}
+
+impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, const SLLEN: usize>
+    Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    /// Register a function to be called with [`Self::oom_report`] when
+    /// [`Self::allocate`] or [`Self::reallocate`] fails, so that failures
+    /// in the field leave something more actionable than a bare `None`.
+    ///
+    /// The hook is called while `self` is borrowed mutably, so it can't use
+    /// `self`.
+    ///
+    /// # Time Complexity
+    ///
+    /// When a hook is registered, a failed allocation takes time
+    /// proportional to the number of free memory blocks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::{OomReport, Tlsf};
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// fn on_oom(report: OomReport) {
+    ///     eprintln!("{}", report);
+    /// }
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 1024];
+    /// let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut pool);
+    /// tlsf.set_oom_hook(on_oom);
+    ///
+    /// // Calls `on_oom`
+    /// assert!(tlsf.allocate(Layout::new::<[u8; 4096]>()).is_none());
+    /// ```
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "oom-report")))]
+    #[inline]
+    pub fn set_oom_hook(&mut self, hook: OomHook) {
+        self.oom_hook = Some(hook);
+    }
+
+    /// Unregister the function registered by [`Self::set_oom_hook`].
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "oom-report")))]
+    #[inline]
+    pub fn clear_oom_hook(&mut self) {
+        self.oom_hook = None;
+    }
+
+    /// Describe why an allocation request for `layout` can't be satisfied
+    /// in the current state of the heap.
+    ///
+    /// The result is meaningful only if the allocation would actually fail.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method takes time proportional to the number of free memory
+    /// blocks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::Tlsf;
+    /// use std::{alloc::Layout, mem::MaybeUninit};
+    ///
+    /// let mut pool = [MaybeUninit::uninit(); 1024];
+    /// let mut tlsf: Tlsf<'_, u8, u8, 8, 8> = Tlsf::new();
+    /// tlsf.insert_free_block(&mut pool);
+    ///
+    /// let layout = Layout::new::<[u8; 2048]>();
+    /// assert!(tlsf.allocate(layout).is_none());
+    /// let report = tlsf.oom_report(layout);
+    /// assert_eq!(report.free_blocks, 1);
+    /// assert_eq!(report.largest_free_block, report.free_bytes);
+    /// assert_eq!(report.fragmentation_percent(), 0);
+    /// ```
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "oom-report")))]
+    pub fn oom_report(&self, layout: Layout) -> OomReport {
+        // Mirror the computation in `allocate_untraced`
+        let max_overhead =
+            layout.align().saturating_sub(GRANULARITY / 2) + mem::size_of::<UsedBlockHdr>();
+        let size_class = layout
+            .size()
+            .checked_add(max_overhead)
+            .and_then(|x| x.checked_add(GRANULARITY - 1))
+            .map(|x| x & !(GRANULARITY - 1))
+            .and_then(Self::map_ceil);
+
+        let mut report = OomReport {
+            size: layout.size(),
+            align: layout.align(),
+            size_class,
+            largest_free_block: 0,
+            free_bytes: 0,
+            free_blocks: 0,
+        };
+        for first_free in self.first_free.iter().flatten() {
+            let mut next_free = *first_free;
+            while let Some(block) = next_free {
+                // Safety: `block` is a free block in one of the free lists
+                unsafe {
+                    let size = block.as_ref().common.size & SIZE_SIZE_MASK;
+                    report.free_blocks += 1;
+                    report.free_bytes += size;
+                    report.largest_free_block = report.largest_free_block.max(size);
+                    next_free = block.as_ref().next_free(self.link_key);
+                }
+            }
+        }
+        report
+    }
+
+    /// Call the hook registered by [`Self::set_oom_hook`] with the
+    /// description of the failed request for `layout`.
+    #[cold]
+    pub(super) fn report_oom(&self, layout: Layout) {
+        if let Some(hook) = self.oom_hook {
+            hook(self.oom_report(layout));
+        }
+    }
+}
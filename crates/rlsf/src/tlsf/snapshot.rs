@@ -0,0 +1,138 @@
+//! Exporting the state of a [`Tlsf`] as JSON
+use std::io::{self, Write};
+
+use super::*;
+
+impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, const SLLEN: usize>
+    Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    /// Write a snapshot of the memory blocks in the specified memory pools
+    /// and the occupancy of the free lists to `writer` as a JSON document,
+    /// for offline tools that visualize fragmentation.
+    ///
+    /// The document has the following form:
+    ///
+    /// ```json
+    /// {
+    ///   "version": 1,
+    ///   "granularity": 32,
+    ///   "pools": [
+    ///     {
+    ///       "start": "0x7f0000001000",
+    ///       "len": 1024,
+    ///       "blocks": [
+    ///         { "offset": 0, "size": 64, "used": true },
+    ///         { "offset": 64, "size": 928, "used": false }
+    ///       ]
+    ///     }
+    ///   ],
+    ///   "free_lists": [
+    ///     { "fl": 3, "sl": 13, "blocks": 1, "bytes": 928 }
+    ///   ]
+    /// }
+    /// ```
+    ///
+    /// `pools[].start` is the address of the first memory block, formatted
+    /// as a hexadecimal string so that it doesn't lose precision in JSON
+    /// parsers. `blocks[].offset` is relative to it, and `size` includes the
+    /// block header. The sentinel blocks aren't included. `free_lists`
+    /// contains only non-empty free lists. Whitespace is omitted in the
+    /// actual output.
+    ///
+    /// Walking a memory pool stops at a memory block with an invalid size,
+    /// but the free lists are trusted, so [`Self::check_integrity`] should
+    /// be called first if the heap might be corrupted.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in linear time (`O(num_blocks)`).
+    ///
+    /// # Safety
+    ///
+    /// `pools` must satisfy the requirements of [`Self::check_integrity`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::Tlsf;
+    /// use std::{mem::MaybeUninit, alloc::Layout, ptr::{NonNull, slice_from_raw_parts_mut}};
+    ///
+    /// static mut POOL: MaybeUninit<[u8; 1024]> = MaybeUninit::uninit();
+    /// let pool_ptr = NonNull::new(unsafe { POOL.as_mut_ptr() }).unwrap();
+    ///
+    /// let mut tlsf: Tlsf<'_, u16, u16, 12, 16> = Tlsf::new();
+    /// let pool_len = unsafe { tlsf.insert_free_block_ptr(pool_ptr) }.unwrap().get();
+    /// let pool_ptr = NonNull::new(
+    ///     slice_from_raw_parts_mut(pool_ptr.as_ptr() as *mut u8, pool_len)
+    /// ).unwrap();
+    ///
+    /// tlsf.allocate(Layout::new::<u64>()).unwrap();
+    ///
+    /// let mut json = Vec::new();
+    /// unsafe { tlsf.write_snapshot(&[pool_ptr], &mut json) }.unwrap();
+    /// let json = String::from_utf8(json).unwrap();
+    /// assert!(json.contains(r#""used":true"#));
+    /// ```
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "std")))]
+    pub unsafe fn write_snapshot(
+        &self,
+        pools: &[NonNull<[u8]>],
+        mut writer: impl Write,
+    ) -> io::Result<()> {
+        write!(
+            writer,
+            r#"{{"version":1,"granularity":{},"pools":["#,
+            GRANULARITY
+        )?;
+        for (i, &pool) in pools.iter().enumerate() {
+            let (start, len) = Self::pool_range(pool);
+            write!(
+                writer,
+                r#"{}{{"start":"{:#x}","len":{},"blocks":["#,
+                if i == 0 { "" } else { "," },
+                start,
+                len
+            )?;
+
+            for (j, (block, size_and_flags)) in Self::pool_blocks(pool).enumerate() {
+                write!(
+                    writer,
+                    r#"{}{{"offset":{},"size":{},"used":{}}}"#,
+                    if j == 0 { "" } else { "," },
+                    block - start,
+                    size_and_flags & SIZE_SIZE_MASK,
+                    (size_and_flags & SIZE_USED) != 0
+                )?;
+            }
+            write!(writer, "]}}")?;
+        }
+
+        write!(writer, r#"],"free_lists":["#)?;
+        let mut is_first = true;
+        for (fl, first_free) in self.first_free.iter().enumerate() {
+            for (sl, &first_free) in first_free.iter().enumerate() {
+                let (mut num_blocks, mut num_bytes) = (0usize, 0usize);
+                let mut next_free = first_free;
+                while let Some(block) = next_free {
+                    num_blocks += 1;
+                    num_bytes += block.as_ref().common.size & SIZE_SIZE_MASK;
+                    next_free = block.as_ref().next_free_unchecked(self.link_key);
+                }
+                if num_blocks == 0 {
+                    continue;
+                }
+                write!(
+                    writer,
+                    r#"{}{{"fl":{},"sl":{},"blocks":{},"bytes":{}}}"#,
+                    if is_first { "" } else { "," },
+                    fl,
+                    sl,
+                    num_blocks,
+                    num_bytes
+                )?;
+                is_first = false;
+            }
+        }
+        write!(writer, "]}}")
+    }
+}
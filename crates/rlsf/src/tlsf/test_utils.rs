@@ -0,0 +1,208 @@
+//! Assertions on the state of a [`Tlsf`] for downstream tests (the
+//! `test-utils` feature)
+use super::*;
+
+/// The expected state of a memory block. See [`Tlsf::assert_block_at`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "test-utils")))]
+pub enum BlockState {
+    /// The memory block is in a free list.
+    Free,
+    /// The memory block is allocated.
+    Used,
+}
+
+/// Implements [`fmt::Display`] by calling the wrapped closure.
+struct DisplayFn<F>(F);
+
+impl<F: Fn(&mut fmt::Formatter<'_>) -> fmt::Result> fmt::Display for DisplayFn<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.0)(f)
+    }
+}
+
+/// The number of memory blocks listed in a panic message.
+const MAX_LISTED_BLOCKS: usize = 16;
+
+/// These methods panic with a description of the discrepancy if the heap
+/// isn't in the expected state, so that tests can check the effect of their
+/// allocations precisely. They also panic if [`Self::check_integrity`]
+/// reports an inconsistency.
+impl<'pool, FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, const SLLEN: usize>
+    Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    /// Panic if the heap is corrupted.
+    #[track_caller]
+    unsafe fn assert_integrity(&self, pools: &[NonNull<[u8]>]) {
+        if let Err(e) = self.check_integrity(pools) {
+            panic!("heap corruption detected: {}", e);
+        }
+    }
+
+    /// Assert that the payloads of the free memory blocks in `pools` (i.e.,
+    /// the free memory blocks minus their headers) amount to `expected`
+    /// bytes.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in `O(num_blocks * max_free_list_len)` time.
+    ///
+    /// # Safety
+    ///
+    /// `pools` must satisfy the requirements of [`Self::check_integrity`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rlsf::{BlockState, Tlsf, GRANULARITY};
+    /// use std::{mem::MaybeUninit, alloc::Layout, ptr::{NonNull, slice_from_raw_parts_mut}};
+    ///
+    /// #[repr(align(64))]
+    /// struct Pool([MaybeUninit<u8>; 1024]);
+    /// static mut POOL: Pool = Pool([MaybeUninit::uninit(); 1024]);
+    /// let pool_ptr = unsafe { POOL.0.as_mut_ptr() as *mut u8 };
+    /// let pools = [NonNull::new(slice_from_raw_parts_mut(pool_ptr, 1024)).unwrap()];
+    ///
+    /// let mut tlsf: Tlsf<'_, u16, u16, 12, 16> = Tlsf::new();
+    /// unsafe { tlsf.insert_free_block_ptr(pools[0]) }.unwrap();
+    ///
+    /// // The pool minus the sentinel block(s) and the free block's header
+    #[cfg_attr(
+        not(feature = "pool-guards"),
+        doc = " let initial_free_bytes = 1024 - GRANULARITY - GRANULARITY / 2;"
+    )]
+    #[cfg_attr(
+        feature = "pool-guards",
+        doc = " let initial_free_bytes = 1024 - GRANULARITY * 2 - GRANULARITY / 2;"
+    )]
+    ///
+    /// // A memory block can hold a header and a `GRANULARITY`-byte allocation
+    /// // only if it's `GRANULARITY * 2` bytes long
+    /// let ptr = tlsf.allocate(Layout::from_size_align(GRANULARITY, 1).unwrap()).unwrap();
+    /// unsafe {
+    ///     tlsf.assert_block_at(&pools, ptr.as_ptr(), GRANULARITY * 3 / 2, BlockState::Used);
+    ///     tlsf.assert_free_bytes(&pools, initial_free_bytes - GRANULARITY * 2);
+    ///
+    ///     tlsf.deallocate(ptr, 1);
+    ///     tlsf.assert_block_at(&pools, ptr.as_ptr(), initial_free_bytes, BlockState::Free);
+    ///     tlsf.assert_free_bytes(&pools, initial_free_bytes);
+    ///     tlsf.assert_no_live_allocations(&pools);
+    /// }
+    /// ```
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "test-utils")))]
+    #[track_caller]
+    pub unsafe fn assert_free_bytes(&self, pools: &[NonNull<[u8]>], expected: usize) {
+        self.assert_integrity(pools);
+
+        let free_bytes: usize = pools
+            .iter()
+            .flat_map(|&pool| Self::pool_blocks(pool))
+            .filter(|&(_, size_and_flags)| (size_and_flags & SIZE_USED) == 0)
+            .map(|(_, size_and_flags)| {
+                (size_and_flags & SIZE_SIZE_MASK) - mem::size_of::<UsedBlockHdr>()
+            })
+            .sum();
+        if free_bytes != expected {
+            panic!(
+                "expected {} free bytes, found {} free bytes",
+                expected, free_bytes
+            );
+        }
+    }
+
+    /// Assert that there are no allocated memory blocks in `pools`. The panic
+    /// message lists the remaining allocations.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in `O(num_blocks * max_free_list_len)` time.
+    ///
+    /// # Safety
+    ///
+    /// `pools` must satisfy the requirements of [`Self::check_integrity`].
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "test-utils")))]
+    #[track_caller]
+    pub unsafe fn assert_no_live_allocations(&self, pools: &[NonNull<[u8]>]) {
+        self.assert_integrity(pools);
+
+        let live_blocks = || {
+            pools
+                .iter()
+                .flat_map(|&pool| Self::pool_blocks(pool))
+                .filter(|&(_, size_and_flags)| (size_and_flags & SIZE_USED) != 0)
+        };
+        let num_live_blocks = live_blocks().count();
+        if num_live_blocks != 0 {
+            panic!(
+                "expected no live allocations, found {}: {}",
+                num_live_blocks,
+                DisplayFn(|f: &mut fmt::Formatter<'_>| {
+                    for (i, (block, size_and_flags)) in
+                        live_blocks().take(MAX_LISTED_BLOCKS).enumerate()
+                    {
+                        write!(
+                            f,
+                            "{}the {}-byte memory block at {:#x}",
+                            if i == 0 { "" } else { ", " },
+                            size_and_flags & SIZE_SIZE_MASK,
+                            block
+                        )?;
+                    }
+                    if num_live_blocks > MAX_LISTED_BLOCKS {
+                        write!(f, ", ...")?;
+                    }
+                    Ok(())
+                })
+            );
+        }
+    }
+
+    /// Assert that the memory block containing `addr` in `pools` is in the
+    /// state `state` and has a `size`-byte payload (i.e., the memory block
+    /// minus its header, which is the size of the largest allocation with an
+    /// alignment smaller than [`GRANULARITY`] that fits in it).
+    ///
+    /// See [`Self::assert_free_bytes`] for an example.
+    ///
+    /// # Time Complexity
+    ///
+    /// This method will complete in `O(num_blocks * max_free_list_len)` time.
+    ///
+    /// # Safety
+    ///
+    /// `pools` must satisfy the requirements of [`Self::check_integrity`].
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "test-utils")))]
+    #[track_caller]
+    pub unsafe fn assert_block_at(
+        &self,
+        pools: &[NonNull<[u8]>],
+        addr: *const u8,
+        size: usize,
+        state: BlockState,
+    ) {
+        self.assert_integrity(pools);
+
+        let addr = addr as usize;
+        let (block, size_and_flags) = pools
+            .iter()
+            .flat_map(|&pool| Self::pool_blocks(pool))
+            .find(|&(block, size_and_flags)| {
+                (block..block + (size_and_flags & SIZE_SIZE_MASK)).contains(&addr)
+            })
+            .unwrap_or_else(|| panic!("no memory block contains {:#x}", addr));
+
+        let actual_size = (size_and_flags & SIZE_SIZE_MASK) - mem::size_of::<UsedBlockHdr>();
+        let actual_state = if (size_and_flags & SIZE_USED) != 0 {
+            BlockState::Used
+        } else {
+            BlockState::Free
+        };
+        if (actual_size, actual_state) != (size, state) {
+            panic!(
+                "expected a {:?} memory block with a {}-byte payload at {:#x}, found a {:?} \
+                memory block with a {}-byte payload at {:#x}",
+                state, size, addr, actual_state, actual_size, block
+            );
+        }
+    }
+}
@@ -35,6 +35,19 @@ mod blocks_checker {
     }
 }
 
+/// A [`RandomSource`] for testing
+#[cfg(feature = "randomize")]
+fn xorshift() -> usize {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static STATE: AtomicUsize = AtomicUsize::new(0x2545_f491);
+    let mut x = STATE.load(Ordering::Relaxed);
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    STATE.store(x, Ordering::Relaxed);
+    x
+}
+
 macro_rules! gen_test {
     ($mod:ident, $($tt:tt)*) => {
         mod $mod {
@@ -106,6 +119,676 @@ macro_rules! gen_test {
                 }
             }
 
+            #[cfg(feature = "hardened")]
+            #[test]
+            fn check_allocation() {
+                let _ = env_logger::builder().is_test(true).try_init();
+
+                let mut tlsf: TheTlsf = Tlsf::new();
+
+                let mut pool = [MaybeUninit::new(0u8); 65536];
+                tlsf.insert_free_block(&mut pool);
+
+                unsafe {
+                    let layouts = [
+                        Layout::from_size_align(20, 1).unwrap(),
+                        Layout::from_size_align(20, 64).unwrap(),
+                        Layout::from_size_align(20, 1).unwrap(),
+                    ];
+                    let ptrs: Option<Vec<_>> = layouts
+                        .iter()
+                        .map(|&layout| tlsf.allocate(layout))
+                        .collect();
+                    let ptrs = if let Some(ptrs) = ptrs {
+                        ptrs
+                    } else {
+                        // The configuration doesn't support these allocations
+                        return;
+                    };
+                    for (&ptr, layout) in ptrs.iter().zip(layouts.iter()) {
+                        assert_eq!(TheTlsf::check_allocation(ptr, Some(layout.align())), Ok(()));
+                        assert_eq!(TheTlsf::check_allocation(ptr, None), Ok(()));
+                    }
+
+                    // Misaligned pointer
+                    let bad_ptr = NonNull::new_unchecked(ptrs[0].as_ptr().add(1));
+                    assert!(TheTlsf::check_allocation(bad_ptr, Some(1)).is_err());
+
+                    // Double free
+                    tlsf.deallocate(ptrs[0], 1);
+                    assert!(TheTlsf::check_allocation(ptrs[0], Some(1)).is_err());
+
+                    // Overwrite the size field of `ptrs[2]`, as if by a
+                    // buffer overflow from the preceding block
+                    let size = ptrs[2].as_ptr().sub(GRANULARITY / 2).cast::<usize>();
+                    *size += GRANULARITY;
+                    assert!(TheTlsf::check_allocation(ptrs[2], Some(1)).is_err());
+                }
+            }
+
+            #[test]
+            fn check_integrity() {
+                let _ = env_logger::builder().is_test(true).try_init();
+
+                let mut pool = Align([MaybeUninit::new(0u8); 65536]);
+                let pool_ptr = NonNull::new(pool.0.as_mut_ptr() as *mut u8).unwrap();
+
+                // Create a heap with a free block followed by two used blocks
+                let new_tlsf = || -> Option<(TheTlsf, NonNull<[u8]>, NonNull<u8>)> {
+                    let mut tlsf: TheTlsf = Tlsf::new();
+                    let pool_len = unsafe {
+                        tlsf.insert_free_block_ptr(nonnull_slice_from_raw_parts(pool_ptr, 65536))
+                    }?
+                    .get();
+                    let pool = nonnull_slice_from_raw_parts(pool_ptr, pool_len);
+                    assert_eq!(unsafe { tlsf.check_integrity(&[pool]) }, Ok(()));
+
+                    let layout = Layout::from_size_align(64, 1).unwrap();
+                    let ptrs = [
+                        tlsf.allocate(layout)?,
+                        tlsf.allocate(layout)?,
+                        tlsf.allocate(layout)?,
+                    ];
+                    unsafe { tlsf.deallocate(ptrs[0], 1) };
+                    assert_eq!(unsafe { tlsf.check_integrity(&[pool]) }, Ok(()));
+
+                    let block = NonNull::new(ptrs[1].as_ptr().wrapping_sub(GRANULARITY / 2)).unwrap();
+                    Some((tlsf, pool, block))
+                };
+                let first_listed = |tlsf: &TheTlsf| {
+                    tlsf.first_free
+                        .iter()
+                        .enumerate()
+                        .flat_map(|(fl, lists)| {
+                            lists.iter().enumerate().filter_map(move |(sl, &b)| Some((fl, sl, b?)))
+                        })
+                        .next()
+                        .unwrap()
+                };
+
+                if new_tlsf().is_none() {
+                    // The configuration doesn't support these allocations
+                    return;
+                }
+
+                unsafe {
+                    // No pools
+                    let (tlsf, _, _) = new_tlsf().unwrap();
+                    assert!(matches!(
+                        tlsf.check_integrity(&[]),
+                        Err(IntegrityError::ForeignFreeBlock { .. })
+                    ));
+
+                    // A buffer overflow into the used block's size field
+                    let (tlsf, pool, block) = new_tlsf().unwrap();
+                    *block.cast::<usize>().as_ptr() += GRANULARITY;
+                    assert!(tlsf.check_integrity(&[pool]).is_err());
+
+                    // A used block marked as free
+                    let (tlsf, pool, block) = new_tlsf().unwrap();
+                    *block.cast::<usize>().as_ptr() &= !SIZE_USED;
+                    assert!(matches!(
+                        tlsf.check_integrity(&[pool]),
+                        Err(IntegrityError::AdjacentFreeBlocks { block: b }
+                            | IntegrityError::UnlistedFreeBlock { block: b }) if b == block
+                    ));
+
+                    // A corrupted bitmap
+                    let (mut tlsf, pool, _) = new_tlsf().unwrap();
+                    let (fl, _, _) = first_listed(&tlsf);
+                    tlsf.fl_bitmap.clear_bit(fl as u32);
+                    assert_eq!(
+                        tlsf.check_integrity(&[pool]),
+                        Err(IntegrityError::BitmapMismatch { fl })
+                    );
+
+                    // A corrupted free list link
+                    let (tlsf, pool, _) = new_tlsf().unwrap();
+                    let (fl, sl, mut free_block) = first_listed(&tlsf);
+                    free_block
+                        .as_mut()
+                        .set_prev_free(Some(free_block), tlsf.link_key);
+                    assert_eq!(
+                        tlsf.check_integrity(&[pool]),
+                        Err(IntegrityError::BadPrevFree {
+                            fl,
+                            sl,
+                            block: free_block.cast()
+                        })
+                    );
+
+                    // A free list pointing to outside the pool
+                    let (tlsf, pool, _) = new_tlsf().unwrap();
+                    let (fl, sl, mut free_block) = first_listed(&tlsf);
+                    let mut foreign = Align([0u8; 64]);
+                    let foreign = NonNull::new(foreign.0.as_mut_ptr()).unwrap();
+                    free_block
+                        .as_mut()
+                        .set_next_free(Some(foreign.cast()), tlsf.link_key);
+                    assert_eq!(
+                        tlsf.check_integrity(&[pool]),
+                        Err(IntegrityError::ForeignFreeBlock {
+                            fl,
+                            sl,
+                            block: foreign
+                        })
+                    );
+                }
+            }
+
+            // AddressSanitizer would abort on the use-after-free accesses
+            #[cfg(all(feature = "poison", not(feature = "asan")))]
+            #[test]
+            fn poison() {
+                use std::panic::{catch_unwind, AssertUnwindSafe};
+
+                let _ = env_logger::builder().is_test(true).try_init();
+
+                let mut tlsf: TheTlsf = Tlsf::new();
+
+                let mut pool = [MaybeUninit::uninit(); 4096];
+                tlsf.insert_free_block(&mut pool);
+
+                let layout = Layout::from_size_align(64, 1).unwrap();
+                let ptr = if let Some(ptr) = tlsf.allocate(layout) {
+                    ptr
+                } else {
+                    // The configuration doesn't support this allocation
+                    return;
+                };
+
+                unsafe {
+                    // Reuse without a use-after-free write
+                    tlsf.deallocate(ptr, 1);
+                    assert_eq!(*ptr.as_ptr().add(GRANULARITY / 2), 0xdd);
+                    let ptr = tlsf.allocate(layout).unwrap();
+
+                    // Use-after-free write
+                    tlsf.deallocate(ptr, 1);
+                    *ptr.as_ptr().add(GRANULARITY / 2) = 0;
+                }
+
+                // Exhaust the free space to reuse the modified memory
+                let small_layout = Layout::from_size_align(1, 1).unwrap();
+                loop {
+                    match catch_unwind(AssertUnwindSafe(|| tlsf.allocate(small_layout))) {
+                        Ok(Some(_)) => {}
+                        Ok(None) => panic!("use after free wasn't detected"),
+                        Err(_) => break,
+                    }
+                }
+            }
+
+            #[cfg(feature = "randomize")]
+            #[test]
+            fn randomize() {
+                let _ = env_logger::builder().is_test(true).try_init();
+
+                let mut pool = Align([MaybeUninit::<u8>::uninit(); 65536]);
+                let pool_ptr = NonNull::new(pool.0.as_mut_ptr() as *mut u8).unwrap();
+
+                let layout = Layout::from_size_align(64, 1).unwrap();
+                let mut addresses = std::collections::BTreeSet::new();
+                for _ in 0..16 {
+                    let mut tlsf: TheTlsf = Tlsf::new();
+                    tlsf.set_random_source(xorshift);
+                    let pool_len = if let Some(pool_len) = unsafe {
+                        tlsf.insert_free_block_ptr(nonnull_slice_from_raw_parts(pool_ptr, 65536))
+                    } {
+                        pool_len.get()
+                    } else {
+                        // The configuration doesn't support this memory pool
+                        return;
+                    };
+                    let pool = nonnull_slice_from_raw_parts(pool_ptr, pool_len);
+
+                    let ptrs: Vec<_> = (0..8).map_while(|_| tlsf.allocate(layout)).collect();
+                    if ptrs.is_empty() {
+                        // The configuration doesn't support this allocation
+                        return;
+                    }
+                    for &ptr in ptrs.iter().step_by(2) {
+                        unsafe { tlsf.deallocate(ptr, 1) };
+                    }
+                    assert_eq!(unsafe { tlsf.check_integrity(&[pool]) }, Ok(()));
+
+                    if let Some(ptr) = tlsf.allocate(layout) {
+                        addresses.insert(ptr);
+                    }
+                    assert_eq!(unsafe { tlsf.check_integrity(&[pool]) }, Ok(()));
+                }
+
+                // The allocations aren't always placed at the same address
+                assert!(addresses.len() > 1, "{:?}", addresses);
+            }
+
+            #[cfg(feature = "safe-linking")]
+            #[test]
+            fn safe_linking() {
+                use std::panic::{catch_unwind, AssertUnwindSafe};
+
+                let _ = env_logger::builder().is_test(true).try_init();
+
+                let mut tlsf: TheTlsf = Tlsf::new();
+
+                let mut pool = [MaybeUninit::uninit(); 65536];
+                tlsf.insert_free_block(&mut pool);
+
+                let layout = Layout::from_size_align(64, 1).unwrap();
+                let ptrs: Option<Vec<_>> = (0..4).map(|_| tlsf.allocate(layout)).collect();
+                let ptrs = if let Some(ptrs) = ptrs {
+                    ptrs
+                } else {
+                    // The configuration doesn't support these allocations
+                    return;
+                };
+
+                unsafe {
+                    // Create free blocks that aren't merged with each other
+                    tlsf.deallocate(ptrs[0], 1);
+                    tlsf.deallocate(ptrs[2], 1);
+
+                    // The free lists stay usable after changing the secret
+                    tlsf.set_link_secret(0x5a5a_a5a5);
+                    let ptr = tlsf.allocate(layout).unwrap();
+                    tlsf.deallocate(ptr, 1);
+
+                    // Corrupt the first block of each free list
+                    for &block in tlsf.first_free.iter().flatten().flatten() {
+                        let link = &mut (*block.as_ptr()).next_free;
+                        *link = crate::utils::ptr_map_addr(*link, |addr| addr ^ 1);
+                    }
+                }
+
+                let result = catch_unwind(AssertUnwindSafe(|| tlsf.allocate(layout)));
+                assert!(result.is_err(), "corrupted link wasn't detected");
+            }
+
+            #[cfg(feature = "header-checksum")]
+            #[test]
+            fn header_checksum() {
+                use std::panic::{catch_unwind, AssertUnwindSafe};
+
+                let _ = env_logger::builder().is_test(true).try_init();
+
+                let mut tlsf: TheTlsf = Tlsf::new();
+
+                let mut pool = [MaybeUninit::uninit(); 65536];
+                tlsf.insert_free_block(&mut pool);
+
+                let layout = Layout::from_size_align(64, 1).unwrap();
+                let ptrs: Option<Vec<_>> = (0..3).map(|_| tlsf.allocate(layout)).collect();
+                let ptrs = if let Some(ptrs) = ptrs {
+                    ptrs
+                } else {
+                    // The configuration doesn't support these allocations
+                    return;
+                };
+
+                unsafe {
+                    // Intact headers pass the check
+                    tlsf.deallocate(ptrs[2], 1);
+
+                    // Corrupt the header of the second block
+                    let block = ptrs[1].as_ptr().sub(GRANULARITY / 2) as *mut BlockHdr;
+                    (*block).size ^= 1 << 2;
+                }
+
+                // Merging the first block requires the next block's header
+                let result = catch_unwind(AssertUnwindSafe(|| unsafe { tlsf.deallocate(ptrs[0], 1) }));
+                assert!(result.is_err(), "corrupted header wasn't detected");
+            }
+
+            #[cfg(feature = "test-utils")]
+            #[test]
+            fn test_utils() {
+                use std::panic::{catch_unwind, AssertUnwindSafe};
+
+                let _ = env_logger::builder().is_test(true).try_init();
+
+                let mut pool = Align([MaybeUninit::<u8>::uninit(); 65536]);
+                let pool_ptr = NonNull::new(pool.0.as_mut_ptr() as *mut u8).unwrap();
+
+                let mut tlsf: TheTlsf = Tlsf::new();
+                let pool_len = if let Some(pool_len) = unsafe {
+                    tlsf.insert_free_block_ptr(nonnull_slice_from_raw_parts(pool_ptr, 65536))
+                } {
+                    pool_len.get()
+                } else {
+                    // The configuration doesn't support this memory pool
+                    return;
+                };
+                let pools = [nonnull_slice_from_raw_parts(pool_ptr, pool_len)];
+
+                let free_bytes: usize = unsafe { Tlsf::<u8, u8, 1, 1>::pool_blocks(pools[0]) }
+                    .map(|(_, size_and_flags)| (size_and_flags & SIZE_SIZE_MASK) - GRANULARITY / 2)
+                    .sum();
+                unsafe {
+                    tlsf.assert_free_bytes(&pools, free_bytes);
+                    tlsf.assert_no_live_allocations(&pools);
+                }
+
+                let layout = Layout::from_size_align(GRANULARITY, 1).unwrap();
+                let ptr = if let Some(ptr) = tlsf.allocate(layout) {
+                    ptr
+                } else {
+                    // The configuration doesn't support this allocation
+                    return;
+                };
+                unsafe {
+                    tlsf.assert_block_at(&pools, ptr.as_ptr(), GRANULARITY * 3 / 2, BlockState::Used);
+                    tlsf.assert_free_bytes(&pools, free_bytes - GRANULARITY * 2);
+                }
+
+                let panics = |f: &dyn Fn()| catch_unwind(AssertUnwindSafe(f)).is_err();
+                assert!(panics(&|| unsafe { tlsf.assert_free_bytes(&pools, free_bytes) }));
+                assert!(panics(&|| unsafe { tlsf.assert_no_live_allocations(&pools) }));
+                assert!(panics(&|| unsafe {
+                    tlsf.assert_block_at(&pools, ptr.as_ptr(), GRANULARITY * 3 / 2, BlockState::Free)
+                }));
+
+                unsafe {
+                    tlsf.deallocate(ptr, 1);
+                    tlsf.assert_free_bytes(&pools, free_bytes);
+                    tlsf.assert_no_live_allocations(&pools);
+                }
+            }
+
+            #[cfg(feature = "std")]
+            #[test]
+            fn snapshot() {
+                let _ = env_logger::builder().is_test(true).try_init();
+
+                let mut pool = Align([MaybeUninit::<u8>::uninit(); 65536]);
+                let pool_ptr = NonNull::new(pool.0.as_mut_ptr() as *mut u8).unwrap();
+
+                let mut tlsf: TheTlsf = Tlsf::new();
+                let pool_len = if let Some(pool_len) = unsafe {
+                    tlsf.insert_free_block_ptr(nonnull_slice_from_raw_parts(pool_ptr, 65536))
+                } {
+                    pool_len.get()
+                } else {
+                    // The configuration doesn't support this memory pool
+                    return;
+                };
+                let pool = nonnull_slice_from_raw_parts(pool_ptr, pool_len);
+
+                let layout = Layout::from_size_align(64, 1).unwrap();
+                let ptrs: Vec<_> = (0..4).map_while(|_| tlsf.allocate(layout)).collect();
+                if let Some(&ptr) = ptrs.first() {
+                    unsafe { tlsf.deallocate(ptr, 1) };
+                }
+
+                let mut json = Vec::new();
+                unsafe { tlsf.write_snapshot(&[pool], &mut json) }.unwrap();
+                let json = String::from_utf8(json).unwrap();
+                log::trace!("{}", json);
+
+                assert!(json.starts_with(r#"{"version":1,"#), "{}", json);
+                assert_eq!(
+                    json.matches(r#""used":true"#).count(),
+                    ptrs.len().saturating_sub(1),
+                    "{}",
+                    json
+                );
+
+                // Every free memory block appears in both the block map and
+                // a free list
+                let num_free_blocks = json.matches(r#""used":false"#).count();
+                let num_listed_blocks: usize = json
+                    .match_indices(r#""blocks":"#)
+                    .filter_map(|(i, m)| {
+                        let rest = &json[i + m.len()..];
+                        let end = rest.find(|c: char| !c.is_ascii_digit())?;
+                        rest[..end].parse::<usize>().ok()
+                    })
+                    .sum();
+                assert_eq!(num_free_blocks, num_listed_blocks, "{}", json);
+            }
+
+            #[cfg(all(
+                feature = "zero-on-free",
+                not(any(feature = "poison", feature = "asan"))
+            ))]
+            #[test]
+            fn zero_on_free() {
+                let _ = env_logger::builder().is_test(true).try_init();
+
+                let mut tlsf: TheTlsf = Tlsf::new();
+
+                let mut pool = [MaybeUninit::uninit(); 65536];
+                tlsf.insert_free_block(&mut pool);
+
+                let layout = Layout::from_size_align(256, 1).unwrap();
+                let ptrs: Option<Vec<_>> = (0..5).map(|_| tlsf.allocate(layout)).collect();
+                let ptrs = if let Some(ptrs) = ptrs {
+                    ptrs
+                } else {
+                    // The configuration doesn't support these allocations
+                    return;
+                };
+
+                // The part of the payload that doesn't store the free block's
+                // header
+                let header_len = mem::size_of::<FreeBlockHdr>() - mem::size_of::<UsedBlockHdr>();
+
+                unsafe {
+                    // Free blocks that aren't merged with their neighbors
+                    ptrs[1].as_ptr().write_bytes(0xaa, 256);
+                    tlsf.deallocate(ptrs[1], 1);
+                    let payload = std::slice::from_raw_parts(ptrs[1].as_ptr(), 256);
+                    assert!(payload[header_len..].iter().all(|&b| b == 0));
+
+                    tlsf.set_zero_on_free_limit(64);
+                    ptrs[3].as_ptr().write_bytes(0xaa, 256);
+                    tlsf.deallocate(ptrs[3], 1);
+                    let payload = std::slice::from_raw_parts(ptrs[3].as_ptr(), 256);
+                    assert!(payload[header_len..64].iter().all(|&b| b == 0));
+                    assert!(payload[64..].iter().all(|&b| b == 0xaa));
+                }
+            }
+
+            #[cfg(feature = "asan")]
+            #[test]
+            fn asan_poisoned() {
+                let _ = env_logger::builder().is_test(true).try_init();
+
+                let mut tlsf: TheTlsf = Tlsf::new();
+
+                let mut pool = [MaybeUninit::uninit(); 4096];
+                tlsf.insert_free_block(&mut pool);
+
+                let layout = Layout::from_size_align(64, 1).unwrap();
+                let ptr = if let Some(ptr) = tlsf.allocate(layout) {
+                    ptr
+                } else {
+                    // The configuration doesn't support this allocation
+                    return;
+                };
+
+                unsafe {
+                    assert!(!crate::asan::is_poisoned(ptr.as_ptr()));
+                    assert!(!crate::asan::is_poisoned(ptr.as_ptr().add(63)));
+
+                    tlsf.deallocate(ptr, 1);
+                    assert!(crate::asan::is_poisoned(ptr.as_ptr().add(GRANULARITY / 2)));
+                    assert!(crate::asan::is_poisoned(ptr.as_ptr().add(63)));
+
+                    let ptr = tlsf.allocate(layout).unwrap();
+                    assert!(!crate::asan::is_poisoned(ptr.as_ptr()));
+                    assert!(!crate::asan::is_poisoned(ptr.as_ptr().add(63)));
+                    tlsf.deallocate(ptr, 1);
+                }
+            }
+
+            // AddressSanitizer would abort on the accesses to the freed
+            // memory blocks' headers
+            #[cfg(not(feature = "asan"))]
+            #[test]
+            fn double_free() {
+                use std::panic::{catch_unwind, AssertUnwindSafe};
+
+                let _ = env_logger::builder().is_test(true).try_init();
+
+                let mut tlsf: TheTlsf = Tlsf::new();
+
+                let mut pool = [MaybeUninit::uninit(); 65536];
+                tlsf.insert_free_block(&mut pool);
+
+                for &align in &[1, GRANULARITY, GRANULARITY * 4] {
+                    for &provide_align in &[true, false] {
+                        let layout = Layout::from_size_align(20, align).unwrap();
+                        let ptrs: Option<Vec<_>> = (0..3).map(|_| tlsf.allocate(layout)).collect();
+                        let ptrs = if let Some(ptrs) = ptrs {
+                            ptrs
+                        } else {
+                            // The configuration doesn't support these allocations
+                            return;
+                        };
+
+                        unsafe {
+                            // `ptrs[1..]` are merged into `ptrs[0]`'s free block
+                            for &ptr in &ptrs {
+                                tlsf.deallocate(ptr, align);
+                            }
+
+                            for &ptr in &ptrs {
+                                let result = catch_unwind(AssertUnwindSafe(|| {
+                                    if provide_align {
+                                        tlsf.deallocate(ptr, align);
+                                    } else {
+                                        tlsf.deallocate_unknown_align(ptr);
+                                    }
+                                }));
+                                assert!(result.is_err(), "double free of {:p} wasn't detected", ptr);
+                            }
+                        }
+                    }
+                }
+            }
+
+            #[cfg(debug_assertions)]
+            #[test]
+            fn align_mismatch() {
+                use std::panic::{catch_unwind, AssertUnwindSafe};
+
+                let _ = env_logger::builder().is_test(true).try_init();
+
+                let mut tlsf: TheTlsf = Tlsf::new();
+
+                let mut pool = Align([MaybeUninit::uninit(); 65536]);
+                tlsf.insert_free_block(&mut pool.0);
+
+                for &(align, wrong_align) in &[
+                    (1, GRANULARITY * 8),
+                    (8, GRANULARITY * 8),
+                    (GRANULARITY, 1),
+                    (GRANULARITY * 4, 1),
+                    // These don't change the header location
+                    (1, 8),
+                    (8, 2),
+                    (GRANULARITY, GRANULARITY * 2),
+                    (GRANULARITY * 4, GRANULARITY),
+                ] {
+                    if align < GRANULARITY && wrong_align < GRANULARITY && !SIZE_ALIGN_RECORDED {
+                        continue;
+                    }
+                    let layout = Layout::from_size_align(40, align).unwrap();
+                    let ptr = if let Some(ptr) = tlsf.allocate(layout) {
+                        ptr
+                    } else {
+                        // The configuration doesn't support this allocation
+                        continue;
+                    };
+
+                    unsafe {
+                        let result = catch_unwind(AssertUnwindSafe(|| {
+                            tlsf.deallocate(ptr, wrong_align);
+                        }));
+                        let payload = result.unwrap_err();
+                        assert_eq!(
+                            payload.downcast_ref::<String>(),
+                            Some(
+                                &crate::HeapError::AlignMismatch {
+                                    ptr,
+                                    align: wrong_align
+                                }
+                                .to_string()
+                            )
+                        );
+
+                        let result = catch_unwind(AssertUnwindSafe(|| {
+                            tlsf.reallocate(ptr, Layout::from_size_align(80, wrong_align).unwrap());
+                        }));
+                        assert!(result.is_err(), "mismatch wasn't detected");
+
+                        // The memory block is intact
+                        tlsf.deallocate(ptr, align);
+                    }
+                }
+            }
+
+            // AddressSanitizer would abort on the out-of-bounds writes
+            #[cfg(all(feature = "pool-guards", not(feature = "asan")))]
+            #[test]
+            fn pool_guards() {
+                use std::panic::{catch_unwind, AssertUnwindSafe};
+
+                let _ = env_logger::builder().is_test(true).try_init();
+
+                let layout = Layout::from_size_align(20, 1).unwrap();
+                for &clobber_leading in &[false, true] {
+                    let mut tlsf: TheTlsf = Tlsf::new();
+                    let mut pool = Align([MaybeUninit::<u8>::uninit(); 4096]);
+                    let pool_ptr = NonNull::new(pool.0.as_mut_ptr() as *mut u8).unwrap();
+                    let pool_len = unsafe {
+                        tlsf.insert_free_block_ptr(nonnull_slice_from_raw_parts(pool_ptr, 4096))
+                    }
+                    .unwrap()
+                    .get();
+                    let pools = [nonnull_slice_from_raw_parts(pool_ptr, pool_len)];
+
+                    let ptr = if let Some(ptr) = tlsf.allocate(layout) {
+                        ptr
+                    } else {
+                        // The configuration doesn't support this allocation
+                        return;
+                    };
+
+                    unsafe {
+                        assert_eq!(tlsf.check_integrity(&pools), Ok(()));
+
+                        // The memory block is the first one in its memory pool,
+                        // and the free memory block following it precedes the
+                        // guard at the end
+                        let block = TheTlsf::used_block_hdr_for_allocation(ptr, 1).cast::<BlockHdr>();
+                        assert_eq!(block.as_ref().prev_phys_block, None);
+                        let free_block = block.as_ref().next_phys_block();
+                        let guard = if clobber_leading {
+                            NonNull::new_unchecked(block.cast::<u8>().as_ptr().sub(GRANULARITY))
+                        } else {
+                            free_block.as_ref().next_phys_block().cast()
+                        };
+
+                        // A one-byte overflow into the guard
+                        *guard.as_ptr() ^= SIZE_USED as u8;
+                        assert_eq!(
+                            tlsf.check_integrity(&pools),
+                            Err(IntegrityError::BadBlockHeader { block: guard })
+                        );
+
+                        let payload = catch_unwind(AssertUnwindSafe(|| {
+                            tlsf.deallocate(ptr, 1);
+                        }))
+                        .unwrap_err();
+                        assert_eq!(
+                            payload.downcast_ref::<String>(),
+                            Some(&crate::HeapError::PoolGuardViolation { block: guard }.to_string())
+                        );
+                    }
+                }
+            }
+
             #[test]
             fn ara() {
                 let _ = env_logger::builder().is_test(true).try_init();
@@ -129,6 +812,65 @@ macro_rules! gen_test {
                 log::trace!("ptr = {:?}", ptr);
             }
 
+            #[test]
+            fn reallocate_checked() {
+                let _ = env_logger::builder().is_test(true).try_init();
+
+                let mut tlsf: TheTlsf = Tlsf::new();
+
+                let mut pool = Align([MaybeUninit::uninit(); 65536]);
+                tlsf.insert_free_block(&mut pool.0);
+
+                for &align in &[1, 8, GRANULARITY, GRANULARITY * 4] {
+                    let layout = Layout::from_size_align(40, align).unwrap();
+                    let ptr = if let Some(ptr) = tlsf.allocate(layout) {
+                        ptr
+                    } else {
+                        // The configuration doesn't support this allocation
+                        continue;
+                    };
+                    let usable = unsafe { TheTlsf::size_of_allocation(ptr, align) };
+                    let new_layout = Layout::from_size_align(200, align).unwrap();
+
+                    let claim = |size, align| Layout::from_size_align(size, align).unwrap();
+                    unsafe {
+                        assert_eq!(
+                            tlsf.reallocate_checked(ptr, claim(usable + 1, align), new_layout),
+                            Err(LayoutMismatch::Size {
+                                claimed: usable + 1,
+                                usable
+                            })
+                        );
+                        assert_eq!(
+                            tlsf.reallocate_checked(ptr, claim(40, align), claim(200, align * 2)),
+                            Err(LayoutMismatch::AlignChange)
+                        );
+                        let wrong_align = if align >= GRANULARITY { 1 } else { GRANULARITY * 8 };
+                        assert_eq!(
+                            tlsf.reallocate_checked(
+                                ptr,
+                                claim(40, wrong_align),
+                                claim(200, wrong_align)
+                            ),
+                            Err(LayoutMismatch::Align {
+                                claimed: wrong_align
+                            })
+                        );
+
+                        ptr.as_ptr().write_bytes(0x5a, usable);
+                        if let Ok(Some(new_ptr)) =
+                            tlsf.reallocate_checked(ptr, claim(usable, align), new_layout)
+                        {
+                            let new_ptr = new_ptr.as_ptr();
+                            assert!((0..usable).all(|i| *new_ptr.add(i) == 0x5a));
+                            tlsf.deallocate(NonNull::new_unchecked(new_ptr), align);
+                        } else {
+                            tlsf.deallocate(ptr, align);
+                        }
+                    }
+                }
+            }
+
             #[test]
             fn append_free_block_ptr() {
                 let _ = env_logger::builder().is_test(true).try_init();
@@ -207,6 +949,11 @@ macro_rules! gen_test {
                 let mut sa = ShadowAllocator::new();
                 let mut tlsf: TheTlsf = Tlsf::new();
 
+                #[cfg(feature = "randomize")]
+                if pool_start % 2 == 0 {
+                    tlsf.set_random_source(xorshift);
+                }
+
                 let mut pool = Align([MaybeUninit::<u8>::uninit(); 65536]);
                 let pool_ptr;
                 // The end index of the memory pool inserted to `tlsf`
@@ -351,6 +1098,85 @@ macro_rules! gen_test {
 
                     // Scan all blocks for every iteration
                     unsafe { blocks_checker::trace_blocks(pool_ptr, pool_len, &tlsf) };
+
+                    let pools: Vec<_> = pool_len
+                        .map(|pool_len| {
+                            nonnull_slice_from_raw_parts(NonNull::new(pool_ptr).unwrap(), pool_len)
+                        })
+                        .into_iter()
+                        .collect();
+                    assert_eq!(unsafe { tlsf.check_integrity(&pools) }, Ok(()));
+
+                    // The queries that don't walk the heap should agree with
+                    // the free lists
+                    let (mut largest, mut free_bytes) = (0, 0);
+                    let mut histogram = [[0; TheTlsf::SLLEN]; TheTlsf::FLLEN];
+                    for &first_free in tlsf.first_free.iter().flatten() {
+                        let mut next_free = first_free;
+                        while let Some(block) = next_free {
+                            let size = unsafe { block.as_ref().common.size } & SIZE_SIZE_MASK;
+                            largest = largest.max(size);
+                            free_bytes += size;
+                            let (fl, sl) = TheTlsf::map_floor(size).unwrap();
+                            histogram[fl][sl] += 1;
+                            next_free = unsafe { block.as_ref().next_free(tlsf.link_key) };
+                        }
+                    }
+                    assert_eq!(tlsf.free_histogram(), histogram);
+
+                    // The heap walk should agree with the free lists and
+                    // the live allocations
+                    let occupancy = unsafe { tlsf.occupancy(&pools) };
+                    for (fl, classes) in occupancy.iter().enumerate() {
+                        for (sl, class) in classes.iter().enumerate() {
+                            assert_eq!(class.free_blocks, histogram[fl][sl]);
+                        }
+                    }
+                    let classes = || occupancy.iter().flatten();
+                    assert_eq!(classes().map(|c| c.free_bytes).sum::<usize>(), free_bytes);
+                    assert_eq!(classes().map(|c| c.used_blocks).sum::<usize>(), allocs.len());
+                    assert!(
+                        classes().map(|c| c.used_bytes).sum::<usize>()
+                            >= allocs.iter().map(|a| a.layout.size()).sum::<usize>()
+                    );
+
+                    assert_eq!(tlsf.largest_free_block_size(), largest);
+
+                    #[cfg(feature = "stats")]
+                    {
+                        let padding = (pool_ptr as usize).wrapping_neg() % super::GRANULARITY;
+                        let utilization = tlsf.utilization();
+                        assert_eq!(utilization.free_bytes, free_bytes);
+                        assert_eq!(utilization.pool_bytes, pool_len.map_or(0, |len| len - padding));
+
+                        // Only the first 8 memory pools are tracked. More memory
+                        // pools are created when `append_free_block_ptr` can't
+                        // extend the existing one.
+                        #[cfg(feature = "pool-stats")]
+                        if tlsf.pool_usage().count() < 8 {
+                            let usage: Vec<_> = tlsf.pool_usage().collect();
+                            assert_eq!(
+                                usage.iter().map(|u| nonnull_slice_len(u.pool)).sum::<usize>(),
+                                utilization.pool_bytes
+                            );
+                            assert_eq!(usage.iter().map(|u| u.in_use).sum::<usize>(), tlsf.in_use());
+                        }
+                    }
+                }
+            }
+
+            #[test]
+            fn free_list_min_size() {
+                for i in 1..4096 {
+                    let size = i * super::GRANULARITY;
+                    let (fl, sl) = if let Some(x) = TheTlsf::map_floor(size) {
+                        x
+                    } else {
+                        break;
+                    };
+                    let min_size = TheTlsf::free_list_min_size(fl, sl);
+                    assert!(min_size <= size);
+                    assert_eq!(TheTlsf::map_floor(min_size), Some((fl, sl)));
                 }
             }
 
@@ -499,3 +1325,540 @@ gen_test!(tlsf_u64_u8_59_8, u64, u64, 59, 8);
 gen_test!(tlsf_u64_u8_60_8, u64, u64, 60, 8);
 gen_test!(tlsf_u64_u8_61_8, u64, u64, 61, 8);
 gen_test!(tlsf_u64_u8_64_8, u64, u64, 64, 8);
+
+#[cfg(feature = "debug-leak-check")]
+mod leak_check {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    type TheTlsf = Tlsf<'static, u32, u32, 20, 32>;
+
+    fn new_tlsf() -> TheTlsf {
+        let mut tlsf = TheTlsf::new();
+        let pool = Box::leak(Box::new([MaybeUninit::uninit(); 65536]));
+        tlsf.insert_free_block(pool);
+        tlsf
+    }
+
+    #[test]
+    fn leak_ignored_without_reporter() {
+        let mut tlsf = new_tlsf();
+        tlsf.allocate(Layout::new::<u64>()).unwrap();
+    }
+
+    #[test]
+    fn leak_reported() {
+        static REPORTED_COUNT: AtomicUsize = AtomicUsize::new(0);
+        static REPORTED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+        let mut tlsf = new_tlsf();
+        tlsf.set_leak_reporter(|report| {
+            REPORTED_COUNT.store(report.count, Ordering::Relaxed);
+            REPORTED_BYTES.store(report.bytes, Ordering::Relaxed);
+        });
+        let ptr1 = tlsf.allocate(Layout::new::<u64>()).unwrap();
+        let ptr2 = tlsf.allocate(Layout::new::<[u8; 100]>()).unwrap();
+        let ptr3 = tlsf.allocate(Layout::new::<u64>()).unwrap();
+        unsafe {
+            // In-place and moving reallocation
+            let ptr2 = tlsf.reallocate(ptr2, Layout::new::<[u8; 50]>()).unwrap();
+            let ptr1 = tlsf.reallocate(ptr1, Layout::new::<[u64; 64]>()).unwrap();
+            tlsf.deallocate(ptr3, Layout::new::<u64>().align());
+
+            let expected_bytes =
+                TheTlsf::size_of_allocation(ptr1, 8) + TheTlsf::size_of_allocation(ptr2, 1);
+            drop(tlsf);
+
+            assert_eq!(REPORTED_COUNT.load(Ordering::Relaxed), 2);
+            assert_eq!(REPORTED_BYTES.load(Ordering::Relaxed), expected_bytes);
+        }
+    }
+
+    #[test]
+    fn no_leak() {
+        let mut tlsf = new_tlsf();
+        tlsf.set_leak_reporter(|_| panic!("no leaks expected"));
+        let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+        let ptr = unsafe { tlsf.reallocate(ptr, Layout::new::<[u64; 64]>()) }.unwrap();
+        let ptr2 = tlsf.allocate(Layout::new::<u64>()).unwrap();
+        unsafe {
+            tlsf.deallocate(ptr, Layout::new::<u64>().align());
+            tlsf.deallocate_unknown_align(ptr2);
+        }
+    }
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn counters() {
+    let mut tlsf: Tlsf<'static, u32, u32, 20, 32> = Tlsf::new();
+    let pool = Box::leak(Box::new([MaybeUninit::uninit(); 65536]));
+    tlsf.insert_free_block(pool);
+    assert_eq!(tlsf.counters(), TlsfCounters::default());
+
+    let ptrs: Vec<_> = (0..4)
+        .map(|_| tlsf.allocate(Layout::new::<u64>()).unwrap())
+        .collect();
+    assert!(tlsf.allocate(Layout::new::<[u8; 1 << 20]>()).is_none());
+
+    unsafe {
+        // In-place reallocation
+        let ptr0 = tlsf
+            .reallocate(ptrs[0], Layout::from_size_align(4, 8).unwrap())
+            .unwrap();
+        assert_eq!(ptr0, ptrs[0]);
+
+        // Moving reallocation
+        let ptr1 = tlsf
+            .reallocate(ptrs[1], Layout::new::<[u64; 64]>())
+            .unwrap();
+        assert_ne!(ptr1, ptrs[1]);
+
+        // Failed reallocation
+        assert!(tlsf
+            .reallocate(ptr1, Layout::new::<[u64; 1 << 17]>())
+            .is_none());
+
+        tlsf.deallocate(ptr0, 8);
+        tlsf.deallocate(ptr1, 8);
+        tlsf.deallocate(ptrs[2], 8);
+    }
+
+    let counters = tlsf.counters();
+    assert_eq!(counters.allocations, 4);
+    assert_eq!(counters.deallocations, 3);
+    assert_eq!(counters.reallocation_moves, 1);
+    assert_eq!(counters.failed_allocations, 2);
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn peak_in_use() {
+    type TheTlsf = Tlsf<'static, u32, u32, 20, 32>;
+    let mut tlsf = TheTlsf::new();
+    let pool = Box::leak(Box::new([MaybeUninit::uninit(); 65536]));
+    tlsf.insert_free_block(pool);
+    assert_eq!((tlsf.in_use(), tlsf.peak_in_use()), (0, 0));
+
+    unsafe {
+        let ptr1 = tlsf.allocate(Layout::new::<[u8; 1000]>()).unwrap();
+        let ptr2 = tlsf.allocate(Layout::new::<[u64; 100]>()).unwrap();
+        let size1 = TheTlsf::size_of_allocation(ptr1, 1);
+        let size2 = TheTlsf::size_of_allocation(ptr2, 8);
+        assert_eq!(tlsf.in_use(), size1 + size2);
+
+        // A moving reallocation holds both memory blocks for a moment
+        let ptr1 = tlsf.reallocate(ptr1, Layout::new::<[u8; 3000]>()).unwrap();
+        let new_size1 = TheTlsf::size_of_allocation(ptr1, 1);
+        assert_eq!(tlsf.in_use(), new_size1 + size2);
+        assert_eq!(tlsf.peak_in_use(), size1 + new_size1 + size2);
+
+        tlsf.deallocate(ptr1, 1);
+        assert_eq!(tlsf.in_use(), size2);
+        assert_eq!(tlsf.peak_in_use(), size1 + new_size1 + size2);
+
+        tlsf.reset_peak();
+        assert_eq!(tlsf.peak_in_use(), size2);
+
+        // An in-place reallocation
+        let ptr2 = tlsf.reallocate(ptr2, Layout::new::<[u64; 10]>()).unwrap();
+        let new_size2 = TheTlsf::size_of_allocation(ptr2, 8);
+        assert_eq!(tlsf.in_use(), new_size2);
+        assert_eq!(tlsf.peak_in_use(), size2);
+
+        tlsf.deallocate(ptr2, 8);
+    }
+    assert_eq!(tlsf.in_use(), 0);
+}
+
+#[cfg(feature = "allocation-histogram")]
+#[test]
+fn allocation_histogram() {
+    type TheTlsf = Tlsf<'static, u32, u32, 20, 32>;
+    let mut tlsf = TheTlsf::new();
+    let pool = Box::leak(Box::new([MaybeUninit::uninit(); 65536]));
+    tlsf.insert_free_block(pool);
+
+    let size_class = |size: usize| {
+        TheTlsf::map_floor(
+            (size + mem::size_of::<UsedBlockHdr>() + GRANULARITY - 1) & !(GRANULARITY - 1),
+        )
+        .unwrap()
+    };
+
+    let mut expected = [[0; 32]; 20];
+    let mut ptrs = Vec::new();
+    for &size in &[0, 1, 100, 100, 1000, 5000, 1 << 20] {
+        let (fl, sl) = size_class(size);
+        expected[fl][sl] += 1;
+        ptrs.extend(tlsf.allocate(Layout::from_size_align(size, 1).unwrap()));
+    }
+    assert_eq!(tlsf.allocation_histogram(), expected);
+
+    // Reallocations are counted by their new sizes
+    let ptr = unsafe { tlsf.reallocate(ptrs[2], Layout::new::<[u8; 2000]>()) }.unwrap();
+    let (fl, sl) = size_class(2000);
+    expected[fl][sl] += 1;
+    assert_eq!(tlsf.allocation_histogram(), expected);
+
+    // Requests too large for any size class aren't counted
+    assert!(tlsf
+        .allocate(Layout::from_size_align(usize::MAX / 2, 1).unwrap())
+        .is_none());
+    assert_eq!(tlsf.allocation_histogram(), expected);
+
+    unsafe { tlsf.deallocate(ptr, 1) };
+}
+
+#[cfg(feature = "pool-stats")]
+#[test]
+fn pool_usage() {
+    let mut tlsf: Tlsf<'static, u32, u32, 20, 32> = Tlsf::new();
+    let banks = [
+        Box::leak(Box::new([MaybeUninit::uninit(); 4096])),
+        Box::leak(Box::new([MaybeUninit::uninit(); 4096])),
+    ];
+    let [bank0, bank1] = banks;
+    tlsf.insert_free_block(bank0);
+    let layout = Layout::from_size_align(3000, 8).unwrap();
+    let ptr0 = tlsf.allocate(layout).unwrap();
+    tlsf.insert_free_block(bank1);
+    let ptr1 = tlsf.allocate(layout).unwrap();
+    let size = unsafe { Tlsf::<'static, u32, u32, 20, 32>::size_of_allocation(ptr1, 8) };
+
+    let usage: Vec<_> = tlsf.pool_usage().collect();
+    assert_eq!(usage.len(), 2);
+    for (usage, ptr) in usage.iter().zip([ptr0, ptr1]) {
+        let offset = (ptr.as_ptr() as usize).wrapping_sub(usage.pool.as_ptr() as *mut u8 as usize);
+        assert!(offset < nonnull_slice_len(usage.pool));
+        assert_eq!(usage.in_use, size);
+        assert_eq!(usage.peak_in_use, size);
+    }
+
+    // The peak of each memory pool is kept until reset
+    unsafe { tlsf.deallocate(ptr1, 8) };
+    let usage: Vec<_> = tlsf
+        .pool_usage()
+        .map(|u| (u.in_use, u.peak_in_use))
+        .collect();
+    assert_eq!(usage, [(size, size), (0, size)]);
+    tlsf.reset_peak();
+    let usage: Vec<_> = tlsf
+        .pool_usage()
+        .map(|u| (u.in_use, u.peak_in_use))
+        .collect();
+    assert_eq!(usage, [(size, size), (0, 0)]);
+
+    unsafe { tlsf.deallocate(ptr0, 8) };
+}
+
+#[cfg(feature = "timing")]
+#[test]
+fn timing() {
+    let mut tlsf: Tlsf<'static, u32, u32, 20, 32> = Tlsf::new();
+    let pool = Box::leak(Box::new([MaybeUninit::uninit(); 65536]));
+    tlsf.insert_free_block(pool);
+    assert_eq!(tlsf.timing(), TlsfTiming::default());
+    assert_eq!(tlsf.timing().allocate.mean(), None);
+
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    let ptrs: Vec<_> = (0..10).map(|_| tlsf.allocate(layout).unwrap()).collect();
+    assert!(tlsf
+        .allocate(Layout::from_size_align(1 << 20, 8).unwrap())
+        .is_none());
+    for &ptr in &ptrs[..4] {
+        unsafe { tlsf.deallocate(ptr, 8) };
+    }
+
+    let timing = tlsf.timing();
+    assert_eq!(timing.allocate.count(), 11);
+    assert_eq!(timing.deallocate.count(), 4);
+    for latency in [timing.allocate, timing.deallocate] {
+        let (min, mean, max) = (
+            latency.min().unwrap(),
+            latency.mean().unwrap(),
+            latency.max().unwrap(),
+        );
+        assert!(min <= mean && mean <= max, "{:?}", latency);
+        #[cfg(target_arch = "x86_64")]
+        assert_ne!(max, 0);
+    }
+
+    tlsf.reset_timing();
+    assert_eq!(tlsf.timing(), TlsfTiming::default());
+
+    for &ptr in &ptrs[4..] {
+        unsafe { tlsf.deallocate(ptr, 8) };
+    }
+    assert_eq!(tlsf.timing().allocate.count(), 0);
+    assert_eq!(tlsf.timing().deallocate.count(), 6);
+}
+
+#[cfg(feature = "observer")]
+#[test]
+fn observer() {
+    use crate::AllocObserver;
+    use std::{ptr::NonNull, sync::Mutex};
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Alloc(NonNull<u8>, Layout),
+        Dealloc(NonNull<u8>, usize),
+        Realloc(NonNull<u8>, NonNull<u8>, Layout),
+        PoolAdded(NonNull<[u8]>),
+    }
+    // Safety: The pointers are only compared
+    unsafe impl Send for Event {}
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<Event>>);
+
+    impl AllocObserver for Recorder {
+        fn on_alloc(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.0.lock().unwrap().push(Event::Alloc(ptr, layout));
+        }
+        fn on_dealloc(&self, ptr: NonNull<u8>, size: usize) {
+            self.0.lock().unwrap().push(Event::Dealloc(ptr, size));
+        }
+        fn on_realloc(&self, old_ptr: NonNull<u8>, new_ptr: NonNull<u8>, new_layout: Layout) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(Event::Realloc(old_ptr, new_ptr, new_layout));
+        }
+        fn on_pool_added(&self, pool: NonNull<[u8]>) {
+            self.0.lock().unwrap().push(Event::PoolAdded(pool));
+        }
+    }
+
+    let recorder: &'static Recorder = Box::leak(Box::default());
+    let take = || std::mem::take(&mut *recorder.0.lock().unwrap());
+
+    let mut tlsf: Tlsf<'static, u32, u32, 20, 32> = Tlsf::new();
+    tlsf.set_observer(recorder);
+
+    let pool = Box::leak(Box::new([MaybeUninit::<u8>::uninit(); 65536]));
+    let pool_start = NonNull::new(pool.as_mut_ptr() as *mut u8).unwrap();
+    let pool_len =
+        unsafe { tlsf.insert_free_block_ptr(nonnull_slice_from_raw_parts(pool_start, 32768)) }
+            .unwrap()
+            .get();
+    match &take()[..] {
+        // The reported region excludes the alignment padding
+        [Event::PoolAdded(added)] => assert_eq!(
+            added.as_ptr() as *mut u8 as usize + added.len(),
+            pool_start.as_ptr() as usize + pool_len
+        ),
+        events => panic!("unexpected events: {:?}", events),
+    }
+
+    // Extending the pool reports the appended region
+    let appended_len = unsafe {
+        tlsf.append_free_block_ptr(nonnull_slice_from_raw_parts(
+            NonNull::new(pool_start.as_ptr().wrapping_add(32768)).unwrap(),
+            32768,
+        ))
+    };
+    assert_ne!(appended_len, 0);
+    match &take()[..] {
+        [Event::PoolAdded(added)] => {
+            assert_eq!(
+                added.as_ptr() as *mut u8,
+                pool_start.as_ptr().wrapping_add(32768)
+            );
+            assert_eq!(added.len(), appended_len);
+        }
+        events => panic!("unexpected events: {:?}", events),
+    }
+
+    let layout = Layout::from_size_align(100, 8).unwrap();
+    let ptr = tlsf.allocate(layout).unwrap();
+    assert_eq!(take(), [Event::Alloc(ptr, layout)]);
+
+    let new_layout = Layout::from_size_align(1000, 8).unwrap();
+    let new_ptr = unsafe { tlsf.reallocate(ptr, new_layout) }.unwrap();
+    assert_eq!(take(), [Event::Realloc(ptr, new_ptr, new_layout)]);
+
+    let size = unsafe { Tlsf::<'static, u32, u32, 20, 32>::size_of_allocation(new_ptr, 8) };
+    unsafe { tlsf.deallocate(new_ptr, 8) };
+    assert_eq!(take(), [Event::Dealloc(new_ptr, size)]);
+
+    // Failed allocations aren't reported
+    assert!(tlsf
+        .allocate(Layout::from_size_align(usize::MAX / 2, 1).unwrap())
+        .is_none());
+    assert_eq!(take(), []);
+
+    // A double free is detected before the observer reads the header
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| unsafe {
+        tlsf.deallocate(new_ptr, 8)
+    }));
+    assert!(result.is_err());
+    assert_eq!(take(), []);
+}
+
+#[cfg(feature = "track-allocations")]
+mod allocation_tracking {
+    use super::*;
+
+    type TheTlsf = Tlsf<'static, u32, u32, 20, 32>;
+
+    fn new_tlsf() -> TheTlsf {
+        let mut tlsf = TheTlsf::new();
+        let pool = Box::leak(Box::new([MaybeUninit::uninit(); 65536]));
+        tlsf.insert_free_block(pool);
+        tlsf
+    }
+
+    #[test]
+    fn disabled() {
+        let mut tlsf = new_tlsf();
+        let ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+        assert_eq!(unsafe { tlsf.allocation_site(ptr, 8) }, None);
+
+        // Allocations made before enabling aren't tracked
+        tlsf.enable_allocation_tracking();
+        assert_eq!(unsafe { tlsf.allocation_site(ptr, 8) }, None);
+        unsafe { tlsf.deallocate(ptr, 8) };
+    }
+
+    #[test]
+    fn sites() {
+        let mut tlsf = new_tlsf();
+        tlsf.enable_allocation_tracking();
+
+        let ptr1 = tlsf.allocate(Layout::new::<u64>()).unwrap();
+        let line1 = line!() - 1;
+        let layout2 = Layout::from_size_align(100, 64).unwrap();
+        let ptr2 = tlsf.allocate(layout2).unwrap();
+        let line2 = line!() - 1;
+        let ptr3 = tlsf.allocate(Layout::new::<u64>()).unwrap();
+
+        unsafe {
+            assert_eq!(tlsf.allocation_site(ptr1, 8).unwrap().line(), line1);
+            assert_eq!(tlsf.allocation_site(ptr2, 64).unwrap().line(), line2);
+            assert_eq!(tlsf.allocation_site(ptr1, 8).unwrap().file(), file!());
+
+            // In-place reallocation
+            let layout2 = Layout::from_size_align(50, 64).unwrap();
+            let ptr2 = tlsf.reallocate(ptr2, layout2).unwrap();
+            let line2 = line!() - 1;
+            assert_eq!(tlsf.allocation_site(ptr2, 64).unwrap().line(), line2);
+
+            // Moving reallocation
+            let ptr1 = tlsf.reallocate(ptr1, Layout::new::<[u64; 64]>()).unwrap();
+            let line1 = line!() - 1;
+            assert_eq!(tlsf.allocation_site(ptr1, 8).unwrap().line(), line1);
+
+            // A deallocated and reused memory block doesn't keep the old site
+            tlsf.deallocate(ptr3, 8);
+            let ptr3 = tlsf.allocate(Layout::new::<u64>()).unwrap();
+            let line3 = line!() - 1;
+            assert_eq!(tlsf.allocation_site(ptr3, 8).unwrap().line(), line3);
+
+            tlsf.deallocate(ptr1, 8);
+            tlsf.deallocate(ptr2, 64);
+            tlsf.deallocate(ptr3, 8);
+        }
+        assert!(tlsf.allocation_sites.as_ref().unwrap().is_empty());
+    }
+
+    #[cfg(feature = "unstable")]
+    #[test]
+    fn iter_blocks() {
+        let mut tlsf = TheTlsf::new();
+        let pool = Box::leak(Box::new(Align([MaybeUninit::uninit(); 65536])));
+        let pool = NonNull::new(&mut pool.0 as *mut [MaybeUninit<u8>] as *mut [u8]).unwrap();
+        let pool_len = unsafe { tlsf.insert_free_block_ptr(pool) }.unwrap().get();
+        let pool = nonnull_slice_from_raw_parts(pool.cast::<u8>(), pool_len);
+        tlsf.enable_allocation_tracking();
+
+        let _ptr = tlsf.allocate(Layout::new::<u64>()).unwrap();
+        let line = line!() - 1;
+
+        let sites: Vec<_> = unsafe { tlsf.iter_blocks(pool) }
+            .map(|block_info| (block_info.is_occupied(), block_info.allocation_site()))
+            .collect();
+        assert_eq!(sites.len(), 2);
+        assert_eq!(sites[0].0, true);
+        assert_eq!(sites[0].1.unwrap().line(), line);
+        assert_eq!(sites[1], (false, None));
+    }
+}
+
+#[cfg(feature = "oom-report")]
+#[test]
+fn oom_report() {
+    use core::ptr::addr_of_mut;
+
+    // Only used by this test, on this thread
+    static mut REPORTS: Vec<OomReport> = Vec::new();
+    fn on_oom(report: OomReport) {
+        unsafe { (*addr_of_mut!(REPORTS)).push(report) };
+    }
+    let take = || std::mem::take(unsafe { &mut *addr_of_mut!(REPORTS) });
+
+    let mut tlsf: Tlsf<'static, u32, u32, 20, 32> = Tlsf::new();
+    tlsf.set_oom_hook(on_oom);
+    let pool = Box::leak(Box::new([MaybeUninit::uninit(); 4096]));
+    tlsf.insert_free_block(pool);
+
+    // Fragment the heap
+    let small = Layout::from_size_align(200, 8).unwrap();
+    let mut ptrs = Vec::new();
+    while let Some(ptr) = tlsf.allocate(small) {
+        ptrs.push(ptr);
+    }
+    let reports = take();
+    assert_eq!(reports.len(), 1);
+    // Depending on the pool's alignment, a free block too small for `small`
+    // might be left after the last allocation. It's merged with the last
+    // allocation if that's deallocated below.
+    let num_remnants = reports[0].free_blocks;
+    assert!(num_remnants <= 1);
+    let last_freed = ptrs.len() % 2 == 1;
+    let expected_free_blocks = (ptrs.len() + 1) / 2 + if last_freed { 0 } else { num_remnants };
+    for ptr in ptrs.iter().step_by(2) {
+        unsafe { tlsf.deallocate(*ptr, small.align()) };
+    }
+
+    let large = Layout::from_size_align(1000, 8).unwrap();
+    assert!(tlsf.allocate(large).is_none());
+    let reports = take();
+    assert_eq!(reports.len(), 1);
+    let report = reports[0];
+    log::debug!("{}", report);
+    assert_eq!(report, tlsf.oom_report(large));
+    assert_eq!((report.size, report.align), (1000, 8));
+    assert!(report.size_class.is_some());
+    assert_eq!(report.free_blocks, expected_free_blocks);
+    assert!(report.largest_free_block < 1000);
+    assert!(report.free_bytes >= 1000);
+    assert!(report.fragmentation_percent() >= 50);
+    assert!(report
+        .to_string()
+        .starts_with("failed to allocate 1000 bytes aligned to 8: no free blocks in size class"));
+
+    // Reallocation failures are reported as well
+    let ptr = ptrs[1];
+    assert!(unsafe { tlsf.reallocate(ptr, large) }.is_none());
+    assert_eq!(take(), [tlsf.oom_report(large)]);
+
+    // A request larger than the largest size class
+    let huge = Layout::from_size_align(usize::MAX / 4, 8).unwrap();
+    assert!(tlsf.allocate(huge).is_none());
+    let reports = take();
+    assert_eq!(reports[0].size_class, None);
+    assert!(reports[0]
+        .to_string()
+        .contains("larger than the largest size class"));
+
+    // No more reports after unregistering the hook
+    tlsf.clear_oom_hook();
+    assert!(tlsf.allocate(large).is_none());
+    assert!(take().is_empty());
+
+    for ptr in ptrs.iter().skip(1).step_by(2) {
+        unsafe { tlsf.deallocate(*ptr, small.align()) };
+    }
+}
@@ -0,0 +1,24 @@
+//! Operation trace logging (the `trace` and `trace-defmt` features)
+//!
+//! The records only take integer arguments (pointers are formatted as
+//! addresses, a failed allocation is reported as `0x0`, and an unknown
+//! alignment as `0`) so that the same format strings are accepted by both
+//! [`log`] and [`defmt`]. A wrapper (e.g., [`FlexTlsf`]) emits its own record
+//! in addition to those of the allocator it delegates to.
+//!
+//! [`FlexTlsf`]: crate::FlexTlsf
+//! [`log`]: https://crates.io/crates/log
+//! [`defmt`]: https://crates.io/crates/defmt
+#![allow(unused_macros)]
+
+/// Emit a trace record of an allocator operation through every enabled
+/// logging backend. Expands to nothing if neither `trace` nor `trace-defmt`
+/// is enabled, in which case the arguments aren't evaluated.
+macro_rules! trace_op {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "trace")]
+        log::trace!($($arg)*);
+        #[cfg(feature = "trace-defmt")]
+        defmt::trace!($($arg)*);
+    }};
+}
@@ -1,5 +1,8 @@
 use core::{mem::MaybeUninit, ptr::NonNull};
 
+#[cfg(unix)]
+use crate::flex::SourceError;
+
 /// Polyfill for <https://github.com/rust-lang/rust/issues/71941>
 #[inline]
 pub fn nonnull_slice_from_raw_parts<T>(ptr: NonNull<T>, len: usize) -> NonNull<[T]> {
@@ -26,3 +29,44 @@ pub fn nonnull_slice_start<T>(ptr: NonNull<[T]>) -> NonNull<T> {
 pub fn nonnull_slice_end<T>(ptr: NonNull<[T]>) -> *mut T {
     (ptr.as_ptr() as *mut T).wrapping_add(nonnull_slice_len(ptr))
 }
+
+/// Get a pointer with the address `addr` and the provenance of `ptr`.
+///
+/// With the `strict-provenance` feature, this is `<*mut T>::with_addr`, so
+/// that Miri accepts the result under `-Zmiri-strict-provenance`. Otherwise,
+/// this is an integer-to-pointer cast, which doesn't require Rust 1.84.
+#[inline(always)]
+#[cfg_attr(feature = "strict-provenance", allow(clippy::incompatible_msrv))]
+pub fn ptr_with_addr<T>(ptr: *mut T, addr: usize) -> *mut T {
+    #[cfg(feature = "strict-provenance")]
+    {
+        ptr.with_addr(addr)
+    }
+    #[cfg(not(feature = "strict-provenance"))]
+    {
+        let _ = ptr;
+        addr as *mut T
+    }
+}
+
+/// Apply `f` to the address of `ptr`, keeping its provenance (see
+/// [`ptr_with_addr`]).
+#[inline(always)]
+pub fn ptr_map_addr<T>(ptr: *mut T, f: impl FnOnce(usize) -> usize) -> *mut T {
+    ptr_with_addr(ptr, f(ptr as usize))
+}
+
+/// Get the error code of the last failed system call.
+#[cfg(unix)]
+#[inline]
+pub fn last_os_error() -> SourceError {
+    cfg_if::cfg_if! {
+        if #[cfg(any(target_os = "linux", target_os = "android"))] {
+            SourceError::Os(unsafe { *libc::__errno_location() })
+        } else if #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))] {
+            SourceError::Os(unsafe { *libc::__error() })
+        } else {
+            SourceError::Exhausted
+        }
+    }
+}
@@ -6,7 +6,11 @@ use std::{
 };
 
 #[global_allocator]
-#[cfg(any(all(target_arch = "wasm32", not(target_feature = "atomics")), unix))]
+#[cfg(any(
+    all(target_arch = "wasm32", not(target_feature = "atomics")),
+    unix,
+    windows
+))]
 static A: rlsf::SmallGlobalTlsf = rlsf::SmallGlobalTlsf::new();
 
 #[test]
@@ -0,0 +1,110 @@
+//! Model-checks the concurrent allocators with loom. Run with
+//! `RUSTFLAGS="--cfg loom" cargo test --release --test loom`.
+#![cfg(loom)]
+use loom::{sync::Arc, thread};
+use rlsf::{ConcurrentTlsf, EpochTlsf, SpinLock, SyncTlsf};
+use std::{
+    alloc::{GlobalAlloc, Layout},
+    mem::MaybeUninit,
+};
+
+type TheSyncTlsf = SyncTlsf<'static, u16, u16, 12, 16, SpinLock>;
+
+fn new_pool(size: usize) -> &'static mut [MaybeUninit<u8>] {
+    Box::leak(vec![MaybeUninit::uninit(); size].into_boxed_slice())
+}
+
+#[test]
+fn sync_tlsf() {
+    loom::model(|| {
+        let tlsf = Arc::new(TheSyncTlsf::new());
+        tlsf.lock().insert_free_block(new_pool(1024));
+        let layout = Layout::new::<u64>();
+
+        let threads: Vec<_> = (0..2)
+            .map(|i| {
+                let tlsf = Arc::clone(&tlsf);
+                thread::spawn(move || {
+                    let ptr = tlsf.lock().allocate(layout).unwrap();
+                    unsafe { ptr.as_ptr().cast::<u64>().write(i) };
+                    thread::yield_now();
+                    assert_eq!(unsafe { ptr.as_ptr().cast::<u64>().read() }, i);
+                    unsafe { tlsf.lock().deallocate(ptr, layout.align()) };
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    });
+}
+
+#[test]
+fn concurrent_tlsf() {
+    loom::model(|| {
+        let tlsf = Arc::new(ConcurrentTlsf::<'static, u16, u16, 12, 16, SpinLock>::new());
+        tlsf.lock().insert_free_block(new_pool(1024));
+        let layout = Layout::new::<u64>();
+
+        let other = {
+            let tlsf = Arc::clone(&tlsf);
+            thread::spawn(move || unsafe {
+                let ptr = tlsf.alloc(layout);
+                assert!(!ptr.is_null());
+                tlsf.dealloc(ptr, layout);
+            })
+        };
+        unsafe {
+            let ptr = tlsf.alloc(layout);
+            assert!(!ptr.is_null());
+            tlsf.dealloc(ptr, layout);
+        }
+        other.join().unwrap();
+    });
+}
+
+#[test]
+fn concurrent_tlsf_stats() {
+    loom::model(|| {
+        let tlsf = Arc::new(ConcurrentTlsf::<'static, u16, u16, 12, 16, SpinLock>::new());
+        tlsf.lock().insert_free_block(new_pool(4096));
+        let layout = Layout::from_size_align(2048, 8).unwrap();
+
+        let other = {
+            let tlsf = Arc::clone(&tlsf);
+            thread::spawn(move || unsafe {
+                let ptr = tlsf.alloc(layout);
+                assert!(!ptr.is_null());
+                tlsf.dealloc(ptr, layout);
+            })
+        };
+        let stats = tlsf.stats();
+        assert_eq!(stats.bytes_allocated == 0, stats.num_blocks_allocated == 0);
+        assert!(stats.bytes_allocated <= stats.peak_bytes_allocated);
+        other.join().unwrap();
+    });
+}
+
+#[test]
+fn epoch_tlsf() {
+    loom::model(|| {
+        let tlsf = Arc::new(EpochTlsf::<'static, 2, u16, u16, 12, 16, SpinLock>::new());
+        tlsf.lock().insert_free_block(new_pool(1024));
+        let layout = Layout::new::<u64>();
+        let ptr = tlsf.allocate(layout).unwrap();
+
+        let reader = {
+            let tlsf = Arc::clone(&tlsf);
+            thread::spawn(move || {
+                let mut handle = tlsf.register().unwrap();
+                let _guard = handle.pin();
+                tlsf.try_advance();
+            })
+        };
+
+        let mut handle = tlsf.register().unwrap();
+        unsafe { handle.pin().defer_deallocate(ptr, layout.align()) }.unwrap();
+        drop(handle);
+        reader.join().unwrap();
+    });
+}
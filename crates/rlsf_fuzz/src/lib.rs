@@ -0,0 +1,376 @@
+//! A fuzzing harness for [`::rlsf`].
+//!
+//! [`fuzz_tlsf`] and [`fuzz_flex_tlsf`] interpret arbitrary bytes as a
+//! sequence of allocations, deallocations, and reallocations and run it on a
+//! [`Tlsf`] or a [`FlexTlsf`] of any configuration. Every result is checked
+//! against a shadow allocator, which tracks the state of every byte: an
+//! allocation must be properly aligned, lie in a memory pool, and not overlap
+//! another live allocation, and its contents must survive until it's
+//! deallocated. A violation causes a panic, which a fuzzer reports as a
+//! crash.
+//!
+//! The memory pools of a `FlexTlsf` are provided by the given [`FlexSource`],
+//! whose results are checked as well, so this can be used to fuzz custom
+//! `FlexSource` implementations along with the parameters of `FlexTlsf`.
+//! A [cargo-fuzz] target looks like this:
+//!
+//! ```text
+//! #![no_main]
+//! use rlsf::GlobalAllocAsFlexSource;
+//!
+//! libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+//!     let source = GlobalAllocAsFlexSource::<std::alloc::System, 1024>(std::alloc::System);
+//!     rlsf_fuzz::fuzz_flex_tlsf::<_, u16, u16, 12, 16>(source, data);
+//! });
+//! ```
+//!
+//! # Input Format
+//!
+//! The input starts with a header (see [`fuzz_tlsf`] and [`fuzz_flex_tlsf`]),
+//! which is followed by a sequence of operations. The first byte of each
+//! operation modulo 8 determines its kind:
+//!
+//!  - `0..=2`: Allocate. Followed by three bytes representing the size (a
+//!    24-bit fixed-point fraction of the maximum allocation size) and a byte
+//!    representing the alignment (`1 << (x % 6)`).
+//!  - `3..=5`: Deallocate. Followed by a byte selecting a live allocation.
+//!  - `6..=7`: Reallocate. Followed by a byte selecting a live allocation and
+//!    three bytes representing the new size.
+//!
+//! The input may end at any point. The remaining allocations are deallocated
+//! at the end.
+//!
+//! [cargo-fuzz]: https://github.com/rust-fuzz/cargo-fuzz
+use rlsf::{int::BinInteger, FlexSource, FlexTlsf, Tlsf};
+use std::{alloc::Layout, mem::MaybeUninit, ptr::NonNull};
+
+mod shadow;
+mod source;
+
+use self::{shadow::ShadowAllocator, source::TrackingFlexSource};
+
+/// The size of the memory pool of [`fuzz_tlsf`]
+const TLSF_POOL_LEN: usize = 65536;
+
+/// Run the operations encoded in `data` on a [`Tlsf`] with the specified
+/// parameters.
+///
+/// The header consists of a byte representing the start offset of the memory
+/// pool (modulo 64), two little-endian bytes representing its size (modulo
+/// 65473), and two little-endian bytes representing the maximum allocation
+/// size. Additionally, [`Tlsf::check_integrity`] is called after each
+/// operation.
+///
+/// # Panics
+///
+/// This function panics if it detects an inconsistency.
+///
+/// # Examples
+///
+/// ```
+/// rlsf_fuzz::fuzz_tlsf::<u16, u16, 12, 16>(&[
+///     0, 0x00, 0x80, 0x00, 0x01, // header
+///     0, 0x00, 0x00, 0x80, 3,    // allocate 128 bytes aligned to 8 bytes
+///     6, 0, 0x00, 0x00, 0xc0,    // reallocate it to 192 bytes
+///     3, 0,                      // deallocate it
+/// ]);
+/// ```
+pub fn fuzz_tlsf<
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    const FLLEN: usize,
+    const SLLEN: usize,
+>(
+    data: &[u8],
+) {
+    let (pool_start, pool_size, max_alloc_size, bytecode) = match data {
+        [pool_start, s0, s1, m0, m1, bytecode @ ..] => (
+            *pool_start as usize % 64,
+            u16::from_le_bytes([*s0, *s1]) as usize % (TLSF_POOL_LEN - 63),
+            u16::from_le_bytes([*m0, *m1]) as usize,
+            bytecode,
+        ),
+        _ => return,
+    };
+
+    let mut pool = vec![MaybeUninit::<u8>::uninit(); TLSF_POOL_LEN];
+    let pool_ptr = pool[pool_start..].as_mut_ptr() as *mut u8;
+    log::trace!("pool = {:p}: [u8; {}]", pool_ptr, pool_size);
+
+    let mut target = TlsfTarget {
+        tlsf: Tlsf::<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>::new(),
+        sa: ShadowAllocator::new(),
+        pool: None,
+    };
+
+    // Safety: `pool` outlives `target.tlsf`
+    if let Some(pool_len) = unsafe {
+        target.tlsf.insert_free_block_ptr(
+            NonNull::new(std::ptr::slice_from_raw_parts_mut(pool_ptr, pool_size)).unwrap(),
+        )
+    } {
+        let pool =
+            NonNull::new(std::ptr::slice_from_raw_parts_mut(pool_ptr, pool_len.get())).unwrap();
+        log::trace!("pool (actual) = {:?}", pool);
+        target.sa.insert_free_block(pool.as_ptr());
+        target.pool = Some(pool);
+    }
+
+    run(&mut target, max_alloc_size, bytecode);
+    drop(target);
+    drop(pool);
+}
+
+/// Run the operations encoded in `data` on a [`FlexTlsf`] with the specified
+/// parameters and `source`.
+///
+/// The header consists of two little-endian bytes representing the maximum
+/// allocation size. Additionally, `source`'s results are checked for
+/// consistency, and it's checked that all memory pools have been returned to
+/// `source` (if [`FlexSource::supports_dealloc`] is `true`) when the
+/// `FlexTlsf` is dropped.
+///
+/// # Panics
+///
+/// This function panics if it detects an inconsistency.
+///
+/// # Examples
+///
+/// ```
+/// use rlsf::GlobalAllocAsFlexSource;
+/// use std::alloc::System;
+///
+/// rlsf_fuzz::fuzz_flex_tlsf::<_, u16, u16, 12, 16>(
+///     GlobalAllocAsFlexSource::<_, 1024>(System),
+///     &[
+///         0x00, 0x01,             // header
+///         0, 0x00, 0x00, 0x80, 3, // allocate 128 bytes aligned to 8 bytes
+///         6, 0, 0x00, 0x00, 0xc0, // reallocate it to 192 bytes
+///         3, 0,                   // deallocate it
+///     ],
+/// );
+/// ```
+pub fn fuzz_flex_tlsf<
+    Source: FlexSource,
+    FLBitmap: BinInteger,
+    SLBitmap: BinInteger,
+    const FLLEN: usize,
+    const SLLEN: usize,
+>(
+    source: Source,
+    data: &[u8],
+) {
+    let (max_alloc_size, bytecode) = match data {
+        [m0, m1, bytecode @ ..] => (u16::from_le_bytes([*m0, *m1]) as usize, bytecode),
+        _ => return,
+    };
+
+    let mut target =
+        FlexTlsf::<_, FLBitmap, SLBitmap, FLLEN, SLLEN>::new(TrackingFlexSource::new(source));
+    run(&mut target, max_alloc_size, bytecode);
+}
+
+/// An allocator driven by [`run`]
+trait Target {
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>>;
+
+    /// # Safety
+    ///
+    /// See [`Tlsf::deallocate`].
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, align: usize);
+
+    /// # Safety
+    ///
+    /// See [`Tlsf::reallocate`].
+    unsafe fn reallocate(&mut self, ptr: NonNull<u8>, new_layout: Layout) -> Option<NonNull<u8>>;
+
+    fn sa(&mut self) -> &mut ShadowAllocator;
+
+    /// Check the allocator's internal consistency.
+    fn check(&mut self) {}
+}
+
+struct TlsfTarget<'pool, FLBitmap, SLBitmap, const FLLEN: usize, const SLLEN: usize> {
+    tlsf: Tlsf<'pool, FLBitmap, SLBitmap, FLLEN, SLLEN>,
+    sa: ShadowAllocator,
+    /// The memory pool inserted to `tlsf`
+    pool: Option<NonNull<[u8]>>,
+}
+
+impl<FLBitmap: BinInteger, SLBitmap: BinInteger, const FLLEN: usize, const SLLEN: usize> Target
+    for TlsfTarget<'_, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        self.tlsf.allocate(layout)
+    }
+
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, align: usize) {
+        self.tlsf.deallocate(ptr, align)
+    }
+
+    unsafe fn reallocate(&mut self, ptr: NonNull<u8>, new_layout: Layout) -> Option<NonNull<u8>> {
+        self.tlsf.reallocate(ptr, new_layout)
+    }
+
+    fn sa(&mut self) -> &mut ShadowAllocator {
+        &mut self.sa
+    }
+
+    fn check(&mut self) {
+        let pools: Vec<_> = self.pool.into_iter().collect();
+        // Safety: `pools` is the memory pool inserted to `self.tlsf`
+        assert_eq!(unsafe { self.tlsf.check_integrity(&pools) }, Ok(()));
+    }
+}
+
+impl<
+        Source: FlexSource,
+        FLBitmap: BinInteger,
+        SLBitmap: BinInteger,
+        const FLLEN: usize,
+        const SLLEN: usize,
+    > Target for FlexTlsf<TrackingFlexSource<Source>, FLBitmap, SLBitmap, FLLEN, SLLEN>
+{
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        FlexTlsf::allocate(self, layout)
+    }
+
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, align: usize) {
+        FlexTlsf::deallocate(self, ptr, align)
+    }
+
+    unsafe fn reallocate(&mut self, ptr: NonNull<u8>, new_layout: Layout) -> Option<NonNull<u8>> {
+        FlexTlsf::reallocate(self, ptr, new_layout)
+    }
+
+    fn sa(&mut self) -> &mut ShadowAllocator {
+        // Safety: `FlexTlsf` doesn't care about `sa`
+        unsafe { &mut self.source_mut_unchecked().sa }
+    }
+}
+
+#[derive(Debug)]
+struct Alloc {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+/// Run the operations encoded in `bytecode` on `target` and deallocate the
+/// remaining allocations.
+fn run(target: &mut impl Target, max_alloc_size: usize, bytecode: &[u8]) {
+    let mut allocs = Vec::new();
+    run_inner(target, &mut allocs, max_alloc_size, bytecode);
+
+    for alloc in allocs {
+        log::trace!("dealloc {:?}", alloc);
+        verify_data(alloc.ptr, alloc.layout.size());
+        target.sa().deallocate(alloc.layout, alloc.ptr);
+        // Safety: `alloc` is a live allocation
+        unsafe { target.deallocate(alloc.ptr, alloc.layout.align()) };
+        target.check();
+    }
+}
+
+fn run_inner(
+    target: &mut impl Target,
+    allocs: &mut Vec<Alloc>,
+    max_alloc_size: usize,
+    bytecode: &[u8],
+) -> Option<()> {
+    let mut it = bytecode.iter().cloned();
+    loop {
+        match it.next()? % 8 {
+            0..=2 => {
+                let len = u32::from_le_bytes([it.next()?, it.next()?, it.next()?, 0]);
+                let len = ((len as u64 * max_alloc_size as u64) >> 24) as usize;
+                let align = 1 << (it.next()? % 6);
+                let layout = Layout::from_size_align(len, align).unwrap();
+                log::trace!("alloc {:?}", layout);
+
+                let ptr = target.allocate(layout);
+                log::trace!(" → {:?}", ptr);
+
+                if let Some(ptr) = ptr {
+                    allocs.push(Alloc { ptr, layout });
+                    target.sa().allocate(layout, ptr);
+
+                    // Fill it with dummy data
+                    fill_data(ptr, len);
+                }
+            }
+            3..=5 => {
+                let alloc_i = it.next()?;
+                if !allocs.is_empty() {
+                    let alloc = allocs.swap_remove(alloc_i as usize % allocs.len());
+                    log::trace!("dealloc {:?}", alloc);
+
+                    // Make sure the stored dummy data is not corrupted
+                    verify_data(alloc.ptr, alloc.layout.size());
+
+                    // Update `sa` first because `deallocate` might return the
+                    // memory block to the source
+                    target.sa().deallocate(alloc.layout, alloc.ptr);
+                    // Safety: `alloc` is a live allocation
+                    unsafe { target.deallocate(alloc.ptr, alloc.layout.align()) };
+                }
+            }
+            6..=7 => {
+                let alloc_i = it.next()?;
+                if !allocs.is_empty() {
+                    let len = u32::from_le_bytes([it.next()?, it.next()?, it.next()?, 0]);
+                    let len = ((len as u64 * max_alloc_size as u64) >> 24) as usize;
+
+                    let alloc_i = alloc_i as usize % allocs.len();
+                    let alloc = &mut allocs[alloc_i];
+                    log::trace!("realloc {:?} to {:?}", alloc, len);
+
+                    let new_layout = Layout::from_size_align(len, alloc.layout.align()).unwrap();
+
+                    target.sa().deallocate(alloc.layout, alloc.ptr);
+
+                    // Safety: `alloc` is a live allocation
+                    if let Some(ptr) = unsafe { target.reallocate(alloc.ptr, new_layout) } {
+                        log::trace!(" {:?} → {:?}", alloc.ptr, ptr);
+
+                        // Check and refill the dummy data
+                        verify_data(ptr, len.min(alloc.layout.size()));
+                        fill_data(ptr, len);
+
+                        alloc.ptr = ptr;
+                        alloc.layout = new_layout;
+                    } else {
+                        log::trace!(" {:?} → fail", alloc.ptr);
+                    }
+
+                    target.sa().allocate(alloc.layout, alloc.ptr);
+                }
+            }
+            _ => unreachable!(),
+        }
+
+        target.check();
+    }
+}
+
+fn fill_data(ptr: NonNull<u8>, len: usize) {
+    // Safety: `ptr` is an allocation of `len` bytes
+    let slice =
+        unsafe { std::slice::from_raw_parts_mut(ptr.as_ptr() as *mut MaybeUninit<u8>, len) };
+    for (i, p) in slice.iter_mut().enumerate() {
+        *p = MaybeUninit::new((i as u8).reverse_bits());
+    }
+}
+
+fn verify_data(ptr: NonNull<u8>, len: usize) {
+    // Safety: `ptr` is an allocation of `len` bytes filled by `fill_data`
+    let slice = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), len) };
+    for (i, p) in slice.iter().enumerate() {
+        assert_eq!(
+            *p,
+            (i as u8).reverse_bits(),
+            "the contents of the allocation at {:p} were corrupted at offset {}",
+            ptr,
+            i
+        );
+    }
+}
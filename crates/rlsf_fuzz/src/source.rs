@@ -0,0 +1,134 @@
+//! A [`FlexSource`] wrapper that reports memory pools to the shadow allocator
+use rlsf::{FlexSource, SourceError};
+use std::{mem::MaybeUninit, ptr::NonNull};
+
+use crate::shadow::ShadowAllocator;
+
+pub(crate) struct TrackingFlexSource<T: FlexSource> {
+    pub(crate) sa: ShadowAllocator,
+    inner: T,
+}
+
+impl<T: FlexSource> TrackingFlexSource<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        Self {
+            sa: ShadowAllocator::new(),
+            inner,
+        }
+    }
+}
+
+impl<T: FlexSource> Drop for TrackingFlexSource<T> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+
+        if self.inner.supports_dealloc() {
+            // All existing pools should have been removed by `FlexTlsf::drop`
+            self.sa.assert_no_pools();
+        }
+    }
+}
+
+unsafe impl<T: FlexSource> FlexSource for TrackingFlexSource<T> {
+    unsafe fn alloc(&mut self, min_size: usize) -> Option<NonNull<[u8]>> {
+        self.try_alloc(min_size).ok()
+    }
+
+    unsafe fn try_alloc(&mut self, min_size: usize) -> Result<NonNull<[u8]>, SourceError> {
+        log::trace!("FlexSource::try_alloc({:?})", min_size);
+        let range = self.inner.try_alloc(min_size)?;
+        log::trace!(" FlexSource::try_alloc(...) = {:?}", range);
+        assert!(
+            nonnull_slice_len(range) >= min_size,
+            "the source returned a memory block smaller than requested"
+        );
+        self.sa.insert_free_block(range.as_ptr());
+        Ok(range)
+    }
+
+    unsafe fn realloc_inplace_grow(
+        &mut self,
+        ptr: NonNull<[u8]>,
+        min_new_len: usize,
+    ) -> Option<usize> {
+        log::trace!("FlexSource::realloc_inplace_grow{:?}", (ptr, min_new_len));
+        let new_len = self.inner.realloc_inplace_grow(ptr, min_new_len)?;
+        log::trace!(" FlexSource::realloc_inplace_grow(...) = {:?}", new_len);
+        assert!(
+            new_len >= min_new_len,
+            "the source grew a memory block less than requested"
+        );
+        self.sa.append_free_block(std::ptr::slice_from_raw_parts(
+            nonnull_slice_end(ptr),
+            new_len - nonnull_slice_len(ptr),
+        ));
+        Some(new_len)
+    }
+
+    unsafe fn alloc_contiguous_with(
+        &mut self,
+        existing: NonNull<[u8]>,
+        min_extra: usize,
+    ) -> Option<NonNull<[u8]>> {
+        log::trace!(
+            "FlexSource::alloc_contiguous_with{:?}",
+            (existing, min_extra)
+        );
+        let range = self.inner.alloc_contiguous_with(existing, min_extra)?;
+        log::trace!(" FlexSource::alloc_contiguous_with(...) = {:?}", range);
+        assert_eq!(
+            range.as_ptr() as *mut u8,
+            nonnull_slice_end(existing),
+            "the source returned a memory block that doesn't follow `existing`"
+        );
+        assert!(
+            nonnull_slice_len(range) >= min_extra,
+            "the source returned a memory block smaller than requested"
+        );
+        self.sa.append_free_block(range.as_ptr());
+        Some(range)
+    }
+
+    #[inline]
+    fn min_align(&self) -> usize {
+        self.inner.min_align()
+    }
+
+    #[inline]
+    unsafe fn dealloc(&mut self, ptr: NonNull<[u8]>) {
+        // TODO: check that `ptr` represents an exact allocation, not just
+        //       a part of it
+        self.inner.dealloc(ptr);
+        log::trace!("FlexSource::dealloc({:?})", ptr);
+        self.sa.remove_pool(ptr.as_ptr());
+    }
+
+    #[inline]
+    fn is_contiguous_growable(&self) -> bool {
+        self.inner.is_contiguous_growable()
+    }
+
+    #[inline]
+    fn supports_dealloc(&self) -> bool {
+        self.inner.supports_dealloc()
+    }
+
+    #[inline]
+    fn supports_realloc_inplace_grow(&self) -> bool {
+        self.inner.supports_realloc_inplace_grow()
+    }
+}
+
+#[inline]
+fn nonnull_slice_len<T>(ptr: NonNull<[T]>) -> usize {
+    // Safety: We are just reading the slice length embedded in the fat
+    //         pointer and not dereferencing the pointer
+    unsafe { (*(ptr.as_ptr() as *const [MaybeUninit<T>])).len() }
+}
+
+#[inline]
+fn nonnull_slice_end<T>(ptr: NonNull<[T]>) -> *mut T {
+    (ptr.as_ptr() as *mut T).wrapping_add(nonnull_slice_len(ptr))
+}
@@ -0,0 +1,56 @@
+use quickcheck_macros::quickcheck;
+use rlsf::{FlexSource, GlobalAllocAsFlexSource};
+use std::{alloc::System, ptr::NonNull};
+
+type SysSource = GlobalAllocAsFlexSource<System, 1024>;
+
+#[quickcheck]
+fn tlsf_u8_u8_8_8(data: Vec<u8>) {
+    let _ = env_logger::builder().is_test(true).try_init();
+    rlsf_fuzz::fuzz_tlsf::<u8, u8, 8, 8>(&data);
+}
+
+#[quickcheck]
+fn tlsf_u16_u16_12_16(data: Vec<u8>) {
+    let _ = env_logger::builder().is_test(true).try_init();
+    rlsf_fuzz::fuzz_tlsf::<u16, u16, 12, 16>(&data);
+}
+
+#[quickcheck]
+fn flex_tlsf_sys_u8_u8_8_8(data: Vec<u8>) {
+    let _ = env_logger::builder().is_test(true).try_init();
+    rlsf_fuzz::fuzz_flex_tlsf::<_, u8, u8, 8, 8>(SysSource::default(), &data);
+}
+
+#[quickcheck]
+fn flex_tlsf_sys_u16_u16_12_16(data: Vec<u8>) {
+    let _ = env_logger::builder().is_test(true).try_init();
+    rlsf_fuzz::fuzz_flex_tlsf::<_, u16, u16, 12, 16>(SysSource::default(), &data);
+}
+
+/// A broken source that returns the same memory block every time
+struct RepeatingSource(Vec<usize>);
+
+unsafe impl FlexSource for RepeatingSource {
+    unsafe fn alloc(&mut self, min_size: usize) -> Option<NonNull<[u8]>> {
+        if min_size > self.0.len() * std::mem::size_of::<usize>() {
+            return None;
+        }
+        NonNull::new(std::ptr::slice_from_raw_parts_mut(
+            self.0.as_mut_ptr() as *mut u8,
+            self.0.len() * std::mem::size_of::<usize>(),
+        ))
+    }
+}
+
+#[test]
+#[should_panic]
+fn flex_tlsf_broken_source() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // Two allocations that don't fit in one memory pool
+    rlsf_fuzz::fuzz_flex_tlsf::<_, u16, u16, 12, 16>(
+        RepeatingSource(vec![0; 128]),
+        &[0x00, 0x04, 0, 0x00, 0x00, 0x80, 3, 0, 0x00, 0x00, 0x80, 3],
+    );
+}
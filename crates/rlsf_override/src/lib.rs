@@ -1,4 +1,12 @@
 //! Overrides C memory allocation functions with [`::rlsf`].
+//!
+//! The built shared library can be loaded into an existing program by
+//! `LD_PRELOAD` to test `rlsf` against real-world workloads:
+//!
+//! ```text
+//! cargo build --release -p rlsf_override
+//! LD_PRELOAD=target/release/librlsf_override.so python3 -c 'print("hello")'
+//! ```
 use rlsf::CAlloc;
 use std::{
     alloc::Layout,
@@ -93,11 +101,15 @@ pub unsafe extern "C" fn posix_memalign(
     alignment: usize,
     size: usize,
 ) -> c_int {
+    if !alignment.is_power_of_two() || alignment % std::mem::size_of::<*mut c_void>() != 0 {
+        return libc::EINVAL;
+    }
+
     let ptr = aligned_alloc(alignment, size);
-    *out_ptr = ptr as *mut c_void;
     if ptr.is_null() {
         libc::ENOMEM
     } else {
+        *out_ptr = ptr;
         0
     }
 }
@@ -132,6 +144,15 @@ pub unsafe extern "C" fn realloc(ptr: *mut c_void, size: usize) -> *mut c_void {
     }
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn reallocarray(ptr: *mut c_void, number: usize, size: usize) -> *mut c_void {
+    if let Some(size) = number.checked_mul(size) {
+        realloc(ptr, size)
+    } else {
+        null_mut()
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn free(ptr: *mut c_void) {
     if let Some(ptr) = NonNull::new(ptr) {
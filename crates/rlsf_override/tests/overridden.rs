@@ -23,3 +23,40 @@ fn bz2() {
     decompressor.read_to_string(&mut contents).unwrap();
     assert_eq!(contents, "Hello, World!");
 }
+
+#[test]
+fn posix_memalign() {
+    use std::{os::raw::c_void, ptr::null_mut};
+    unsafe {
+        let mut ptr: *mut c_void = null_mut();
+        assert_eq!(rlsf_override::posix_memalign(&mut ptr, 256, 100), 0);
+        assert_eq!(ptr as usize % 256, 0);
+        rlsf_override::free(ptr);
+
+        // The alignment must be a power of two multiple of `sizeof(void *)`
+        let mut ptr2: *mut c_void = null_mut();
+        assert_eq!(
+            rlsf_override::posix_memalign(&mut ptr2, 24, 100),
+            libc::EINVAL
+        );
+        assert_eq!(
+            rlsf_override::posix_memalign(&mut ptr2, 2, 100),
+            libc::EINVAL
+        );
+        assert!(ptr2.is_null());
+    }
+}
+
+#[test]
+fn reallocarray() {
+    unsafe {
+        let ptr = rlsf_override::reallocarray(std::ptr::null_mut(), 10, 8) as *mut u64;
+        assert!(!ptr.is_null());
+        ptr.write_bytes(0, 10);
+        let ptr = rlsf_override::reallocarray(ptr as _, 20, 8) as *mut u64;
+        assert!(!ptr.is_null());
+        assert_eq!(*ptr.add(9), 0);
+        assert!(rlsf_override::reallocarray(ptr as _, usize::MAX, 8).is_null());
+        rlsf_override::free(ptr as _);
+    }
+}